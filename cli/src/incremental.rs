@@ -0,0 +1,1025 @@
+//! Incremental parsing and checking of a single document, for editors that
+//! ask about the same file on every keystroke.
+//!
+//! The document is split into chunks of top-level statements, each ending
+//! with `;` so an edit can't change how the text around it is parsed. An
+//! edit reparses only the chunks it touches. Statements are then inferred one
+//! at a time, a statement is reused as long as it isn't reparsed and the
+//! declarations it refers to keep their types.
+
+use crate::load::{load_modules, Loaded};
+use hir::{
+    pattern::Var,
+    resolve::{resolve_partial, Resolution},
+    statement::{AliasDeclare, Module, NewtypeDeclare, Statement},
+    visit::Visit,
+    Atom, FileId, PrettyPrintType, Span,
+};
+use parser::{
+    cst::{Node, NodeKind, Token, TokenKind},
+    lower, syntax_tree_in, EasyParser,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    mem::take,
+    ops::Range,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use type_system::{Checker, Declared, Scheme, Type, TypeError, TypeWarning};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+/// Problem found in the document, the range is in bytes
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub range: Range<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+/// Statement along with what is needed to know when it has to be inferred
+/// again
+struct Item {
+    statement: Statement<()>,
+    /// Range of the statement within the source of its chunk
+    range: Range<usize>,
+    /// Concrete syntax of the statement, for the tokens within it
+    syntax: Node,
+    /// Names of variables used, including the ones bound within it
+    reads: Vec<Atom>,
+    declares: Vec<Atom>,
+}
+struct Chunk {
+    id: u64,
+    /// Source shared by the chunks parsed together, spans of the statements
+    /// are within it
+    source: Rc<str>,
+    file: FileId,
+    range: Range<usize>,
+    items: Vec<Item>,
+    /// File modules loaded by the statements
+    modules: Option<Loaded>,
+    /// Parse error along with its offset from the start of the chunk
+    error: Option<(usize, String)>,
+}
+impl Chunk {
+    fn text(&self) -> &str {
+        &self.source[self.range.clone()]
+    }
+    /// Range of the span within the text of the chunk
+    fn translate(&self, span: Span) -> Option<Range<usize>> {
+        let range = span.range();
+        (span.file() == self.file && self.range.start <= range.start && range.end <= self.range.end)
+            .then(|| range.start - self.range.start..range.end - self.range.start)
+    }
+}
+/// Source that can't be parsed, the offset of the error is relative to it
+struct Unparsed {
+    source: String,
+    offset: usize,
+    message: String,
+}
+type Key = (u64, usize);
+
+enum Outcome {
+    Inferred {
+        typed: Box<Statement<Type>>,
+        declared: Declared,
+        warnings: Vec<TypeWarning>,
+    },
+    Failed(TypeError),
+}
+enum Checked {
+    Reused(Entry),
+    Inferred(Outcome),
+}
+impl Checked {
+    fn outcome(&self) -> &Outcome {
+        match self {
+            Self::Reused(entry) => &entry.outcome,
+            Self::Inferred(outcome) => outcome,
+        }
+    }
+}
+struct Entry {
+    /// Statements declaring the names read, as they were when it was
+    /// inferred
+    writers: Vec<Option<Key>>,
+    outcome: Outcome,
+}
+pub struct Document {
+    path: PathBuf,
+    text: String,
+    chunks: Vec<Chunk>,
+    next_id: u64,
+    /// Whether the queries are up to date with the text
+    checked: bool,
+    diagnostics: Vec<Diagnostic>,
+    resolution: Resolution,
+    aliases: Vec<AliasDeclare>,
+    newtypes: Vec<NewtypeDeclare>,
+    /// Trait and impl declarations, in the order they're declared in
+    traits: Vec<Statement<()>>,
+    cache: HashMap<Key, Entry>,
+    /// Declarations of the statements removed since the last check, the
+    /// statements reading them are kept if they declare the same again
+    stale: HashMap<Key, Declared>,
+    /// Number of statements inferred on the last check
+    inferred: usize,
+}
+impl Document {
+    /// The document at `path`, file modules are loaded relative to it
+    pub fn new(path: PathBuf, text: String) -> Self {
+        let mut document = Self {
+            path,
+            text: String::new(),
+            chunks: Vec::new(),
+            next_id: 0,
+            checked: false,
+            diagnostics: Vec::new(),
+            resolution: Resolution::default(),
+            aliases: Vec::new(),
+            newtypes: Vec::new(),
+            traits: Vec::new(),
+            cache: HashMap::new(),
+            stale: HashMap::new(),
+            inferred: 0,
+        };
+        document.edit(0..0, &text);
+        document
+    }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    /// Replaces the bytes within `range` with `text`
+    pub fn edit(&mut self, range: Range<usize>, text: &str) {
+        self.text.replace_range(range.clone(), text);
+        self.checked = false;
+        let starts = self.starts();
+        let total = starts
+            .last()
+            .map_or(0, |start| start + self.chunks.last().unwrap().range.len());
+        // the chunk before is left as it is, it ends with `;` which can't be
+        // joined with what comes after
+        let chunk_at = |offset: usize| starts.iter().rposition(|start| *start <= offset);
+        let mut first = chunk_at(range.start).unwrap_or(0);
+        let mut last = match chunk_at(range.end) {
+            Some(last) if range.end < total => last,
+            _ => self.chunks.len().saturating_sub(1),
+        };
+        // text made invalid by an earlier edit may be fixed by this one
+        if let Some(error) = self.chunks.iter().position(|chunk| chunk.error.is_some()) {
+            first = first.min(error);
+        }
+        if let Some(error) = self.chunks.iter().rposition(|chunk| chunk.error.is_some()) {
+            last = last.max(error);
+        }
+        let reparsed = |chunks: &[Chunk], end: usize| {
+            let mut source = String::new();
+            for chunk in &chunks[first..end] {
+                source.push_str(chunk.text());
+            }
+            let start = starts.get(first).copied().unwrap_or(0);
+            let edit_start = range.start - start;
+            let edit_end = (range.end - start).min(source.len());
+            source.replace_range(edit_start..edit_end, text);
+            source
+        };
+        let end = (last + 1).min(self.chunks.len());
+        let source = reparsed(&self.chunks, end);
+        let at_end = end == self.chunks.len();
+        let chunks = match self.parse(source, first == 0, at_end) {
+            Ok(chunks) => (chunks, end),
+            Err(err) if at_end => (vec![self.error_chunk(err)], end),
+            Err(err) => {
+                let source = reparsed(&self.chunks, self.chunks.len());
+                match self.parse(source, first == 0, true) {
+                    Ok(chunks) => (chunks, self.chunks.len()),
+                    Err(_) => (vec![self.error_chunk(err)], end),
+                }
+            }
+        };
+        let (chunks, end) = chunks;
+        let removed: Vec<_> = self.chunks.splice(first..end, chunks).collect();
+        for chunk in removed {
+            for index in 0..chunk.items.len() {
+                let entry = self.cache.remove(&(chunk.id, index));
+                if let Some(Entry {
+                    outcome: Outcome::Inferred { declared, .. },
+                    ..
+                }) = entry
+                {
+                    self.stale.insert((chunk.id, index), declared);
+                }
+            }
+        }
+    }
+    fn starts(&self) -> Vec<usize> {
+        let mut start = 0;
+        self.chunks
+            .iter()
+            .map(|chunk| {
+                let this = start;
+                start += chunk.range.len();
+                this
+            })
+            .collect()
+    }
+    fn error_chunk(&mut self, unparsed: Unparsed) -> Chunk {
+        self.next_id += 1;
+        Chunk {
+            id: self.next_id,
+            range: 0..unparsed.source.len(),
+            source: unparsed.source.into(),
+            file: FileId::fresh(),
+            items: Vec::new(),
+            modules: None,
+            error: Some((unparsed.offset, unparsed.message)),
+        }
+    }
+    /// Parses the source into chunks. A source that isn't at the end of the
+    /// document must end with `;` and not within a comment, or else the text
+    /// after it may continue its last statement
+    fn parse(
+        &mut self,
+        source: String,
+        at_start: bool,
+        at_end: bool,
+    ) -> Result<Vec<Chunk>, Unparsed> {
+        let source: Rc<str> = source.into();
+        let file = FileId::fresh();
+        let source_file = match syntax_tree_in(file).easy_parse(&source[..]) {
+            Ok((source_file, _)) => source_file,
+            Err(err) => {
+                let err = err.map_position(|position| position.translate_position(&source[..]));
+                // the first line is the position which is already given
+                let message = err.to_string();
+                let message: Vec<_> = message.lines().skip(1).map(str::trim).collect();
+                return Err(Unparsed {
+                    source: source.to_string(),
+                    offset: err.position,
+                    message: message.join("\n"),
+                });
+            }
+        };
+        let nodes: Vec<_> = source_file.nodes().collect();
+        let clean_end = nodes
+            .last()
+            .is_none_or(|node| node.last_token().is_some_and(|token| token.text == ";"))
+            && source_file
+                .descendant_tokens()
+                .last()
+                .is_none_or(|token| token.kind != TokenKind::Comment);
+        let has_shebang = source_file
+            .descendant_tokens()
+            .next()
+            .is_some_and(|token| token.kind == TokenKind::Shebang);
+        if !at_end && !clean_end || !at_start && has_shebang {
+            return Err(Unparsed {
+                source: source.to_string(),
+                offset: source.len(),
+                message: "expected `;` before the following statements".to_string(),
+            });
+        }
+        let statements = lower(&source_file);
+        let mut chunks: Vec<Chunk> = Vec::new();
+        let mut start = 0;
+        let mut items = Vec::new();
+        for (i, (node, statement)) in nodes.iter().zip(statements).enumerate() {
+            items.push(item(statement, (*node).clone()));
+            let ends_chunk = node.last_token().is_some_and(|token| token.text == ";");
+            if ends_chunk || i + 1 == nodes.len() {
+                let end = match nodes.get(i + 1) {
+                    Some(next) => next.span().start(),
+                    None => source.len(),
+                };
+                chunks.push(self.chunk(source.clone(), file, start..end, take(&mut items)));
+                start = end;
+            }
+        }
+        if chunks.is_empty() {
+            chunks.push(self.chunk(source.clone(), file, 0..source.len(), Vec::new()));
+        }
+        Ok(chunks)
+    }
+    fn chunk(
+        &mut self,
+        source: Rc<str>,
+        file: FileId,
+        range: Range<usize>,
+        items: Vec<Item>,
+    ) -> Chunk {
+        self.next_id += 1;
+        let mut chunk = Chunk {
+            id: self.next_id,
+            source,
+            file,
+            range,
+            items,
+            modules: None,
+            error: None,
+        };
+        let has_file_module = chunk.items.iter().any(|item| {
+            matches!(
+                &item.statement,
+                Statement::ModuleDeclare(module) if matches!(module.module, Module::File(_)),
+            )
+        });
+        if has_file_module {
+            let statements = chunk
+                .items
+                .iter()
+                .map(|item| item.statement.clone())
+                .collect();
+            match load_modules(&self.path, statements) {
+                Ok(mut loaded) => {
+                    for (item, statement) in
+                        chunk.items.iter_mut().zip(take(&mut loaded.statements))
+                    {
+                        *item = self::item(statement, item.syntax.clone());
+                    }
+                    chunk.modules = Some(loaded);
+                }
+                Err(err) => chunk.error = Some((0, err.to_string())),
+            }
+        }
+        chunk
+    }
+    /// Byte range of the span within the document
+    pub fn locate(&self, span: Span) -> Option<Range<usize>> {
+        self.chunks
+            .iter()
+            .zip(self.starts())
+            .find_map(|(chunk, start)| {
+                let range = chunk.translate(span)?;
+                Some(range.start + start..range.end + start)
+            })
+    }
+    /// The byte at `offset` as it is within spans, the file along with the
+    /// offset within it
+    pub fn position(&self, offset: usize) -> Option<(FileId, usize)> {
+        self.chunks
+            .iter()
+            .zip(self.starts())
+            .find(|(chunk, start)| offset < start + chunk.range.len())
+            .map(|(chunk, start)| (chunk.file, chunk.range.start + offset - start))
+    }
+    /// Byte range of the token at `offset`, or of the token ending there
+    /// when there is none, as the cursor is often right after a name.
+    /// Whitespaces and comments have no token.
+    pub fn token_at(&self, offset: usize) -> Option<Range<usize>> {
+        let mut before = None;
+        for (range, _) in self.tokens() {
+            if range.start > offset {
+                break;
+            }
+            if offset < range.end {
+                return Some(range);
+            }
+            if range.end == offset {
+                before = Some(range);
+            }
+        }
+        before
+    }
+    /// Byte ranges of the innermost declaration around the span and of the
+    /// first `name` within it, or of the whole declaration when the name
+    /// isn't found
+    pub fn declaration(&self, span: Span, name: &str) -> Option<(Range<usize>, Range<usize>)> {
+        self.chunks
+            .iter()
+            .zip(self.starts())
+            .find_map(|(chunk, start)| {
+                chunk.translate(span)?;
+                let node = chunk
+                    .items
+                    .iter()
+                    .find_map(|item| declaration(&item.syntax, span))?;
+                let locate = |span| {
+                    let range = chunk.translate(span)?;
+                    Some(range.start + start..range.end + start)
+                };
+                let whole = locate(node.span())?;
+                let name = node
+                    .descendant_tokens()
+                    .find(|token| token.kind == TokenKind::Ident && token.text == name)
+                    .and_then(|token| locate(token.span))
+                    .unwrap_or(whole.clone());
+                Some((whole, name))
+            })
+    }
+    /// Tokens of the statements in order along with their byte range within
+    /// the document
+    fn tokens(&self) -> impl Iterator<Item = (Range<usize>, &Token)> {
+        self.chunks
+            .iter()
+            .zip(self.starts())
+            .flat_map(|(chunk, start)| {
+                chunk.items.iter().flat_map(move |item| {
+                    item.syntax
+                        .descendant_tokens()
+                        .filter(|token| !token.kind.is_trivia())
+                        .filter_map(move |token| {
+                            let range = chunk.translate(token.span)?;
+                            Some((range.start + start..range.end + start, token))
+                        })
+                })
+            })
+    }
+    /// The file module a span is from along with its source
+    pub fn source_of(&self, span: Span) -> Option<(&Path, &str)> {
+        self.chunks
+            .iter()
+            .filter_map(|chunk| chunk.modules.as_ref())
+            .find_map(|modules| modules.source_of(span))
+    }
+    /// Statements as they're parsed, statements that can't be parsed are
+    /// left out
+    pub fn statements(&self) -> impl Iterator<Item = &Statement<()>> {
+        self.items().map(|(_, item)| &item.statement)
+    }
+    fn items(&self) -> impl Iterator<Item = (&Chunk, &Item)> {
+        self.chunks
+            .iter()
+            .flat_map(|chunk| chunk.items.iter().map(move |item| (chunk, item)))
+    }
+    pub fn diagnostics(&mut self) -> &[Diagnostic] {
+        self.check();
+        &self.diagnostics
+    }
+    pub fn resolution(&mut self) -> &Resolution {
+        self.check();
+        &self.resolution
+    }
+    /// Scheme of the last top-level declaration of `name`
+    pub fn type_of(&mut self, name: &str) -> Option<Scheme> {
+        self.check();
+        self.keys()
+            .rev()
+            .filter_map(|key| match &self.cache.get(&key)?.outcome {
+                Outcome::Inferred { declared, .. } => declared.get(name),
+                Outcome::Failed(_) => None,
+            })
+            .next()
+            .cloned()
+    }
+    /// Statements that are successfully inferred, they may be from an
+    /// earlier version when there are parse errors
+    pub fn typed_statements(&mut self) -> impl Iterator<Item = &Statement<Type>> {
+        self.check();
+        self.cache
+            .values()
+            .filter_map(|entry| match &entry.outcome {
+                Outcome::Inferred { typed, .. } => Some(&**typed),
+                Outcome::Failed(_) => None,
+            })
+    }
+    fn keys(&self) -> impl DoubleEndedIterator<Item = Key> + '_ {
+        self.chunks
+            .iter()
+            .flat_map(|chunk| (0..chunk.items.len()).map(|index| (chunk.id, index)))
+    }
+    fn check(&mut self) {
+        if self.checked {
+            return;
+        }
+        self.checked = true;
+        self.diagnostics.clear();
+        for (chunk, start) in self.chunks.iter().zip(self.starts()) {
+            if let Some((offset, message)) = &chunk.error {
+                let offset = start + offset;
+                self.diagnostics.push(Diagnostic {
+                    range: offset..offset,
+                    severity: Severity::Error,
+                    message: message.clone(),
+                });
+            }
+        }
+        // types from before are kept until it can be parsed again
+        if !self.diagnostics.is_empty() {
+            return;
+        }
+        let keys: Vec<_> = self.keys().collect();
+        let statements: Vec<_> = self.statements().cloned().collect();
+        let items: Vec<_> = self.items().collect();
+        let ranges: Vec<_> = self
+            .chunks
+            .iter()
+            .zip(self.starts())
+            .flat_map(|(chunk, start)| {
+                let chunk_start = chunk.range.start;
+                chunk.items.iter().map(move |item| {
+                    item.range.start - chunk_start + start..item.range.end - chunk_start + start
+                })
+            })
+            .collect();
+
+        let (resolution, unbound) = resolve_partial(&statements);
+        let mut diagnostics = Vec::new();
+        let mut unbound_items = HashSet::new();
+        for unbound in unbound.iter() {
+            let index = items.iter().position(|(chunk, item)| {
+                chunk.translate(unbound.span).is_some_and(|range| {
+                    let chunk_start = chunk.range.start;
+                    item.range.start - chunk_start <= range.start
+                        && range.end <= item.range.end - chunk_start
+                }) || item_has_module_span(chunk, item, unbound.span)
+            });
+            if let Some(index) = index {
+                unbound_items.insert(index);
+            }
+            let diagnostic = match self.locate(unbound.span) {
+                Some(range) => Diagnostic {
+                    range,
+                    severity: Severity::Error,
+                    message: unbound.to_string(),
+                },
+                None => {
+                    let location = self
+                        .chunks
+                        .iter()
+                        .filter_map(|chunk| chunk.modules.as_ref())
+                        .find_map(|modules| modules.locate(unbound.span))
+                        .unwrap_or_default();
+                    Diagnostic {
+                        range: index.map_or(0..0, |index| ranges[index].clone()),
+                        severity: Severity::Error,
+                        message: format!("{location}: {unbound}"),
+                    }
+                }
+            };
+            diagnostics.push(diagnostic);
+        }
+
+        let aliases: Vec<_> = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::AliasDeclare(alias) => Some(alias.clone()),
+                _ => None,
+            })
+            .collect();
+        let newtypes: Vec<_> = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::NewtypeDeclare(newtype) => Some(newtype.clone()),
+                _ => None,
+            })
+            .collect();
+        let traits: Vec<_> = statements
+            .iter()
+            .filter(|statement| {
+                matches!(
+                    statement,
+                    Statement::TraitDeclare(_) | Statement::ImplDeclare(_)
+                )
+            })
+            .cloned()
+            .collect();
+        // statement declaring each name read, as of the statement reading it
+        let mut declared_by: HashMap<&Atom, usize> = HashMap::new();
+        let mut writers = Vec::with_capacity(items.len());
+        for (index, (_, item)) in items.iter().enumerate() {
+            writers.push(
+                item.reads
+                    .iter()
+                    .map(|name| declared_by.get(name).copied())
+                    .collect::<Vec<_>>(),
+            );
+            for name in &item.declares {
+                declared_by.insert(name, index);
+            }
+        }
+        let count = items.len();
+        drop(items);
+        // newtypes are told apart by the order they're declared in, traits
+        // and impls are declared for every statement and any of them may
+        // find a method or an instance through them
+        if aliases != self.aliases || newtypes != self.newtypes || traits != self.traits {
+            self.cache.clear();
+            self.stale.clear();
+        }
+        // statements that must be inferred even when what they read is the
+        // same, as they share type variables with a statement inferred again
+        let mut dirty = HashSet::new();
+        let mut results: Vec<Option<Checked>>;
+        let mut checker;
+        'run: loop {
+            checker = match Checker::new(&statements) {
+                Ok(checker) => checker,
+                Err(err) => {
+                    diagnostics.push(Diagnostic {
+                        range: 0..0,
+                        severity: Severity::Error,
+                        message: err.to_string(),
+                    });
+                    self.diagnostics = diagnostics;
+                    self.resolution = resolution;
+                    return;
+                }
+            };
+            results = Vec::with_capacity(count);
+            // whether the statement is inferred again and its declarations
+            // may have changed
+            let mut changed = Vec::with_capacity(count);
+            for index in 0..count {
+                let writers = &writers[index];
+                let poisoned = unbound_items.contains(&index)
+                    || writers.iter().flatten().any(|writer| {
+                        !matches!(
+                            results[*writer].as_ref().map(Checked::outcome),
+                            Some(Outcome::Inferred { .. }),
+                        )
+                    });
+                if poisoned {
+                    results.push(None);
+                    changed.push(true);
+                    continue;
+                }
+                let cached = self.cache.get(&keys[index]);
+                let unchanged = |entry: &Entry| {
+                    entry.writers.len() == writers.len()
+                        && entry
+                            .writers
+                            .iter()
+                            .zip(writers)
+                            .all(|(old, new)| match (old, new) {
+                                (None, None) => true,
+                                (Some(old), Some(new)) if *old == keys[*new] => !changed[*new],
+                                // the declaring statement is parsed again
+                                (Some(old), Some(new)) => {
+                                    match (
+                                        self.stale.get(old),
+                                        results[*new].as_ref().map(Checked::outcome),
+                                    ) {
+                                        (Some(old), Some(Outcome::Inferred { declared, .. })) => {
+                                            declared.is_closed() && declared.is_same(old)
+                                        }
+                                        _ => false,
+                                    }
+                                }
+                                _ => false,
+                            })
+                };
+                if !dirty.contains(&index) && cached.is_some_and(unchanged) {
+                    let entry = self.cache.remove(&keys[index]).unwrap();
+                    if let Outcome::Inferred { declared, .. } = &entry.outcome {
+                        // open declarations are only used by statements
+                        // inferred along with it
+                        if declared.is_closed() {
+                            checker.declare(declared);
+                        }
+                    }
+                    results.push(Some(Checked::Reused(entry)));
+                    changed.push(false);
+                    continue;
+                }
+                let open: Vec<_> = writers
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .filter(|writer| match &results[*writer] {
+                        Some(Checked::Reused(Entry {
+                            outcome: Outcome::Inferred { declared, .. },
+                            ..
+                        })) => !declared.is_closed(),
+                        _ => false,
+                    })
+                    .collect();
+                if !open.is_empty() {
+                    dirty.extend(open);
+                    for (index, result) in take(&mut results).into_iter().enumerate() {
+                        if let Some(Checked::Reused(entry)) = result {
+                            self.cache.insert(keys[index], entry);
+                        }
+                    }
+                    continue 'run;
+                }
+                let outcome = match checker.infer(statements[index].clone()) {
+                    Ok((typed, declared, warnings)) => Outcome::Inferred {
+                        typed: Box::new(typed),
+                        declared,
+                        warnings,
+                    },
+                    Err(err) => Outcome::Failed(err),
+                };
+                let same = match (&outcome, cached.map(|entry| &entry.outcome)) {
+                    (
+                        Outcome::Inferred { declared, .. },
+                        Some(Outcome::Inferred { declared: old, .. }),
+                    ) => declared.is_closed() && declared.is_same(old),
+                    _ => false,
+                };
+                results.push(Some(Checked::Inferred(outcome)));
+                changed.push(!same);
+            }
+            break;
+        }
+        self.cache.clear();
+        self.stale.clear();
+        self.inferred = 0;
+        for (index, result) in results.into_iter().enumerate() {
+            let outcome = match result {
+                None => continue,
+                Some(Checked::Reused(entry)) => entry.outcome,
+                Some(Checked::Inferred(mut outcome)) => {
+                    self.inferred += 1;
+                    if let Outcome::Inferred { typed, .. } = &mut outcome {
+                        if let Err(err) = checker.finish(typed) {
+                            outcome = Outcome::Failed(err);
+                        }
+                    }
+                    outcome
+                }
+            };
+            match &outcome {
+                Outcome::Inferred { warnings, .. } => {
+                    diagnostics.extend(warnings.iter().map(|warning| {
+                        Diagnostic {
+                            range: self
+                                .locate(warning.span())
+                                .unwrap_or_else(|| ranges[index].clone()),
+                            severity: Severity::Warning,
+                            message: warning.to_string(),
+                        }
+                    }))
+                }
+                Outcome::Failed(err) => diagnostics.push(Diagnostic {
+                    range: err
+                        .span()
+                        .and_then(|span| self.locate(span))
+                        .unwrap_or_else(|| ranges[index].clone()),
+                    severity: Severity::Error,
+                    message: err.to_string(),
+                }),
+            }
+            let writers = writers[index]
+                .iter()
+                .map(|writer| writer.map(|writer| keys[writer]))
+                .collect();
+            self.cache.insert(keys[index], Entry { writers, outcome });
+        }
+        diagnostics.sort_by_key(|diagnostic| diagnostic.range.start);
+        self.diagnostics = diagnostics;
+        self.resolution = resolution;
+        self.aliases = aliases;
+        self.newtypes = newtypes;
+        self.traits = traits;
+    }
+}
+fn item_has_module_span(chunk: &Chunk, item: &Item, span: Span) -> bool {
+    matches!(item.statement, Statement::ModuleDeclare(_))
+        && chunk
+            .modules
+            .as_ref()
+            .is_some_and(|modules| modules.source_of(span).is_some())
+}
+/// Innermost declaration node around the span
+fn declaration(node: &Node, span: Span) -> Option<&Node> {
+    let within = node.span();
+    if within.file() != span.file() || span.start() < within.start() || within.end() < span.end() {
+        return None;
+    }
+    let is_declaration = matches!(
+        node.kind,
+        NodeKind::Declare
+            | NodeKind::FunDeclare
+            | NodeKind::AliasDeclare
+            | NodeKind::NewtypeDeclare
+            | NodeKind::TraitDeclare
+            | NodeKind::MethodSignature
+            | NodeKind::ImplDeclare
+            | NodeKind::ModuleDeclare
+    );
+    node.nodes()
+        .find_map(|node| declaration(node, span))
+        .or(is_declaration.then_some(node))
+}
+fn item(statement: Statement<()>, syntax: Node) -> Item {
+    let mut reads = Reads(Vec::new());
+    reads.visit_statement(&statement);
+    let declares = match &statement {
+        Statement::Declare(declare) => {
+            let mut vars = PatternVars(Vec::new());
+            vars.visit_pattern(&declare.pattern);
+            vars.0.into_iter().map(|var| var.ident).collect()
+        }
+        Statement::FunDeclare(fun) => vec![fun.ident.clone()],
+        Statement::NewtypeDeclare(newtype) => vec![newtype.ident.clone()],
+        Statement::ModuleDeclare(module) => vec![module.ident.clone()],
+        Statement::TraitDeclare(trait_declare) => trait_declare
+            .methods
+            .iter()
+            .map(|method| method.ident.clone())
+            .collect(),
+        Statement::AliasDeclare(_) | Statement::ImplDeclare(_) | Statement::Expr(_) => Vec::new(),
+    };
+    Item {
+        statement,
+        range: syntax.span().range(),
+        syntax,
+        reads: reads.0,
+        declares,
+    }
+}
+struct Reads(Vec<Atom>);
+
+impl Visit<()> for Reads {
+    fn visit_var_expr(&mut self, name: &Atom, _: Span) {
+        if !self.0.contains(name) {
+            self.0.push(name.clone());
+        }
+    }
+}
+/// Variables bound by a pattern
+pub struct PatternVars(pub Vec<Var>);
+
+impl<T: PrettyPrintType> Visit<T> for PatternVars {
+    fn visit_var(&mut self, var: &Var) {
+        self.0.push(var.clone());
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::{Document, Severity};
+    use hir::statement::Statement;
+    use std::path::PathBuf;
+
+    fn document(src: &str) -> Document {
+        Document::new(PathBuf::from("test.butter"), src.to_string())
+    }
+    /// Edits the text replacing the first occurrence of `from`
+    fn replace(document: &mut Document, from: &str, to: &str) {
+        let start = document.text().find(from).unwrap();
+        document.edit(start..start + from.len(), to);
+    }
+    /// Checks the document against a fresh one with the same text
+    fn assert_fresh(document: &mut Document, names: &[&str]) {
+        let mut fresh = self::document(document.text());
+        assert_eq!(document.diagnostics(), fresh.diagnostics());
+        // there's nothing to compare with when it can't be parsed, the
+        // types from before are kept
+        if fresh.statements().next().is_none() {
+            return;
+        }
+        for name in names {
+            match (document.type_of(name), fresh.type_of(name)) {
+                (Some(a), Some(b)) => assert!(a.is_equivalent(&b), "{name}: {a} and {b}"),
+                (a, b) => assert_eq!(a, b, "{name}"),
+            }
+        }
+    }
+    #[test]
+    fn matches_fresh() {
+        // `b` is left out, its type has a variable only named differently
+        let names = ["id", "a", "c", "d"];
+        let mut document = document("id(x) => x;\na = id(1);\nb = [];\nc = a;\n");
+        assert!(document.diagnostics().is_empty());
+        assert_eq!(document.type_of("c").unwrap().to_string(), "I64");
+        let edits = [
+            ("id(1)", "id(true)"),
+            ("c = a", "c = a + 1"),
+            // `b` is only known once a later statement uses it
+            ("c = a + 1;", "c = a;\nd = b.len"),
+            ("d = b.len", "d = z"),
+            ("= z", "= b"),
+            ("id(x) => x", "id(x) => x +"),
+            ("x +", "x + 0"),
+            // joins the statement with the ones after it until it's fixed
+            ("id(true);", "id(true)"),
+            ("id(true)", "id(true);"),
+        ];
+        for (from, to) in edits {
+            replace(&mut document, from, to);
+            assert_fresh(&mut document, &names);
+        }
+    }
+    #[test]
+    fn only_dependents() {
+        let mut document = document("a = 1;\nb = 2;\nf(x) => x + a;\nc = f(b);\n");
+        assert!(document.diagnostics().is_empty());
+        assert_eq!(document.inferred, 4);
+
+        replace(&mut document, "a = 1", "a = 10");
+        assert!(document.diagnostics().is_empty());
+        assert_eq!(document.inferred, 1);
+
+        // same type so `c` is kept
+        replace(&mut document, "x + a", "x * a");
+        assert!(document.diagnostics().is_empty());
+        assert_eq!(document.inferred, 1);
+
+        replace(&mut document, "b = 2", "b = true");
+        assert_eq!(document.diagnostics().len(), 1);
+        assert_eq!(document.inferred, 2);
+        assert_fresh(&mut document, &["a", "b", "f", "c"]);
+    }
+    #[test]
+    fn traits() {
+        let impl_declare = "impl T(Num) { m(x) => 1; };\n";
+        let src = format!("trait T(a) {{ m(x: a) -> Num; }};\n{impl_declare}y = m(1);\n");
+        let mut document = document(&src);
+        assert!(document.diagnostics().is_empty());
+
+        replace(&mut document, impl_declare, "");
+        assert_eq!(document.diagnostics().len(), 1);
+        assert_fresh(&mut document, &["y"]);
+
+        replace(
+            &mut document,
+            "y = m(1)",
+            &format!("{impl_declare}y = m(1)"),
+        );
+        assert!(document.diagnostics().is_empty());
+        assert_fresh(&mut document, &["y"]);
+
+        replace(&mut document, "-> Num", "-> Bool");
+        assert!(!document.diagnostics().is_empty());
+        assert_fresh(&mut document, &["y"]);
+    }
+    #[test]
+    fn error_range() {
+        let mut document = document("a = 1;\nf(x) => { @val y = x; y };\n");
+        let [diagnostic] = document.diagnostics() else {
+            panic!("expected one diagnostic");
+        };
+        let range = diagnostic.range.clone();
+        assert_eq!(&document.text()[range], "@val y");
+        // the statement is reused but its error moves along with it
+        replace(&mut document, "a = 1", "a = 10");
+        let [diagnostic] = document.diagnostics() else {
+            panic!("expected one diagnostic");
+        };
+        let range = diagnostic.range.clone();
+        assert_eq!(&document.text()[range], "@val y");
+        // errors found by unification are located at their expression
+        replace(&mut document, "@val y = x", "y = [1, true]");
+        let [diagnostic] = document.diagnostics() else {
+            panic!("expected one diagnostic");
+        };
+        let range = diagnostic.range.clone();
+        assert_eq!(&document.text()[range], "[1, true]");
+    }
+    #[test]
+    fn warning_range() {
+        let mut document = document("f(x) => match x { true => 1, false => 2, _ => 3 };\n");
+        let [diagnostic] = document.diagnostics() else {
+            panic!("expected one diagnostic");
+        };
+        assert_eq!(diagnostic.severity, Severity::Warning);
+        let range = diagnostic.range.clone();
+        assert_eq!(&document.text()[range], "_");
+        replace(&mut document, "f(x)", "\nf(x)");
+        let [diagnostic] = document.diagnostics() else {
+            panic!("expected one diagnostic");
+        };
+        let range = diagnostic.range.clone();
+        assert_eq!(&document.text()[range], "_");
+    }
+    #[test]
+    fn tokens() {
+        let src = "x = 1; -- one\n\nid(a) => a;";
+        let document = document(src);
+        let at = |offset| document.token_at(offset).map(|range| &src[range]);
+        assert_eq!(at(0), Some("x"));
+        assert_eq!(at(1), Some("x"));
+        assert_eq!(at(2), Some("="));
+        assert_eq!(at(9), None);
+        assert_eq!(at(src.find("a)").unwrap()), Some("a"));
+
+        let mut statements = document.statements();
+        let var = match statements.next().unwrap() {
+            Statement::Declare(declare) => declare.pattern.span,
+            _ => unreachable!(),
+        };
+        assert_eq!(document.declaration(var, "x"), Some((0..6, 0..1)));
+        let fun_declare = match statements.next().unwrap() {
+            Statement::FunDeclare(fun_declare) => fun_declare.span,
+            _ => unreachable!(),
+        };
+        let start = src.find("id").unwrap();
+        assert_eq!(
+            document.declaration(fun_declare, "id"),
+            Some((start..src.len(), start..start + 2)),
+        );
+    }
+    #[test]
+    fn parse_error() {
+        let mut document = document("a = 1;\nb = a;\nc = 2;\n");
+        assert!(document.diagnostics().is_empty());
+        replace(&mut document, "c = 2;", "c = ");
+        let [diagnostic] = document.diagnostics() else {
+            panic!("expected one diagnostic");
+        };
+        assert_eq!(diagnostic.range.start, 19);
+        // the types from before are kept
+        assert_eq!(document.type_of("b").unwrap().to_string(), "I64");
+
+        replace(&mut document, "c = ", "c = a;");
+        assert!(document.diagnostics().is_empty());
+        assert_eq!(document.inferred, 1);
+        assert_fresh(&mut document, &["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,133 @@
+use hir::{
+    statement::{Module, ModuleDeclare, Statement},
+    FileId, Span,
+};
+use parser::{ast_in, EasyParser};
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, String),
+    Reloaded(PathBuf),
+}
+impl Display for LoadError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(fmt, "{}: {err}", path.display())?,
+            Self::Parse(path, err) => write!(fmt, "{}: {err}", path.display())?,
+            Self::Reloaded(path) => write!(
+                fmt,
+                "{}: module file is loaded more than once",
+                path.display()
+            )?,
+        }
+        Ok(())
+    }
+}
+/// The parsed statements along with the sources they're parsed from, each
+/// source has its own file ID
+pub struct Loaded {
+    pub statements: Vec<Statement<()>>,
+    sources: Vec<(PathBuf, FileId, String)>,
+}
+impl Loaded {
+    /// The file `span` is from along with its source
+    pub fn source_of(&self, span: Span) -> Option<(&Path, &str)> {
+        self.sources
+            .iter()
+            .find(|(_, file, _)| *file == span.file())
+            .map(|(path, _, src)| (path.as_path(), src.as_str()))
+    }
+    /// Formats the location of `span` as `path:line:column`
+    pub fn locate(&self, span: Span) -> Option<String> {
+        let (path, src) = self.source_of(span)?;
+        let before = src.get(..span.start())?;
+        let line = before.matches('\n').count() + 1;
+        let column = match before.rfind('\n') {
+            Some(newline) => before[newline + 1..].chars().count() + 1,
+            None => before.chars().count() + 1,
+        };
+        Some(format!("{}:{line}:{column}", path.display()))
+    }
+}
+/// Parses the entry file along with every file module it refers to, `mod
+/// name;` is loaded from `name.butter` next to the file declaring it
+pub fn load(path: &Path) -> Result<Loaded, LoadError> {
+    let mut loader = Loader {
+        loaded: HashSet::new(),
+        sources: Vec::new(),
+    };
+    let statements = loader.file(path)?;
+    Ok(Loaded {
+        statements,
+        sources: loader.sources,
+    })
+}
+/// Loads the file modules `statements` refers to. The statements are already
+/// parsed from the file at `path`, its source is kept by the caller and isn't
+/// part of the result
+pub fn load_modules(path: &Path, statements: Vec<Statement<()>>) -> Result<Loaded, LoadError> {
+    let mut loader = Loader {
+        loaded: HashSet::new(),
+        sources: Vec::new(),
+    };
+    if let Ok(canonical) = path.canonicalize() {
+        loader.loaded.insert(canonical);
+    }
+    let statements = loader.modules(statements, path.parent().unwrap_or(Path::new("")))?;
+    Ok(Loaded {
+        statements,
+        sources: loader.sources,
+    })
+}
+struct Loader {
+    loaded: HashSet<PathBuf>,
+    sources: Vec<(PathBuf, FileId, String)>,
+}
+impl Loader {
+    fn file(&mut self, path: &Path) -> Result<Vec<Statement<()>>, LoadError> {
+        let io_err = |err| LoadError::Io(path.to_path_buf(), err);
+        let canonical = path.canonicalize().map_err(io_err)?;
+        if !self.loaded.insert(canonical) {
+            return Err(LoadError::Reloaded(path.to_path_buf()));
+        }
+        let src = fs::read_to_string(path).map_err(io_err)?;
+        let file = FileId::fresh();
+        let (statements, _) = ast_in(file).easy_parse(&src[..]).map_err(|err| {
+            let err = err.map_position(|position| position.translate_position(&src[..]));
+            LoadError::Parse(path.to_path_buf(), err.to_string())
+        })?;
+        self.sources.push((path.to_path_buf(), file, src));
+        self.modules(statements, path.parent().unwrap_or(Path::new("")))
+    }
+    fn modules(
+        &mut self,
+        statements: Vec<Statement<()>>,
+        dir: &Path,
+    ) -> Result<Vec<Statement<()>>, LoadError> {
+        statements
+            .into_iter()
+            .map(|statement| match statement {
+                Statement::ModuleDeclare(module) => {
+                    let statements = match module.module {
+                        Module::File(file) => self.file(&dir.join(format!("{file}.butter")))?,
+                        Module::Inline(statements) => self.modules(statements.into(), dir)?,
+                    };
+                    Ok(Statement::ModuleDeclare(ModuleDeclare {
+                        visibility: module.visibility,
+                        ident: module.ident,
+                        span: module.span,
+                        module: Module::Inline(statements.into()),
+                    }))
+                }
+                statement => Ok(statement),
+            })
+            .collect()
+    }
+}
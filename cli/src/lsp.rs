@@ -0,0 +1,669 @@
+//! Language server speaking LSP over stdio. Documents are synced with
+//! incremental changes and kept as [`Document`]s, so only the statements
+//! around an edit are parsed and inferred again.
+
+use crate::incremental::{Document, PatternVars, Severity};
+use hir::{
+    arena::{self, Arena},
+    statement::{Module, Statement},
+    visit::Visit,
+    Span,
+};
+use json::Json;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+mod json;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+const SEVERITY_ERROR: usize = 1;
+const SEVERITY_WARNING: usize = 2;
+
+const SYMBOL_MODULE: usize = 2;
+const SYMBOL_FIELD: usize = 8;
+const SYMBOL_METHOD: usize = 6;
+const SYMBOL_INTERFACE: usize = 11;
+const SYMBOL_STRUCT: usize = 23;
+const SYMBOL_FUNCTION: usize = 12;
+const SYMBOL_VARIABLE: usize = 13;
+const SYMBOL_TYPE_PARAMETER: usize = 26;
+
+/// Serves over stdin and stdout until the client sends `exit`, returns
+/// whether the client asked to shut down before that
+pub fn serve() -> io::Result<bool> {
+    run(io::stdin().lock(), io::stdout().lock())
+}
+pub fn run(mut input: impl BufRead, output: impl Write) -> io::Result<bool> {
+    let mut server = Server {
+        output,
+        documents: HashMap::new(),
+        shut_down: false,
+    };
+    while let Some(body) = read_message(&mut input)? {
+        if !server.handle(&body)? {
+            break;
+        }
+    }
+    Ok(server.shut_down)
+}
+/// Reads the body of the next message, `None` on the end of the input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+struct Server<W> {
+    output: W,
+    /// Open documents by their URI
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+}
+impl<W: Write> Server<W> {
+    fn send(&mut self, message: Json) -> io::Result<()> {
+        let body = message.to_string();
+        write!(self.output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.output.flush()
+    }
+    /// Handles a message, returns `false` once the server should exit
+    fn handle(&mut self, body: &str) -> io::Result<bool> {
+        let message = match Json::parse(body) {
+            Ok(message) => message,
+            Err(err) => {
+                self.send(error_response(Json::Null, PARSE_ERROR, err.to_string()))?;
+                return Ok(true);
+            }
+        };
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let method = message.get("method").and_then(Json::as_str);
+        match (message.get("id"), method) {
+            (Some(id), Some(method)) => {
+                let response = match self.request(method, params) {
+                    Ok(result) => Json::from([
+                        ("jsonrpc", "2.0".into()),
+                        ("id", id.clone()),
+                        ("result", result),
+                    ]),
+                    Err((code, message)) => error_response(id.clone(), code, message),
+                };
+                self.send(response)?;
+            }
+            (None, Some("exit")) => return Ok(false),
+            (None, Some(method)) => self.notification(method, params)?,
+            // responses to requests the server never sends
+            (_, None) => (),
+        }
+        Ok(true)
+    }
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, (i64, String)> {
+        match method {
+            "initialize" => Ok(Json::from([
+                (
+                    "capabilities",
+                    Json::from([
+                        // changed ranges only
+                        ("textDocumentSync", 2.into()),
+                        ("hoverProvider", true.into()),
+                        ("definitionProvider", true.into()),
+                        ("documentSymbolProvider", true.into()),
+                    ]),
+                ),
+                ("serverInfo", Json::from([("name", "butter".into())])),
+            ])),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Json::Null)
+            }
+            "textDocument/hover" => {
+                let (_, document, offset) = self.position_params(params)?;
+                Ok(hover(document, offset).unwrap_or(Json::Null))
+            }
+            "textDocument/definition" => {
+                let (uri, document, offset) = self.position_params(params)?;
+                Ok(definition(&uri, document, offset).unwrap_or(Json::Null))
+            }
+            "textDocument/documentSymbol" => {
+                let uri = document_uri(params)?;
+                self.document(uri)?;
+                let document = self.documents.get_mut(uri).unwrap();
+                let mut symbols = symbols(document, document.statements());
+                // the type of a name is of its last declaration, shadowed
+                // ones are left without
+                let mut seen = HashSet::new();
+                for symbol in symbols.iter_mut().rev() {
+                    let Some(name) = symbol.get("name").and_then(Json::as_str) else {
+                        continue;
+                    };
+                    if !seen.insert(name.to_string()) {
+                        continue;
+                    }
+                    if let (Some(scheme), Json::Object(fields)) = (document.type_of(name), symbol) {
+                        fields.push(("detail".to_string(), scheme.to_string().into()));
+                    }
+                }
+                Ok(symbols.into())
+            }
+            method => Err((METHOD_NOT_FOUND, format!("unknown method `{method}`"))),
+        }
+    }
+    fn notification(&mut self, method: &str, params: &Json) -> io::Result<()> {
+        let Ok(uri) = document_uri(params) else {
+            return Ok(());
+        };
+        let uri = uri.to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let Some(text) = params
+                    .get("textDocument")
+                    .and_then(|document| document.get("text"))
+                    .and_then(Json::as_str)
+                else {
+                    return Ok(());
+                };
+                let document = Document::new(uri_to_path(&uri), text.to_string());
+                self.documents.insert(uri.clone(), document);
+            }
+            "textDocument/didChange" => {
+                let Some(document) = self.documents.get_mut(&uri) else {
+                    return Ok(());
+                };
+                let changes = params.get("contentChanges").and_then(Json::as_array);
+                for change in changes.unwrap_or_default() {
+                    let Some(text) = change.get("text").and_then(Json::as_str) else {
+                        continue;
+                    };
+                    // a change without a range replaces the whole document
+                    let range = match change.get("range") {
+                        Some(range) => {
+                            let src = document.text();
+                            let offset = |name| {
+                                let position = range.get(name)?;
+                                let line = position.get("line")?.as_usize()?;
+                                let character = position.get("character")?.as_usize()?;
+                                Some(offset(src, line, character))
+                            };
+                            let (Some(start), Some(end)) = (offset("start"), offset("end")) else {
+                                continue;
+                            };
+                            start..end.max(start)
+                        }
+                        None => 0..document.text().len(),
+                    };
+                    document.edit(range, text);
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return self.publish_diagnostics(uri, Vec::new());
+            }
+            _ => return Ok(()),
+        }
+        let document = self.documents.get_mut(&uri).unwrap();
+        let diagnostics = document.diagnostics().to_vec();
+        let diagnostics = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let severity = match diagnostic.severity {
+                    Severity::Error => SEVERITY_ERROR,
+                    Severity::Warning => SEVERITY_WARNING,
+                };
+                Json::from([
+                    ("range", range(document.text(), diagnostic.range.clone())),
+                    ("severity", severity.into()),
+                    ("source", "butter".into()),
+                    ("message", diagnostic.message.clone().into()),
+                ])
+            })
+            .collect();
+        self.publish_diagnostics(uri, diagnostics)
+    }
+    fn publish_diagnostics(&mut self, uri: String, diagnostics: Vec<Json>) -> io::Result<()> {
+        self.send(Json::from([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                Json::from([("uri", uri.into()), ("diagnostics", diagnostics.into())]),
+            ),
+        ]))
+    }
+    fn document(&self, uri: &str) -> Result<&Document, (i64, String)> {
+        self.documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("document `{uri}` is not open")))
+    }
+    /// The document and byte offset of `TextDocumentPositionParams`
+    fn position_params(
+        &mut self,
+        params: &Json,
+    ) -> Result<(String, &mut Document, usize), (i64, String)> {
+        let uri = document_uri(params)?;
+        let src = self.document(uri)?.text();
+        let position = params.get("position");
+        let coordinate = |name| {
+            position
+                .and_then(|position| position.get(name))
+                .and_then(Json::as_usize)
+                .ok_or_else(|| (INVALID_PARAMS, format!("missing position {name}")))
+        };
+        let offset = offset(src, coordinate("line")?, coordinate("character")?);
+        let document = self.documents.get_mut(uri).unwrap();
+        Ok((uri.to_string(), document, offset))
+    }
+}
+fn error_response(id: Json, code: i64, message: String) -> Json {
+    Json::from([
+        ("jsonrpc", "2.0".into()),
+        ("id", id),
+        (
+            "error",
+            Json::from([
+                ("code", Json::Number(code as f64)),
+                ("message", message.into()),
+            ]),
+        ),
+    ])
+}
+fn document_uri(params: &Json) -> Result<&str, (i64, String)> {
+    params
+        .get("textDocument")
+        .and_then(|document| document.get("uri"))
+        .and_then(Json::as_str)
+        .ok_or_else(|| (INVALID_PARAMS, "missing document URI".to_string()))
+}
+/// Inferred type of the innermost expression, pattern or function
+/// declaration at the token at `offset`
+fn hover(document: &mut Document, offset: usize) -> Option<Json> {
+    let token = document.token_at(offset)?;
+    let (file, position) = document.position(token.start)?;
+    let statements = document.typed_statements().cloned().collect();
+    let (arena, types) = Arena::from_tree(statements);
+    let exprs = types
+        .exprs
+        .iter()
+        .filter_map(|(id, ty)| Some((arena.expr_span(id)?, ty.to_string())));
+    let patterns = types
+        .patterns
+        .iter()
+        .filter_map(|(id, ty)| Some((arena.pattern_span(id)?, ty.to_string())));
+    let schemes = types
+        .schemes
+        .iter()
+        .filter_map(|(id, scheme)| match &arena[id] {
+            arena::Statement::FunDeclare { span, .. } => Some((*span, scheme.to_string())),
+            _ => None,
+        });
+    let (span, ty) = exprs
+        .chain(patterns)
+        .chain(schemes)
+        .filter(|(span, _)| span.contains(file, position))
+        .min_by_key(|(span, _)| span.end() - span.start())?;
+    let range = range(document.text(), document.locate(span)?);
+    Some(Json::from([
+        (
+            "contents",
+            Json::from([("kind", "plaintext".into()), ("value", ty.into())]),
+        ),
+        ("range", range),
+    ]))
+}
+fn definition(uri: &str, document: &mut Document, offset: usize) -> Option<Json> {
+    let token = document.token_at(offset)?;
+    let (file, position) = document.position(token.start)?;
+    let resolution = document.resolution();
+    let span = resolution
+        .binding(resolution.binding_at(file, position)?)
+        .span;
+    let (uri, range) = match document.locate(span) {
+        Some(found) => (uri.to_string(), range(document.text(), found)),
+        None => {
+            let (path, src) = document.source_of(span)?;
+            (path_to_uri(path), range(src, span.range()))
+        }
+    };
+    Some(Json::from([("uri", uri.into()), ("range", range)]))
+}
+/// `DocumentSymbol`s of the declarations
+fn symbols<'a>(
+    document: &Document,
+    statements: impl IntoIterator<Item = &'a Statement<()>>,
+) -> Vec<Json> {
+    let symbol = |name: &str, kind: usize, span: Span, children: Vec<Json>| {
+        let (whole, selection) = document.declaration(span, name)?;
+        Some(Json::from([
+            ("name", name.into()),
+            ("kind", kind.into()),
+            ("range", range(document.text(), whole)),
+            ("selectionRange", range(document.text(), selection)),
+            ("children", children.into()),
+        ]))
+    };
+    statements
+        .into_iter()
+        .flat_map(|statement| match statement {
+            Statement::Declare(declare) => {
+                let mut vars = PatternVars(Vec::new());
+                vars.visit_pattern(&declare.pattern);
+                vars.0
+                    .iter()
+                    .filter_map(|var| symbol(&var.ident, SYMBOL_VARIABLE, var.span, Vec::new()))
+                    .collect()
+            }
+            Statement::FunDeclare(fun_declare) => symbol(
+                &fun_declare.ident,
+                SYMBOL_FUNCTION,
+                fun_declare.span,
+                Vec::new(),
+            )
+            .into_iter()
+            .collect(),
+            Statement::AliasDeclare(alias) => {
+                symbol(&alias.ident, SYMBOL_TYPE_PARAMETER, alias.span, Vec::new())
+                    .into_iter()
+                    .collect()
+            }
+            Statement::NewtypeDeclare(newtype) => {
+                let children = newtype
+                    .fields
+                    .iter()
+                    .filter_map(|field| symbol(&field.name, SYMBOL_FIELD, newtype.span, Vec::new()))
+                    .collect();
+                symbol(&newtype.ident, SYMBOL_STRUCT, newtype.span, children)
+                    .into_iter()
+                    .collect()
+            }
+            Statement::ModuleDeclare(module) => {
+                let children = match &module.module {
+                    Module::File(_) => Vec::new(),
+                    Module::Inline(statements) => symbols(document, statements),
+                };
+                symbol(&module.ident, SYMBOL_MODULE, module.span, children)
+                    .into_iter()
+                    .collect()
+            }
+            Statement::TraitDeclare(trait_declare) => {
+                let children = trait_declare
+                    .methods
+                    .iter()
+                    .filter_map(|method| {
+                        symbol(&method.ident, SYMBOL_METHOD, method.span, Vec::new())
+                    })
+                    .collect();
+                symbol(
+                    &trait_declare.ident,
+                    SYMBOL_INTERFACE,
+                    trait_declare.span,
+                    children,
+                )
+                .into_iter()
+                .collect()
+            }
+            Statement::ImplDeclare(_) | Statement::Expr(_) => Vec::new(),
+        })
+        .collect()
+}
+/// LSP `Position` of a byte offset, characters are counted in UTF-16 code
+/// units
+fn position(src: &str, offset: usize) -> Json {
+    let before = &src[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    Json::from([
+        ("line", before.matches('\n').count().into()),
+        ("character", character.into()),
+    ])
+}
+fn range(src: &str, range: Range<usize>) -> Json {
+    Json::from([
+        ("start", position(src, range.start)),
+        ("end", position(src, range.end)),
+    ])
+}
+/// Byte offset of an LSP `Position`, clamped to the line and the source
+fn offset(src: &str, line: usize, character: usize) -> usize {
+    let line_start: usize = src.split_inclusive('\n').take(line).map(str::len).sum();
+    let mut units = 0;
+    for (i, ch) in src[line_start..].char_indices() {
+        if units >= character || ch == '\n' {
+            return line_start + i;
+        }
+        units += ch.len_utf16();
+    }
+    src.len()
+}
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let mut bytes = Vec::new();
+    let mut rest = path.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        let escaped = after
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (byte, escaped) {
+            (b'%', Some(escaped)) => {
+                bytes.push(escaped);
+                rest = &after[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = after;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            byte => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+#[cfg(test)]
+mod test {
+    use super::{json::Json, offset, position, read_message, run};
+    use std::io::Cursor;
+
+    /// Runs the server over the scripted messages, returns every message it
+    /// sends back
+    fn session(messages: &[Json]) -> (bool, Vec<Json>) {
+        let mut input = Vec::new();
+        for message in messages {
+            let body = message.to_string();
+            input.extend(format!("Content-Length: {}\r\n\r\n{body}", body.len()).bytes());
+        }
+        let mut output = Vec::new();
+        let shut_down = run(Cursor::new(input), &mut output).unwrap();
+        let mut output = Cursor::new(output);
+        let mut responses = Vec::new();
+        while let Some(body) = read_message(&mut output).unwrap() {
+            responses.push(Json::parse(&body).unwrap());
+        }
+        (shut_down, responses)
+    }
+    fn request(id: usize, method: &str, params: Json) -> Json {
+        Json::from([
+            ("jsonrpc", "2.0".into()),
+            ("id", id.into()),
+            ("method", method.into()),
+            ("params", params),
+        ])
+    }
+    fn notification(method: &str, params: Json) -> Json {
+        Json::from([
+            ("jsonrpc", "2.0".into()),
+            ("method", method.into()),
+            ("params", params),
+        ])
+    }
+    fn document() -> Json {
+        Json::from([("uri", "untitled:test".into())])
+    }
+    fn at(line: usize, character: usize) -> Json {
+        Json::from([
+            ("textDocument", document()),
+            (
+                "position",
+                Json::from([("line", line.into()), ("character", character.into())]),
+            ),
+        ])
+    }
+    fn start(line: usize, character: usize) -> Json {
+        Json::from([("line", line.into()), ("character", character.into())])
+    }
+    #[test]
+    fn scripted() {
+        let broken = "x = 1;\ny = z;\n";
+        let open = Json::from([(
+            "textDocument",
+            Json::from([
+                ("uri", "untitled:test".into()),
+                ("languageId", "butter".into()),
+                ("version", 1.into()),
+                ("text", broken.into()),
+            ]),
+        )]);
+        // edited into `id(a) => a;\nx = id(1);\n` one line at a time
+        let edit = |line, end, text: &str| {
+            Json::from([
+                (
+                    "range",
+                    Json::from([("start", start(line, 0)), ("end", start(line, end))]),
+                ),
+                ("text", text.into()),
+            ])
+        };
+        let change = Json::from([
+            ("textDocument", document()),
+            (
+                "contentChanges",
+                vec![edit(0, 5, "id(a) => a"), edit(1, 5, "x = id(1)")].into(),
+            ),
+        ]);
+        let (shut_down, responses) = session(&[
+            request(1, "initialize", Json::from([])),
+            notification("initialized", Json::from([])),
+            notification("textDocument/didOpen", open),
+            notification("textDocument/didChange", change),
+            request(2, "textDocument/hover", at(0, 1)),
+            request(3, "textDocument/hover", at(1, 0)),
+            request(4, "textDocument/definition", at(1, 4)),
+            request(
+                5,
+                "textDocument/documentSymbol",
+                Json::from([("textDocument", document())]),
+            ),
+            request(6, "unknown", Json::Null),
+            request(7, "shutdown", Json::Null),
+            notification("exit", Json::Null),
+        ]);
+        assert!(shut_down);
+        let [initialize, broken, fixed, hover_fun, hover_var, definition, symbols, unknown, shutdown] =
+            &responses[..]
+        else {
+            panic!("unexpected responses {responses:?}");
+        };
+        let result = |response: &Json| response.get("result").unwrap().clone();
+        let capabilities = result(initialize).get("capabilities").unwrap().clone();
+        assert_eq!(capabilities.get("hoverProvider"), Some(&true.into()));
+
+        let diagnostics = |published: &Json| {
+            published
+                .get("params")
+                .unwrap()
+                .get("diagnostics")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .to_vec()
+        };
+        let [unbound] = &diagnostics(broken)[..] else {
+            panic!("expected one diagnostic");
+        };
+        assert_eq!(
+            unbound.get("range").unwrap().get("start"),
+            Some(&start(1, 4))
+        );
+        assert_eq!(
+            unbound.get("message").unwrap().as_str(),
+            Some("unbound variable `z`"),
+        );
+        assert_eq!(diagnostics(fixed), []);
+
+        let hover = |response: &Json| {
+            result(response)
+                .get("contents")
+                .unwrap()
+                .get("value")
+                .unwrap()
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+        assert!(hover(hover_fun).starts_with(":("));
+        assert_eq!(hover(hover_var), "I64");
+        assert_eq!(
+            result(definition).get("range").unwrap().get("start"),
+            Some(&start(0, 0)),
+        );
+        let names: Vec<_> = result(symbols)
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|symbol| symbol.get("name").unwrap().as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, ["id", "x"]);
+        let symbols = result(symbols);
+        let id = &symbols.as_array().unwrap()[0];
+        assert_eq!(
+            id.get("selectionRange").unwrap().get("end"),
+            Some(&start(0, 2)),
+        );
+        assert_eq!(id.get("range").unwrap().get("end"), Some(&start(0, 11)));
+        let x = &symbols.as_array().unwrap()[1];
+        assert_eq!(x.get("detail").and_then(Json::as_str), Some("I64"));
+        assert!(unknown.get("error").is_some());
+        assert_eq!(shutdown.get("result"), Some(&Json::Null));
+    }
+    #[test]
+    fn utf16() {
+        let src = "a = \"🦋\";\nb";
+        assert_eq!(position(src, 9), start(0, 7));
+        assert_eq!(offset(src, 0, 7), 9);
+        assert_eq!(offset(src, 1, 0), src.len() - 1);
+        assert_eq!(offset(src, 0, 100), src.len() - 2);
+    }
+}
@@ -0,0 +1,369 @@
+//! Just enough JSON for the messages of the language server protocol
+
+use std::{
+    fmt::{self, Display, Formatter, Write},
+    iter::Peekable,
+    str::CharIndices,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Fields are kept in the order they're written
+    Object(Vec<(String, Json)>),
+}
+impl Json {
+    /// Value of the field, `None` if it's missing or this isn't an object
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Self::Object(fields) => fields
+                .iter()
+                .find(|(field, _)| field == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(string) => Some(string),
+            _ => None,
+        }
+    }
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Self::Number(number) if *number >= 0.0 && number.fract() == 0.0 => {
+                Some(*number as usize)
+            }
+            _ => None,
+        }
+    }
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Self::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+    pub fn parse(src: &str) -> Result<Self, JsonError> {
+        let mut parser = JsonParser {
+            src,
+            chars: src.char_indices().peekable(),
+        };
+        let value = parser.value()?;
+        parser.whitespace();
+        match parser.chars.next() {
+            Some((offset, _)) => Err(JsonError(offset)),
+            None => Ok(value),
+        }
+    }
+}
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Self::Number(value as f64)
+    }
+}
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+impl From<Vec<Json>> for Json {
+    fn from(value: Vec<Json>) -> Self {
+        Self::Array(value)
+    }
+}
+impl<const N: usize> From<[(&str, Json); N]> for Json {
+    fn from(value: [(&str, Json); N]) -> Self {
+        Self::Object(
+            value
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+}
+impl Display for Json {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => write!(fmt, "null")?,
+            Self::Bool(value) => write!(fmt, "{value}")?,
+            Self::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+                write!(fmt, "{}", *number as i64)?
+            }
+            Self::Number(number) => write!(fmt, "{number}")?,
+            Self::String(string) => write_string(fmt, string)?,
+            Self::Array(array) => {
+                write!(fmt, "[")?;
+                for (i, value) in array.iter().enumerate() {
+                    if i != 0 {
+                        write!(fmt, ",")?;
+                    }
+                    write!(fmt, "{value}")?;
+                }
+                write!(fmt, "]")?;
+            }
+            Self::Object(fields) => {
+                write!(fmt, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        write!(fmt, ",")?;
+                    }
+                    write_string(fmt, key)?;
+                    write!(fmt, ":{value}")?;
+                }
+                write!(fmt, "}}")?;
+            }
+        }
+        Ok(())
+    }
+}
+fn write_string(fmt: &mut Formatter<'_>, string: &str) -> fmt::Result {
+    fmt.write_char('"')?;
+    for ch in string.chars() {
+        match ch {
+            '"' => write!(fmt, "\\\"")?,
+            '\\' => write!(fmt, "\\\\")?,
+            '\n' => write!(fmt, "\\n")?,
+            '\r' => write!(fmt, "\\r")?,
+            '\t' => write!(fmt, "\\t")?,
+            ch if ch.is_control() => write!(fmt, "\\u{:04x}", ch as u32)?,
+            ch => fmt.write_char(ch)?,
+        }
+    }
+    fmt.write_char('"')?;
+    Ok(())
+}
+/// Invalid JSON, holds the byte offset where it is found
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct JsonError(pub usize);
+
+impl Display for JsonError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "invalid JSON at byte {}", self.0)?;
+        Ok(())
+    }
+}
+struct JsonParser<'a> {
+    src: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+impl<'a> JsonParser<'a> {
+    fn offset(&mut self) -> usize {
+        self.chars
+            .peek()
+            .map_or(self.src.len(), |(offset, _)| *offset)
+    }
+    fn whitespace(&mut self) {
+        while self
+            .chars
+            .next_if(|(_, ch)| matches!(ch, ' ' | '\t' | '\n' | '\r'))
+            .is_some()
+        {}
+    }
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        match self.chars.next() {
+            Some((_, ch)) if ch == expected => Ok(()),
+            Some((offset, _)) => Err(JsonError(offset)),
+            None => Err(JsonError(self.src.len())),
+        }
+    }
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, JsonError> {
+        for expected in keyword.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.whitespace();
+        let offset = self.offset();
+        match self.chars.peek().map(|(_, ch)| *ch) {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') => {
+                self.chars.next();
+                let mut array = Vec::new();
+                self.whitespace();
+                if self.chars.next_if(|(_, ch)| *ch == ']').is_none() {
+                    loop {
+                        array.push(self.value()?);
+                        self.whitespace();
+                        let offset = self.offset();
+                        match self.chars.next() {
+                            Some((_, ',')) => (),
+                            Some((_, ']')) => break,
+                            _ => return Err(JsonError(offset)),
+                        }
+                    }
+                }
+                Ok(Json::Array(array))
+            }
+            Some('{') => {
+                self.chars.next();
+                let mut fields = Vec::new();
+                self.whitespace();
+                if self.chars.next_if(|(_, ch)| *ch == '}').is_none() {
+                    loop {
+                        self.whitespace();
+                        let key = self.string()?;
+                        self.whitespace();
+                        self.expect(':')?;
+                        fields.push((key, self.value()?));
+                        self.whitespace();
+                        let offset = self.offset();
+                        match self.chars.next() {
+                            Some((_, ',')) => (),
+                            Some((_, '}')) => break,
+                            _ => return Err(JsonError(offset)),
+                        }
+                    }
+                }
+                Ok(Json::Object(fields))
+            }
+            Some('-' | '0'..='9') => {
+                while self
+                    .chars
+                    .next_if(|(_, ch)| matches!(ch, '-' | '+' | '.' | 'e' | 'E' | '0'..='9'))
+                    .is_some()
+                {}
+                let end = self.offset();
+                self.src[offset..end]
+                    .parse()
+                    .map(Json::Number)
+                    .map_err(|_| JsonError(offset))
+            }
+            _ => Err(JsonError(offset)),
+        }
+    }
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            let offset = self.offset();
+            match self.chars.next() {
+                Some((_, '"')) => break,
+                Some((_, '\\')) => {
+                    let ch = match self.chars.next() {
+                        Some((_, '"')) => '"',
+                        Some((_, '\\')) => '\\',
+                        Some((_, '/')) => '/',
+                        Some((_, 'b')) => '\u{8}',
+                        Some((_, 'f')) => '\u{c}',
+                        Some((_, 'n')) => '\n',
+                        Some((_, 'r')) => '\r',
+                        Some((_, 't')) => '\t',
+                        Some((_, 'u')) => {
+                            let high = self.hex()?;
+                            let code = if (0xD800..0xDC00).contains(&high) {
+                                self.expect('\\')?;
+                                self.expect('u')?;
+                                let low = self.hex()?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(JsonError(offset));
+                                }
+                                0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                            } else {
+                                high
+                            };
+                            char::from_u32(code).ok_or(JsonError(offset))?
+                        }
+                        _ => return Err(JsonError(offset)),
+                    };
+                    string.push(ch);
+                }
+                Some((_, ch)) => string.push(ch),
+                None => return Err(JsonError(offset)),
+            }
+        }
+        Ok(string)
+    }
+    fn hex(&mut self) -> Result<u32, JsonError> {
+        let offset = self.offset();
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .chars
+                .next()
+                .and_then(|(_, ch)| ch.to_digit(16))
+                .ok_or(JsonError(offset))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::{Json, JsonError};
+
+    #[test]
+    fn round_trip() {
+        let src = r#"{"a":[1,-2.5,true,null],"b":"q\"\\\n\u0001","c":{}}"#;
+        let json = Json::parse(src).unwrap();
+        assert_eq!(json.get("a").unwrap().as_array().unwrap().len(), 4);
+        assert_eq!(json.get("b").unwrap().as_str(), Some("q\"\\\n\u{1}"));
+        assert_eq!(json.to_string(), src);
+    }
+    #[test]
+    fn escape() {
+        let json = Json::parse(r#" [ "\u00e9\ud83e\udd8b\/" ] "#).unwrap();
+        assert_eq!(json, Json::Array(vec!["é🦋/".into()]));
+    }
+    #[test]
+    fn short_escapes() {
+        let json = Json::parse(r#""\"\\\/\b\f\n\r\t""#).unwrap();
+        assert_eq!(json, Json::String("\"\\/\u{8}\u{c}\n\r\t".into()));
+        assert_eq!(Json::parse(r#""\x""#), Err(JsonError(1)));
+        assert_eq!(Json::parse(r#""\u00g0""#), Err(JsonError(3)));
+        assert_eq!(Json::parse(r#""abc"#), Err(JsonError(4)));
+    }
+    #[test]
+    fn write_escapes() {
+        let json = Json::from("\"\\\n\r\t\u{8}\u{1f}é/");
+        assert_eq!(json.to_string(), r#""\"\\\n\r\t\u0008\u001fé/""#);
+    }
+    #[test]
+    fn surrogate_pairs() {
+        assert_eq!(Json::parse(r#""\ud83e\udd8b""#), Ok("🦋".into()));
+        assert_eq!(Json::parse(r#""\uD834\uDD1E""#), Ok("𝄞".into()));
+        // a lone half of a pair isn't a character
+        assert_eq!(Json::parse(r#""\ud83e""#), Err(JsonError(7)));
+        assert_eq!(Json::parse(r#""\ud83ea""#), Err(JsonError(7)));
+        assert_eq!(Json::parse(r#""\ud83e\u0041""#), Err(JsonError(1)));
+        assert_eq!(Json::parse(r#""\udd8b""#), Err(JsonError(1)));
+    }
+    #[test]
+    fn numbers() {
+        let json = Json::parse("[0, -0, 12, -3.25, 1e3, 2.5E-1, 1E+2]").unwrap();
+        let numbers = [0.0, -0.0, 12.0, -3.25, 1000.0, 0.25, 100.0].map(Json::Number);
+        assert_eq!(json, Json::Array(numbers.to_vec()));
+        assert_eq!(Json::parse("12").unwrap().as_usize(), Some(12));
+        assert_eq!(Json::parse("1.5").unwrap().as_usize(), None);
+        assert_eq!(Json::parse("-1").unwrap().as_usize(), None);
+        assert_eq!(Json::Number(42.0).to_string(), "42");
+        assert_eq!(Json::Number(-0.5).to_string(), "-0.5");
+        assert_eq!(Json::Number(1e20).to_string(), "100000000000000000000");
+        assert_eq!(Json::parse("-"), Err(JsonError(0)));
+        assert_eq!(Json::parse("1e"), Err(JsonError(0)));
+        assert_eq!(Json::parse("NaN"), Err(JsonError(0)));
+    }
+    #[test]
+    fn invalid() {
+        assert_eq!(Json::parse(r#"{"a" 1}"#), Err(JsonError(5)));
+        assert_eq!(Json::parse("[1,]"), Err(JsonError(3)));
+        assert_eq!(Json::parse("1 2"), Err(JsonError(2)));
+    }
+}
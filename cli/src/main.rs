@@ -1,12 +1,39 @@
 use clap::Parser;
-use hir::pretty_print::PrettyPrint;
-use parser::{expr_parser, EasyParser};
-use std::io::{self, Write};
-use type_system::test_infer;
+use hir::{
+    fold::Fold, pretty_print::PrettyPrint, resolve::resolve, span::EraseSpans, statement::Statement,
+};
+use lifetime::{check_borrows, check_moves};
+use load::load;
+use parser::{ast, expr_parser, format, lower, syntax_tree, EasyParser};
+use refinement::check_refinements;
+use std::{
+    fs,
+    io::{self, Write},
+    mem::take,
+    path::{Path, PathBuf},
+    process::exit,
+};
+use type_system::{infer_with_warnings, test_infer_with_warnings};
+
+mod incremental;
+mod load;
+mod lsp;
 
 /// Butter compiler
-#[derive(Parser, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
 enum Command {
+    /// Parse and type check a file along with its modules
+    Check { path: PathBuf },
+    /// Format a file in place
+    Fmt {
+        path: PathBuf,
+        /// Only check whether the file is formatted, exits with an error if
+        /// it isn't
+        #[arg(long)]
+        check: bool,
+    },
+    /// Start a language server speaking LSP over stdio
+    Lsp,
     /// Start a repl for testing type inference
     TypeRepl,
     /// Start a repl for testing parser
@@ -14,10 +41,127 @@ enum Command {
 }
 fn main() {
     match Command::parse() {
+        Command::Check { path } => {
+            if !check(&path) {
+                exit(1);
+            }
+        }
+        Command::Fmt { path, check } => {
+            if !fmt(&path, check) {
+                exit(1);
+            }
+        }
+        Command::Lsp => {
+            if !lsp::serve().unwrap() {
+                exit(1);
+            }
+        }
         Command::TypeRepl => type_repl().unwrap(),
         Command::ParserRepl => parser_repl().unwrap(),
     }
 }
+fn check(path: &Path) -> bool {
+    let mut loaded = match load(path) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("{}", err);
+            return false;
+        }
+    };
+    let resolution = match resolve(&loaded.statements) {
+        Ok(resolution) => resolution,
+        Err(unbound) => {
+            for unbound in unbound.iter() {
+                match loaded.locate(unbound.span) {
+                    Some(location) => eprintln!("{location}: {unbound}"),
+                    None => eprintln!("{unbound}"),
+                }
+            }
+            return false;
+        }
+    };
+    match infer_with_warnings(take(&mut loaded.statements)) {
+        Ok((statements, warnings)) => {
+            for warning in warnings {
+                match loaded.locate(warning.span()) {
+                    Some(location) => eprintln!("{location}: warning: {warning}"),
+                    None => eprintln!("warning: {warning}"),
+                }
+            }
+            let move_errors = check_moves(&statements, &resolution);
+            let borrow_errors = check_borrows(&statements, &resolution);
+            let refinement_errors = check_refinements(&statements, &resolution);
+            let locate = |span| loaded.locate(span).unwrap_or_default();
+            for err in &move_errors {
+                eprintln!("{}: {err}", locate(err.span));
+                eprintln!("{}: moved here", locate(err.moved));
+            }
+            for err in &borrow_errors {
+                eprintln!("{}: {err}", locate(err.span));
+                eprintln!("{}: borrowed here", locate(err.borrowed));
+            }
+            for err in &refinement_errors {
+                eprintln!("{}: {err}", locate(err.span));
+            }
+            move_errors.is_empty() && borrow_errors.is_empty() && refinement_errors.is_empty()
+        }
+        Err(err) => {
+            match err.span().and_then(|span| loaded.locate(span)) {
+                Some(location) => eprintln!("{location}: {err}"),
+                None => eprintln!("{err}"),
+            }
+            false
+        }
+    }
+}
+fn erase_spans(statements: &[Statement<()>]) -> Vec<Statement<()>> {
+    statements
+        .iter()
+        .map(|statement| EraseSpans.fold_statement(statement.clone()))
+        .collect()
+}
+fn fmt(path: &Path, check: bool) -> bool {
+    let src = match fs::read_to_string(path) {
+        Ok(src) => src,
+        Err(err) => {
+            eprintln!("{}: {err}", path.display());
+            return false;
+        }
+    };
+    let source_file = match syntax_tree().easy_parse(&src[..]) {
+        Ok((source_file, _)) => source_file,
+        Err(err) => {
+            let err = err.map_position(|position| position.translate_position(&src[..]));
+            eprintln!("{}: {err}", path.display());
+            return false;
+        }
+    };
+    let statements = lower(&source_file);
+    let formatted = format(&source_file, 80);
+    // guards against formatter bugs, the formatted code must mean the same
+    match ast().easy_parse(&formatted[..]) {
+        Ok((reparsed, _)) if erase_spans(&reparsed) == erase_spans(&statements) => (),
+        _ => {
+            eprintln!(
+                "{}: formatting would change the meaning of the code, file is left unchanged",
+                path.display()
+            );
+            return false;
+        }
+    }
+    if formatted == src {
+        return true;
+    }
+    if check {
+        eprintln!("{}: not formatted", path.display());
+        return false;
+    }
+    if let Err(err) = fs::write(path, formatted) {
+        eprintln!("{}: {err}", path.display());
+        return false;
+    }
+    true
+}
 fn type_repl() -> io::Result<()> {
     let mut stdout = io::stdout();
     let stdin = io::stdin();
@@ -39,10 +183,15 @@ fn type_repl() -> io::Result<()> {
                 continue;
             }
         };
-        let ty = match test_infer(ast) {
-            Ok(ty) => ty,
+        let ty = match test_infer_with_warnings(ast) {
+            Ok((ty, warnings)) => {
+                for warning in warnings {
+                    eprintln!("warning: {}", warning);
+                }
+                ty
+            }
             Err(err) => {
-                eprintln!("{:?}", err);
+                eprintln!("{}", err);
                 continue;
             }
         };
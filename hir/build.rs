@@ -4,8 +4,10 @@ use std::path::Path;
 fn main() {
     string_cache_codegen::AtomType::new("hir_string_cache::Atom", "keyword!")
         .atoms([
-            "", "_", "break", "clone", "continue", "else", "false", "for", "if", "imm", "in",
-            "len", "loop", "match", "mut", "ref", "return", "true", "while",
+            "", "_", "alias", "break", "clone", "concat", "continue", "else", "equal", "false",
+            "for", "given", "if", "imm", "impl", "in", "len", "loop", "match", "mod", "mut",
+            "never", "newtype", "pub", "ref", "return", "trait", "true", "while", "Bool", "Char",
+            "Concat", "Eq", "Num", "Str",
         ])
         .write_to_file(&Path::new(&env::var("OUT_DIR").unwrap()).join("hir_string_cache.rs"))
         .unwrap()
@@ -0,0 +1,777 @@
+//! Flat representation of the tree where every expression, pattern and
+//! statement is stored once in an [`Arena`] and refers to its children by ID.
+//! Nodes carry no type, types and any other analysis results are kept in
+//! [`SideTable`]s indexed by the same IDs.
+
+use crate::{
+    expr::{self, BinaryType, BoundType, ElementKind, Literal, Str, UnaryType},
+    pattern::{self, Var},
+    statement::{self, AliasDeclare, NewtypeDeclare, TraitDeclare, Visibility},
+    ty::{TraitBound, TypeExpr},
+    Atom, PrettyPrintType, Span,
+};
+use std::{collections::HashMap, marker::PhantomData, ops::Index};
+
+pub trait Id: Copy {
+    fn new(index: usize) -> Self;
+    fn index(self) -> usize;
+}
+macro_rules! id {
+    ($name:ident) => {
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+        pub struct $name(u32);
+        impl Id for $name {
+            fn new(index: usize) -> Self {
+                Self(index.try_into().expect("too many nodes for an arena"))
+            }
+            fn index(self) -> usize {
+                self.0 as usize
+            }
+        }
+    };
+}
+id!(ExprId);
+id!(PatternId);
+id!(StatementId);
+
+/// Values attached to nodes of an arena, missing values are allowed
+#[derive(Debug, PartialEq, Clone)]
+pub struct SideTable<I, V> {
+    values: Vec<Option<V>>,
+    id: PhantomData<I>,
+}
+impl<I: Id, V> SideTable<I, V> {
+    pub fn new() -> Self {
+        Self {
+            values: Vec::new(),
+            id: PhantomData,
+        }
+    }
+    pub fn insert(&mut self, id: I, value: V) -> Option<V> {
+        let index = id.index();
+        if index >= self.values.len() {
+            self.values.resize_with(index + 1, || None);
+        }
+        self.values[index].replace(value)
+    }
+    pub fn get(&self, id: I) -> Option<&V> {
+        self.values.get(id.index())?.as_ref()
+    }
+    pub fn get_mut(&mut self, id: I) -> Option<&mut V> {
+        self.values.get_mut(id.index())?.as_mut()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (I, &V)> {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(|(index, value)| Some((I::new(index), value.as_ref()?)))
+    }
+}
+impl<I: Id, V> Default for SideTable<I, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<I: Id, V> Index<I> for SideTable<I, V> {
+    type Output = V;
+
+    fn index(&self, id: I) -> &V {
+        self.get(id).expect("no value for the node")
+    }
+}
+/// Types of a tree converted with [`Arena::from_tree`]
+#[derive(Debug, Clone)]
+pub struct Types<T: PrettyPrintType> {
+    pub exprs: SideTable<ExprId, T>,
+    pub patterns: SideTable<PatternId, T>,
+    /// Schemes of function declarations
+    pub schemes: SideTable<StatementId, T::FunScheme>,
+}
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Literal(Literal),
+    Str(Str),
+    Unit,
+    Never,
+    Tag(Atom, Option<ExprId>),
+    Assign(Box<[Assign]>),
+    Array(Box<[Element]>),
+    ArrayRange(Range),
+    Splat(ExprId),
+    Record(Collection<Field>),
+    Tuple(Collection<ExprId>),
+    Unary(UnaryType, ExprId),
+    Binary(BinaryType, ExprId, ExprId),
+    Var(Atom, Span),
+    FieldAccess(ExprId, Atom),
+    Index(ExprId, ExprId),
+    Slice(ExprId, Range),
+    Deref(ExprId),
+    Len(ExprId),
+    Call(ExprId, Arg),
+    Block(Block),
+    If(If),
+    For(For),
+    While(ExprId, Block),
+    Loop(Block),
+    Match(ExprId, Box<[MatchArm]>),
+    Fun(Fun),
+    Break(Option<ExprId>),
+    Continue,
+    Return(Option<ExprId>),
+    Annotated(ExprId, TypeExpr),
+}
+impl Expr {
+    /// Whether the expression can be assigned to
+    pub fn is_place(&self) -> bool {
+        matches!(
+            self,
+            Self::Var(_, _)
+                | Self::FieldAccess(_, _)
+                | Self::Index(_, _)
+                | Self::Slice(_, _)
+                | Self::Deref(_)
+                | Self::Len(_)
+        )
+    }
+}
+/// The place of an assignment is always an expression where
+/// [`Expr::is_place`] holds
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Assign {
+    pub place: ExprId,
+    pub expr: ExprId,
+}
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Element {
+    pub expr: ExprId,
+    pub kind: ElementKind,
+}
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Bound {
+    pub kind: BoundType,
+    pub expr: ExprId,
+}
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Range {
+    pub left: Option<Bound>,
+    pub right: Option<Bound>,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Collection<E> {
+    Collection(Box<[E]>),
+    WithSplat {
+        left: Box<[E]>,
+        splat: ExprId,
+        right: Box<[E]>,
+    },
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Field {
+    pub name: Atom,
+    pub expr: ExprId,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Arg {
+    Unit,
+    Splat(ExprId),
+    Record(Collection<Field>),
+    Tuple(Collection<ExprId>),
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Block {
+    pub statements: Box<[StatementId]>,
+    pub expr: Option<ExprId>,
+}
+/// The else part is either a block or another if expression
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct If {
+    pub condition: ExprId,
+    pub body: Block,
+    pub else_part: Option<ExprId>,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct For {
+    pub pattern: PatternId,
+    pub expr: ExprId,
+    pub body: Block,
+}
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MatchArm {
+    pub pattern: PatternId,
+    pub expr: ExprId,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Fun {
+    pub param: PatternId,
+    pub ret: Option<TypeExpr>,
+    pub body: ExprId,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Pattern {
+    True,
+    False,
+    UInt(u64),
+    Int(i64),
+    Discard,
+    Var(Var),
+    Record(RecordPattern),
+    Tuple(ListPattern),
+    /// Every parameter is a [`Pattern::TypedVar`]
+    Param(Box<[PatternId]>),
+    TypedVar(Var, Option<TypeExpr>),
+    Array(ListPattern),
+    Tag(Atom, Option<PatternId>),
+    Ref(PatternId),
+    Annotated(PatternId, TypeExpr),
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RecordPattern {
+    pub fields: HashMap<Atom, PatternId>,
+    pub rest: Option<PatternId>,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ListPattern {
+    List(Box<[PatternId]>),
+    WithRest {
+        left: Box<[PatternId]>,
+        rest: PatternId,
+        right: Box<[PatternId]>,
+    },
+}
+#[derive(Debug, PartialEq, Clone)]
+pub enum Statement {
+    Declare {
+        visibility: Visibility,
+        pattern: PatternId,
+        expr: ExprId,
+        else_part: Option<Block>,
+    },
+    FunDeclare {
+        visibility: Visibility,
+        ident: Atom,
+        span: Span,
+        given: Box<[TraitBound]>,
+        generics: Box<[Atom]>,
+        fun: Fun,
+    },
+    AliasDeclare(AliasDeclare),
+    NewtypeDeclare(NewtypeDeclare),
+    TraitDeclare(TraitDeclare),
+    /// The methods are function declarations
+    ImplDeclare {
+        given: Box<[TraitBound]>,
+        bound: TraitBound,
+        span: Span,
+        methods: Box<[StatementId]>,
+    },
+    ModuleDeclare {
+        visibility: Visibility,
+        ident: Atom,
+        span: Span,
+        module: Module,
+    },
+    Expr(ExprId),
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Module {
+    File(Atom),
+    Inline(Box<[StatementId]>),
+}
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Arena {
+    exprs: Vec<Expr>,
+    patterns: Vec<Pattern>,
+    statements: Vec<Statement>,
+    root: Box<[StatementId]>,
+    expr_spans: SideTable<ExprId, Span>,
+    pattern_spans: SideTable<PatternId, Span>,
+}
+impl Arena {
+    /// Moves the tree into a new arena, returning the types of its nodes
+    /// separately
+    pub fn from_tree<T: PrettyPrintType>(
+        statements: Vec<statement::Statement<T>>,
+    ) -> (Self, Types<T>) {
+        let mut builder = Builder {
+            arena: Arena::default(),
+            types: Types {
+                exprs: SideTable::new(),
+                patterns: SideTable::new(),
+                schemes: SideTable::new(),
+            },
+        };
+        builder.arena.root = builder.statements(statements.into());
+        (builder.arena, builder.types)
+    }
+    /// The top-level statements
+    pub fn root(&self) -> &[StatementId] {
+        &self.root
+    }
+    pub fn exprs(&self) -> impl Iterator<Item = (ExprId, &Expr)> {
+        self.exprs
+            .iter()
+            .enumerate()
+            .map(|(index, expr)| (ExprId::new(index), expr))
+    }
+    pub fn patterns(&self) -> impl Iterator<Item = (PatternId, &Pattern)> {
+        self.patterns
+            .iter()
+            .enumerate()
+            .map(|(index, pattern)| (PatternId::new(index), pattern))
+    }
+    pub fn statements(&self) -> impl Iterator<Item = (StatementId, &Statement)> {
+        self.statements
+            .iter()
+            .enumerate()
+            .map(|(index, statement)| (StatementId::new(index), statement))
+    }
+    /// Span of the expression, places on the left of an assignment and else
+    /// parts have none
+    pub fn expr_span(&self, id: ExprId) -> Option<Span> {
+        self.expr_spans.get(id).copied()
+    }
+    pub fn pattern_span(&self, id: PatternId) -> Option<Span> {
+        self.pattern_spans.get(id).copied()
+    }
+}
+impl Index<ExprId> for Arena {
+    type Output = Expr;
+
+    fn index(&self, id: ExprId) -> &Expr {
+        &self.exprs[id.index()]
+    }
+}
+impl Index<PatternId> for Arena {
+    type Output = Pattern;
+
+    fn index(&self, id: PatternId) -> &Pattern {
+        &self.patterns[id.index()]
+    }
+}
+impl Index<StatementId> for Arena {
+    type Output = Statement;
+
+    fn index(&self, id: StatementId) -> &Statement {
+        &self.statements[id.index()]
+    }
+}
+struct Builder<T: PrettyPrintType> {
+    arena: Arena,
+    types: Types<T>,
+}
+impl<T: PrettyPrintType> Builder<T> {
+    fn alloc_expr(&mut self, expr: Expr, typed: Option<(T, Span)>) -> ExprId {
+        let id = ExprId::new(self.arena.exprs.len());
+        self.arena.exprs.push(expr);
+        if let Some((ty, span)) = typed {
+            self.types.exprs.insert(id, ty);
+            self.arena.expr_spans.insert(id, span);
+        }
+        id
+    }
+    fn alloc_pattern(&mut self, pattern: Pattern, ty: T, span: Span) -> PatternId {
+        let id = PatternId::new(self.arena.patterns.len());
+        self.arena.patterns.push(pattern);
+        self.types.patterns.insert(id, ty);
+        self.arena.pattern_spans.insert(id, span);
+        id
+    }
+    fn alloc_statement(&mut self, statement: Statement) -> StatementId {
+        let id = StatementId::new(self.arena.statements.len());
+        self.arena.statements.push(statement);
+        id
+    }
+    fn statements(&mut self, statements: Box<[statement::Statement<T>]>) -> Box<[StatementId]> {
+        Vec::from(statements)
+            .into_iter()
+            .map(|statement| self.statement(statement))
+            .collect()
+    }
+    fn statement(&mut self, statement: statement::Statement<T>) -> StatementId {
+        let statement = match statement {
+            statement::Statement::Declare(declare) => {
+                let expr = self.expr(declare.expr);
+                let else_part = declare.else_part.map(|block| self.block(block));
+                Statement::Declare {
+                    visibility: declare.visibility,
+                    pattern: self.pattern(declare.pattern),
+                    expr,
+                    else_part,
+                }
+            }
+            statement::Statement::FunDeclare(fun_declare) => {
+                let fun = self.fun(fun_declare.fun);
+                let id = self.alloc_statement(Statement::FunDeclare {
+                    visibility: fun_declare.visibility,
+                    ident: fun_declare.ident,
+                    span: fun_declare.span,
+                    given: fun_declare.given,
+                    generics: fun_declare.generics,
+                    fun,
+                });
+                self.types.schemes.insert(id, fun_declare.ty);
+                return id;
+            }
+            statement::Statement::AliasDeclare(alias) => Statement::AliasDeclare(alias),
+            statement::Statement::NewtypeDeclare(newtype) => Statement::NewtypeDeclare(newtype),
+            statement::Statement::TraitDeclare(trait_declare) => {
+                Statement::TraitDeclare(trait_declare)
+            }
+            statement::Statement::ImplDeclare(impl_declare) => Statement::ImplDeclare {
+                given: impl_declare.given,
+                bound: impl_declare.bound,
+                span: impl_declare.span,
+                methods: Vec::from(impl_declare.methods)
+                    .into_iter()
+                    .map(|method| self.statement(statement::Statement::FunDeclare(method)))
+                    .collect(),
+            },
+            statement::Statement::ModuleDeclare(module_declare) => Statement::ModuleDeclare {
+                visibility: module_declare.visibility,
+                ident: module_declare.ident,
+                span: module_declare.span,
+                module: match module_declare.module {
+                    statement::Module::File(file) => Module::File(file),
+                    statement::Module::Inline(statements) => {
+                        Module::Inline(self.statements(statements))
+                    }
+                },
+            },
+            statement::Statement::Expr(expr) => Statement::Expr(self.expr(expr)),
+        };
+        self.alloc_statement(statement)
+    }
+    fn exprs(&mut self, exprs: Box<[expr::Expr<T>]>) -> Box<[ExprId]> {
+        Vec::from(exprs)
+            .into_iter()
+            .map(|expr| self.expr(expr))
+            .collect()
+    }
+    fn expr(&mut self, expr: expr::Expr<T>) -> ExprId {
+        use expr::ExprKind;
+
+        let node = match expr.expr {
+            ExprKind::Literal(literal) => Expr::Literal(literal),
+            ExprKind::Str(string) => Expr::Str(string),
+            ExprKind::Tag(tag) => Expr::Tag(tag.tag, tag.expr.map(|expr| self.expr(*expr))),
+            ExprKind::Assign(assign) => Expr::Assign(
+                Vec::from(assign)
+                    .into_iter()
+                    .map(|assign| {
+                        let expr = self.expr(assign.expr);
+                        Assign {
+                            place: self.place(assign.place, None),
+                            expr,
+                        }
+                    })
+                    .collect(),
+            ),
+            ExprKind::Array(elements) => Expr::Array(
+                Vec::from(elements)
+                    .into_iter()
+                    .map(|element| Element {
+                        expr: self.expr(element.expr),
+                        kind: element.kind,
+                    })
+                    .collect(),
+            ),
+            ExprKind::ArrayRange(range) => Expr::ArrayRange(self.range(range)),
+            ExprKind::Unit => Expr::Unit,
+            ExprKind::Never => Expr::Never,
+            ExprKind::Splat(expr) => Expr::Splat(self.expr(*expr)),
+            ExprKind::Record(record) => Expr::Record(self.record(record)),
+            ExprKind::Tuple(tuple) => Expr::Tuple(self.tuple(tuple)),
+            ExprKind::Unary(unary) => Expr::Unary(unary.kind, self.expr(*unary.expr)),
+            ExprKind::Binary(binary) => {
+                let left = self.expr(*binary.left);
+                Expr::Binary(binary.kind, left, self.expr(*binary.right))
+            }
+            ExprKind::Place(place) => return self.place(place, Some((expr.ty, expr.span))),
+            ExprKind::Call(call) => {
+                let callee = self.expr(*call.expr);
+                let arg = match call.arg {
+                    expr::Arg::Unit => Arg::Unit,
+                    expr::Arg::Splat(expr) => Arg::Splat(self.expr(*expr)),
+                    expr::Arg::Record(record) => Arg::Record(self.record(record)),
+                    expr::Arg::Tuple(tuple) => Arg::Tuple(self.tuple(tuple)),
+                };
+                Expr::Call(callee, arg)
+            }
+            ExprKind::ControlFlow(control_flow) => {
+                return self.control_flow(control_flow, Some((expr.ty, expr.span)));
+            }
+            ExprKind::Fun(fun) => Expr::Fun(self.fun(fun)),
+            ExprKind::Jump(expr::Jump::Break(expr)) => {
+                Expr::Break(expr.map(|expr| self.expr(*expr)))
+            }
+            ExprKind::Jump(expr::Jump::Continue) => Expr::Continue,
+            ExprKind::Jump(expr::Jump::Return(expr)) => {
+                Expr::Return(expr.map(|expr| self.expr(*expr)))
+            }
+            ExprKind::Annotated(annotated) => {
+                Expr::Annotated(self.expr(*annotated.expr), annotated.annotation)
+            }
+        };
+        self.alloc_expr(node, Some((expr.ty, expr.span)))
+    }
+    // places on the left of an assignment have no type of their own in the
+    // tree
+    fn place(&mut self, place: expr::PlaceExpr<T>, typed: Option<(T, Span)>) -> ExprId {
+        use expr::PlaceExpr;
+
+        let node = match place {
+            PlaceExpr::Var(name, span) => Expr::Var(name, span),
+            PlaceExpr::FieldAccess(access) => {
+                Expr::FieldAccess(self.expr(*access.expr), access.name)
+            }
+            PlaceExpr::Index(index) => {
+                let expr = self.expr(*index.expr);
+                Expr::Index(expr, self.expr(*index.index))
+            }
+            PlaceExpr::Slice(slice) => {
+                let expr = self.expr(*slice.expr);
+                Expr::Slice(expr, self.range(slice.range))
+            }
+            PlaceExpr::Deref(expr) => Expr::Deref(self.expr(*expr)),
+            PlaceExpr::Len(expr) => Expr::Len(self.expr(*expr)),
+        };
+        self.alloc_expr(node, typed)
+    }
+    fn range(&mut self, range: expr::Range<T>) -> Range {
+        let mut bound = |bound: expr::Bound<T>| Bound {
+            kind: bound.kind,
+            expr: self.expr(*bound.expr),
+        };
+        let left = range.left.map(&mut bound);
+        Range {
+            left,
+            right: range.right.map(bound),
+        }
+    }
+    fn record(&mut self, record: expr::Collection<expr::Field<T>, T>) -> Collection<Field> {
+        match record {
+            expr::Collection::Collection(record) => {
+                Collection::Collection(self.record_fields(record))
+            }
+            expr::Collection::WithSplat(record) => {
+                let left = self.record_fields(record.left);
+                let splat = self.expr(*record.splat);
+                Collection::WithSplat {
+                    left,
+                    splat,
+                    right: self.record_fields(record.right),
+                }
+            }
+        }
+    }
+    fn record_fields(&mut self, fields: Box<[expr::Field<T>]>) -> Box<[Field]> {
+        Vec::from(fields)
+            .into_iter()
+            .map(|field| Field {
+                name: field.name,
+                expr: self.expr(field.expr),
+            })
+            .collect()
+    }
+    fn tuple(&mut self, tuple: expr::Collection<expr::Expr<T>, T>) -> Collection<ExprId> {
+        match tuple {
+            expr::Collection::Collection(tuple) => Collection::Collection(self.exprs(tuple)),
+            expr::Collection::WithSplat(tuple) => {
+                let left = self.exprs(tuple.left);
+                let splat = self.expr(*tuple.splat);
+                Collection::WithSplat {
+                    left,
+                    splat,
+                    right: self.exprs(tuple.right),
+                }
+            }
+        }
+    }
+    fn block(&mut self, block: expr::Block<T>) -> Block {
+        let statements = self.statements(block.statement);
+        Block {
+            statements,
+            expr: block.expr.map(|expr| self.expr(*expr)),
+        }
+    }
+    fn control_flow(
+        &mut self,
+        control_flow: expr::ControlFlow<T>,
+        typed: Option<(T, Span)>,
+    ) -> ExprId {
+        use expr::ControlFlow;
+
+        let node = match control_flow {
+            ControlFlow::Block(block) => Expr::Block(self.block(block)),
+            ControlFlow::If(if_expr) => {
+                let condition = self.expr(*if_expr.condition);
+                let body = self.block(if_expr.body);
+                // the else part has no type of its own in the tree
+                let else_part = if_expr
+                    .else_part
+                    .map(|else_part| self.control_flow(*else_part, None));
+                Expr::If(If {
+                    condition,
+                    body,
+                    else_part,
+                })
+            }
+            ControlFlow::For(for_expr) => {
+                let expr = self.expr(*for_expr.expr);
+                Expr::For(For {
+                    pattern: self.pattern(for_expr.pattern),
+                    expr,
+                    body: self.block(for_expr.body),
+                })
+            }
+            ControlFlow::While(while_expr) => {
+                let condition = self.expr(*while_expr.condition);
+                Expr::While(condition, self.block(while_expr.body))
+            }
+            ControlFlow::Loop(block) => Expr::Loop(self.block(block)),
+            ControlFlow::Match(match_expr) => {
+                let expr = self.expr(*match_expr.expr);
+                let arms = Vec::from(match_expr.arm)
+                    .into_iter()
+                    .map(|arm| {
+                        let pattern = self.pattern(arm.pattern);
+                        MatchArm {
+                            pattern,
+                            expr: self.expr(arm.expr),
+                        }
+                    })
+                    .collect();
+                Expr::Match(expr, arms)
+            }
+        };
+        self.alloc_expr(node, typed)
+    }
+    fn fun(&mut self, fun: expr::Fun<T>) -> Fun {
+        let param = self.pattern(fun.param);
+        Fun {
+            param,
+            ret: fun.ret,
+            body: self.expr(*fun.body),
+        }
+    }
+    fn patterns(&mut self, patterns: Box<[pattern::Pattern<T>]>) -> Box<[PatternId]> {
+        Vec::from(patterns)
+            .into_iter()
+            .map(|pattern| self.pattern(pattern))
+            .collect()
+    }
+    fn list_pattern(&mut self, list: pattern::ListPattern<T>) -> ListPattern {
+        match list {
+            pattern::ListPattern::List(list) => ListPattern::List(self.patterns(list)),
+            pattern::ListPattern::ListWithRest(list) => {
+                let left = self.patterns(list.left);
+                let rest = self.pattern(*list.rest);
+                ListPattern::WithRest {
+                    left,
+                    rest,
+                    right: self.patterns(list.right),
+                }
+            }
+        }
+    }
+    fn pattern(&mut self, pattern: pattern::Pattern<T>) -> PatternId {
+        use pattern::PatternKind;
+
+        let node = match pattern.pattern {
+            PatternKind::True => Pattern::True,
+            PatternKind::False => Pattern::False,
+            PatternKind::UInt(value) => Pattern::UInt(value),
+            PatternKind::Int(value) => Pattern::Int(value),
+            PatternKind::Discard => Pattern::Discard,
+            PatternKind::Var(var) => Pattern::Var(var),
+            PatternKind::Record(record) => {
+                let fields = record
+                    .fields
+                    .into_iter()
+                    .map(|(name, pattern)| (name, self.pattern(pattern)))
+                    .collect();
+                Pattern::Record(RecordPattern {
+                    fields,
+                    rest: record.rest.map(|rest| self.pattern(*rest)),
+                })
+            }
+            PatternKind::Tuple(list) => Pattern::Tuple(self.list_pattern(list)),
+            PatternKind::Param(param) => Pattern::Param(
+                Vec::from(param)
+                    .into_iter()
+                    .map(|typed_var| {
+                        let span = typed_var.var.span;
+                        self.alloc_pattern(
+                            Pattern::TypedVar(typed_var.var, typed_var.annotation),
+                            typed_var.ty,
+                            span,
+                        )
+                    })
+                    .collect(),
+            ),
+            PatternKind::Array(list) => Pattern::Array(self.list_pattern(list)),
+            PatternKind::Tag(tagged) => Pattern::Tag(
+                tagged.tag,
+                tagged.pattern.map(|pattern| self.pattern(*pattern)),
+            ),
+            PatternKind::Ref(pattern) => Pattern::Ref(self.pattern(*pattern)),
+            PatternKind::Annotated(annotated) => {
+                Pattern::Annotated(self.pattern(*annotated.pattern), annotated.annotation)
+            }
+        };
+        self.alloc_pattern(node, pattern.ty, pattern.span)
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::{
+        arena::{Arena, Expr, Statement},
+        expr::{Assign, Binary, BinaryType, ExprKind, PlaceExpr},
+        statement, Atom, Span,
+    };
+
+    fn var(name: &str) -> PlaceExpr<()> {
+        PlaceExpr::Var(Atom::from(name), Span::default())
+    }
+    #[test]
+    fn from_tree() {
+        // a = b + c
+        let sum = ExprKind::Binary(Binary {
+            kind: BinaryType::Add,
+            left: Box::new(ExprKind::Place(var("b")).into_untyped()),
+            right: Box::new(ExprKind::Place(var("c")).into_untyped()),
+        })
+        .into_untyped();
+        let assign = ExprKind::Assign(
+            vec![Assign {
+                place: var("a"),
+                expr: sum,
+            }]
+            .into(),
+        )
+        .into_untyped();
+        let (arena, types) = Arena::from_tree(vec![statement::Statement::Expr(assign)]);
+        let [root] = arena.root() else {
+            panic!("expected a single statement");
+        };
+        let Statement::Expr(assign) = &arena[*root] else {
+            panic!("expected expression statement");
+        };
+        let Expr::Assign(assign) = &arena[*assign] else {
+            panic!("expected assignment");
+        };
+        assert!(arena[assign[0].place].is_place());
+        let Expr::Binary(BinaryType::Add, left, right) = arena[assign[0].expr] else {
+            panic!("expected addition");
+        };
+        assert_eq!(arena[left], Expr::Var(Atom::from("b"), Span::default()));
+        assert_eq!(arena[right], Expr::Var(Atom::from("c"), Span::default()));
+        assert_eq!(arena.exprs().count(), 5);
+        // the place has no type of its own
+        assert_eq!(types.exprs.iter().count(), 4);
+        assert_eq!(types.exprs.get(assign[0].place), None);
+        assert_eq!(arena.expr_span(assign[0].place), None);
+        assert_eq!(arena.expr_span(left), Some(Span::default()));
+    }
+}
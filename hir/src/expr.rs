@@ -5,7 +5,8 @@ use crate::{
         bracket, line, multiline_sequence, postfix, prefix, sequence, PrettyPrint, PrettyPrintTree,
     },
     statement::Statement,
-    Atom, PrettyPrintType, TraverseType,
+    ty::TypeExpr,
+    Atom, MapTypes, PrettyPrintType, Span, TraverseType, TypeSite,
 };
 use std::{
     fmt::{self, Display, Formatter},
@@ -19,6 +20,8 @@ pub enum Literal {
 
     UInt(u64),
     Float(f64),
+
+    Char(char),
 }
 impl Display for Literal {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
@@ -27,14 +30,49 @@ impl Display for Literal {
             Self::False => write!(fmt, "false")?,
             Self::UInt(num) => write!(fmt, "{num}")?,
             Self::Float(num) => write!(fmt, "{num}")?,
+            Self::Char(ch) => write!(fmt, "'{}'", escape(*ch, '\''))?,
         }
         Ok(())
     }
 }
+// the character as written within quotes of `delimiter`
+fn escape(ch: char, delimiter: char) -> String {
+    match ch {
+        '\\' => "\\\\".to_string(),
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\t' => "\\t".to_string(),
+        '\u{b}' => "\\v".to_string(),
+        '\0' => "\\0".to_string(),
+        ch if ch == delimiter => format!("\\{ch}"),
+        ch if ch.is_ascii_control() => format!("\\x{:02x}", ch as u8),
+        ch => ch.to_string(),
+    }
+}
+/// A string literal, its bytes are kept as written even if they're not valid
+/// UTF-8
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Str(pub Box<[u8]>);
+impl Display for Str {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        let Self(bytes) = self;
+        write!(fmt, "\"")?;
+        for chunk in bytes.utf8_chunks() {
+            for ch in chunk.valid().chars() {
+                write!(fmt, "{}", escape(ch, '"'))?;
+            }
+            for byte in chunk.invalid() {
+                write!(fmt, "\\x{byte:02x}")?;
+            }
+        }
+        write!(fmt, "\"")
+    }
+}
 #[derive(Debug, PartialEq, Clone)]
 pub struct Expr<T: PrettyPrintType> {
     pub expr: ExprKind<T>,
     pub ty: T,
+    pub span: Span,
 }
 impl<T: PrettyPrintType> Expr<T> {
     pub fn field_name(&self) -> Option<Atom> {
@@ -63,44 +101,52 @@ impl<T: PrettyPrintType> Expr<T> {
 }
 impl<T: PrettyPrintType> TraverseType for Expr<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Expr<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        for_type(&mut self.ty, data)?;
+        f(TypeSite::Type(&mut self.ty))?;
         match &mut self.expr {
-            ExprKind::Literal(_) => (),
-            ExprKind::Tag(tag) => tag.traverse_type(data, for_type, for_scheme)?,
+            ExprKind::Literal(_) | ExprKind::Str(_) => (),
+            ExprKind::Tag(tag) => tag.traverse_type(f)?,
             ExprKind::Assign(assign) => {
                 for assign in assign.iter_mut() {
-                    assign.traverse_type(data, for_type, for_scheme)?;
+                    assign.traverse_type(f)?;
                 }
             }
             ExprKind::Array(array) => {
                 for elem in array.iter_mut() {
-                    elem.traverse_type(data, for_type, for_scheme)?;
+                    elem.traverse_type(f)?;
                 }
             }
-            ExprKind::ArrayRange(range) => range.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Unit => (),
-            ExprKind::Splat(expr) => expr.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Record(record) => record.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Tuple(tuple) => tuple.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Unary(unary) => unary.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Binary(binary) => binary.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Place(place) => place.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Call(call) => call.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::ControlFlow(control_flow) => {
-                control_flow.traverse_type(data, for_type, for_scheme)?
-            }
-            ExprKind::Fun(fun) => fun.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Jump(jump) => jump.traverse_type(data, for_type, for_scheme)?,
+            ExprKind::ArrayRange(range) => range.traverse_type(f)?,
+            ExprKind::Unit | ExprKind::Never => (),
+            ExprKind::Splat(expr) => expr.traverse_type(f)?,
+            ExprKind::Record(record) => record.traverse_type(f)?,
+            ExprKind::Tuple(tuple) => tuple.traverse_type(f)?,
+            ExprKind::Unary(unary) => unary.traverse_type(f)?,
+            ExprKind::Binary(binary) => binary.traverse_type(f)?,
+            ExprKind::Place(place) => place.traverse_type(f)?,
+            ExprKind::Call(call) => call.traverse_type(f)?,
+            ExprKind::ControlFlow(control_flow) => control_flow.traverse_type(f)?,
+            ExprKind::Fun(fun) => fun.traverse_type(f)?,
+            ExprKind::Jump(jump) => jump.traverse_type(f)?,
+            ExprKind::Annotated(annotated) => annotated.traverse_type(f)?,
         }
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Expr {
+            ty: map.map_type(self.ty),
+            expr: self.expr.map_types(map),
+            span: self.span,
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Expr<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -114,6 +160,7 @@ impl<T: PrettyPrintType> PrettyPrint for Expr<T> {
 #[derive(Debug, PartialEq, Clone)]
 pub enum ExprKind<T: PrettyPrintType> {
     Literal(Literal),
+    Str(Str),
 
     Tag(Tag<T>),
 
@@ -123,6 +170,8 @@ pub enum ExprKind<T: PrettyPrintType> {
     ArrayRange(Range<T>),
 
     Unit,
+    /// Marks code that is never reached
+    Never,
     Splat(Box<Expr<T>>),
     Record(Collection<Field<T>, T>),
     Tuple(Collection<Expr<T>, T>),
@@ -136,6 +185,8 @@ pub enum ExprKind<T: PrettyPrintType> {
     ControlFlow(ControlFlow<T>),
     Fun(Fun<T>),
     Jump(Jump<T>),
+
+    Annotated(Annotated<T>),
 }
 impl<T: PrettyPrintType> ExprKind<T> {
     pub fn field_name(&self) -> Option<Atom> {
@@ -152,11 +203,13 @@ impl<T: PrettyPrintType> ExprKind<T> {
     pub fn precedence(&self) -> u8 {
         match self {
             ExprKind::Literal(_) => 0,
+            ExprKind::Str(_) => 0,
             ExprKind::Tag(_) => 2,
             ExprKind::Assign(_) => 8,
             ExprKind::Array(_) => 0,
             ExprKind::ArrayRange(_) => 0,
             ExprKind::Unit => 0,
+            ExprKind::Never => 0,
             ExprKind::Splat(_) => 0,
             ExprKind::Record(_) => 0,
             ExprKind::Tuple(_) => 0,
@@ -167,53 +220,81 @@ impl<T: PrettyPrintType> ExprKind<T> {
             ExprKind::ControlFlow(_) => 0,
             ExprKind::Fun(_) => 9,
             ExprKind::Jump(jump) => jump.precedence(),
+            ExprKind::Annotated(_) => 8,
         }
     }
 }
 impl<T: PrettyPrintType> TraverseType for ExprKind<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = ExprKind<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         match self {
-            ExprKind::Literal(_) => (),
-            ExprKind::Tag(tag) => tag.traverse_type(data, for_type, for_scheme)?,
+            ExprKind::Literal(_) | ExprKind::Str(_) => (),
+            ExprKind::Tag(tag) => tag.traverse_type(f)?,
             ExprKind::Assign(assign) => {
                 for assign in assign.iter_mut() {
-                    assign.traverse_type(data, for_type, for_scheme)?;
+                    assign.traverse_type(f)?;
                 }
             }
             ExprKind::Array(array) => {
                 for element in array.iter_mut() {
-                    element.traverse_type(data, for_type, for_scheme)?;
+                    element.traverse_type(f)?;
                 }
             }
-            ExprKind::ArrayRange(range) => range.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Unit => (),
-            ExprKind::Splat(expr) => expr.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Record(record) => record.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Tuple(tuple) => tuple.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Unary(unary) => unary.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Binary(binary) => binary.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Place(place) => place.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Call(call) => call.traverse_type(data, for_type, for_scheme)?,
+            ExprKind::ArrayRange(range) => range.traverse_type(f)?,
+            ExprKind::Unit | ExprKind::Never => (),
+            ExprKind::Splat(expr) => expr.traverse_type(f)?,
+            ExprKind::Record(record) => record.traverse_type(f)?,
+            ExprKind::Tuple(tuple) => tuple.traverse_type(f)?,
+            ExprKind::Unary(unary) => unary.traverse_type(f)?,
+            ExprKind::Binary(binary) => binary.traverse_type(f)?,
+            ExprKind::Place(place) => place.traverse_type(f)?,
+            ExprKind::Call(call) => call.traverse_type(f)?,
+            ExprKind::ControlFlow(control_flow) => control_flow.traverse_type(f)?,
+            ExprKind::Fun(fun) => fun.traverse_type(f)?,
+            ExprKind::Jump(jump) => jump.traverse_type(f)?,
+            ExprKind::Annotated(annotated) => annotated.traverse_type(f)?,
+        }
+        Ok(())
+    }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        match self {
+            ExprKind::Literal(literal) => ExprKind::Literal(literal),
+            ExprKind::Str(string) => ExprKind::Str(string),
+            ExprKind::Tag(tag) => ExprKind::Tag(tag.map_types(map)),
+            ExprKind::Assign(assign) => ExprKind::Assign(assign.map_types(map)),
+            ExprKind::Array(array) => ExprKind::Array(array.map_types(map)),
+            ExprKind::ArrayRange(range) => ExprKind::ArrayRange(range.map_types(map)),
+            ExprKind::Unit => ExprKind::Unit,
+            ExprKind::Never => ExprKind::Never,
+            ExprKind::Splat(expr) => ExprKind::Splat(expr.map_types(map)),
+            ExprKind::Record(record) => ExprKind::Record(record.map_types(map)),
+            ExprKind::Tuple(tuple) => ExprKind::Tuple(tuple.map_types(map)),
+            ExprKind::Unary(unary) => ExprKind::Unary(unary.map_types(map)),
+            ExprKind::Binary(binary) => ExprKind::Binary(binary.map_types(map)),
+            ExprKind::Place(place) => ExprKind::Place(place.map_types(map)),
+            ExprKind::Call(call) => ExprKind::Call(call.map_types(map)),
             ExprKind::ControlFlow(control_flow) => {
-                control_flow.traverse_type(data, for_type, for_scheme)?
+                ExprKind::ControlFlow(control_flow.map_types(map))
             }
-            ExprKind::Fun(fun) => fun.traverse_type(data, for_type, for_scheme)?,
-            ExprKind::Jump(jump) => jump.traverse_type(data, for_type, for_scheme)?,
+            ExprKind::Fun(fun) => ExprKind::Fun(fun.map_types(map)),
+            ExprKind::Jump(jump) => ExprKind::Jump(jump.map_types(map)),
+            ExprKind::Annotated(annotated) => ExprKind::Annotated(annotated.map_types(map)),
         }
-        Ok(())
     }
 }
 impl<T: PrettyPrintType> PrettyPrint for ExprKind<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
         match self {
             ExprKind::Literal(literal) => Box::new(literal.to_string()),
+            ExprKind::Str(string) => Box::new(string.to_string()),
             ExprKind::Tag(tag) => tag.to_pretty_print(),
             ExprKind::Assign(assign) if assign.len() == 1 => assign[0].to_pretty_print(),
             ExprKind::Assign(assign) => sequence(
@@ -242,6 +323,7 @@ impl<T: PrettyPrintType> PrettyPrint for ExprKind<T> {
             }
             ExprKind::ArrayRange(array) => array.to_pretty_print(),
             ExprKind::Unit => Box::new("()".to_string()),
+            ExprKind::Never => Box::new("never".to_string()),
             ExprKind::Splat(expr) => bracket("(", ")", prefix("*", expr.to_pretty_print())),
             ExprKind::Record(record) => record.to_pretty_print(),
             ExprKind::Tuple(tuple) => tuple.to_pretty_print(),
@@ -252,17 +334,22 @@ impl<T: PrettyPrintType> PrettyPrint for ExprKind<T> {
             ExprKind::ControlFlow(control_flow) => control_flow.to_pretty_print(),
             ExprKind::Fun(fun) => fun.to_pretty_print(),
             ExprKind::Jump(jump) => jump.to_pretty_print(),
+            ExprKind::Annotated(annotated) => annotated.to_pretty_print(),
         }
     }
 }
 impl ExprKind<()> {
     pub fn into_untyped(self) -> Expr<()> {
-        Expr { expr: self, ty: () }
+        Expr {
+            expr: self,
+            ty: (),
+            span: Span::default(),
+        }
     }
 }
 #[derive(Debug, PartialEq, Clone)]
 pub enum PlaceExpr<T: PrettyPrintType> {
-    Var(Atom),
+    Var(Atom, Span),
     FieldAccess(FieldAccess<T>),
     Index(Index<T>),
     Slice(Slice<T>),
@@ -271,30 +358,42 @@ pub enum PlaceExpr<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for PlaceExpr<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = PlaceExpr<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         match self {
-            PlaceExpr::Var(_) => (),
+            PlaceExpr::Var(_, _) => (),
+            PlaceExpr::FieldAccess(field_access) => field_access.traverse_type(f)?,
+            PlaceExpr::Index(index) => index.traverse_type(f)?,
+            PlaceExpr::Slice(slice) => slice.traverse_type(f)?,
+            PlaceExpr::Deref(expr) => expr.traverse_type(f)?,
+            PlaceExpr::Len(expr) => expr.traverse_type(f)?,
+        }
+        Ok(())
+    }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        match self {
+            PlaceExpr::Var(var, span) => PlaceExpr::Var(var, span),
             PlaceExpr::FieldAccess(field_access) => {
-                field_access.traverse_type(data, for_type, for_scheme)?
+                PlaceExpr::FieldAccess(field_access.map_types(map))
             }
-            PlaceExpr::Index(index) => index.traverse_type(data, for_type, for_scheme)?,
-            PlaceExpr::Slice(slice) => slice.traverse_type(data, for_type, for_scheme)?,
-            PlaceExpr::Deref(expr) => expr.traverse_type(data, for_type, for_scheme)?,
-            PlaceExpr::Len(expr) => expr.traverse_type(data, for_type, for_scheme)?,
+            PlaceExpr::Index(index) => PlaceExpr::Index(index.map_types(map)),
+            PlaceExpr::Slice(slice) => PlaceExpr::Slice(slice.map_types(map)),
+            PlaceExpr::Deref(expr) => PlaceExpr::Deref(expr.map_types(map)),
+            PlaceExpr::Len(expr) => PlaceExpr::Len(expr.map_types(map)),
         }
-        Ok(())
     }
 }
 impl<T: PrettyPrintType> PrettyPrint for PlaceExpr<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
         match self {
-            PlaceExpr::Var(var) => Box::new(var.to_string()),
+            PlaceExpr::Var(var, _) => Box::new(var.to_string()),
             PlaceExpr::FieldAccess(field_access) => field_access.to_pretty_print(),
             PlaceExpr::Index(index) => index.to_pretty_print(),
             PlaceExpr::Slice(slice) => slice.to_pretty_print(),
@@ -306,7 +405,7 @@ impl<T: PrettyPrintType> PrettyPrint for PlaceExpr<T> {
 impl<T: PrettyPrintType> PlaceExpr<T> {
     fn precedence(&self) -> u8 {
         match self {
-            PlaceExpr::Var(_) => 0,
+            PlaceExpr::Var(_, _) => 0,
             _ => 1,
         }
     }
@@ -314,7 +413,7 @@ impl<T: PrettyPrintType> PlaceExpr<T> {
 impl<T: PrettyPrintType> PlaceExpr<T> {
     pub fn field_name(&self) -> Option<Atom> {
         match self {
-            Self::Var(var) => Some(var.clone()),
+            Self::Var(var, _) => Some(var.clone()),
             Self::FieldAccess(field) => field.field_name(),
             Self::Deref(deref) => deref.field_name(),
             _ => None,
@@ -322,7 +421,7 @@ impl<T: PrettyPrintType> PlaceExpr<T> {
     }
     pub fn var(&self) -> Option<Atom> {
         match self {
-            PlaceExpr::Var(var) => Some(var.clone()),
+            PlaceExpr::Var(var, _) => Some(var.clone()),
             PlaceExpr::FieldAccess(FieldAccess { expr, name: _ })
             | PlaceExpr::Index(Index { expr, index: _ })
             | PlaceExpr::Slice(Slice { expr, range: _ })
@@ -337,32 +436,103 @@ impl<T: PrettyPrintType> PlaceExpr<T> {
             }
         }
     }
+    /// Span of the variable the place is within, where it's used
+    pub fn var_span(&self) -> Option<Span> {
+        match self {
+            PlaceExpr::Var(_, span) => Some(*span),
+            PlaceExpr::FieldAccess(FieldAccess { expr, name: _ })
+            | PlaceExpr::Index(Index { expr, index: _ })
+            | PlaceExpr::Slice(Slice { expr, range: _ })
+            | PlaceExpr::Deref(expr)
+            | PlaceExpr::Len(expr) => {
+                let expr: &ExprKind<_> = &expr.expr;
+                if let ExprKind::Place(place) = expr {
+                    place.var_span()
+                } else {
+                    None
+                }
+            }
+        }
+    }
 }
 #[derive(Debug, PartialEq, Clone)]
 pub struct Fun<T: PrettyPrintType> {
     pub param: Pattern<T>,
+    pub ret: Option<TypeExpr>,
     pub body: Box<Expr<T>>,
 }
 impl<T: PrettyPrintType> TraverseType for Fun<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Fun<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.param.traverse_type(data, for_type, for_scheme)?;
-        self.body.traverse_type(data, for_type, for_scheme)?;
+        self.param.traverse_type(f)?;
+        self.body.traverse_type(f)?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Fun {
+            param: self.param.map_types(map),
+            ret: self.ret,
+            body: self.body.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Fun<T> {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        match &self.ret {
+            Some(ret) => line([
+                self.param.to_pretty_print(),
+                Box::new(" -> ".to_string()),
+                ret.to_pretty_print(),
+                Box::new(" => ".to_string()),
+                self.body.to_auto_wrap(9),
+            ]),
+            None => line([
+                self.param.to_pretty_print(),
+                Box::new(" => ".to_string()),
+                self.body.to_auto_wrap(9),
+            ]),
+        }
+    }
+}
+#[derive(Debug, PartialEq, Clone)]
+pub struct Annotated<T: PrettyPrintType> {
+    pub expr: Box<Expr<T>>,
+    pub annotation: TypeExpr,
+}
+impl<T: PrettyPrintType> TraverseType for Annotated<T> {
+    type Type = T;
+    type Mapped<U: PrettyPrintType> = Annotated<U>;
+
+    fn traverse_type<E>(
+        &mut self,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        self.expr.traverse_type(f)
+    }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Annotated {
+            expr: self.expr.map_types(map),
+            annotation: self.annotation,
+        }
+    }
+}
+impl<T: PrettyPrintType> PrettyPrint for Annotated<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
         line([
-            self.param.to_pretty_print(),
-            Box::new(" => ".to_string()),
-            self.body.to_auto_wrap(9),
+            self.expr.to_auto_wrap(7),
+            Box::new(" : ".to_string()),
+            self.annotation.to_pretty_print(),
         ])
     }
 }
@@ -374,26 +544,35 @@ pub enum Jump<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Jump<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Jump<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         match self {
             Jump::Break(expr) => expr
                 .as_mut()
-                .map(|expr| expr.traverse_type(data, for_type, for_scheme))
+                .map(|expr| expr.traverse_type(f))
                 .unwrap_or(Ok(()))?,
             Jump::Continue => (),
             Jump::Return(expr) => expr
                 .as_mut()
-                .map(|expr| expr.traverse_type(data, for_type, for_scheme))
+                .map(|expr| expr.traverse_type(f))
                 .unwrap_or(Ok(()))?,
         }
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        match self {
+            Jump::Break(expr) => Jump::Break(expr.map_types(map)),
+            Jump::Continue => Jump::Continue,
+            Jump::Return(expr) => Jump::Return(expr.map_types(map)),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Jump<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -422,14 +601,22 @@ pub struct Unary<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Unary<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Unary<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.expr.traverse_type(data, for_type, for_scheme)
+        self.expr.traverse_type(f)
+    }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Unary {
+            kind: self.kind,
+            expr: self.expr.map_types(map),
+        }
     }
 }
 impl<T: PrettyPrintType> PrettyPrint for Unary<T> {
@@ -470,17 +657,26 @@ pub struct Binary<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Binary<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Binary<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.left.traverse_type(data, for_type, for_scheme)?;
-        self.right.traverse_type(data, for_type, for_scheme)?;
+        self.left.traverse_type(f)?;
+        self.right.traverse_type(f)?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Binary {
+            kind: self.kind,
+            left: self.left.map_types(map),
+            right: self.right.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Binary<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -557,17 +753,25 @@ pub struct Index<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Index<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Index<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.expr.traverse_type(data, for_type, for_scheme)?;
-        self.index.traverse_type(data, for_type, for_scheme)?;
+        self.expr.traverse_type(f)?;
+        self.index.traverse_type(f)?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Index {
+            expr: self.expr.map_types(map),
+            index: self.index.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Index<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -584,14 +788,22 @@ pub struct Element<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Element<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Element<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.expr.traverse_type(data, for_type, for_scheme)
+        self.expr.traverse_type(f)
+    }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Element {
+            expr: self.expr.map_types(map),
+            kind: self.kind,
+        }
     }
 }
 impl<T: PrettyPrintType> PrettyPrint for Element<T> {
@@ -629,23 +841,31 @@ impl<T: PrettyPrintType> Collection<Field<T>, T> {
 }
 impl<T: TraverseType> TraverseType for Collection<T, T::Type> {
     type Type = T::Type;
+    type Mapped<U: PrettyPrintType> = Collection<T::Mapped<U>, U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         match self {
             Collection::Collection(collection) => {
                 for element in collection.iter_mut() {
-                    element.traverse_type(data, for_type, for_scheme)?;
+                    element.traverse_type(f)?;
                 }
             }
-            Collection::WithSplat(_) => todo!(),
+            Collection::WithSplat(with_splat) => with_splat.traverse_type(f)?,
         }
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        match self {
+            Collection::Collection(collection) => Collection::Collection(collection.map_types(map)),
+            Collection::WithSplat(with_splat) => Collection::WithSplat(with_splat.map_types(map)),
+        }
+    }
 }
 impl<T, U> PrettyPrint for Collection<T, U>
 where
@@ -673,22 +893,31 @@ pub struct WithSplat<T, U: PrettyPrintType> {
 }
 impl<T: TraverseType> TraverseType for WithSplat<T, T::Type> {
     type Type = T::Type;
+    type Mapped<U: PrettyPrintType> = WithSplat<T::Mapped<U>, U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         for element in self.left.iter_mut() {
-            element.traverse_type(data, for_type, for_scheme)?;
+            element.traverse_type(f)?;
         }
-        self.splat.traverse_type(data, for_type, for_scheme)?;
+        self.splat.traverse_type(f)?;
         for element in self.right.iter_mut() {
-            element.traverse_type(data, for_type, for_scheme)?;
+            element.traverse_type(f)?;
         }
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        WithSplat {
+            left: self.left.map_types(map),
+            splat: self.splat.map_types(map),
+            right: self.right.map_types(map),
+        }
+    }
 }
 impl<T, U> PrettyPrint for WithSplat<T, U>
 where
@@ -715,14 +944,22 @@ pub struct Field<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Field<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Field<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.expr.traverse_type(data, for_type, for_scheme)
+        self.expr.traverse_type(f)
+    }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Field {
+            name: self.name,
+            expr: self.expr.map_types(map),
+        }
     }
 }
 impl<T: PrettyPrintType> PrettyPrint for Field<T> {
@@ -744,30 +981,38 @@ pub enum ControlFlow<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for ControlFlow<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = ControlFlow<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         match self {
-            ControlFlow::Block(block) => block.traverse_type(data, for_type, for_scheme)?,
-            ControlFlow::If(if_statement) => {
-                if_statement.traverse_type(data, for_type, for_scheme)?
-            }
-            ControlFlow::For(for_statement) => {
-                for_statement.traverse_type(data, for_type, for_scheme)?
-            }
+            ControlFlow::Block(block) => block.traverse_type(f)?,
+            ControlFlow::If(if_statement) => if_statement.traverse_type(f)?,
+            ControlFlow::For(for_statement) => for_statement.traverse_type(f)?,
+            ControlFlow::While(while_statement) => while_statement.traverse_type(f)?,
+            ControlFlow::Loop(block) => block.traverse_type(f)?,
+            ControlFlow::Match(match_statement) => match_statement.traverse_type(f)?,
+        }
+        Ok(())
+    }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        match self {
+            ControlFlow::Block(block) => ControlFlow::Block(block.map_types(map)),
+            ControlFlow::If(if_statement) => ControlFlow::If(if_statement.map_types(map)),
+            ControlFlow::For(for_statement) => ControlFlow::For(for_statement.map_types(map)),
             ControlFlow::While(while_statement) => {
-                while_statement.traverse_type(data, for_type, for_scheme)?
+                ControlFlow::While(while_statement.map_types(map))
             }
-            ControlFlow::Loop(block) => block.traverse_type(data, for_type, for_scheme)?,
+            ControlFlow::Loop(block) => ControlFlow::Loop(block.map_types(map)),
             ControlFlow::Match(match_statement) => {
-                match_statement.traverse_type(data, for_type, for_scheme)?
+                ControlFlow::Match(match_statement.map_types(map))
             }
         }
-        Ok(())
     }
 }
 impl<T: PrettyPrintType> PrettyPrint for ControlFlow<T> {
@@ -789,25 +1034,35 @@ impl<T: PrettyPrintType> PrettyPrint for ControlFlow<T> {
 pub struct Block<T: PrettyPrintType> {
     pub statement: Box<[Statement<T>]>,
     pub expr: Option<Box<Expr<T>>>,
+    pub span: Span,
 }
 impl<T: PrettyPrintType> TraverseType for Block<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Block<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         for statement in self.statement.iter_mut() {
-            statement.traverse_type(data, for_type, for_scheme)?;
+            statement.traverse_type(f)?;
         }
         self.expr
             .as_mut()
-            .map(|expr| expr.traverse_type(data, for_type, for_scheme))
+            .map(|expr| expr.traverse_type(f))
             .unwrap_or(Ok(()))?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Block {
+            statement: self.statement.map_types(map),
+            expr: self.expr.map_types(map),
+            span: self.span,
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Block<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -840,21 +1095,30 @@ pub struct If<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for If<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = If<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.condition.traverse_type(data, for_type, for_scheme)?;
-        self.body.traverse_type(data, for_type, for_scheme)?;
+        self.condition.traverse_type(f)?;
+        self.body.traverse_type(f)?;
         self.else_part
             .as_mut()
-            .map(|else_part| else_part.traverse_type(data, for_type, for_scheme))
+            .map(|else_part| else_part.traverse_type(f))
             .unwrap_or(Ok(()))?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        If {
+            condition: self.condition.map_types(map),
+            body: self.body.map_types(map),
+            else_part: self.else_part.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for If<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -884,18 +1148,27 @@ pub struct For<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for For<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = For<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.pattern.traverse_type(data, for_type, for_scheme)?;
-        self.expr.traverse_type(data, for_type, for_scheme)?;
-        self.body.traverse_type(data, for_type, for_scheme)?;
+        self.pattern.traverse_type(f)?;
+        self.expr.traverse_type(f)?;
+        self.body.traverse_type(f)?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        For {
+            pattern: self.pattern.map_types(map),
+            expr: self.expr.map_types(map),
+            body: self.body.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for For<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -916,17 +1189,25 @@ pub struct While<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for While<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = While<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.condition.traverse_type(data, for_type, for_scheme)?;
-        self.body.traverse_type(data, for_type, for_scheme)?;
+        self.condition.traverse_type(f)?;
+        self.body.traverse_type(f)?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        While {
+            condition: self.condition.map_types(map),
+            body: self.body.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for While<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -945,19 +1226,27 @@ pub struct Match<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Match<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Match<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.expr.traverse_type(data, for_type, for_scheme)?;
+        self.expr.traverse_type(f)?;
         for arm in self.arm.iter_mut() {
-            arm.traverse_type(data, for_type, for_scheme)?;
+            arm.traverse_type(f)?;
         }
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Match {
+            expr: self.expr.map_types(map),
+            arm: self.arm.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Match<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -986,17 +1275,25 @@ pub struct MatchArm<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for MatchArm<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = MatchArm<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.pattern.traverse_type(data, for_type, for_scheme)?;
-        self.expr.traverse_type(data, for_type, for_scheme)?;
+        self.pattern.traverse_type(f)?;
+        self.expr.traverse_type(f)?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        MatchArm {
+            pattern: self.pattern.map_types(map),
+            expr: self.expr.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for MatchArm<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -1014,17 +1311,25 @@ pub struct Assign<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Assign<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Assign<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.place.traverse_type(data, for_type, for_scheme)?;
-        self.expr.traverse_type(data, for_type, for_scheme)?;
+        self.place.traverse_type(f)?;
+        self.expr.traverse_type(f)?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Assign {
+            place: self.place.map_types(map),
+            expr: self.expr.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Assign<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -1042,14 +1347,22 @@ pub struct FieldAccess<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for FieldAccess<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = FieldAccess<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.expr.traverse_type(data, for_type, for_scheme)
+        self.expr.traverse_type(f)
+    }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        FieldAccess {
+            expr: self.expr.map_types(map),
+            name: self.name,
+        }
     }
 }
 impl<T: PrettyPrintType> FieldAccess<T> {
@@ -1072,17 +1385,25 @@ pub struct Slice<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Slice<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Slice<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.expr.traverse_type(data, for_type, for_scheme)?;
-        self.range.traverse_type(data, for_type, for_scheme)?;
+        self.expr.traverse_type(f)?;
+        self.range.traverse_type(f)?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Slice {
+            expr: self.expr.map_types(map),
+            range: self.range.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Slice<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -1096,17 +1417,25 @@ pub struct Call<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Call<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Call<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.expr.traverse_type(data, for_type, for_scheme)?;
-        self.arg.traverse_type(data, for_type, for_scheme)?;
+        self.expr.traverse_type(f)?;
+        self.arg.traverse_type(f)?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Call {
+            expr: self.expr.map_types(map),
+            arg: self.arg.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Call<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -1122,21 +1451,31 @@ pub enum Arg<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Arg<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Arg<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         match self {
             Arg::Unit => (),
-            Arg::Splat(expr) => expr.traverse_type(data, for_type, for_scheme)?,
-            Arg::Record(record) => record.traverse_type(data, for_type, for_scheme)?,
-            Arg::Tuple(tuple) => tuple.traverse_type(data, for_type, for_scheme)?,
+            Arg::Splat(expr) => expr.traverse_type(f)?,
+            Arg::Record(record) => record.traverse_type(f)?,
+            Arg::Tuple(tuple) => tuple.traverse_type(f)?,
         }
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        match self {
+            Arg::Unit => Arg::Unit,
+            Arg::Splat(expr) => Arg::Splat(expr.map_types(map)),
+            Arg::Record(record) => Arg::Record(record.map_types(map)),
+            Arg::Tuple(tuple) => Arg::Tuple(tuple.map_types(map)),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Arg<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -1155,18 +1494,26 @@ pub struct Tag<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Tag<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Tag<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         self.expr
             .as_mut()
-            .map(|expr| expr.traverse_type(data, for_type, for_scheme))
+            .map(|expr| expr.traverse_type(f))
             .unwrap_or(Ok(()))
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Tag {
+            tag: self.tag,
+            expr: self.expr.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Tag<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -1191,14 +1538,22 @@ pub struct Bound<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Bound<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Bound<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.expr.traverse_type(data, for_type, for_scheme)
+        self.expr.traverse_type(f)
+    }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Bound {
+            kind: self.kind,
+            expr: self.expr.map_types(map),
+        }
     }
 }
 #[derive(Debug, PartialEq, Clone)]
@@ -1208,23 +1563,31 @@ pub struct Range<T: PrettyPrintType> {
 }
 impl<T: PrettyPrintType> TraverseType for Range<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Range<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         self.left
             .as_mut()
-            .map(|bound| bound.traverse_type(data, for_type, for_scheme))
+            .map(|bound| bound.traverse_type(f))
             .unwrap_or(Ok(()))?;
         self.right
             .as_mut()
-            .map(|bound| bound.traverse_type(data, for_type, for_scheme))
+            .map(|bound| bound.traverse_type(f))
             .unwrap_or(Ok(()))?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Range {
+            left: self.left.map_types(map),
+            right: self.right.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Range<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -0,0 +1,824 @@
+//! Rewriting traversal over an owned tree. Every method of [`Fold`] defaults
+//! to the `walk_*` function of the same node which rebuilds it from its
+//! folded children, so an implementor only overrides the nodes it rewrites.
+//!
+//! Children are folded in the same order [`crate::visit::Visit`] visits them.
+
+use crate::{
+    expr::{
+        Annotated, Arg, Assign, Binary, Block, Bound, Call, Collection, ControlFlow, Element, Expr,
+        ExprKind, Field, FieldAccess, For, Fun, If, Index, Jump, Literal, Match, MatchArm,
+        PlaceExpr, Range, Slice, Str, Tag, Unary, While, WithSplat,
+    },
+    pattern::{
+        AnnotatedPattern, ListPattern, ListWithRest, Pattern, PatternKind, RecordPattern,
+        TaggedPattern, TypedVar, Var,
+    },
+    statement::{
+        AliasDeclare, Declare, FunDeclare, ImplDeclare, MethodSignature, Module, ModuleDeclare,
+        NewtypeDeclare, NewtypeField, Statement, TraitDeclare,
+    },
+    ty::{TraitBound, TypeExpr},
+    Atom, PrettyPrintType, Span,
+};
+
+pub trait Fold<T: PrettyPrintType> {
+    fn fold_statement(&mut self, statement: Statement<T>) -> Statement<T> {
+        walk_statement(self, statement)
+    }
+    fn fold_declare(&mut self, declare: Declare<T>) -> Declare<T> {
+        walk_declare(self, declare)
+    }
+    fn fold_fun_declare(&mut self, fun_declare: FunDeclare<T>) -> FunDeclare<T> {
+        walk_fun_declare(self, fun_declare)
+    }
+    fn fold_alias_declare(&mut self, alias_declare: AliasDeclare) -> AliasDeclare {
+        walk_alias_declare(self, alias_declare)
+    }
+    fn fold_newtype_declare(&mut self, newtype_declare: NewtypeDeclare) -> NewtypeDeclare {
+        walk_newtype_declare(self, newtype_declare)
+    }
+    fn fold_trait_declare(&mut self, trait_declare: TraitDeclare) -> TraitDeclare {
+        walk_trait_declare(self, trait_declare)
+    }
+    fn fold_impl_declare(&mut self, impl_declare: ImplDeclare<T>) -> ImplDeclare<T> {
+        walk_impl_declare(self, impl_declare)
+    }
+    fn fold_trait_bound(&mut self, bound: TraitBound) -> TraitBound {
+        walk_trait_bound(self, bound)
+    }
+    fn fold_module_declare(&mut self, module_declare: ModuleDeclare<T>) -> ModuleDeclare<T> {
+        walk_module_declare(self, module_declare)
+    }
+    fn fold_module(&mut self, module: Module<T>) -> Module<T> {
+        walk_module(self, module)
+    }
+    fn fold_expr(&mut self, expr: Expr<T>) -> Expr<T> {
+        walk_expr(self, expr)
+    }
+    fn fold_literal(&mut self, literal: Literal) -> Literal {
+        literal
+    }
+    fn fold_str(&mut self, string: Str) -> Str {
+        string
+    }
+    fn fold_tag(&mut self, tag: Tag<T>) -> Tag<T> {
+        walk_tag(self, tag)
+    }
+    fn fold_assign(&mut self, assign: Assign<T>) -> Assign<T> {
+        walk_assign(self, assign)
+    }
+    fn fold_element(&mut self, element: Element<T>) -> Element<T> {
+        walk_element(self, element)
+    }
+    fn fold_range(&mut self, range: Range<T>) -> Range<T> {
+        walk_range(self, range)
+    }
+    fn fold_record(&mut self, record: Collection<Field<T>, T>) -> Collection<Field<T>, T> {
+        walk_record(self, record)
+    }
+    fn fold_field(&mut self, field: Field<T>) -> Field<T> {
+        walk_field(self, field)
+    }
+    fn fold_tuple(&mut self, tuple: Collection<Expr<T>, T>) -> Collection<Expr<T>, T> {
+        walk_tuple(self, tuple)
+    }
+    fn fold_unary(&mut self, unary: Unary<T>) -> Unary<T> {
+        walk_unary(self, unary)
+    }
+    fn fold_binary(&mut self, binary: Binary<T>) -> Binary<T> {
+        walk_binary(self, binary)
+    }
+    fn fold_place(&mut self, place: PlaceExpr<T>) -> PlaceExpr<T> {
+        walk_place(self, place)
+    }
+    /// A variable used as an expression, as opposed to [`Fold::fold_var`]
+    /// which is a variable bound by a pattern
+    fn fold_var_expr(&mut self, name: Atom, span: Span) -> (Atom, Span) {
+        (name, self.fold_span(span))
+    }
+    fn fold_span(&mut self, span: Span) -> Span {
+        span
+    }
+    fn fold_field_access(&mut self, field_access: FieldAccess<T>) -> FieldAccess<T> {
+        walk_field_access(self, field_access)
+    }
+    fn fold_index(&mut self, index: Index<T>) -> Index<T> {
+        walk_index(self, index)
+    }
+    fn fold_slice(&mut self, slice: Slice<T>) -> Slice<T> {
+        walk_slice(self, slice)
+    }
+    fn fold_call(&mut self, call: Call<T>) -> Call<T> {
+        walk_call(self, call)
+    }
+    fn fold_arg(&mut self, arg: Arg<T>) -> Arg<T> {
+        walk_arg(self, arg)
+    }
+    fn fold_control_flow(&mut self, control_flow: ControlFlow<T>) -> ControlFlow<T> {
+        walk_control_flow(self, control_flow)
+    }
+    fn fold_block(&mut self, block: Block<T>) -> Block<T> {
+        walk_block(self, block)
+    }
+    fn fold_if(&mut self, if_expr: If<T>) -> If<T> {
+        walk_if(self, if_expr)
+    }
+    fn fold_for(&mut self, for_expr: For<T>) -> For<T> {
+        walk_for(self, for_expr)
+    }
+    fn fold_while(&mut self, while_expr: While<T>) -> While<T> {
+        walk_while(self, while_expr)
+    }
+    fn fold_match(&mut self, match_expr: Match<T>) -> Match<T> {
+        walk_match(self, match_expr)
+    }
+    fn fold_match_arm(&mut self, arm: MatchArm<T>) -> MatchArm<T> {
+        walk_match_arm(self, arm)
+    }
+    fn fold_fun(&mut self, fun: Fun<T>) -> Fun<T> {
+        walk_fun(self, fun)
+    }
+    fn fold_jump(&mut self, jump: Jump<T>) -> Jump<T> {
+        walk_jump(self, jump)
+    }
+    fn fold_annotated(&mut self, annotated: Annotated<T>) -> Annotated<T> {
+        walk_annotated(self, annotated)
+    }
+    fn fold_pattern(&mut self, pattern: Pattern<T>) -> Pattern<T> {
+        walk_pattern(self, pattern)
+    }
+    fn fold_var(&mut self, var: Var) -> Var {
+        Var {
+            span: self.fold_span(var.span),
+            ..var
+        }
+    }
+    fn fold_typed_var(&mut self, typed_var: TypedVar<T>) -> TypedVar<T> {
+        walk_typed_var(self, typed_var)
+    }
+    fn fold_record_pattern(&mut self, record: RecordPattern<T>) -> RecordPattern<T> {
+        walk_record_pattern(self, record)
+    }
+    fn fold_list_pattern(&mut self, list: ListPattern<T>) -> ListPattern<T> {
+        walk_list_pattern(self, list)
+    }
+    fn fold_tagged_pattern(&mut self, tagged: TaggedPattern<T>) -> TaggedPattern<T> {
+        walk_tagged_pattern(self, tagged)
+    }
+    fn fold_annotated_pattern(&mut self, annotated: AnnotatedPattern<T>) -> AnnotatedPattern<T> {
+        walk_annotated_pattern(self, annotated)
+    }
+    fn fold_type_expr(&mut self, ty: TypeExpr) -> TypeExpr {
+        ty
+    }
+}
+fn fold_all<U, F>(items: Box<[U]>, mut fold: F) -> Box<[U]>
+where
+    F: FnMut(U) -> U,
+{
+    Vec::from(items).into_iter().map(&mut fold).collect()
+}
+fn fold_box<T, F>(folder: &mut F, expr: Expr<T>) -> Box<Expr<T>>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    Box::new(folder.fold_expr(expr))
+}
+pub fn walk_statement<T, F>(folder: &mut F, statement: Statement<T>) -> Statement<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    match statement {
+        Statement::Declare(declare) => Statement::Declare(folder.fold_declare(declare)),
+        Statement::FunDeclare(fun_declare) => {
+            Statement::FunDeclare(folder.fold_fun_declare(fun_declare))
+        }
+        Statement::AliasDeclare(alias_declare) => {
+            Statement::AliasDeclare(folder.fold_alias_declare(alias_declare))
+        }
+        Statement::NewtypeDeclare(newtype_declare) => {
+            Statement::NewtypeDeclare(folder.fold_newtype_declare(newtype_declare))
+        }
+        Statement::TraitDeclare(trait_declare) => {
+            Statement::TraitDeclare(folder.fold_trait_declare(trait_declare))
+        }
+        Statement::ImplDeclare(impl_declare) => {
+            Statement::ImplDeclare(folder.fold_impl_declare(impl_declare))
+        }
+        Statement::ModuleDeclare(module_declare) => {
+            Statement::ModuleDeclare(folder.fold_module_declare(module_declare))
+        }
+        Statement::Expr(expr) => Statement::Expr(folder.fold_expr(expr)),
+    }
+}
+pub fn walk_declare<T, F>(folder: &mut F, declare: Declare<T>) -> Declare<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let expr = folder.fold_expr(declare.expr);
+    let else_part = declare.else_part.map(|block| folder.fold_block(block));
+    Declare {
+        visibility: declare.visibility,
+        pattern: folder.fold_pattern(declare.pattern),
+        expr,
+        else_part,
+    }
+}
+pub fn walk_fun_declare<T, F>(folder: &mut F, fun_declare: FunDeclare<T>) -> FunDeclare<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    FunDeclare {
+        span: folder.fold_span(fun_declare.span),
+        given: fold_all(fun_declare.given, |bound| folder.fold_trait_bound(bound)),
+        fun: folder.fold_fun(fun_declare.fun),
+        ..fun_declare
+    }
+}
+pub fn walk_alias_declare<T, F>(folder: &mut F, alias_declare: AliasDeclare) -> AliasDeclare
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    AliasDeclare {
+        span: folder.fold_span(alias_declare.span),
+        ty: folder.fold_type_expr(alias_declare.ty),
+        ..alias_declare
+    }
+}
+pub fn walk_newtype_declare<T, F>(folder: &mut F, newtype_declare: NewtypeDeclare) -> NewtypeDeclare
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let fields = fold_all(newtype_declare.fields, |field| NewtypeField {
+        ty: folder.fold_type_expr(field.ty),
+        ..field
+    });
+    NewtypeDeclare {
+        span: folder.fold_span(newtype_declare.span),
+        fields,
+        ..newtype_declare
+    }
+}
+pub fn walk_trait_declare<T, F>(folder: &mut F, trait_declare: TraitDeclare) -> TraitDeclare
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let methods = fold_all(trait_declare.methods, |method| MethodSignature {
+        span: folder.fold_span(method.span),
+        params: fold_all(method.params, |(name, ty)| {
+            (name, folder.fold_type_expr(ty))
+        }),
+        ret: folder.fold_type_expr(method.ret),
+        ..method
+    });
+    TraitDeclare {
+        span: folder.fold_span(trait_declare.span),
+        methods,
+        ..trait_declare
+    }
+}
+pub fn walk_impl_declare<T, F>(folder: &mut F, impl_declare: ImplDeclare<T>) -> ImplDeclare<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    ImplDeclare {
+        given: fold_all(impl_declare.given, |bound| folder.fold_trait_bound(bound)),
+        bound: folder.fold_trait_bound(impl_declare.bound),
+        span: folder.fold_span(impl_declare.span),
+        methods: fold_all(impl_declare.methods, |method| {
+            folder.fold_fun_declare(method)
+        }),
+    }
+}
+pub fn walk_trait_bound<T, F>(folder: &mut F, bound: TraitBound) -> TraitBound
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    TraitBound {
+        ty: folder.fold_type_expr(bound.ty),
+        ..bound
+    }
+}
+pub fn walk_module_declare<T, F>(
+    folder: &mut F,
+    module_declare: ModuleDeclare<T>,
+) -> ModuleDeclare<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    ModuleDeclare {
+        span: folder.fold_span(module_declare.span),
+        module: folder.fold_module(module_declare.module),
+        ..module_declare
+    }
+}
+pub fn walk_module<T, F>(folder: &mut F, module: Module<T>) -> Module<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    match module {
+        Module::File(file) => Module::File(file),
+        Module::Inline(statements) => Module::Inline(fold_all(statements, |statement| {
+            folder.fold_statement(statement)
+        })),
+    }
+}
+pub fn walk_expr<T, F>(folder: &mut F, expr: Expr<T>) -> Expr<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let kind = match expr.expr {
+        ExprKind::Literal(literal) => ExprKind::Literal(folder.fold_literal(literal)),
+        ExprKind::Str(string) => ExprKind::Str(folder.fold_str(string)),
+        ExprKind::Tag(tag) => ExprKind::Tag(folder.fold_tag(tag)),
+        ExprKind::Assign(assign) => {
+            ExprKind::Assign(fold_all(assign, |assign| folder.fold_assign(assign)))
+        }
+        ExprKind::Array(elements) => {
+            ExprKind::Array(fold_all(elements, |element| folder.fold_element(element)))
+        }
+        ExprKind::ArrayRange(range) => ExprKind::ArrayRange(folder.fold_range(range)),
+        ExprKind::Unit => ExprKind::Unit,
+        ExprKind::Never => ExprKind::Never,
+        ExprKind::Splat(expr) => ExprKind::Splat(fold_box(folder, *expr)),
+        ExprKind::Record(record) => ExprKind::Record(folder.fold_record(record)),
+        ExprKind::Tuple(tuple) => ExprKind::Tuple(folder.fold_tuple(tuple)),
+        ExprKind::Unary(unary) => ExprKind::Unary(folder.fold_unary(unary)),
+        ExprKind::Binary(binary) => ExprKind::Binary(folder.fold_binary(binary)),
+        ExprKind::Place(place) => ExprKind::Place(folder.fold_place(place)),
+        ExprKind::Call(call) => ExprKind::Call(folder.fold_call(call)),
+        ExprKind::ControlFlow(control_flow) => {
+            ExprKind::ControlFlow(folder.fold_control_flow(control_flow))
+        }
+        ExprKind::Fun(fun) => ExprKind::Fun(folder.fold_fun(fun)),
+        ExprKind::Jump(jump) => ExprKind::Jump(folder.fold_jump(jump)),
+        ExprKind::Annotated(annotated) => ExprKind::Annotated(folder.fold_annotated(annotated)),
+    };
+    Expr {
+        expr: kind,
+        ty: expr.ty,
+        span: folder.fold_span(expr.span),
+    }
+}
+pub fn walk_tag<T, F>(folder: &mut F, tag: Tag<T>) -> Tag<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    Tag {
+        tag: tag.tag,
+        expr: tag.expr.map(|expr| fold_box(folder, *expr)),
+    }
+}
+pub fn walk_assign<T, F>(folder: &mut F, assign: Assign<T>) -> Assign<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let expr = folder.fold_expr(assign.expr);
+    Assign {
+        place: folder.fold_place(assign.place),
+        expr,
+    }
+}
+pub fn walk_element<T, F>(folder: &mut F, element: Element<T>) -> Element<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    Element {
+        expr: folder.fold_expr(element.expr),
+        kind: element.kind,
+    }
+}
+pub fn walk_range<T, F>(folder: &mut F, range: Range<T>) -> Range<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let mut bound = |bound: Bound<T>| Bound {
+        kind: bound.kind,
+        expr: fold_box(folder, *bound.expr),
+    };
+    let left = range.left.map(&mut bound);
+    Range {
+        left,
+        right: range.right.map(bound),
+    }
+}
+pub fn walk_record<T, F>(folder: &mut F, record: Collection<Field<T>, T>) -> Collection<Field<T>, T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    match record {
+        Collection::Collection(fields) => {
+            Collection::Collection(fold_all(fields, |field| folder.fold_field(field)))
+        }
+        Collection::WithSplat(record) => {
+            let left = fold_all(record.left, |field| folder.fold_field(field));
+            let splat = fold_box(folder, *record.splat);
+            Collection::WithSplat(WithSplat {
+                left,
+                splat,
+                right: fold_all(record.right, |field| folder.fold_field(field)),
+            })
+        }
+    }
+}
+pub fn walk_field<T, F>(folder: &mut F, field: Field<T>) -> Field<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    Field {
+        name: field.name,
+        expr: folder.fold_expr(field.expr),
+    }
+}
+pub fn walk_tuple<T, F>(folder: &mut F, tuple: Collection<Expr<T>, T>) -> Collection<Expr<T>, T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    match tuple {
+        Collection::Collection(exprs) => {
+            Collection::Collection(fold_all(exprs, |expr| folder.fold_expr(expr)))
+        }
+        Collection::WithSplat(tuple) => {
+            let left = fold_all(tuple.left, |expr| folder.fold_expr(expr));
+            let splat = fold_box(folder, *tuple.splat);
+            Collection::WithSplat(WithSplat {
+                left,
+                splat,
+                right: fold_all(tuple.right, |expr| folder.fold_expr(expr)),
+            })
+        }
+    }
+}
+pub fn walk_unary<T, F>(folder: &mut F, unary: Unary<T>) -> Unary<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    Unary {
+        kind: unary.kind,
+        expr: fold_box(folder, *unary.expr),
+    }
+}
+pub fn walk_binary<T, F>(folder: &mut F, binary: Binary<T>) -> Binary<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let left = fold_box(folder, *binary.left);
+    Binary {
+        kind: binary.kind,
+        left,
+        right: fold_box(folder, *binary.right),
+    }
+}
+pub fn walk_place<T, F>(folder: &mut F, place: PlaceExpr<T>) -> PlaceExpr<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    match place {
+        PlaceExpr::Var(name, span) => {
+            let (name, span) = folder.fold_var_expr(name, span);
+            PlaceExpr::Var(name, span)
+        }
+        PlaceExpr::FieldAccess(field_access) => {
+            PlaceExpr::FieldAccess(folder.fold_field_access(field_access))
+        }
+        PlaceExpr::Index(index) => PlaceExpr::Index(folder.fold_index(index)),
+        PlaceExpr::Slice(slice) => PlaceExpr::Slice(folder.fold_slice(slice)),
+        PlaceExpr::Deref(expr) => PlaceExpr::Deref(fold_box(folder, *expr)),
+        PlaceExpr::Len(expr) => PlaceExpr::Len(fold_box(folder, *expr)),
+    }
+}
+pub fn walk_field_access<T, F>(folder: &mut F, field_access: FieldAccess<T>) -> FieldAccess<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    FieldAccess {
+        expr: fold_box(folder, *field_access.expr),
+        name: field_access.name,
+    }
+}
+pub fn walk_index<T, F>(folder: &mut F, index: Index<T>) -> Index<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let expr = fold_box(folder, *index.expr);
+    Index {
+        expr,
+        index: fold_box(folder, *index.index),
+    }
+}
+pub fn walk_slice<T, F>(folder: &mut F, slice: Slice<T>) -> Slice<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let expr = fold_box(folder, *slice.expr);
+    Slice {
+        expr,
+        range: folder.fold_range(slice.range),
+    }
+}
+pub fn walk_call<T, F>(folder: &mut F, call: Call<T>) -> Call<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let expr = fold_box(folder, *call.expr);
+    Call {
+        expr,
+        arg: folder.fold_arg(call.arg),
+    }
+}
+pub fn walk_arg<T, F>(folder: &mut F, arg: Arg<T>) -> Arg<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    match arg {
+        Arg::Unit => Arg::Unit,
+        Arg::Splat(expr) => Arg::Splat(fold_box(folder, *expr)),
+        Arg::Record(record) => Arg::Record(folder.fold_record(record)),
+        Arg::Tuple(tuple) => Arg::Tuple(folder.fold_tuple(tuple)),
+    }
+}
+pub fn walk_control_flow<T, F>(folder: &mut F, control_flow: ControlFlow<T>) -> ControlFlow<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    match control_flow {
+        ControlFlow::Block(block) => ControlFlow::Block(folder.fold_block(block)),
+        ControlFlow::If(if_expr) => ControlFlow::If(folder.fold_if(if_expr)),
+        ControlFlow::For(for_expr) => ControlFlow::For(folder.fold_for(for_expr)),
+        ControlFlow::While(while_expr) => ControlFlow::While(folder.fold_while(while_expr)),
+        ControlFlow::Loop(block) => ControlFlow::Loop(folder.fold_block(block)),
+        ControlFlow::Match(match_expr) => ControlFlow::Match(folder.fold_match(match_expr)),
+    }
+}
+pub fn walk_block<T, F>(folder: &mut F, block: Block<T>) -> Block<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let statement = fold_all(block.statement, |statement| {
+        folder.fold_statement(statement)
+    });
+    Block {
+        statement,
+        expr: block.expr.map(|expr| fold_box(folder, *expr)),
+        span: folder.fold_span(block.span),
+    }
+}
+pub fn walk_if<T, F>(folder: &mut F, if_expr: If<T>) -> If<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let condition = fold_box(folder, *if_expr.condition);
+    let body = folder.fold_block(if_expr.body);
+    If {
+        condition,
+        body,
+        else_part: if_expr
+            .else_part
+            .map(|else_part| Box::new(folder.fold_control_flow(*else_part))),
+    }
+}
+pub fn walk_for<T, F>(folder: &mut F, for_expr: For<T>) -> For<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let expr = fold_box(folder, *for_expr.expr);
+    let pattern = folder.fold_pattern(for_expr.pattern);
+    For {
+        pattern,
+        expr,
+        body: folder.fold_block(for_expr.body),
+    }
+}
+pub fn walk_while<T, F>(folder: &mut F, while_expr: While<T>) -> While<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let condition = fold_box(folder, *while_expr.condition);
+    While {
+        condition,
+        body: folder.fold_block(while_expr.body),
+    }
+}
+pub fn walk_match<T, F>(folder: &mut F, match_expr: Match<T>) -> Match<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let expr = fold_box(folder, *match_expr.expr);
+    Match {
+        expr,
+        arm: fold_all(match_expr.arm, |arm| folder.fold_match_arm(arm)),
+    }
+}
+pub fn walk_match_arm<T, F>(folder: &mut F, arm: MatchArm<T>) -> MatchArm<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let pattern = folder.fold_pattern(arm.pattern);
+    MatchArm {
+        pattern,
+        expr: folder.fold_expr(arm.expr),
+    }
+}
+pub fn walk_fun<T, F>(folder: &mut F, fun: Fun<T>) -> Fun<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let param = folder.fold_pattern(fun.param);
+    let ret = fun.ret.map(|ret| folder.fold_type_expr(ret));
+    Fun {
+        param,
+        ret,
+        body: fold_box(folder, *fun.body),
+    }
+}
+pub fn walk_jump<T, F>(folder: &mut F, jump: Jump<T>) -> Jump<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    match jump {
+        Jump::Break(expr) => Jump::Break(expr.map(|expr| fold_box(folder, *expr))),
+        Jump::Continue => Jump::Continue,
+        Jump::Return(expr) => Jump::Return(expr.map(|expr| fold_box(folder, *expr))),
+    }
+}
+pub fn walk_annotated<T, F>(folder: &mut F, annotated: Annotated<T>) -> Annotated<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let expr = fold_box(folder, *annotated.expr);
+    Annotated {
+        expr,
+        annotation: folder.fold_type_expr(annotated.annotation),
+    }
+}
+pub fn walk_pattern<T, F>(folder: &mut F, pattern: Pattern<T>) -> Pattern<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let kind = match pattern.pattern {
+        kind @ (PatternKind::True
+        | PatternKind::False
+        | PatternKind::UInt(_)
+        | PatternKind::Int(_)
+        | PatternKind::Discard) => kind,
+        PatternKind::Var(var) => PatternKind::Var(folder.fold_var(var)),
+        PatternKind::Record(record) => PatternKind::Record(folder.fold_record_pattern(record)),
+        PatternKind::Tuple(list) => PatternKind::Tuple(folder.fold_list_pattern(list)),
+        PatternKind::Param(param) => PatternKind::Param(fold_all(param, |typed_var| {
+            folder.fold_typed_var(typed_var)
+        })),
+        PatternKind::Array(list) => PatternKind::Array(folder.fold_list_pattern(list)),
+        PatternKind::Tag(tagged) => PatternKind::Tag(folder.fold_tagged_pattern(tagged)),
+        PatternKind::Ref(pattern) => PatternKind::Ref(Box::new(folder.fold_pattern(*pattern))),
+        PatternKind::Annotated(annotated) => {
+            PatternKind::Annotated(folder.fold_annotated_pattern(annotated))
+        }
+    };
+    Pattern {
+        pattern: kind,
+        ty: pattern.ty,
+        span: folder.fold_span(pattern.span),
+    }
+}
+pub fn walk_typed_var<T, F>(folder: &mut F, typed_var: TypedVar<T>) -> TypedVar<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let var = folder.fold_var(typed_var.var);
+    TypedVar {
+        var,
+        annotation: typed_var
+            .annotation
+            .map(|annotation| folder.fold_type_expr(annotation)),
+        ty: typed_var.ty,
+    }
+}
+pub fn walk_record_pattern<T, F>(folder: &mut F, record: RecordPattern<T>) -> RecordPattern<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let fields = record
+        .fields
+        .into_iter()
+        .map(|(name, pattern)| (name, folder.fold_pattern(pattern)))
+        .collect();
+    RecordPattern {
+        fields,
+        rest: record.rest.map(|rest| Box::new(folder.fold_pattern(*rest))),
+    }
+}
+pub fn walk_list_pattern<T, F>(folder: &mut F, list: ListPattern<T>) -> ListPattern<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    match list {
+        ListPattern::List(list) => {
+            ListPattern::List(fold_all(list, |pattern| folder.fold_pattern(pattern)))
+        }
+        ListPattern::ListWithRest(list) => {
+            let left = fold_all(list.left, |pattern| folder.fold_pattern(pattern));
+            let rest = Box::new(folder.fold_pattern(*list.rest));
+            ListPattern::ListWithRest(ListWithRest {
+                left,
+                rest,
+                right: fold_all(list.right, |pattern| folder.fold_pattern(pattern)),
+            })
+        }
+    }
+}
+pub fn walk_tagged_pattern<T, F>(folder: &mut F, tagged: TaggedPattern<T>) -> TaggedPattern<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    TaggedPattern {
+        tag: tagged.tag,
+        pattern: tagged
+            .pattern
+            .map(|pattern| Box::new(folder.fold_pattern(*pattern))),
+    }
+}
+pub fn walk_annotated_pattern<T, F>(
+    folder: &mut F,
+    annotated: AnnotatedPattern<T>,
+) -> AnnotatedPattern<T>
+where
+    T: PrettyPrintType,
+    F: Fold<T> + ?Sized,
+{
+    let pattern = Box::new(folder.fold_pattern(*annotated.pattern));
+    AnnotatedPattern {
+        pattern,
+        annotation: folder.fold_type_expr(annotated.annotation),
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::{
+        expr::{Expr, ExprKind, PlaceExpr, Unary, UnaryType},
+        fold::Fold,
+        Atom, Span,
+    };
+
+    struct Rename;
+    impl Fold<()> for Rename {
+        fn fold_var_expr(&mut self, name: Atom, span: Span) -> (Atom, Span) {
+            (Atom::from(format!("{name}_renamed")), span)
+        }
+    }
+    fn not_var(name: &str) -> Expr<()> {
+        ExprKind::Unary(Unary {
+            kind: UnaryType::Not,
+            expr: Box::new(
+                ExprKind::Place(PlaceExpr::Var(Atom::from(name), Span::default())).into_untyped(),
+            ),
+        })
+        .into_untyped()
+    }
+    #[test]
+    fn rename() {
+        assert_eq!(Rename.fold_expr(not_var("foo")), not_var("foo_renamed"));
+    }
+}
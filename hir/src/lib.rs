@@ -5,10 +5,17 @@
 use pretty_print::PrettyPrintTree;
 use std::{collections::HashSet, fmt::Debug, hash::Hash};
 
+pub mod arena;
 pub mod expr;
+pub mod fold;
 pub mod pattern;
 pub mod pretty_print;
+pub mod resolve;
+pub mod span;
 pub mod statement;
+pub mod ty;
+pub mod visit;
+pub mod visit_mut;
 
 #[doc(hidden)]
 pub mod hir_string_cache {
@@ -16,6 +23,7 @@ pub mod hir_string_cache {
 }
 
 pub use hir_string_cache::Atom;
+pub use span::{FileId, Span};
 
 pub trait PrettyPrintType {
     const TYPED: bool;
@@ -37,31 +45,110 @@ impl PrettyPrintFunScheme for () {
         vec![].into()
     }
 }
-pub trait TraverseType {
+/// A type encountered by [`TraverseType::traverse_type`]. The scheme of a
+/// function declaration is given before its function and `EndScheme` after
+/// it, so passes can scope their state to the scheme's generic variables
+pub enum TypeSite<'a, T: PrettyPrintType> {
+    Type(&'a mut T),
+    Scheme(&'a mut T::FunScheme),
+    EndScheme,
+}
+/// Conversion used by [`TraverseType::map_types`], implemented for a pair of
+/// closures mapping types and function schemes respectively
+pub trait MapTypes<T: PrettyPrintType, U: PrettyPrintType> {
+    fn map_type(&mut self, ty: T) -> U;
+    fn map_scheme(&mut self, scheme: T::FunScheme) -> U::FunScheme;
+}
+impl<T, U, F, G> MapTypes<T, U> for (F, G)
+where
+    T: PrettyPrintType,
+    U: PrettyPrintType,
+    F: FnMut(T) -> U,
+    G: FnMut(T::FunScheme) -> U::FunScheme,
+{
+    fn map_type(&mut self, ty: T) -> U {
+        (self.0)(ty)
+    }
+    fn map_scheme(&mut self, scheme: T::FunScheme) -> U::FunScheme {
+        (self.1)(scheme)
+    }
+}
+pub trait TraverseType: Sized {
     type Type: PrettyPrintType;
+    type Mapped<U: PrettyPrintType>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E>;
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U>;
+    /// Drops every type, turning a typed tree into an untyped one
+    fn erase_types(self) -> Self::Mapped<()> {
+        self.map_types(&mut (|_| (), |_| ()))
+    }
 }
 impl<T: TraverseType> TraverseType for Option<T> {
     type Type = T::Type;
+    type Mapped<U: PrettyPrintType> = Option<T::Mapped<U>>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        match self {
-            Some(traverse) => traverse.traverse_type(data, for_type, for_scheme)?,
-            None => (),
+        if let Some(traverse) = self {
+            traverse.traverse_type(f)?
         }
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        self.map(|traverse| traverse.map_types(map))
+    }
+}
+impl<T: TraverseType> TraverseType for Box<T> {
+    type Type = T::Type;
+    type Mapped<U: PrettyPrintType> = Box<T::Mapped<U>>;
+
+    fn traverse_type<E>(
+        &mut self,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        (**self).traverse_type(f)
+    }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Box::new((*self).map_types(map))
+    }
+}
+impl<T: TraverseType> TraverseType for Box<[T]> {
+    type Type = T::Type;
+    type Mapped<U: PrettyPrintType> = Box<[T::Mapped<U>]>;
+
+    fn traverse_type<E>(
+        &mut self,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        for traverse in self.iter_mut() {
+            traverse.traverse_type(f)?;
+        }
+        Ok(())
+    }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Vec::from(self)
+            .into_iter()
+            .map(|traverse| traverse.map_types(map))
+            .collect()
+    }
 }
 fn all_unique<I>(iter: I) -> bool
 where
@@ -79,3 +166,158 @@ where
         })
         .is_some()
 }
+#[cfg(test)]
+mod test {
+    use crate::{
+        expr::{Expr, ExprKind, Fun, PlaceExpr},
+        pattern::{Pattern, PatternKind, TypedVar, Var},
+        pretty_print::PrettyPrintTree,
+        statement::{FunDeclare, Statement, Visibility},
+        Atom, PrettyPrintFunScheme, PrettyPrintType, Span, TraverseType, TypeSite,
+    };
+
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    struct Ty(u32);
+    impl PrettyPrintType for Ty {
+        const TYPED: bool = true;
+        type FunScheme = Scheme;
+        fn to_pretty_print(&self) -> Option<Box<dyn PrettyPrintTree>> {
+            None
+        }
+    }
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    struct Scheme(u32);
+    impl PrettyPrintFunScheme for Scheme {
+        fn to_pretty_print_generics(&self) -> Box<[Box<dyn PrettyPrintTree>]> {
+            vec![].into()
+        }
+    }
+    fn var<T: PrettyPrintType>(name: &str, ty: T) -> Expr<T> {
+        Expr {
+            expr: ExprKind::Place(PlaceExpr::Var(Atom::from(name), Span::default())),
+            ty,
+            span: Span::default(),
+        }
+    }
+    // `id(x) => x; id`, with its types given in the order they're written
+    fn program<T: PrettyPrintType>(
+        scheme: T::FunScheme,
+        mut ty: impl FnMut() -> T,
+    ) -> Box<[Statement<T>]> {
+        let param = TypedVar {
+            var: Var {
+                ident: Atom::from("x"),
+                mutable: false,
+                bind_to_ref: false,
+                span: Span::default(),
+            },
+            annotation: None,
+            ty: ty(),
+        };
+        let param = Pattern {
+            pattern: PatternKind::Param(vec![param].into()),
+            ty: ty(),
+            span: Span::default(),
+        };
+        let body = var("x", ty());
+        vec![
+            Statement::FunDeclare(FunDeclare {
+                visibility: Visibility::Private,
+                ident: Atom::from("id"),
+                span: Span::default(),
+                given: vec![].into(),
+                generics: vec![].into(),
+                fun: Fun {
+                    param,
+                    ret: None,
+                    body: Box::new(body),
+                },
+                ty: scheme,
+            }),
+            Statement::Expr(var("id", ty())),
+        ]
+        .into()
+    }
+    fn numbered() -> Box<[Statement<Ty>]> {
+        let mut count = 0;
+        program(Scheme(0), || {
+            count += 1;
+            Ty(count)
+        })
+    }
+    fn sites(statements: &mut Box<[Statement<Ty>]>) -> Vec<String> {
+        let mut sites = Vec::new();
+        statements
+            .traverse_type(&mut |site| {
+                sites.push(match site {
+                    TypeSite::Type(ty) => format!("type {}", ty.0),
+                    TypeSite::Scheme(scheme) => format!("scheme {}", scheme.0),
+                    TypeSite::EndScheme => "end".to_string(),
+                });
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        sites
+    }
+    #[test]
+    fn type_sites() {
+        assert_eq!(
+            sites(&mut numbered()),
+            ["scheme 0", "type 2", "type 1", "type 3", "end", "type 4"],
+        );
+    }
+    #[test]
+    fn traverse_mut() {
+        let mut statements = numbered();
+        statements
+            .traverse_type(&mut |site| {
+                match site {
+                    TypeSite::Type(ty) => ty.0 *= 10,
+                    TypeSite::Scheme(scheme) => scheme.0 = 5,
+                    TypeSite::EndScheme => (),
+                }
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        assert_eq!(
+            sites(&mut statements),
+            ["scheme 5", "type 20", "type 10", "type 30", "end", "type 40"],
+        );
+    }
+    #[test]
+    fn traverse_error() {
+        let mut visited = 0;
+        let result = numbered().traverse_type(&mut |site| {
+            visited += 1;
+            match site {
+                TypeSite::Type(Ty(3)) => Err("body"),
+                _ => Ok(()),
+            }
+        });
+        assert_eq!(result, Err("body"));
+        // neither the end of the scheme nor the last statement is reached
+        assert_eq!(visited, 4);
+    }
+    #[test]
+    fn map_types() {
+        let mut mapped = Vec::new();
+        let doubled = numbered().map_types(&mut (
+            |Ty(ty)| {
+                mapped.push(ty);
+                Ty(ty * 2)
+            },
+            |Scheme(scheme)| Scheme(scheme + 1),
+        ));
+        assert_eq!(mapped, [2, 1, 3, 4]);
+        let mut count = 0;
+        let expected = program(Scheme(1), || {
+            count += 2;
+            Ty(count)
+        });
+        assert_eq!(doubled, expected);
+    }
+    #[test]
+    fn erase_types() {
+        assert_eq!(numbered().erase_types(), program((), || ()));
+    }
+}
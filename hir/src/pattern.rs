@@ -1,6 +1,7 @@
 use crate::{
     pretty_print::{bracket, line, postfix, prefix, sequence, PrettyPrint, PrettyPrintTree},
-    Atom, PrettyPrintType, TraverseType,
+    ty::TypeExpr,
+    Atom, MapTypes, PrettyPrintType, Span, TraverseType, TypeSite,
 };
 use std::{
     collections::HashMap,
@@ -12,25 +13,38 @@ use std::{
 pub struct Pattern<T> {
     pub pattern: PatternKind<T>,
     pub ty: T,
+    pub span: Span,
 }
 impl<T> Pattern<T> {
     pub fn field_name(&self) -> Option<Atom> {
         self.pattern.field_name()
     }
+    pub fn is_refutable(&self) -> bool {
+        self.pattern.is_refutable()
+    }
 }
 impl<T: PrettyPrintType> TraverseType for Pattern<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Pattern<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut T, &U) -> Result<(), E>,
-        for_scheme: fn(&mut T::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        for_type(&mut self.ty, data)?;
-        self.pattern.traverse_type(data, for_type, for_scheme)?;
+        f(TypeSite::Type(&mut self.ty))?;
+        self.pattern.traverse_type(f)?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Pattern {
+            ty: map.map_type(self.ty),
+            pattern: self.pattern.map_types(map),
+            span: self.span,
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Pattern<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -55,15 +69,15 @@ pub enum PatternKind<T> {
     Array(ListPattern<T>),
     Tag(TaggedPattern<T>),
     Ref(Box<Pattern<T>>),
+    Annotated(AnnotatedPattern<T>),
 }
 impl<T: PrettyPrintType> TraverseType for PatternKind<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = PatternKind<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut T, &U) -> Result<(), E>,
-        for_scheme: fn(&mut T::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         match self {
             PatternKind::True => (),
@@ -72,27 +86,71 @@ impl<T: PrettyPrintType> TraverseType for PatternKind<T> {
             PatternKind::Int(_) => (),
             PatternKind::Discard => (),
             PatternKind::Var(_) => (),
-            PatternKind::Record(record) => record.traverse_type(data, for_type, for_scheme)?,
-            PatternKind::Tuple(tuple) => tuple.traverse_type(data, for_type, for_scheme)?,
+            PatternKind::Record(record) => record.traverse_type(f)?,
+            PatternKind::Tuple(tuple) => tuple.traverse_type(f)?,
             PatternKind::Param(param) => {
                 for var in param.iter_mut() {
-                    var.traverse_type(data,  for_type,  for_scheme)?
+                    var.traverse_type(f)?
                 }
             }
-            PatternKind::Array(array) => array.traverse_type(data, for_type, for_scheme)?,
-            PatternKind::Tag(tag) => tag.traverse_type(data, for_type, for_scheme)?,
-            PatternKind::Ref(reference) => reference.traverse_type(data, for_type, for_scheme)?,
+            PatternKind::Array(array) => array.traverse_type(f)?,
+            PatternKind::Tag(tag) => tag.traverse_type(f)?,
+            PatternKind::Ref(reference) => reference.traverse_type(f)?,
+            PatternKind::Annotated(annotated) => annotated.pattern.traverse_type(f)?,
         }
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        match self {
+            PatternKind::True => PatternKind::True,
+            PatternKind::False => PatternKind::False,
+            PatternKind::UInt(value) => PatternKind::UInt(value),
+            PatternKind::Int(value) => PatternKind::Int(value),
+            PatternKind::Discard => PatternKind::Discard,
+            PatternKind::Var(var) => PatternKind::Var(var),
+            PatternKind::Record(record) => PatternKind::Record(record.map_types(map)),
+            PatternKind::Tuple(tuple) => PatternKind::Tuple(tuple.map_types(map)),
+            PatternKind::Param(param) => PatternKind::Param(param.map_types(map)),
+            PatternKind::Array(array) => PatternKind::Array(array.map_types(map)),
+            PatternKind::Tag(tag) => PatternKind::Tag(tag.map_types(map)),
+            PatternKind::Ref(reference) => PatternKind::Ref(reference.map_types(map)),
+            PatternKind::Annotated(annotated) => PatternKind::Annotated(AnnotatedPattern {
+                pattern: annotated.pattern.map_types(map),
+                annotation: annotated.annotation,
+            }),
+        }
+    }
 }
 impl<T> PatternKind<T> {
     pub fn field_name(&self) -> Option<Atom> {
         match self {
             Self::Var(var) => Some(var.ident.clone()),
+            Self::Annotated(annotated) => annotated.pattern.field_name(),
             _ => None,
         }
     }
+    pub fn is_refutable(&self) -> bool {
+        match self {
+            Self::True | Self::False | Self::UInt(_) | Self::Int(_) | Self::Tag(_) => true,
+            Self::Discard | Self::Var(_) | Self::Param(_) => false,
+            Self::Record(record) => {
+                record.fields.values().any(Pattern::is_refutable)
+                    || record.rest.iter().any(|rest| rest.is_refutable())
+            }
+            Self::Tuple(tuple) => tuple.is_refutable(),
+            Self::Array(ListPattern::ListWithRest(list))
+                if list.left.is_empty() && list.right.is_empty() =>
+            {
+                list.rest.is_refutable()
+            }
+            Self::Array(_) => true,
+            Self::Ref(pattern) => pattern.is_refutable(),
+            Self::Annotated(annotated) => annotated.pattern.is_refutable(),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for PatternKind<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree>
@@ -133,6 +191,11 @@ impl<T: PrettyPrintType> PrettyPrint for PatternKind<T> {
             Self::Array(arr) => bracket("(", ")", arr.to_pretty_print()),
             Self::Tag(tag) => tag.to_pretty_print(),
             Self::Ref(pattern) => line([Box::new("&".to_string()), pattern.to_pretty_print()]),
+            Self::Annotated(annotated) => line([
+                annotated.pattern.to_pretty_print(),
+                Box::new(" : ".to_string()),
+                annotated.annotation.to_pretty_print(),
+            ]),
         }
     }
 }
@@ -141,6 +204,7 @@ impl PatternKind<()> {
         Pattern {
             pattern: self,
             ty: (),
+            span: Span::default(),
         }
     }
 }
@@ -149,10 +213,15 @@ pub struct Var {
     pub ident: Atom,
     pub mutable: bool,
     pub bind_to_ref: bool,
+    pub span: Span,
 }
 impl Var {
     pub fn into_untyped(self) -> TypedVar<()> {
-        TypedVar { var: self, ty: () }
+        TypedVar {
+            var: self,
+            annotation: None,
+            ty: (),
+        }
     }
 }
 impl Display for Var {
@@ -166,24 +235,37 @@ impl Display for Var {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct TypedVar<T> {
     pub var: Var,
+    pub annotation: Option<TypeExpr>,
     pub ty: T,
 }
 impl<T: PrettyPrintType> TraverseType for TypedVar<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = TypedVar<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut T, &U) -> Result<(), E>,
-        _for_scheme: fn(&mut T::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        for_type(&mut self.ty, data)?;
+        f(TypeSite::Type(&mut self.ty))?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        TypedVar {
+            var: self.var,
+            annotation: self.annotation,
+            ty: map.map_type(self.ty),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for TypedVar<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
         let mut s = self.var.to_string();
+        if let (false, Some(annotation)) = (T::TYPED, &self.annotation) {
+            s.push_str(&format!(" : {annotation}"));
+        }
         match self.ty.to_pretty_print() {
             Some(ty) => {
                 s.push_str(": ");
@@ -200,26 +282,31 @@ pub enum ListPattern<T> {
 }
 impl<T: PrettyPrintType> TraverseType for ListPattern<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = ListPattern<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(
-            &mut <Self::Type as PrettyPrintType>::FunScheme,
-            &mut U,
-        ) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         match self {
             ListPattern::List(list) => {
                 for pattern in list.iter_mut() {
-                    pattern.traverse_type(data,  for_type,  for_scheme)?;
+                    pattern.traverse_type(f)?;
                 }
             }
-            ListPattern::ListWithRest(list) => list.traverse_type(data, for_type, for_scheme)?,
+            ListPattern::ListWithRest(list) => list.traverse_type(f)?,
         }
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        match self {
+            ListPattern::List(list) => ListPattern::List(list.map_types(map)),
+            ListPattern::ListWithRest(list) => ListPattern::ListWithRest(list.map_types(map)),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for ListPattern<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -246,6 +333,18 @@ impl<T: PrettyPrintType> PrettyPrint for ListPattern<T> {
         }
     }
 }
+impl<T> ListPattern<T> {
+    pub fn is_refutable(&self) -> bool {
+        match self {
+            ListPattern::List(list) => list.iter().any(Pattern::is_refutable),
+            ListPattern::ListWithRest(list) => {
+                list.left.iter().any(Pattern::is_refutable)
+                    || list.rest.is_refutable()
+                    || list.right.iter().any(Pattern::is_refutable)
+            }
+        }
+    }
+}
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct ListWithRest<T> {
     pub left: Box<[Pattern<T>]>,
@@ -254,26 +353,31 @@ pub struct ListWithRest<T> {
 }
 impl<T: PrettyPrintType> TraverseType for ListWithRest<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = ListWithRest<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(
-            &mut <Self::Type as PrettyPrintType>::FunScheme,
-            &mut U,
-        ) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         for pattern in self.left.iter_mut() {
-            pattern.traverse_type(data,  for_type,  for_scheme)?;
+            pattern.traverse_type(f)?;
         }
-        self.rest
-            .traverse_type(data,  for_type,  for_scheme)?;
+        self.rest.traverse_type(f)?;
         for pattern in self.right.iter_mut() {
-            pattern.traverse_type(data,  for_type,  for_scheme)?;
+            pattern.traverse_type(f)?;
         }
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        ListWithRest {
+            left: self.left.map_types(map),
+            rest: self.rest.map_types(map),
+            right: self.right.map_types(map),
+        }
+    }
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct RecordPattern<T> {
@@ -282,25 +386,39 @@ pub struct RecordPattern<T> {
 }
 impl<T: PrettyPrintType> TraverseType for RecordPattern<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = RecordPattern<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(
-            &mut <Self::Type as PrettyPrintType>::FunScheme,
-            &mut U,
-        ) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         for (_, pattern) in self.fields.iter_mut() {
-            pattern.traverse_type(data, for_type,  for_scheme)?;
+            pattern.traverse_type(f)?;
         }
         self.rest
             .as_mut()
-            .map(|pattern| pattern.traverse_type(data, for_type, for_scheme))
+            .map(|pattern| pattern.traverse_type(f))
             .unwrap_or(Ok(()))?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        RecordPattern {
+            fields: self
+                .fields
+                .into_iter()
+                .map(|(name, pattern)| (name, pattern.map_types(map)))
+                .collect(),
+            rest: self.rest.map_types(map),
+        }
+    }
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AnnotatedPattern<T> {
+    pub pattern: Box<Pattern<T>>,
+    pub annotation: TypeExpr,
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct TaggedPattern<T> {
@@ -309,18 +427,26 @@ pub struct TaggedPattern<T> {
 }
 impl<T: PrettyPrintType> TraverseType for TaggedPattern<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = TaggedPattern<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(&mut <Self::Type as PrettyPrintType>::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         self.pattern
             .as_mut()
-            .map(|pattern| pattern.traverse_type(data, for_type, for_scheme))
+            .map(|pattern| pattern.traverse_type(f))
             .unwrap_or(Ok(()))
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        TaggedPattern {
+            tag: self.tag,
+            pattern: self.pattern.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for TaggedPattern<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -19,11 +19,7 @@ pub trait PrettyPrintTree {
     fn write_line(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()>;
     fn write_multiline(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()>;
     fn pretty_write(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()> {
-        let fit = match self.write_len() {
-            Some(len) => state.level * state.indent.len() + len <= state.max,
-            None => false,
-        };
-        if fit {
+        if state.fits(self.write_len()) {
             self.write_line(writer, state)?;
         } else {
             self.write_multiline(writer, state)?;
@@ -38,6 +34,7 @@ pub trait PrettyPrintTree {
                 indent,
                 max,
                 newline: true,
+                column: 0,
             },
         )?;
         Ok(())
@@ -49,12 +46,44 @@ pub struct PrettyPrintState {
     indent: &'static str,
     max: usize,
     newline: bool,
+    // width already written on the current line after the indentation
+    column: usize,
 }
 impl PrettyPrintState {
+    pub fn fits(&self, len: Option<usize>) -> bool {
+        match len {
+            Some(len) => self.level * self.indent.len() + self.column + len <= self.max,
+            None => false,
+        }
+    }
+    /// The state after `written` is written in this state
+    pub fn after(self, written: &str) -> Self {
+        match written.rfind('\n') {
+            Some(i) => Self {
+                newline: false,
+                column: (written.len() - i - 1).saturating_sub(self.level * self.indent.len()),
+                ..self
+            },
+            None => Self {
+                newline: self.newline && written.is_empty(),
+                column: self.column + written.len(),
+                ..self
+            },
+        }
+    }
+    /// The state at the start of the next line, one level deeper
+    pub fn next_line(self) -> Self {
+        Self {
+            level: self.level + 1,
+            newline: true,
+            column: 0,
+            ..self
+        }
+    }
     fn incr(&mut self) {
         self.level += 1;
     }
-    fn write_indent(&self, writer: &mut impl Write) -> io::Result<()> {
+    pub fn write_indent(&self, writer: &mut (impl Write + ?Sized)) -> io::Result<()> {
         for _ in 0..self.level {
             write!(writer, "{}", self.indent)?;
         }
@@ -157,12 +186,21 @@ where
             .into_iter()
             .zip(once(false).chain(repeat(true)));
         if self.multiline_override == Some(false) {
+            let mut column = state.column;
             for (tree, rest) in iter {
                 let state = PrettyPrintState {
                     newline: state.newline && !rest,
+                    column,
                     ..state
                 };
+                let len = tree.write_len();
                 tree.pretty_write(writer, state)?;
+                // the width of the last line of a tree that didn't fit is
+                // unknown, the rest of the line is taken as full
+                column = match len {
+                    Some(len) if state.fits(Some(len)) => column + len,
+                    _ => state.max,
+                };
             }
         } else {
             for (tree, rest) in iter {
@@ -172,6 +210,7 @@ where
                 }
                 let state = PrettyPrintState {
                     newline: state.newline || rest,
+                    column: if rest { 0 } else { state.column },
                     ..state
                 };
                 tree.pretty_write(writer, state)?;
@@ -180,6 +219,142 @@ where
         Ok(())
     }
 }
+pub enum ListItem {
+    Item(Box<dyn PrettyPrintTree>),
+    /// Empty line between items, only written when the list spans multiple
+    /// lines
+    Blank,
+    /// Comment on its own line without a separator, the list then spans
+    /// multiple lines
+    Comment(Box<dyn PrettyPrintTree>),
+    /// Comment after the previous item and its separator on the same line
+    TrailingComment(Box<dyn PrettyPrintTree>),
+}
+/// Items between brackets, written on a single line as `(a, b)` if it fits,
+/// otherwise each item is on its own line followed by the separator
+pub struct List {
+    pub open: &'static str,
+    pub close: &'static str,
+    pub separator: &'static str,
+    /// Space between the brackets and the items on a single line, as in
+    /// `{ a }`
+    pub padded: bool,
+    /// Write the separator after the last item even on a single line, as in
+    /// `(a,)`
+    pub trailing: bool,
+    pub multiline: bool,
+    pub items: Vec<ListItem>,
+}
+impl List {
+    fn items(&self) -> impl Iterator<Item = &dyn PrettyPrintTree> {
+        self.items.iter().filter_map(|item| match item {
+            ListItem::Item(item) => Some(item.as_ref()),
+            _ => None,
+        })
+    }
+    fn has_comment(&self) -> bool {
+        self.items
+            .iter()
+            .any(|item| matches!(item, ListItem::Comment(_) | ListItem::TrailingComment(_)))
+    }
+}
+impl PrettyPrintTree for List {
+    fn write_len(&self) -> Option<usize> {
+        if self.multiline || self.has_comment() {
+            return None;
+        }
+        let count = self.items().count();
+        let mut len = self.open.len() + self.close.len();
+        if count > 0 {
+            len += self.items().try_fold(0, |a, b| Some(a + b.write_len()?))?;
+            len += (count - 1) * (self.separator.len() + 1);
+            if self.trailing {
+                len += self.separator.len();
+            }
+            if self.padded {
+                len += 2;
+            }
+        }
+        Some(len)
+    }
+    fn write_line(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()> {
+        write!(writer, "{}", self.open)?;
+        let count = self.items().count();
+        let padding = if self.padded && count > 0 { " " } else { "" };
+        write!(writer, "{padding}")?;
+        let state = PrettyPrintState {
+            newline: false,
+            ..state
+        };
+        for (i, item) in self.items().enumerate() {
+            if i > 0 {
+                write!(writer, "{} ", self.separator)?;
+            }
+            item.write_line(writer, state)?;
+        }
+        if self.trailing && count > 0 {
+            write!(writer, "{}", self.separator)?;
+        }
+        write!(writer, "{padding}{}", self.close)?;
+        Ok(())
+    }
+    fn write_multiline(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()> {
+        let mut writer = writer;
+        if self.items().next().is_none() && !self.has_comment() {
+            return self.write_line(writer, state);
+        }
+        write!(writer, "{}", self.open)?;
+        let mut inner = PrettyPrintState {
+            newline: true,
+            column: 0,
+            ..state
+        };
+        inner.incr();
+        for item in &self.items {
+            match item {
+                ListItem::Item(item) => {
+                    writeln!(writer)?;
+                    inner.write_indent(&mut writer)?;
+                    item.pretty_write(writer, inner)?;
+                    write!(writer, "{}", self.separator)?;
+                }
+                ListItem::Blank => writeln!(writer)?,
+                ListItem::Comment(comment) => {
+                    writeln!(writer)?;
+                    inner.write_indent(&mut writer)?;
+                    comment.pretty_write(writer, inner)?;
+                }
+                ListItem::TrailingComment(comment) => {
+                    write!(writer, " ")?;
+                    comment.pretty_write(writer, inner)?;
+                }
+            }
+        }
+        writeln!(writer)?;
+        state.write_indent(&mut writer)?;
+        write!(writer, "{}", self.close)?;
+        Ok(())
+    }
+}
+/// Comment running until the end of the line, nothing else can be written on
+/// the same line after it
+struct Comment(String);
+impl PrettyPrintTree for Comment {
+    fn write_len(&self) -> Option<usize> {
+        None
+    }
+    fn write_line(&self, writer: &mut dyn Write, _: PrettyPrintState) -> io::Result<()> {
+        write!(writer, "{}", self.0)?;
+        Ok(())
+    }
+    fn write_multiline(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()> {
+        self.write_line(writer, state)?;
+        Ok(())
+    }
+}
+pub fn comment(comment: &str) -> Box<dyn PrettyPrintTree> {
+    Box::new(Comment(comment.to_string()))
+}
 pub fn indent(content: Box<dyn PrettyPrintTree>) -> Box<dyn PrettyPrintTree> {
     Box::new(Indent(content))
 }
@@ -212,6 +387,15 @@ pub fn line<const L: usize>(content: [Box<dyn PrettyPrintTree>; L]) -> Box<dyn P
         multiline_override: Some(false),
     })
 }
+/// Like `line` but for any number of trees
+pub fn line_sequence(
+    content: impl IntoIterator<Item = Box<dyn PrettyPrintTree>>,
+) -> Box<dyn PrettyPrintTree> {
+    Box::new(Sequence {
+        content: content.into_iter().collect::<Vec<_>>(),
+        multiline_override: Some(false),
+    })
+}
 pub fn prefix(prefix: &str, content: Box<dyn PrettyPrintTree>) -> Box<dyn PrettyPrintTree> {
     line([Box::new(prefix.to_string()), indent(content)])
 }
@@ -0,0 +1,285 @@
+use crate::{
+    expr::{Block, For, Fun, MatchArm},
+    pattern::Var,
+    statement::{FunDeclare, ImplDeclare, ModuleDeclare, NewtypeDeclare, Statement, TraitDeclare},
+    visit::{walk_block, walk_fun, walk_fun_declare, walk_match_arm, walk_module_declare, Visit},
+    Atom, FileId, PrettyPrintType, Span,
+};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+
+/// Names bound by the language itself rather than the source, these are the
+/// conversions between strings, characters, and numbers
+pub const BUILTINS: [&str; 5] = ["bytes", "from_utf8", "chars", "code", "from_code"];
+
+/// Identifies a single binding site, two bindings with the same name have
+/// different IDs
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct BindingId(u32);
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Binding {
+    pub name: Atom,
+    pub span: Span,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Unbound {
+    pub name: Atom,
+    pub span: Span,
+}
+impl Display for Unbound {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "unbound variable `{}`", self.name)?;
+        Ok(())
+    }
+}
+/// The binding sites along with the def-use table, mapping every use of a
+/// variable to where it is bound
+#[derive(Debug, Clone, Default)]
+pub struct Resolution {
+    bindings: Vec<Binding>,
+    uses: Vec<(Span, BindingId)>,
+    binding_spans: HashMap<Span, BindingId>,
+    use_spans: HashMap<Span, BindingId>,
+}
+impl Resolution {
+    pub fn binding(&self, id: BindingId) -> &Binding {
+        let BindingId(index) = id;
+        &self.bindings[index as usize]
+    }
+    pub fn bindings(&self) -> impl Iterator<Item = (BindingId, &Binding)> {
+        self.bindings
+            .iter()
+            .enumerate()
+            .map(|(index, binding)| (BindingId(index as u32), binding))
+    }
+    /// Every use of a variable in the order they appear
+    pub fn uses(&self) -> impl Iterator<Item = (Span, BindingId)> + '_ {
+        self.uses.iter().copied()
+    }
+    /// The binding the variable used at `span` refers to
+    pub fn resolve_use(&self, span: Span) -> Option<BindingId> {
+        self.use_spans.get(&span).copied()
+    }
+    /// The binding whose binding site is at `span`
+    pub fn binding_of(&self, span: Span) -> Option<BindingId> {
+        self.binding_spans.get(&span).copied()
+    }
+    pub fn uses_of(&self, id: BindingId) -> impl Iterator<Item = Span> + '_ {
+        self.uses
+            .iter()
+            .filter(move |(_, use_id)| *use_id == id)
+            .map(|(span, _)| *span)
+    }
+    /// The binding defined or used at the byte `offset` of `file`
+    pub fn binding_at(&self, file: FileId, offset: usize) -> Option<BindingId> {
+        self.uses
+            .iter()
+            .find(|(span, _)| span.contains(file, offset))
+            .map(|(_, id)| *id)
+            .or_else(|| {
+                self.bindings()
+                    .find(|(_, binding)| binding.span.contains(file, offset))
+                    .map(|(id, _)| id)
+            })
+    }
+}
+/// Maps every variable use to its binding, following the same scoping the
+/// type checker uses. All unbound variables are reported at once
+pub fn resolve<T: PrettyPrintType>(
+    statements: &[Statement<T>],
+) -> Result<Resolution, Box<[Unbound]>> {
+    let (resolution, unbound) = resolve_partial(statements);
+    if unbound.is_empty() {
+        Ok(resolution)
+    } else {
+        Err(unbound)
+    }
+}
+/// Like [`resolve`] but keeps the resolution of the bound variables even
+/// when some are unbound
+pub fn resolve_partial<T: PrettyPrintType>(
+    statements: &[Statement<T>],
+) -> (Resolution, Box<[Unbound]>) {
+    let mut resolver = Resolver {
+        resolution: Resolution::default(),
+        scopes: vec![HashMap::new()],
+        unbound: Vec::new(),
+    };
+    for statement in statements {
+        resolver.visit_statement(statement);
+    }
+    (resolver.resolution, resolver.unbound.into())
+}
+struct Resolver {
+    resolution: Resolution,
+    scopes: Vec<HashMap<Atom, BindingId>>,
+    unbound: Vec<Unbound>,
+}
+impl Resolver {
+    fn bind(&mut self, name: &Atom, span: Span) {
+        let id = BindingId(self.resolution.bindings.len() as u32);
+        self.resolution.bindings.push(Binding {
+            name: name.clone(),
+            span,
+        });
+        self.resolution.binding_spans.insert(span, id);
+        self.scopes.last_mut().unwrap().insert(name.clone(), id);
+    }
+    fn use_var(&mut self, name: &Atom, span: Span) {
+        let id = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied());
+        match id {
+            Some(id) => {
+                self.resolution.uses.push((span, id));
+                self.resolution.use_spans.insert(span, id);
+            }
+            // built-ins have no binding site to refer to
+            None if BUILTINS.contains(&name.as_ref()) => (),
+            None => self.unbound.push(Unbound {
+                name: name.clone(),
+                span,
+            }),
+        }
+    }
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        f(self);
+        self.scopes.pop();
+    }
+}
+impl<T: PrettyPrintType> Visit<T> for Resolver {
+    fn visit_fun_declare(&mut self, fun_declare: &FunDeclare<T>) {
+        // bound before the body so it can be called recursively
+        self.bind(&fun_declare.ident, fun_declare.span);
+        walk_fun_declare(self, fun_declare);
+    }
+    // the name of a newtype is also its constructor
+    fn visit_newtype_declare(&mut self, newtype_declare: &NewtypeDeclare) {
+        self.bind(&newtype_declare.ident, newtype_declare.span);
+    }
+    fn visit_trait_declare(&mut self, trait_declare: &TraitDeclare) {
+        for method in trait_declare.methods.iter() {
+            self.bind(&method.ident, method.span);
+        }
+    }
+    // methods of an impl implement those of the trait, they bind nothing
+    fn visit_impl_declare(&mut self, impl_declare: &ImplDeclare<T>) {
+        for method in impl_declare.methods.iter() {
+            self.visit_fun(&method.fun);
+        }
+    }
+    fn visit_module_declare(&mut self, module_declare: &ModuleDeclare<T>) {
+        self.scoped(|resolver| walk_module_declare(resolver, module_declare));
+        self.bind(&module_declare.ident, module_declare.span);
+    }
+    fn visit_var_expr(&mut self, name: &Atom, span: Span) {
+        self.use_var(name, span);
+    }
+    fn visit_var(&mut self, var: &Var) {
+        self.bind(&var.ident, var.span);
+    }
+    fn visit_block(&mut self, block: &Block<T>) {
+        self.scoped(|resolver| walk_block(resolver, block));
+    }
+    fn visit_for(&mut self, for_expr: &For<T>) {
+        self.visit_expr(&for_expr.expr);
+        self.scoped(|resolver| {
+            resolver.visit_pattern(&for_expr.pattern);
+            resolver.visit_block(&for_expr.body);
+        });
+    }
+    fn visit_match_arm(&mut self, arm: &MatchArm<T>) {
+        self.scoped(|resolver| walk_match_arm(resolver, arm));
+    }
+    fn visit_fun(&mut self, fun: &Fun<T>) {
+        self.scoped(|resolver| walk_fun(resolver, fun));
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::{
+        expr::{Block, ControlFlow, Expr, ExprKind, PlaceExpr},
+        pattern::{PatternKind, Var},
+        resolve::{resolve, Unbound},
+        statement::{Declare, Statement, Visibility},
+        Atom, FileId, Span,
+    };
+
+    // spans are made up, each name in the tests is given a distinct offset
+    fn span(at: usize) -> Span {
+        Span::new(FileId::default(), at, at + 1)
+    }
+    fn var(name: &str, at: usize) -> Expr<()> {
+        ExprKind::Place(PlaceExpr::Var(Atom::from(name), span(at))).into_untyped()
+    }
+    fn declare(name: &str, at: usize, expr: Expr<()>) -> Statement<()> {
+        Statement::Declare(Declare {
+            visibility: Visibility::Private,
+            pattern: PatternKind::Var(Var {
+                ident: Atom::from(name),
+                mutable: false,
+                bind_to_ref: false,
+                span: span(at),
+            })
+            .into_untyped(),
+            expr,
+            else_part: None,
+        })
+    }
+    #[test]
+    fn shadowing() {
+        // x = (); x = x; x;
+        let statements = [
+            declare("x", 0, ExprKind::Unit.into_untyped()),
+            declare("x", 1, var("x", 2)),
+            Statement::Expr(var("x", 3)),
+        ];
+        let resolution = resolve(&statements).unwrap();
+        let first = resolution.resolve_use(span(2)).unwrap();
+        let second = resolution.resolve_use(span(3)).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(resolution.binding(first).span, span(0));
+        assert_eq!(resolution.binding_of(span(0)), Some(first));
+        assert_eq!(resolution.binding_of(span(2)), None);
+        assert_eq!(resolution.binding(second).span, span(1));
+        let uses: Vec<_> = resolution.uses_of(first).collect();
+        assert_eq!(uses.len(), 1);
+        assert_eq!(uses[0], span(2));
+        assert_eq!(resolution.binding_at(FileId::default(), 1), Some(second));
+    }
+    #[test]
+    fn unbound() {
+        // { y = (); y; }; y; z;
+        let block = Block {
+            statement: vec![declare("y", 0, ExprKind::Unit.into_untyped())].into(),
+            expr: Some(Box::new(var("y", 1))),
+            span: Span::default(),
+        };
+        let statements = [
+            Statement::Expr(ExprKind::ControlFlow(ControlFlow::Block(block)).into_untyped()),
+            Statement::Expr(var("y", 2)),
+            Statement::Expr(var("z", 3)),
+        ];
+        let unbound = resolve(&statements).unwrap_err();
+        assert_eq!(
+            &unbound[..],
+            [
+                Unbound {
+                    name: Atom::from("y"),
+                    span: span(2),
+                },
+                Unbound {
+                    name: Atom::from("z"),
+                    span: span(3),
+                },
+            ],
+        );
+        assert_eq!(unbound[0].span, span(2));
+    }
+}
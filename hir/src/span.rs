@@ -0,0 +1,67 @@
+use crate::{fold::Fold, PrettyPrintType};
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// Identifies a source, sources loaded at the same time have distinct IDs.
+/// The default ID is for code that is parsed on its own
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+impl FileId {
+    /// An ID that no other source has
+    pub fn fresh() -> Self {
+        static NEXT: AtomicU32 = AtomicU32::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+/// Location of a node as a byte range within its source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    file: FileId,
+    start: usize,
+    end: usize,
+}
+impl Span {
+    pub fn new(file: FileId, start: usize, end: usize) -> Self {
+        Self { file, start, end }
+    }
+    pub fn file(self) -> FileId {
+        self.file
+    }
+    pub fn start(self) -> usize {
+        self.start
+    }
+    pub fn end(self) -> usize {
+        self.end
+    }
+    pub fn range(self) -> Range<usize> {
+        self.start..self.end
+    }
+    /// Whether the byte at `offset` of `file` is within the span
+    pub fn contains(self, file: FileId, offset: usize) -> bool {
+        self.file == file && self.start <= offset && offset < self.end
+    }
+}
+/// Folder resetting every span, so trees parsed from different places can be
+/// compared
+pub struct EraseSpans;
+impl<T: PrettyPrintType> Fold<T> for EraseSpans {
+    fn fold_span(&mut self, _: Span) -> Span {
+        Span::default()
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::span::{FileId, Span};
+
+    #[test]
+    fn contains() {
+        let file = FileId::fresh();
+        let span = Span::new(file, 4, 7);
+        assert!(span.contains(file, 4));
+        assert!(!span.contains(file, 7));
+        assert!(!span.contains(FileId::default(), 5));
+        assert_ne!(span, Span::new(FileId::default(), 4, 7));
+    }
+}
@@ -1,99 +1,189 @@
 use crate::{
-    expr::{Expr, Fun},
+    expr::{Block, Expr, Fun},
     pattern::Pattern,
     pretty_print::{
         bracket, line, multiline_sequence, postfix, sequence, PrettyPrint, PrettyPrintTree,
     },
-    Atom, PrettyPrintFunScheme, PrettyPrintType, TraverseType,
+    ty::{TraitBound, TypeExpr},
+    Atom, MapTypes, PrettyPrintFunScheme, PrettyPrintType, Span, TraverseType, TypeSite,
 };
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement<T: PrettyPrintType> {
     Declare(Declare<T>),
     FunDeclare(FunDeclare<T>),
+    AliasDeclare(AliasDeclare),
+    NewtypeDeclare(NewtypeDeclare),
+    TraitDeclare(TraitDeclare),
+    ImplDeclare(ImplDeclare<T>),
+    ModuleDeclare(ModuleDeclare<T>),
     Expr(Expr<T>),
 }
 impl<T: PrettyPrintType> TraverseType for Statement<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Statement<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut T, &U) -> Result<(), E>,
-        for_scheme: fn(&mut T::FunScheme, &mut U) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
         match self {
-            Statement::Declare(declare) => declare.traverse_type(data, for_type, for_scheme)?,
-            Statement::FunDeclare(fun) => fun.traverse_type(data, for_type, for_scheme)?,
-            Statement::Expr(expr) => expr.traverse_type(data, for_type, for_scheme)?,
+            Statement::Declare(declare) => declare.traverse_type(f)?,
+            Statement::FunDeclare(fun) => fun.traverse_type(f)?,
+            Statement::AliasDeclare(_)
+            | Statement::NewtypeDeclare(_)
+            | Statement::TraitDeclare(_) => (),
+            Statement::ImplDeclare(impl_declare) => impl_declare.traverse_type(f)?,
+            Statement::ModuleDeclare(module) => {
+                module.traverse_type(f)?;
+            }
+            Statement::Expr(expr) => expr.traverse_type(f)?,
         }
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        match self {
+            Statement::Declare(declare) => Statement::Declare(declare.map_types(map)),
+            Statement::FunDeclare(fun) => Statement::FunDeclare(fun.map_types(map)),
+            Statement::AliasDeclare(alias) => Statement::AliasDeclare(alias),
+            Statement::NewtypeDeclare(newtype) => Statement::NewtypeDeclare(newtype),
+            Statement::TraitDeclare(trait_declare) => Statement::TraitDeclare(trait_declare),
+            Statement::ImplDeclare(impl_declare) => {
+                Statement::ImplDeclare(impl_declare.map_types(map))
+            }
+            Statement::ModuleDeclare(module) => Statement::ModuleDeclare(module.map_types(map)),
+            Statement::Expr(expr) => Statement::Expr(expr.map_types(map)),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Statement<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
         match self {
             Statement::Declare(declare) => declare.to_pretty_print(),
             Statement::FunDeclare(fun_declare) => fun_declare.to_pretty_print(),
+            Statement::AliasDeclare(alias) => alias.to_pretty_print(),
+            Statement::NewtypeDeclare(newtype) => newtype.to_pretty_print(),
+            Statement::TraitDeclare(trait_declare) => trait_declare.to_pretty_print(),
+            Statement::ImplDeclare(impl_declare) => impl_declare.to_pretty_print(),
+            Statement::ModuleDeclare(module) => module.to_pretty_print(),
             Statement::Expr(expr) => expr.to_pretty_print(),
         }
     }
 }
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub enum Visibility {
+    #[default]
+    Private,
+    Public,
+    /// `pub(path.to.module)`, visible only within the module at the path
+    /// relative to the root module
+    Restricted(Box<[Atom]>),
+}
+impl Visibility {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        let visibility = match self {
+            Self::Private => String::new(),
+            Self::Public => "pub ".to_string(),
+            Self::Restricted(path) => {
+                let path: Vec<&str> = path.iter().map(Atom::as_ref).collect();
+                format!("pub({}) ", path.join("."))
+            }
+        };
+        Box::new(visibility)
+    }
+}
 #[derive(Debug, PartialEq, Clone)]
 pub struct Declare<T: PrettyPrintType> {
+    pub visibility: Visibility,
     pub pattern: Pattern<T>,
     pub expr: Expr<T>,
+    pub else_part: Option<Block<T>>,
 }
 impl<T: PrettyPrintType> TraverseType for Declare<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = Declare<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(
-            &mut <Self::Type as PrettyPrintType>::FunScheme,
-            &mut U,
-        ) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        self.pattern
-            .traverse_type(data, for_type,  for_scheme)?;
-        self.expr.traverse_type(data, for_type, for_scheme)?;
+        self.pattern.traverse_type(f)?;
+        self.expr.traverse_type(f)?;
+        if let Some(else_part) = &mut self.else_part {
+            else_part.traverse_type(f)?;
+        }
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        Declare {
+            visibility: self.visibility,
+            pattern: self.pattern.map_types(map),
+            expr: self.expr.map_types(map),
+            else_part: self.else_part.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for Declare<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
-        line([
+        let declare = line([
+            self.visibility.to_pretty_print(),
             self.pattern.to_pretty_print(),
             Box::new(" = ".to_string()),
             self.expr.to_pretty_print(),
-        ])
+        ]);
+        match &self.else_part {
+            Some(else_part) => line([
+                declare,
+                Box::new(" else ".to_string()),
+                else_part.to_pretty_print(),
+            ]),
+            None => declare,
+        }
     }
 }
 #[derive(Debug, PartialEq, Clone)]
 pub struct FunDeclare<T: PrettyPrintType> {
+    pub visibility: Visibility,
     pub ident: Atom,
+    pub span: Span,
+    pub given: Box<[TraitBound]>,
+    pub generics: Box<[Atom]>,
     pub fun: Fun<T>,
     pub ty: T::FunScheme,
 }
 impl<T: PrettyPrintType> TraverseType for FunDeclare<T> {
     type Type = T;
+    type Mapped<U: PrettyPrintType> = FunDeclare<U>;
 
-    fn traverse_type<U: Clone, E>(
+    fn traverse_type<E>(
         &mut self,
-        data: &U,
-        for_type: fn(&mut Self::Type, &U) -> Result<(), E>,
-        for_scheme: fn(
-            &mut <Self::Type as PrettyPrintType>::FunScheme,
-            &mut U,
-        ) -> Result<(), E>,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
     ) -> Result<(), E> {
-        let mut data = data.clone();
-        for_scheme(&mut self.ty, &mut data)?;
-        self.fun.traverse_type(&data, for_type, for_scheme)?;
+        f(TypeSite::Scheme(&mut self.ty))?;
+        self.fun.traverse_type(f)?;
+        f(TypeSite::EndScheme)?;
         Ok(())
     }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        FunDeclare {
+            visibility: self.visibility,
+            ident: self.ident,
+            span: self.span,
+            given: self.given,
+            generics: self.generics,
+            ty: map.map_scheme(self.ty),
+            fun: self.fun.map_types(map),
+        }
+    }
 }
 impl<T: PrettyPrintType> PrettyPrint for FunDeclare<T> {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
@@ -106,6 +196,15 @@ impl<T: PrettyPrintType> PrettyPrint for FunDeclare<T> {
                 Box::new(" => ".to_string()),
                 self.fun.body.to_pretty_print(),
             ])
+        } else if let Some(ret) = &self.fun.ret {
+            line([
+                Box::new(self.ident.to_string()),
+                self.fun.param.to_pretty_print(),
+                Box::new(" -> ".to_string()),
+                ret.to_pretty_print(),
+                Box::new(" => ".to_string()),
+                self.fun.body.to_pretty_print(),
+            ])
         } else {
             line([
                 Box::new(self.ident.to_string()),
@@ -114,8 +213,15 @@ impl<T: PrettyPrintType> PrettyPrint for FunDeclare<T> {
                 self.fun.body.to_pretty_print(),
             ])
         };
-        let generics = self.ty.to_pretty_print_generics();
-        if generics.is_empty() {
+        let generics = if T::TYPED {
+            self.ty.to_pretty_print_generics()
+        } else {
+            self.generics
+                .iter()
+                .map(|var| Box::new(var.to_string()) as Box<dyn PrettyPrintTree>)
+                .collect()
+        };
+        let fun_declare = if generics.is_empty() {
             fun
         } else {
             let generics: Vec<_> = generics.into();
@@ -131,6 +237,322 @@ impl<T: PrettyPrintType> PrettyPrint for FunDeclare<T> {
                 ]),
                 fun,
             ])
+        };
+        // typed declarations show their `given` in the scheme
+        let fun_declare = if T::TYPED || self.given.is_empty() {
+            fun_declare
+        } else {
+            multiline_sequence([
+                line([
+                    Box::new("given ".to_string()),
+                    sequence(
+                        self.given
+                            .iter()
+                            .map(|bound| postfix(", ", bound.to_pretty_print())),
+                    ),
+                    Box::new(":".to_string()),
+                ]),
+                fun_declare,
+            ])
+        };
+        line([self.visibility.to_pretty_print(), fun_declare])
+    }
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AliasDeclare {
+    pub ident: Atom,
+    pub span: Span,
+    pub params: Box<[Atom]>,
+    pub ty: TypeExpr,
+}
+impl PrettyPrint for AliasDeclare {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        let name = if self.params.is_empty() {
+            Box::new(self.ident.to_string()) as Box<dyn PrettyPrintTree>
+        } else {
+            line([
+                Box::new(self.ident.to_string()),
+                bracket(
+                    "(",
+                    ")",
+                    sequence(
+                        self.params
+                            .iter()
+                            .map(|param| postfix(", ", Box::new(param.to_string()))),
+                    ),
+                ),
+            ])
+        };
+        line([
+            Box::new("alias ".to_string()),
+            name,
+            Box::new(" = ".to_string()),
+            self.ty.to_pretty_print(),
+        ])
+    }
+}
+/// `newtype Point(x: Num, #y: Num);`, the span covers the whole declaration
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NewtypeDeclare {
+    pub visibility: Visibility,
+    pub ident: Atom,
+    pub span: Span,
+    pub generics: Box<[Atom]>,
+    pub fields: Box<[NewtypeField]>,
+}
+impl PrettyPrint for NewtypeDeclare {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        let generics = if self.generics.is_empty() {
+            Box::new(String::new()) as Box<dyn PrettyPrintTree>
+        } else {
+            line([
+                bracket(
+                    ":(",
+                    "):",
+                    sequence(
+                        self.generics
+                            .iter()
+                            .map(|name| postfix(", ", Box::new(name.to_string()))),
+                    ),
+                ),
+                Box::new(" ".to_string()),
+            ])
+        };
+        line([
+            self.visibility.to_pretty_print(),
+            generics,
+            Box::new(format!("newtype {}", self.ident)),
+            bracket(
+                "(",
+                ")",
+                sequence(
+                    self.fields
+                        .iter()
+                        .map(|field| postfix(", ", field.to_pretty_print())),
+                ),
+            ),
+        ])
+    }
+}
+/// Field of a newtype, those named with `#` are private unless declared
+/// with `pub`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NewtypeField {
+    pub visibility: Visibility,
+    pub name: Atom,
+    pub ty: TypeExpr,
+}
+impl NewtypeField {
+    /// Whether the field is named with `#`
+    pub fn is_hidden(&self) -> bool {
+        self.name.starts_with('#')
+    }
+}
+impl PrettyPrint for NewtypeField {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        // fields without `#` are always public
+        let visibility = if self.is_hidden() {
+            self.visibility.to_pretty_print()
+        } else {
+            Box::new(String::new())
+        };
+        line([
+            visibility,
+            Box::new(format!("{}: ", self.name)),
+            self.ty.to_pretty_print(),
+        ])
+    }
+}
+/// `trait Eq(a) { equal(a: &a, b: &a) -> Bool; }`, the span covers the whole
+/// declaration
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TraitDeclare {
+    pub visibility: Visibility,
+    pub ident: Atom,
+    pub span: Span,
+    pub param: Atom,
+    pub methods: Box<[MethodSignature]>,
+}
+impl PrettyPrint for TraitDeclare {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        let header = Box::new(format!("trait {}({}) ", self.ident, self.param));
+        let body = if self.methods.is_empty() {
+            Box::new("{}".to_string()) as Box<dyn PrettyPrintTree>
+        } else {
+            bracket(
+                "{",
+                "}",
+                multiline_sequence(
+                    self.methods
+                        .iter()
+                        .map(|method| postfix(";", method.to_pretty_print())),
+                ),
+            )
+        };
+        line([self.visibility.to_pretty_print(), header, body])
+    }
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MethodSignature {
+    pub ident: Atom,
+    pub span: Span,
+    pub params: Box<[(Atom, TypeExpr)]>,
+    pub ret: TypeExpr,
+}
+impl PrettyPrint for MethodSignature {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        line([
+            Box::new(self.ident.to_string()),
+            bracket(
+                "(",
+                ")",
+                sequence(self.params.iter().map(|(name, ty)| {
+                    postfix(
+                        ", ",
+                        line([Box::new(format!("{name}: ")), ty.to_pretty_print()]),
+                    )
+                })),
+            ),
+            Box::new(" -> ".to_string()),
+            self.ret.to_pretty_print(),
+        ])
+    }
+}
+/// `given Eq(a): impl Eq([a]) { ... }`, the span covers the whole declaration
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImplDeclare<T: PrettyPrintType> {
+    pub given: Box<[TraitBound]>,
+    pub bound: TraitBound,
+    pub span: Span,
+    pub methods: Box<[FunDeclare<T>]>,
+}
+impl<T: PrettyPrintType> TraverseType for ImplDeclare<T> {
+    type Type = T;
+    type Mapped<U: PrettyPrintType> = ImplDeclare<U>;
+
+    fn traverse_type<E>(
+        &mut self,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        self.methods.traverse_type(f)
+    }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        ImplDeclare {
+            given: self.given,
+            bound: self.bound,
+            span: self.span,
+            methods: self.methods.map_types(map),
+        }
+    }
+}
+impl<T: PrettyPrintType> PrettyPrint for ImplDeclare<T> {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        let body = if self.methods.is_empty() {
+            Box::new("{}".to_string()) as Box<dyn PrettyPrintTree>
+        } else {
+            bracket(
+                "{",
+                "}",
+                multiline_sequence(
+                    self.methods
+                        .iter()
+                        .map(|method| postfix(";", method.to_pretty_print())),
+                ),
+            )
+        };
+        let impl_declare = line([
+            Box::new("impl ".to_string()),
+            self.bound.to_pretty_print(),
+            Box::new(" ".to_string()),
+            body,
+        ]);
+        if self.given.is_empty() {
+            impl_declare
+        } else {
+            multiline_sequence([
+                line([
+                    Box::new("given ".to_string()),
+                    sequence(
+                        self.given
+                            .iter()
+                            .map(|bound| postfix(", ", bound.to_pretty_print())),
+                    ),
+                    Box::new(":".to_string()),
+                ]),
+                impl_declare,
+            ])
         }
     }
 }
+#[derive(Debug, PartialEq, Clone)]
+pub struct ModuleDeclare<T: PrettyPrintType> {
+    pub visibility: Visibility,
+    pub ident: Atom,
+    pub span: Span,
+    pub module: Module<T>,
+}
+impl<T: PrettyPrintType> TraverseType for ModuleDeclare<T> {
+    type Type = T;
+    type Mapped<U: PrettyPrintType> = ModuleDeclare<U>;
+
+    fn traverse_type<E>(
+        &mut self,
+        f: &mut impl FnMut(TypeSite<'_, Self::Type>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        if let Module::Inline(statements) = &mut self.module {
+            for statement in statements.iter_mut() {
+                statement.traverse_type(f)?;
+            }
+        }
+        Ok(())
+    }
+    fn map_types<U: PrettyPrintType>(
+        self,
+        map: &mut impl MapTypes<Self::Type, U>,
+    ) -> Self::Mapped<U> {
+        ModuleDeclare {
+            visibility: self.visibility,
+            ident: self.ident,
+            span: self.span,
+            module: match self.module {
+                Module::File(file) => Module::File(file),
+                Module::Inline(statements) => Module::Inline(statements.map_types(map)),
+            },
+        }
+    }
+}
+impl<T: PrettyPrintType> PrettyPrint for ModuleDeclare<T> {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        let name = Box::new(format!("mod {}", self.ident));
+        let module = match &self.module {
+            Module::File(file) if *file == self.ident => name,
+            Module::File(file) => Box::new(format!("{} = mod {file}", self.ident)),
+            Module::Inline(statements) if statements.is_empty() => {
+                line([name, Box::new(" {}".to_string())])
+            }
+            Module::Inline(statements) => line([
+                name,
+                Box::new(" ".to_string()),
+                bracket(
+                    "{",
+                    "}",
+                    multiline_sequence(
+                        statements
+                            .iter()
+                            .map(|statement| postfix(";", statement.to_pretty_print())),
+                    ),
+                ),
+            ]),
+        };
+        line([self.visibility.to_pretty_print(), module])
+    }
+}
+#[derive(Debug, PartialEq, Clone)]
+pub enum Module<T: PrettyPrintType> {
+    /// `mod name;`, the content is in `name.butter` and is yet to be loaded
+    File(Atom),
+    Inline(Box<[Statement<T>]>),
+}
@@ -0,0 +1,209 @@
+use crate::{
+    pretty_print::{bracket, line, postfix, prefix, sequence, PrettyPrint, PrettyPrintTree},
+    Atom,
+};
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TypeExpr {
+    Discard,
+    Name(Atom, Box<[TypeExpr]>),
+    Var(Atom),
+    Array(Box<TypeExpr>),
+    Tuple(TupleType),
+    Record(RecordType),
+    Union(UnionType),
+    Ref(RefType),
+    Fun(Box<TypeExpr>, Box<TypeExpr>),
+}
+impl TypeExpr {
+    fn to_auto_wrap(&self) -> Box<dyn PrettyPrintTree> {
+        let ty = self.to_pretty_print();
+        match self {
+            Self::Fun(_, _) => bracket("(", ")", ty),
+            _ => ty,
+        }
+    }
+}
+impl PrettyPrint for TypeExpr {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        match self {
+            Self::Discard => Box::new("_".to_string()),
+            Self::Name(name, args) if args.is_empty() => Box::new(name.to_string()),
+            Self::Name(name, args) => line([
+                Box::new(name.to_string()),
+                bracket(
+                    "(",
+                    ")",
+                    sequence(args.iter().map(|ty| postfix(", ", ty.to_pretty_print()))),
+                ),
+            ]),
+            Self::Var(name) => Box::new(name.to_string()),
+            Self::Array(ty) => bracket("[", "]", ty.to_pretty_print()),
+            Self::Tuple(tuple) => tuple.to_pretty_print(),
+            Self::Record(record) => record.to_pretty_print(),
+            Self::Union(union) => union.to_pretty_print(),
+            Self::Ref(reference) => reference.to_pretty_print(),
+            Self::Fun(param, ret) => line([
+                param.to_auto_wrap(),
+                Box::new(" -> ".to_string()),
+                ret.to_pretty_print(),
+            ]),
+        }
+    }
+}
+fn rest_to_pretty_print(rest: &Option<Box<TypeExpr>>) -> Option<Box<dyn PrettyPrintTree>> {
+    rest.as_ref()
+        .map(|rest| postfix(", ", prefix("*", rest.to_pretty_print())))
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TupleType {
+    pub left: Box<[TypeExpr]>,
+    pub rest: Option<Box<TypeExpr>>,
+    pub right: Box<[TypeExpr]>,
+}
+impl PrettyPrint for TupleType {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        let iter = self
+            .left
+            .iter()
+            .map(|ty| postfix(", ", ty.to_pretty_print()))
+            .chain(rest_to_pretty_print(&self.rest))
+            .chain(
+                self.right
+                    .iter()
+                    .map(|ty| postfix(", ", ty.to_pretty_print())),
+            );
+        bracket("(", ")", sequence(iter))
+    }
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RecordType {
+    pub fields: Box<[(Atom, TypeExpr)]>,
+    pub rest: Option<Box<TypeExpr>>,
+}
+impl PrettyPrint for RecordType {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        let iter = self
+            .fields
+            .iter()
+            .map(|(name, ty)| {
+                postfix(
+                    ", ",
+                    line([Box::new(format!("{name} : ")), ty.to_pretty_print()]),
+                )
+            })
+            .chain(rest_to_pretty_print(&self.rest));
+        bracket("{", "}", sequence(iter))
+    }
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UnionType {
+    pub tags: Box<[(Atom, Option<TypeExpr>)]>,
+    pub rest: Option<Box<TypeExpr>>,
+}
+impl PrettyPrint for UnionType {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        let iter = self
+            .tags
+            .iter()
+            .map(|(tag, ty)| {
+                let tag = match ty {
+                    Some(ty) => line([Box::new(format!("@{tag} ")), ty.to_pretty_print()]),
+                    None => Box::new(format!("@{tag}")),
+                };
+                postfix(", ", tag)
+            })
+            .chain(rest_to_pretty_print(&self.rest));
+        bracket("(", ")", sequence(iter))
+    }
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MutTypeExpr {
+    Imm,
+    Mut,
+    Var(Atom),
+}
+impl Display for MutTypeExpr {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Imm => write!(fmt, "imm")?,
+            Self::Mut => write!(fmt, "mut")?,
+            Self::Var(var) => write!(fmt, "{var}")?,
+        }
+        Ok(())
+    }
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RefType {
+    pub mutability: Option<MutTypeExpr>,
+    pub lifetime: Option<Atom>,
+    pub ty: Box<TypeExpr>,
+}
+impl PrettyPrint for RefType {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        let mut reference = "&".to_string();
+        if let Some(mutability) = &self.mutability {
+            reference.push_str(&format!(":{mutability} "));
+        }
+        if let Some(lifetime) = &self.lifetime {
+            reference.push_str(&format!("/{lifetime} "));
+        }
+        line([Box::new(reference), self.ty.to_auto_wrap()])
+    }
+}
+impl Display for TypeExpr {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        let mut buffer = Vec::new();
+        self.to_pretty_print()
+            .write(&mut buffer, "", usize::MAX)
+            .map_err(|_| fmt::Error)?;
+        write!(fmt, "{}", String::from_utf8_lossy(&buffer))?;
+        Ok(())
+    }
+}
+/// A trait applied to a type, as in `Eq([a])`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TraitBound {
+    pub name: Atom,
+    pub ty: TypeExpr,
+}
+impl PrettyPrint for TraitBound {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        line([
+            Box::new(self.name.to_string()),
+            bracket("(", ")", self.ty.to_pretty_print()),
+        ])
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::{
+        ty::{MutTypeExpr, RecordType, RefType, TypeExpr, UnionType},
+        Atom,
+    };
+
+    #[test]
+    fn pretty_print() {
+        let ty = TypeExpr::Ref(RefType {
+            mutability: Some(MutTypeExpr::Mut),
+            lifetime: Some(Atom::from("l")),
+            ty: Box::new(TypeExpr::Record(RecordType {
+                fields: vec![(
+                    Atom::from("name"),
+                    TypeExpr::Union(UnionType {
+                        tags: vec![
+                            (Atom::from("val"), Some(TypeExpr::Discard)),
+                            (Atom::from("none"), None),
+                        ]
+                        .into(),
+                        rest: None,
+                    }),
+                )]
+                .into(),
+                rest: Some(Box::new(TypeExpr::Discard)),
+            })),
+        });
+        assert_eq!(ty.to_string(), "&:mut /l {name : (@val _, @none, ), *_, }");
+    }
+}
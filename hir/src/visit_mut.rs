@@ -0,0 +1,647 @@
+//! Traversal over a mutably borrowed tree. Every method of [`VisitMut`]
+//! defaults to the `walk_*` function of the same node which visits its
+//! children, so an implementor only overrides the nodes it cares about and
+//! calls the `walk_*` function when it still needs the children visited.
+//!
+//! Children are visited in evaluation order, the value of a declaration is
+//! visited before its pattern and the value of an assignment before its place.
+
+use crate::{
+    expr::{
+        Annotated, Arg, Assign, Binary, Block, Call, Collection, ControlFlow, Element, Expr,
+        ExprKind, Field, FieldAccess, For, Fun, If, Index, Jump, Literal, Match, MatchArm,
+        PlaceExpr, Range, Slice, Str, Tag, Unary, While,
+    },
+    pattern::{
+        AnnotatedPattern, ListPattern, Pattern, PatternKind, RecordPattern, TaggedPattern,
+        TypedVar, Var,
+    },
+    statement::{
+        AliasDeclare, Declare, FunDeclare, ImplDeclare, Module, ModuleDeclare, NewtypeDeclare,
+        Statement, TraitDeclare,
+    },
+    ty::{TraitBound, TypeExpr},
+    Atom, PrettyPrintType, Span,
+};
+
+pub trait VisitMut<T: PrettyPrintType> {
+    fn visit_statement(&mut self, statement: &mut Statement<T>) {
+        walk_statement(self, statement);
+    }
+    fn visit_declare(&mut self, declare: &mut Declare<T>) {
+        walk_declare(self, declare);
+    }
+    fn visit_fun_declare(&mut self, fun_declare: &mut FunDeclare<T>) {
+        walk_fun_declare(self, fun_declare);
+    }
+    fn visit_alias_declare(&mut self, alias_declare: &mut AliasDeclare) {
+        walk_alias_declare(self, alias_declare);
+    }
+    fn visit_newtype_declare(&mut self, newtype_declare: &mut NewtypeDeclare) {
+        walk_newtype_declare(self, newtype_declare);
+    }
+    fn visit_trait_declare(&mut self, trait_declare: &mut TraitDeclare) {
+        walk_trait_declare(self, trait_declare);
+    }
+    fn visit_impl_declare(&mut self, impl_declare: &mut ImplDeclare<T>) {
+        walk_impl_declare(self, impl_declare);
+    }
+    fn visit_trait_bound(&mut self, bound: &mut TraitBound) {
+        walk_trait_bound(self, bound);
+    }
+    fn visit_module_declare(&mut self, module_declare: &mut ModuleDeclare<T>) {
+        walk_module_declare(self, module_declare);
+    }
+    fn visit_module(&mut self, module: &mut Module<T>) {
+        walk_module(self, module);
+    }
+    fn visit_expr(&mut self, expr: &mut Expr<T>) {
+        walk_expr(self, expr);
+    }
+    fn visit_literal(&mut self, _literal: &mut Literal) {}
+    fn visit_str(&mut self, _string: &mut Str) {}
+    fn visit_tag(&mut self, tag: &mut Tag<T>) {
+        walk_tag(self, tag);
+    }
+    fn visit_assign(&mut self, assign: &mut Assign<T>) {
+        walk_assign(self, assign);
+    }
+    fn visit_element(&mut self, element: &mut Element<T>) {
+        walk_element(self, element);
+    }
+    fn visit_range(&mut self, range: &mut Range<T>) {
+        walk_range(self, range);
+    }
+    fn visit_record(&mut self, record: &mut Collection<Field<T>, T>) {
+        walk_record(self, record);
+    }
+    fn visit_field(&mut self, field: &mut Field<T>) {
+        walk_field(self, field);
+    }
+    fn visit_tuple(&mut self, tuple: &mut Collection<Expr<T>, T>) {
+        walk_tuple(self, tuple);
+    }
+    fn visit_unary(&mut self, unary: &mut Unary<T>) {
+        walk_unary(self, unary);
+    }
+    fn visit_binary(&mut self, binary: &mut Binary<T>) {
+        walk_binary(self, binary);
+    }
+    fn visit_place(&mut self, place: &mut PlaceExpr<T>) {
+        walk_place(self, place);
+    }
+    /// A variable used as an expression, as opposed to [`VisitMut::visit_var`]
+    /// which is a variable bound by a pattern
+    fn visit_var_expr(&mut self, _name: &mut Atom, _span: &mut Span) {}
+    fn visit_field_access(&mut self, field_access: &mut FieldAccess<T>) {
+        walk_field_access(self, field_access);
+    }
+    fn visit_index(&mut self, index: &mut Index<T>) {
+        walk_index(self, index);
+    }
+    fn visit_slice(&mut self, slice: &mut Slice<T>) {
+        walk_slice(self, slice);
+    }
+    fn visit_call(&mut self, call: &mut Call<T>) {
+        walk_call(self, call);
+    }
+    fn visit_arg(&mut self, arg: &mut Arg<T>) {
+        walk_arg(self, arg);
+    }
+    fn visit_control_flow(&mut self, control_flow: &mut ControlFlow<T>) {
+        walk_control_flow(self, control_flow);
+    }
+    fn visit_block(&mut self, block: &mut Block<T>) {
+        walk_block(self, block);
+    }
+    fn visit_if(&mut self, if_expr: &mut If<T>) {
+        walk_if(self, if_expr);
+    }
+    fn visit_for(&mut self, for_expr: &mut For<T>) {
+        walk_for(self, for_expr);
+    }
+    fn visit_while(&mut self, while_expr: &mut While<T>) {
+        walk_while(self, while_expr);
+    }
+    fn visit_match(&mut self, match_expr: &mut Match<T>) {
+        walk_match(self, match_expr);
+    }
+    fn visit_match_arm(&mut self, arm: &mut MatchArm<T>) {
+        walk_match_arm(self, arm);
+    }
+    fn visit_fun(&mut self, fun: &mut Fun<T>) {
+        walk_fun(self, fun);
+    }
+    fn visit_jump(&mut self, jump: &mut Jump<T>) {
+        walk_jump(self, jump);
+    }
+    fn visit_annotated(&mut self, annotated: &mut Annotated<T>) {
+        walk_annotated(self, annotated);
+    }
+    fn visit_pattern(&mut self, pattern: &mut Pattern<T>) {
+        walk_pattern(self, pattern);
+    }
+    fn visit_var(&mut self, _var: &mut Var) {}
+    fn visit_typed_var(&mut self, typed_var: &mut TypedVar<T>) {
+        walk_typed_var(self, typed_var);
+    }
+    fn visit_record_pattern(&mut self, record: &mut RecordPattern<T>) {
+        walk_record_pattern(self, record);
+    }
+    fn visit_list_pattern(&mut self, list: &mut ListPattern<T>) {
+        walk_list_pattern(self, list);
+    }
+    fn visit_tagged_pattern(&mut self, tagged: &mut TaggedPattern<T>) {
+        walk_tagged_pattern(self, tagged);
+    }
+    fn visit_annotated_pattern(&mut self, annotated: &mut AnnotatedPattern<T>) {
+        walk_annotated_pattern(self, annotated);
+    }
+    fn visit_type_expr(&mut self, _ty: &mut TypeExpr) {}
+}
+pub fn walk_statement<T, V>(visitor: &mut V, statement: &mut Statement<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    match statement {
+        Statement::Declare(declare) => visitor.visit_declare(declare),
+        Statement::FunDeclare(fun_declare) => visitor.visit_fun_declare(fun_declare),
+        Statement::AliasDeclare(alias_declare) => visitor.visit_alias_declare(alias_declare),
+        Statement::NewtypeDeclare(newtype_declare) => {
+            visitor.visit_newtype_declare(newtype_declare)
+        }
+        Statement::TraitDeclare(trait_declare) => visitor.visit_trait_declare(trait_declare),
+        Statement::ImplDeclare(impl_declare) => visitor.visit_impl_declare(impl_declare),
+        Statement::ModuleDeclare(module_declare) => visitor.visit_module_declare(module_declare),
+        Statement::Expr(expr) => visitor.visit_expr(expr),
+    }
+}
+pub fn walk_declare<T, V>(visitor: &mut V, declare: &mut Declare<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut declare.expr);
+    if let Some(else_part) = &mut declare.else_part {
+        visitor.visit_block(else_part);
+    }
+    visitor.visit_pattern(&mut declare.pattern);
+}
+pub fn walk_fun_declare<T, V>(visitor: &mut V, fun_declare: &mut FunDeclare<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    for bound in fun_declare.given.iter_mut() {
+        visitor.visit_trait_bound(bound);
+    }
+    visitor.visit_fun(&mut fun_declare.fun);
+}
+pub fn walk_alias_declare<T, V>(visitor: &mut V, alias_declare: &mut AliasDeclare)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_type_expr(&mut alias_declare.ty);
+}
+pub fn walk_newtype_declare<T, V>(visitor: &mut V, newtype_declare: &mut NewtypeDeclare)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    for field in newtype_declare.fields.iter_mut() {
+        visitor.visit_type_expr(&mut field.ty);
+    }
+}
+pub fn walk_trait_declare<T, V>(visitor: &mut V, trait_declare: &mut TraitDeclare)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    for method in trait_declare.methods.iter_mut() {
+        for (_, ty) in method.params.iter_mut() {
+            visitor.visit_type_expr(ty);
+        }
+        visitor.visit_type_expr(&mut method.ret);
+    }
+}
+pub fn walk_impl_declare<T, V>(visitor: &mut V, impl_declare: &mut ImplDeclare<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    for bound in impl_declare.given.iter_mut() {
+        visitor.visit_trait_bound(bound);
+    }
+    visitor.visit_trait_bound(&mut impl_declare.bound);
+    for method in impl_declare.methods.iter_mut() {
+        visitor.visit_fun_declare(method);
+    }
+}
+pub fn walk_trait_bound<T, V>(visitor: &mut V, bound: &mut TraitBound)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_type_expr(&mut bound.ty);
+}
+pub fn walk_module_declare<T, V>(visitor: &mut V, module_declare: &mut ModuleDeclare<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_module(&mut module_declare.module);
+}
+pub fn walk_module<T, V>(visitor: &mut V, module: &mut Module<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    if let Module::Inline(statements) = module {
+        for statement in statements.iter_mut() {
+            visitor.visit_statement(statement);
+        }
+    }
+}
+pub fn walk_expr<T, V>(visitor: &mut V, expr: &mut Expr<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    match &mut expr.expr {
+        ExprKind::Literal(literal) => visitor.visit_literal(literal),
+        ExprKind::Str(string) => visitor.visit_str(string),
+        ExprKind::Tag(tag) => visitor.visit_tag(tag),
+        ExprKind::Assign(assign) => {
+            for assign in assign.iter_mut() {
+                visitor.visit_assign(assign);
+            }
+        }
+        ExprKind::Array(elements) => {
+            for element in elements.iter_mut() {
+                visitor.visit_element(element);
+            }
+        }
+        ExprKind::ArrayRange(range) => visitor.visit_range(range),
+        ExprKind::Unit | ExprKind::Never => (),
+        ExprKind::Splat(expr) => visitor.visit_expr(expr),
+        ExprKind::Record(record) => visitor.visit_record(record),
+        ExprKind::Tuple(tuple) => visitor.visit_tuple(tuple),
+        ExprKind::Unary(unary) => visitor.visit_unary(unary),
+        ExprKind::Binary(binary) => visitor.visit_binary(binary),
+        ExprKind::Place(place) => visitor.visit_place(place),
+        ExprKind::Call(call) => visitor.visit_call(call),
+        ExprKind::ControlFlow(control_flow) => visitor.visit_control_flow(control_flow),
+        ExprKind::Fun(fun) => visitor.visit_fun(fun),
+        ExprKind::Jump(jump) => visitor.visit_jump(jump),
+        ExprKind::Annotated(annotated) => visitor.visit_annotated(annotated),
+    }
+}
+pub fn walk_tag<T, V>(visitor: &mut V, tag: &mut Tag<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    if let Some(expr) = &mut tag.expr {
+        visitor.visit_expr(expr);
+    }
+}
+pub fn walk_assign<T, V>(visitor: &mut V, assign: &mut Assign<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut assign.expr);
+    visitor.visit_place(&mut assign.place);
+}
+pub fn walk_element<T, V>(visitor: &mut V, element: &mut Element<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut element.expr);
+}
+pub fn walk_range<T, V>(visitor: &mut V, range: &mut Range<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    if let Some(bound) = &mut range.left {
+        visitor.visit_expr(&mut bound.expr);
+    }
+    if let Some(bound) = &mut range.right {
+        visitor.visit_expr(&mut bound.expr);
+    }
+}
+pub fn walk_record<T, V>(visitor: &mut V, record: &mut Collection<Field<T>, T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    match record {
+        Collection::Collection(fields) => {
+            for field in fields.iter_mut() {
+                visitor.visit_field(field);
+            }
+        }
+        Collection::WithSplat(record) => {
+            for field in record.left.iter_mut() {
+                visitor.visit_field(field);
+            }
+            visitor.visit_expr(&mut record.splat);
+            for field in record.right.iter_mut() {
+                visitor.visit_field(field);
+            }
+        }
+    }
+}
+pub fn walk_field<T, V>(visitor: &mut V, field: &mut Field<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut field.expr);
+}
+pub fn walk_tuple<T, V>(visitor: &mut V, tuple: &mut Collection<Expr<T>, T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    match tuple {
+        Collection::Collection(exprs) => {
+            for expr in exprs.iter_mut() {
+                visitor.visit_expr(expr);
+            }
+        }
+        Collection::WithSplat(tuple) => {
+            for expr in tuple.left.iter_mut() {
+                visitor.visit_expr(expr);
+            }
+            visitor.visit_expr(&mut tuple.splat);
+            for expr in tuple.right.iter_mut() {
+                visitor.visit_expr(expr);
+            }
+        }
+    }
+}
+pub fn walk_unary<T, V>(visitor: &mut V, unary: &mut Unary<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut unary.expr);
+}
+pub fn walk_binary<T, V>(visitor: &mut V, binary: &mut Binary<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut binary.left);
+    visitor.visit_expr(&mut binary.right);
+}
+pub fn walk_place<T, V>(visitor: &mut V, place: &mut PlaceExpr<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    match place {
+        PlaceExpr::Var(name, span) => visitor.visit_var_expr(name, span),
+        PlaceExpr::FieldAccess(field_access) => visitor.visit_field_access(field_access),
+        PlaceExpr::Index(index) => visitor.visit_index(index),
+        PlaceExpr::Slice(slice) => visitor.visit_slice(slice),
+        PlaceExpr::Deref(expr) | PlaceExpr::Len(expr) => visitor.visit_expr(expr),
+    }
+}
+pub fn walk_field_access<T, V>(visitor: &mut V, field_access: &mut FieldAccess<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut field_access.expr);
+}
+pub fn walk_index<T, V>(visitor: &mut V, index: &mut Index<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut index.expr);
+    visitor.visit_expr(&mut index.index);
+}
+pub fn walk_slice<T, V>(visitor: &mut V, slice: &mut Slice<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut slice.expr);
+    visitor.visit_range(&mut slice.range);
+}
+pub fn walk_call<T, V>(visitor: &mut V, call: &mut Call<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut call.expr);
+    visitor.visit_arg(&mut call.arg);
+}
+pub fn walk_arg<T, V>(visitor: &mut V, arg: &mut Arg<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    match arg {
+        Arg::Unit => (),
+        Arg::Splat(expr) => visitor.visit_expr(expr),
+        Arg::Record(record) => visitor.visit_record(record),
+        Arg::Tuple(tuple) => visitor.visit_tuple(tuple),
+    }
+}
+pub fn walk_control_flow<T, V>(visitor: &mut V, control_flow: &mut ControlFlow<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    match control_flow {
+        ControlFlow::Block(block) | ControlFlow::Loop(block) => visitor.visit_block(block),
+        ControlFlow::If(if_expr) => visitor.visit_if(if_expr),
+        ControlFlow::For(for_expr) => visitor.visit_for(for_expr),
+        ControlFlow::While(while_expr) => visitor.visit_while(while_expr),
+        ControlFlow::Match(match_expr) => visitor.visit_match(match_expr),
+    }
+}
+pub fn walk_block<T, V>(visitor: &mut V, block: &mut Block<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    for statement in block.statement.iter_mut() {
+        visitor.visit_statement(statement);
+    }
+    if let Some(expr) = &mut block.expr {
+        visitor.visit_expr(expr);
+    }
+}
+pub fn walk_if<T, V>(visitor: &mut V, if_expr: &mut If<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut if_expr.condition);
+    visitor.visit_block(&mut if_expr.body);
+    if let Some(else_part) = &mut if_expr.else_part {
+        visitor.visit_control_flow(else_part);
+    }
+}
+pub fn walk_for<T, V>(visitor: &mut V, for_expr: &mut For<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut for_expr.expr);
+    visitor.visit_pattern(&mut for_expr.pattern);
+    visitor.visit_block(&mut for_expr.body);
+}
+pub fn walk_while<T, V>(visitor: &mut V, while_expr: &mut While<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut while_expr.condition);
+    visitor.visit_block(&mut while_expr.body);
+}
+pub fn walk_match<T, V>(visitor: &mut V, match_expr: &mut Match<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut match_expr.expr);
+    for arm in match_expr.arm.iter_mut() {
+        visitor.visit_match_arm(arm);
+    }
+}
+pub fn walk_match_arm<T, V>(visitor: &mut V, arm: &mut MatchArm<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_pattern(&mut arm.pattern);
+    visitor.visit_expr(&mut arm.expr);
+}
+pub fn walk_fun<T, V>(visitor: &mut V, fun: &mut Fun<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_pattern(&mut fun.param);
+    if let Some(ret) = &mut fun.ret {
+        visitor.visit_type_expr(ret);
+    }
+    visitor.visit_expr(&mut fun.body);
+}
+pub fn walk_jump<T, V>(visitor: &mut V, jump: &mut Jump<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    match jump {
+        Jump::Break(expr) | Jump::Return(expr) => {
+            if let Some(expr) = expr {
+                visitor.visit_expr(expr);
+            }
+        }
+        Jump::Continue => (),
+    }
+}
+pub fn walk_annotated<T, V>(visitor: &mut V, annotated: &mut Annotated<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_expr(&mut annotated.expr);
+    visitor.visit_type_expr(&mut annotated.annotation);
+}
+pub fn walk_pattern<T, V>(visitor: &mut V, pattern: &mut Pattern<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    match &mut pattern.pattern {
+        PatternKind::True
+        | PatternKind::False
+        | PatternKind::UInt(_)
+        | PatternKind::Int(_)
+        | PatternKind::Discard => (),
+        PatternKind::Var(var) => visitor.visit_var(var),
+        PatternKind::Record(record) => visitor.visit_record_pattern(record),
+        PatternKind::Tuple(list) | PatternKind::Array(list) => visitor.visit_list_pattern(list),
+        PatternKind::Param(param) => {
+            for typed_var in param.iter_mut() {
+                visitor.visit_typed_var(typed_var);
+            }
+        }
+        PatternKind::Tag(tagged) => visitor.visit_tagged_pattern(tagged),
+        PatternKind::Ref(pattern) => visitor.visit_pattern(pattern),
+        PatternKind::Annotated(annotated) => visitor.visit_annotated_pattern(annotated),
+    }
+}
+pub fn walk_typed_var<T, V>(visitor: &mut V, typed_var: &mut TypedVar<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_var(&mut typed_var.var);
+    if let Some(annotation) = &mut typed_var.annotation {
+        visitor.visit_type_expr(annotation);
+    }
+}
+pub fn walk_record_pattern<T, V>(visitor: &mut V, record: &mut RecordPattern<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    for pattern in record.fields.values_mut() {
+        visitor.visit_pattern(pattern);
+    }
+    if let Some(rest) = &mut record.rest {
+        visitor.visit_pattern(rest);
+    }
+}
+pub fn walk_list_pattern<T, V>(visitor: &mut V, list: &mut ListPattern<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    match list {
+        ListPattern::List(list) => {
+            for pattern in list.iter_mut() {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        ListPattern::ListWithRest(list) => {
+            for pattern in list.left.iter_mut() {
+                visitor.visit_pattern(pattern);
+            }
+            visitor.visit_pattern(&mut list.rest);
+            for pattern in list.right.iter_mut() {
+                visitor.visit_pattern(pattern);
+            }
+        }
+    }
+}
+pub fn walk_tagged_pattern<T, V>(visitor: &mut V, tagged: &mut TaggedPattern<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    if let Some(pattern) = &mut tagged.pattern {
+        visitor.visit_pattern(pattern);
+    }
+}
+pub fn walk_annotated_pattern<T, V>(visitor: &mut V, annotated: &mut AnnotatedPattern<T>)
+where
+    T: PrettyPrintType,
+    V: VisitMut<T> + ?Sized,
+{
+    visitor.visit_pattern(&mut annotated.pattern);
+    visitor.visit_type_expr(&mut annotated.annotation);
+}
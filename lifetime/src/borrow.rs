@@ -0,0 +1,1125 @@
+//! Lifetime inference and borrow checking. A reference is live from where
+//! it's taken until the last use of anything holding it, following the
+//! control flow. A mutable reference is never live at the same time as
+//! another use of the place it refers to, and a reference to a place local to
+//! a function never outlives it.
+
+use crate::path::{Path, Projection};
+use hir::{
+    expr::{
+        Arg, Block, Collection, ControlFlow, Expr, ExprKind, Fun, Jump, PlaceExpr, Range, UnaryType,
+    },
+    pattern::{ListPattern, Pattern, PatternKind},
+    resolve::{BindingId, Resolution},
+    statement::{Module, Statement},
+    Atom, Span,
+};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+use type_system::{Cons, MutType, Type};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BorrowErrorKind {
+    /// The place is used or borrowed while a mutable reference to it is live
+    UseWhileMutBorrowed,
+    /// The place is mutably borrowed while another reference to it is live
+    MutBorrowWhileBorrowed,
+    /// The place is assigned while a reference to it is live
+    AssignWhileBorrowed,
+    /// The place is moved out while a reference to it is live
+    MoveWhileBorrowed,
+    /// A reference to a place local to a function is returned from it
+    Dangling,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BorrowError {
+    pub kind: BorrowErrorKind,
+    /// The place as written in the code, `_` stands for any index
+    pub place: String,
+    /// Where the place is borrowed
+    pub borrowed: Span,
+    /// Where the place is used, or where the reference is returned for
+    /// [`BorrowErrorKind::Dangling`]
+    pub span: Span,
+}
+impl Display for BorrowError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        let place = &self.place;
+        match self.kind {
+            BorrowErrorKind::UseWhileMutBorrowed => write!(
+                fmt,
+                "use of `{place}` while a mutable reference to it is live"
+            )?,
+            BorrowErrorKind::MutBorrowWhileBorrowed => write!(
+                fmt,
+                "mutable borrow of `{place}` while another reference to it is live"
+            )?,
+            BorrowErrorKind::AssignWhileBorrowed => write!(
+                fmt,
+                "assignment to `{place}` while a reference to it is live"
+            )?,
+            BorrowErrorKind::MoveWhileBorrowed => {
+                write!(fmt, "move out of `{place}` while a reference to it is live")?
+            }
+            BorrowErrorKind::Dangling => write!(
+                fmt,
+                "returned reference to `{place}` outlives it, it is local to the function"
+            )?,
+        }
+        Ok(())
+    }
+}
+/// Checks that no mutable reference is live alongside another use of the same
+/// place, and that no function returns a reference to its own locals.
+/// Variables are looked up in `resolution` which must be resolved from the
+/// same statements
+pub fn check_borrows(statements: &[Statement<Type>], resolution: &Resolution) -> Vec<BorrowError> {
+    let mut analysis = Analysis {
+        resolution,
+        declared: HashMap::new(),
+        bindings: Vec::new(),
+        loans: Vec::new(),
+        accesses: Vec::new(),
+        returns: Vec::new(),
+        frames: Vec::new(),
+        next_frame: 0,
+        order: 0,
+        funs: Vec::new(),
+    };
+    for statement in statements {
+        analysis.statement(statement);
+    }
+    analysis.errors()
+}
+/// A construct the code is within, points are compared by these to tell
+/// whether one may run after the other
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Frame {
+    /// An arm of a branching construct, different arms never run after one
+    /// another
+    Branch(usize, usize),
+    /// The body of a loop, code within may run again on the next iteration
+    Loop(usize),
+    /// The body of a function, ran whenever it's called
+    Fun(usize),
+}
+#[derive(Debug, Clone)]
+struct Point {
+    /// Position in the order of evaluation
+    order: usize,
+    frames: Vec<Frame>,
+}
+impl Point {
+    /// Whether the code at `self` may run and then the code at `other`.
+    /// Loops repeat only the bindings declared outside of them, those within
+    /// are new bindings on each iteration.
+    fn reaches(&self, other: &Self, declared: &[Frame]) -> bool {
+        let common = self
+            .frames
+            .iter()
+            .zip(&other.frames)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let compatible = match (self.frames.get(common), other.frames.get(common)) {
+            (Some(Frame::Branch(a, _)), Some(Frame::Branch(b, _))) if a == b => false,
+            _ => !self.frames[common..]
+                .iter()
+                .chain(&other.frames[common..])
+                .any(|frame| matches!(frame, Frame::Fun(_))),
+        };
+        let repeated = self.frames[..common]
+            .iter()
+            .rev()
+            .take_while(|frame| !matches!(frame, Frame::Fun(_)))
+            .any(|frame| matches!(frame, Frame::Loop(_)) && !declared.contains(frame));
+        (self.order < other.order && compatible) || repeated
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mutability {
+    Imm,
+    Mut,
+    /// Bound with `ref`, it's mutable when something is assigned or moved
+    /// through the binding
+    Ref(u32),
+}
+struct Loan {
+    path: Path,
+    mutability: Mutability,
+    at: Point,
+    span: Span,
+    /// When it's passed to a call, it's live until the call ends
+    until: Option<usize>,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AccessKind {
+    Use,
+    Assign,
+    Move,
+    Borrow(usize),
+}
+struct Access {
+    path: Path,
+    kind: AccessKind,
+    at: Point,
+    span: Span,
+}
+struct Binding {
+    declared: Point,
+    uses: Vec<Point>,
+    /// Loans the binding may hold
+    loans: Vec<usize>,
+    /// Other bindings whose loans the binding may hold
+    from: Vec<u32>,
+    /// Captured places the binding assigns when it's a function and called
+    assigns: Vec<Path>,
+    written_through: bool,
+}
+/// Loans a value may hold, directly or through the bindings it's read from
+#[derive(Default)]
+struct Holds {
+    loans: Vec<usize>,
+    bindings: Vec<u32>,
+    /// Captured places assigned by calling the value
+    assigns: Vec<Path>,
+}
+impl Holds {
+    fn extend(&mut self, other: Self) {
+        self.loans.extend(other.loans);
+        self.bindings.extend(other.bindings);
+        self.assigns.extend(other.assigns);
+    }
+    /// Only values of types that can hold a reference keeps these
+    fn filter(self, ty: &Type) -> Self {
+        if ty.may_hold_ref() {
+            self
+        } else {
+            Self::default()
+        }
+    }
+}
+struct Return {
+    holds: Holds,
+    /// The first binding within the function
+    fun_start: u32,
+    span: Span,
+}
+struct Analysis<'a> {
+    resolution: &'a Resolution,
+    /// The latest declaration of each binding, a binding declared again, as
+    /// in a loop, is a new one
+    declared: HashMap<BindingId, u32>,
+    bindings: Vec<Binding>,
+    loans: Vec<Loan>,
+    accesses: Vec<Access>,
+    returns: Vec<Return>,
+    frames: Vec<Frame>,
+    next_frame: usize,
+    order: usize,
+    /// The functions the code is within
+    funs: Vec<FunFrame>,
+}
+struct FunFrame {
+    /// The first binding within the function
+    first: u32,
+    /// Where the function is
+    at: Point,
+    /// Bindings from outside of the function used within, the function
+    /// holds whatever they hold so the loans live as long as it's called
+    captures: Vec<u32>,
+    /// Places outside of the function assigned within, these are assigned
+    /// wherever it's called
+    assigns: Vec<Path>,
+}
+impl Analysis<'_> {
+    fn point(&mut self) -> Point {
+        self.order += 1;
+        Point {
+            order: self.order,
+            frames: self.frames.clone(),
+        }
+    }
+    fn new_frame(&mut self) -> usize {
+        self.next_frame += 1;
+        self.next_frame
+    }
+    fn within<U>(&mut self, frame: Frame, body: impl FnOnce(&mut Self) -> U) -> U {
+        self.frames.push(frame);
+        let result = body(self);
+        self.frames.pop();
+        result
+    }
+    fn fun_start(&self) -> Option<u32> {
+        self.funs.last().map(|fun| fun.first)
+    }
+    fn declare(&mut self, span: Span, holds: Holds) -> u32 {
+        let binding = self.bindings.len() as u32;
+        let declared = self.point();
+        self.bindings.push(Binding {
+            declared,
+            uses: Vec::new(),
+            loans: holds.loans,
+            from: holds.bindings,
+            assigns: holds.assigns,
+            written_through: false,
+        });
+        if let Some(id) = self.resolution.binding_of(span) {
+            self.declared.insert(id, binding);
+        }
+        binding
+    }
+    /// Declares the variables of the pattern matched against a value, `path`
+    /// is the place the value is from which `ref` variables borrow
+    fn declare_pattern(&mut self, pattern: &Pattern<Type>, holds: &Holds, path: Option<Path>) {
+        let project = |projection| {
+            path.clone().map(|mut path: Path| {
+                path.projections.push(projection);
+                path
+            })
+        };
+        match &pattern.pattern {
+            PatternKind::True
+            | PatternKind::False
+            | PatternKind::UInt(_)
+            | PatternKind::Int(_)
+            | PatternKind::Discard => (),
+            PatternKind::Var(var) => {
+                let holds = Holds {
+                    loans: holds.loans.clone(),
+                    bindings: holds.bindings.clone(),
+                    assigns: holds.assigns.clone(),
+                };
+                let binding = self.declare(var.span, holds);
+                if let (true, Some(path)) = (var.bind_to_ref, path) {
+                    let holds = self.borrow(path, Mutability::Ref(binding), var.span);
+                    let binding = &mut self.bindings[binding as usize];
+                    binding.loans.extend(holds.loans);
+                    binding.from.extend(holds.bindings);
+                }
+            }
+            PatternKind::Record(record) => {
+                for (name, field) in &record.fields {
+                    self.declare_pattern(field, holds, project(Projection::Field(name.clone())));
+                }
+                if let Some(rest) = &record.rest {
+                    self.declare_pattern(rest, holds, None);
+                }
+            }
+            PatternKind::Tuple(tuple) => self.list_pattern(tuple, holds, |i| {
+                project(Projection::Field(Atom::from(i.to_string())))
+            }),
+            PatternKind::Param(params) => {
+                for param in params.iter() {
+                    let path = project(Projection::Field(param.var.ident.clone()));
+                    self.declare_pattern(
+                        &Pattern {
+                            pattern: PatternKind::Var(param.var.clone()),
+                            ty: param.ty.clone(),
+                            span: param.var.span,
+                        },
+                        holds,
+                        path,
+                    );
+                }
+            }
+            PatternKind::Array(array) => {
+                self.list_pattern(array, holds, |_| project(Projection::Index))
+            }
+            PatternKind::Tag(tag) => {
+                if let Some(pattern) = &tag.pattern {
+                    self.declare_pattern(pattern, holds, path);
+                }
+            }
+            PatternKind::Ref(pattern) => {
+                self.declare_pattern(pattern, holds, project(Projection::Deref))
+            }
+            PatternKind::Annotated(annotated) => {
+                self.declare_pattern(&annotated.pattern, holds, path)
+            }
+        }
+    }
+    /// The rest and the elements after it are new lists so these aren't
+    /// borrowed
+    fn list_pattern(
+        &mut self,
+        list: &ListPattern<Type>,
+        holds: &Holds,
+        element: impl Fn(usize) -> Option<Path>,
+    ) {
+        match list {
+            ListPattern::List(elements) => {
+                for (i, pattern) in elements.iter().enumerate() {
+                    self.declare_pattern(pattern, holds, element(i));
+                }
+            }
+            ListPattern::ListWithRest(with_rest) => {
+                for (i, pattern) in with_rest.left.iter().enumerate() {
+                    self.declare_pattern(pattern, holds, element(i));
+                }
+                for pattern in [&*with_rest.rest].into_iter().chain(&*with_rest.right) {
+                    self.declare_pattern(pattern, holds, None);
+                }
+            }
+        }
+    }
+    /// Records a use of the binding, uses within a function count as uses
+    /// where the function is as it may be called anytime after
+    fn use_binding(&mut self, binding: u32) {
+        for fun in &mut self.funs {
+            if fun.first > binding && !fun.captures.contains(&binding) {
+                fun.captures.push(binding);
+            }
+        }
+        let at = match self.funs.iter().find(|fun| fun.first > binding) {
+            Some(fun) => fun.at.clone(),
+            None => self.point(),
+        };
+        self.bindings[binding as usize].uses.push(at);
+    }
+    fn access(&mut self, path: Path, kind: AccessKind, span: Span) {
+        if let (AccessKind::Assign | AccessKind::Move, true) = (kind, path.has_deref(0)) {
+            self.bindings[path.binding as usize].written_through = true;
+        }
+        if kind == AccessKind::Assign {
+            for fun in &mut self.funs {
+                if fun.first > path.binding && !fun.assigns.contains(&path) {
+                    fun.assigns.push(path.clone());
+                }
+            }
+        }
+        let at = self.point();
+        self.accesses.push(Access {
+            path,
+            kind,
+            at,
+            span,
+        });
+    }
+    /// Borrows the place, a reference borrowed through another reference
+    /// also holds what the other holds
+    fn borrow(&mut self, path: Path, mutability: Mutability, span: Span) -> Holds {
+        let loan = self.loans.len();
+        let bindings = if path.has_deref(0) {
+            vec![path.binding]
+        } else {
+            Vec::new()
+        };
+        let at = self.point();
+        self.loans.push(Loan {
+            path: path.clone(),
+            mutability,
+            at,
+            span,
+            until: None,
+        });
+        self.access(path, AccessKind::Borrow(loan), span);
+        Holds {
+            loans: vec![loan],
+            bindings,
+            assigns: Vec::new(),
+        }
+    }
+    /// The place as a path, subexpressions that aren't part of the path are
+    /// evaluated
+    fn path(&mut self, place: &PlaceExpr<Type>) -> Option<Path> {
+        let (base, projection) = match place {
+            PlaceExpr::Var(name, span) => {
+                let id = self.resolution.resolve_use(*span)?;
+                let binding = *self.declared.get(&id)?;
+                self.use_binding(binding);
+                return Some(Path {
+                    binding,
+                    name: name.clone(),
+                    projections: Vec::new(),
+                });
+            }
+            PlaceExpr::FieldAccess(field_access) => (
+                self.base(&field_access.expr),
+                Projection::Field(field_access.name.clone()),
+            ),
+            PlaceExpr::Index(index) => {
+                let base = self.base(&index.expr);
+                self.expr(&index.index);
+                (base, Projection::Index)
+            }
+            PlaceExpr::Slice(slice) => {
+                let base = self.base(&slice.expr);
+                self.range(&slice.range);
+                (base, Projection::Index)
+            }
+            PlaceExpr::Deref(expr) => (self.base(expr), Projection::Deref),
+            PlaceExpr::Len(expr) => {
+                self.expr(expr);
+                return None;
+            }
+        };
+        let mut base = base?;
+        base.projections.push(projection);
+        Some(base)
+    }
+    fn base(&mut self, expr: &Expr<Type>) -> Option<Path> {
+        match &expr.expr {
+            ExprKind::Place(place) => self.path(place),
+            _ => {
+                self.expr(expr);
+                None
+            }
+        }
+    }
+    /// Uses the place, returning what the value holds and the place as a path
+    fn place(&mut self, place: &PlaceExpr<Type>, expr: &Expr<Type>) -> (Holds, Option<Path>) {
+        let Some(path) = self.path(place) else {
+            return (Holds::default(), None);
+        };
+        self.access(path.clone(), AccessKind::Use, expr.span);
+        let holds = Holds {
+            loans: Vec::new(),
+            bindings: vec![path.binding],
+            assigns: Vec::new(),
+        };
+        (holds.filter(&expr.ty), Some(path))
+    }
+    /// Evaluates a value that's matched against a pattern
+    fn scrutinee(&mut self, expr: &Expr<Type>) -> (Holds, Option<Path>) {
+        match &expr.expr {
+            ExprKind::Place(place) => self.place(place, expr),
+            _ => (self.expr(expr), None),
+        }
+    }
+    fn statement(&mut self, statement: &Statement<Type>) {
+        match statement {
+            Statement::Declare(declare) => {
+                let (holds, path) = self.scrutinee(&declare.expr);
+                if let Some(else_part) = &declare.else_part {
+                    self.block(else_part);
+                }
+                self.declare_pattern(&declare.pattern, &holds, path);
+            }
+            Statement::FunDeclare(fun_declare) => {
+                // declared first so it can be called recursively
+                let binding = self.declare(fun_declare.span, Holds::default());
+                let holds = self.fun(&fun_declare.fun);
+                let binding = &mut self.bindings[binding as usize];
+                binding.from.extend(holds.bindings);
+                binding.assigns.extend(holds.assigns);
+            }
+            // the constructor holds nothing
+            Statement::NewtypeDeclare(newtype) => {
+                self.declare(newtype.span, Holds::default());
+            }
+            Statement::AliasDeclare(_) | Statement::TraitDeclare(_) => (),
+            // methods aren't bound in the scope, they're used through the
+            // trait
+            Statement::ImplDeclare(impl_declare) => {
+                for method in impl_declare.methods.iter() {
+                    self.fun(&method.fun);
+                }
+            }
+            Statement::ModuleDeclare(module_declare) => match &module_declare.module {
+                Module::File(_) => (),
+                Module::Inline(statements) => {
+                    for statement in statements.iter() {
+                        self.statement(statement);
+                    }
+                }
+            },
+            Statement::Expr(expr) => {
+                self.expr(expr);
+            }
+        }
+    }
+    fn block(&mut self, block: &Block<Type>) -> Holds {
+        for statement in block.statement.iter() {
+            self.statement(statement);
+        }
+        match &block.expr {
+            Some(expr) => self.expr(expr),
+            None => Holds::default(),
+        }
+    }
+    /// Returns the bindings the function captures
+    fn fun(&mut self, fun: &Fun<Type>) -> Holds {
+        let frame = Frame::Fun(self.new_frame());
+        let at = self.point();
+        let fun_start = self.bindings.len() as u32;
+        self.funs.push(FunFrame {
+            first: fun_start,
+            at,
+            captures: Vec::new(),
+            assigns: Vec::new(),
+        });
+        self.within(frame, |analysis| {
+            analysis.declare_pattern(&fun.param, &Holds::default(), None);
+            let holds = analysis.expr(&fun.body);
+            analysis.returns.push(Return {
+                holds,
+                fun_start,
+                span: fun.body.span,
+            });
+        });
+        let fun = self.funs.pop().unwrap();
+        Holds {
+            loans: Vec::new(),
+            bindings: fun.captures,
+            assigns: fun.assigns,
+        }
+    }
+    fn expr(&mut self, expr: &Expr<Type>) -> Holds {
+        match &expr.expr {
+            ExprKind::Literal(_) | ExprKind::Str(_) | ExprKind::Unit | ExprKind::Never => {
+                Holds::default()
+            }
+            ExprKind::Tag(tag) => match &tag.expr {
+                Some(expr) => self.expr(expr),
+                None => Holds::default(),
+            },
+            ExprKind::Assign(assign) => {
+                // every value is evaluated before any of the places is
+                // assigned
+                let values: Vec<_> = assign
+                    .iter()
+                    .map(|assign| self.expr(&assign.expr))
+                    .collect();
+                for (assign, holds) in assign.iter().zip(values) {
+                    if let Some(path) = self.path(&assign.place) {
+                        let binding = &mut self.bindings[path.binding as usize];
+                        binding.loans.extend(holds.loans);
+                        binding.from.extend(holds.bindings);
+                        binding.assigns.extend(holds.assigns);
+                        self.access(path, AccessKind::Assign, expr.span);
+                    }
+                }
+                Holds::default()
+            }
+            ExprKind::Array(elements) => {
+                let mut holds = Holds::default();
+                for element in elements.iter() {
+                    holds.extend(self.expr(&element.expr));
+                }
+                holds
+            }
+            ExprKind::ArrayRange(range) => {
+                self.range(range);
+                Holds::default()
+            }
+            ExprKind::Splat(expr) => self.expr(expr),
+            ExprKind::Record(record) => {
+                self.collection(record, |analysis, field| analysis.expr(&field.expr))
+            }
+            ExprKind::Tuple(tuple) => self.collection(tuple, Self::expr),
+            ExprKind::Unary(unary) => match (unary.kind, &unary.expr.expr) {
+                (UnaryType::Ref, ExprKind::Place(place)) => {
+                    let Some(path) = self.path(place) else {
+                        return Holds::default();
+                    };
+                    let mutability = match &expr.ty {
+                        Type::Cons(Cons::Ref(MutType::Mut, _)) => Mutability::Mut,
+                        _ => Mutability::Imm,
+                    };
+                    self.borrow(path, mutability, expr.span)
+                }
+                (UnaryType::Move, ExprKind::Place(place)) => {
+                    let Some(path) = self.path(place) else {
+                        return Holds::default();
+                    };
+                    let binding = path.binding;
+                    self.access(path, AccessKind::Move, expr.span);
+                    let holds = Holds {
+                        loans: Vec::new(),
+                        bindings: vec![binding],
+                        assigns: Vec::new(),
+                    };
+                    holds.filter(&expr.ty)
+                }
+                _ => self.expr(&unary.expr).filter(&expr.ty),
+            },
+            ExprKind::Binary(binary) => {
+                let mut holds = self.expr(&binary.left);
+                holds.extend(self.expr(&binary.right));
+                holds.filter(&expr.ty)
+            }
+            ExprKind::Place(place) => self.place(place, expr).0,
+            ExprKind::Call(call) => {
+                let callee = self.expr(&call.expr);
+                let first = self.loans.len();
+                let holds = match &call.arg {
+                    Arg::Unit => Holds::default(),
+                    Arg::Splat(expr) => self.expr(expr),
+                    Arg::Record(record) => {
+                        self.collection(record, |analysis, field| analysis.expr(&field.expr))
+                    }
+                    Arg::Tuple(tuple) => self.collection(tuple, Self::expr),
+                };
+                for path in self.assigned(callee) {
+                    self.access(path, AccessKind::Assign, expr.span);
+                }
+                let end = self.point().order;
+                for loan in &mut self.loans[first..] {
+                    loan.until.get_or_insert(end);
+                }
+                holds.filter(&expr.ty)
+            }
+            ExprKind::ControlFlow(control_flow) => self.control_flow(control_flow),
+            ExprKind::Fun(fun) => self.fun(fun).filter(&expr.ty),
+            ExprKind::Jump(jump) => {
+                self.jump(jump, expr.span);
+                Holds::default()
+            }
+            ExprKind::Annotated(annotated) => self.expr(&annotated.expr),
+        }
+    }
+    /// Captured places assigned by calling a value, including those of the
+    /// bindings it's read from
+    fn assigned(&self, holds: Holds) -> Vec<Path> {
+        let mut assigns = holds.assigns;
+        let mut visited = Vec::new();
+        let mut bindings = holds.bindings;
+        while let Some(binding) = bindings.pop() {
+            if visited.contains(&binding) {
+                continue;
+            }
+            visited.push(binding);
+            let binding = &self.bindings[binding as usize];
+            assigns.extend(binding.assigns.iter().cloned());
+            bindings.extend(&binding.from);
+        }
+        assigns
+    }
+    fn collection<T>(
+        &mut self,
+        collection: &Collection<T, Type>,
+        mut each: impl FnMut(&mut Self, &T) -> Holds,
+    ) -> Holds {
+        let mut holds = Holds::default();
+        match collection {
+            Collection::Collection(items) => {
+                for item in items.iter() {
+                    holds.extend(each(self, item));
+                }
+            }
+            Collection::WithSplat(with_splat) => {
+                for item in with_splat.left.iter() {
+                    holds.extend(each(self, item));
+                }
+                holds.extend(self.expr(&with_splat.splat));
+                for item in with_splat.right.iter() {
+                    holds.extend(each(self, item));
+                }
+            }
+        }
+        holds
+    }
+    fn range(&mut self, range: &Range<Type>) {
+        for bound in [&range.left, &range.right].into_iter().flatten() {
+            self.expr(&bound.expr);
+        }
+    }
+    fn jump(&mut self, jump: &Jump<Type>, span: Span) {
+        match jump {
+            Jump::Break(expr) => {
+                if let Some(expr) = expr {
+                    self.expr(expr);
+                }
+            }
+            Jump::Continue => (),
+            Jump::Return(expr) => {
+                let holds = match expr {
+                    Some(expr) => self.expr(expr),
+                    None => Holds::default(),
+                };
+                if let Some(fun_start) = self.fun_start() {
+                    self.returns.push(Return {
+                        holds,
+                        fun_start,
+                        span,
+                    });
+                }
+            }
+        }
+    }
+    fn control_flow(&mut self, control_flow: &ControlFlow<Type>) -> Holds {
+        match control_flow {
+            ControlFlow::Block(block) => self.block(block),
+            ControlFlow::If(if_expr) => {
+                self.expr(&if_expr.condition);
+                let id = self.new_frame();
+                let mut holds = self.within(Frame::Branch(id, 0), |analysis| {
+                    analysis.block(&if_expr.body)
+                });
+                if let Some(else_part) = &if_expr.else_part {
+                    holds.extend(self.within(Frame::Branch(id, 1), |analysis| {
+                        analysis.control_flow(else_part)
+                    }));
+                }
+                holds
+            }
+            ControlFlow::Match(match_expr) => {
+                let (scrutinee, path) = self.scrutinee(&match_expr.expr);
+                let id = self.new_frame();
+                let mut holds = Holds::default();
+                for (i, arm) in match_expr.arm.iter().enumerate() {
+                    holds.extend(self.within(Frame::Branch(id, i), |analysis| {
+                        analysis.declare_pattern(&arm.pattern, &scrutinee, path.clone());
+                        analysis.expr(&arm.expr)
+                    }));
+                }
+                holds
+            }
+            ControlFlow::For(for_expr) => {
+                let holds = self.expr(&for_expr.expr);
+                let frame = Frame::Loop(self.new_frame());
+                self.within(frame, |analysis| {
+                    analysis.declare_pattern(&for_expr.pattern, &holds, None);
+                    analysis.block(&for_expr.body);
+                });
+                Holds::default()
+            }
+            ControlFlow::While(while_expr) => {
+                let frame = Frame::Loop(self.new_frame());
+                self.within(frame, |analysis| {
+                    analysis.expr(&while_expr.condition);
+                    analysis.block(&while_expr.body);
+                });
+                Holds::default()
+            }
+            ControlFlow::Loop(block) => {
+                let frame = Frame::Loop(self.new_frame());
+                self.within(frame, |analysis| analysis.block(block));
+                Holds::default()
+            }
+        }
+    }
+    /// Loans each binding may hold, including those of the bindings it's
+    /// assigned from
+    fn held(&self) -> Vec<Vec<usize>> {
+        let mut held: Vec<Vec<usize>> = self
+            .bindings
+            .iter()
+            .map(|binding| binding.loans.clone())
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, binding) in self.bindings.iter().enumerate() {
+                for from in &binding.from {
+                    for loan in held[*from as usize].clone() {
+                        if !held[i].contains(&loan) {
+                            held[i].push(loan);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+        held
+    }
+    /// Whether each loan is mutable. Inference may leave it unknown, so a
+    /// loan is also mutable when something is assigned through what holds
+    /// it, or when a mutable reference is borrowed through it.
+    fn mutable(&self, holders: &[Vec<u32>]) -> Vec<bool> {
+        let written = |binding: &u32| self.bindings[*binding as usize].written_through;
+        let mut mutable: Vec<bool> = self
+            .loans
+            .iter()
+            .zip(holders)
+            .map(|(loan, holders)| {
+                let own = match loan.mutability {
+                    Mutability::Imm => false,
+                    Mutability::Mut => true,
+                    Mutability::Ref(binding) => written(&binding),
+                };
+                own || holders.iter().any(written)
+            })
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (loan, reborrowed) in self.loans.iter().zip(mutable.clone()) {
+                if !reborrowed || !loan.path.has_deref(0) {
+                    continue;
+                }
+                for (i, holders) in holders.iter().enumerate() {
+                    if !mutable[i] && holders.contains(&loan.path.binding) {
+                        mutable[i] = true;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        mutable
+    }
+    /// Whether the loan is live at the point, it is when anything holding it
+    /// is used after the point
+    fn is_live(&self, loan: usize, at: &Point, holders: &[u32]) -> bool {
+        let current = &self.loans[loan];
+        if let Some(until) = current.until {
+            if current.at.order < at.order && at.order <= until {
+                return true;
+            }
+        }
+        holders.iter().any(|binding| {
+            let binding = &self.bindings[*binding as usize];
+            let declared = &binding.declared;
+            current.at.reaches(at, &declared.frames)
+                && binding.uses.iter().any(|used| {
+                    // the binding is declared anew before the use
+                    let redeclared = at.order < declared.order && declared.order < used.order;
+                    at.reaches(used, &declared.frames) && !redeclared
+                })
+        })
+    }
+    fn errors(self) -> Vec<BorrowError> {
+        let held = self.held();
+        let holders: Vec<Vec<u32>> = (0..self.loans.len())
+            .map(|loan| {
+                (0..self.bindings.len() as u32)
+                    .filter(|binding| held[*binding as usize].contains(&loan))
+                    .collect()
+            })
+            .collect();
+        let mutable = self.mutable(&holders);
+        let mut errors = Vec::new();
+        for access in &self.accesses {
+            let conflict = self.loans.iter().enumerate().find(|(i, loan)| {
+                access.kind != AccessKind::Borrow(*i)
+                    && loan.path.overlaps(&access.path)
+                    && match access.kind {
+                        AccessKind::Use => mutable[*i],
+                        AccessKind::Borrow(other) => mutable[*i] || mutable[other],
+                        AccessKind::Assign | AccessKind::Move => true,
+                    }
+                    && self.is_live(*i, &access.at, &holders[*i])
+            });
+            let Some((_, loan)) = conflict else {
+                continue;
+            };
+            let kind = match access.kind {
+                AccessKind::Use => BorrowErrorKind::UseWhileMutBorrowed,
+                AccessKind::Borrow(other) if mutable[other] => {
+                    BorrowErrorKind::MutBorrowWhileBorrowed
+                }
+                AccessKind::Borrow(_) => BorrowErrorKind::UseWhileMutBorrowed,
+                AccessKind::Assign => BorrowErrorKind::AssignWhileBorrowed,
+                AccessKind::Move => BorrowErrorKind::MoveWhileBorrowed,
+            };
+            errors.push(BorrowError {
+                kind,
+                place: access.path.to_string(),
+                borrowed: loan.span,
+                span: access.span,
+            });
+        }
+        for returned in &self.returns {
+            let mut loans = returned.holds.loans.clone();
+            for binding in &returned.holds.bindings {
+                loans.extend(&held[*binding as usize]);
+            }
+            loans.sort_unstable();
+            loans.dedup();
+            for loan in loans {
+                let loan = &self.loans[loan];
+                // references borrowed through other references are from
+                // outside of the function
+                if loan.path.binding >= returned.fun_start && !loan.path.has_deref(0) {
+                    errors.push(BorrowError {
+                        kind: BorrowErrorKind::Dangling,
+                        place: loan.path.to_string(),
+                        borrowed: loan.span,
+                        span: returned.span,
+                    });
+                }
+            }
+        }
+        errors
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::{check_borrows, BorrowErrorKind};
+    use hir::{resolve::resolve, statement::Statement};
+    use parser::{ast, EasyParser};
+    use type_system::Type;
+
+    /// Errors as the kind, the place, and the code borrowing and using it
+    fn errors(src: &str, statements: &[Statement<Type>]) -> Vec<(BorrowErrorKind, String, String)> {
+        let resolution = resolve(statements).unwrap();
+        check_borrows(statements, &resolution)
+            .into_iter()
+            .map(|err| {
+                let borrowed = &src[err.borrowed.range()];
+                let span = &src[err.span.range()];
+                (
+                    err.kind,
+                    format!("`{}` from `{borrowed}`", err.place),
+                    span.to_string(),
+                )
+            })
+            .collect()
+    }
+    fn check(src: &str) -> Vec<(BorrowErrorKind, String, String)> {
+        let (statements, rest) = ast().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        errors(src, &type_system::infer(statements).unwrap())
+    }
+    fn error(kind: BorrowErrorKind, place: &str, span: &str) -> (BorrowErrorKind, String, String) {
+        (kind, place.to_string(), span.to_string())
+    }
+    #[test]
+    fn shared_mut() {
+        assert_eq!(check("mut x = 1; mut r = &x; r^ <- 2; y = x;"), []);
+        assert_eq!(
+            check("mut x = 1; mut r = &x; y = x; r^ <- 2;"),
+            [error(
+                BorrowErrorKind::UseWhileMutBorrowed,
+                "`x` from `&x`",
+                "x"
+            )],
+        );
+        assert_eq!(
+            check("mut x = 1; mut r = &x; s = &x; r^ <- 2;"),
+            [error(
+                BorrowErrorKind::UseWhileMutBorrowed,
+                "`x` from `&x`",
+                "&x"
+            )],
+        );
+        assert_eq!(check("mut x = 1; r = &x; s = &x; y = r^ + s^;"), []);
+    }
+    #[test]
+    fn assign_borrowed() {
+        assert_eq!(
+            check("mut x = 1; r = &x; x <- 3; y = r^;"),
+            [error(
+                BorrowErrorKind::AssignWhileBorrowed,
+                "`x` from `&x`",
+                "x <- 3"
+            )],
+        );
+        assert_eq!(check("mut x = 1; r = &x; y = r^; x <- 3;"), []);
+    }
+    #[test]
+    fn disjoint() {
+        let src = "mut p = (a = 1, b = 2); mut r = &p.a; mut s = &p.b; r^ <- 1; s^ <- 2;";
+        assert_eq!(check(src), []);
+        let src = "mut u = (a = 1, b = 2); { (a = ref mut a, = b) = u; y = u.b; a^ <- 3; };";
+        assert_eq!(check(src), []);
+        let src = "mut u = (a = 1, b = 2); { (a = ref mut a, = b) = u; y = u; a^ <- 3; };";
+        assert_eq!(
+            check(src),
+            [error(
+                BorrowErrorKind::UseWhileMutBorrowed,
+                "`u` from `a`",
+                "u"
+            )],
+        );
+    }
+    #[test]
+    fn through_calls() {
+        let src = "mut x = 1; set(mut r, v) => { r^ <- v; }; set(&x, x);";
+        assert_eq!(
+            check(src),
+            [error(
+                BorrowErrorKind::UseWhileMutBorrowed,
+                "`x` from `&x`",
+                "x"
+            )],
+        );
+        let src = "mut x = 1; id(r) => r; mut r = id(&x); y = x; r^ <- 2;";
+        assert_eq!(check(src).len(), 1);
+        let src = "mut x = 1; get(r) => r^; y = get(&x); x <- 2;";
+        assert_eq!(check(src), []);
+    }
+    #[test]
+    fn captured() {
+        let src = "mut x = 1; r = &x; g() => r^ <- 2; y = x; g();";
+        assert_eq!(
+            check(src),
+            [error(
+                BorrowErrorKind::UseWhileMutBorrowed,
+                "`x` from `&x`",
+                "x"
+            )],
+        );
+        let src = "mut x = 1; r = &x; g = () => r^ <- 2; h = g; y = x; h();";
+        assert_eq!(check(src).len(), 1);
+        let src = "mut x = 1; r = &x; g() => r^ <- 2; g(); y = x;";
+        assert_eq!(check(src), []);
+        let src = "g() => { mut x = 1; r = &x; f() => { x <- 2; }; f(); r^ };";
+        assert_eq!(
+            check(src),
+            [error(
+                BorrowErrorKind::AssignWhileBorrowed,
+                "`x` from `&x`",
+                "f()"
+            )],
+        );
+        let src = "g() => { mut x = 1; r = &x; f = () => { x <- 2; }; h = f; h(); r^ };";
+        assert_eq!(check(src).len(), 1);
+        let src = "g() => { mut x = 1; r = &x; f() => { x <- 2; }; y = r^; f(); };";
+        assert_eq!(check(src), []);
+    }
+    #[test]
+    fn reborrow() {
+        let src = "mut u = (a = 1, b = 2); { mut r = &u; mut s = &r^.a; y = u; s^ <- 3; };";
+        assert_eq!(
+            check(src),
+            [error(
+                BorrowErrorKind::UseWhileMutBorrowed,
+                "`u` from `&u`",
+                "u"
+            )],
+        );
+    }
+    #[test]
+    fn branches() {
+        let src = "c = true; mut x = 1; mut r = &x; if c { r^ <- 2; } else { y = x; };";
+        assert_eq!(check(src), []);
+        let src = "c = true; mut x = 1; mut r = &x; if c { y = x; } else { y = 1; }; r^ <- 2;";
+        assert_eq!(check(src).len(), 1);
+    }
+    #[test]
+    fn loops() {
+        assert_eq!(
+            check("mut x = 1; mut r = &x; loop { r^ <- 2; y = x; }"),
+            [error(
+                BorrowErrorKind::UseWhileMutBorrowed,
+                "`x` from `&x`",
+                "x"
+            )],
+        );
+        assert_eq!(check("mut x = 1; loop { mut r = &x; r^ <- 2; y = x; }"), []);
+        assert_eq!(check("mut x = 1; mut r = &x; r^ <- 2; loop { y = x; }"), []);
+    }
+    #[test]
+    fn dangling() {
+        assert_eq!(
+            check("f() => { x = 1; r = &x; r };"),
+            [error(
+                BorrowErrorKind::Dangling,
+                "`x` from `&x`",
+                "{ x = 1; r = &x; r }"
+            )],
+        );
+        assert_eq!(
+            check("f(x) => { if true { return [&x]; }; [] };"),
+            [error(
+                BorrowErrorKind::Dangling,
+                "`x` from `&x`",
+                "return [&x]"
+            )],
+        );
+        assert_eq!(check("f(r) => &r^;"), []);
+        assert_eq!(check("x = 1; f() => &x;"), []);
+        assert_eq!(check("f() => { x = 1; r = &x; r^ };"), []);
+        assert_eq!(
+            check("f() => { x = 1; r = &x; (() => r^) };"),
+            [error(
+                BorrowErrorKind::Dangling,
+                "`x` from `&x`",
+                "{ x = 1; r = &x; (() => r^) }"
+            )],
+        );
+    }
+}
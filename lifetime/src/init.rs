@@ -0,0 +1,692 @@
+//! Flow-sensitive analysis of initialization. A place is deinitialized when
+//! its value is moved out with `>`, and is initialized again when something
+//! is assigned to it. Moves are only ever explicit so a place that is never
+//! moved out is always initialized.
+
+use crate::path::{Path, Projection};
+use hir::{
+    expr::{
+        Arg, BinaryType, Block, Collection, ControlFlow, Expr, ExprKind, Fun, Jump, PlaceExpr,
+        Range, UnaryType,
+    },
+    pattern::{Pattern, Var},
+    resolve::{BindingId, Resolution},
+    statement::{Module, Statement},
+    visit::Visit,
+    PrettyPrintType, Span,
+};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    mem::{replace, take},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MoveErrorKind {
+    /// The place is used after it is moved out
+    UseAfterMove,
+    /// The place is used after it is moved out on a previous iteration of
+    /// the loop
+    MovedInLoop,
+    /// The value of a reference is moved out and the reference ends before
+    /// something is assigned back
+    NeverRestored,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MoveError {
+    pub kind: MoveErrorKind,
+    /// The place as written in the code, `_` stands for any index
+    pub place: String,
+    /// Where the place is moved out
+    pub moved: Span,
+    /// Where the place is used, or where the reference ends for
+    /// [`MoveErrorKind::NeverRestored`]
+    pub span: Span,
+}
+impl Display for MoveError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        let place = &self.place;
+        match self.kind {
+            MoveErrorKind::UseAfterMove => write!(fmt, "use of moved `{place}`")?,
+            MoveErrorKind::MovedInLoop => write!(
+                fmt,
+                "use of `{place}` which is moved on a previous iteration of the loop"
+            )?,
+            MoveErrorKind::NeverRestored => write!(
+                fmt,
+                "`{place}` is moved out of a reference and is never restored"
+            )?,
+        }
+        Ok(())
+    }
+}
+/// Checks that no place is used while it's moved out, and that every value
+/// moved out of a reference is restored. Variables are looked up in
+/// `resolution` which must be resolved from the same statements
+pub fn check_moves<T: PrettyPrintType>(
+    statements: &[Statement<T>],
+    resolution: &Resolution,
+) -> Vec<MoveError> {
+    let mut analysis = Analysis {
+        state: Some(Vec::new()),
+        resolution,
+        declared: HashMap::new(),
+        next_binding: 0,
+        fun_start: 0,
+        loops: Vec::new(),
+        report: true,
+        errors: Vec::new(),
+    };
+    for statement in statements {
+        analysis.statement(statement);
+    }
+    analysis.errors
+}
+/// Places that may be moved out along with where, `None` when the code is
+/// unreachable
+type State = Option<Vec<(Path, Span)>>;
+
+fn join(a: State, b: State) -> State {
+    match (a, b) {
+        (None, state) | (state, None) => state,
+        (Some(mut a), Some(b)) => {
+            for (path, span) in b {
+                if !a.iter().any(|(moved, _)| *moved == path) {
+                    a.push((path, span));
+                }
+            }
+            Some(a)
+        }
+    }
+}
+fn is_same(a: &State, b: &State) -> bool {
+    match (a, b) {
+        (None, None) => true,
+        (Some(a), Some(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(path, _)| b.iter().any(|(other, _)| other == path))
+        }
+        _ => false,
+    }
+}
+#[derive(Default)]
+struct Loop {
+    breaks: Vec<(State, Span)>,
+    continues: Vec<(State, Span)>,
+}
+struct Analysis<'a> {
+    state: State,
+    resolution: &'a Resolution,
+    /// Bindings numbered in the order they're declared, the bindings within
+    /// a scope are numbered from where it starts. A binding declared again,
+    /// as in a loop, is a new one
+    declared: HashMap<BindingId, u32>,
+    next_binding: u32,
+    /// The first binding within the current function
+    fun_start: u32,
+    loops: Vec<Loop>,
+    /// Errors aren't reported while the state of a loop is still computed
+    report: bool,
+    errors: Vec<MoveError>,
+}
+impl Analysis<'_> {
+    fn error(&mut self, kind: MoveErrorKind, place: &Path, moved: Span, span: Span) {
+        if self.report {
+            self.errors.push(MoveError {
+                kind,
+                place: place.to_string(),
+                moved,
+                span,
+            });
+        }
+    }
+    fn declare(&mut self, span: Span) {
+        if let Some(id) = self.resolution.binding_of(span) {
+            self.declared.insert(id, self.next_binding);
+            self.next_binding += 1;
+        }
+    }
+    fn declare_pattern<T: PrettyPrintType>(&mut self, pattern: &Pattern<T>) {
+        let mut vars = Vars(Vec::new());
+        vars.visit_pattern(pattern);
+        for var in vars.0 {
+            self.declare(var.span);
+        }
+    }
+    /// Runs `body` within a new scope, places within it are dropped at
+    /// `end`
+    fn scope(&mut self, end: Span, body: impl FnOnce(&mut Self)) {
+        let first = self.next_binding;
+        body(self);
+        let mut state = self.state.take();
+        self.drop_bindings(&mut state, first, end);
+        self.state = state;
+    }
+    /// Removes places of bindings starting from `first` as they go out of
+    /// scope, values moved out of references must be restored before that
+    fn drop_bindings(&mut self, state: &mut State, first: u32, end: Span) {
+        let Some(moved) = state else {
+            return;
+        };
+        let (dropped, kept) = take(moved)
+            .into_iter()
+            .partition(|(path, _)| path.binding >= first);
+        *moved = kept;
+        for (path, span) in dropped {
+            if path.has_deref(0) {
+                self.error(MoveErrorKind::NeverRestored, &path, span, end);
+            }
+        }
+    }
+    fn find_moved(&self, path: &Path, overlaps: impl Fn(&Path) -> bool) -> Option<(Path, Span)> {
+        self.state
+            .iter()
+            .flatten()
+            .find(|(moved, _)| moved.binding == path.binding && overlaps(moved))
+            .cloned()
+    }
+    fn use_path(&mut self, path: &Path, span: Span) {
+        if let Some((moved, moved_span)) = self.find_moved(path, |moved| moved.overlaps(path)) {
+            // code later in the loop is ran before only on the iterations
+            // before
+            let kind = if moved_span.start() >= span.start() {
+                MoveErrorKind::MovedInLoop
+            } else {
+                MoveErrorKind::UseAfterMove
+            };
+            self.error(kind, &moved, moved_span, span);
+        }
+    }
+    fn move_path(&mut self, path: Path, span: Span) {
+        self.use_path(&path, span);
+        if let Some(state) = &mut self.state {
+            if !state.iter().any(|(moved, _)| *moved == path) {
+                state.push((path, span));
+            }
+        }
+    }
+    fn assign_path(&mut self, path: &Path, span: Span) {
+        // the place assigned to must be within an initialized place
+        let containing = |moved: &Path| moved.is_prefix_of(path) && *moved != *path;
+        if let Some((moved, moved_span)) = self.find_moved(path, containing) {
+            self.error(MoveErrorKind::UseAfterMove, &moved, moved_span, span);
+        }
+        let Some(state) = &mut self.state else {
+            return;
+        };
+        let (restored, kept) = take(state)
+            .into_iter()
+            .partition(|(moved, _)| path.is_prefix_of(moved));
+        *state = kept;
+        // replacing a reference drops the place it refers to
+        for (moved, moved_span) in restored {
+            if moved.has_deref(path.projections.len()) {
+                self.error(MoveErrorKind::NeverRestored, &moved, moved_span, span);
+            }
+        }
+    }
+    /// The place as a path, subexpressions that aren't part of the path are
+    /// evaluated
+    fn path<T: PrettyPrintType>(&mut self, place: &PlaceExpr<T>) -> Option<Path> {
+        let (base, projection) = match place {
+            PlaceExpr::Var(name, span) => {
+                let id = self.resolution.resolve_use(*span)?;
+                let binding = self.declared.get(&id)?;
+                return Some(Path {
+                    binding: *binding,
+                    name: name.clone(),
+                    projections: Vec::new(),
+                });
+            }
+            PlaceExpr::FieldAccess(field_access) => (
+                self.base(&field_access.expr),
+                Projection::Field(field_access.name.clone()),
+            ),
+            PlaceExpr::Index(index) => {
+                let base = self.base(&index.expr);
+                self.expr(&index.index);
+                (base, Projection::Index)
+            }
+            PlaceExpr::Slice(slice) => {
+                let base = self.base(&slice.expr);
+                self.range(&slice.range);
+                (base, Projection::Index)
+            }
+            PlaceExpr::Deref(expr) => (self.base(expr), Projection::Deref),
+            PlaceExpr::Len(expr) => {
+                self.expr(expr);
+                return None;
+            }
+        };
+        let mut base = base?;
+        base.projections.push(projection);
+        Some(base)
+    }
+    fn base<T: PrettyPrintType>(&mut self, expr: &Expr<T>) -> Option<Path> {
+        match &expr.expr {
+            ExprKind::Place(place) => self.path(place),
+            _ => {
+                self.expr(expr);
+                None
+            }
+        }
+    }
+    fn statement<T: PrettyPrintType>(&mut self, statement: &Statement<T>) {
+        match statement {
+            Statement::Declare(declare) => {
+                self.expr(&declare.expr);
+                if let Some(else_part) = &declare.else_part {
+                    // the else part diverges
+                    let state = self.state.clone();
+                    self.block(else_part);
+                    self.state = state;
+                }
+                self.declare_pattern(&declare.pattern);
+            }
+            Statement::FunDeclare(fun_declare) => {
+                self.declare(fun_declare.span);
+                self.fun(&fun_declare.fun);
+            }
+            Statement::NewtypeDeclare(newtype) => self.declare(newtype.span),
+            Statement::AliasDeclare(_) | Statement::TraitDeclare(_) => (),
+            // methods aren't bound in the scope, they're used through the
+            // trait
+            Statement::ImplDeclare(impl_declare) => {
+                for method in impl_declare.methods.iter() {
+                    self.fun(&method.fun);
+                }
+            }
+            Statement::ModuleDeclare(module_declare) => match &module_declare.module {
+                Module::File(_) => (),
+                Module::Inline(statements) => self.scope(module_declare.span, |analysis| {
+                    for statement in statements.iter() {
+                        analysis.statement(statement);
+                    }
+                }),
+            },
+            Statement::Expr(expr) => self.expr(expr),
+        }
+    }
+    fn block<T: PrettyPrintType>(&mut self, block: &Block<T>) {
+        self.scope(block.span, |analysis| {
+            for statement in block.statement.iter() {
+                analysis.statement(statement);
+            }
+            if let Some(expr) = &block.expr {
+                analysis.expr(expr);
+            }
+        });
+    }
+    /// The body is checked with the places outside as they are where the
+    /// function is, what it does with them doesn't affect the code after it
+    fn fun<T: PrettyPrintType>(&mut self, fun: &Fun<T>) {
+        let state = self.state.clone();
+        let loops = take(&mut self.loops);
+        let fun_start = replace(&mut self.fun_start, self.next_binding);
+        self.scope(fun.body.span, |analysis| {
+            analysis.declare_pattern(&fun.param);
+            analysis.expr(&fun.body);
+        });
+        self.fun_start = fun_start;
+        self.loops = loops;
+        self.state = state;
+    }
+    fn expr<T: PrettyPrintType>(&mut self, expr: &Expr<T>) {
+        match &expr.expr {
+            ExprKind::Literal(_) | ExprKind::Str(_) | ExprKind::Unit => (),
+            ExprKind::Tag(tag) => {
+                if let Some(expr) = &tag.expr {
+                    self.expr(expr);
+                }
+            }
+            ExprKind::Assign(assign) => {
+                // every value is evaluated before any of the places is
+                // assigned
+                for assign in assign.iter() {
+                    self.expr(&assign.expr);
+                }
+                for assign in assign.iter() {
+                    if let Some(path) = self.path(&assign.place) {
+                        self.assign_path(&path, expr.span);
+                    }
+                }
+            }
+            ExprKind::Array(elements) => {
+                for element in elements.iter() {
+                    self.expr(&element.expr);
+                }
+            }
+            ExprKind::ArrayRange(range) => self.range(range),
+            ExprKind::Splat(expr) => self.expr(expr),
+            ExprKind::Record(record) => self.collection(record, |analysis, field| {
+                analysis.expr(&field.expr);
+            }),
+            ExprKind::Tuple(tuple) => self.collection(tuple, Self::expr),
+            ExprKind::Unary(unary) => match (unary.kind, &unary.expr.expr) {
+                (UnaryType::Move, ExprKind::Place(place)) => {
+                    if let Some(path) = self.path(place) {
+                        self.move_path(path, expr.span);
+                    }
+                }
+                _ => self.expr(&unary.expr),
+            },
+            ExprKind::Binary(binary) => {
+                self.expr(&binary.left);
+                if let BinaryType::LazyAnd | BinaryType::LazyOr = binary.kind {
+                    let state = self.state.clone();
+                    self.expr(&binary.right);
+                    self.state = join(state, self.state.take());
+                } else {
+                    self.expr(&binary.right);
+                }
+            }
+            ExprKind::Place(place) => {
+                if let Some(path) = self.path(place) {
+                    self.use_path(&path, expr.span);
+                }
+            }
+            ExprKind::Call(call) => {
+                self.expr(&call.expr);
+                match &call.arg {
+                    Arg::Unit => (),
+                    Arg::Splat(expr) => self.expr(expr),
+                    Arg::Record(record) => self.collection(record, |analysis, field| {
+                        analysis.expr(&field.expr);
+                    }),
+                    Arg::Tuple(tuple) => self.collection(tuple, Self::expr),
+                }
+            }
+            ExprKind::ControlFlow(control_flow) => self.control_flow(control_flow),
+            ExprKind::Fun(fun) => self.fun(fun),
+            ExprKind::Jump(jump) => self.jump(jump, expr.span),
+            // nothing after `never` is reached
+            ExprKind::Never => self.state = None,
+            ExprKind::Annotated(annotated) => self.expr(&annotated.expr),
+        }
+    }
+    fn collection<T, U: PrettyPrintType>(
+        &mut self,
+        collection: &Collection<T, U>,
+        mut each: impl FnMut(&mut Self, &T),
+    ) {
+        match collection {
+            Collection::Collection(items) => {
+                for item in items.iter() {
+                    each(self, item);
+                }
+            }
+            Collection::WithSplat(with_splat) => {
+                for item in with_splat.left.iter() {
+                    each(self, item);
+                }
+                self.expr(&with_splat.splat);
+                for item in with_splat.right.iter() {
+                    each(self, item);
+                }
+            }
+        }
+    }
+    fn range<T: PrettyPrintType>(&mut self, range: &Range<T>) {
+        for bound in [&range.left, &range.right].into_iter().flatten() {
+            self.expr(&bound.expr);
+        }
+    }
+    fn jump<T: PrettyPrintType>(&mut self, jump: &Jump<T>, span: Span) {
+        match jump {
+            Jump::Break(expr) => {
+                if let Some(expr) = expr {
+                    self.expr(expr);
+                }
+                let state = self.state.take();
+                if let Some(current) = self.loops.last_mut() {
+                    current.breaks.push((state, span));
+                }
+            }
+            Jump::Continue => {
+                let state = self.state.take();
+                if let Some(current) = self.loops.last_mut() {
+                    current.continues.push((state, span));
+                }
+            }
+            Jump::Return(expr) => {
+                if let Some(expr) = expr {
+                    self.expr(expr);
+                }
+                let mut state = self.state.take();
+                self.drop_bindings(&mut state, self.fun_start, span);
+            }
+        }
+    }
+    fn control_flow<T: PrettyPrintType>(&mut self, control_flow: &ControlFlow<T>) {
+        match control_flow {
+            ControlFlow::Block(block) => self.block(block),
+            ControlFlow::If(if_expr) => {
+                self.expr(&if_expr.condition);
+                let state = self.state.clone();
+                self.block(&if_expr.body);
+                let body = replace(&mut self.state, state);
+                if let Some(else_part) = &if_expr.else_part {
+                    self.control_flow(else_part);
+                }
+                self.state = join(body, self.state.take());
+            }
+            ControlFlow::Match(match_expr) => {
+                self.expr(&match_expr.expr);
+                let state = self.state.take();
+                for arm in match_expr.arm.iter() {
+                    let arm_state = replace(&mut self.state, state.clone());
+                    self.scope(arm.expr.span, |analysis| {
+                        analysis.declare_pattern(&arm.pattern);
+                        analysis.expr(&arm.expr);
+                    });
+                    self.state = join(arm_state, self.state.take());
+                }
+            }
+            ControlFlow::For(for_expr) => {
+                self.expr(&for_expr.expr);
+                self.looping(|analysis| {
+                    // there may be no more element
+                    let exit = analysis.state.clone();
+                    analysis.scope(for_expr.body.span, |analysis| {
+                        analysis.declare_pattern(&for_expr.pattern);
+                        analysis.block(&for_expr.body);
+                    });
+                    exit
+                });
+            }
+            ControlFlow::While(while_expr) => self.looping(|analysis| {
+                analysis.expr(&while_expr.condition);
+                let exit = analysis.state.clone();
+                analysis.block(&while_expr.body);
+                exit
+            }),
+            ControlFlow::Loop(block) => self.looping(|analysis| {
+                analysis.block(block);
+                None
+            }),
+        }
+    }
+    /// Runs the iterations of a loop until the places moved at the start of
+    /// an iteration stop changing, then once more reporting the errors. An
+    /// iteration returns the state for when the loop ends without a break.
+    fn looping(&mut self, mut iteration: impl FnMut(&mut Self) -> State) {
+        let first = self.next_binding;
+        let report = replace(&mut self.report, false);
+        let mut start = self.state.clone();
+        loop {
+            let (next, _) = self.iterate(&start, first, &mut iteration);
+            if is_same(&next, &start) {
+                break;
+            }
+            start = next;
+        }
+        self.report = report;
+        let (_, exit) = self.iterate(&start, first, &mut iteration);
+        self.state = exit;
+    }
+    /// Returns the state for the next iteration and the state after the loop
+    fn iterate(
+        &mut self,
+        start: &State,
+        first: u32,
+        iteration: &mut impl FnMut(&mut Self) -> State,
+    ) -> (State, State) {
+        self.state = start.clone();
+        self.loops.push(Loop::default());
+        let mut exit = iteration(self);
+        let current = self.loops.pop().unwrap();
+        let mut next = join(start.clone(), self.state.take());
+        for (mut state, span) in current.continues {
+            self.drop_bindings(&mut state, first, span);
+            next = join(next, state);
+        }
+        for (mut state, span) in current.breaks {
+            self.drop_bindings(&mut state, first, span);
+            exit = join(exit, state);
+        }
+        (next, exit)
+    }
+}
+struct Vars(Vec<Var>);
+
+impl<T: PrettyPrintType> Visit<T> for Vars {
+    fn visit_var(&mut self, var: &Var) {
+        self.0.push(var.clone());
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::{check_moves, MoveErrorKind};
+    use hir::resolve::resolve;
+    use parser::{ast, EasyParser};
+
+    /// Errors as the kind, the place, and the code moving and using it
+    fn check(src: &str) -> Vec<(MoveErrorKind, String, &str, &str)> {
+        let (statements, rest) = ast().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        let resolution = resolve(&statements).unwrap();
+        check_moves(&statements, &resolution)
+            .into_iter()
+            .map(|err| {
+                let moved = &src[err.moved.range()];
+                let span = &src[err.span.range()];
+                (err.kind, err.place, moved, span)
+            })
+            .collect()
+    }
+    #[test]
+    fn use_after_move() {
+        let src = "foo = 10; bar = >foo; baz = foo;";
+        assert_eq!(
+            check(src),
+            [(
+                MoveErrorKind::UseAfterMove,
+                "foo".to_string(),
+                ">foo",
+                "foo"
+            )],
+        );
+        let src = "mut foo = 10; bar = >foo; foo <- 20; baz = foo;";
+        assert_eq!(check(src), []);
+    }
+    #[test]
+    fn shadowed() {
+        assert_eq!(check("foo = 10; bar = >foo; foo = 20; baz = foo;"), []);
+    }
+    #[test]
+    fn partial() {
+        let src = "mut r = (a = 1, b = 2); x = >r.a; y = r.b; z = r;";
+        assert_eq!(
+            check(src),
+            [(MoveErrorKind::UseAfterMove, "r.a".to_string(), ">r.a", "r")],
+        );
+        let src = "mut r = (a = 1, b = 2); x = >r.a; r.a <- 3; z = r;";
+        assert_eq!(check(src), []);
+        let src = "mut r = (a = 1, b = 2); x = >r; r.a <- 3;";
+        assert_eq!(
+            check(src),
+            [(
+                MoveErrorKind::UseAfterMove,
+                "r".to_string(),
+                ">r",
+                "r.a <- 3"
+            )],
+        );
+    }
+    #[test]
+    fn branches() {
+        let src = "c = true; mut x = 1; if c { y = >x; }; z = x;";
+        assert_eq!(
+            check(src),
+            [(MoveErrorKind::UseAfterMove, "x".to_string(), ">x", "x")],
+        );
+        let src = "c = true; mut x = 1; if c { y = >x; x <- 2; } else { y = 3; }; z = x;";
+        assert_eq!(check(src), []);
+        let src = "c = true; mut x = 1; match c { true => >x, false => 2 }; z = x;";
+        assert_eq!(check(src).len(), 1);
+        let src = "c = true; mut x = 1; y = c && { _ = >x; true }; z = x;";
+        assert_eq!(check(src).len(), 1);
+    }
+    #[test]
+    fn loops() {
+        let src = "mut x = 1; while true { y = >x; }";
+        assert_eq!(
+            check(src),
+            [(MoveErrorKind::MovedInLoop, "x".to_string(), ">x", ">x")],
+        );
+        assert_eq!(check("mut x = 1; while true { y = >x; x <- 2; }"), []);
+        let src = "mut x = 1; for i in [1, 2] { y = x; x <- 2; z = >x; }";
+        assert_eq!(
+            check(src),
+            [(MoveErrorKind::MovedInLoop, "x".to_string(), ">x", "x")],
+        );
+        let src = "mut x = 1; loop { y = >x; break; }; z = x;";
+        assert_eq!(
+            check(src),
+            [(MoveErrorKind::UseAfterMove, "x".to_string(), ">x", "x")],
+        );
+        let src = "mut x = 1; loop { if true { y = >x; continue; }; x <- 2; }";
+        assert_eq!(check(src).len(), 1);
+    }
+    #[test]
+    fn never_restored() {
+        let src = "mut foo = 10; { mut bar = &foo; baz = >bar^; };";
+        let [(kind, place, moved, _)] = &check(src)[..] else {
+            panic!("expected one error");
+        };
+        assert_eq!(
+            (*kind, &place[..], *moved),
+            (MoveErrorKind::NeverRestored, "bar^", ">bar^"),
+        );
+        let src = "mut foo = 10; { mut bar = &foo; baz = >bar^; bar^ <- 20; };";
+        assert_eq!(check(src), []);
+        let src = "f(mut bar) => { baz = >bar^; return 1; };";
+        assert_eq!(
+            check(src),
+            [(
+                MoveErrorKind::NeverRestored,
+                "bar^".to_string(),
+                ">bar^",
+                "return 1"
+            )],
+        );
+    }
+    #[test]
+    fn swap() {
+        let src = "mut a = 1; mut b = 2; {
+            mut x = &a;
+            mut y = &b;
+            x^, y^ <- >y^, >x^;
+        };";
+        assert_eq!(check(src), []);
+    }
+    #[test]
+    fn captured() {
+        let src = "x = 1; y = >x; f() => x;";
+        assert_eq!(
+            check(src),
+            [(MoveErrorKind::UseAfterMove, "x".to_string(), ">x", "x")],
+        );
+    }
+}
@@ -0,0 +1,12 @@
+#![warn(clippy::all)]
+#![deny(clippy::correctness)]
+#![forbid(unsafe_code)]
+
+mod borrow;
+mod init;
+mod path;
+
+pub use crate::{
+    borrow::{check_borrows, BorrowError, BorrowErrorKind},
+    init::{check_moves, MoveError, MoveErrorKind},
+};
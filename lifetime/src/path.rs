@@ -0,0 +1,57 @@
+//! Places as paths from a variable, these are how the analyses tell whether
+//! two places are the same or one is part of the other.
+
+use hir::Atom;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) enum Projection {
+    Field(Atom),
+    /// Any element of an array, indices aren't known so every index is
+    /// treated as the same one
+    Index,
+    Deref,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) struct Path {
+    /// The variable, shadowed variables are different bindings
+    pub binding: u32,
+    pub name: Atom,
+    pub projections: Vec<Projection>,
+}
+impl Path {
+    pub fn is_prefix_of(&self, other: &Self) -> bool {
+        self.binding == other.binding
+            && self.projections.len() <= other.projections.len()
+            && self
+                .projections
+                .iter()
+                .zip(&other.projections)
+                .all(|(a, b)| a == b)
+    }
+    /// Whether one is part of the other
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.is_prefix_of(other) || other.is_prefix_of(self)
+    }
+    /// Whether it's within where a reference refers to, past the first
+    /// `skip` projections
+    pub fn has_deref(&self, skip: usize) -> bool {
+        self.projections
+            .iter()
+            .skip(skip)
+            .any(|projection| *projection == Projection::Deref)
+    }
+}
+impl Display for Path {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}", self.name)?;
+        for projection in &self.projections {
+            match projection {
+                Projection::Field(name) => write!(fmt, ".{name}")?,
+                Projection::Index => write!(fmt, "[_]")?,
+                Projection::Deref => write!(fmt, "^")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,174 +1,142 @@
 use crate::{
+    cst::{node, Node, NodeKind},
     expr::expr,
-    ident_keyword::keyword,
-    lex,
+    lex_keyword,
     pattern::pattern,
-    statement::{statement_return, StatementReturn},
+    punct, punct_str,
+    statement::statement_return,
+    Offset,
 };
 use combine::{
-    attempt, between, choice, look_ahead, many, optional,
-    parser::char::{char, string},
-    value, ParseError, Parser, Stream,
-};
-use hir::{
-    expr::{Block, ControlFlow, Expr, ExprKind, For, If, Match, MatchArm, While},
-    statement::Statement,
+    attempt, choice, look_ahead, many, optional, parser::char::char, ParseError, Parser, Stream,
 };
 
-#[derive(Debug, Default, Clone, PartialEq)]
-struct StatementExpr {
-    statement: Vec<Statement<()>>,
-    expr: Option<Expr<()>>,
-}
-impl Extend<StatementReturn> for StatementExpr {
-    fn extend<I>(&mut self, iter: I)
-    where
-        I: IntoIterator<Item = StatementReturn>,
-    {
-        let iter = iter.into_iter();
-        let (min_count, _) = iter.size_hint();
-        self.statement.reserve(min_count);
-        for statement_return in iter {
-            self.statement
-                .extend(self.expr.take().into_iter().map(Statement::Expr));
-            match statement_return {
-                StatementReturn::Statement(statement) => self.statement.push(statement),
-                StatementReturn::Return(expr) => self.expr = Some(expr),
-            }
-        }
-    }
-}
-pub(super) fn block<I>() -> impl Parser<I, Output = Block<()>>
+fn block_<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    between(
-        lex(char('{')),
-        lex(char('}')),
-        many(statement_return(char('}'))),
+    (
+        punct('{'),
+        many::<Vec<_>, _, _>(statement_return(char('}'))),
+        punct('}'),
     )
-    .map(|statement_expr| {
-        let StatementExpr { statement, expr } = statement_expr;
-        Block {
-            statement: statement.into(),
-            expr: expr.map(Box::new),
-        }
-    })
-    .expected("block")
+        .map(|block| node(NodeKind::Block, block))
+        .expected("block")
+}
+combine::parser! {
+    pub(super) fn block[I]()(I) -> Node
+    where [
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    ] {
+        block_()
+    }
 }
-fn if_<I>() -> impl Parser<I, Output = If<()>>
+fn if_<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    let else_part = || {
-        lex(keyword("else")).with(choice((
-            block().map(ControlFlow::Block),
-            if_expression().map(ControlFlow::If),
-        )))
-    };
-    attempt(lex(keyword("if")))
-        .with((expr(0), block(), optional(else_part())))
-        .map(|(condition, body, else_part)| If {
-            condition: Box::new(condition),
-            body,
-            else_part: else_part.map(Box::new),
-        })
+    let else_part = || (lex_keyword("else"), choice((block(), if_expression())));
+    (
+        attempt(lex_keyword("if")),
+        expr(0),
+        block(),
+        optional(else_part()),
+    )
+        .map(|if_| node(NodeKind::If, if_))
 }
 combine::parser! {
-    fn if_expression[I]()(I) -> If<()>
+    fn if_expression[I]()(I) -> Node
     where [
         I: Stream<Token = char>,
         I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
     ] {
         if_()
     }
 }
-fn for_expression<I>() -> impl Parser<I, Output = For<()>>
+fn for_expression<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    attempt(lex(keyword("for")))
-        .with((pattern(), lex(keyword("in")), expr(0), block()))
-        .map(|(pattern, _, expr, body)| For {
-            pattern,
-            expr: Box::new(expr),
-            body,
-        })
+    (
+        attempt(lex_keyword("for")),
+        pattern(),
+        lex_keyword("in"),
+        expr(0),
+        block(),
+    )
+        .map(|for_| node(NodeKind::For, for_))
 }
-fn while_expression<I>() -> impl Parser<I, Output = While<()>>
+fn while_expression<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    attempt(lex(keyword("while")))
-        .with((expr(0), block()))
-        .map(|(condition, body)| While {
-            condition: Box::new(condition),
-            body,
-        })
+    (attempt(lex_keyword("while")), expr(0), block()).map(|while_| node(NodeKind::While, while_))
 }
-fn loop_expression<I>() -> impl Parser<I, Output = Block<()>>
+fn loop_expression<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    attempt(lex(keyword("loop"))).with(block())
+    (attempt(lex_keyword("loop")), block()).map(|loop_| node(NodeKind::Loop, loop_))
 }
-fn match_expression<I>() -> impl Parser<I, Output = Match<()>>
+fn match_expression<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     let arm_expr = || {
         choice((
-            attempt(control_flow())
-                .skip(optional(lex(char(','))))
-                .map(ExprKind::ControlFlow)
-                .map(ExprKind::into_untyped),
-            expr(0).skip(choice((
-                lex(char(',')).with(value(())),
-                look_ahead(char('}')).with(value(())),
-            ))),
+            (attempt(control_flow()), optional(punct(','))),
+            (
+                expr(0),
+                choice((punct(',').map(Some), look_ahead(char('}')).map(|_| None))),
+            ),
         ))
     };
     let arm = || {
-        (pattern().skip(lex(string("=>"))), arm_expr())
-            .map(|(pattern, expr)| MatchArm { pattern, expr })
-    };
-    let body = || {
-        between(lex(char('{')), lex(char('}')), many(arm()))
-            .map(Vec::into)
-            .expected("match body")
-    };
-    attempt(lex(keyword("match")))
-        .with((expr(0), body()))
-        .map(|(expr, arm)| Match {
-            expr: Box::new(expr),
-            arm,
+        (pattern(), punct_str("=>"), arm_expr()).map(|(pattern, arrow, (expr, comma))| {
+            node(NodeKind::MatchArm, (pattern, arrow, expr, comma))
         })
+    };
+    let body = || (punct('{'), many::<Vec<_>, _, _>(arm()), punct('}')).expected("match body");
+    (attempt(lex_keyword("match")), expr(0), body()).map(|(keyword, expr, (open, arms, close))| {
+        node(NodeKind::Match, (keyword, expr, open, arms, close))
+    })
 }
-fn control_flow_<I>() -> impl Parser<I, Output = ControlFlow<()>>
+fn control_flow_<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     choice((
-        block().map(ControlFlow::Block),
-        if_expression().map(ControlFlow::If),
-        for_expression().map(ControlFlow::For),
-        while_expression().map(ControlFlow::While),
-        loop_expression().map(ControlFlow::Loop),
-        match_expression().map(ControlFlow::Match),
+        block(),
+        if_expression(),
+        for_expression(),
+        while_expression(),
+        loop_expression(),
+        match_expression(),
     ))
 }
 combine::parser! {
-    pub(super) fn control_flow[I]()(I) -> ControlFlow<()>
+    pub(super) fn control_flow[I]()(I) -> Node
     where [
         I: Stream<Token = char>,
         I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
     ] {
         control_flow_()
     }
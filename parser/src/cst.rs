@@ -0,0 +1,366 @@
+use hir::{FileId, Span};
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Whitespace,
+    Comment,
+    /// `#!` line at the start of the source
+    Shebang,
+    Ident,
+    Keyword,
+    Integer,
+    Float,
+    Char,
+    String,
+    Punctuation,
+}
+impl TokenKind {
+    /// Whitespaces and comments, tokens that don't affect the meaning of the
+    /// code
+    pub fn is_trivia(self) -> bool {
+        matches!(self, Self::Whitespace | Self::Comment | Self::Shebang)
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub span: Span,
+}
+impl Display for Token {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}", self.text)
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    SourceFile,
+
+    Visibility,
+    Declare,
+    /// `:(a, b):` before the name of a function
+    Generics,
+    FunDeclare,
+    /// `(a, b)` after the name of an alias
+    TypeParams,
+    AliasDeclare,
+    /// `x: Num` or `#y: Num` within a newtype
+    NewtypeField,
+    NewtypeDeclare,
+    /// `Eq(a)` in `given` and `impl`
+    TraitBound,
+    /// `name(a: T) -> U;` within a trait
+    MethodSignature,
+    TraitDeclare,
+    /// `given A(a), B(b):` before an impl or a function
+    Given,
+    ImplDeclare,
+    ModuleDeclare,
+    /// Statements of an inline module along with its brackets
+    ModuleBody,
+    /// Expression used as a statement, without a semicolon it is the value of
+    /// the block if it is the last statement
+    ExprStatement,
+
+    Literal,
+    String,
+    Tag,
+    /// `place <- expr` or `a, b <- c, d`
+    Assign,
+    Array,
+    /// `[a .. b]`, used both as an array and as a slice
+    Range,
+    Unit,
+    Splat,
+    Group,
+    Tuple,
+    Record,
+    /// `name = expr` or `= name`
+    Field,
+    /// `*rest` within collections, patterns and types
+    Spread,
+    Unary,
+    Binary,
+    Var,
+    FieldAccess,
+    Len,
+    Index,
+    Slice,
+    Deref,
+    Call,
+    Block,
+    If,
+    For,
+    While,
+    Loop,
+    Match,
+    MatchArm,
+    Fun,
+    Jump,
+    Never,
+    Annotated,
+
+    LiteralPattern,
+    DiscardPattern,
+    VarPattern,
+    RecordPattern,
+    FieldPattern,
+    TuplePattern,
+    ArrayPattern,
+    /// `(a: T, mut b)`, parameters of a function
+    Param,
+    /// Variable with optional annotation within `Param`
+    Parameter,
+    TagPattern,
+    RefPattern,
+    GroupPattern,
+    AnnotatedPattern,
+
+    DiscardType,
+    NameType,
+    VarType,
+    ArrayType,
+    TupleType,
+    RecordType,
+    FieldType,
+    UnionType,
+    TagType,
+    RefType,
+    FunType,
+    GroupType,
+}
+/// Node of the concrete syntax tree. The tree is lossless, every whitespace
+/// and comment is kept as a token, so the source can be written back exactly
+/// as it is with `Display`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub children: Vec<Element>,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element {
+    Node(Node),
+    Token(Token),
+}
+impl Node {
+    /// Span from the first to the last token, excluding trivia
+    pub fn span(&self) -> Span {
+        match (self.first_token(), self.last_token()) {
+            (Some(first), Some(last)) => {
+                Span::new(first.span.file(), first.span.start(), last.span.end())
+            }
+            _ => Span::default(),
+        }
+    }
+    pub fn first_token(&self) -> Option<&Token> {
+        self.children.iter().find_map(|element| match element {
+            Element::Node(node) => node.first_token(),
+            Element::Token(token) if !token.kind.is_trivia() => Some(token),
+            Element::Token(_) => None,
+        })
+    }
+    pub fn last_token(&self) -> Option<&Token> {
+        self.children
+            .iter()
+            .rev()
+            .find_map(|element| match element {
+                Element::Node(node) => node.last_token(),
+                Element::Token(token) if !token.kind.is_trivia() => Some(token),
+                Element::Token(_) => None,
+            })
+    }
+    /// Child nodes
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter_map(|element| match element {
+            Element::Node(node) => Some(node),
+            Element::Token(_) => None,
+        })
+    }
+    /// Child tokens, excluding trivia
+    pub fn tokens(&self) -> impl Iterator<Item = &Token> {
+        self.children.iter().filter_map(|element| match element {
+            Element::Token(token) if !token.kind.is_trivia() => Some(token),
+            _ => None,
+        })
+    }
+    /// Child token with the text
+    pub fn token(&self, text: &str) -> Option<&Token> {
+        self.tokens().find(|token| token.text == text)
+    }
+    /// Child token of the kind
+    pub fn token_of(&self, kind: TokenKind) -> Option<&Token> {
+        self.tokens().find(|token| token.kind == kind)
+    }
+    /// Makes the token spans relative to `base`, the offset where the
+    /// source starts, and puts them in `file`
+    pub fn relocate(&mut self, base: usize, file: FileId) {
+        for element in &mut self.children {
+            match element {
+                Element::Node(node) => node.relocate(base, file),
+                Element::Token(token) => {
+                    let span = token.span;
+                    token.span = Span::new(file, span.start() - base, span.end() - base);
+                }
+            }
+        }
+    }
+    /// Every token within the node, including trivia
+    pub fn descendant_tokens(&self) -> Box<dyn Iterator<Item = &Token> + '_> {
+        Box::new(self.children.iter().flat_map(|element| match element {
+            Element::Node(node) => node.descendant_tokens(),
+            Element::Token(token) => Box::new(std::iter::once(token)),
+        }))
+    }
+}
+impl Display for Node {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        for element in &self.children {
+            write!(fmt, "{element}")?;
+        }
+        Ok(())
+    }
+}
+impl Display for Element {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Node(node) => write!(fmt, "{node}"),
+            Self::Token(token) => write!(fmt, "{token}"),
+        }
+    }
+}
+/// Parts of a node as they're parsed, flattened into its children
+pub(crate) trait Parts {
+    fn push_to(self, children: &mut Vec<Element>);
+}
+impl Parts for Element {
+    fn push_to(self, children: &mut Vec<Element>) {
+        children.push(self);
+    }
+}
+impl Parts for Node {
+    fn push_to(self, children: &mut Vec<Element>) {
+        children.push(Element::Node(self));
+    }
+}
+impl Parts for Token {
+    fn push_to(self, children: &mut Vec<Element>) {
+        children.push(Element::Token(self));
+    }
+}
+impl<T: Parts> Parts for Vec<T> {
+    fn push_to(self, children: &mut Vec<Element>) {
+        for part in self {
+            part.push_to(children);
+        }
+    }
+}
+impl<T: Parts> Parts for Option<T> {
+    fn push_to(self, children: &mut Vec<Element>) {
+        if let Some(part) = self {
+            part.push_to(children);
+        }
+    }
+}
+macro_rules! tuple_parts {
+    ($($name:ident),*) => {
+        impl<$($name: Parts),*> Parts for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn push_to(self, children: &mut Vec<Element>) {
+                let ($($name,)*) = self;
+                $($name.push_to(children);)*
+            }
+        }
+    };
+}
+tuple_parts!(A, B);
+tuple_parts!(A, B, C);
+tuple_parts!(A, B, C, D);
+tuple_parts!(A, B, C, D, E);
+tuple_parts!(A, B, C, D, E, F);
+tuple_parts!(A, B, C, D, E, F, G);
+pub(crate) fn node(kind: NodeKind, parts: impl Parts) -> Node {
+    let mut children = Vec::new();
+    parts.push_to(&mut children);
+    Node { kind, children }
+}
+pub(crate) fn elements(parts: impl Parts) -> Vec<Element> {
+    let mut children = Vec::new();
+    parts.push_to(&mut children);
+    children
+}
+#[cfg(test)]
+mod test {
+    use crate::{
+        cst::{Node, NodeKind, TokenKind},
+        lower, syntax_tree,
+    };
+    use combine::EasyParser;
+    use hir::{fold::Fold, span::EraseSpans, statement::Statement};
+
+    fn parse(src: &str) -> Node {
+        let (source_file, rest) = syntax_tree().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        source_file
+    }
+    #[test]
+    fn lossless() {
+        let src = r#"#!/usr/bin/env butter
+-- about
+pub(app . ui) x = ( a  +b ) * -- why
+  c ;
+r = (= x,y = 'y', *z,);
+(= a, b = [c, *_]) : T = r;
+:(a): f( mut a : &:mut /l a, ) -> a =>{a}
+alias Opt (a) = @val a | @none;
+mod m { n = "\x41ß\n"; };
+m = mod ( );
+main() => {
+    for i in [0 .< 10] { s[i ..] <- i.len^ ; }
+    a, b <- b, a;
+    if ! c { } else if d{ break } else { loop { continue; } };
+    match e { @val x => x , _ => { 1.5e3 } }
+    while true { return; }
+    if false { never }
+    ((x) => x)(1)
+};
+"#;
+        assert_eq!(parse(src).to_string(), src);
+    }
+    #[test]
+    fn sugar() {
+        let short = parse("r = (= a);");
+        let long = parse("r = (a = a);");
+        let erase = |source_file: &Node| -> Vec<Statement<()>> {
+            lower(source_file)
+                .into_iter()
+                .map(|statement| EraseSpans.fold_statement(statement))
+                .collect()
+        };
+        assert_eq!(erase(&short), erase(&long));
+        let field = |source_file: &Node| {
+            let declare = source_file.nodes().next().unwrap();
+            let record = declare.nodes().nth(1).unwrap();
+            record.nodes().next().unwrap().clone()
+        };
+        assert_eq!(field(&short).token_of(TokenKind::Ident), None);
+        assert!(field(&long).token_of(TokenKind::Ident).is_some());
+    }
+    #[test]
+    fn group() {
+        let src = "x = (a) -- comment\n;";
+        let source_file = parse(src);
+        let declare = source_file.nodes().next().unwrap();
+        assert_eq!(declare.span().range(), 0..src.len());
+        let group = declare.nodes().nth(1).unwrap();
+        assert_eq!(group.kind, NodeKind::Group);
+        assert_eq!(group.span().range(), 4..7);
+        let [statement] = &lower(&source_file)[..] else {
+            panic!("expected one statement");
+        };
+        let hir::statement::Statement::Declare(declare) = statement else {
+            panic!("expected declaration");
+        };
+        assert_eq!(declare.expr.span.range(), 5..6);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     control_flow::control_flow,
+    cst::{node, Node, NodeKind, TokenKind},
     expr::{
         array::{array, range},
         infix::{expr_0, expr_6, infix_expr_op},
@@ -7,185 +8,166 @@ use crate::{
         string::{char_literal, string_literal},
         tuple::tuple,
     },
-    ident_keyword::{ident, keyword},
-    lex,
+    ident_keyword::keyword,
+    lex, lex_ident, lex_keyword,
     number::{float, integer_u64},
     pattern::parameter,
+    punct, punct_str, token,
+    ty::ty,
+    Offset,
 };
-use combine::{
-    attempt, between, chainl1, choice, optional,
-    parser::char::{char, string},
-    value, ParseError, Parser, Stream,
-};
-use hir::expr::{
-    Element, ElementKind, Expr, ExprKind, Fun, Jump, Literal, PlaceExpr, Tag, Unary, UnaryType,
-};
+use combine::{attempt, chainl1, choice, optional, parser::char::char, ParseError, Parser, Stream};
 
 mod array;
 mod infix;
 mod record;
-mod string;
+pub(crate) mod string;
 mod tuple;
 
-fn literal<I>() -> impl Parser<I, Output = Literal>
+fn literal<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     choice((
-        char_literal().map(Literal::UInt),
-        float().map(Literal::Float),
-        integer_u64().map(Literal::UInt),
+        lex(token(TokenKind::Char, char_literal())),
+        lex(token(TokenKind::Float, float())),
+        lex(token(TokenKind::Integer, integer_u64())),
         // TODO: minus integer parser
-        attempt(keyword("false")).with(value(Literal::False)),
-        attempt(keyword("true")).with(value(Literal::True)),
+        attempt(lex_keyword("false")),
+        attempt(lex_keyword("true")),
     ))
+    .map(|literal| node(NodeKind::Literal, literal))
 }
-fn jump<I>() -> impl Parser<I, Output = Jump<()>>
+fn jump<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     choice((
-        lex(keyword("break"))
-            .with(optional(expr(0)))
-            .map(|expr| expr.map(Box::new))
-            .map(Jump::Break),
-        lex(keyword("continue")).with(value(Jump::Continue)),
-        lex(keyword("return"))
-            .with(optional(expr(0)))
-            .map(|expr| expr.map(Box::new))
-            .map(Jump::Return),
+        (lex_keyword("break"), optional(expr(0))),
+        lex_keyword("continue").map(|keyword| (keyword, None)),
+        (lex_keyword("return"), optional(expr(0))),
     ))
+    .map(|jump| node(NodeKind::Jump, jump))
 }
-fn unary<I>() -> impl Parser<I, Output = Unary<()>>
+fn unary<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     let kind = || {
         choice((
-            char('!').with(value(UnaryType::Not)),
-            char('&').with(value(UnaryType::Ref)),
-            char('-').with(value(UnaryType::Minus)),
-            char('>').with(value(UnaryType::Move)),
-            attempt(keyword("clone")).with(value(UnaryType::Clone)),
+            token(
+                TokenKind::Punctuation,
+                choice([char('!'), char('&'), char('-'), char('>')]),
+            ),
+            token(TokenKind::Keyword, attempt(keyword("clone"))),
         ))
     };
-    (lex(kind()), expr(6)).map(|(kind, expr)| Unary {
-        kind,
-        expr: Box::new(expr),
-    })
+    (lex(kind()), expr(6)).map(|unary| node(NodeKind::Unary, unary))
 }
-fn tag<I>() -> impl Parser<I, Output = Tag<()>>
+fn tag<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    lex(char('@'))
-        .with((lex(ident()), optional(expr(6))))
-        .map(|(tag, expr)| Tag {
-            tag,
-            expr: expr.map(Box::new),
-        })
+    (punct('@'), lex_ident(), optional(expr(6))).map(|tag| node(NodeKind::Tag, tag))
 }
-fn fun<I>() -> impl Parser<I, Output = Fun<()>>
+fn fun<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    (attempt(parameter().skip(lex(string("=>")))), expr(0)).map(|(param, body)| Fun {
-        param,
-        body: Box::new(body),
-    })
+    (
+        attempt((
+            parameter(),
+            optional((punct_str("->"), ty())),
+            punct_str("=>"),
+        )),
+        expr(0),
+    )
+        .map(|fun| node(NodeKind::Fun, fun))
 }
-fn array_range<I>() -> impl Parser<I, Output = Expr<()>>
+fn array_range<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    choice((
-        attempt(range()).map(ExprKind::ArrayRange),
-        array().map(ExprKind::Array),
-    ))
-    .map(ExprKind::into_untyped)
+    choice((attempt(range()), array()))
 }
-fn tuple_record_group<I>() -> impl Parser<I, Output = Expr<()>>
+fn tuple_record_group<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     choice((
-        attempt((lex(char('(')), lex(char(')'))))
-            .with(value(ExprKind::Unit.into_untyped()))
+        attempt((punct('('), punct(')')))
+            .map(|unit| node(NodeKind::Unit, unit))
             .silent(),
-        attempt(between(
-            (lex(char('(')), lex(char('*'))),
-            (optional(lex(char(','))), lex(char(')'))),
+        attempt((
+            punct('('),
+            punct('*'),
             expr(0),
+            optional(punct(',')),
+            punct(')'),
         ))
-        .map(Box::new)
-        .map(ExprKind::Splat)
-        .map(ExprKind::into_untyped)
+        .map(|splat| node(NodeKind::Splat, splat))
         .silent(),
-        attempt(between(lex(char('(')), lex(char(')')), expr(0))).expected("group"),
-        attempt(tuple())
-            .map(ExprKind::Tuple)
-            .map(ExprKind::into_untyped),
-        record().map(ExprKind::Record).map(ExprKind::into_untyped),
+        attempt((punct('('), expr(0), punct(')')))
+            .map(|group| node(NodeKind::Group, group))
+            .expected("group"),
+        attempt(tuple()),
+        record(),
     ))
 }
-fn prefix_expr_<I>() -> impl Parser<I, Output = Expr<()>>
+fn prefix_expr_<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     choice((
-        fun().map(|fun| ExprKind::Fun(fun).into_untyped()),
+        fun(),
         tuple_record_group(),
         array_range(),
-        lex(string_literal()).map(|vec| {
-            let vec = vec
-                .into_iter()
-                .map(|byte| Element {
-                    expr: ExprKind::Literal(Literal::UInt(byte as u64)).into_untyped(),
-                    kind: ElementKind::Element,
-                })
-                .collect();
-            ExprKind::Array(vec).into_untyped()
-        }),
-        unary().map(ExprKind::Unary).map(ExprKind::into_untyped),
-        tag().map(ExprKind::Tag).map(ExprKind::into_untyped),
-        attempt(lex(ident()))
-            .map(PlaceExpr::Var)
-            .map(ExprKind::Place)
-            .map(ExprKind::into_untyped),
-        control_flow()
-            .map(ExprKind::ControlFlow)
-            .map(ExprKind::into_untyped),
-        lex(literal())
-            .map(ExprKind::Literal)
-            .map(ExprKind::into_untyped),
-        jump().map(ExprKind::Jump).map(ExprKind::into_untyped),
+        lex(token(TokenKind::String, string_literal()))
+            .map(|string| node(NodeKind::String, string)),
+        unary(),
+        tag(),
+        attempt(lex_ident()).map(|var| node(NodeKind::Var, var)),
+        control_flow(),
+        literal(),
+        jump(),
+        attempt(lex_keyword("never")).map(|never| node(NodeKind::Never, never)),
     ))
 }
 combine::parser! {
-    fn prefix_expr[I]()(I) -> Expr<()>
+    fn prefix_expr[I]()(I) -> Node
     where [
         I: Stream<Token = char>,
         I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
     ] {
         prefix_expr_()
     }
 }
-fn expr_<I>(precedence: u8) -> impl Parser<I, Output = Expr<()>>
+fn expr_<I>(precedence: u8) -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     match precedence {
         0 => expr_0().left().left(),
-        1..=5 => chainl1(expr(precedence + 1), lex(infix_expr_op(precedence)))
+        1..=5 => chainl1(expr(precedence + 1), infix_expr_op(precedence))
             .right()
             .left(),
         6 => expr_6().left().right(),
@@ -193,10 +175,11 @@ where
     }
 }
 combine::parser! {
-    pub(super) fn expr[I](precedence: u8)(I) -> Expr<()>
+    pub(super) fn expr[I](precedence: u8)(I) -> Node
     where [
         I: Stream<Token = char>,
         I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
     ] {
         expr_(*precedence)
     }
@@ -204,11 +187,25 @@ combine::parser! {
 #[cfg(test)]
 mod test {
     use crate::{
-        expr::{expr, ExprKind},
+        lower,
         test::{var_expr, var_place},
+        Offset,
+    };
+    use combine::{EasyParser, ParseError, Parser, Stream};
+    use hir::{
+        expr::{Assign, Binary, BinaryType, Expr, ExprKind},
+        fold::Fold,
+        span::EraseSpans,
     };
-    use combine::EasyParser;
-    use hir::expr::{Assign, Binary, BinaryType};
+
+    fn expr<I>(precedence: u8) -> impl Parser<I, Output = Expr<()>>
+    where
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    {
+        super::expr(precedence).map(|expr| EraseSpans.fold_expr(lower::expr(&expr)))
+    }
 
     #[test]
     fn group() {
@@ -1,65 +1,51 @@
-use crate::{expr::expr, lex};
-use combine::{
-    between, choice, optional, parser::char::char, sep_end_by, value, ParseError, Parser, Stream,
+use crate::{
+    cst::{node, Node, NodeKind, TokenKind},
+    expr::expr,
+    lex, punct, sep_end, token, Offset,
 };
-use hir::expr::{Bound, BoundType, Element, ElementKind, Range};
+use combine::{choice, optional, parser::char::char, ParseError, Parser, Stream};
 
-pub(super) fn range_operator<I>() -> impl Parser<I, Output = (BoundType, BoundType)>
+pub(super) fn range_operator<I>() -> impl Parser<I, Output = (char, char)>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     (
-        choice((
-            char('.').with(value(BoundType::Inclusive)),
-            char('>').with(value(BoundType::Exclusive)),
-        )),
-        choice((
-            char('.').with(value(BoundType::Inclusive)),
-            char('<').with(value(BoundType::Exclusive)),
-        )),
+        choice((char('.'), char('>'))),
+        choice((char('.'), char('<'))),
     )
         .expected("range operator")
 }
-pub(super) fn range<I>() -> impl Parser<I, Output = Range<()>>
+pub(super) fn range<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    let range = || {
-        (optional(expr(0)), lex(range_operator()), optional(expr(0))).map(
-            |(left, (left_in, right_in), right)| {
-                let left = left.map(|left| Bound {
-                    kind: left_in,
-                    expr: Box::new(left),
-                });
-                let right = right.map(|right| Bound {
-                    kind: right_in,
-                    expr: Box::new(right),
-                });
-                Range { left, right }
-            },
-        )
-    };
-    between(lex(char('[')), lex(char(']')), range()).expected("range array")
+    (
+        punct('['),
+        optional(expr(0)),
+        lex(token(TokenKind::Punctuation, range_operator())),
+        optional(expr(0)),
+        punct(']'),
+    )
+        .map(|range| node(NodeKind::Range, range))
+        .expected("range array")
 }
-pub(super) fn array<I>() -> impl Parser<I, Output = Box<[Element<()>]>>
+pub(super) fn array<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    let element_kind = || {
-        choice((
-            char('*').with(value(ElementKind::Splat)),
-            value(ElementKind::Element),
-        ))
+    let element = || {
+        (optional(punct('*')), expr(0)).map(|(splat, expr)| match splat {
+            Some(splat) => node(NodeKind::Spread, (splat, expr)),
+            None => expr,
+        })
     };
-    let element = || (lex(element_kind()), expr(0)).map(|(kind, expr)| Element { expr, kind });
-    between(
-        lex(char('[')),
-        lex(char(']')),
-        sep_end_by(element(), lex(char(','))),
-    )
-    .map(Vec::into)
-    .expected("array")
+    (punct('['), sep_end(element, || punct(',')), punct(']'))
+        .map(|array| node(NodeKind::Array, array))
+        .expected("array")
 }
@@ -1,22 +1,22 @@
-use crate::{expr::expr, ident_keyword::ident, lex, sep_optional_between};
-use combine::{
-    between, error::StreamError, optional, parser::char::char, stream::StreamErrorFor, ParseError,
-    Parser, Stream,
-};
-use hir::{
-    expr::{Collection, Field, WithSplat},
-    Atom,
+use crate::{
+    cst::{node, Node, NodeKind},
+    expr::expr,
+    lex_field_name, lower, punct, sep_optional_between, Offset,
 };
+use combine::{error::StreamError, optional, stream::StreamErrorFor, ParseError, Parser, Stream};
+use std::collections::HashSet;
 
-pub(super) fn record<I>() -> impl Parser<I, Output = Collection<Field<()>, ()>>
+pub(super) fn record<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     let field = || {
-        (optional(lex(ident())), lex(char('=')).with(expr(0))).and_then(|(name, expr)| {
-            match name.map(Atom::from).or_else(|| expr.field_name()) {
-                Some(name) => Ok(Field { name, expr }),
+        (optional(lex_field_name()), punct('='), expr(0)).and_then(|field| {
+            let field = node(NodeKind::Field, field);
+            match lower::field_name(&field) {
+                Some(_) => Ok(field),
                 None => Err(<StreamErrorFor<I>>::message_static_message(
                     "couldn't infer field name",
                 )),
@@ -24,27 +24,27 @@ where
         })
     };
     let fields = || {
-        sep_optional_between(field, lex(char('*')).with(expr(0)), || lex(char(',')))
-            .map(|(left, rest_right)| {
-                let left: Vec<_> = left;
-                match rest_right {
-                    Some((rest, right)) => Collection::WithSplat(WithSplat {
-                        left: left.into(),
-                        splat: Box::new(rest),
-                        right: right.into(),
-                    }),
-                    None => Collection::Collection(left.into()),
-                }
-            })
-            .and_then(|record| {
-                if record.all_name_unique() {
-                    Ok(record)
-                } else {
-                    Err(<StreamErrorFor<I>>::message_static_message(
-                        "duplicate field name",
-                    ))
-                }
-            })
+        sep_optional_between(
+            field,
+            (punct('*'), expr(0)).map(|splat| node(NodeKind::Spread, splat)),
+            || punct(','),
+        )
     };
-    between(lex(char('(')), lex(char(')')), fields()).expected("record")
+    (punct('('), fields(), punct(')'))
+        .map(|record| node(NodeKind::Record, record))
+        .and_then(|record| {
+            let mut names = HashSet::new();
+            let all_name_unique = record
+                .nodes()
+                .filter(|field| field.kind == NodeKind::Field)
+                .all(|field| names.insert(lower::field_name(field)));
+            if all_name_unique {
+                Ok(record)
+            } else {
+                Err(<StreamErrorFor<I>>::message_static_message(
+                    "duplicate field name",
+                ))
+            }
+        })
+        .expected("record")
 }
@@ -1,4 +1,4 @@
-use crate::number::parse_digit;
+use crate::{number::parse_digit, Offset};
 use combine::{
     between, choice,
     error::StreamError,
@@ -18,6 +18,7 @@ fn char_inside<I>(delimiter: char) -> impl Parser<I, Output = Char>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     let simple_escape = || {
         choice((
@@ -42,23 +43,25 @@ where
         satisfy(move |ch: char| ch != delimiter && ch != '\n').map(Char::Char),
     ))
 }
-pub(super) fn char_literal<I>() -> impl Parser<I, Output = u64>
+pub(crate) fn char_literal<I>() -> impl Parser<I, Output = char>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     between(char('\''), char('\''), char_inside('\''))
         .and_then(|ch| match ch {
-            Char::Byte(byte) => Ok(byte as u64),
-            Char::Char(ch) => {
-                if ch.len_utf8() == 1 {
-                    Ok(ch as u8 as u64)
+            Char::Byte(byte) => {
+                if byte.is_ascii() {
+                    Ok(byte as char)
                 } else {
+                    // a lone code unit beyond ASCII is only part of a character
                     Err(<StreamErrorFor<I>>::message_static_message(
-                        "multiple character in char literal",
+                        "code unit in char literal is not a character",
                     ))
                 }
             }
+            Char::Char(ch) => Ok(ch),
         })
         .expected("char")
 }
@@ -85,10 +88,11 @@ impl Extend<Char> for StringLiteral {
         }
     }
 }
-pub(super) fn string_literal<I>() -> impl Parser<I, Output = Vec<u8>>
+pub(crate) fn string_literal<I>() -> impl Parser<I, Output = Vec<u8>>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     between(char('"'), char('"'), many(char_inside('"')))
         .map(|StringLiteral(vec)| vec)
@@ -96,9 +100,15 @@ where
 }
 #[cfg(test)]
 mod test {
-    use crate::expr::string_literal;
+    use crate::expr::{char_literal, string_literal};
     use combine::EasyParser;
 
+    #[test]
+    fn char() {
+        assert_eq!(char_literal().easy_parse("'ß'"), Ok(('ß', "")));
+        assert_eq!(char_literal().easy_parse("'\\x41'"), Ok(('A', "")));
+        assert!(char_literal().easy_parse("'\\xc3'").is_err());
+    }
     #[test]
     fn string() {
         let src = r#""\x41Aßℝ💣\n""#;
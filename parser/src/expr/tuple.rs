@@ -1,26 +1,24 @@
-use crate::{expr::expr, lex, sep_optional_between};
-use combine::{between, parser::char::char, ParseError, Parser, Stream};
-use hir::expr::{Collection, Expr, WithSplat};
+use crate::{
+    cst::{node, Node, NodeKind},
+    expr::expr,
+    punct, sep_optional_between, Offset,
+};
+use combine::{ParseError, Parser, Stream};
 
-pub(super) fn tuple<I>() -> impl Parser<I, Output = Collection<Expr<()>, ()>>
+pub(super) fn tuple<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     let fields = || {
-        sep_optional_between(|| expr(0), lex(char('*')).with(expr(0)), || lex(char(','))).map(
-            |(left, rest_right)| {
-                let left: Vec<_> = left;
-                match rest_right {
-                    Some((rest, right)) => Collection::WithSplat(WithSplat {
-                        left: left.into(),
-                        splat: Box::new(rest),
-                        right: right.into(),
-                    }),
-                    None => Collection::Collection(left.into()),
-                }
-            },
+        sep_optional_between(
+            || expr(0),
+            (punct('*'), expr(0)).map(|splat| node(NodeKind::Spread, splat)),
+            || punct(','),
         )
     };
-    between(lex(char('(')), lex(char(')')), fields()).expected("tuple")
+    (punct('('), fields(), punct(')'))
+        .map(|tuple| node(NodeKind::Tuple, tuple))
+        .expected("tuple")
 }
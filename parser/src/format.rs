@@ -0,0 +1,1085 @@
+use crate::cst::{Element, Node, NodeKind, Token, TokenKind};
+use hir::{
+    pretty_print::{self, List, ListItem, PrettyPrintState, PrettyPrintTree},
+    Span,
+};
+use std::io::{self, Write};
+
+type Tree = Box<dyn PrettyPrintTree>;
+
+/// Writes the source file back with its whitespaces normalized and lines
+/// broken to fit `width` where possible. Tokens and comments are kept as they
+/// are written.
+pub fn format(source_file: &Node, width: usize) -> String {
+    let mut formatter = Formatter {
+        src: source_file.to_string(),
+        comments: source_file
+            .descendant_tokens()
+            .filter(|token| token.kind == TokenKind::Comment)
+            .map(|comment| {
+                let start = comment.span.start();
+                Span::new(
+                    comment.span.file(),
+                    start,
+                    start + comment.text.trim_end().len(),
+                )
+            })
+            .collect(),
+        next: 0,
+    };
+    let mut out = String::new();
+    let mut previous = None;
+    let shebang = source_file.descendant_tokens().next();
+    if let Some(shebang) = shebang.filter(|token| token.kind == TokenKind::Shebang) {
+        out.push_str(shebang.text.trim_end());
+        out.push('\n');
+        previous = Some(shebang.span.end());
+    }
+    let mut items = Vec::new();
+    formatter.statements(&mut items, &mut previous, source_file.nodes());
+    formatter.comments_before(&mut items, &mut previous, usize::MAX);
+    for item in items {
+        if let ListItem::Item(tree) = item {
+            let mut buffer = Vec::new();
+            tree.write(&mut buffer, "    ", width).unwrap();
+            out.push_str(&String::from_utf8(buffer).unwrap());
+        }
+        out.push('\n');
+    }
+    out
+}
+#[derive(Clone, Copy)]
+enum Part<'a> {
+    Node(&'a Node),
+    Token(&'a Token),
+}
+impl Part<'_> {
+    fn is(&self, text: &str) -> bool {
+        matches!(self, Part::Token(token) if token.text == text)
+    }
+    fn is_node(&self, kind: NodeKind) -> bool {
+        matches!(self, Part::Node(node) if node.kind == kind)
+    }
+    fn span(&self) -> Span {
+        match self {
+            Part::Node(node) => node.span(),
+            Part::Token(token) => token.span,
+        }
+    }
+}
+/// Children of the node without whitespaces and comments
+fn parts(node: &Node) -> Vec<Part<'_>> {
+    node.children
+        .iter()
+        .filter_map(|element| match element {
+            Element::Node(node) => Some(Part::Node(node)),
+            Element::Token(token) if !token.kind.is_trivia() => Some(Part::Token(token)),
+            Element::Token(_) => None,
+        })
+        .collect()
+}
+fn text(text: impl Into<String>) -> Tree {
+    Box::new(text.into())
+}
+fn line(trees: impl IntoIterator<Item = Tree>) -> Tree {
+    Box::new(Line(trees.into_iter().collect()))
+}
+// `List` takes its brackets as static strings
+fn bracket(text: &str) -> &'static str {
+    match text {
+        "(" => "(",
+        ")" => ")",
+        "[" => "[",
+        "]" => "]",
+        "{" => "{",
+        "}" => "}",
+        _ => unreachable!("`{text}` is not a bracket"),
+    }
+}
+fn has_blank_line(text: &str) -> bool {
+    let lines: Vec<_> = text.split('\n').collect();
+    lines.len() > 2
+        && lines[1..lines.len() - 1]
+            .iter()
+            .any(|line| line.trim().is_empty())
+}
+// two tokens written next to each other that would be lexed as something
+// else, like `-` and `-e` making a comment
+fn glues(left: &str, right: &str) -> bool {
+    let (Some(last), Some(first)) = (left.chars().last(), right.chars().next()) else {
+        return false;
+    };
+    let word = |ch: char| ch.is_alphanumeric() || ch == '_' || ch == '#';
+    word(last) && word(first)
+        || matches!(
+            &format!("{last}{first}")[..],
+            "--" | "->"
+                | "=>"
+                | "<-"
+                | "=="
+                | "!="
+                | "<="
+                | ">="
+                | "&&"
+                | "||"
+                | "++"
+                | "//"
+                | ".."
+                | ".<"
+                | ">."
+                | "><"
+        )
+}
+fn first_text(part: Part<'_>) -> &str {
+    match part {
+        Part::Node(node) => node.first_token().map_or("", |token| &token.text),
+        Part::Token(token) => &token.text,
+    }
+}
+fn precedence(op: &str) -> u8 {
+    match op {
+        "//" | "*" | "/" | "%" => 5,
+        "++" | "+" | "-" => 4,
+        "==" | "!=" | "<=" | ">=" | "<" | ">" => 3,
+        "&&" | "&" => 2,
+        "||" | "|" => 1,
+        _ => unreachable!("`{op}` is not a binary operator"),
+    }
+}
+fn binary_op(node: &Node) -> &str {
+    match parts(node)[1] {
+        Part::Token(token) => &token.text,
+        Part::Node(_) => unreachable!(),
+    }
+}
+// expressions and types that can start on the line of what's before them
+// and break their own lines
+fn breaks_itself(part: Part) -> bool {
+    match part {
+        Part::Node(node) => match node.kind {
+            NodeKind::Array
+            | NodeKind::Tuple
+            | NodeKind::Record
+            | NodeKind::Call
+            | NodeKind::Block
+            | NodeKind::If
+            | NodeKind::For
+            | NodeKind::While
+            | NodeKind::Loop
+            | NodeKind::Match
+            | NodeKind::Fun
+            | NodeKind::TupleType
+            | NodeKind::RecordType => true,
+            NodeKind::UnionType => node.first_token().is_some_and(|token| token.text == "("),
+            _ => false,
+        },
+        Part::Token(_) => false,
+    }
+}
+struct Formatter {
+    src: String,
+    comments: Vec<Span>,
+    // index of the first comment not yet written
+    next: usize,
+}
+impl Formatter {
+    fn comment(&self, span: Span) -> Tree {
+        pretty_print::comment(&self.src[span.range()])
+    }
+    fn blank_line(&self, items: &mut Vec<ListItem>, previous: Option<usize>, start: usize) {
+        if let Some(previous) = previous {
+            if has_blank_line(&self.src[previous..start]) {
+                items.push(ListItem::Blank);
+            }
+        }
+    }
+    /// Writes the comments before `position` on their own lines
+    fn comments_before(
+        &mut self,
+        items: &mut Vec<ListItem>,
+        previous: &mut Option<usize>,
+        position: usize,
+    ) {
+        while let Some(&comment) = self.comments.get(self.next) {
+            if comment.start() >= position {
+                break;
+            }
+            self.next += 1;
+            self.blank_line(items, *previous, comment.start());
+            items.push(ListItem::Item(self.comment(comment)));
+            *previous = Some(comment.end());
+        }
+    }
+    /// Writes an item of a block along with the comments around it. A comment
+    /// on the same line after the item stays on that line, comments inside
+    /// the item that aren't within a nested block are moved after it.
+    fn item(&mut self, items: &mut Vec<ListItem>, previous: &mut Option<usize>, node: &Node) {
+        let span = node.span();
+        self.comments_before(items, previous, span.start());
+        self.blank_line(items, *previous, span.start());
+        let mut tree = self.tree(node);
+        let mut inner = Vec::new();
+        while let Some(&comment) = self.comments.get(self.next) {
+            if comment.start() >= span.end() {
+                break;
+            }
+            self.next += 1;
+            inner.push(comment);
+        }
+        let mut end = span.end();
+        if let Some(&comment) = self.comments.get(self.next) {
+            let between = &self.src[end..comment.start()];
+            if !between.contains('\n') && matches!(between.trim(), "" | ";" | ",") {
+                self.next += 1;
+                tree = line([tree, text(" "), self.comment(comment)]);
+                end = comment.end();
+            }
+        }
+        items.push(ListItem::Item(tree));
+        for comment in inner {
+            items.push(ListItem::Item(self.comment(comment)));
+            end = end.max(comment.end());
+        }
+        *previous = Some(end);
+    }
+    fn statements<'a>(
+        &mut self,
+        items: &mut Vec<ListItem>,
+        previous: &mut Option<usize>,
+        nodes: impl IntoIterator<Item = &'a Node>,
+    ) {
+        for node in nodes {
+            self.item(items, previous, node);
+        }
+    }
+    /// Statements between brackets, each on its own line unless there is
+    /// only a value
+    fn body(&mut self, parts: &[Part], multiline: bool) -> Tree {
+        let (Some(Part::Token(open)), Some(Part::Token(close))) = (parts.first(), parts.last())
+        else {
+            unreachable!()
+        };
+        let mut items = Vec::new();
+        let mut previous = None;
+        let nodes = parts[1..parts.len() - 1].iter().map(|part| match part {
+            Part::Node(node) => *node,
+            Part::Token(_) => unreachable!(),
+        });
+        self.statements(&mut items, &mut previous, nodes);
+        self.comments_before(&mut items, &mut previous, close.span.start());
+        Box::new(List {
+            open: bracket(&open.text),
+            close: bracket(&close.text),
+            separator: "",
+            padded: true,
+            trailing: false,
+            multiline,
+            items,
+        })
+    }
+    fn block(&mut self, node: &Node) -> Tree {
+        let parts = parts(node);
+        let statements = &parts[1..parts.len() - 1];
+        // the last expression without semicolon is the value of the block
+        let value = statements.last().is_some_and(|part| match part {
+            Part::Node(node) => node.kind == NodeKind::ExprStatement && node.token(";").is_none(),
+            Part::Token(_) => false,
+        });
+        let multiline = statements.len() > usize::from(value);
+        self.body(&parts, multiline)
+    }
+    /// Comments before `position` on their own lines within a list
+    fn list_comments(&mut self, items: &mut Vec<ListItem>, position: usize) {
+        while let Some(&comment) = self.comments.get(self.next) {
+            if comment.start() >= position {
+                break;
+            }
+            self.next += 1;
+            items.push(ListItem::Comment(self.comment(comment)));
+        }
+    }
+    /// Items between the brackets at the ends of `parts`, separated by
+    /// commas. Comments stay between the items, which puts each item on its
+    /// own line.
+    fn list(&mut self, parts: &[Part], trailing: bool) -> List {
+        let (Some(Part::Token(open)), Some(Part::Token(close))) = (parts.first(), parts.last())
+        else {
+            unreachable!()
+        };
+        let mut items = Vec::new();
+        for &part in parts[1..parts.len() - 1]
+            .iter()
+            .filter(|part| !part.is(","))
+        {
+            let span = part.span();
+            self.list_comments(&mut items, span.start());
+            items.push(ListItem::Item(self.part(part)));
+            // comments within the item that it doesn't keep are moved after it
+            let inner = self.next;
+            self.list_comments(&mut items, span.end());
+            if let Some(&comment) = self.comments.get(self.next).filter(|_| self.next == inner) {
+                let between = &self.src[span.end()..comment.start()];
+                if !between.contains('\n') && matches!(between.trim(), "" | ",") {
+                    self.next += 1;
+                    items.push(ListItem::TrailingComment(self.comment(comment)));
+                }
+            }
+        }
+        self.list_comments(&mut items, close.span.start());
+        List {
+            open: bracket(&open.text),
+            close: bracket(&close.text),
+            separator: ",",
+            padded: false,
+            trailing,
+            multiline: false,
+            items,
+        }
+    }
+    // a tuple of one element keeps its comma
+    fn tuple(&mut self, parts: &[Part]) -> Tree {
+        let trailing = parts.len() == 4 && parts[2].is(",") && !parts[1].is_node(NodeKind::Spread);
+        Box::new(self.list(parts, trailing))
+    }
+    fn part(&mut self, part: Part) -> Tree {
+        match part {
+            Part::Node(node) => self.tree(node),
+            Part::Token(token) => text(token.text.clone()),
+        }
+    }
+    /// The parts next to each other
+    fn glued(&mut self, parts: &[Part]) -> Tree {
+        let trees: Vec<_> = parts.iter().map(|&part| self.part(part)).collect();
+        line(trees)
+    }
+    /// The parts separated by spaces, except before `,` and `;` and after
+    /// visibility
+    fn spaced(&mut self, parts: &[Part]) -> Vec<Tree> {
+        let mut trees = Vec::new();
+        for (i, &part) in parts.iter().enumerate() {
+            let space = i > 0
+                && !part.is(",")
+                && !part.is(";")
+                && !parts[i - 1].is_node(NodeKind::Visibility);
+            if space {
+                trees.push(text(" "));
+            }
+            trees.push(self.part(part));
+        }
+        trees
+    }
+    /// The parts separated by a space where the source has whitespaces
+    /// between them
+    fn as_written(&mut self, parts: &[Part]) -> Tree {
+        let mut trees = Vec::new();
+        for (i, &part) in parts.iter().enumerate() {
+            if i > 0 && parts[i - 1].span().end() < part.span().start() {
+                trees.push(text(" "));
+            }
+            trees.push(self.part(part));
+        }
+        line(trees)
+    }
+    /// `a, b` with the commas of the parts
+    fn separated(&mut self, parts: &[Part]) -> Vec<Tree> {
+        parts
+            .iter()
+            .map(|&part| match part {
+                Part::Token(token) if token.text == "," => text(", "),
+                part => self.part(part),
+            })
+            .collect()
+    }
+    /// `x: T` with the colon of the parts
+    fn annotated(&mut self, parts: &[Part]) -> Tree {
+        let trees: Vec<_> = parts
+            .iter()
+            .map(|&part| match part {
+                Part::Token(token) if token.text == ":" => text(": "),
+                part => self.part(part),
+            })
+            .collect();
+        line(trees)
+    }
+    /// `@name payload`
+    fn tag(&mut self, parts: &[Part]) -> Tree {
+        let mut trees = vec![self.part(parts[0]), self.part(parts[1])];
+        if let Some(&payload) = parts.get(2) {
+            trees.push(text(" "));
+            trees.push(self.part(payload));
+        }
+        line(trees)
+    }
+    /// `head` followed by what's after `at`, which goes on the next line if
+    /// it doesn't fit
+    fn hang(&mut self, head: Vec<Tree>, parts: &[Part], at: usize) -> Tree {
+        let mut head = head;
+        head.push(text(format!(" {}", first_text(parts[at]))));
+        let body = self.spaced(&parts[at + 1..]);
+        Box::new(Hang {
+            head: line(head),
+            body: line(body),
+            breaks_itself: breaks_itself(parts[at + 1]),
+        })
+    }
+    /// Function along with its parameters and the optional return type,
+    /// starting from the parameters
+    fn fun(&mut self, head: Vec<Tree>, parts: &[Part]) -> Tree {
+        let mut head = head;
+        let arrow = parts.iter().position(|part| part.is("=>")).unwrap();
+        for &part in &parts[..arrow] {
+            if part.is("->") {
+                head.push(text(" -> "));
+            } else {
+                head.push(self.part(part));
+            }
+        }
+        self.hang(head, parts, arrow)
+    }
+    /// Operands of the same precedence are broken before their operators,
+    /// the ones of a higher precedence are kept together
+    fn binary(&mut self, node: &Node) -> Tree {
+        let op = binary_op(node);
+        let mut nodes = vec![node];
+        while let Some(Part::Node(left)) = parts(nodes.last().unwrap()).first().copied() {
+            if left.kind != NodeKind::Binary || precedence(binary_op(left)) != precedence(op) {
+                break;
+            }
+            nodes.push(left);
+        }
+        let first = match parts(nodes.last().unwrap())[0] {
+            Part::Node(node) => self.tree(node),
+            Part::Token(_) => unreachable!(),
+        };
+        let rest = nodes
+            .iter()
+            .rev()
+            .map(|node| {
+                let parts = parts(node);
+                (first_text(parts[1]).to_string(), self.part(parts[2]))
+            })
+            .collect();
+        Box::new(Chain { first, rest })
+    }
+    // the blocks of an `if else` chain are either all on a single line or
+    // none is
+    fn if_else(&mut self, node: &Node, breaking: bool, trees: &mut Vec<Tree>) {
+        for (i, part) in parts(node).into_iter().enumerate() {
+            match part {
+                Part::Node(condition) if i == 1 => trees.push(self.tree(condition)),
+                Part::Node(node) if node.kind == NodeKind::Block => {
+                    trees.push(text(" "));
+                    let block = self.block(node);
+                    trees.push(if breaking {
+                        Box::new(Breaking(block))
+                    } else {
+                        block
+                    });
+                }
+                Part::Node(node) if node.kind == NodeKind::If => {
+                    trees.push(text(" "));
+                    self.if_else(node, breaking, trees);
+                }
+                Part::Token(token) if token.text == "if" => trees.push(text("if ")),
+                Part::Token(token) => trees.push(text(format!(" {}", token.text))),
+                Part::Node(_) => unreachable!(),
+            }
+        }
+    }
+    fn tree(&mut self, node: &Node) -> Tree {
+        let parts = parts(node);
+        let position = |text| parts.iter().position(|part: &Part| part.is(text));
+        match node.kind {
+            NodeKind::SourceFile => unreachable!(),
+            NodeKind::Visibility => {
+                let spelling: String = node.tokens().map(|token| &token.text[..]).collect();
+                text(format!("{spelling} "))
+            }
+            NodeKind::Declare => {
+                let equal = position("=").unwrap();
+                let head = self.spaced(&parts[..equal]);
+                self.hang(head, &parts, equal)
+            }
+            NodeKind::FunDeclare => {
+                let name = parts.iter().position(|part| matches!(part, Part::Token(_)));
+                let name = name.unwrap();
+                let mut head = Vec::new();
+                for &part in &parts[..name] {
+                    head.push(self.part(part));
+                    if part.is_node(NodeKind::Generics) {
+                        head.push(text(" "));
+                    }
+                }
+                head.push(self.part(parts[name]));
+                self.fun(head, &parts[name + 1..])
+            }
+            NodeKind::Generics => {
+                let list = self.list(&parts[1..parts.len() - 1], false);
+                line([text(":"), Box::new(list), text(":")])
+            }
+            NodeKind::TypeParams
+            | NodeKind::Array
+            | NodeKind::Record
+            | NodeKind::Param
+            | NodeKind::RecordPattern
+            | NodeKind::ArrayPattern
+            | NodeKind::RecordType => Box::new(self.list(&parts, false)),
+            NodeKind::Tuple | NodeKind::TuplePattern | NodeKind::TupleType => self.tuple(&parts),
+            NodeKind::AliasDeclare => {
+                let equal = position("=").unwrap();
+                let mut head = vec![text("alias ")];
+                for &part in &parts[1..equal] {
+                    head.push(self.part(part));
+                }
+                self.hang(head, &parts, equal)
+            }
+            NodeKind::NewtypeDeclare | NodeKind::TraitDeclare | NodeKind::ImplDeclare => {
+                let mut trees: Vec<Tree> = Vec::new();
+                let mut i = 0;
+                while i < parts.len() {
+                    let part = parts[i];
+                    if part.is("(") || part.is("{") {
+                        let close = if part.is("(") { ")" } else { "}" };
+                        let end = i + parts[i..].iter().position(|part| part.is(close)).unwrap();
+                        if part.is("(") {
+                            trees.push(Box::new(self.list(&parts[i..=end], false)));
+                        } else {
+                            trees.push(text(" "));
+                            trees.push(self.body(&parts[i..=end], end > i + 1));
+                        }
+                        i = end + 1;
+                        continue;
+                    }
+                    trees.push(self.part(part));
+                    if let Part::Token(token) = part {
+                        if token.kind == TokenKind::Keyword {
+                            trees.push(text(" "));
+                        }
+                    } else if part.is_node(NodeKind::Generics) {
+                        trees.push(text(" "));
+                    }
+                    i += 1;
+                }
+                line(trees)
+            }
+            NodeKind::Given => {
+                let colon = position(":").unwrap();
+                let mut bounds = &parts[1..colon];
+                if bounds.last().is_some_and(|part| part.is(",")) {
+                    bounds = &bounds[..bounds.len() - 1];
+                }
+                let mut trees = vec![text("given ")];
+                trees.extend(self.separated(bounds));
+                trees.push(text(": "));
+                line(trees)
+            }
+            NodeKind::NewtypeField | NodeKind::Parameter | NodeKind::FieldType => {
+                self.annotated(&parts)
+            }
+            NodeKind::TraitBound | NodeKind::MethodSignature => {
+                let trees: Vec<_> = parts
+                    .iter()
+                    .map(|&part| match part {
+                        Part::Token(token) if token.text == "->" => text(" -> "),
+                        part => self.part(part),
+                    })
+                    .collect();
+                line(trees)
+            }
+            NodeKind::ModuleDeclare
+            | NodeKind::ExprStatement
+            | NodeKind::Field
+            | NodeKind::FieldPattern
+            | NodeKind::For
+            | NodeKind::While
+            | NodeKind::Loop
+            | NodeKind::Jump
+            | NodeKind::VarPattern
+            | NodeKind::FunType => line(self.spaced(&parts)),
+            NodeKind::ModuleBody => self.body(&parts, parts.len() > 2),
+            NodeKind::Literal
+            | NodeKind::String
+            | NodeKind::Var
+            | NodeKind::Never
+            | NodeKind::Unit
+            | NodeKind::Splat
+            | NodeKind::Group
+            | NodeKind::Spread
+            | NodeKind::FieldAccess
+            | NodeKind::Len
+            | NodeKind::Index
+            | NodeKind::Slice
+            | NodeKind::Deref
+            | NodeKind::Call
+            | NodeKind::LiteralPattern
+            | NodeKind::DiscardPattern
+            | NodeKind::RefPattern
+            | NodeKind::GroupPattern
+            | NodeKind::DiscardType
+            | NodeKind::VarType
+            | NodeKind::NameType
+            | NodeKind::ArrayType
+            | NodeKind::GroupType => {
+                // the arguments of a type are a list
+                if node.kind == NodeKind::NameType && parts.len() > 1 {
+                    let name = self.part(parts[0]);
+                    let args = self.list(&parts[1..], false);
+                    return line([name, Box::new(args)]);
+                }
+                self.glued(&parts)
+            }
+            NodeKind::Range => self.as_written(&parts),
+            NodeKind::Tag | NodeKind::TagPattern | NodeKind::TagType => self.tag(&parts),
+            NodeKind::Assign => {
+                let arrow = position("<-").unwrap();
+                let head = self.separated(&parts[..arrow]);
+                let mut head = head;
+                head.push(text(" <-"));
+                let body = self.separated(&parts[arrow + 1..]);
+                Box::new(Hang {
+                    head: line(head),
+                    body: line(body),
+                    breaks_itself: breaks_itself(parts[arrow + 1]),
+                })
+            }
+            NodeKind::Unary => {
+                let op = first_text(parts[0]);
+                let space = node.first_token().unwrap().kind == TokenKind::Keyword
+                    || glues(op, first_text(parts[1]));
+                let op = self.part(parts[0]);
+                let operand = self.part(parts[1]);
+                if space {
+                    line([op, text(" "), operand])
+                } else {
+                    line([op, operand])
+                }
+            }
+            NodeKind::Binary => self.binary(node),
+            NodeKind::Block => self.block(node),
+            NodeKind::If => {
+                let mut trees = Vec::new();
+                self.if_else(node, position("else").is_some(), &mut trees);
+                line(trees)
+            }
+            NodeKind::Match => {
+                let open = position("{").unwrap();
+                let mut trees = self.spaced(&parts[..open]);
+                trees.push(text(" "));
+                trees.push(self.body(&parts[open..], true));
+                line(trees)
+            }
+            // arms that aren't control flow are followed by a comma
+            NodeKind::MatchArm => {
+                let arrow = position("=>").unwrap();
+                let head = vec![self.part(parts[0])];
+                let control_flow = matches!(
+                    parts[arrow + 1],
+                    Part::Node(node) if matches!(
+                        node.kind,
+                        NodeKind::Block
+                            | NodeKind::If
+                            | NodeKind::For
+                            | NodeKind::While
+                            | NodeKind::Loop
+                            | NodeKind::Match
+                    ),
+                );
+                let mut parts = parts.clone();
+                let comma = parts.last().is_some_and(|part| part.is(","));
+                if comma {
+                    parts.pop();
+                }
+                let mut tree = self.hang(head, &parts, arrow);
+                if comma || !control_flow {
+                    tree = line([tree, text(",")]);
+                }
+                tree
+            }
+            NodeKind::Fun => self.fun(Vec::new(), &parts),
+            NodeKind::Annotated | NodeKind::AnnotatedPattern => self.annotated(&parts),
+            NodeKind::UnionType => {
+                if parts[0].is("(") {
+                    return Box::new(self.list(&parts, false));
+                }
+                let first = self.part(parts[0]);
+                let rest = parts[1..]
+                    .chunks(2)
+                    .map(|pair| ("|".to_string(), self.part(pair[1])))
+                    .collect();
+                Box::new(Chain { first, rest })
+            }
+            // `&:mut /l T`
+            NodeKind::RefType => {
+                let mut trees = Vec::new();
+                for (i, &part) in parts.iter().enumerate() {
+                    trees.push(self.part(part));
+                    if i > 0 && (parts[i - 1].is(":") || parts[i - 1].is("/")) {
+                        trees.push(text(" "));
+                    }
+                }
+                line(trees)
+            }
+        }
+    }
+}
+/// Renders the tree as it is written from `state`
+fn render(tree: &dyn PrettyPrintTree, state: PrettyPrintState) -> String {
+    let mut buffer = Vec::new();
+    tree.pretty_write(&mut buffer, state).unwrap();
+    String::from_utf8(buffer).unwrap()
+}
+/// Trees written one after another, each breaking its own lines when the rest
+/// of the line is too short
+struct Line(Vec<Tree>);
+impl PrettyPrintTree for Line {
+    fn write_len(&self) -> Option<usize> {
+        self.0
+            .iter()
+            .try_fold(0, |len, tree| Some(len + tree.write_len()?))
+    }
+    fn write_line(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()> {
+        let mut state = state;
+        for tree in &self.0 {
+            let mut buffer = Vec::new();
+            tree.write_line(&mut buffer, state)?;
+            let written = String::from_utf8(buffer).unwrap();
+            write!(writer, "{written}")?;
+            state = state.after(&written);
+        }
+        Ok(())
+    }
+    fn write_multiline(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()> {
+        let mut state = state;
+        for tree in &self.0 {
+            let written = render(tree.as_ref(), state);
+            write!(writer, "{written}")?;
+            state = state.after(&written);
+        }
+        Ok(())
+    }
+}
+/// `head` followed by a space and `body`, or `body` on the next line indented
+/// if it doesn't fit
+struct Hang {
+    head: Tree,
+    body: Tree,
+    /// Whether `body` is kept after `head` if its first line fits
+    breaks_itself: bool,
+}
+impl PrettyPrintTree for Hang {
+    fn write_len(&self) -> Option<usize> {
+        Some(self.head.write_len()? + 1 + self.body.write_len()?)
+    }
+    fn write_line(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()> {
+        self.head.write_line(writer, state)?;
+        write!(writer, " ")?;
+        self.body.write_line(writer, state.after(" "))
+    }
+    fn write_multiline(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()> {
+        let head = render(self.head.as_ref(), state);
+        write!(writer, "{head}")?;
+        let after = state.after(&head).after(" ");
+        if after.fits(self.body.write_len()) {
+            write!(writer, " ")?;
+            return self.body.write_line(writer, after);
+        }
+        // a body written on multiple lines whatever the width, like a block
+        // of statements, also starts on the line of the head
+        if self.breaks_itself || self.body.write_len().is_none() {
+            let body = render(self.body.as_ref(), after);
+            let first_line = body.split('\n').next().unwrap();
+            if after.fits(Some(first_line.len())) {
+                return write!(writer, " {body}");
+            }
+        }
+        let next_line = state.next_line();
+        writeln!(writer)?;
+        next_line.write_indent(writer)?;
+        self.body.pretty_write(writer, next_line)
+    }
+}
+/// Operands separated by operators, on multiple lines each operator starts a
+/// line
+struct Chain {
+    first: Tree,
+    rest: Vec<(String, Tree)>,
+}
+impl PrettyPrintTree for Chain {
+    fn write_len(&self) -> Option<usize> {
+        self.rest
+            .iter()
+            .try_fold(self.first.write_len()?, |len, (op, tree)| {
+                Some(len + op.len() + 2 + tree.write_len()?)
+            })
+    }
+    fn write_line(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()> {
+        self.first.write_line(writer, state)?;
+        let state = state.after(" ");
+        for (op, tree) in &self.rest {
+            write!(writer, " {op} ")?;
+            tree.write_line(writer, state)?;
+        }
+        Ok(())
+    }
+    fn write_multiline(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()> {
+        self.first.pretty_write(writer, state)?;
+        let next_line = state.next_line();
+        for (op, tree) in &self.rest {
+            writeln!(writer)?;
+            next_line.write_indent(writer)?;
+            let op = format!("{op} ");
+            write!(writer, "{op}")?;
+            tree.pretty_write(writer, next_line.after(&op))?;
+        }
+        Ok(())
+    }
+}
+/// Tree that is written on multiple lines whenever its parent is
+struct Breaking(Tree);
+impl PrettyPrintTree for Breaking {
+    fn write_len(&self) -> Option<usize> {
+        self.0.write_len()
+    }
+    fn write_line(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()> {
+        self.0.write_line(writer, state)
+    }
+    fn write_multiline(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()> {
+        self.0.write_multiline(writer, state)
+    }
+    fn pretty_write(&self, writer: &mut dyn Write, state: PrettyPrintState) -> io::Result<()> {
+        self.write_multiline(writer, state)
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::{format, lower, syntax_tree};
+    use combine::EasyParser;
+    use hir::{fold::Fold, span::EraseSpans, statement::Statement};
+
+    fn parse(src: &str) -> (crate::cst::Node, Vec<Statement<()>>) {
+        let (source_file, _) = syntax_tree().easy_parse(src).unwrap();
+        let statements = lower(&source_file)
+            .into_iter()
+            .map(|statement| EraseSpans.fold_statement(statement))
+            .collect();
+        (source_file, statements)
+    }
+    fn assert_format(src: &str, width: usize, expected: &str) {
+        let (source_file, statements) = parse(src);
+        let formatted = format(&source_file, width);
+        assert_eq!(formatted, expected);
+        let (reparsed, reparsed_statements) = parse(&formatted);
+        assert_eq!(reparsed_statements, statements);
+        assert_eq!(format(&reparsed, width), formatted);
+    }
+    #[test]
+    fn statements() {
+        let src = "\
+pub  :(T) :id( x:T )->T=>x ;
+alias Pair( a ) = ( a,a, ) ;
+mod math ;
+geo=mod geometry;
+pub(a.b) mod inner { pub pi = 3; }
+main()=>{ ref mut y = 1; a , b <- b , a; if y { } ; }";
+        let expected = "\
+pub :(T): id(x: T) -> T => x;
+alias Pair(a) = (a, a);
+mod math;
+geo = mod geometry;
+pub(a.b) mod inner {
+    pub pi = 3;
+}
+main() => {
+    ref mut y = 1;
+    a, b <- b, a;
+    if y {};
+}
+";
+        assert_format(src, 80, expected);
+    }
+    #[test]
+    fn traits() {
+        let src = "\
+pub trait Show(a) { show(x: &a) -> [Num]; }
+given Show(a),: impl Show([a]) { show(x) => [1]; }
+pub  given Show(a):f(x: a) => show(&x);";
+        let expected = "\
+pub trait Show(a) {
+    show(x: &a) -> [Num];
+}
+given Show(a): impl Show([a]) {
+    show(x) => [1];
+}
+pub given Show(a): f(x: a) => show(&x);
+";
+        assert_format(src, 80, expected);
+    }
+    #[test]
+    fn newtype() {
+        let src = "\
+pub  :(a):newtype Pair( first: a, #second :a ,pub(app) #count: Num);
+pair = Pair(first = 1, #second = 2, #count = 0);
+second = pair.#second;";
+        let expected = "\
+pub :(a): newtype Pair(first: a, #second: a, pub(app) #count: Num);
+pair = Pair(first = 1, #second = 2, #count = 0);
+second = pair.#second;
+";
+        assert_format(src, 80, expected);
+    }
+    #[test]
+    fn literals() {
+        let src = "x = [0x1F_FF, 'a', 1.50, \"a -- b\\n\"];";
+        assert_format(src, 80, "x = [0x1F_FF, 'a', 1.50, \"a -- b\\n\"];\n");
+    }
+    #[test]
+    fn as_written() {
+        let src = "\
+x = (a - b) - (c - d) * -( - e) - - e;
+y = (@a) - 1 + @b (-1);
+z = ((x) => x)(1);
+((x) => x);
+({ 1 }).field;
+w = if (@a) { 1 };
+f(v) => (v);
+r = (= id);
+s = xs[i ..][.. 2];
+alias Opt(a) = @val a | @none;";
+        let expected = "\
+x = (a - b) - (c - d) * -(-e) - -e;
+y = (@a) - 1 + @b (-1);
+z = ((x) => x)(1);
+((x) => x);
+({ 1 }).field;
+w = if (@a) { 1 };
+f(v) => (v);
+r = (= id);
+s = xs[i ..][.. 2];
+alias Opt(a) = @val a | @none;
+";
+        assert_format(src, 80, expected);
+    }
+    #[test]
+    fn patterns_and_types() {
+        let src = "\
+(a,) = b;
+[a, *rest] = c;
+(= a, b = @some (c: Num)) = d;
+(@some x): Opt = e;
+f: (Num -> Num) -> [&:mut /l Str] = g;
+(h,i,): (Num,) = j;";
+        let expected = "\
+(a,) = b;
+[a, *rest] = c;
+(= a, b = @some (c: Num)) = d;
+(@some x): Opt = e;
+f: (Num -> Num) -> [&:mut /l Str] = g;
+(h, i): (Num,) = j;
+";
+        assert_format(src, 80, expected);
+    }
+    #[test]
+    fn comments() {
+        let src = "\
+#!/usr/bin/env butter
+-- about a
+a = 1; -- one
+
+
+b = { -- inside
+    c -- value
+};
+@val c = c else { return 0; };
+x = match a {
+    1 => 2, -- two
+    -- rest
+    _ => 3
+};
+-- end";
+        let expected = "\
+#!/usr/bin/env butter
+-- about a
+a = 1; -- one
+
+b = {
+    -- inside
+    c -- value
+};
+@val c = c else {
+    return 0;
+};
+x = match a {
+    1 => 2, -- two
+    -- rest
+    _ => 3,
+};
+-- end
+";
+        assert_format(src, 80, expected);
+    }
+    #[test]
+    fn list_comments() {
+        let src = "\
+y = [
+  1, -- first
+  -- between
+  2,
+];
+f(a, -- a
+  b);
+z = (x = 1, y = 2 -- y
+);";
+        let expected = "\
+y = [
+    1, -- first
+    -- between
+    2,
+];
+f(
+    a, -- a
+    b,
+);
+z = (
+    x = 1,
+    y = 2, -- y
+);
+";
+        assert_format(src, 80, expected);
+    }
+    #[test]
+    fn width() {
+        let src = "\
+x = f(first_argument, second_argument);
+y = if condition { first_value } else { second_value };";
+        let expected = "\
+x = f(
+    first_argument,
+    second_argument,
+);
+y = if condition {
+    first_value
+} else {
+    second_value
+};
+";
+        assert_format(src, 30, expected);
+    }
+    #[test]
+    fn long_lines() {
+        let src = "\
+compute_something_long(first_parameter, second_parameter, third_parameter) => first_parameter + second_parameter * third_parameter;
+total = first_operand_of_sum + second_operand_of_sum + third_operand_of_the_sum * factor;
+alias Result(a, e) = @ok a | @error e | @interrupted | @timed_out_after_waiting_for_reply;";
+        let expected = "\
+compute_something_long(first_parameter, second_parameter, third_parameter) =>
+    first_parameter + second_parameter * third_parameter;
+total =
+    first_operand_of_sum
+        + second_operand_of_sum
+        + third_operand_of_the_sum * factor;
+alias Result(a, e) =
+    @ok a | @error e | @interrupted | @timed_out_after_waiting_for_reply;
+";
+        assert_format(src, 80, expected);
+    }
+}
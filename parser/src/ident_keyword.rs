@@ -1,3 +1,4 @@
+use crate::Offset;
 use combine::{
     error::StreamError,
     not_followed_by,
@@ -15,6 +16,7 @@ pub(super) fn ident_or_keyword<I>() -> impl Parser<I, Output = Atom>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     let start = move |ch: char| rest(ch) && !ch.is_ascii_digit();
     recognize::<String, _, _>((satisfy(start), skip_many(satisfy(rest))))
@@ -25,6 +27,7 @@ pub(super) fn keyword<I>(keyword: &'static str) -> impl Parser<I, Output = ()>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     string(keyword)
         .skip(not_followed_by(satisfy(rest)))
@@ -34,23 +37,32 @@ pub(super) fn ident<I>() -> impl Parser<I, Output = Atom>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     ident_or_keyword().and_then(|ident| match ident {
         keyword!("_")
+        | keyword!("alias")
         | keyword!("break")
         | keyword!("clone")
         | keyword!("continue")
         | keyword!("else")
         | keyword!("false")
         | keyword!("for")
+        | keyword!("given")
         | keyword!("if")
         | keyword!("imm")
+        | keyword!("impl")
         | keyword!("in")
         | keyword!("loop")
         | keyword!("match")
+        | keyword!("mod")
         | keyword!("mut")
+        | keyword!("never")
+        | keyword!("newtype")
+        | keyword!("pub")
         | keyword!("ref")
         | keyword!("return")
+        | keyword!("trait")
         | keyword!("true")
         | keyword!("while") => Err(<StreamErrorFor<I>>::unexpected_static_message("keyword")),
         ident => Ok(ident),
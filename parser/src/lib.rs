@@ -4,30 +4,99 @@
 
 use combine::{
     attempt, choice, eof, many, none_of, optional,
-    parser::char::{space, string},
-    sep_end_by, skip_many, skip_many1, value, ParseError, Stream,
+    parser::{
+        char::{char, space, string},
+        combinator::recognize,
+    },
+    position, skip_many, skip_many1,
+    stream::PointerOffset,
+    ParseError, Stream,
 };
-use hir::{expr::Expr, statement::Statement};
+use cst::{elements, node, Element, Node, NodeKind, Parts, Token, TokenKind};
+use hir::{expr::Expr, statement::Statement, FileId, Span};
 
 pub use combine::{EasyParser, Parser};
+pub use format::format;
+pub use lower::lower;
 
 mod control_flow;
+pub mod cst;
 mod expr;
+mod format;
 mod ident_keyword;
+mod lower;
 mod number;
 mod pattern;
 mod statement;
+mod ty;
 
+/// Positions that can be turned into an address of the source. Spans are
+/// made of these addresses while parsing, the entry points then make them
+/// relative to where the source starts
+pub trait Offset {
+    fn offset(&self) -> usize;
+}
+impl Offset for PointerOffset<str> {
+    fn offset(&self) -> usize {
+        self.0
+    }
+}
+
+combine::parser! {
+    pub fn syntax_tree[I]()(I) -> Node
+    where [
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    ] {
+        syntax_tree_in(FileId::default())
+    }
+}
+combine::parser! {
+    /// Like [`syntax_tree`] but the spans are in `file`
+    pub fn syntax_tree_in[I](file: FileId)(I) -> Node
+    where [
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    ] {
+        let file = *file;
+        (
+            position(),
+            optional(token(
+                TokenKind::Shebang,
+                (attempt(string("#!")), skip_many(none_of(['\n']))),
+            )),
+            insignificants(),
+            statement::statements(),
+            eof(),
+        )
+            .map(move |(start, shebang, trivia, statements, ()): (I::Position, _, _, _, _)| {
+                let mut source_file = node(NodeKind::SourceFile, (shebang, trivia, statements));
+                source_file.relocate(start.offset(), file);
+                source_file
+            })
+    }
+}
 combine::parser! {
     pub fn ast[I]()(I) -> Vec<Statement<()>>
     where [
         I: Stream<Token = char>,
         I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
     ] {
-        optional(attempt(string("#!")).with(skip_many(none_of(['\n']))))
-            .with(insignificants())
-            .with(many(statement::statement()))
-            .skip(eof())
+        ast_in(FileId::default())
+    }
+}
+combine::parser! {
+    /// Like [`ast`] but the spans are in `file`
+    pub fn ast_in[I](file: FileId)(I) -> Vec<Statement<()>>
+    where [
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    ] {
+        syntax_tree_in(*file).map(|source_file| lower(&source_file))
     }
 }
 combine::parser! {
@@ -35,79 +104,214 @@ combine::parser! {
     where [
         I: Stream<Token = char>,
         I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
     ] {
-        insignificants().with(expr::expr(0)).skip(eof())
+        (position(), insignificants(), expr::expr(0))
+            .skip(eof())
+            .map(|(start, _, mut expr): (I::Position, Vec<Element>, Node)| {
+                expr.relocate(start.offset(), FileId::default());
+                lower::expr(&expr)
+            })
     }
 }
-fn comment<I>() -> impl Parser<I, Output = ()>
+fn comment<I>() -> impl Parser<I, Output = Token>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    (attempt(string("--")), skip_many(none_of(['\n']))).with(value(()))
+    token(
+        TokenKind::Comment,
+        (attempt(string("--")), skip_many(none_of(['\n']))),
+    )
 }
-pub fn insignificants<I>() -> impl Parser<I, Output = ()>
+combine::parser! {
+    pub fn insignificants[I]()(I) -> Vec<Element>
+    where [
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    ] {
+        many(
+            choice((
+                token(TokenKind::Whitespace, skip_many1(space())),
+                comment(),
+            ))
+            .map(Element::Token),
+        )
+        .silent()
+    }
+}
+/// Token made of everything `parser` consumes
+fn token<I, P>(kind: TokenKind, parser: P) -> impl Parser<I, Output = Token>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+    P: Parser<I>,
 {
-    skip_many(skip_many1(space()).or(comment())).silent()
+    spanned(recognize::<String, _, _>(parser)).map(move |(text, span)| Token { kind, text, span })
 }
-fn lex<I, P>(parser: P) -> impl Parser<I, Output = P::Output>
+/// The token followed by the whitespaces and comments after it
+fn lex<I, P>(parser: P) -> impl Parser<I, Output = Vec<Element>>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+    P: Parser<I, Output = Token>,
+{
+    (parser, insignificants()).map(elements)
+}
+// leaf parsers are put behind `parser!` so their state isn't carried around
+// in every parser containing them, the parser would otherwise need a lot
+// more stack
+combine::parser! {
+    fn punct[I](ch: char)(I) -> Vec<Element>
+    where [
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    ] {
+        lex(token(TokenKind::Punctuation, char(*ch)))
+    }
+}
+combine::parser! {
+    fn punct_str[I](punct: &'static str)(I) -> Vec<Element>
+    where [
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    ] {
+        lex(token(TokenKind::Punctuation, attempt(string(punct))))
+    }
+}
+combine::parser! {
+    fn lex_keyword[I](keyword: &'static str)(I) -> Vec<Element>
+    where [
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    ] {
+        lex(token(TokenKind::Keyword, ident_keyword::keyword(keyword)))
+    }
+}
+combine::parser! {
+    fn lex_ident[I]()(I) -> Vec<Element>
+    where [
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    ] {
+        lex(token(TokenKind::Ident, ident_keyword::ident()))
+    }
+}
+combine::parser! {
+    /// Name of a field, private fields of newtypes start with `#`
+    fn lex_field_name[I]()(I) -> Vec<Element>
+    where [
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    ] {
+        lex(token(TokenKind::Ident, (optional(char('#')), ident_keyword::ident())))
+    }
+}
+fn spanned<I, P>(parser: P) -> impl Parser<I, Output = (P::Output, Span)>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
     P: Parser<I>,
 {
-    parser.skip(insignificants())
+    (position(), parser, position()).map(|(start, output, end): (I::Position, _, I::Position)| {
+        (
+            output,
+            Span::new(FileId::default(), start.offset(), end.offset()),
+        )
+    })
+}
+/// `sep_end_by` that keeps the separators
+fn sep_end<I, EP, SP>(element: fn() -> EP, sep: fn() -> SP) -> impl Parser<I, Output = Vec<Element>>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+    EP: Parser<I>,
+    EP::Output: Parts,
+    SP: Parser<I>,
+    SP::Output: Parts,
+{
+    optional((
+        element(),
+        many::<Vec<_>, _, _>(attempt((sep(), element()))),
+        optional(sep()),
+    ))
+    .map(elements)
 }
-fn sep_optional_between<I, EP, RP, SP, C>(
+fn sep_optional_between<I, EP, RP, SP>(
     element: fn() -> EP,
     rest: RP,
     sep: fn() -> SP,
-) -> impl Parser<I, Output = (C, Option<(RP::Output, C)>)>
+) -> impl Parser<I, Output = Vec<Element>>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
     EP: Parser<I>,
+    EP::Output: Parts,
     RP: Parser<I>,
+    RP::Output: Parts,
     SP: Parser<I>,
-    C: Extend<EP::Output> + Default,
+    SP::Output: Parts,
 {
-    let no_rest = move || sep_end_by(element(), sep());
+    let no_rest = move || sep_end(element, sep);
     let have_rest = move || {
         (
-            attempt((many(element().skip(sep())), rest)),
-            optional(sep().with(no_rest())).map(Option::unwrap_or_default),
+            attempt((many::<Vec<_>, _, _>((element(), sep())), rest)),
+            optional((sep(), no_rest())),
         )
     };
-    choice((
-        have_rest().map(|((left, rest), right)| (left, Some((rest, right)))),
-        no_rest().map(|collection| (collection, None)),
-    ))
+    choice((have_rest().map(elements), no_rest()))
 }
 #[cfg(test)]
 mod test {
-    use crate::insignificants;
+    use crate::{
+        cst::{Element, TokenKind},
+        insignificants,
+    };
     use combine::Parser;
     use hir::{
         expr::{Expr, ExprKind, PlaceExpr},
-        Atom,
+        Atom, Span,
     };
 
     pub(super) fn var_expr(var: &str) -> Expr<()> {
         ExprKind::Place(var_place(var)).into_untyped()
     }
     pub(super) fn var_place(var: &str) -> PlaceExpr<()> {
-        PlaceExpr::Var(Atom::from(var))
+        PlaceExpr::Var(Atom::from(var), Span::default())
     }
     #[test]
     fn insignificant() {
+        let (trivia, rest) = insignificants()
+            .parse("  -- comment\n  -- more comment")
+            .unwrap();
+        assert_eq!(rest, "");
+        let kinds: Vec<_> = trivia
+            .iter()
+            .map(|element| match element {
+                Element::Token(token) => token.kind,
+                Element::Node(_) => panic!("expected token"),
+            })
+            .collect();
         assert_eq!(
-            insignificants()
-                .parse("  -- comment\n  -- more comment")
-                .unwrap(),
-            ((), "")
-        )
+            kinds,
+            [
+                TokenKind::Whitespace,
+                TokenKind::Comment,
+                TokenKind::Whitespace,
+                TokenKind::Comment,
+            ],
+        );
     }
 }
@@ -0,0 +1,705 @@
+use crate::{
+    cst::{Element, Node, NodeKind, Token, TokenKind},
+    expr::string::{char_literal, string_literal},
+    number::{float, integer_u64},
+};
+use combine::Parser;
+use hir::{
+    expr::{
+        Annotated, Arg, Assign, Binary, BinaryType, Block, Bound, BoundType, Call, Collection,
+        ControlFlow, Element as ArrayElement, ElementKind, Expr, ExprKind, Field, FieldAccess, For,
+        Fun, If, Index, Jump, Literal, Match, MatchArm, PlaceExpr, Range, Slice, Str, Tag, Unary,
+        UnaryType, While, WithSplat,
+    },
+    pattern::{
+        AnnotatedPattern, ListPattern, ListWithRest, Pattern, PatternKind, RecordPattern,
+        TaggedPattern, TypedVar, Var,
+    },
+    statement::{
+        AliasDeclare, Declare, FunDeclare, ImplDeclare, MethodSignature, Module, ModuleDeclare,
+        NewtypeDeclare, NewtypeField, Statement, TraitDeclare, Visibility,
+    },
+    ty::{MutTypeExpr, RecordType, RefType, TraitBound, TupleType, TypeExpr, UnionType},
+    Atom,
+};
+use std::iter::Peekable;
+
+/// Lowers the syntax tree of a source file into the statements of the HIR.
+/// Parentheses are dropped and sugars are expanded, the spans still point
+/// into the source.
+pub fn lower(source_file: &Node) -> Vec<Statement<()>> {
+    source_file.nodes().map(statement).collect()
+}
+fn atom(token: &Token) -> Atom {
+    Atom::from(&token.text[..])
+}
+fn ident(node: &Node) -> &Token {
+    node.token_of(TokenKind::Ident).unwrap()
+}
+fn first(node: &Node) -> &Node {
+    node.nodes().next().unwrap()
+}
+/// Whether the expression can be assigned to
+pub(crate) fn is_place(expr: &Node) -> bool {
+    match expr.kind {
+        NodeKind::Var
+        | NodeKind::FieldAccess
+        | NodeKind::Len
+        | NodeKind::Index
+        | NodeKind::Slice
+        | NodeKind::Deref => true,
+        NodeKind::Group => is_place(first(expr)),
+        _ => false,
+    }
+}
+/// Name of a record field, written before `=` or inferred from the value as
+/// in `= name`
+pub(crate) fn field_name(field: &Node) -> Option<Atom> {
+    fn inferred(node: &Node) -> Option<Atom> {
+        match node.kind {
+            NodeKind::Var | NodeKind::FieldAccess | NodeKind::VarPattern => Some(atom(ident(node))),
+            NodeKind::Group
+            | NodeKind::Tag
+            | NodeKind::Unary
+            | NodeKind::Deref
+            | NodeKind::GroupPattern
+            | NodeKind::AnnotatedPattern => inferred(node.nodes().next()?),
+            _ => None,
+        }
+    }
+    match field.token_of(TokenKind::Ident) {
+        Some(name) => Some(atom(name)),
+        None => inferred(first(field)),
+    }
+}
+fn visibility(node: &Node) -> Visibility {
+    match node.nodes().find(|node| node.kind == NodeKind::Visibility) {
+        Some(visibility) => {
+            let path: Vec<_> = visibility
+                .tokens()
+                .filter(|token| token.kind == TokenKind::Ident)
+                .map(atom)
+                .collect();
+            if path.is_empty() {
+                Visibility::Public
+            } else {
+                Visibility::Restricted(path.into())
+            }
+        }
+        None => Visibility::Private,
+    }
+}
+pub(crate) fn statement(node: &Node) -> Statement<()> {
+    let mut nodes = node
+        .nodes()
+        .filter(|node| node.kind != NodeKind::Visibility)
+        .peekable();
+    match node.kind {
+        NodeKind::ExprStatement => Statement::Expr(expr(first(node))),
+        NodeKind::Declare => Statement::Declare(Declare {
+            visibility: visibility(node),
+            pattern: pattern(nodes.next().unwrap()),
+            expr: expr(nodes.next().unwrap()),
+            else_part: nodes.next().map(block),
+        }),
+        NodeKind::FunDeclare => {
+            let given = given(&mut nodes);
+            let generics = generics(&mut nodes);
+            let ident = ident(node);
+            Statement::FunDeclare(FunDeclare {
+                visibility: visibility(node),
+                ident: atom(ident),
+                span: ident.span,
+                given,
+                generics,
+                fun: fun(node, nodes),
+                ty: (),
+            })
+        }
+        NodeKind::AliasDeclare => {
+            let ident = ident(node);
+            let params = match nodes.next_if(|node| node.kind == NodeKind::TypeParams) {
+                Some(params) => params
+                    .tokens()
+                    .filter(|token| token.kind == TokenKind::Ident)
+                    .map(atom)
+                    .collect(),
+                None => Vec::new(),
+            };
+            Statement::AliasDeclare(AliasDeclare {
+                ident: atom(ident),
+                span: ident.span,
+                params: params.into(),
+                ty: ty(nodes.next().unwrap()),
+            })
+        }
+        NodeKind::NewtypeDeclare => Statement::NewtypeDeclare(NewtypeDeclare {
+            visibility: visibility(node),
+            ident: atom(ident(node)),
+            span: node.span(),
+            generics: generics(&mut nodes),
+            fields: nodes.map(newtype_field).collect(),
+        }),
+        NodeKind::TraitDeclare => {
+            let mut names = node
+                .tokens()
+                .filter(|token| token.kind == TokenKind::Ident)
+                .map(atom);
+            Statement::TraitDeclare(TraitDeclare {
+                visibility: visibility(node),
+                ident: names.next().unwrap(),
+                span: node.span(),
+                param: names.next().unwrap(),
+                methods: nodes.map(method_signature).collect(),
+            })
+        }
+        NodeKind::ImplDeclare => Statement::ImplDeclare(ImplDeclare {
+            given: given(&mut nodes),
+            bound: trait_bound(nodes.next().unwrap()),
+            span: node.span(),
+            methods: nodes
+                .map(|method| match statement(method) {
+                    Statement::FunDeclare(method) => method,
+                    _ => unreachable!(),
+                })
+                .collect(),
+        }),
+        NodeKind::ModuleDeclare => {
+            let ident = ident(node);
+            let module = match nodes.next() {
+                Some(body) => Module::Inline(body.nodes().map(statement).collect()),
+                None => Module::File(atom(
+                    node.tokens()
+                        .filter(|token| token.kind == TokenKind::Ident)
+                        .last()
+                        .unwrap(),
+                )),
+            };
+            Statement::ModuleDeclare(ModuleDeclare {
+                visibility: visibility(node),
+                ident: atom(ident),
+                span: ident.span,
+                module,
+            })
+        }
+        kind => unreachable!("{kind:?} is not a statement"),
+    }
+}
+fn given<'a>(nodes: &mut Peekable<impl Iterator<Item = &'a Node>>) -> Box<[TraitBound]> {
+    match nodes.next_if(|node| node.kind == NodeKind::Given) {
+        Some(given) => given.nodes().map(trait_bound).collect(),
+        None => Box::new([]),
+    }
+}
+fn generics<'a>(nodes: &mut Peekable<impl Iterator<Item = &'a Node>>) -> Box<[Atom]> {
+    match nodes.next_if(|node| node.kind == NodeKind::Generics) {
+        Some(generics) => generics
+            .tokens()
+            .filter(|token| token.kind == TokenKind::Ident)
+            .map(atom)
+            .collect(),
+        None => Box::new([]),
+    }
+}
+fn newtype_field(node: &Node) -> NewtypeField {
+    let name = atom(ident(node));
+    // only fields named with `#` can be restricted
+    let visibility = if name.starts_with('#') {
+        visibility(node)
+    } else {
+        Visibility::Public
+    };
+    let ty_node = node
+        .nodes()
+        .find(|node| node.kind != NodeKind::Visibility)
+        .unwrap();
+    NewtypeField {
+        visibility,
+        name,
+        ty: ty(ty_node),
+    }
+}
+fn trait_bound(node: &Node) -> TraitBound {
+    TraitBound {
+        name: atom(ident(node)),
+        ty: ty(first(node)),
+    }
+}
+// parameters of a signature are named for documentation, those without
+// annotation are of any type
+fn method_signature(node: &Node) -> MethodSignature {
+    let ident = ident(node);
+    let mut nodes = node.nodes();
+    let params = match pattern(nodes.next().unwrap()).pattern {
+        PatternKind::Param(params) => Vec::from(params)
+            .into_iter()
+            .map(|param| {
+                let ty = param.annotation.unwrap_or(TypeExpr::Discard);
+                (param.var.ident, ty)
+            })
+            .collect(),
+        _ => unreachable!(),
+    };
+    MethodSignature {
+        ident: atom(ident),
+        span: ident.span,
+        params,
+        ret: ty(nodes.next().unwrap()),
+    }
+}
+fn block(node: &Node) -> Block<()> {
+    let mut statements = Vec::new();
+    let mut tail = None;
+    for child in node.nodes() {
+        statements.extend(tail.take().map(Statement::Expr));
+        if child.kind == NodeKind::ExprStatement && child.token(";").is_none() {
+            tail = Some(expr(first(child)));
+        } else {
+            statements.push(statement(child));
+        }
+    }
+    Block {
+        statement: statements.into(),
+        expr: tail.map(Box::new),
+        span: node.span(),
+    }
+}
+/// Lowers the parameter, return type and body of either a function
+/// expression or declaration
+fn fun<'a>(node: &Node, mut nodes: impl Iterator<Item = &'a Node>) -> Fun<()> {
+    let param = pattern(nodes.next().unwrap());
+    let ret = node.token("->").map(|_| ty(nodes.next().unwrap()));
+    Fun {
+        param,
+        ret,
+        body: Box::new(expr(nodes.next().unwrap())),
+    }
+}
+fn literal(token: &Token) -> Literal {
+    match token.kind {
+        TokenKind::Char => Literal::Char(char_literal().parse(&token.text[..]).unwrap().0),
+        TokenKind::Float => Literal::Float(float().parse(&token.text[..]).unwrap().0),
+        TokenKind::Integer => Literal::UInt(integer_u64().parse(&token.text[..]).unwrap().0),
+        _ if token.text == "true" => Literal::True,
+        _ => Literal::False,
+    }
+}
+fn unary_type(op: &str) -> UnaryType {
+    match op {
+        "!" => UnaryType::Not,
+        "&" => UnaryType::Ref,
+        "-" => UnaryType::Minus,
+        ">" => UnaryType::Move,
+        "clone" => UnaryType::Clone,
+        op => unreachable!("invalid unary operator {op}"),
+    }
+}
+fn binary_type(op: &str) -> BinaryType {
+    match op {
+        "//" => BinaryType::FloorDiv,
+        "*" => BinaryType::Multiply,
+        "/" => BinaryType::Div,
+        "%" => BinaryType::Mod,
+        "++" => BinaryType::Concatenate,
+        "+" => BinaryType::Add,
+        "-" => BinaryType::Sub,
+        "==" => BinaryType::Equal,
+        "!=" => BinaryType::NotEqual,
+        "<=" => BinaryType::LessEqual,
+        ">=" => BinaryType::GreaterEqual,
+        "<" => BinaryType::Less,
+        ">" => BinaryType::Greater,
+        "&&" => BinaryType::LazyAnd,
+        "&" => BinaryType::And,
+        "||" => BinaryType::LazyOr,
+        "|" => BinaryType::Or,
+        op => unreachable!("invalid binary operator {op}"),
+    }
+}
+/// Splits the elements of a tuple, record or list pattern around `*rest`
+fn collection<'a, T>(
+    node: &'a Node,
+    mut element: impl FnMut(&'a Node) -> T,
+) -> (Vec<T>, Option<&'a Node>, Vec<T>) {
+    let mut left = Vec::new();
+    let mut rest = None;
+    let mut right = Vec::new();
+    for child in node.nodes() {
+        if child.kind == NodeKind::Spread {
+            rest = Some(first(child));
+        } else if rest.is_some() {
+            right.push(element(child));
+        } else {
+            left.push(element(child));
+        }
+    }
+    (left, rest, right)
+}
+fn expr_collection<'a, T>(node: &'a Node, element: impl FnMut(&'a Node) -> T) -> Collection<T, ()> {
+    match collection(node, element) {
+        (left, Some(splat), right) => Collection::WithSplat(WithSplat {
+            left: left.into(),
+            splat: Box::new(expr(splat)),
+            right: right.into(),
+        }),
+        (left, None, _) => Collection::Collection(left.into()),
+    }
+}
+fn field(node: &Node) -> Field<()> {
+    Field {
+        name: field_name(node).unwrap(),
+        expr: expr(first(node)),
+    }
+}
+fn range(node: &Node) -> Range<()> {
+    let mut left = None;
+    let mut op = None;
+    let mut right = None;
+    for element in &node.children {
+        match element {
+            Element::Node(bound) if op.is_none() => left = Some(expr(bound)),
+            Element::Node(bound) => right = Some(expr(bound)),
+            Element::Token(token)
+                if token.kind == TokenKind::Punctuation && token.text.len() == 2 =>
+            {
+                op = Some(&token.text[..]);
+            }
+            Element::Token(_) => (),
+        }
+    }
+    let bound = |ch| match ch {
+        '.' => BoundType::Inclusive,
+        _ => BoundType::Exclusive,
+    };
+    let mut op = op.unwrap().chars();
+    let left_kind = bound(op.next().unwrap());
+    let right_kind = bound(op.next().unwrap());
+    Range {
+        left: left.map(|left| Bound {
+            kind: left_kind,
+            expr: Box::new(left),
+        }),
+        right: right.map(|right| Bound {
+            kind: right_kind,
+            expr: Box::new(right),
+        }),
+    }
+}
+fn if_(node: &Node) -> If<()> {
+    let mut nodes = node.nodes();
+    If {
+        condition: Box::new(expr(nodes.next().unwrap())),
+        body: block(nodes.next().unwrap()),
+        else_part: nodes
+            .next()
+            .map(|else_part| Box::new(control_flow(else_part))),
+    }
+}
+fn control_flow(node: &Node) -> ControlFlow<()> {
+    let mut nodes = node.nodes();
+    match node.kind {
+        NodeKind::Block => ControlFlow::Block(block(node)),
+        NodeKind::If => ControlFlow::If(if_(node)),
+        NodeKind::For => ControlFlow::For(For {
+            pattern: pattern(nodes.next().unwrap()),
+            expr: Box::new(expr(nodes.next().unwrap())),
+            body: block(nodes.next().unwrap()),
+        }),
+        NodeKind::While => ControlFlow::While(While {
+            condition: Box::new(expr(nodes.next().unwrap())),
+            body: block(nodes.next().unwrap()),
+        }),
+        NodeKind::Loop => ControlFlow::Loop(block(first(node))),
+        NodeKind::Match => ControlFlow::Match(Match {
+            expr: Box::new(expr(nodes.next().unwrap())),
+            arm: nodes
+                .map(|arm| {
+                    let mut nodes = arm.nodes();
+                    MatchArm {
+                        pattern: pattern(nodes.next().unwrap()),
+                        expr: expr(nodes.next().unwrap()),
+                    }
+                })
+                .collect(),
+        }),
+        kind => unreachable!("{kind:?} is not a control flow"),
+    }
+}
+fn place(node: &Node) -> PlaceExpr<()> {
+    match expr(node).expr {
+        ExprKind::Place(place) => place,
+        _ => unreachable!("not a place expression"),
+    }
+}
+pub(crate) fn expr(node: &Node) -> Expr<()> {
+    let mut nodes = node.nodes();
+    let kind = match node.kind {
+        // parentheses are transparent, the span is the one of the inside
+        NodeKind::Group => return expr(first(node)),
+        NodeKind::Literal => ExprKind::Literal(literal(node.tokens().next().unwrap())),
+        NodeKind::String => {
+            let string = node.token_of(TokenKind::String).unwrap();
+            let (bytes, _) = string_literal().parse(&string.text[..]).unwrap();
+            ExprKind::Str(Str(bytes.into()))
+        }
+        NodeKind::Tag => ExprKind::Tag(Tag {
+            tag: atom(ident(node)),
+            expr: nodes.next().map(|expr| Box::new(self::expr(expr))),
+        }),
+        NodeKind::Assign => {
+            // places and values are separated by `<-`
+            let arrow = node
+                .children
+                .iter()
+                .position(|element| matches!(element, Element::Token(token) if token.text == "<-"))
+                .unwrap();
+            let mut places = Vec::new();
+            let mut exprs = Vec::new();
+            for (i, element) in node.children.iter().enumerate() {
+                if let Element::Node(node) = element {
+                    if i < arrow {
+                        places.push(node);
+                    } else {
+                        exprs.push(node);
+                    }
+                }
+            }
+            ExprKind::Assign(
+                places
+                    .into_iter()
+                    .zip(exprs)
+                    .map(|(place, expr)| Assign {
+                        place: self::place(place),
+                        expr: self::expr(expr),
+                    })
+                    .collect(),
+            )
+        }
+        NodeKind::Array => ExprKind::Array(
+            nodes
+                .map(|element| match element.kind {
+                    NodeKind::Spread => ArrayElement {
+                        expr: expr(first(element)),
+                        kind: ElementKind::Splat,
+                    },
+                    _ => ArrayElement {
+                        expr: expr(element),
+                        kind: ElementKind::Element,
+                    },
+                })
+                .collect(),
+        ),
+        NodeKind::Range => ExprKind::ArrayRange(range(node)),
+        NodeKind::Unit => ExprKind::Unit,
+        NodeKind::Splat => ExprKind::Splat(Box::new(expr(first(node)))),
+        NodeKind::Tuple => ExprKind::Tuple(expr_collection(node, expr)),
+        NodeKind::Record => ExprKind::Record(expr_collection(node, field)),
+        NodeKind::Unary => ExprKind::Unary(Unary {
+            kind: unary_type(&node.tokens().next().unwrap().text),
+            expr: Box::new(expr(first(node))),
+        }),
+        NodeKind::Binary => ExprKind::Binary(Binary {
+            kind: binary_type(&node.tokens().next().unwrap().text),
+            left: Box::new(expr(nodes.next().unwrap())),
+            right: Box::new(expr(nodes.next().unwrap())),
+        }),
+        NodeKind::Var => {
+            let var = ident(node);
+            ExprKind::Place(PlaceExpr::Var(atom(var), var.span))
+        }
+        NodeKind::FieldAccess => ExprKind::Place(PlaceExpr::FieldAccess(FieldAccess {
+            expr: Box::new(expr(first(node))),
+            name: atom(ident(node)),
+        })),
+        NodeKind::Len => ExprKind::Place(PlaceExpr::Len(Box::new(expr(first(node))))),
+        NodeKind::Index => ExprKind::Place(PlaceExpr::Index(Index {
+            expr: Box::new(expr(nodes.next().unwrap())),
+            index: Box::new(expr(nodes.next().unwrap())),
+        })),
+        NodeKind::Slice => ExprKind::Place(PlaceExpr::Slice(Slice {
+            expr: Box::new(expr(nodes.next().unwrap())),
+            range: range(nodes.next().unwrap()),
+        })),
+        NodeKind::Deref => ExprKind::Place(PlaceExpr::Deref(Box::new(expr(first(node))))),
+        NodeKind::Call => {
+            let callee = expr(nodes.next().unwrap());
+            let arg = nodes.next().unwrap();
+            let arg = match arg.kind {
+                NodeKind::Unit => Arg::Unit,
+                NodeKind::Splat => Arg::Splat(Box::new(expr(first(arg)))),
+                NodeKind::Tuple => Arg::Tuple(expr_collection(arg, expr)),
+                _ => Arg::Record(expr_collection(arg, field)),
+            };
+            ExprKind::Call(Call {
+                expr: Box::new(callee),
+                arg,
+            })
+        }
+        NodeKind::Block
+        | NodeKind::If
+        | NodeKind::For
+        | NodeKind::While
+        | NodeKind::Loop
+        | NodeKind::Match => ExprKind::ControlFlow(control_flow(node)),
+        NodeKind::Fun => ExprKind::Fun(fun(node, nodes)),
+        NodeKind::Jump => {
+            let value = nodes.next().map(|value| Box::new(expr(value)));
+            match &node.tokens().next().unwrap().text[..] {
+                "break" => ExprKind::Jump(Jump::Break(value)),
+                "continue" => ExprKind::Jump(Jump::Continue),
+                _ => ExprKind::Jump(Jump::Return(value)),
+            }
+        }
+        NodeKind::Never => ExprKind::Never,
+        NodeKind::Annotated => ExprKind::Annotated(Annotated {
+            expr: Box::new(expr(nodes.next().unwrap())),
+            annotation: ty(nodes.next().unwrap()),
+        }),
+        kind => unreachable!("{kind:?} is not an expression"),
+    };
+    Expr {
+        expr: kind,
+        ty: (),
+        span: node.span(),
+    }
+}
+fn var(node: &Node) -> Var {
+    let ident = ident(node);
+    Var {
+        ident: atom(ident),
+        span: ident.span,
+        mutable: node.token("mut").is_some(),
+        bind_to_ref: node.token("ref").is_some(),
+    }
+}
+fn list_pattern(node: &Node) -> ListPattern<()> {
+    match collection(node, pattern) {
+        (left, Some(rest), right) => ListPattern::ListWithRest(ListWithRest {
+            left: left.into(),
+            rest: Box::new(pattern(rest)),
+            right: right.into(),
+        }),
+        (left, None, _) => ListPattern::List(left.into()),
+    }
+}
+pub(crate) fn pattern(node: &Node) -> Pattern<()> {
+    let kind = match node.kind {
+        NodeKind::GroupPattern => return pattern(first(node)),
+        NodeKind::LiteralPattern => {
+            let literal = node.tokens().next().unwrap();
+            match &literal.text[..] {
+                "true" => PatternKind::True,
+                "false" => PatternKind::False,
+                _ => PatternKind::UInt(integer_u64().parse(&literal.text[..]).unwrap().0),
+            }
+        }
+        NodeKind::DiscardPattern => PatternKind::Discard,
+        NodeKind::VarPattern => PatternKind::Var(var(node)),
+        NodeKind::RecordPattern => {
+            let (left, rest, right) = collection(node, |field| {
+                (field_name(field).unwrap(), pattern(first(field)))
+            });
+            PatternKind::Record(RecordPattern {
+                fields: left.into_iter().chain(right).collect(),
+                rest: rest.map(|rest| Box::new(pattern(rest))),
+            })
+        }
+        NodeKind::TuplePattern => PatternKind::Tuple(list_pattern(node)),
+        NodeKind::ArrayPattern => PatternKind::Array(list_pattern(node)),
+        NodeKind::Param => PatternKind::Param(
+            node.nodes()
+                .map(|parameter| {
+                    let mut nodes = parameter.nodes();
+                    TypedVar {
+                        var: var(nodes.next().unwrap()),
+                        annotation: nodes.next().map(ty),
+                        ty: (),
+                    }
+                })
+                .collect(),
+        ),
+        NodeKind::TagPattern => PatternKind::Tag(TaggedPattern {
+            tag: atom(ident(node)),
+            pattern: node
+                .nodes()
+                .next()
+                .map(|pattern| Box::new(self::pattern(pattern))),
+        }),
+        NodeKind::RefPattern => PatternKind::Ref(Box::new(pattern(first(node)))),
+        NodeKind::AnnotatedPattern => {
+            let mut nodes = node.nodes();
+            PatternKind::Annotated(AnnotatedPattern {
+                pattern: Box::new(pattern(nodes.next().unwrap())),
+                annotation: ty(nodes.next().unwrap()),
+            })
+        }
+        kind => unreachable!("{kind:?} is not a pattern"),
+    };
+    Pattern {
+        pattern: kind,
+        ty: (),
+        span: node.span(),
+    }
+}
+pub(crate) fn ty(node: &Node) -> TypeExpr {
+    let mut nodes = node.nodes();
+    match node.kind {
+        NodeKind::GroupType => ty(first(node)),
+        NodeKind::DiscardType => TypeExpr::Discard,
+        NodeKind::NameType => TypeExpr::Name(atom(ident(node)), nodes.map(ty).collect()),
+        NodeKind::VarType => TypeExpr::Var(atom(ident(node))),
+        NodeKind::ArrayType => TypeExpr::Array(Box::new(ty(first(node)))),
+        NodeKind::TupleType => {
+            let (left, rest, right) = collection(node, ty);
+            TypeExpr::Tuple(TupleType {
+                left: left.into(),
+                rest: rest.map(|rest| Box::new(ty(rest))),
+                right: right.into(),
+            })
+        }
+        NodeKind::RecordType => {
+            let (left, rest, right) =
+                collection(node, |field| (atom(ident(field)), ty(first(field))));
+            TypeExpr::Record(RecordType {
+                fields: left.into_iter().chain(right).collect(),
+                rest: rest.map(|rest| Box::new(ty(rest))),
+            })
+        }
+        NodeKind::UnionType => {
+            let (left, rest, right) =
+                collection(node, |tag| (atom(ident(tag)), tag.nodes().next().map(ty)));
+            TypeExpr::Union(UnionType {
+                tags: left.into_iter().chain(right).collect(),
+                rest: rest.map(|rest| Box::new(ty(rest))),
+            })
+        }
+        NodeKind::RefType => {
+            let mut mutability = None;
+            let mut lifetime = None;
+            let mut tokens = node.tokens();
+            while let Some(token) = tokens.next() {
+                match &token.text[..] {
+                    ":" => {
+                        mutability = tokens.next().map(|token| match &token.text[..] {
+                            "mut" => MutTypeExpr::Mut,
+                            "imm" => MutTypeExpr::Imm,
+                            _ => MutTypeExpr::Var(atom(token)),
+                        })
+                    }
+                    "/" => lifetime = tokens.next().map(atom),
+                    _ => (),
+                }
+            }
+            TypeExpr::Ref(RefType {
+                mutability,
+                lifetime,
+                ty: Box::new(ty(first(node))),
+            })
+        }
+        NodeKind::FunType => TypeExpr::Fun(
+            Box::new(ty(nodes.next().unwrap())),
+            Box::new(ty(nodes.next().unwrap())),
+        ),
+        kind => unreachable!("{kind:?} is not a type"),
+    }
+}
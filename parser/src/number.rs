@@ -1,3 +1,4 @@
+use crate::Offset;
 use combine::{
     attempt, choice,
     error::StreamError,
@@ -48,6 +49,7 @@ pub(super) fn integer_str<I>(base: u8) -> impl Parser<I, Output = Atom>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     recognize::<String, _, _>((
         satisfy(move |ch| parse_digit(ch, base).is_some()),
@@ -61,6 +63,7 @@ pub(super) fn integer_str_allow_underscore<I>(base: u8) -> impl Parser<I, Output
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     many1::<String, _, _>(satisfy(move |ch| {
         parse_digit(ch, base).is_some() || ch == '_'
@@ -73,6 +76,7 @@ macro_rules! gen_integer_parser {
         where
             I: Stream<Token = char>,
             I::Error: ParseError<I::Token, I::Range, I::Position>,
+            I::Position: Offset,
         {
             let parse_mapper = |base| {
                 move |src: Atom| {
@@ -143,6 +147,7 @@ fn float_src<I>() -> impl Parser<I, Output = FloatSrc>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     let sign = || {
         choice((
@@ -180,6 +185,7 @@ pub(super) fn float<I>() -> impl Parser<I, Output = f64>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     float_src()
         .and_then(|src| {
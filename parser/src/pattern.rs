@@ -1,92 +1,85 @@
 use crate::{
-    ident_keyword::{ident, keyword},
-    lex,
+    cst::{node, Node, NodeKind, TokenKind},
+    lex, lex_ident, lex_keyword, lower,
     number::integer_u64,
-    sep_optional_between,
+    punct, sep_end, sep_optional_between, token,
+    ty::ty,
+    Offset,
 };
 use combine::{
-    attempt, between, choice, error::StreamError, optional, parser::char::char, sep_end_by,
-    stream::StreamErrorFor, value, ParseError, Parser, Stream,
+    attempt, choice, error::StreamError, optional, stream::StreamErrorFor, ParseError, Parser,
+    Stream,
 };
-use hir::{
-    pattern::{ListPattern, ListWithRest, Pattern, PatternKind, RecordPattern, TaggedPattern, Var},
-    Atom,
-};
-use std::collections::HashMap;
 
-fn var<I>() -> impl Parser<I, Output = Var>
+fn var<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     (
-        optional(attempt(lex(keyword("ref")))),
-        optional(attempt(lex(keyword("mut")))),
-        lex(ident()),
+        optional(attempt(lex_keyword("ref"))),
+        optional(attempt(lex_keyword("mut"))),
+        lex_ident(),
     )
-        .map(|(bind_to_ref, mutability, ident)| Var {
-            ident,
-            mutable: mutability.is_some(),
-            bind_to_ref: bind_to_ref.is_some(),
-        })
+        .map(|var| node(NodeKind::VarPattern, var))
 }
-fn list<I>() -> impl Parser<I, Output = ListPattern<()>>
+fn list<I>() -> impl Parser<I, Output = Vec<crate::cst::Element>>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    sep_optional_between(pattern, lex(char('*')).with(pattern()), || lex(char(','))).map(
-        |(left, rest_right)| {
-            let left: Vec<_> = left;
-            match rest_right {
-                Some((rest, right)) => ListPattern::ListWithRest(ListWithRest {
-                    left: left.into(),
-                    rest: Box::new(rest),
-                    right: right.into(),
-                }),
-                None => ListPattern::List(left.into()),
-            }
-        },
+    sep_optional_between(
+        pattern,
+        (punct('*'), pattern()).map(|rest| node(NodeKind::Spread, rest)),
+        || punct(','),
     )
 }
-fn array<I>() -> impl Parser<I, Output = ListPattern<()>>
+fn array<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    between(lex(char('[')), lex(char(']')), list()).expected("array pattern")
+    (punct('['), list(), punct(']'))
+        .map(|array| node(NodeKind::ArrayPattern, array))
+        .expected("array pattern")
 }
-fn tuple<I>() -> impl Parser<I, Output = ListPattern<()>>
+fn tuple<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    between(lex(char('(')), lex(char(')')), list()).expected("tuple pattern")
+    (punct('('), list(), punct(')'))
+        .map(|tuple| node(NodeKind::TuplePattern, tuple))
+        .expected("tuple pattern")
 }
-pub(super) fn parameter<I>() -> impl Parser<I, Output = Pattern<()>>
+pub(super) fn parameter<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    between(
-        lex(char('(')),
-        lex(char(')')),
-        sep_end_by(var().map(Var::into_untyped), lex(char(','))),
-    )
-    .map(Vec::into)
-    .map(PatternKind::Param)
-    .map(PatternKind::into_untyped)
-    .expected("parameter")
+    let parameter = || {
+        (var(), optional((punct(':'), ty()))).map(|parameter| node(NodeKind::Parameter, parameter))
+    };
+    (punct('('), sep_end(parameter, || punct(',')), punct(')'))
+        .map(|param| node(NodeKind::Param, param))
+        .expected("parameter")
 }
-fn record<I>() -> impl Parser<I, Output = RecordPattern<()>>
+fn record<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     let field = || {
-        (optional(lex(ident())), lex(char('=')).with(pattern())).and_then(|(name, pattern)| {
-            match name.map(Atom::from).or_else(|| pattern.field_name()) {
-                Some(name) => Ok((name, pattern)),
+        (optional(lex_ident()), punct('='), pattern()).and_then(|field| {
+            let field = node(NodeKind::FieldPattern, field);
+            match lower::field_name(&field) {
+                Some(_) => Ok(field),
                 None => Err(<StreamErrorFor<I>>::message_static_message(
                     "couldn't infer field name",
                 )),
@@ -94,73 +87,52 @@ where
         })
     };
     // TODO: handle duplicate name as error
-    between(
-        lex(char('(')),
-        lex(char(')')),
-        sep_optional_between(field, lex(char('*')).with(pattern()), || lex(char(','))),
+    (
+        punct('('),
+        sep_optional_between(
+            field,
+            (punct('*'), pattern()).map(|rest| node(NodeKind::Spread, rest)),
+            || punct(','),
+        ),
+        punct(')'),
     )
-    .map(|(left, rest_right)| match rest_right {
-        Some((rest, right)) => {
-            let mut fields: HashMap<_, _> = left;
-            fields.extend(right);
-            RecordPattern {
-                fields,
-                rest: Some(Box::new(rest)),
-            }
-        }
-        None => RecordPattern {
-            fields: left,
-            rest: None,
-        },
-    })
-    .expected("record pattern")
+        .map(|record| node(NodeKind::RecordPattern, record))
+        .expected("record pattern")
 }
-fn pattern_<I>() -> impl Parser<I, Output = Pattern<()>>
+fn pattern_<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
     choice((
-        lex(char('@'))
-            .with((lex(ident()), optional(pattern())))
-            .map(|(tag, pattern)| {
-                PatternKind::Tag(TaggedPattern {
-                    tag,
-                    pattern: pattern.map(Box::new),
-                })
-            })
-            .map(PatternKind::into_untyped),
-        lex(char('&'))
-            .with(pattern())
-            .map(Box::new)
-            .map(PatternKind::Ref)
-            .map(PatternKind::into_untyped),
+        (punct('@'), lex_ident(), optional(pattern())).map(|tag| node(NodeKind::TagPattern, tag)),
+        (punct('&'), pattern()).map(|reference| node(NodeKind::RefPattern, reference)),
         // TODO: minus integer
-        integer_u64()
-            .map(PatternKind::UInt)
-            .map(PatternKind::into_untyped),
-        attempt(between(lex(char('(')), lex(char(')')), pattern())).expected("group"),
-        record()
-            .map(PatternKind::Record)
-            .map(PatternKind::into_untyped),
-        tuple()
-            .map(PatternKind::Tuple)
-            .map(PatternKind::into_untyped),
-        array()
-            .map(PatternKind::Array)
-            .map(PatternKind::into_untyped),
-        attempt(lex(keyword("_"))).with(value(PatternKind::Discard.into_untyped())),
-        attempt(lex(keyword("true"))).with(value(PatternKind::True.into_untyped())),
-        attempt(lex(keyword("false"))).with(value(PatternKind::False.into_untyped())),
-        var().map(PatternKind::Var).map(PatternKind::into_untyped),
+        lex(token(TokenKind::Integer, integer_u64()))
+            .map(|integer| node(NodeKind::LiteralPattern, integer)),
+        attempt((punct('('), pattern(), punct(')')))
+            .map(|group| node(NodeKind::GroupPattern, group))
+            .expected("group"),
+        attempt(record()),
+        tuple(),
+        array(),
+        attempt(lex_keyword("_")).map(|discard| node(NodeKind::DiscardPattern, discard)),
+        attempt(lex_keyword("true")).map(|literal| node(NodeKind::LiteralPattern, literal)),
+        attempt(lex_keyword("false")).map(|literal| node(NodeKind::LiteralPattern, literal)),
+        var(),
     ))
 }
 combine::parser! {
-    pub(super) fn pattern[I]()(I) -> Pattern<()>
+    pub(super) fn pattern[I]()(I) -> Node
     where [
         I: Stream<Token = char>,
         I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
     ] {
-        pattern_()
+        (pattern_(), optional((punct(':'), ty()))).map(|(pattern, annotation)| match annotation {
+            Some(annotation) => node(NodeKind::AnnotatedPattern, (pattern, annotation)),
+            None => pattern,
+        })
     }
 }
@@ -1,72 +1,178 @@
 use crate::{
-    control_flow::control_flow,
+    control_flow::{block, control_flow},
+    cst::{elements, node, Element, Node, NodeKind, TokenKind},
     expr::expr,
-    ident_keyword::ident,
-    lex,
+    ident_keyword::{ident, keyword},
+    insignificants, lex_field_name, lex_ident, lex_keyword, lower,
     pattern::{parameter, pattern},
+    punct, punct_str, sep_end, token,
+    ty::ty,
+    Offset,
 };
 use combine::{
-    attempt, choice,
-    error::StreamError,
-    look_ahead, optional,
-    parser::char::{char, string},
-    sep_by1,
-    stream::StreamErrorFor,
-    value, ParseError, Parser, Stream,
-};
-use hir::{
-    expr::{Assign, Expr, ExprKind, Fun},
-    statement::{Declare, FunDeclare, Statement},
+    attempt, choice, error::StreamError, look_ahead, many, optional, parser::char::char,
+    stream::StreamErrorFor, ParseError, Parser, Stream,
 };
 
-pub(super) enum StatementReturn {
-    Statement(Statement<()>),
-    Return(Expr<()>),
+// `pub(` without space is a restricted visibility, so `pub (a, b) = ...`
+// still declares a tuple
+fn visibility<I>() -> impl Parser<I, Output = Node>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    (
+        attempt(token(TokenKind::Keyword, keyword("pub"))),
+        optional((
+            punct('('),
+            lex_ident(),
+            many::<Vec<_>, _, _>((punct('.'), lex_ident())),
+            token(TokenKind::Punctuation, char(')')),
+        )),
+        insignificants(),
+    )
+        .map(|visibility| node(NodeKind::Visibility, visibility))
 }
-pub(super) fn statement_return<I, P>(end_look_ahead: P) -> impl Parser<I, Output = StatementReturn>
+/// `Eq([a])`, a trait applied to a type
+fn trait_bound<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    (lex_ident(), punct('('), ty(), punct(')')).map(|bound| node(NodeKind::TraitBound, bound))
+}
+/// Statement of a block, an expression statement without a semicolon is the
+/// value of the block if it is followed by `end_look_ahead`
+pub(super) fn statement_return<I, P>(end_look_ahead: P) -> impl Parser<I, Output = Node>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
     P: Parser<I>,
 {
     let control_flow_statement = || {
-        (control_flow(), optional(lex(char(';')))).map(|(control_flow, semicolon)| {
-            let expr = ExprKind::ControlFlow(control_flow).into_untyped();
-            match semicolon {
-                Some(_) => StatementReturn::Statement(Statement::Expr(expr)),
-                None => StatementReturn::Return(expr),
-            }
-        })
+        (control_flow(), optional(punct(';')))
+            .map(|statement| node(NodeKind::ExprStatement, statement))
     };
     let fun_body = || {
         choice((
-            control_flow()
-                .skip(optional(lex(char(';'))))
-                .map(ExprKind::ControlFlow)
-                .map(ExprKind::into_untyped),
-            expr(0).skip(lex(char(';'))),
+            (control_flow(), optional(punct(';'))),
+            (expr(0), punct(';').map(Some)),
         ))
     };
+    let generics = || {
+        (
+            punct(':'),
+            punct('('),
+            sep_end(lex_ident, || punct(',')),
+            punct(')'),
+            punct(':'),
+        )
+            .map(|generics| node(NodeKind::Generics, generics))
+    };
+    let given = || {
+        (
+            attempt(lex_keyword("given")),
+            sep_end(trait_bound, || punct(',')),
+            punct(':'),
+        )
+            .map(|given| node(NodeKind::Given, given))
+    };
     let fun_declare = || {
         (
-            attempt((ident(), parameter().skip(lex(string("=>"))))),
+            optional(given()),
+            optional(generics()),
+            attempt((
+                token(TokenKind::Ident, ident()),
+                parameter(),
+                optional((punct_str("->"), ty())),
+                punct_str("=>"),
+            )),
             fun_body(),
         )
-            .map(|((ident, param), body)| {
-                Statement::FunDeclare(FunDeclare {
-                    ident,
-                    fun: Fun {
-                        param,
-                        body: Box::new(body),
-                    },
-                    ty: (),
-                })
-            })
+            .map(|fun_declare| node(NodeKind::FunDeclare, fun_declare))
+    };
+    let alias_declare = || {
+        (
+            attempt(lex_keyword("alias")),
+            lex_ident(),
+            optional(
+                (punct('('), sep_end(lex_ident, || punct(',')), punct(')'))
+                    .map(|params| node(NodeKind::TypeParams, params)),
+            ),
+            punct('='),
+            ty(),
+            punct(';'),
+        )
+            .map(|alias_declare| node(NodeKind::AliasDeclare, alias_declare))
+    };
+    let newtype_declare = || {
+        let field = || {
+            (optional(visibility()), lex_field_name(), punct(':'), ty())
+                .map(|field| node(NodeKind::NewtypeField, field))
+        };
+        (
+            attempt((optional(generics()), lex_keyword("newtype"))),
+            lex_ident(),
+            punct('('),
+            sep_end(field, || punct(',')),
+            punct(')'),
+            punct(';'),
+        )
+            .map(|newtype_declare| node(NodeKind::NewtypeDeclare, newtype_declare))
+    };
+    let trait_declare = || {
+        let method_signature = (lex_ident(), parameter(), punct_str("->"), ty(), punct(';'))
+            .map(|signature| node(NodeKind::MethodSignature, signature));
+        (
+            attempt(lex_keyword("trait")),
+            (lex_ident(), punct('('), lex_ident(), punct(')')),
+            punct('{'),
+            many::<Vec<_>, _, _>(method_signature),
+            punct('}'),
+            optional(punct(';')),
+        )
+            .map(|trait_declare| node(NodeKind::TraitDeclare, trait_declare))
+    };
+    let impl_declare = || {
+        (
+            attempt((optional(given()), lex_keyword("impl"))),
+            trait_bound(),
+            (punct('{'), many::<Vec<_>, _, _>(fun_declare()), punct('}')),
+            optional(punct(';')),
+        )
+            .map(|impl_declare| node(NodeKind::ImplDeclare, impl_declare))
+    };
+    let module_declare = || {
+        let inline = |open, close| {
+            (punct(open), statements(), punct(close))
+                .map(|module| node(NodeKind::ModuleBody, module))
+        };
+        choice((
+            (
+                attempt(lex_keyword("mod")),
+                lex_ident(),
+                choice((
+                    punct(';').map(|semicolon| (None, Some(semicolon))),
+                    (inline('{', '}').map(Some), optional(punct(';'))),
+                )),
+            )
+                .map(elements),
+            (
+                attempt((lex_ident(), punct('='), lex_keyword("mod"))),
+                choice((lex_ident(), inline('(', ')').map(elements))),
+                punct(';'),
+            )
+                .map(elements),
+        ))
+        .map(|module_declare| node(NodeKind::ModuleDeclare, module_declare))
     };
     let place = || {
         expr(1).and_then(|expr| {
-            if let ExprKind::Place(place) = expr.expr {
-                Ok(place)
+            if lower::is_place(&expr) {
+                Ok(expr)
             } else {
                 Err(<StreamErrorFor<I>>::expected_static_message(
                     "place expression",
@@ -75,81 +181,123 @@ where
         })
     };
     let declare = || {
-        (attempt(pattern().skip(lex(char('=')))), expr(0))
-            .skip(lex(char(';')))
-            .map(|(pattern, expr)| {
-                StatementReturn::Statement(Statement::Declare(Declare { pattern, expr }))
+        (
+            attempt((pattern(), punct('='))),
+            expr(0),
+            optional((lex_keyword("else"), block())),
+            punct(';'),
+        )
+            .map(|declare| node(NodeKind::Declare, declare))
+    };
+    let declaration = || {
+        (
+            optional(visibility()),
+            choice((
+                trait_declare(),
+                newtype_declare(),
+                module_declare(),
+                declare(),
+                fun_declare(),
+            )),
+        )
+            .map(|(visibility, mut declaration)| {
+                if let Some(visibility) = visibility {
+                    declaration.children.insert(0, Element::Node(visibility));
+                }
+                declaration
             })
     };
     let parallel_assign = || {
         (
-            attempt(sep_by1(place(), lex(char(','))).skip(lex(string("<-")))),
-            sep_by1(expr(0), lex(char(','))),
+            attempt((
+                place(),
+                many::<Vec<_>, _, _>((punct(','), place())),
+                punct_str("<-"),
+            )),
+            expr(0),
+            many::<Vec<_>, _, _>((punct(','), expr(0))),
         )
-            .and_then(|(place, expr)| {
-                let place: Vec<_> = place;
-                let expr: Vec<_> = expr;
-                if place.len() != expr.len() {
+            .and_then(|(places, first, exprs)| {
+                let (_, rest_places, _) = &places;
+                if rest_places.len() != exprs.len() {
                     return Err(<StreamErrorFor<I>>::message_static_message(
                         "mismatching count of place and value expressions",
                     ));
                 }
-                let assign = place
-                    .into_iter()
-                    .zip(expr.into_iter())
-                    .map(|(place, expr)| Assign { place, expr })
-                    .collect();
-                Ok(ExprKind::Assign(assign).into_untyped())
+                Ok(node(NodeKind::Assign, (places, first, exprs)))
             })
     };
     let expr = || {
         (
             choice((parallel_assign(), expr(0))),
             choice((
-                lex(char(';')).with(value(false)),
-                look_ahead(end_look_ahead).with(value(true)),
+                punct(';').map(Some),
+                look_ahead(end_look_ahead).map(|_| None),
             )),
         )
-            .map(|(expr, implicit_return)| {
-                if implicit_return {
-                    StatementReturn::Return(expr)
-                } else {
-                    StatementReturn::Statement(Statement::Expr(expr))
-                }
-            })
+            .map(|statement| node(NodeKind::ExprStatement, statement))
     };
     choice((
         control_flow_statement(),
-        declare(),
-        fun_declare().map(StatementReturn::Statement),
+        alias_declare(),
+        impl_declare(),
+        declaration(),
         expr(),
     ))
 }
-pub(super) fn statement<I>() -> impl Parser<I, Output = Statement<()>>
+combine::parser! {
+    pub(super) fn statements[I]()(I) -> Vec<Node>
+    where [
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    ] {
+        many(statement())
+    }
+}
+pub(super) fn statement<I>() -> impl Parser<I, Output = Node>
 where
     I: Stream<Token = char>,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
 {
-    statement_return(char(';')).map(|statement_return| match statement_return {
-        StatementReturn::Statement(statement) => statement,
-        StatementReturn::Return(expr) => Statement::Expr(expr),
-    })
+    statement_return(char(';'))
 }
 #[cfg(test)]
 mod test {
     use crate::{
-        statement::{statement, Assign, ExprKind},
+        ast, lower,
         test::{var_expr, var_place},
-        Statement,
+        Offset,
     };
-    use combine::EasyParser;
+    use combine::{EasyParser, ParseError, Parser, Stream};
     use hir::{
-        expr::Literal,
-        pattern::{PatternKind, Var},
-        statement::Declare,
-        Atom,
+        expr::{Annotated, Assign, Block, ExprKind, Fun, Jump, Literal, PlaceExpr},
+        fold::Fold,
+        pattern::{AnnotatedPattern, PatternKind, TaggedPattern, TypedVar, Var},
+        pretty_print::PrettyPrint,
+        span::EraseSpans,
+        statement::{
+            AliasDeclare, Declare, FunDeclare, Module, ModuleDeclare, Statement, Visibility,
+        },
+        ty::{TypeExpr, UnionType},
+        Atom, Span,
     };
 
+    fn statement<I>() -> impl Parser<I, Output = Statement<()>>
+    where
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    {
+        super::statement().map(|statement| EraseSpans.fold_statement(lower::statement(&statement)))
+    }
+
+    fn print(statement: &Statement<()>) -> String {
+        let mut buffer = Vec::new();
+        statement.pretty_print(&mut buffer, "    ", 80).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
     #[test]
     fn parallel_assign() {
         let src = "foo, bar <- bar, foo;";
@@ -197,14 +345,250 @@ mod test {
     fn var() {
         let src = "foo = 10;";
         let expected = Statement::Declare(Declare {
+            visibility: Visibility::Private,
             pattern: PatternKind::Var(Var {
                 ident: Atom::from("foo"),
+                span: Span::default(),
                 mutable: false,
                 bind_to_ref: false,
             })
             .into_untyped(),
             expr: ExprKind::Literal(Literal::UInt(10)).into_untyped(),
+            else_part: None,
         });
         assert_eq!(statement().easy_parse(src), Ok((expected, "")));
     }
+    #[test]
+    fn var_else() {
+        let src = "@val foo = bar else { return; };";
+        let expected = Statement::Declare(Declare {
+            visibility: Visibility::Private,
+            pattern: PatternKind::Tag(TaggedPattern {
+                tag: Atom::from("val"),
+                pattern: Some(Box::new(
+                    PatternKind::Var(Var {
+                        ident: Atom::from("foo"),
+                        span: Span::default(),
+                        mutable: false,
+                        bind_to_ref: false,
+                    })
+                    .into_untyped(),
+                )),
+            })
+            .into_untyped(),
+            expr: var_expr("bar"),
+            else_part: Some(Block {
+                statement: vec![Statement::Expr(
+                    ExprKind::Jump(Jump::Return(None)).into_untyped(),
+                )]
+                .into(),
+                expr: None,
+                span: Span::default(),
+            }),
+        });
+        assert_eq!(statement().easy_parse(src), Ok((expected, "")));
+    }
+    #[test]
+    fn annotated_var() {
+        let src = "foo : Num = 10 : _;";
+        let expected = Statement::Declare(Declare {
+            visibility: Visibility::Private,
+            pattern: PatternKind::Annotated(AnnotatedPattern {
+                pattern: Box::new(
+                    PatternKind::Var(Var {
+                        ident: Atom::from("foo"),
+                        span: Span::default(),
+                        mutable: false,
+                        bind_to_ref: false,
+                    })
+                    .into_untyped(),
+                ),
+                annotation: TypeExpr::Name(Atom::from("Num"), Box::new([])),
+            })
+            .into_untyped(),
+            expr: ExprKind::Annotated(Annotated {
+                expr: Box::new(ExprKind::Literal(Literal::UInt(10)).into_untyped()),
+                annotation: TypeExpr::Discard,
+            })
+            .into_untyped(),
+            else_part: None,
+        });
+        assert_eq!(statement().easy_parse(src), Ok((expected, "")));
+    }
+    #[test]
+    fn fun_signature() {
+        let src = ":(a): id(x : a) -> a => x;";
+        let expected = Statement::FunDeclare(FunDeclare {
+            visibility: Visibility::Private,
+            ident: Atom::from("id"),
+            span: Span::default(),
+            given: vec![].into(),
+            generics: vec![Atom::from("a")].into(),
+            fun: Fun {
+                param: PatternKind::Param(
+                    vec![TypedVar {
+                        var: Var {
+                            ident: Atom::from("x"),
+                            span: Span::default(),
+                            mutable: false,
+                            bind_to_ref: false,
+                        },
+                        annotation: Some(TypeExpr::Var(Atom::from("a"))),
+                        ty: (),
+                    }]
+                    .into(),
+                )
+                .into_untyped(),
+                ret: Some(TypeExpr::Var(Atom::from("a"))),
+                body: Box::new(var_expr("x")),
+            },
+            ty: (),
+        });
+        assert_eq!(statement().easy_parse(src), Ok((expected, "")));
+    }
+    #[test]
+    fn alias() {
+        let src = "alias Option(a) = @val a | @none;";
+        let expected = Statement::AliasDeclare(AliasDeclare {
+            ident: Atom::from("Option"),
+            span: Span::default(),
+            params: vec![Atom::from("a")].into(),
+            ty: TypeExpr::Union(UnionType {
+                tags: vec![
+                    (Atom::from("val"), Some(TypeExpr::Var(Atom::from("a")))),
+                    (Atom::from("none"), None),
+                ]
+                .into(),
+                rest: None,
+            }),
+        });
+        assert_eq!(statement().easy_parse(src), Ok((expected, "")));
+    }
+    #[test]
+    fn module() {
+        let src = "mod math { pi = 3; };";
+        let (module, rest) = statement().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        let printed = print(&module);
+        assert_eq!(
+            statement().easy_parse(&printed[..]),
+            Ok((module.clone(), ""))
+        );
+        assert_eq!(
+            statement().easy_parse("math = mod (pi = 3;);"),
+            Ok((module, "")),
+        );
+        let (module, _) = statement().easy_parse("mod math;").unwrap();
+        assert_eq!(
+            module,
+            Statement::ModuleDeclare(ModuleDeclare {
+                visibility: Visibility::Private,
+                ident: Atom::from("math"),
+                span: Span::default(),
+                module: Module::File(Atom::from("math")),
+            }),
+        );
+        let (module, _) = statement().easy_parse("m = mod math;").unwrap();
+        assert_eq!(print(&module), "m = mod math");
+    }
+    #[test]
+    fn trait_impl() {
+        let src = "trait Show(a) { show(x: &a) -> [Num]; }";
+        let (declare, rest) = statement().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        let Statement::TraitDeclare(trait_declare) = &declare else {
+            panic!("expected trait declaration");
+        };
+        assert_eq!(trait_declare.param, Atom::from("a"));
+        assert_eq!(trait_declare.methods.len(), 1);
+        let printed = print(&declare);
+        assert_eq!(statement().easy_parse(&printed[..]), Ok((declare, "")));
+        let src = "given Show(a): impl Show([a]) { show(x) => [1]; }";
+        let (declare, rest) = statement().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        let Statement::ImplDeclare(impl_declare) = &declare else {
+            panic!("expected implementation");
+        };
+        assert_eq!(impl_declare.given.len(), 1);
+        assert_eq!(impl_declare.methods.len(), 1);
+        let printed = print(&declare);
+        assert_eq!(statement().easy_parse(&printed[..]), Ok((declare, "")));
+        let src = "given Show(a), Show(b): f(x: a, y: b) => show(&x);";
+        let (declare, rest) = statement().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        let Statement::FunDeclare(fun_declare) = &declare else {
+            panic!("expected function declaration");
+        };
+        assert_eq!(fun_declare.given.len(), 2);
+        let printed = format!("{};", print(&declare));
+        assert_eq!(statement().easy_parse(&printed[..]), Ok((declare, "")));
+    }
+    #[test]
+    fn newtype() {
+        let src = "pub :(a): newtype Point(x: a, #y: a, pub(app) #z: Num);";
+        let (declare, rest) = statement().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        let Statement::NewtypeDeclare(newtype) = &declare else {
+            panic!("expected newtype declaration");
+        };
+        assert_eq!(newtype.visibility, Visibility::Public);
+        assert_eq!(&*newtype.generics, [Atom::from("a")]);
+        let fields: Vec<_> = newtype
+            .fields
+            .iter()
+            .map(|field| (field.name.as_ref(), field.visibility.clone()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("x", Visibility::Public),
+                ("#y", Visibility::Private),
+                ("#z", Visibility::Restricted(vec![Atom::from("app")].into())),
+            ],
+        );
+        let printed = format!("{};", print(&declare));
+        assert_eq!(statement().easy_parse(&printed[..]), Ok((declare, "")));
+        let (access, _) = statement().easy_parse("y = point.#y;").unwrap();
+        let Statement::Declare(declare) = access else {
+            panic!("expected declaration");
+        };
+        let ExprKind::Place(PlaceExpr::FieldAccess(access)) = declare.expr.expr else {
+            panic!("expected field access");
+        };
+        assert_eq!(access.name, Atom::from("#y"));
+    }
+    #[test]
+    fn visibility() {
+        let (declare, _) = statement().easy_parse("pub(app.ui) x = 1;").unwrap();
+        let Statement::Declare(declare) = declare else {
+            panic!("expected declaration");
+        };
+        assert_eq!(
+            declare.visibility,
+            Visibility::Restricted(vec![Atom::from("app"), Atom::from("ui")].into()),
+        );
+        let (declare, _) = statement().easy_parse("pub (x, y) = 1;").unwrap();
+        assert_eq!(print(&declare), "pub (x, y, ) = 1");
+        let (fun, _) = statement().easy_parse("pub :(a): id(x : a) => x;").unwrap();
+        let Statement::FunDeclare(fun) = fun else {
+            panic!("expected function declaration");
+        };
+        assert_eq!(fun.visibility, Visibility::Public);
+    }
+    #[test]
+    fn span() {
+        let src = "foo = bar  -- comment\n;";
+        let (mut statements, _) = ast().easy_parse(src).unwrap();
+        let Statement::Declare(declare) = statements.remove(0) else {
+            panic!("expected declaration");
+        };
+        let PatternKind::Var(var) = declare.pattern.pattern else {
+            panic!("expected variable");
+        };
+        assert_eq!(var.span.range(), 0..3);
+        let ExprKind::Place(PlaceExpr::Var(_, span)) = declare.expr.expr else {
+            panic!("expected variable");
+        };
+        assert_eq!(span.range(), 6..9);
+    }
 }
@@ -0,0 +1,236 @@
+use crate::{
+    cst::{elements, node, Element, Node, NodeKind, TokenKind},
+    ident_keyword::ident,
+    insignificants, lex_ident, lex_keyword, punct, punct_str, sep_end, sep_optional_between, token,
+    Offset,
+};
+use combine::{
+    attempt, choice, look_ahead, many, optional, parser::char::char, ParseError, Parser, Stream,
+};
+
+fn name<I>() -> impl Parser<I, Output = Node>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    (token(TokenKind::Ident, ident()), insignificants()).then(|(name, trivia)| {
+        if name.text.starts_with(char::is_uppercase) {
+            optional((punct('('), sep_end(ty, || punct(',')), punct(')')))
+                .map(move |args| node(NodeKind::NameType, (name.clone(), trivia.clone(), args)))
+                .left()
+        } else {
+            combine::value(node(NodeKind::VarType, (name, trivia))).right()
+        }
+    })
+}
+fn rest<I>() -> impl Parser<I, Output = Node>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    (
+        punct('*'),
+        choice((
+            attempt(lex_keyword("_")).map(|discard| node(NodeKind::DiscardType, discard)),
+            lex_ident().map(|var| node(NodeKind::VarType, var)),
+        )),
+    )
+        .map(|rest| node(NodeKind::Spread, rest))
+}
+fn record_field<I>() -> impl Parser<I, Output = Node>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    (lex_ident(), punct(':'), ty()).map(|field| node(NodeKind::FieldType, field))
+}
+fn record_fields<I>() -> impl Parser<I, Output = Vec<Element>>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    sep_optional_between(record_field, rest(), || punct(','))
+}
+fn union_tag<I>() -> impl Parser<I, Output = Node>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    (punct('@'), lex_ident(), optional(ty())).map(|tag| node(NodeKind::TagType, tag))
+}
+fn union<I>() -> impl Parser<I, Output = Vec<Element>>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    sep_optional_between(union_tag, rest(), || punct(','))
+}
+// `@tag ty | @tag | *rest`, a union without parentheses
+fn bar_union<I>() -> impl Parser<I, Output = Node>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    let tag =
+        || (punct('@'), lex_ident(), optional(ty_atom())).map(|tag| node(NodeKind::TagType, tag));
+    (
+        tag(),
+        many::<Vec<_>, _, _>((attempt(punct('|').skip(look_ahead(char('@')))), tag())),
+        optional((punct('|'), rest())),
+    )
+        .map(|union| node(NodeKind::UnionType, union))
+}
+fn tuple<I>() -> impl Parser<I, Output = Vec<Element>>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    sep_optional_between(ty, rest(), || punct(','))
+}
+fn parenthesized<I>() -> impl Parser<I, Output = Node>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    (
+        punct('('),
+        choice((
+            attempt(ty().skip(look_ahead(char(')')))).map(|ty| (NodeKind::GroupType, elements(ty))),
+            look_ahead(char('@'))
+                .with(union())
+                .map(|union| (NodeKind::UnionType, union)),
+            attempt(look_ahead((lex_ident(), char(':'))))
+                .with(record_fields())
+                .map(|record| (NodeKind::RecordType, record)),
+            tuple().map(|tuple| (NodeKind::TupleType, tuple)),
+        )),
+        punct(')'),
+    )
+        .map(|(open, (kind, inside), close)| node(kind, (open, inside, close)))
+}
+fn reference<I>() -> impl Parser<I, Output = Node>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    let mutability = choice((
+        attempt(lex_keyword("mut")),
+        attempt(lex_keyword("imm")),
+        lex_ident(),
+    ));
+    (
+        punct('&'),
+        optional((punct(':'), mutability)),
+        optional((punct('/'), lex_ident())),
+        ty_atom(),
+    )
+        .map(|reference| node(NodeKind::RefType, reference))
+}
+fn ty_atom_<I>() -> impl Parser<I, Output = Node>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    choice((
+        attempt(lex_keyword("_")).map(|discard| node(NodeKind::DiscardType, discard)),
+        name(),
+        (punct('['), ty(), punct(']')).map(|array| node(NodeKind::ArrayType, array)),
+        (punct('{'), record_fields(), punct('}')).map(|record| node(NodeKind::RecordType, record)),
+        reference(),
+        parenthesized(),
+    ))
+    .expected("type")
+}
+combine::parser! {
+    fn ty_atom[I]()(I) -> Node
+    where [
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    ] {
+        ty_atom_()
+    }
+}
+fn ty_<I>() -> impl Parser<I, Output = Node>
+where
+    I: Stream<Token = char>,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+    I::Position: Offset,
+{
+    let fun = (ty_atom(), optional((punct_str("->"), ty()))).map(|(param, ret)| match ret {
+        Some(ret) => node(NodeKind::FunType, (param, ret)),
+        None => param,
+    });
+    choice((look_ahead(char('@')).with(bar_union()), fun))
+}
+combine::parser! {
+    pub(super) fn ty[I]()(I) -> Node
+    where [
+        I: Stream<Token = char>,
+        I::Error: ParseError<I::Token, I::Range, I::Position>,
+        I::Position: Offset,
+    ] {
+        ty_()
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::{lower, ty::ty};
+    use combine::{EasyParser, Parser};
+    use hir::ty::TypeExpr;
+
+    fn parse(src: &str) -> TypeExpr {
+        let (ty, rest) = ty().map(|ty| lower::ty(&ty)).easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        ty
+    }
+    #[test]
+    fn array() {
+        assert_eq!(parse("[_]"), TypeExpr::Array(Box::new(TypeExpr::Discard)));
+    }
+    #[test]
+    fn round_trip() {
+        let src = [
+            "Num -> [Str]",
+            "(Num, Bool, )",
+            "(a, *_, )",
+            "{name : Str, *_, }",
+            "(@val _, @none, )",
+            "(@val a, *r, )",
+            "&:mut /l [a]",
+            "&{name : Str, } -> (Num -> Num) -> Bool",
+        ];
+        for src in src {
+            assert_eq!(parse(src).to_string(), src);
+        }
+    }
+    #[test]
+    fn name_args() {
+        assert_eq!(parse("Map(Str, [a])").to_string(), "Map(Str, [a], )");
+    }
+    #[test]
+    fn bar_union() {
+        assert_eq!(parse("@val a | @none"), parse("(@val a, @none)"));
+        assert_eq!(parse("@val [a] | *r"), parse("(@val [a], *r)"));
+        assert_eq!(parse("(@val a | @none)"), parse("(@val a, @none)"));
+    }
+    #[test]
+    fn paren_record() {
+        assert_eq!(parse("(name : Str)"), parse("{name : Str}"));
+    }
+    #[test]
+    fn group() {
+        assert_eq!(parse("(Num)"), parse("Num"));
+    }
+}
@@ -0,0 +1,1634 @@
+//! Symbolic evaluation of numbers and array lengths. Numbers are tracked as
+//! linear combinations of unknowns, conditions of branches and loops become
+//! facts about these unknowns, and each division and index is checked
+//! against the facts on its path.
+//!
+//! Values that can't be tracked, like results of calls, parameters, and
+//! mutable variables after loops, are opaque. An opaque value is still the
+//! same value on every use, its number and length are unknowns made on
+//! demand.
+//!
+//! The analysis is conservative, errors may be reported for code that is
+//! correct. Mutable variables from outside a function are opaque within it,
+//! those borrowed or assigned within a function are opaque from then on
+//! even when they're never changed, and a product of two unknowns is only
+//! known to be non-negative when it's a square.
+
+use crate::linear::{solve, Clause, Constraint, Linear, Outcome, Ratio, Sym};
+use hir::{
+    expr::{
+        Arg, Assign, BinaryType, Block, Bound, BoundType, Collection, ControlFlow, ElementKind,
+        Expr, ExprKind, Fun, Jump, Literal, PlaceExpr, Range, UnaryType,
+    },
+    pattern::{ListPattern, Pattern, PatternKind, Var},
+    resolve::{BindingId, Resolution},
+    statement::{Module, Statement},
+    visit::{walk_assign, walk_fun, walk_jump, Visit},
+    Atom, PrettyPrintType, Span,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+    iter::once,
+    mem::replace,
+};
+
+/// A bound of a range along with whether it's exclusive
+type RangeBound = Option<(Linear, bool)>;
+
+/// Conversions of a condition into clauses before giving up on it
+const MAX_CLAUSES: usize = 16;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RefinementErrorKind {
+    /// The right side of `/`, `//`, or `%` may be zero
+    DivisionByZero,
+    /// The index or a bound of a slice may be negative or past the length
+    IndexOutOfBounds,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RefinementError {
+    pub kind: RefinementErrorKind,
+    /// Values of named numbers and lengths that lead to the error, empty
+    /// when none could be found
+    pub counterexample: Vec<(String, String)>,
+    /// The divisor or the index
+    pub span: Span,
+}
+impl Display for RefinementError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            RefinementErrorKind::DivisionByZero => write!(fmt, "divisor may be zero")?,
+            RefinementErrorKind::IndexOutOfBounds => write!(fmt, "index may be out of bounds")?,
+        }
+        for (i, (name, value)) in self.counterexample.iter().enumerate() {
+            let separator = if i == 0 { ", for example when" } else { " and" };
+            write!(fmt, "{separator} `{name} = {value}`")?;
+        }
+        Ok(())
+    }
+}
+/// Checks that no divisor may be zero and no index may be out of bounds.
+/// Variables are looked up in `resolution` which must be resolved from the
+/// same statements
+pub fn check_refinements<T: PrettyPrintType>(
+    statements: &[Statement<T>],
+    resolution: &Resolution,
+) -> Vec<RefinementError> {
+    let mut captured = Captured {
+        resolution,
+        declared: Vec::new(),
+        captured: HashSet::new(),
+    };
+    for statement in statements {
+        captured.visit_statement(statement);
+    }
+    let mut analysis = Analysis {
+        syms: Vec::new(),
+        opaques: Vec::new(),
+        global: Vec::new(),
+        facts: Vec::new(),
+        resolution,
+        declared: HashMap::new(),
+        bindings: Vec::new(),
+        captured: captured.captured,
+        reachable: true,
+        report: true,
+        errors: Vec::new(),
+    };
+    for statement in statements {
+        analysis.statement(statement);
+    }
+    analysis.errors
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Formula {
+    Const(bool),
+    Constraint(Constraint),
+    Not(Box<Formula>),
+    And(Box<Formula>, Box<Formula>),
+    Or(Box<Formula>, Box<Formula>),
+    Unknown,
+}
+impl Formula {
+    fn eq(left: &Linear, right: &Linear) -> Self {
+        match (Constraint::ge(left, right), Constraint::ge(right, left)) {
+            (Some(ge), Some(le)) => Self::And(
+                Box::new(Self::Constraint(ge)),
+                Box::new(Self::Constraint(le)),
+            ),
+            _ => Self::Unknown,
+        }
+    }
+    fn constraint(constraint: Option<Constraint>) -> Self {
+        constraint.map_or(Self::Unknown, Self::Constraint)
+    }
+    /// Clauses implied by the formula holding, or not holding when not
+    /// `positive`. Parts too large to convert are left out.
+    fn clauses(&self, positive: bool) -> Vec<Clause> {
+        match (self, positive) {
+            (Self::Const(value), _) if *value == positive => Vec::new(),
+            (Self::Const(_), _) => vec![Vec::new()],
+            (Self::Constraint(constraint), true) => vec![vec![constraint.clone()]],
+            (Self::Constraint(constraint), false) => match constraint.negate() {
+                Some(negated) => vec![vec![negated]],
+                None => Vec::new(),
+            },
+            (Self::Not(formula), positive) => formula.clauses(!positive),
+            (Self::And(left, right), true) | (Self::Or(left, right), false) => {
+                let mut clauses = left.clauses(positive);
+                clauses.extend(right.clauses(positive));
+                clauses
+            }
+            (Self::And(left, right), false) | (Self::Or(left, right), true) => {
+                let left = left.clauses(positive);
+                let right = right.clauses(positive);
+                if left.len() * right.len() > MAX_CLAUSES {
+                    return Vec::new();
+                }
+                left.iter()
+                    .flat_map(|left| {
+                        right
+                            .iter()
+                            .map(|right| left.iter().chain(right).cloned().collect())
+                    })
+                    .collect()
+            }
+            (Self::Unknown, _) => Vec::new(),
+        }
+    }
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Value {
+    Num(Linear),
+    Bool(Formula),
+    /// An array with its length
+    Array(Linear),
+    Record(HashMap<Atom, Value>),
+    Opaque(usize),
+}
+#[derive(Default)]
+struct Opaque {
+    name: Option<String>,
+    num: Option<Sym>,
+    len: Option<Sym>,
+    fields: HashMap<Atom, usize>,
+}
+struct SymInfo {
+    name: Option<String>,
+    integral: bool,
+}
+struct Binding {
+    name: Atom,
+    value: Value,
+    mutable: bool,
+    /// The variable may be changed without an assignment to it, through a
+    /// reference or within a function, so its value is never known
+    escaped: bool,
+}
+/// Where a branch ends
+struct Branch {
+    values: Vec<Value>,
+    facts: Vec<Clause>,
+    reachable: bool,
+    value: Value,
+}
+/// What a branch starts from
+struct Start {
+    values: Vec<Value>,
+    facts: usize,
+    reachable: bool,
+}
+struct Analysis<'a> {
+    syms: Vec<SymInfo>,
+    opaques: Vec<Opaque>,
+    /// Facts that always hold, like lengths never being negative
+    global: Vec<Clause>,
+    /// Facts that hold on the current path
+    facts: Vec<Clause>,
+    resolution: &'a Resolution,
+    /// The latest declaration of each binding
+    declared: HashMap<BindingId, usize>,
+    bindings: Vec<Binding>,
+    /// Variables assigned within a function they're not declared in
+    captured: HashSet<BindingId>,
+    reachable: bool,
+    /// Errors aren't reported while a condition is evaluated again
+    report: bool,
+    errors: Vec<RefinementError>,
+}
+impl Analysis<'_> {
+    fn fresh_sym(&mut self, name: Option<String>, integral: bool) -> Sym {
+        let sym = self.syms.len() as Sym;
+        self.syms.push(SymInfo { name, integral });
+        sym
+    }
+    fn fresh_len(&mut self, name: Option<String>) -> Linear {
+        let len = Linear::sym(self.fresh_sym(name, true));
+        if let Some(constraint) = Constraint::ge(&len, &Linear::constant(Ratio::ZERO)) {
+            self.global.push(vec![constraint]);
+        }
+        len
+    }
+    fn fresh_num(&mut self, integral: bool) -> Value {
+        Value::Num(Linear::sym(self.fresh_sym(None, integral)))
+    }
+    fn opaque(&mut self, name: Option<String>) -> Value {
+        self.opaques.push(Opaque {
+            name,
+            ..Opaque::default()
+        });
+        Value::Opaque(self.opaques.len() - 1)
+    }
+    fn is_integral(&self, linear: &Linear) -> bool {
+        linear.is_integral(|sym| self.syms[sym as usize].integral)
+    }
+    fn num(&mut self, value: Value) -> Linear {
+        match value {
+            Value::Num(linear) => linear,
+            Value::Opaque(id) => {
+                let sym = match self.opaques[id].num {
+                    Some(sym) => sym,
+                    None => {
+                        let sym = self.fresh_sym(self.opaques[id].name.clone(), false);
+                        self.opaques[id].num = Some(sym);
+                        sym
+                    }
+                };
+                Linear::sym(sym)
+            }
+            _ => Linear::sym(self.fresh_sym(None, false)),
+        }
+    }
+    fn len(&mut self, value: Value) -> Linear {
+        match value {
+            Value::Array(len) => len,
+            Value::Opaque(id) => match self.opaques[id].len {
+                Some(sym) => Linear::sym(sym),
+                None => {
+                    let name = self.opaques[id]
+                        .name
+                        .as_ref()
+                        .map(|name| format!("{name}.len"));
+                    let len = self.fresh_len(name);
+                    self.opaques[id].len = len.syms().next();
+                    len
+                }
+            },
+            _ => self.fresh_len(None),
+        }
+    }
+    fn field(&mut self, value: Value, name: &Atom) -> Value {
+        match value {
+            Value::Record(mut fields) => match fields.remove(name) {
+                Some(value) => value,
+                None => self.opaque(None),
+            },
+            Value::Opaque(id) => match self.opaques[id].fields.get(name) {
+                Some(field) => Value::Opaque(*field),
+                None => {
+                    let field_name = self.opaques[id]
+                        .name
+                        .as_ref()
+                        .map(|base| format!("{base}.{name}"));
+                    let Value::Opaque(field) = self.opaque(field_name) else {
+                        unreachable!()
+                    };
+                    self.opaques[id].fields.insert(name.clone(), field);
+                    Value::Opaque(field)
+                }
+            },
+            _ => self.opaque(None),
+        }
+    }
+    fn formula(&self, value: Value) -> Formula {
+        match value {
+            Value::Bool(formula) => formula,
+            _ => Formula::Unknown,
+        }
+    }
+    /// Names an unnamed value after the variable it's bound to
+    fn name(&mut self, value: &Value, name: &str) {
+        match value {
+            Value::Opaque(id) => {
+                let opaque = &mut self.opaques[*id];
+                if opaque.name.is_none() {
+                    opaque.name = Some(name.to_string());
+                }
+            }
+            Value::Num(linear) | Value::Array(linear) => {
+                let mut syms = linear.syms();
+                if let (Some(sym), None) = (syms.next(), syms.next()) {
+                    if *linear == Linear::sym(sym) {
+                        let info = &mut self.syms[sym as usize];
+                        if info.name.is_none() {
+                            info.name = Some(match value {
+                                Value::Array(_) => format!("{name}.len"),
+                                _ => name.to_string(),
+                            });
+                        }
+                    }
+                }
+            }
+            Value::Bool(_) | Value::Record(_) => (),
+        }
+    }
+    fn assume(&mut self, formula: &Formula, positive: bool) {
+        self.facts.extend(formula.clauses(positive));
+    }
+    /// Reports an error if the violation may hold on the current path
+    fn check(&mut self, kind: RefinementErrorKind, violation: Option<Constraint>, span: Span) {
+        if !self.reachable || !self.report {
+            return;
+        }
+        let mut syms: HashSet<Sym> = violation
+            .iter()
+            .flat_map(|constraint| constraint.linear.syms())
+            .collect();
+        // only the facts related to the violation matter
+        let mut remaining: Vec<&Clause> = self.global.iter().chain(&self.facts).collect();
+        let mut clauses: Vec<Clause> = violation
+            .into_iter()
+            .map(|violation| vec![violation])
+            .collect();
+        loop {
+            let (related, unrelated) = remaining.into_iter().partition(|clause: &&Clause| {
+                let mut clause_syms = clause
+                    .iter()
+                    .flat_map(|constraint| constraint.linear.syms())
+                    .peekable();
+                clause_syms.peek().is_none() || clause_syms.any(|sym| syms.contains(&sym))
+            });
+            remaining = unrelated;
+            let related: Vec<&Clause> = related;
+            if related.is_empty() {
+                break;
+            }
+            for clause in related {
+                syms.extend(
+                    clause
+                        .iter()
+                        .flat_map(|constraint| constraint.linear.syms()),
+                );
+                clauses.push(clause.clone());
+            }
+        }
+        let integral = |sym: Sym| self.syms[sym as usize].integral;
+        let counterexample = match solve(&clauses, &integral) {
+            Outcome::Unsat => return,
+            Outcome::Sat(model) => {
+                let mut counterexample: Vec<_> = syms
+                    .into_iter()
+                    .filter_map(|sym| {
+                        let name = self.syms[sym as usize].name.clone()?;
+                        let value = model.get(&sym)?;
+                        Some((name, value.to_string()))
+                    })
+                    .collect();
+                counterexample.sort();
+                counterexample
+            }
+            Outcome::Unknown => Vec::new(),
+        };
+        self.errors.push(RefinementError {
+            kind,
+            counterexample,
+            span,
+        });
+    }
+    fn check_divisor(&mut self, divisor: &Linear, span: Span) {
+        let zero = Linear::constant(Ratio::ZERO);
+        let violation = Formula::eq(divisor, &zero).clauses(true);
+        let [ge, le] = &violation[..] else {
+            return self.check(RefinementErrorKind::DivisionByZero, None, span);
+        };
+        // the divisor is zero when both hold, the second one is assumed
+        // while the first is checked
+        let facts = self.facts.len();
+        self.facts.push(le.clone());
+        self.check(
+            RefinementErrorKind::DivisionByZero,
+            ge.first().cloned(),
+            span,
+        );
+        self.facts.truncate(facts);
+    }
+    /// Checks that `0 <= index <= len`, or `< len` when `exclusive`
+    fn check_index(&mut self, index: &Linear, len: &Linear, exclusive: bool, span: Span) {
+        let errors = self.errors.len();
+        let zero = Linear::constant(Ratio::ZERO);
+        self.check(
+            RefinementErrorKind::IndexOutOfBounds,
+            Constraint::gt(&zero, index),
+            span,
+        );
+        if self.errors.len() > errors {
+            return;
+        }
+        let past = if exclusive {
+            Constraint::ge(index, len)
+        } else {
+            Constraint::gt(index, len)
+        };
+        self.check(RefinementErrorKind::IndexOutOfBounds, past, span);
+    }
+    /// The variable used at `span`
+    fn lookup(&self, span: Span) -> Option<usize> {
+        let binding = self.resolution.resolve_use(span)?;
+        self.declared.get(&binding).copied()
+    }
+    fn declare(&mut self, var: &Var, value: Value) {
+        self.name(&value, &var.ident);
+        let id = self.bindings.len();
+        let binding = self.resolution.binding_of(var.span);
+        self.bindings.push(Binding {
+            name: var.ident.clone(),
+            value,
+            mutable: var.mutable,
+            escaped: var.mutable && binding.is_some_and(|binding| self.captured.contains(&binding)),
+        });
+        if let Some(binding) = binding {
+            self.declared.insert(binding, id);
+        }
+    }
+    fn bind<T: PrettyPrintType>(&mut self, pattern: &Pattern<T>, value: Value) {
+        match &pattern.pattern {
+            PatternKind::Var(var) => {
+                let value = if var.bind_to_ref {
+                    self.opaque(None)
+                } else {
+                    value
+                };
+                self.declare(var, value);
+            }
+            PatternKind::Record(record) => {
+                for (name, field) in &record.fields {
+                    let value = self.field(value.clone(), name);
+                    self.bind(field, value);
+                }
+                if let Some(rest) = &record.rest {
+                    let value = self.opaque(None);
+                    self.bind(rest, value);
+                }
+            }
+            PatternKind::Annotated(annotated) => self.bind(&annotated.pattern, value),
+            _ => {
+                let mut vars = Vars(Vec::new());
+                vars.visit_pattern(pattern);
+                for var in vars.0 {
+                    let value = self.opaque(None);
+                    self.declare(&var, value);
+                }
+            }
+        }
+    }
+    /// The condition for the value to match the pattern, `None` when it
+    /// always matches or isn't known
+    fn pattern_formula<T: PrettyPrintType>(
+        &mut self,
+        pattern: &Pattern<T>,
+        value: &Value,
+    ) -> Option<Formula> {
+        let literal = match &pattern.pattern {
+            PatternKind::UInt(uint) => Ratio::int(*uint as i128),
+            PatternKind::Int(int) => Ratio::int(*int as i128),
+            PatternKind::True => return Some(self.formula(value.clone())),
+            PatternKind::False => return Some(Formula::Not(Box::new(self.formula(value.clone())))),
+            PatternKind::Annotated(annotated) => {
+                return self.pattern_formula(&annotated.pattern, value)
+            }
+            // only the length is known, so the formula is exact only when
+            // nothing else can fail to match
+            PatternKind::Array(ListPattern::List(list))
+                if list.iter().all(|pattern| !pattern.is_refutable()) =>
+            {
+                let len = self.len(value.clone());
+                let count = Linear::constant(Ratio::int(list.len() as i128));
+                return Some(Formula::eq(&len, &count));
+            }
+            PatternKind::Array(ListPattern::ListWithRest(list))
+                if list
+                    .left
+                    .iter()
+                    .chain(list.right.iter())
+                    .chain(once(&*list.rest))
+                    .all(|pattern| !pattern.is_refutable()) =>
+            {
+                let len = self.len(value.clone());
+                let count = list.left.len() + list.right.len();
+                let count = Linear::constant(Ratio::int(count as i128));
+                return Some(Formula::constraint(Constraint::ge(&len, &count)));
+            }
+            _ => return None,
+        };
+        let num = self.num(value.clone());
+        Some(Formula::eq(&num, &Linear::constant(literal)))
+    }
+    /// Forgets the values of mutable variables used at these spans
+    fn havoc(&mut self, spans: &HashSet<Span>) {
+        for span in spans {
+            if let Some(id) = self.lookup(*span) {
+                if self.bindings[id].mutable {
+                    let name = self.bindings[id].name.to_string();
+                    self.bindings[id].value = self.opaque(Some(name));
+                }
+            }
+        }
+    }
+    fn start(&self) -> Start {
+        Start {
+            values: self
+                .bindings
+                .iter()
+                .map(|binding| binding.value.clone())
+                .collect(),
+            facts: self.facts.len(),
+            reachable: self.reachable,
+        }
+    }
+    fn restore(&mut self, start: &Start) {
+        self.bindings.truncate(start.values.len());
+        self.declared.retain(|_, id| *id < start.values.len());
+        for (binding, value) in self.bindings.iter_mut().zip(&start.values) {
+            binding.value = value.clone();
+        }
+        self.facts.truncate(start.facts);
+        self.reachable = start.reachable;
+    }
+    fn end(&mut self, start: &Start, value: Value) -> Branch {
+        let branch = Branch {
+            values: self.bindings[..start.values.len()]
+                .iter()
+                .map(|binding| binding.value.clone())
+                .collect(),
+            facts: self.facts.clone(),
+            reachable: self.reachable,
+            value,
+        };
+        self.restore(start);
+        branch
+    }
+    /// Continues from where the reachable branches end, values that differ
+    /// are forgotten
+    fn merge(&mut self, start: &Start, branches: Vec<Branch>) -> Value {
+        let mut branches: Vec<_> = branches
+            .into_iter()
+            .filter(|branch| branch.reachable)
+            .collect();
+        if branches.is_empty() {
+            self.reachable = false;
+            return self.opaque(None);
+        }
+        if branches.len() == 1 {
+            let branch = branches.pop().unwrap();
+            for (binding, value) in self.bindings.iter_mut().zip(branch.values) {
+                binding.value = value;
+            }
+            self.facts = branch.facts;
+            return branch.value;
+        }
+        for id in 0..start.values.len() {
+            let value = &branches[0].values[id];
+            if branches.iter().any(|branch| branch.values[id] != *value) {
+                let name = self.bindings[id].name.to_string();
+                self.bindings[id].value = self.opaque(Some(name));
+            } else {
+                self.bindings[id].value = value.clone();
+            }
+        }
+        let value = &branches[0].value;
+        if branches.iter().all(|branch| branch.value == *value) {
+            value.clone()
+        } else {
+            self.opaque(None)
+        }
+    }
+    fn statement<T: PrettyPrintType>(&mut self, statement: &Statement<T>) {
+        match statement {
+            Statement::Declare(declare) => {
+                let value = self.expr(&declare.expr);
+                if let ExprKind::Place(place) = &declare.expr.expr {
+                    self.escape_if_ref(place, &declare.pattern);
+                }
+                if let Some(else_part) = &declare.else_part {
+                    let start = self.start();
+                    self.block(else_part);
+                    self.restore(&start);
+                }
+                self.bind(&declare.pattern, value);
+            }
+            Statement::FunDeclare(fun_declare) => {
+                let var = Var {
+                    ident: fun_declare.ident.clone(),
+                    mutable: false,
+                    bind_to_ref: false,
+                    span: fun_declare.span,
+                };
+                let value = self.opaque(None);
+                self.declare(&var, value);
+                self.fun(&fun_declare.fun);
+            }
+            Statement::NewtypeDeclare(newtype) => {
+                let var = Var {
+                    ident: newtype.ident.clone(),
+                    mutable: false,
+                    bind_to_ref: false,
+                    span: newtype.span,
+                };
+                let value = self.opaque(None);
+                self.declare(&var, value);
+            }
+            Statement::AliasDeclare(_) | Statement::TraitDeclare(_) => (),
+            Statement::ImplDeclare(impl_declare) => {
+                for method in impl_declare.methods.iter() {
+                    self.fun(&method.fun);
+                }
+            }
+            Statement::ModuleDeclare(module_declare) => match &module_declare.module {
+                Module::File(_) => (),
+                Module::Inline(statements) => {
+                    for statement in statements.iter() {
+                        self.statement(statement);
+                    }
+                }
+            },
+            Statement::Expr(expr) => {
+                self.expr(expr);
+            }
+        }
+    }
+    fn block<T: PrettyPrintType>(&mut self, block: &Block<T>) -> Value {
+        for statement in block.statement.iter() {
+            self.statement(statement);
+        }
+        match &block.expr {
+            Some(expr) => self.expr(expr),
+            None => self.opaque(None),
+        }
+    }
+    /// The body is checked once with mutable variables outside of it
+    /// unknown as it may be called anywhere
+    fn fun<T: PrettyPrintType>(&mut self, fun: &Fun<T>) {
+        let start = self.start();
+        let escaped: Vec<_> = self
+            .bindings
+            .iter_mut()
+            .map(|binding| {
+                let escaped = binding.escaped;
+                binding.escaped |= binding.mutable;
+                escaped
+            })
+            .collect();
+        self.reachable = true;
+        match &fun.param.pattern {
+            PatternKind::Param(params) => {
+                for param in params.iter() {
+                    let value = self.opaque(None);
+                    self.declare(&param.var, value);
+                }
+            }
+            _ => {
+                let value = self.opaque(None);
+                self.bind(&fun.param, value);
+            }
+        }
+        self.expr(&fun.body);
+        self.restore(&start);
+        for (binding, escaped) in self.bindings.iter_mut().zip(escaped) {
+            binding.escaped = escaped;
+        }
+    }
+    /// Mutable variables referred to can change without being assigned
+    fn escape<T: PrettyPrintType>(&mut self, place: &PlaceExpr<T>) {
+        if let Some(id) = place.var_span().and_then(|span| self.lookup(span)) {
+            let binding = &mut self.bindings[id];
+            binding.escaped |= binding.mutable;
+        }
+    }
+    fn escape_if_ref<T: PrettyPrintType>(&mut self, place: &PlaceExpr<T>, pattern: &Pattern<T>) {
+        let mut vars = Vars(Vec::new());
+        vars.visit_pattern(pattern);
+        if vars.0.iter().any(|var| var.bind_to_ref) {
+            self.escape(place);
+        }
+    }
+    fn expr<T: PrettyPrintType>(&mut self, expr: &Expr<T>) -> Value {
+        match &expr.expr {
+            ExprKind::Literal(literal) => match literal {
+                Literal::True => Value::Bool(Formula::Const(true)),
+                Literal::False => Value::Bool(Formula::Const(false)),
+                Literal::UInt(uint) => Value::Num(Linear::constant(Ratio::int(*uint as i128))),
+                Literal::Float(float) => match Ratio::from_f64(*float) {
+                    Some(ratio) => Value::Num(Linear::constant(ratio)),
+                    None => self.fresh_num(false),
+                },
+                Literal::Char(_) => self.opaque(None),
+            },
+            ExprKind::Str(_) => self.opaque(None),
+            ExprKind::Tag(tag) => {
+                if let Some(expr) = &tag.expr {
+                    self.expr(expr);
+                }
+                self.opaque(None)
+            }
+            ExprKind::Assign(assign) => {
+                // every value is evaluated before any of the places is
+                // assigned
+                let values: Vec<_> = assign
+                    .iter()
+                    .map(|assign| self.expr(&assign.expr))
+                    .collect();
+                for (assign, value) in assign.iter().zip(values) {
+                    self.assign(assign, value);
+                }
+                self.opaque(None)
+            }
+            ExprKind::Array(elements) => {
+                let mut len = Linear::constant(Ratio::ZERO);
+                for element in elements.iter() {
+                    let value = self.expr(&element.expr);
+                    let element_len = match element.kind {
+                        ElementKind::Element => Linear::constant(Ratio::ONE),
+                        ElementKind::Splat => self.len(value),
+                    };
+                    len = match len.add(&element_len) {
+                        Some(len) => len,
+                        None => self.fresh_len(None),
+                    };
+                }
+                Value::Array(len)
+            }
+            ExprKind::ArrayRange(range) => {
+                let (lower, upper) = self.range(range);
+                Value::Array(self.range_len(lower, upper))
+            }
+            ExprKind::Unit => self.opaque(None),
+            ExprKind::Splat(expr) => self.expr(expr),
+            ExprKind::Record(record) => {
+                let mut fields = HashMap::new();
+                let splat = self.collection(record, |analysis, field| {
+                    let value = analysis.expr(&field.expr);
+                    fields.insert(field.name.clone(), value);
+                });
+                match splat {
+                    Some(_) => self.opaque(None),
+                    None => Value::Record(fields),
+                }
+            }
+            ExprKind::Tuple(tuple) => {
+                self.collection(tuple, |analysis, expr| {
+                    analysis.expr(expr);
+                });
+                self.opaque(None)
+            }
+            ExprKind::Unary(unary) => {
+                let value = self.expr(&unary.expr);
+                // the place is evaluated as it is before it's referenced
+                if let (UnaryType::Ref, ExprKind::Place(place)) = (unary.kind, &unary.expr.expr) {
+                    self.escape(place);
+                }
+                match unary.kind {
+                    UnaryType::Minus => {
+                        let num = self.num(value);
+                        match num.scale(Ratio::int(-1)) {
+                            Some(negated) => Value::Num(negated),
+                            None => self.fresh_num(false),
+                        }
+                    }
+                    UnaryType::Not => Value::Bool(Formula::Not(Box::new(self.formula(value)))),
+                    UnaryType::Ref => self.opaque(None),
+                    UnaryType::Move | UnaryType::Clone => value,
+                }
+            }
+            ExprKind::Binary(binary) => {
+                let left = self.expr(&binary.left);
+                let right = match binary.kind {
+                    // the right side is only evaluated depending on the left
+                    BinaryType::LazyAnd | BinaryType::LazyOr => {
+                        let formula = self.formula(left.clone());
+                        let facts = self.facts.len();
+                        self.assume(&formula, binary.kind == BinaryType::LazyAnd);
+                        let right = self.expr(&binary.right);
+                        self.facts.truncate(facts);
+                        right
+                    }
+                    _ => self.expr(&binary.right),
+                };
+                self.binary(binary.kind, left, right, binary.right.span)
+            }
+            ExprKind::Place(place) => self.place(place),
+            ExprKind::Call(call) => {
+                self.expr(&call.expr);
+                match &call.arg {
+                    Arg::Unit => (),
+                    Arg::Splat(expr) => {
+                        self.expr(expr);
+                    }
+                    Arg::Record(record) => {
+                        self.collection(record, |analysis, field| {
+                            analysis.expr(&field.expr);
+                        });
+                    }
+                    Arg::Tuple(tuple) => {
+                        self.collection(tuple, |analysis, expr| {
+                            analysis.expr(expr);
+                        });
+                    }
+                }
+                self.opaque(None)
+            }
+            ExprKind::ControlFlow(control_flow) => self.control_flow(control_flow),
+            ExprKind::Fun(fun) => {
+                self.fun(fun);
+                self.opaque(None)
+            }
+            ExprKind::Jump(jump) => {
+                match jump {
+                    Jump::Break(Some(expr)) | Jump::Return(Some(expr)) => {
+                        self.expr(expr);
+                    }
+                    Jump::Break(None) | Jump::Continue | Jump::Return(None) => (),
+                }
+                self.reachable = false;
+                self.opaque(None)
+            }
+            ExprKind::Never => {
+                self.reachable = false;
+                self.opaque(None)
+            }
+            ExprKind::Annotated(annotated) => self.expr(&annotated.expr),
+        }
+    }
+    fn binary(&mut self, kind: BinaryType, left: Value, right: Value, right_span: Span) -> Value {
+        match kind {
+            BinaryType::Add | BinaryType::Sub => {
+                let left = self.num(left);
+                let right = self.num(right);
+                let result = match kind {
+                    BinaryType::Add => left.add(&right),
+                    _ => left.sub(&right),
+                };
+                match result {
+                    Some(result) => Value::Num(result),
+                    None => self.fresh_num(false),
+                }
+            }
+            BinaryType::Multiply => {
+                let left = self.num(left);
+                let right = self.num(right);
+                let result = match (left.as_constant(), right.as_constant()) {
+                    (Some(factor), _) => right.scale(factor),
+                    (_, Some(factor)) => left.scale(factor),
+                    (None, None) => None,
+                };
+                match result {
+                    Some(result) => Value::Num(result),
+                    None => {
+                        let integral = self.is_integral(&left) && self.is_integral(&right);
+                        let product = self.fresh_num(integral);
+                        // a square is never negative
+                        if left == right {
+                            let product = self.num(product.clone());
+                            let zero = Linear::constant(Ratio::ZERO);
+                            if let Some(constraint) = Constraint::ge(&product, &zero) {
+                                self.global.push(vec![constraint]);
+                            }
+                        }
+                        product
+                    }
+                }
+            }
+            BinaryType::Div | BinaryType::FloorDiv | BinaryType::Mod => {
+                let left = self.num(left);
+                let right = self.num(right);
+                self.check_divisor(&right, right_span);
+                let integral = self.is_integral(&left) && self.is_integral(&right);
+                match (kind, right.as_constant()) {
+                    (BinaryType::Div, Some(divisor)) if !divisor.is_zero() => {
+                        match Ratio::ONE
+                            .div(divisor)
+                            .and_then(|inverse| left.scale(inverse))
+                        {
+                            Some(result) => Value::Num(result),
+                            None => self.fresh_num(false),
+                        }
+                    }
+                    (BinaryType::Div, _) => self.fresh_num(false),
+                    (BinaryType::FloorDiv, _) => self.fresh_num(true),
+                    // the result has the sign of the divisor and is smaller
+                    (_, Some(divisor)) if divisor > Ratio::ZERO => {
+                        let result = self.fresh_num(integral);
+                        let Value::Num(linear) = &result else {
+                            unreachable!()
+                        };
+                        let zero = Linear::constant(Ratio::ZERO);
+                        let facts = [
+                            Constraint::ge(linear, &zero),
+                            Constraint::gt(&Linear::constant(divisor), linear),
+                        ];
+                        self.global
+                            .extend(facts.into_iter().flatten().map(|fact| vec![fact]));
+                        result
+                    }
+                    _ => self.fresh_num(integral),
+                }
+            }
+            BinaryType::And | BinaryType::LazyAnd => Value::Bool(Formula::And(
+                Box::new(self.formula(left)),
+                Box::new(self.formula(right)),
+            )),
+            BinaryType::Or | BinaryType::LazyOr => Value::Bool(Formula::Or(
+                Box::new(self.formula(left)),
+                Box::new(self.formula(right)),
+            )),
+            BinaryType::Equal
+            | BinaryType::NotEqual
+            | BinaryType::Greater
+            | BinaryType::GreaterEqual
+            | BinaryType::Less
+            | BinaryType::LessEqual => {
+                let left = self.num(left);
+                let right = self.num(right);
+                let formula = match kind {
+                    BinaryType::Equal => Formula::eq(&left, &right),
+                    BinaryType::NotEqual => Formula::Not(Box::new(Formula::eq(&left, &right))),
+                    BinaryType::Greater => Formula::constraint(Constraint::gt(&left, &right)),
+                    BinaryType::GreaterEqual => Formula::constraint(Constraint::ge(&left, &right)),
+                    BinaryType::Less => Formula::constraint(Constraint::gt(&right, &left)),
+                    _ => Formula::constraint(Constraint::ge(&right, &left)),
+                };
+                Value::Bool(formula)
+            }
+            BinaryType::Concatenate => {
+                let left = self.len(left);
+                let right = self.len(right);
+                match left.add(&right) {
+                    Some(len) => Value::Array(len),
+                    None => Value::Array(self.fresh_len(None)),
+                }
+            }
+        }
+    }
+    fn collection<T, U: PrettyPrintType>(
+        &mut self,
+        collection: &Collection<T, U>,
+        mut each: impl FnMut(&mut Self, &T),
+    ) -> Option<Value> {
+        match collection {
+            Collection::Collection(items) => {
+                for item in items.iter() {
+                    each(self, item);
+                }
+                None
+            }
+            Collection::WithSplat(with_splat) => {
+                for item in with_splat.left.iter() {
+                    each(self, item);
+                }
+                let splat = self.expr(&with_splat.splat);
+                for item in with_splat.right.iter() {
+                    each(self, item);
+                }
+                Some(splat)
+            }
+        }
+    }
+    /// Bounds of a range as the inclusive lower and upper bound, each along
+    /// with whether it's exclusive instead
+    fn range<T: PrettyPrintType>(&mut self, range: &Range<T>) -> (RangeBound, RangeBound) {
+        let lower = self.bound(&range.left);
+        let upper = self.bound(&range.right);
+        (lower, upper)
+    }
+    fn bound<T: PrettyPrintType>(&mut self, bound: &Option<Bound<T>>) -> RangeBound {
+        let bound = bound.as_ref()?;
+        let value = self.expr(&bound.expr);
+        Some((self.num(value), bound.kind == BoundType::Exclusive))
+    }
+    /// The first integer within the lower bound and the first past the upper
+    /// bound, only when these bounds are integers
+    fn integer_range(
+        &self,
+        lower: &RangeBound,
+        upper: &RangeBound,
+    ) -> (Option<Linear>, Option<Linear>) {
+        let one = Linear::constant(Ratio::ONE);
+        let integer = |bound: &RangeBound, past: bool| match bound {
+            Some((linear, exclusive)) if self.is_integral(linear) => {
+                if *exclusive == past {
+                    Some(linear.clone())
+                } else {
+                    linear.add(&one)
+                }
+            }
+            _ => None,
+        };
+        (integer(lower, false), integer(upper, true))
+    }
+    fn range_len(&mut self, lower: RangeBound, upper: RangeBound) -> Linear {
+        let (Some(start), Some(end)) = self.integer_range(&lower, &upper) else {
+            return self.fresh_len(None);
+        };
+        let len = self.fresh_len(None);
+        // the length is the larger of `end - start` and zero
+        if let Some(difference) = end.sub(&start) {
+            let zero = Linear::constant(Ratio::ZERO);
+            if let Some(fact) = Constraint::ge(&len, &difference) {
+                self.global.push(vec![fact]);
+            }
+            let facts = [
+                Constraint::ge(&zero, &len),
+                Constraint::ge(&difference, &len),
+            ];
+            if let [Some(at_zero), Some(at_difference)] = facts {
+                self.global.push(vec![at_zero, at_difference]);
+            }
+        }
+        len
+    }
+    fn place<T: PrettyPrintType>(&mut self, place: &PlaceExpr<T>) -> Value {
+        match place {
+            PlaceExpr::Var(name, span) => match self.lookup(*span) {
+                Some(id) if self.bindings[id].escaped => self.opaque(Some(name.to_string())),
+                Some(id) => self.bindings[id].value.clone(),
+                None => self.opaque(Some(name.to_string())),
+            },
+            PlaceExpr::FieldAccess(field_access) => {
+                let value = self.expr(&field_access.expr);
+                self.field(value, &field_access.name)
+            }
+            PlaceExpr::Index(index) => {
+                let array = self.expr(&index.expr);
+                let value = self.expr(&index.index);
+                let len = self.len(array);
+                let index_num = self.num(value);
+                self.check_index(&index_num, &len, true, index.index.span);
+                self.opaque(None)
+            }
+            PlaceExpr::Slice(slice) => {
+                let array = self.expr(&slice.expr);
+                let len = self.len(array);
+                let (lower, upper) = self.range(&slice.range);
+                let (start, end) = self.integer_range(&lower, &upper);
+                let bounds = [
+                    (&slice.range.left, start.or(lower.map(|(lower, _)| lower))),
+                    (&slice.range.right, end.or(upper.map(|(upper, _)| upper))),
+                ];
+                for (bound, index) in bounds {
+                    if let (Some(bound), Some(index)) = (bound, index) {
+                        self.check_index(&index, &len, false, bound.expr.span);
+                    }
+                }
+                Value::Array(self.fresh_len(None))
+            }
+            PlaceExpr::Deref(expr) => {
+                self.expr(expr);
+                self.opaque(None)
+            }
+            PlaceExpr::Len(expr) => {
+                let value = self.expr(expr);
+                Value::Num(self.len(value))
+            }
+        }
+    }
+    fn assign<T: PrettyPrintType>(&mut self, assign: &Assign<T>, value: Value) {
+        /// Names of fields from the variable to the place, `None` if the
+        /// place isn't only field accesses
+        fn fields<T: PrettyPrintType>(place: &PlaceExpr<T>, path: &mut Vec<Atom>) -> Option<Span> {
+            match place {
+                PlaceExpr::Var(_, span) => Some(*span),
+                PlaceExpr::FieldAccess(field_access) => match &field_access.expr.expr {
+                    ExprKind::Place(place) => {
+                        let var = fields(place, path)?;
+                        path.push(field_access.name.clone());
+                        Some(var)
+                    }
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        let mut path = Vec::new();
+        let Some(id) = fields(&assign.place, &mut path).and_then(|span| self.lookup(span)) else {
+            // elements and places behind references aren't tracked, only
+            // the indices are checked
+            self.place(&assign.place);
+            return;
+        };
+        let mut target = &mut self.bindings[id].value;
+        for name in &path {
+            match target {
+                Value::Record(fields) if fields.contains_key(name) => {
+                    target = fields.get_mut(name).unwrap();
+                }
+                _ => {
+                    let name = self.bindings[id].name.to_string();
+                    self.bindings[id].value = self.opaque(Some(name));
+                    return;
+                }
+            }
+        }
+        *target = value;
+    }
+    fn control_flow<T: PrettyPrintType>(&mut self, control_flow: &ControlFlow<T>) -> Value {
+        match control_flow {
+            ControlFlow::Block(block) => self.block(block),
+            ControlFlow::If(if_expr) => {
+                let condition = self.expr(&if_expr.condition);
+                let condition = self.formula(condition);
+                let start = self.start();
+                self.assume(&condition, true);
+                let value = self.block(&if_expr.body);
+                let body = self.end(&start, value);
+                self.assume(&condition, false);
+                let value = match &if_expr.else_part {
+                    Some(else_part) => self.control_flow(else_part),
+                    None => self.opaque(None),
+                };
+                let else_part = self.end(&start, value);
+                self.merge(&start, vec![body, else_part])
+            }
+            ControlFlow::Match(match_expr) => {
+                let value = self.expr(&match_expr.expr);
+                let start = self.start();
+                let mut previous = Vec::new();
+                let mut branches = Vec::new();
+                for arm in match_expr.arm.iter() {
+                    for formula in &previous {
+                        self.assume(formula, false);
+                    }
+                    let formula = self.pattern_formula(&arm.pattern, &value);
+                    if let Some(formula) = &formula {
+                        self.assume(formula, true);
+                    }
+                    self.bind(&arm.pattern, value.clone());
+                    let arm_value = self.expr(&arm.expr);
+                    branches.push(self.end(&start, arm_value));
+                    previous.extend(formula);
+                }
+                self.merge(&start, branches)
+            }
+            ControlFlow::For(for_expr) => {
+                let element = match &for_expr.expr.expr {
+                    ExprKind::ArrayRange(range) => {
+                        let (lower, upper) = self.range(range);
+                        let integral = self.integer_range(&lower, &None).0.is_some();
+                        let element = Linear::sym(self.fresh_sym(None, integral));
+                        let facts = [
+                            lower.map(|(lower, exclusive)| (element.clone(), lower, exclusive)),
+                            upper.map(|(upper, exclusive)| (upper, element.clone(), exclusive)),
+                        ];
+                        let facts: Vec<_> = facts
+                            .into_iter()
+                            .flatten()
+                            .map(|(greater, less, exclusive)| {
+                                Formula::constraint(if exclusive {
+                                    Constraint::gt(&greater, &less)
+                                } else {
+                                    Constraint::ge(&greater, &less)
+                                })
+                            })
+                            .collect();
+                        Some((Value::Num(element), facts))
+                    }
+                    _ => {
+                        self.expr(&for_expr.expr);
+                        None
+                    }
+                };
+                self.looping(&for_expr.body, None, |analysis| {
+                    let element = match &element {
+                        Some((element, facts)) => {
+                            for fact in facts {
+                                analysis.assume(fact, true);
+                            }
+                            element.clone()
+                        }
+                        None => analysis.opaque(None),
+                    };
+                    analysis.bind(&for_expr.pattern, element);
+                    analysis.block(&for_expr.body);
+                });
+                self.opaque(None)
+            }
+            ControlFlow::While(while_expr) => {
+                let condition = Some(&*while_expr.condition);
+                self.looping(&while_expr.body, condition, |analysis| {
+                    let condition = analysis.expr(&while_expr.condition);
+                    let condition = analysis.formula(condition);
+                    analysis.assume(&condition, true);
+                    analysis.block(&while_expr.body);
+                });
+                self.opaque(None)
+            }
+            ControlFlow::Loop(block) => {
+                self.looping(block, None, |analysis| {
+                    analysis.block(block);
+                });
+                if !jumps(block).breaks {
+                    self.reachable = false;
+                }
+                self.opaque(None)
+            }
+        }
+    }
+    /// Runs an iteration of a loop with the variables assigned within it
+    /// unknown except for the bounds kept by every iteration. After the loop
+    /// these are unknown again, and the loop condition is false if it's the
+    /// only way out.
+    fn looping<T: PrettyPrintType>(
+        &mut self,
+        body: &Block<T>,
+        condition: Option<&Expr<T>>,
+        mut iteration: impl FnMut(&mut Self),
+    ) {
+        let mut assigned = Assigned(HashSet::new());
+        assigned.visit_block(body);
+        if let Some(condition) = condition {
+            assigned.visit_expr(condition);
+        }
+        let jumps = jumps(body);
+        // the initial values are candidates of bounds, only those still
+        // holding after an iteration are kept
+        let mut bounds = Vec::new();
+        if !jumps.continues {
+            for span in &assigned.0 {
+                let Some(id) = self.lookup(*span) else {
+                    continue;
+                };
+                let binding = &self.bindings[id];
+                if let (true, false, Value::Num(initial)) =
+                    (binding.mutable, binding.escaped, &binding.value)
+                {
+                    bounds.push((id, initial.clone(), false));
+                    bounds.push((id, initial.clone(), true));
+                }
+            }
+        }
+        self.havoc(&assigned.0);
+        loop {
+            let start = self.start();
+            self.assume_bounds(&bounds);
+            let report = replace(&mut self.report, false);
+            iteration(self);
+            self.report = report;
+            let kept: Vec<_> = if self.reachable {
+                bounds
+                    .iter()
+                    .filter(|bound| {
+                        let constraint = self.bound_constraint(bound);
+                        constraint.is_some_and(|constraint| self.implies(constraint))
+                    })
+                    .cloned()
+                    .collect()
+            } else {
+                bounds.clone()
+            };
+            self.restore(&start);
+            if kept.len() == bounds.len() {
+                break;
+            }
+            bounds = kept;
+        }
+        let start = self.start();
+        self.assume_bounds(&bounds);
+        iteration(self);
+        self.restore(&start);
+        self.havoc(&assigned.0);
+        self.assume_bounds(&bounds);
+        if let (Some(condition), false) = (condition, jumps.breaks) {
+            let report = replace(&mut self.report, false);
+            let condition = self.expr(condition);
+            let condition = self.formula(condition);
+            self.assume(&condition, false);
+            self.report = report;
+        }
+    }
+    /// The variable being at least, or at most when `upper`, the value
+    fn bound_constraint(
+        &mut self,
+        (id, value, upper): &(usize, Linear, bool),
+    ) -> Option<Constraint> {
+        let var = self.num(self.bindings[*id].value.clone());
+        if *upper {
+            Constraint::ge(value, &var)
+        } else {
+            Constraint::ge(&var, value)
+        }
+    }
+    fn assume_bounds(&mut self, bounds: &[(usize, Linear, bool)]) {
+        for bound in bounds {
+            if let Some(constraint) = self.bound_constraint(bound) {
+                self.facts.push(vec![constraint]);
+            }
+        }
+    }
+    /// Whether the constraint holds on the current path
+    fn implies(&self, constraint: Constraint) -> bool {
+        let Some(negated) = constraint.negate() else {
+            return false;
+        };
+        let mut clauses: Vec<_> = self.global.iter().chain(&self.facts).cloned().collect();
+        clauses.push(vec![negated]);
+        let integral = |sym: Sym| self.syms[sym as usize].integral;
+        solve(&clauses, &integral) == Outcome::Unsat
+    }
+}
+fn jumps<T: PrettyPrintType>(block: &Block<T>) -> Jumps {
+    let mut jumps = Jumps {
+        breaks: false,
+        continues: false,
+    };
+    jumps.visit_block(block);
+    jumps
+}
+struct Vars(Vec<Var>);
+
+impl<T: PrettyPrintType> Visit<T> for Vars {
+    fn visit_var(&mut self, var: &Var) {
+        self.0.push(var.clone());
+    }
+}
+/// Spans of the variables assigned
+struct Assigned(HashSet<Span>);
+
+impl<T: PrettyPrintType> Visit<T> for Assigned {
+    fn visit_assign(&mut self, assign: &Assign<T>) {
+        self.0.extend(assign.place.var_span());
+        walk_assign(self, assign);
+    }
+}
+/// Whether there are `break` or `continue` within a loop, including those of
+/// the loops within
+struct Jumps {
+    breaks: bool,
+    continues: bool,
+}
+impl<T: PrettyPrintType> Visit<T> for Jumps {
+    fn visit_jump(&mut self, jump: &Jump<T>) {
+        match jump {
+            Jump::Break(_) => self.breaks = true,
+            Jump::Continue => self.continues = true,
+            Jump::Return(_) => (),
+        }
+        walk_jump(self, jump);
+    }
+}
+/// Finds variables assigned within functions they're not declared in
+struct Captured<'a> {
+    resolution: &'a Resolution,
+    declared: Vec<HashSet<BindingId>>,
+    captured: HashSet<BindingId>,
+}
+impl<T: PrettyPrintType> Visit<T> for Captured<'_> {
+    fn visit_fun(&mut self, fun: &Fun<T>) {
+        self.declared.push(HashSet::new());
+        walk_fun(self, fun);
+        self.declared.pop();
+    }
+    fn visit_var(&mut self, var: &Var) {
+        if let (Some(declared), Some(binding)) = (
+            self.declared.last_mut(),
+            self.resolution.binding_of(var.span),
+        ) {
+            declared.insert(binding);
+        }
+    }
+    fn visit_assign(&mut self, assign: &Assign<T>) {
+        let binding = assign
+            .place
+            .var_span()
+            .and_then(|span| self.resolution.resolve_use(span));
+        if let (Some(declared), Some(binding)) = (self.declared.last(), binding) {
+            if !declared.contains(&binding) {
+                self.captured.insert(binding);
+            }
+        }
+        walk_assign(self, assign);
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::{check_refinements, RefinementErrorKind};
+    use hir::resolve::resolve;
+    use parser::{ast, EasyParser};
+
+    /// Errors as the kind, the counterexample, and the code of the divisor
+    /// or index
+    fn check(src: &str) -> Vec<(RefinementErrorKind, String, &str)> {
+        let (statements, rest) = ast().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        let resolution = resolve(&statements).unwrap();
+        check_refinements(&statements, &resolution)
+            .into_iter()
+            .map(|err| {
+                let counterexample = err
+                    .counterexample
+                    .iter()
+                    .map(|(name, value)| format!("{name} = {value}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                (err.kind, counterexample, &src[err.span.range()])
+            })
+            .collect()
+    }
+    use RefinementErrorKind::{DivisionByZero, IndexOutOfBounds};
+
+    #[test]
+    fn division() {
+        assert_eq!(
+            check("div(a, b) => a / b;"),
+            [(DivisionByZero, "b = 0".to_string(), "b")],
+        );
+        assert_eq!(check("div(a, b) => if b != 0 { a / b } else { 0 };"), []);
+        assert_eq!(
+            check("div(a, b) => { if b == 0 { return 0; } a // b };"),
+            [],
+        );
+        assert_eq!(
+            check("div(a, b) => if b >= 0 { a % b } else { 0 };"),
+            [(DivisionByZero, "b = 0".to_string(), "b")],
+        );
+        assert_eq!(
+            check("f(a) => { b = a + 1; 10 / (b - a - 1) };"),
+            [(DivisionByZero, String::new(), "b - a - 1")],
+        );
+        assert_eq!(check("f(a) => a > 0 && 10 / a > 1;"), []);
+    }
+    #[test]
+    fn index() {
+        assert_eq!(
+            check("get(xs, i) => xs[i];"),
+            [(IndexOutOfBounds, "i = -1".to_string(), "i")],
+        );
+        assert_eq!(
+            check("get(xs, i) => if i >= 0 { xs[i] } else { 0 };"),
+            [(IndexOutOfBounds, "i = 0, xs.len = 0".to_string(), "i")],
+        );
+        assert_eq!(
+            check("get(xs, i) => if i >= 0 && i < xs.len { xs[i] } else { 0 };"),
+            [],
+        );
+        assert_eq!(check("f() => { xs = [1, 2, 3]; xs[2] };"), []);
+        assert_eq!(
+            check("f() => { xs = [1, 2, 3]; xs[3] };"),
+            [(IndexOutOfBounds, String::new(), "3")],
+        );
+        assert_eq!(check("f(xs) => { ys = [0, *xs]; ys[xs.len] };"), []);
+    }
+    #[test]
+    fn loops() {
+        let src = "
+            sum(xs) => {
+                mut total = 0;
+                for i in [0.<xs.len] {
+                    total <- total + xs[i];
+                }
+                total
+            };
+        ";
+        assert_eq!(check(src), []);
+        let src = "
+            sum(xs) => {
+                mut total = 0;
+                for i in [0..xs.len] {
+                    total <- total + xs[i];
+                }
+                total
+            };
+        ";
+        assert_eq!(
+            check(src),
+            [(IndexOutOfBounds, "i = 0, xs.len = 0".to_string(), "i")],
+        );
+        let src = "
+            f(xs) => {
+                mut i = 0;
+                while i < xs.len {
+                    xs[i];
+                    i <- i + 1;
+                }
+                xs[i]
+            };
+        ";
+        assert_eq!(
+            check(src),
+            [(IndexOutOfBounds, "i = 0, xs.len = 0".to_string(), "i")],
+        );
+        let src = "
+            f(xs) => {
+                mut i = xs.len;
+                while i > 0 {
+                    i <- i - 2;
+                    xs[i];
+                }
+            };
+        ";
+        assert_eq!(
+            check(src),
+            [(IndexOutOfBounds, "i = 1, xs.len = 1".to_string(), "i")],
+        );
+    }
+    #[test]
+    fn loop_local_mutable() {
+        assert_eq!(check("f() => { loop { mut y = 1; y <- 2; }; };"), []);
+        let src = "
+            f(xs) => {
+                mut i = 0;
+                while i < xs.len {
+                    mut t = xs[i];
+                    t <- t + 1;
+                    i <- i + 1;
+                }
+            };
+        ";
+        assert_eq!(check(src), []);
+        let src = "
+            f(xs) => {
+                mut s = 0;
+                for x in xs {
+                    mut t = x;
+                    t <- t + 1;
+                    s <- s + t;
+                }
+                10 / s
+            };
+        ";
+        assert_eq!(check(src), [(DivisionByZero, "s = 0".to_string(), "s")]);
+    }
+    #[test]
+    fn mutable() {
+        assert_eq!(check("f() => { mut x = 1; x <- 2; 10 / x };"), []);
+        assert_eq!(
+            check("f() => { mut x = 1; x <- 0; 10 / x };"),
+            [(DivisionByZero, String::new(), "x")],
+        );
+        // the variable may be changed through the reference
+        assert_eq!(
+            check("f(g) => { mut x = 1; g(&x); 10 / x };"),
+            [(DivisionByZero, "x = 0".to_string(), "x")],
+        );
+        let src = "
+            f() => {
+                mut x = 1;
+                reset() => { x <- 0; };
+                reset();
+                10 / x
+            };
+        ";
+        assert_eq!(check(src), [(DivisionByZero, "x = 0".to_string(), "x")]);
+    }
+    #[test]
+    fn square() {
+        assert_eq!(check("f(a, b) => a / (b * b + 1);"), []);
+        assert_eq!(
+            check("f(a, b) => a / (b * b);"),
+            [(DivisionByZero, String::new(), "b * b")],
+        );
+        // only squares are known
+        assert_eq!(
+            check("f(a, b, c) => a / (b * c + 1);"),
+            [(DivisionByZero, String::new(), "b * c + 1")],
+        );
+    }
+    #[test]
+    fn escaped_array() {
+        assert_eq!(check("f() => { mut a = [1, 2]; a[1] };"), []);
+        // the function may be called after the array is replaced by a
+        // shorter one
+        assert_eq!(
+            check("f() => { mut a = [1, 2]; g() => a[0]; a[1] };"),
+            [(IndexOutOfBounds, "a.len = 0".to_string(), "0")],
+        );
+        // it may be replaced through the reference
+        assert_eq!(
+            check("f(h) => { mut a = [1, 2]; h(&a); a[1] };"),
+            [(IndexOutOfBounds, "a.len = 0".to_string(), "1")],
+        );
+        assert_eq!(check("g() => { mut xs = [1, 2]; r = &xs[0]; r^ };"), []);
+    }
+    #[test]
+    fn match_array() {
+        assert_eq!(check("f(a) => match a { [x, *_] => a[0], _ => 0 };"), []);
+        assert_eq!(
+            check("f(a) => match a { [x, y] => a[1], [] => 0, _ => a[0] };"),
+            [],
+        );
+        assert_eq!(check("f(a) => match a { [_, *_, _] => a[1], _ => 0 };"), [],);
+        // an element may fail to match, so nothing is known for later arms
+        assert_eq!(
+            check("f(a) => match a { [] => 0, [1] => 1, _ => a[1] };"),
+            [(IndexOutOfBounds, "a.len = 1".to_string(), "1")],
+        );
+    }
+    #[test]
+    fn match_literal() {
+        let src = "
+            f(n) => match n {
+                0 => 0,
+                _ => 10 / n,
+            };
+        ";
+        assert_eq!(check(src), []);
+    }
+}
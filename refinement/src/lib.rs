@@ -0,0 +1,8 @@
+#![warn(clippy::all)]
+#![deny(clippy::correctness)]
+#![forbid(unsafe_code)]
+
+mod check;
+mod linear;
+
+pub use crate::check::{check_refinements, RefinementError, RefinementErrorKind};
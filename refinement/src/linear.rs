@@ -0,0 +1,605 @@
+//! Linear arithmetic over rational numbers where some of the unknowns are
+//! integers. Satisfiability is decided by Fourier-Motzkin elimination with
+//! the constraints over integers tightened along the way, and a model is
+//! found by substituting back through the eliminated unknowns.
+//!
+//! The procedure is complete over rational numbers but not over integers,
+//! when it can't find an integer model the outcome is unknown. Every
+//! arithmetic is checked, an overflow also makes the outcome unknown.
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+};
+
+/// Constraints derived before giving up
+const MAX_CONSTRAINTS: usize = 4096;
+/// Combinations of disjunctions tried before giving up
+const MAX_CASES: usize = 1024;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub(super) struct Ratio {
+    num: i128,
+    den: i128,
+}
+impl Ratio {
+    pub const ZERO: Self = Self { num: 0, den: 1 };
+    pub const ONE: Self = Self { num: 1, den: 1 };
+
+    pub fn int(num: i128) -> Self {
+        Self { num, den: 1 }
+    }
+    fn new(num: i128, den: i128) -> Option<Self> {
+        let (num, den) = if den < 0 {
+            (num.checked_neg()?, den.checked_neg()?)
+        } else {
+            (num, den)
+        };
+        let gcd = gcd(num, den);
+        Some(Self {
+            num: num / gcd,
+            den: den / gcd,
+        })
+    }
+    /// The exact value of a finite float
+    pub fn from_f64(float: f64) -> Option<Self> {
+        if !float.is_finite() {
+            return None;
+        }
+        let mut num = float;
+        let mut den: i128 = 1;
+        while num.fract() != 0.0 {
+            num *= 2.0;
+            den = den.checked_mul(2)?;
+        }
+        if num.abs() >= i128::MAX as f64 {
+            return None;
+        }
+        Self::new(num as i128, den)
+    }
+    pub fn add(self, other: Self) -> Option<Self> {
+        let num = self
+            .num
+            .checked_mul(other.den)?
+            .checked_add(other.num.checked_mul(self.den)?)?;
+        Self::new(num, self.den.checked_mul(other.den)?)
+    }
+    pub fn neg(self) -> Option<Self> {
+        Some(Self {
+            num: self.num.checked_neg()?,
+            den: self.den,
+        })
+    }
+    pub fn sub(self, other: Self) -> Option<Self> {
+        self.add(other.neg()?)
+    }
+    pub fn mul(self, other: Self) -> Option<Self> {
+        Self::new(
+            self.num.checked_mul(other.num)?,
+            self.den.checked_mul(other.den)?,
+        )
+    }
+    pub fn div(self, other: Self) -> Option<Self> {
+        if other.num == 0 {
+            return None;
+        }
+        Self::new(
+            self.num.checked_mul(other.den)?,
+            self.den.checked_mul(other.num)?,
+        )
+    }
+    pub fn is_zero(self) -> bool {
+        self.num == 0
+    }
+    pub fn is_integer(self) -> bool {
+        self.den == 1
+    }
+    fn floor(self) -> i128 {
+        self.num.div_euclid(self.den)
+    }
+    fn ceil(self) -> i128 {
+        -(-self.num).div_euclid(self.den)
+    }
+    fn signum(self) -> i128 {
+        self.num.signum()
+    }
+}
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Ratio {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (
+            self.num.checked_mul(other.den),
+            other.num.checked_mul(self.den),
+        ) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => {
+                let a = self.num as f64 / self.den as f64;
+                let b = other.num as f64 / other.den as f64;
+                a.total_cmp(&b)
+            }
+        }
+    }
+}
+impl Display for Ratio {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        if self.den == 1 {
+            write!(fmt, "{}", self.num)?;
+        } else {
+            write!(fmt, "{}/{}", self.num, self.den)?;
+        }
+        Ok(())
+    }
+}
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    if a == 0 {
+        1
+    } else {
+        a as i128
+    }
+}
+/// An unknown number
+pub(super) type Sym = u32;
+
+/// Sum of unknowns scaled by coefficients and a constant
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) struct Linear {
+    terms: BTreeMap<Sym, Ratio>,
+    constant: Ratio,
+}
+impl Linear {
+    pub fn constant(constant: Ratio) -> Self {
+        Self {
+            terms: BTreeMap::new(),
+            constant,
+        }
+    }
+    pub fn sym(sym: Sym) -> Self {
+        Self {
+            terms: [(sym, Ratio::ONE)].into(),
+            constant: Ratio::ZERO,
+        }
+    }
+    pub fn as_constant(&self) -> Option<Ratio> {
+        self.terms.is_empty().then_some(self.constant)
+    }
+    pub fn syms(&self) -> impl Iterator<Item = Sym> + '_ {
+        self.terms.keys().copied()
+    }
+    pub fn add(&self, other: &Self) -> Option<Self> {
+        let mut terms = self.terms.clone();
+        for (sym, coefficient) in &other.terms {
+            let sum = match terms.get(sym) {
+                Some(current) => current.add(*coefficient)?,
+                None => *coefficient,
+            };
+            if sum.is_zero() {
+                terms.remove(sym);
+            } else {
+                terms.insert(*sym, sum);
+            }
+        }
+        Some(Self {
+            terms,
+            constant: self.constant.add(other.constant)?,
+        })
+    }
+    pub fn scale(&self, factor: Ratio) -> Option<Self> {
+        if factor.is_zero() {
+            return Some(Self::constant(Ratio::ZERO));
+        }
+        let terms = self
+            .terms
+            .iter()
+            .map(|(sym, coefficient)| Some((*sym, coefficient.mul(factor)?)))
+            .collect::<Option<_>>()?;
+        Some(Self {
+            terms,
+            constant: self.constant.mul(factor)?,
+        })
+    }
+    pub fn sub(&self, other: &Self) -> Option<Self> {
+        self.add(&other.scale(Ratio::ONE.neg()?)?)
+    }
+    /// Whether every value is an integer given the integer unknowns
+    pub fn is_integral(&self, integral: impl Fn(Sym) -> bool) -> bool {
+        self.constant.is_integer()
+            && self
+                .terms
+                .iter()
+                .all(|(sym, coefficient)| integral(*sym) && coefficient.is_integer())
+    }
+    fn coefficient(&self, sym: Sym) -> Ratio {
+        self.terms.get(&sym).copied().unwrap_or(Ratio::ZERO)
+    }
+    fn eval(&self, model: &HashMap<Sym, Ratio>) -> Option<Ratio> {
+        let mut value = self.constant;
+        for (sym, coefficient) in &self.terms {
+            let sym_value = model.get(sym).copied().unwrap_or(Ratio::ZERO);
+            value = value.add(coefficient.mul(sym_value)?)?;
+        }
+        Some(value)
+    }
+}
+/// `linear >= 0`, or `linear > 0` when strict
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) struct Constraint {
+    pub linear: Linear,
+    pub strict: bool,
+}
+impl Constraint {
+    /// `left >= right`
+    pub fn ge(left: &Linear, right: &Linear) -> Option<Self> {
+        Some(Self {
+            linear: left.sub(right)?,
+            strict: false,
+        })
+    }
+    /// `left > right`
+    pub fn gt(left: &Linear, right: &Linear) -> Option<Self> {
+        Some(Self {
+            linear: left.sub(right)?,
+            strict: true,
+        })
+    }
+    pub fn negate(&self) -> Option<Self> {
+        Some(Self {
+            linear: self.linear.scale(Ratio::ONE.neg()?)?,
+            strict: !self.strict,
+        })
+    }
+    /// Whether a constraint without unknowns holds, `None` if it has some
+    fn holds(&self) -> Option<bool> {
+        let constant = self.linear.as_constant()?;
+        Some(if self.strict {
+            constant > Ratio::ZERO
+        } else {
+            constant >= Ratio::ZERO
+        })
+    }
+    /// The strongest non-strict constraint with integer coefficients implied
+    /// over integers, the same constraint if some unknown isn't an integer
+    fn tighten(self, integral: &impl Fn(Sym) -> bool) -> Option<Self> {
+        if self.linear.terms.is_empty() || !self.linear.terms.keys().all(|sym| integral(*sym)) {
+            return Some(self);
+        }
+        let lcm = self
+            .linear
+            .terms
+            .values()
+            .try_fold(1, |lcm: i128, coefficient| {
+                lcm.checked_mul(coefficient.den / gcd(lcm, coefficient.den))
+            })?;
+        let scaled = self.linear.scale(Ratio::int(lcm))?;
+        // sum >= bound, or sum > bound when strict
+        let bound = scaled.constant.neg()?;
+        let bound = if self.strict {
+            bound.floor().checked_add(1)?
+        } else {
+            bound.ceil()
+        };
+        let divisor = scaled
+            .terms
+            .values()
+            .fold(0, |divisor, coefficient| gcd(divisor, coefficient.num));
+        let terms = scaled
+            .terms
+            .into_iter()
+            .map(|(sym, coefficient)| (sym, Ratio::int(coefficient.num / divisor)))
+            .collect();
+        let bound = Ratio::int(bound).div(Ratio::int(divisor))?.ceil();
+        Some(Self {
+            linear: Linear {
+                terms,
+                constant: Ratio::int(bound.checked_neg()?),
+            },
+            strict: false,
+        })
+    }
+}
+/// Constraints where at least one holds
+pub(super) type Clause = Vec<Constraint>;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) enum Outcome {
+    Unsat,
+    /// Values of the unknowns within the constraints that satisfy them
+    Sat(HashMap<Sym, Ratio>),
+    Unknown,
+}
+/// Decides whether every clause can hold at once
+pub(super) fn solve(clauses: &[Clause], integral: &impl Fn(Sym) -> bool) -> Outcome {
+    let mut base = Vec::new();
+    let mut disjunctions = Vec::new();
+    for clause in clauses {
+        match &clause[..] {
+            [] => return Outcome::Unsat,
+            [constraint] => base.push(constraint.clone()),
+            _ => disjunctions.push(clause),
+        }
+    }
+    let mut cases = 0;
+    cases_of(&mut base, &disjunctions, integral, &mut cases)
+}
+fn cases_of(
+    chosen: &mut Vec<Constraint>,
+    disjunctions: &[&Clause],
+    integral: &impl Fn(Sym) -> bool,
+    cases: &mut usize,
+) -> Outcome {
+    let Some((first, rest)) = disjunctions.split_first() else {
+        *cases += 1;
+        if *cases > MAX_CASES {
+            return Outcome::Unknown;
+        }
+        return solve_conjunction(chosen.clone(), integral);
+    };
+    let mut unknown = false;
+    for constraint in first.iter() {
+        chosen.push(constraint.clone());
+        let outcome = cases_of(chosen, rest, integral, cases);
+        chosen.pop();
+        match outcome {
+            Outcome::Unsat => (),
+            Outcome::Sat(model) => return Outcome::Sat(model),
+            Outcome::Unknown => unknown = true,
+        }
+    }
+    if unknown {
+        Outcome::Unknown
+    } else {
+        Outcome::Unsat
+    }
+}
+fn solve_conjunction(constraints: Vec<Constraint>, integral: &impl Fn(Sym) -> bool) -> Outcome {
+    match eliminate(constraints, integral) {
+        None => Outcome::Unknown,
+        Some(None) => Outcome::Unsat,
+        Some(Some(stages)) => match substitute_back(&stages, integral) {
+            Some(model) => Outcome::Sat(model),
+            None => Outcome::Unknown,
+        },
+    }
+}
+/// Constraints left when an unknown is eliminated, the unknown is within
+/// these constraints along with the unknowns eliminated after
+struct Stage {
+    sym: Sym,
+    constraints: Vec<Constraint>,
+}
+/// Eliminates every unknown in turn, `Some(None)` when the constraints can't
+/// hold
+fn eliminate(
+    constraints: Vec<Constraint>,
+    integral: &impl Fn(Sym) -> bool,
+) -> Option<Option<Vec<Stage>>> {
+    let mut constraints = constraints
+        .into_iter()
+        .map(|constraint| constraint.tighten(integral))
+        .collect::<Option<Vec<_>>>()?;
+    let mut stages = Vec::new();
+    loop {
+        let mut remaining = Vec::new();
+        for constraint in constraints {
+            match constraint.holds() {
+                Some(true) => (),
+                Some(false) => return Some(None),
+                None => remaining.push(constraint),
+            }
+        }
+        if remaining.len() > MAX_CONSTRAINTS {
+            return None;
+        }
+        // eliminating the unknown with the fewest combinations first keeps
+        // the constraints from growing
+        let syms: HashSet<_> = remaining
+            .iter()
+            .flat_map(|constraint| constraint.linear.syms())
+            .collect();
+        let Some(sym) = syms.into_iter().min_by_key(|sym| {
+            let (lower, upper) = remaining.iter().fold((0, 0), |(lower, upper), constraint| {
+                match constraint.linear.coefficient(*sym).signum() {
+                    1 => (lower + 1, upper),
+                    -1 => (lower, upper + 1),
+                    _ => (lower, upper),
+                }
+            });
+            (lower * upper, *sym)
+        }) else {
+            return Some(Some(stages));
+        };
+        let mut lower = Vec::new();
+        let mut upper = Vec::new();
+        let mut next = Vec::new();
+        for constraint in &remaining {
+            match constraint.linear.coefficient(sym).signum() {
+                1 => lower.push(constraint),
+                -1 => upper.push(constraint),
+                _ => next.push(constraint.clone()),
+            }
+        }
+        for lower in &lower {
+            for upper in &upper {
+                let lower_coefficient = lower.linear.coefficient(sym);
+                let upper_coefficient = upper.linear.coefficient(sym).neg()?;
+                let linear = lower
+                    .linear
+                    .scale(upper_coefficient)?
+                    .add(&upper.linear.scale(lower_coefficient)?)?;
+                let combined = Constraint {
+                    linear,
+                    strict: lower.strict || upper.strict,
+                };
+                next.push(combined.tighten(integral)?);
+            }
+        }
+        stages.push(Stage {
+            sym,
+            constraints: remaining,
+        });
+        constraints = next;
+    }
+}
+/// Finds values for the unknowns from the last eliminated, each value is
+/// the closest to zero within its bounds
+fn substitute_back(
+    stages: &[Stage],
+    integral: &impl Fn(Sym) -> bool,
+) -> Option<HashMap<Sym, Ratio>> {
+    let mut model = HashMap::new();
+    for stage in stages.iter().rev() {
+        let mut lower: Option<(Ratio, bool)> = None;
+        let mut upper: Option<(Ratio, bool)> = None;
+        for constraint in &stage.constraints {
+            let coefficient = constraint.linear.coefficient(stage.sym);
+            if coefficient.is_zero() {
+                continue;
+            }
+            let mut rest = constraint.linear.clone();
+            rest.terms.remove(&stage.sym);
+            // coefficient * sym + rest >= 0
+            let bound = rest.eval(&model)?.neg()?.div(coefficient)?;
+            let strict = constraint.strict;
+            if coefficient > Ratio::ZERO {
+                if lower.is_none_or(|(current, current_strict)| {
+                    bound > current || (bound == current && strict && !current_strict)
+                }) {
+                    lower = Some((bound, strict));
+                }
+            } else if upper.is_none_or(|(current, current_strict)| {
+                bound < current || (bound == current && strict && !current_strict)
+            }) {
+                upper = Some((bound, strict));
+            }
+        }
+        let value = if integral(stage.sym) {
+            let lower = match lower {
+                Some((bound, true)) => Some(bound.floor().checked_add(1)?),
+                Some((bound, false)) => Some(bound.ceil()),
+                None => None,
+            };
+            let upper = match upper {
+                Some((bound, true)) => Some(bound.ceil().checked_sub(1)?),
+                Some((bound, false)) => Some(bound.floor()),
+                None => None,
+            };
+            if let (Some(lower), Some(upper)) = (lower, upper) {
+                if lower > upper {
+                    return None;
+                }
+            }
+            let value = match (lower, upper) {
+                (Some(lower), _) if lower > 0 => lower,
+                (_, Some(upper)) if upper < 0 => upper,
+                _ => 0,
+            };
+            Ratio::int(value)
+        } else {
+            let above = |value: Ratio| match lower {
+                Some((bound, true)) => value > bound,
+                Some((bound, false)) => value >= bound,
+                None => true,
+            };
+            let below = |value: Ratio| match upper {
+                Some((bound, true)) => value < bound,
+                Some((bound, false)) => value <= bound,
+                None => true,
+            };
+            if above(Ratio::ZERO) && below(Ratio::ZERO) {
+                Ratio::ZERO
+            } else {
+                let candidates = [
+                    lower.map(|(bound, _)| bound),
+                    lower.and_then(|(bound, _)| bound.add(Ratio::ONE)),
+                    upper.map(|(bound, _)| bound),
+                    upper.and_then(|(bound, _)| bound.sub(Ratio::ONE)),
+                    lower
+                        .zip(upper)
+                        .and_then(|((lower, _), (upper, _))| lower.add(upper)?.div(Ratio::int(2))),
+                ];
+                candidates
+                    .into_iter()
+                    .flatten()
+                    .find(|value| above(*value) && below(*value))?
+            }
+        };
+        model.insert(stage.sym, value);
+    }
+    Some(model)
+}
+#[cfg(test)]
+mod test {
+    use super::{solve, Clause, Constraint, Linear, Outcome, Ratio};
+
+    fn int(value: i128) -> Linear {
+        Linear::constant(Ratio::int(value))
+    }
+    fn x() -> Linear {
+        Linear::sym(0)
+    }
+    fn y() -> Linear {
+        Linear::sym(1)
+    }
+    fn conjunction(constraints: impl IntoIterator<Item = Constraint>) -> Vec<Clause> {
+        constraints
+            .into_iter()
+            .map(|constraint| vec![constraint])
+            .collect()
+    }
+    fn value(outcome: &Outcome, sym: u32) -> Ratio {
+        match outcome {
+            Outcome::Sat(model) => model[&sym],
+            outcome => panic!("expected a model, found {outcome:?}"),
+        }
+    }
+    #[test]
+    fn rational() {
+        // 0 < 2x < 2 has no integer solution but has rational ones
+        let clauses = conjunction([
+            Constraint::gt(&x().scale(Ratio::int(2)).unwrap(), &int(0)).unwrap(),
+            Constraint::gt(&int(2), &x().scale(Ratio::int(2)).unwrap()).unwrap(),
+        ]);
+        assert_eq!(solve(&clauses, &|_| true), Outcome::Unsat);
+        let outcome = solve(&clauses, &|_| false);
+        assert_eq!(value(&outcome, 0), Ratio::new(1, 2).unwrap());
+    }
+    #[test]
+    fn integer() {
+        // x >= 0, y >= x + 1, y <= 3, model is closest to zero
+        let clauses = conjunction([
+            Constraint::ge(&x(), &int(0)).unwrap(),
+            Constraint::ge(&y(), &x().add(&int(1)).unwrap()).unwrap(),
+            Constraint::ge(&int(3), &y()).unwrap(),
+        ]);
+        let outcome = solve(&clauses, &|_| true);
+        assert_eq!(value(&outcome, 0), Ratio::int(0));
+        assert_eq!(value(&outcome, 1), Ratio::int(1));
+        let mut clauses = clauses;
+        clauses.push(vec![Constraint::gt(&x(), &int(2)).unwrap()]);
+        assert_eq!(solve(&clauses, &|_| true), Outcome::Unsat);
+    }
+    #[test]
+    fn disjunction() {
+        // x != 0 and x == 0
+        let not_zero = vec![
+            Constraint::gt(&x(), &int(0)).unwrap(),
+            Constraint::gt(&int(0), &x()).unwrap(),
+        ];
+        let mut clauses = conjunction([
+            Constraint::ge(&x(), &int(0)).unwrap(),
+            Constraint::ge(&int(0), &x()).unwrap(),
+        ]);
+        clauses.push(not_zero.clone());
+        assert_eq!(solve(&clauses, &|_| false), Outcome::Unsat);
+        let clauses = vec![not_zero, vec![Constraint::ge(&int(-1), &x()).unwrap()]];
+        assert_eq!(value(&solve(&clauses, &|_| true), 0), Ratio::int(-1));
+    }
+}
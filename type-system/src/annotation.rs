@@ -0,0 +1,352 @@
+use crate::{
+    expr::unit,
+    ty::{
+        cons::{Newtype, OrderedAnd},
+        num::{NumBound, NumType, Repr},
+        Env, Kind, KindedVar, Subs, Substitutable, Unifiable, VarState,
+    },
+    Alias, Cons, Keyed, MutType, Type, TypeError, Var,
+};
+use hir::{
+    keyword,
+    statement::Statement,
+    ty::{MutTypeExpr, TypeExpr},
+    Atom, PrettyPrintType,
+};
+use std::collections::{HashMap, HashSet};
+
+// a type variable named for the first time is introduced into `env`, so later
+// annotations within the same scope refer to the same variable
+fn named_var(
+    name: &Atom,
+    kind: Kind,
+    var_state: &mut VarState,
+    env: &mut Env,
+) -> Result<Var, TypeError> {
+    match env.get_type_var(name) {
+        Some(var) if var.kind == kind => Ok(var.var),
+        Some(_) => Err(TypeError::MismatchKind),
+        None => {
+            let var = var_state.new_named(name.clone());
+            env.insert_type_var(
+                name.clone(),
+                KindedVar {
+                    kind,
+                    var: var.clone(),
+                },
+            );
+            Ok(var)
+        }
+    }
+}
+fn row_var(rest: &TypeExpr, var_state: &mut VarState, env: &mut Env) -> Result<Var, TypeError> {
+    match rest {
+        TypeExpr::Discard => Ok(var_state.new_var()),
+        TypeExpr::Var(name) => named_var(name, Kind::Type, var_state, env),
+        _ => Err(TypeError::MismatchKind),
+    }
+}
+fn annotation_types<'a>(
+    annotations: impl IntoIterator<Item = &'a TypeExpr>,
+    var_state: &mut VarState,
+    env: &mut Env,
+) -> Result<Vec<Type>, TypeError> {
+    annotations
+        .into_iter()
+        .map(|annotation| annotation_type(annotation, var_state, env))
+        .collect()
+}
+pub(super) fn annotation_type(
+    annotation: &TypeExpr,
+    var_state: &mut VarState,
+    env: &mut Env,
+) -> Result<Type, TypeError> {
+    let cons = match annotation {
+        TypeExpr::Discard => return Ok(Type::Var(var_state.new_var())),
+        TypeExpr::Var(name) => {
+            return Ok(Type::Var(named_var(name, Kind::Type, var_state, env)?));
+        }
+        TypeExpr::Name(name, args) => return name_type(name, args, var_state, env),
+        TypeExpr::Array(ty) => Cons::Array(Box::new(annotation_type(ty, var_state, env)?)),
+        TypeExpr::Tuple(tuple) => {
+            let left = annotation_types(tuple.left.iter(), var_state, env)?;
+            match &tuple.rest {
+                Some(rest) => {
+                    let rest = row_var(rest, var_state, env)?;
+                    let right = annotation_types(tuple.right.iter(), var_state, env)?;
+                    Cons::Tuple(OrderedAnd::Row(left, rest, right))
+                }
+                None if left.is_empty() => return Ok(unit()),
+                None => Cons::Tuple(OrderedAnd::NonRow(left.into())),
+            }
+        }
+        TypeExpr::Record(record) => {
+            let mut fields = Vec::with_capacity(record.fields.len());
+            for (name, ty) in record.fields.iter() {
+                fields.push((name.clone(), annotation_type(ty, var_state, env)?));
+            }
+            let rest = match &record.rest {
+                Some(rest) => Some(row_var(rest, var_state, env)?),
+                None => None,
+            };
+            Cons::Record(Keyed {
+                fields: fields.into_iter().collect(),
+                rest,
+            })
+        }
+        TypeExpr::Union(union) => {
+            let mut fields = Vec::with_capacity(union.tags.len());
+            for (tag, ty) in union.tags.iter() {
+                let ty = match ty {
+                    Some(ty) => annotation_type(ty, var_state, env)?,
+                    None => unit(),
+                };
+                fields.push((tag.clone(), ty));
+            }
+            let rest = match &union.rest {
+                Some(rest) => Some(row_var(rest, var_state, env)?),
+                None => None,
+            };
+            Cons::Union(Keyed {
+                fields: fields.into_iter().collect(),
+                rest,
+            })
+        }
+        // TODO: lifetimes are not checked yet
+        TypeExpr::Ref(reference) => {
+            let mutability = match &reference.mutability {
+                None | Some(MutTypeExpr::Imm) => MutType::Imm,
+                Some(MutTypeExpr::Mut) => MutType::Mut,
+                Some(MutTypeExpr::Var(name)) => {
+                    MutType::Var(named_var(name, Kind::MutType, var_state, env)?)
+                }
+            };
+            let ty = annotation_type(&reference.ty, var_state, env)?;
+            Cons::Ref(mutability, Box::new(ty))
+        }
+        TypeExpr::Fun(param, ret) => {
+            let param = annotation_type(param, var_state, env)?;
+            let ret = annotation_type(ret, var_state, env)?;
+            Cons::Fun(Box::new(param), Box::new(ret))
+        }
+    };
+    Ok(Type::Cons(cons))
+}
+fn name_type(
+    name: &Atom,
+    args: &[TypeExpr],
+    var_state: &mut VarState,
+    env: &mut Env,
+) -> Result<Type, TypeError> {
+    let cons = match name.clone() {
+        keyword!("Num") => Cons::Num(var_state.new_num(NumBound::default())),
+        keyword!("Char") => Cons::Char,
+        keyword!("Bool") => Cons::Bool,
+        keyword!("Str") => Cons::Str,
+        name if Repr::from_name(&name).is_some() => {
+            Cons::Num(NumType::Repr(Repr::from_name(&name).unwrap()))
+        }
+        name if env.get_newtype(&name).is_some() => {
+            let id = env.get_newtype(&name).unwrap();
+            let expected = var_state.newtypes[id].arity();
+            if expected != args.len() {
+                return Err(TypeError::MismatchTypeArgs {
+                    name,
+                    expected,
+                    found: args.len(),
+                });
+            }
+            let args = annotation_types(args, var_state, env)?;
+            return Ok(Type::Cons(Cons::Newtype(Newtype {
+                name,
+                id,
+                args: args.into(),
+            })));
+        }
+        name => {
+            let alias = match env.get_alias(&name) {
+                Some(alias) => alias.clone(),
+                None => return Err(TypeError::UnboundType(name)),
+            };
+            if alias.params.len() != args.len() {
+                return Err(TypeError::MismatchTypeArgs {
+                    name,
+                    expected: alias.params.len(),
+                    found: args.len(),
+                });
+            }
+            let args = annotation_types(args, var_state, env)?;
+            let mut alias_env = env.clone();
+            let mut subs = Subs::new();
+            for (param, arg) in alias.params.iter().zip(args.iter()) {
+                let var = var_state.new_named(param.clone());
+                alias_env.insert_type_var(
+                    param.clone(),
+                    KindedVar {
+                        kind: Kind::Type,
+                        var: var.clone(),
+                    },
+                );
+                subs.insert_type(var, arg.clone());
+            }
+            let mut ty = annotation_type(&alias.ty, var_state, &mut alias_env)?;
+            ty.substitute(&subs)?;
+            return Ok(Type::Alias(Alias {
+                name: alias.ident,
+                args: args.into(),
+                ty: Box::new(ty),
+            }));
+        }
+    };
+    if !args.is_empty() {
+        return Err(TypeError::MismatchTypeArgs {
+            name: name.clone(),
+            expected: 0,
+            found: args.len(),
+        });
+    }
+    Ok(Type::Cons(cons))
+}
+// collects the type variables and type names an annotation refers to
+pub(super) fn references<'a>(
+    ty: &'a TypeExpr,
+    vars: &mut Vec<&'a Atom>,
+    names: &mut Vec<&'a Atom>,
+) {
+    match ty {
+        TypeExpr::Discard => (),
+        TypeExpr::Var(var) => vars.push(var),
+        TypeExpr::Name(name, args) => {
+            names.push(name);
+            for arg in args.iter() {
+                references(arg, vars, names);
+            }
+        }
+        TypeExpr::Array(ty) => references(ty, vars, names),
+        TypeExpr::Tuple(tuple) => {
+            for ty in tuple.left.iter().chain(tuple.right.iter()) {
+                references(ty, vars, names);
+            }
+            if let Some(rest) = &tuple.rest {
+                references(rest, vars, names);
+            }
+        }
+        TypeExpr::Record(record) => {
+            for (_, ty) in record.fields.iter() {
+                references(ty, vars, names);
+            }
+            if let Some(rest) = &record.rest {
+                references(rest, vars, names);
+            }
+        }
+        TypeExpr::Union(union) => {
+            for ty in union.tags.iter().filter_map(|(_, ty)| ty.as_ref()) {
+                references(ty, vars, names);
+            }
+            if let Some(rest) = &union.rest {
+                references(rest, vars, names);
+            }
+        }
+        TypeExpr::Ref(reference) => {
+            if let Some(MutTypeExpr::Var(var)) = &reference.mutability {
+                vars.push(var);
+            }
+            references(&reference.ty, vars, names);
+        }
+        TypeExpr::Fun(param, ret) => {
+            references(param, vars, names);
+            references(ret, vars, names);
+        }
+    }
+}
+/// Brings every alias declared in a block into `env`, aliases are visible
+/// throughout the whole block
+pub(super) fn declare_aliases<T>(
+    statements: &[Statement<T>],
+    env: &mut Env,
+) -> Result<(), TypeError>
+where
+    T: PrettyPrintType,
+{
+    let aliases: HashMap<_, _> = statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::AliasDeclare(alias) => Some((&alias.ident, alias)),
+            _ => None,
+        })
+        .collect();
+    let mut dependencies = HashMap::with_capacity(aliases.len());
+    for alias in aliases.values() {
+        let mut vars = Vec::new();
+        let mut names = Vec::new();
+        references(&alias.ty, &mut vars, &mut names);
+        if let Some(var) = vars.into_iter().find(|var| !alias.params.contains(var)) {
+            return Err(TypeError::UnboundType(var.clone()).at(alias.span));
+        }
+        names.retain(|name| aliases.contains_key(name));
+        dependencies.insert(&alias.ident, names);
+    }
+    // depth-first search, an alias reached again while still on the stack is
+    // part of a cycle
+    fn visit<'a>(
+        name: &'a Atom,
+        dependencies: &HashMap<&'a Atom, Vec<&'a Atom>>,
+        stack: &mut Vec<&'a Atom>,
+        done: &mut HashSet<&'a Atom>,
+    ) -> Result<(), TypeError> {
+        if done.contains(name) {
+            return Ok(());
+        }
+        if stack.contains(&name) {
+            return Err(TypeError::CyclicAlias(name.clone()));
+        }
+        stack.push(name);
+        for dependency in &dependencies[name] {
+            visit(dependency, dependencies, stack, done)?;
+        }
+        stack.pop();
+        done.insert(name);
+        Ok(())
+    }
+    let mut done = HashSet::new();
+    for statement in statements {
+        if let Statement::AliasDeclare(alias) = statement {
+            visit(&alias.ident, &dependencies, &mut Vec::new(), &mut done)
+                .map_err(|err| err.at(alias.span))?;
+        }
+    }
+    for alias in aliases.into_values() {
+        env.insert_alias(alias.clone());
+    }
+    Ok(())
+}
+/// Unifies an inferred type with the type of its annotation, a mismatch is
+/// reported against the annotation
+pub(super) fn unify_annotation(
+    found: Type,
+    annotation: Type,
+    subs: &mut Subs,
+    var_state: &mut VarState,
+) -> Result<(), TypeError> {
+    match found
+        .clone()
+        .unify_with(annotation.clone(), subs, var_state)
+    {
+        Err(
+            TypeError::MismatchCons(..)
+            | TypeError::MismatchKind
+            | TypeError::MismatchArity
+            | TypeError::MismatchName,
+        ) => {
+            let mut annotation = annotation;
+            let mut found = found;
+            annotation.substitute(var_state.bindings())?;
+            found.substitute(var_state.bindings())?;
+            Err(TypeError::MismatchAnnotation {
+                annotation: Box::new(annotation),
+                found: Box::new(found),
+            })
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{
+    annotation::declare_aliases,
+    expr::infer_statement,
+    module::{declared_values, default_declared_nums, PatternNames},
+    newtype::declare_newtypes,
+    string::declare_conversions,
+    substitute_hir,
+    traits::{declare_prelude, declare_traits, solve_wanted},
+    ty::{Env, FreeVars, Scheme, SchemeMut, Subs, Var, VarState},
+    Type, TypeError, TypeWarning,
+};
+use hir::{statement::Statement, visit::Visit, Atom};
+
+/// What a top-level statement adds to the environment, the statements after
+/// it can be inferred against it without inferring the statement again
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Declared {
+    vars: Vec<(Var, SchemeMut)>,
+    modules: Vec<(Atom, Env)>,
+}
+impl Declared {
+    /// Whether the types are fully known. Types of other declarations may
+    /// still have variables that are only bound by the statements using them
+    pub fn is_closed(&self) -> bool {
+        self.vars
+            .iter()
+            .all(|(_, scheme)| scheme.free_vars().is_empty())
+            && self
+                .modules
+                .iter()
+                .all(|(_, module)| module.free_vars().is_empty())
+    }
+    /// Whether both declare the same names with the same types, generic
+    /// variables may be named differently
+    pub fn is_same(&self, other: &Self) -> bool {
+        self.vars.len() == other.vars.len()
+            && self.vars.iter().zip(&other.vars).all(|(a, b)| {
+                let ((var_a, a), (var_b, b)) = (a, b);
+                var_a == var_b && a.is_mut == b.is_mut && a.scheme.is_equivalent(&b.scheme)
+            })
+            && self.modules == other.modules
+    }
+    /// Scheme of a declared variable
+    pub fn get(&self, name: &str) -> Option<&Scheme> {
+        self.vars
+            .iter()
+            .rev()
+            .find(|(var, _)| var.name.as_ref() == name)
+            .map(|(_, scheme)| &scheme.scheme)
+    }
+}
+/// Infers the top-level statements of a module one at a time. Statements
+/// that are inferred earlier can be skipped by declaring what they've
+/// declared instead.
+pub struct Checker {
+    subs: Subs,
+    var_state: VarState,
+    env: Env,
+}
+impl Checker {
+    /// The checker for a module made of `statements`, type aliases,
+    /// newtypes, traits and implementations are declared for the whole module
+    /// upfront
+    pub fn new(statements: &[Statement<()>]) -> Result<Self, TypeError> {
+        let mut env = Env::new();
+        let mut var_state = VarState::new();
+        declare_prelude(&mut var_state);
+        declare_conversions(&mut env);
+        declare_aliases(statements, &mut env)?;
+        declare_newtypes(statements, &mut env, &mut var_state)?;
+        declare_traits(statements, &env, &mut var_state)?;
+        Ok(Self {
+            subs: Subs::new(),
+            var_state,
+            env,
+        })
+    }
+    /// Infers the statement after every statement inferred or declared so
+    /// far. The environment is left as it is when it fails
+    pub fn infer(
+        &mut self,
+        statement: Statement<()>,
+    ) -> Result<(Statement<Type>, Declared, Vec<TypeWarning>), TypeError> {
+        let mut names = Vec::new();
+        let mut modules = Vec::new();
+        match &statement {
+            Statement::Declare(declare) => {
+                let mut pattern_names = PatternNames(Vec::new());
+                pattern_names.visit_pattern(&declare.pattern);
+                names = pattern_names.0;
+            }
+            Statement::FunDeclare(fun) => names.push(fun.ident.clone()),
+            Statement::NewtypeDeclare(newtype) => names.push(newtype.ident.clone()),
+            Statement::TraitDeclare(trait_declare) => names.extend(
+                trait_declare
+                    .methods
+                    .iter()
+                    .map(|method| method.ident.clone()),
+            ),
+            Statement::ModuleDeclare(module) => modules.push(module.ident.clone()),
+            Statement::AliasDeclare(_) | Statement::ImplDeclare(_) | Statement::Expr(_) => (),
+        }
+        let values = declared_values(&statement);
+        let env = self.env.clone();
+        let wanted = self.var_state.wanted.clone();
+        let sites = self.var_state.sites.len();
+        // predicates on type variables may still be resolved by the
+        // statements after it
+        let typed = infer_statement(
+            &mut self.subs,
+            &mut self.env,
+            &mut self.var_state,
+            statement,
+        )
+        .and_then(|typed| {
+            default_declared_nums(&values, &mut self.subs, &mut self.env, &mut self.var_state)?;
+            solve_wanted(&mut self.var_state, false)?;
+            Ok(typed)
+        })
+        .inspect_err(|_| {
+            self.env = env;
+            self.var_state.wanted = wanted;
+            self.var_state.sites.truncate(sites);
+            self.var_state.take_warnings();
+        })?;
+        let vars = names
+            .into_iter()
+            .filter_map(|name| {
+                let var = Var::new_bare(name);
+                let scheme = self.env.get_scheme_mut(&var)?.clone();
+                Some((var, scheme))
+            })
+            .collect();
+        let modules = modules
+            .into_iter()
+            .filter_map(|name| {
+                let module = self.env.get_module(&name)?.clone();
+                Some((name, module))
+            })
+            .collect();
+        Ok((
+            typed,
+            Declared { vars, modules },
+            self.var_state.take_warnings(),
+        ))
+    }
+    /// Adds what a statement inferred earlier has declared
+    pub fn declare(&mut self, declared: &Declared) {
+        for (var, scheme) in &declared.vars {
+            self.env.insert(var.clone(), scheme.clone());
+        }
+        for (name, module) in &declared.modules {
+            self.env.insert_module(name.clone(), module.clone());
+        }
+    }
+    /// Applies every binding made so far to a statement inferred by this
+    /// checker, done once the statements using it are inferred
+    pub fn finish(&self, statement: &mut Statement<Type>) -> Result<(), TypeError> {
+        substitute_hir(statement, self.var_state.bindings())
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::checker::Checker;
+    use hir::statement::Statement;
+    use parser::{ast, EasyParser};
+
+    fn parse(src: &str) -> Vec<Statement<()>> {
+        let (statements, rest) = ast().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        statements
+    }
+    #[test]
+    fn declared() {
+        let statements = parse("id(x) => x; y = []; z = id(1);");
+        let mut checker = Checker::new(&statements).unwrap();
+        let declared: Vec<_> = statements
+            .into_iter()
+            .map(|statement| checker.infer(statement).unwrap().1)
+            .collect();
+        assert!(declared[0].is_closed());
+        assert!(!declared[1].is_closed());
+        assert_eq!(declared[2].get("z").unwrap().to_string(), "I64");
+    }
+    #[test]
+    fn skip_declared() {
+        let first = parse("id(x) => x; n = id(1);");
+        let mut checker = Checker::new(&first).unwrap();
+        let mut declared = Vec::new();
+        for statement in first {
+            declared.push(checker.infer(statement).unwrap().1);
+        }
+        // the same function again, inferred by a different checker
+        let second = parse("f(x) => x; id(x) => x; b = id(true);");
+        let mut checker = Checker::new(&second).unwrap();
+        let mut statements = second.into_iter();
+        checker.infer(statements.next().unwrap()).unwrap();
+        let (_, id, _) = checker.infer(statements.next().unwrap()).unwrap();
+        assert!(id.is_same(&declared[0]));
+        assert_ne!(id, declared[0]);
+
+        let rest = parse("m = id(id); k = m(1);");
+        let mut checker = Checker::new(&rest).unwrap();
+        checker.declare(&declared[0]);
+        for statement in rest {
+            checker.infer(statement).unwrap();
+        }
+    }
+    #[test]
+    fn failed() {
+        let statements = parse("x = 1; y = x + true; z = x;");
+        let mut checker = Checker::new(&statements).unwrap();
+        let mut statements = statements.into_iter();
+        checker.infer(statements.next().unwrap()).unwrap();
+        assert!(checker.infer(statements.next().unwrap()).is_err());
+        checker.infer(statements.next().unwrap()).unwrap();
+    }
+}
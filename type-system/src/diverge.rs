@@ -0,0 +1,128 @@
+use crate::{expr::is_never, ty::VarState, Type};
+use hir::{
+    expr::{Arg, Block, Call, Collection, ControlFlow, Expr, ExprKind, Field, If, PlaceExpr},
+    statement::Statement,
+};
+
+// NOTE: this is conservative, an expression that isn't detected as diverging
+// may still diverge at runtime
+
+pub(super) struct Divergence<'a> {
+    pub var_state: &'a VarState,
+}
+impl Divergence<'_> {
+    pub fn expr(&self, expr: &Expr<Type>) -> bool {
+        // calls to functions that never return and loops without `break`
+        // have type `Never`
+        if is_never(&expr.ty, self.var_state).unwrap_or(false) {
+            return true;
+        }
+        match &expr.expr {
+            ExprKind::Jump(_) | ExprKind::Never => true,
+            ExprKind::Literal(_) | ExprKind::Str(_) | ExprKind::Unit | ExprKind::Fun(_) => false,
+            ExprKind::Tag(tag) => tag.expr.iter().any(|expr| self.expr(expr)),
+            ExprKind::Assign(assign) => assign.iter().any(|assign| self.expr(&assign.expr)),
+            ExprKind::Array(elements) => elements.iter().any(|element| self.expr(&element.expr)),
+            ExprKind::ArrayRange(range) => [&range.left, &range.right]
+                .into_iter()
+                .flatten()
+                .any(|bound| self.expr(&bound.expr)),
+            ExprKind::Splat(expr) => self.expr(expr),
+            ExprKind::Record(record) => self.record(record),
+            ExprKind::Tuple(tuple) => self.tuple(tuple),
+            ExprKind::Unary(unary) => self.expr(&unary.expr),
+            ExprKind::Binary(binary) => self.expr(&binary.left),
+            ExprKind::Place(place) => self.place(place),
+            ExprKind::Call(call) => self.call(call),
+            ExprKind::ControlFlow(control_flow) => self.control_flow(control_flow),
+            ExprKind::Annotated(annotated) => self.expr(&annotated.expr),
+        }
+    }
+    pub fn block(&self, block: &Block<Type>) -> bool {
+        block
+            .statement
+            .iter()
+            .any(|statement| self.statement(statement))
+            || block.expr.iter().any(|expr| self.expr(expr))
+    }
+    pub fn statement(&self, statement: &Statement<Type>) -> bool {
+        match statement {
+            Statement::Declare(declare) => self.expr(&declare.expr),
+            Statement::FunDeclare(_)
+            | Statement::AliasDeclare(_)
+            | Statement::NewtypeDeclare(_)
+            | Statement::TraitDeclare(_)
+            | Statement::ImplDeclare(_)
+            | Statement::ModuleDeclare(_) => false,
+            Statement::Expr(expr) => self.expr(expr),
+        }
+    }
+    fn place(&self, place: &PlaceExpr<Type>) -> bool {
+        match place {
+            PlaceExpr::Var(_, _) => false,
+            PlaceExpr::FieldAccess(field_access) => self.expr(&field_access.expr),
+            PlaceExpr::Index(index) => self.expr(&index.expr) || self.expr(&index.index),
+            PlaceExpr::Slice(slice) => {
+                self.expr(&slice.expr)
+                    || [&slice.range.left, &slice.range.right]
+                        .into_iter()
+                        .flatten()
+                        .any(|bound| self.expr(&bound.expr))
+            }
+            PlaceExpr::Deref(expr) | PlaceExpr::Len(expr) => self.expr(expr),
+        }
+    }
+    fn record(&self, record: &Collection<Field<Type>, Type>) -> bool {
+        match record {
+            Collection::Collection(fields) => fields.iter().any(|field| self.expr(&field.expr)),
+            Collection::WithSplat(record) => {
+                record.left.iter().any(|field| self.expr(&field.expr))
+                    || self.expr(&record.splat)
+                    || record.right.iter().any(|field| self.expr(&field.expr))
+            }
+        }
+    }
+    fn tuple(&self, tuple: &Collection<Expr<Type>, Type>) -> bool {
+        match tuple {
+            Collection::Collection(exprs) => exprs.iter().any(|expr| self.expr(expr)),
+            Collection::WithSplat(tuple) => {
+                tuple.left.iter().any(|expr| self.expr(expr))
+                    || self.expr(&tuple.splat)
+                    || tuple.right.iter().any(|expr| self.expr(expr))
+            }
+        }
+    }
+    fn call(&self, call: &Call<Type>) -> bool {
+        let arg = match &call.arg {
+            Arg::Unit => false,
+            Arg::Splat(expr) => self.expr(expr),
+            Arg::Record(record) => self.record(record),
+            Arg::Tuple(tuple) => self.tuple(tuple),
+        };
+        self.expr(&call.expr) || arg
+    }
+    fn control_flow(&self, control_flow: &ControlFlow<Type>) -> bool {
+        match control_flow {
+            ControlFlow::Block(block) => self.block(block),
+            ControlFlow::If(if_expr) => self.if_expr(if_expr),
+            ControlFlow::For(for_expr) => self.expr(&for_expr.expr),
+            ControlFlow::While(while_expr) => self.expr(&while_expr.condition),
+            // a jump within the body may only leave the loop, one that never
+            // ends has type `Never`
+            ControlFlow::Loop(_) => false,
+            ControlFlow::Match(match_expr) => {
+                self.expr(&match_expr.expr)
+                    || (!match_expr.arm.is_empty()
+                        && match_expr.arm.iter().all(|arm| self.expr(&arm.expr)))
+            }
+        }
+    }
+    fn if_expr(&self, if_expr: &If<Type>) -> bool {
+        self.expr(&if_expr.condition)
+            || (self.block(&if_expr.body)
+                && if_expr
+                    .else_part
+                    .iter()
+                    .any(|else_part| self.control_flow(else_part)))
+    }
+}
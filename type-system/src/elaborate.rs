@@ -0,0 +1,674 @@
+//! Dictionary passing, the elaborated statements no longer need traits.
+//!
+//! - a function with `given` predicates takes their dictionaries first, as in
+//!   `f(Eq#a#1) => { f(x) => ...; f }`
+//! - an implementation becomes a function returning its dictionary, a record
+//!   of its methods, taking the dictionaries it's given
+//! - uses of these functions pass the dictionaries, methods are taken from
+//!   the dictionary
+//! - `==`, `!=`, and `++` call the method unless it's implemented by the
+//!   primitive operator
+//!
+//! Trait declarations are removed and the built-in dictionaries used are
+//! declared before everything else.
+
+use crate::{
+    expr::unit,
+    traits::{find_impl, Impl, Site},
+    ty::{
+        cons::{Cons, OrderedAnd},
+        num::{NumType, Repr},
+        MutType, Predicate, Scheme, Substitutable, Type, VarState,
+    },
+};
+use hir::{
+    expr::{
+        Arg, Assign, Binary, BinaryType, Block, Call, Collection, ControlFlow, Expr, ExprKind,
+        Field, FieldAccess, Fun, Index, Literal, PlaceExpr, Unary, UnaryType, While,
+    },
+    fold::{
+        walk_binary, walk_block, walk_expr, walk_fun_declare, walk_module, walk_statement, Fold,
+    },
+    keyword,
+    pattern::{Pattern, PatternKind, TypedVar, Var},
+    statement::{Declare, FunDeclare, ImplDeclare, Module, Statement, Visibility},
+    Atom, Span,
+};
+use std::{
+    collections::{BTreeSet, HashSet},
+    mem::take,
+};
+
+pub(super) fn elaborate(
+    statements: Vec<Statement<Type>>,
+    var_state: &VarState,
+) -> Vec<Statement<Type>> {
+    let mut elaborator = Elaborator {
+        var_state,
+        params: Vec::new(),
+        used: BTreeSet::new(),
+    };
+    let statements: Vec<_> = without_traits(statements.into())
+        .into_vec()
+        .into_iter()
+        .map(|statement| elaborator.fold_statement(statement))
+        .collect();
+    let used: Vec<_> = elaborator
+        .used
+        .iter()
+        .copied()
+        .filter(|index| var_state.impls[*index].operator.is_some())
+        .collect();
+    let builtins: Vec<_> = used
+        .into_iter()
+        .map(|index| Statement::FunDeclare(elaborator.builtin_dictionary(&var_state.impls[index])))
+        .collect();
+    builtins.into_iter().chain(statements).collect()
+}
+fn without_traits(statements: Box<[Statement<Type>]>) -> Box<[Statement<Type>]> {
+    statements
+        .into_vec()
+        .into_iter()
+        .filter(|statement| !matches!(statement, Statement::TraitDeclare(_)))
+        .collect()
+}
+fn expr(expr: ExprKind<Type>, ty: Type) -> Expr<Type> {
+    Expr {
+        expr,
+        ty,
+        span: Span::default(),
+    }
+}
+fn var_expr(name: Atom, ty: Type) -> Expr<Type> {
+    expr(ExprKind::Place(PlaceExpr::Var(name, Span::default())), ty)
+}
+fn call(callee: Expr<Type>, args: Vec<Expr<Type>>, ty: Type) -> Expr<Type> {
+    let arg = if args.is_empty() {
+        Arg::Unit
+    } else {
+        Arg::Tuple(Collection::Collection(args.into()))
+    };
+    expr(
+        ExprKind::Call(Call {
+            expr: Box::new(callee),
+            arg,
+        }),
+        ty,
+    )
+}
+fn fun_type(params: &[(Atom, Type)], ret: Type) -> Type {
+    let param = if params.is_empty() {
+        unit()
+    } else {
+        Type::Cons(Cons::RecordTuple(OrderedAnd::NonRow(params.into())))
+    };
+    Type::Cons(Cons::Fun(Box::new(param), Box::new(ret)))
+}
+fn param_pattern(params: &[(Atom, Type)]) -> Pattern<Type> {
+    let vars = params
+        .iter()
+        .map(|(name, ty)| TypedVar {
+            var: Var {
+                ident: name.clone(),
+                mutable: false,
+                bind_to_ref: false,
+                span: Span::default(),
+            },
+            annotation: None,
+            ty: ty.clone(),
+        })
+        .collect();
+    let ty = match fun_type(params, unit()) {
+        Type::Cons(Cons::Fun(param, _)) => *param,
+        _ => unreachable!(),
+    };
+    Pattern {
+        pattern: PatternKind::Param(vars),
+        ty,
+        span: Span::default(),
+    }
+}
+fn declare_mut(name: &str, value: Expr<Type>) -> Statement<Type> {
+    let pattern = Pattern {
+        pattern: PatternKind::Var(Var {
+            ident: Atom::from(name),
+            mutable: true,
+            bind_to_ref: false,
+            span: Span::default(),
+        }),
+        ty: value.ty.clone(),
+        span: Span::default(),
+    };
+    Statement::Declare(Declare {
+        visibility: Visibility::Private,
+        pattern,
+        expr: value,
+        else_part: None,
+    })
+}
+fn assign(name: &str, value: Expr<Type>) -> Statement<Type> {
+    let assign = Assign {
+        place: PlaceExpr::Var(Atom::from(name), Span::default()),
+        expr: value,
+    };
+    Statement::Expr(expr(ExprKind::Assign(Box::new([assign])), unit()))
+}
+fn binary(kind: BinaryType, left: Expr<Type>, right: Expr<Type>, ty: Type) -> Expr<Type> {
+    let binary = Binary {
+        kind,
+        left: Box::new(left),
+        right: Box::new(right),
+    };
+    expr(ExprKind::Binary(binary), ty)
+}
+/// Compares two arrays element by element with `==`, the loop is
+///
+/// ```butter
+/// mut same = left.len == right.len;
+/// mut i = 0;
+/// while same && i < left.len {
+///     same <- left[i] == right[i];
+///     i <- i + 1;
+/// };
+/// same
+/// ```
+fn elementwise_equal(left: Expr<Type>, right: Expr<Type>, element: Type) -> Expr<Type> {
+    let bool = Type::Cons(Cons::Bool);
+    let index = Type::Cons(Cons::Num(NumType::Repr(Repr::U64)));
+    let len = |array: &Expr<Type>| {
+        expr(
+            ExprKind::Place(PlaceExpr::Len(Box::new(array.clone()))),
+            index.clone(),
+        )
+    };
+    let at = |array: &Expr<Type>| {
+        let index = Index {
+            expr: Box::new(array.clone()),
+            index: Box::new(var_expr(Atom::from("i"), index.clone())),
+        };
+        expr(ExprKind::Place(PlaceExpr::Index(index)), element.clone())
+    };
+    let same = || var_expr(Atom::from("same"), bool.clone());
+    let same_len = binary(BinaryType::Equal, len(&left), len(&right), bool.clone());
+    let in_bounds = binary(
+        BinaryType::Less,
+        var_expr(Atom::from("i"), index.clone()),
+        len(&left),
+        bool.clone(),
+    );
+    let next = binary(
+        BinaryType::Add,
+        var_expr(Atom::from("i"), index.clone()),
+        expr(ExprKind::Literal(Literal::UInt(1)), index.clone()),
+        index.clone(),
+    );
+    let body = Block {
+        statement: Box::new([
+            assign(
+                "same",
+                binary(BinaryType::Equal, at(&left), at(&right), bool.clone()),
+            ),
+            assign("i", next),
+        ]),
+        expr: None,
+        span: Span::default(),
+    };
+    let loop_expr = While {
+        condition: Box::new(binary(BinaryType::LazyAnd, same(), in_bounds, bool.clone())),
+        body,
+    };
+    let block = Block {
+        statement: Box::new([
+            declare_mut("same", same_len),
+            declare_mut(
+                "i",
+                expr(ExprKind::Literal(Literal::UInt(0)), index.clone()),
+            ),
+            Statement::Expr(expr(
+                ExprKind::ControlFlow(ControlFlow::While(loop_expr)),
+                unit(),
+            )),
+        ]),
+        expr: Some(Box::new(same())),
+        span: Span::default(),
+    };
+    expr(ExprKind::ControlFlow(ControlFlow::Block(block)), bool)
+}
+/// Function taking the dictionaries as parameters and returning the value
+/// of the block
+fn dictionary_fun(
+    ident: Atom,
+    span: Span,
+    params: &[(Atom, Type)],
+    block: Block<Type>,
+    scheme: Scheme,
+) -> FunDeclare<Type> {
+    let ret = match &block.expr {
+        Some(expr) => expr.ty.clone(),
+        None => unit(),
+    };
+    let body = expr(
+        ExprKind::ControlFlow(ControlFlow::Block(block)),
+        ret.clone(),
+    );
+    FunDeclare {
+        visibility: Visibility::Private,
+        ident,
+        span,
+        given: Box::new([]),
+        generics: Box::new([]),
+        fun: Fun {
+            param: param_pattern(params),
+            ret: None,
+            body: Box::new(body),
+        },
+        ty: Scheme {
+            ty: fun_type(params, ret),
+            ..scheme
+        },
+    }
+}
+struct Elaborator<'a> {
+    var_state: &'a VarState,
+    /// Dictionaries passed as parameters to the functions being elaborated,
+    /// innermost last
+    params: Vec<(Predicate, Atom)>,
+    /// Implementations whose dictionary is used
+    used: BTreeSet<usize>,
+}
+impl Elaborator<'_> {
+    fn resolve(&self, predicate: &Predicate) -> Predicate {
+        let mut predicate = predicate.clone();
+        // every type is already unified, these can't mismatch kinds
+        predicate.substitute(self.var_state.bindings()).unwrap();
+        if let Type::Var(var) = predicate.ty.clone().unalias() {
+            predicate.ty = Type::Var(var);
+        }
+        predicate
+    }
+    fn dictionary_type(&self, predicate: &Predicate) -> Type {
+        self.var_state.traits[&predicate.trait_name].dictionary_type(&predicate.ty)
+    }
+    fn param_name(predicate: &Predicate) -> Atom {
+        Atom::from(format!("{}#{}", predicate.trait_name, predicate.ty))
+    }
+    // predicates left on type variables are given to the enclosing function
+    fn find(&self, predicate: &Predicate) -> Result<Atom, (usize, Vec<Predicate>)> {
+        if let Some((_, name)) = self
+            .params
+            .iter()
+            .rev()
+            .find(|(given, _)| given == predicate)
+        {
+            return Ok(name.clone());
+        }
+        // predicates are resolved by inference, there's an implementation
+        // for every one of them
+        Err(find_impl(predicate, self.var_state).unwrap().unwrap())
+    }
+    /// Whether the predicate is implemented by a primitive operator
+    fn is_primitive(&self, predicate: &Predicate) -> bool {
+        match self.find(&self.resolve(predicate)) {
+            Ok(_) => false,
+            Err((index, given)) => {
+                self.var_state.impls[index].operator.is_some()
+                    && given.iter().all(|predicate| self.is_primitive(predicate))
+            }
+        }
+    }
+    /// Expression evaluating to the dictionary of the predicate
+    fn dictionary(&mut self, predicate: &Predicate) -> Expr<Type> {
+        let predicate = self.resolve(predicate);
+        let ty = self.dictionary_type(&predicate);
+        match self.find(&predicate) {
+            Ok(name) => var_expr(name, ty),
+            Err((index, given)) => {
+                self.used.insert(index);
+                let args: Vec<_> = given
+                    .iter()
+                    .map(|predicate| self.dictionary(predicate))
+                    .collect();
+                let params: Vec<_> = args
+                    .iter()
+                    .enumerate()
+                    .map(|(i, arg)| (Atom::from(i.to_string()), arg.ty.clone()))
+                    .collect();
+                let name = self.var_state.impls[index].dictionary.clone();
+                let callee = var_expr(name, fun_type(&params, ty.clone()));
+                call(callee, args, ty)
+            }
+        }
+    }
+    fn site(&self, span: Span) -> Option<Site> {
+        self.var_state
+            .sites
+            .iter()
+            .rev()
+            .find(|site| site.span == span)
+            .cloned()
+    }
+    /// Passes the dictionaries to a variable used at a site, methods are
+    /// taken from the dictionary instead
+    fn pass_dictionaries(&mut self, expr: Expr<Type>, site: Site) -> Expr<Type> {
+        if site.is_method {
+            let name = match &expr.expr {
+                ExprKind::Place(PlaceExpr::Var(name, _)) => name.clone(),
+                ExprKind::Place(PlaceExpr::FieldAccess(access)) => access.name.clone(),
+                _ => unreachable!(),
+            };
+            let access = FieldAccess {
+                expr: Box::new(self.dictionary(&site.given[0])),
+                name,
+            };
+            return Expr {
+                expr: ExprKind::Place(PlaceExpr::FieldAccess(access)),
+                ..expr
+            };
+        }
+        let args: Vec<_> = site
+            .given
+            .iter()
+            .map(|predicate| self.dictionary(predicate))
+            .collect();
+        let params: Vec<_> = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| (Atom::from(i.to_string()), arg.ty.clone()))
+            .collect();
+        let span = expr.span;
+        let ty = expr.ty.clone();
+        let callee = Expr {
+            ty: fun_type(&params, ty.clone()),
+            ..expr
+        };
+        Expr {
+            span,
+            ..call(callee, args, ty)
+        }
+    }
+    /// `==`, `!=`, and `++` on types implemented by a method
+    fn binary(&mut self, binary: Binary<Type>, ty: Type, span: Span) -> Expr<Type> {
+        let (trait_name, method) = match binary.kind {
+            BinaryType::Equal | BinaryType::NotEqual => (keyword!("Eq"), keyword!("equal")),
+            BinaryType::Concatenate => (keyword!("Concat"), keyword!("concat")),
+            _ => unreachable!(),
+        };
+        let predicate = Predicate {
+            trait_name,
+            ty: binary.left.ty.clone(),
+        };
+        let binary = walk_binary(self, binary);
+        if self.is_primitive(&predicate) {
+            return Expr {
+                expr: ExprKind::Binary(binary),
+                ty,
+                span,
+            };
+        }
+        let dictionary = self.dictionary(&predicate);
+        let method_ty = match &dictionary.ty {
+            Type::Cons(Cons::Record(record)) => record.fields[&method].clone(),
+            _ => unreachable!(),
+        };
+        let method = expr(
+            ExprKind::Place(PlaceExpr::FieldAccess(FieldAccess {
+                expr: Box::new(dictionary),
+                name: method,
+            })),
+            method_ty,
+        );
+        let reference = |operand: Expr<Type>| {
+            let ty = Type::Cons(Cons::Ref(MutType::Imm, Box::new(operand.ty.clone())));
+            expr(
+                ExprKind::Unary(Unary {
+                    kind: UnaryType::Ref,
+                    expr: Box::new(operand),
+                }),
+                ty,
+            )
+        };
+        let (left, right) = (*binary.left, *binary.right);
+        let elaborated = match binary.kind {
+            BinaryType::Concatenate => call(method, vec![left, right], ty),
+            BinaryType::Equal => call(method, vec![reference(left), reference(right)], ty),
+            BinaryType::NotEqual => {
+                let equal = call(method, vec![reference(left), reference(right)], ty.clone());
+                expr(
+                    ExprKind::Unary(Unary {
+                        kind: UnaryType::Not,
+                        expr: Box::new(equal),
+                    }),
+                    ty,
+                )
+            }
+            _ => unreachable!(),
+        };
+        Expr { span, ..elaborated }
+    }
+    /// Elaborates the statements of a function taking the dictionaries of
+    /// `given`, returning them along with the parameters
+    fn with_params<T>(
+        &mut self,
+        given: &[Predicate],
+        elaborate: impl FnOnce(&mut Self) -> T,
+    ) -> (Vec<(Atom, Type)>, T) {
+        let params: Vec<_> = given
+            .iter()
+            .map(|predicate| (Self::param_name(predicate), self.dictionary_type(predicate)))
+            .collect();
+        let depth = self.params.len();
+        self.params.extend(
+            given
+                .iter()
+                .cloned()
+                .zip(params.iter().map(|(name, _)| name.clone())),
+        );
+        let elaborated = elaborate(self);
+        self.params.truncate(depth);
+        (params, elaborated)
+    }
+    fn implementation(&mut self, impl_declare: ImplDeclare<Type>) -> FunDeclare<Type> {
+        let implementation = self
+            .var_state
+            .impls
+            .iter()
+            .find(|implementation| implementation.span == impl_declare.span)
+            .unwrap()
+            .clone();
+        let (params, (statements, fields)) = self.with_params(&implementation.given, |this| {
+            let mut statements = Vec::new();
+            let mut fields = Vec::new();
+            for method in impl_declare.methods.into_vec() {
+                // the method is used for the dictionary, passing what it's
+                // given when it has any
+                let value = Expr {
+                    expr: ExprKind::Place(PlaceExpr::Var(method.ident.clone(), method.span)),
+                    ty: method.ty.ty.clone(),
+                    span: method.span,
+                };
+                fields.push(Field {
+                    name: method.ident.clone(),
+                    expr: this.fold_expr(value),
+                });
+                statements.push(Statement::FunDeclare(this.fold_fun_declare(method)));
+            }
+            (statements, fields)
+        });
+        let ty = self.dictionary_type(&implementation.head);
+        let record = expr(ExprKind::Record(Collection::Collection(fields.into())), ty);
+        let block = Block {
+            statement: statements.into(),
+            expr: Some(Box::new(record)),
+            span: Span::default(),
+        };
+        dictionary_fun(
+            implementation.dictionary,
+            impl_declare.span,
+            &params,
+            block,
+            Scheme {
+                for_all: implementation.for_all,
+                given: Vec::new(),
+                ty: unit(),
+            },
+        )
+    }
+    /// Dictionary of an implementation from the prelude, its method applies
+    /// the primitive operator or compares the elements of arrays
+    fn builtin_dictionary(&mut self, implementation: &Impl) -> FunDeclare<Type> {
+        let declared = &self.var_state.traits[&implementation.head.trait_name];
+        let ty = declared.dictionary_type(&implementation.head.ty);
+        let Type::Cons(Cons::Record(record)) = &ty else {
+            unreachable!()
+        };
+        let (method, _) = &declared.methods[0];
+        let method_ty = record.fields[method].clone();
+        let Type::Cons(Cons::Fun(param, ret)) = &method_ty else {
+            unreachable!()
+        };
+        let Type::Cons(Cons::RecordTuple(OrderedAnd::NonRow(params))) = &**param else {
+            unreachable!()
+        };
+        let mut operands = params.iter().map(|(name, ty)| match ty {
+            Type::Cons(Cons::Ref(_, inner)) => expr(
+                ExprKind::Place(PlaceExpr::Deref(Box::new(var_expr(
+                    name.clone(),
+                    ty.clone(),
+                )))),
+                (**inner).clone(),
+            ),
+            ty => var_expr(name.clone(), ty.clone()),
+        });
+        let (left, right) = (operands.next().unwrap(), operands.next().unwrap());
+        let (dictionary_params, body) = match &implementation.head.ty {
+            Type::Cons(Cons::Array(element)) if !implementation.given.is_empty() => {
+                let body = elementwise_equal(left, right, (**element).clone());
+                self.with_params(&implementation.given, |this| this.fold_expr(body))
+            }
+            _ => {
+                let body = Binary {
+                    kind: implementation.operator.unwrap(),
+                    left: Box::new(left),
+                    right: Box::new(right),
+                };
+                (Vec::new(), expr(ExprKind::Binary(body), (**ret).clone()))
+            }
+        };
+        let method_declare = FunDeclare {
+            visibility: Visibility::Private,
+            ident: method.clone(),
+            span: Span::default(),
+            given: Box::new([]),
+            generics: Box::new([]),
+            fun: Fun {
+                param: param_pattern(params),
+                ret: None,
+                body: Box::new(body),
+            },
+            ty: Scheme {
+                for_all: HashSet::new(),
+                given: Vec::new(),
+                ty: method_ty.clone(),
+            },
+        };
+        let record = expr(
+            ExprKind::Record(Collection::Collection(Box::new([Field {
+                name: method.clone(),
+                expr: var_expr(method.clone(), method_ty),
+            }]))),
+            ty,
+        );
+        let block = Block {
+            statement: Box::new([Statement::FunDeclare(method_declare)]),
+            expr: Some(Box::new(record)),
+            span: Span::default(),
+        };
+        dictionary_fun(
+            implementation.dictionary.clone(),
+            Span::default(),
+            &dictionary_params,
+            block,
+            Scheme {
+                for_all: implementation.for_all.clone(),
+                given: Vec::new(),
+                ty: unit(),
+            },
+        )
+    }
+}
+impl Fold<Type> for Elaborator<'_> {
+    fn fold_statement(&mut self, statement: Statement<Type>) -> Statement<Type> {
+        match statement {
+            Statement::ImplDeclare(impl_declare) => {
+                Statement::FunDeclare(self.implementation(impl_declare))
+            }
+            statement => walk_statement(self, statement),
+        }
+    }
+    fn fold_fun_declare(&mut self, fun_declare: FunDeclare<Type>) -> FunDeclare<Type> {
+        if fun_declare.ty.given.is_empty() {
+            return walk_fun_declare(self, fun_declare);
+        }
+        let mut fun_declare = fun_declare;
+        let given = take(&mut fun_declare.ty.given);
+        let ty = fun_declare.ty.ty.clone();
+        let ident = fun_declare.ident.clone();
+        let span = fun_declare.span;
+        let visibility = fun_declare.visibility.clone();
+        let scheme = Scheme {
+            for_all: take(&mut fun_declare.ty.for_all),
+            given: Vec::new(),
+            ty: unit(),
+        };
+        // the inner function is what the body refers to when it recurses
+        fun_declare.visibility = Visibility::Private;
+        fun_declare.given = Box::new([]);
+        fun_declare.generics = Box::new([]);
+        let (params, inner) = self.with_params(&given, |this| walk_fun_declare(this, fun_declare));
+        let block = Block {
+            statement: Box::new([Statement::FunDeclare(inner)]),
+            expr: Some(Box::new(var_expr(ident.clone(), ty))),
+            span: Span::default(),
+        };
+        FunDeclare {
+            visibility,
+            ..dictionary_fun(ident, span, &params, block, scheme)
+        }
+    }
+    fn fold_module(&mut self, module: Module<Type>) -> Module<Type> {
+        let module = match module {
+            Module::Inline(statements) => Module::Inline(without_traits(statements)),
+            module => module,
+        };
+        walk_module(self, module)
+    }
+    fn fold_block(&mut self, block: Block<Type>) -> Block<Type> {
+        let block = Block {
+            statement: without_traits(block.statement),
+            ..block
+        };
+        walk_block(self, block)
+    }
+    fn fold_expr(&mut self, expr: Expr<Type>) -> Expr<Type> {
+        // module members are used through the path, the site is of the path
+        let span = match &expr.expr {
+            ExprKind::Place(PlaceExpr::Var(_, span)) => Some(*span),
+            ExprKind::Place(PlaceExpr::FieldAccess(access)) => Some(access.expr.span),
+            _ => None,
+        };
+        if let Some(site) = span.and_then(|span| self.site(span)) {
+            return self.pass_dictionaries(expr, site);
+        }
+        match expr.expr {
+            ExprKind::Binary(binary)
+                if matches!(
+                    binary.kind,
+                    BinaryType::Equal | BinaryType::NotEqual | BinaryType::Concatenate
+                ) =>
+            {
+                self.binary(binary, expr.ty, expr.span)
+            }
+            kind => walk_expr(self, Expr { expr: kind, ..expr }),
+        }
+    }
+}
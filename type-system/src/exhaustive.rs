@@ -0,0 +1,508 @@
+use crate::{
+    expr::unit,
+    ty::{cons::OrderedAnd, Subs, Substitutable, TypeWarning, Unifiable, VarState},
+    Cons, Keyed, Type, TypeError,
+};
+use hir::{
+    expr::MatchArm,
+    pattern::{ListPattern, Pattern, PatternKind},
+    Atom, Span,
+};
+use std::{
+    fmt::{self, Display, Formatter},
+    iter::{once, repeat_n},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Witness {
+    Wildcard,
+    Bool(bool),
+    Int(i128),
+    Tag(Atom, Option<Box<Witness>>),
+    Tuple(Box<[Witness]>),
+    Record(Box<[(Atom, Witness)]>),
+    Array(Box<[Witness]>),
+    ArrayWithRest(Box<[Witness]>, Box<[Witness]>),
+    Ref(Box<Witness>),
+}
+fn write_list(fmt: &mut Formatter<'_>, list: &[Witness]) -> fmt::Result {
+    for (i, witness) in list.iter().enumerate() {
+        if i != 0 {
+            write!(fmt, ", ")?;
+        }
+        write!(fmt, "{witness}")?;
+    }
+    Ok(())
+}
+impl Display for Witness {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wildcard => write!(fmt, "_")?,
+            Self::Bool(boolean) => write!(fmt, "{boolean}")?,
+            Self::Int(int) => write!(fmt, "{int}")?,
+            Self::Tag(tag, None) => write!(fmt, "@{tag}")?,
+            Self::Tag(tag, Some(witness)) => write!(fmt, "@{tag} {witness}")?,
+            Self::Tuple(tuple) => {
+                write!(fmt, "(")?;
+                write_list(fmt, tuple)?;
+                if tuple.len() == 1 {
+                    write!(fmt, ",")?;
+                }
+                write!(fmt, ")")?;
+            }
+            Self::Record(record) => {
+                write!(fmt, "(")?;
+                for (i, (name, witness)) in record.iter().enumerate() {
+                    if i != 0 {
+                        write!(fmt, ", ")?;
+                    }
+                    write!(fmt, "{name} = {witness}")?;
+                }
+                write!(fmt, ")")?;
+            }
+            Self::Array(array) => {
+                write!(fmt, "[")?;
+                write_list(fmt, array)?;
+                write!(fmt, "]")?;
+            }
+            Self::ArrayWithRest(left, right) => {
+                write!(fmt, "[")?;
+                for witness in left.iter() {
+                    write!(fmt, "{witness}, ")?;
+                }
+                write!(fmt, "*_")?;
+                for witness in right.iter() {
+                    write!(fmt, ", {witness}")?;
+                }
+                write!(fmt, "]")?;
+            }
+            Self::Ref(witness) => write!(fmt, "&{witness}")?,
+        }
+        Ok(())
+    }
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Ctor {
+    Bool(bool),
+    Int(i128),
+    Tag(Atom),
+    Tuple(usize),
+    Record(Box<[Atom]>),
+    Ref,
+    ArrayLen(usize),
+    ArrayAtLeast {
+        prefix: usize,
+        suffix: usize,
+        len: usize,
+    },
+}
+impl Ctor {
+    fn arity(&self) -> usize {
+        match self {
+            Self::Bool(_) | Self::Int(_) => 0,
+            Self::Tag(_) | Self::Ref => 1,
+            Self::Tuple(len) | Self::ArrayLen(len) => *len,
+            Self::Record(names) => names.len(),
+            Self::ArrayAtLeast { prefix, suffix, .. } => prefix + suffix,
+        }
+    }
+}
+enum Column {
+    Bool,
+    Union(Keyed),
+    Tuple(Box<[Type]>),
+    Record(Keyed),
+    Ref(Type),
+    Array(Type),
+    Opaque,
+}
+impl Column {
+    fn sub_types(&self, ctor: &Ctor) -> Vec<Type> {
+        match (self, ctor) {
+            (Self::Union(union), Ctor::Tag(tag)) => {
+                vec![union.fields.get(tag).cloned().unwrap_or_else(unit)]
+            }
+            (Self::Tuple(tuple), Ctor::Tuple(_)) => tuple.to_vec(),
+            (Self::Record(record), Ctor::Record(names)) => names
+                .iter()
+                .map(|name| record.fields.get(name).cloned().unwrap_or_else(unit))
+                .collect(),
+            (Self::Ref(ty), Ctor::Ref) => vec![ty.clone()],
+            (Self::Array(ty), ctor @ (Ctor::ArrayLen(_) | Ctor::ArrayAtLeast { .. })) => {
+                repeat_n(ty.clone(), ctor.arity()).collect()
+            }
+            (_, ctor) => repeat_n(unit(), ctor.arity()).collect(),
+        }
+    }
+    fn ctor_of(&self, pattern: &PatternKind<Type>) -> Option<Ctor> {
+        let ctor = match (self, pattern) {
+            (_, PatternKind::True) => Ctor::Bool(true),
+            (_, PatternKind::False) => Ctor::Bool(false),
+            (_, PatternKind::UInt(int)) => Ctor::Int(*int as i128),
+            (_, PatternKind::Int(int)) => Ctor::Int(*int as i128),
+            (_, PatternKind::Tag(tag)) => Ctor::Tag(tag.tag.clone()),
+            (Self::Tuple(tuple), PatternKind::Tuple(_)) => Ctor::Tuple(tuple.len()),
+            (Self::Record(record), PatternKind::Record(_)) => Ctor::Record(sorted_keys(record)),
+            (_, PatternKind::Ref(_)) => Ctor::Ref,
+            (_, PatternKind::Array(ListPattern::List(list))) => Ctor::ArrayLen(list.len()),
+            _ => return None,
+        };
+        Some(ctor)
+    }
+}
+fn sorted_keys(keyed: &Keyed) -> Box<[Atom]> {
+    let mut keys: Vec<_> = keyed.fields.keys().cloned().collect();
+    keys.sort_unstable_by(|a, b| a.as_ref().cmp(b.as_ref()));
+    keys.into()
+}
+fn is_unit(ty: &Type) -> bool {
+    match ty.clone().unalias() {
+        Type::Cons(Cons::RecordTuple(OrderedAnd::NonRow(tuple))) => tuple.is_empty(),
+        Type::Cons(Cons::Tuple(OrderedAnd::NonRow(tuple))) => tuple.is_empty(),
+        Type::Cons(Cons::Record(record)) => record.fields.is_empty() && record.rest.is_none(),
+        _ => false,
+    }
+}
+#[derive(Debug, Clone, Copy)]
+enum Pat<'a> {
+    Wild,
+    Pattern(&'a Pattern<Type>),
+}
+impl<'a> Pat<'a> {
+    fn new(pattern: &'a Pattern<Type>) -> Self {
+        match pattern.pattern {
+            PatternKind::Discard | PatternKind::Var(_) | PatternKind::Param(_) => Self::Wild,
+            PatternKind::Annotated(ref annotated) => Self::new(&annotated.pattern),
+            _ => Self::Pattern(pattern),
+        }
+    }
+    fn head(self) -> Option<&'a PatternKind<Type>> {
+        match self {
+            Self::Wild => None,
+            Self::Pattern(pattern) => Some(&pattern.pattern),
+        }
+    }
+    fn specialize(self, ctor: &Ctor) -> Option<Vec<Pat<'a>>> {
+        let wild = |len| repeat_n(Pat::Wild, len);
+        let pattern = match self {
+            Self::Wild => return Some(wild(ctor.arity()).collect()),
+            Self::Pattern(pattern) => &pattern.pattern,
+        };
+        let list = |list: &'a ListPattern<Type>, len: usize| match list {
+            ListPattern::List(list) if list.len() == len => {
+                Some(list.iter().map(Pat::new).collect())
+            }
+            ListPattern::ListWithRest(list)
+                if list.left.len() + list.right.len() <= len && !list.rest.is_refutable() =>
+            {
+                Some(
+                    list.left
+                        .iter()
+                        .map(Pat::new)
+                        .chain(wild(len - list.left.len() - list.right.len()))
+                        .chain(list.right.iter().map(Pat::new))
+                        .collect(),
+                )
+            }
+            _ => None,
+        };
+        match (pattern, ctor) {
+            (PatternKind::True, Ctor::Bool(true)) | (PatternKind::False, Ctor::Bool(false)) => {
+                Some(Vec::new())
+            }
+            (PatternKind::UInt(int), Ctor::Int(ctor)) if *int as i128 == *ctor => Some(Vec::new()),
+            (PatternKind::Int(int), Ctor::Int(ctor)) if *int as i128 == *ctor => Some(Vec::new()),
+            (PatternKind::Tag(tag), Ctor::Tag(ctor)) if tag.tag == *ctor => {
+                let pattern = match &tag.pattern {
+                    Some(pattern) => Pat::new(pattern),
+                    None => Pat::Wild,
+                };
+                Some(vec![pattern])
+            }
+            (PatternKind::Tuple(tuple), Ctor::Tuple(len)) => list(tuple, *len),
+            (PatternKind::Record(record), Ctor::Record(names)) => {
+                if record.rest.as_ref().is_some_and(|rest| rest.is_refutable()) {
+                    return None;
+                }
+                Some(
+                    names
+                        .iter()
+                        .map(|name| record.fields.get(name).map_or(Pat::Wild, Pat::new))
+                        .collect(),
+                )
+            }
+            (PatternKind::Ref(pattern), Ctor::Ref) => Some(vec![Pat::new(pattern)]),
+            (PatternKind::Array(array), Ctor::ArrayLen(len)) => list(array, *len),
+            (
+                PatternKind::Array(ListPattern::ListWithRest(list)),
+                Ctor::ArrayAtLeast { prefix, suffix, .. },
+            ) if !list.rest.is_refutable() => Some(
+                list.left
+                    .iter()
+                    .map(Pat::new)
+                    .chain(wild(prefix - list.left.len()))
+                    .chain(wild(suffix - list.right.len()))
+                    .chain(list.right.iter().map(Pat::new))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+}
+fn array_ctors<'a>(heads: impl IntoIterator<Item = &'a PatternKind<Type>>) -> Vec<Ctor> {
+    let mut fixed = 0;
+    let mut prefix = 0;
+    let mut suffix = 0;
+    for head in heads {
+        match head {
+            PatternKind::Array(ListPattern::List(list)) => fixed = fixed.max(list.len() + 1),
+            PatternKind::Array(ListPattern::ListWithRest(list)) => {
+                prefix = prefix.max(list.left.len());
+                suffix = suffix.max(list.right.len());
+            }
+            _ => (),
+        }
+    }
+    let len = fixed.max(prefix + suffix);
+    (0..len)
+        .map(Ctor::ArrayLen)
+        .chain(once(Ctor::ArrayAtLeast {
+            prefix,
+            suffix,
+            len,
+        }))
+        .collect()
+}
+struct Checker<'a> {
+    var_state: &'a mut VarState,
+    // open unions are closed when nothing else could match other tags, this
+    // is only done while checking exhaustiveness
+    close_union: bool,
+}
+impl<'a> Checker<'a> {
+    fn column(&mut self, ty: &Type) -> Column {
+        let mut ty = ty.clone();
+        if ty.substitute(self.var_state.bindings()).is_err() {
+            return Column::Opaque;
+        }
+        match ty.unalias() {
+            Type::Cons(Cons::Bool) => Column::Bool,
+            Type::Cons(Cons::Union(union)) => Column::Union(union),
+            Type::Cons(Cons::Tuple(OrderedAnd::NonRow(tuple))) => Column::Tuple(tuple),
+            Type::Cons(Cons::Record(record)) => Column::Record(record),
+            Type::Cons(Cons::Ref(_, ty)) => Column::Ref(*ty),
+            Type::Cons(Cons::Array(ty)) => Column::Array(*ty),
+            _ => Column::Opaque,
+        }
+    }
+    fn complete_ctors(
+        &mut self,
+        column: &mut Column,
+        heads: &[&PatternKind<Type>],
+        has_default: bool,
+    ) -> Option<Vec<Ctor>> {
+        if heads.is_empty() {
+            return None;
+        }
+        let ctors = match column {
+            Column::Bool => vec![Ctor::Bool(true), Ctor::Bool(false)],
+            Column::Union(union) => {
+                if let Some(rest) = union.rest.clone() {
+                    if !self.close_union || has_default {
+                        return None;
+                    }
+                    let closed = Type::Cons(Cons::Union(Keyed::default()));
+                    Type::Var(rest)
+                        .unify_with(closed, &mut Subs::new(), self.var_state)
+                        .ok()?;
+                    union.rest = None;
+                }
+                sorted_keys(union).iter().cloned().map(Ctor::Tag).collect()
+            }
+            Column::Tuple(tuple) => vec![Ctor::Tuple(tuple.len())],
+            Column::Record(record) => vec![Ctor::Record(sorted_keys(record))],
+            Column::Ref(_) => vec![Ctor::Ref],
+            Column::Array(_) => array_ctors(heads.iter().copied()),
+            Column::Opaque => return None,
+        };
+        Some(ctors)
+    }
+    fn witness(column: &Column, ctor: &Ctor, witnesses: Vec<Witness>) -> Vec<Witness> {
+        let mut sub = witnesses;
+        let rest = sub.split_off(ctor.arity());
+        let witness = match ctor {
+            Ctor::Bool(boolean) => Witness::Bool(*boolean),
+            Ctor::Int(int) => Witness::Int(*int),
+            Ctor::Tag(tag) => {
+                let payload = sub.pop().unwrap();
+                let unit_payload = match column {
+                    Column::Union(union) => union.fields.get(tag).is_none_or(is_unit),
+                    _ => true,
+                };
+                if unit_payload && payload == Witness::Wildcard {
+                    Witness::Tag(tag.clone(), None)
+                } else {
+                    Witness::Tag(tag.clone(), Some(Box::new(payload)))
+                }
+            }
+            Ctor::Tuple(_) => Witness::Tuple(sub.into()),
+            Ctor::Record(names) => Witness::Record(names.iter().cloned().zip(sub).collect()),
+            Ctor::Ref => Witness::Ref(Box::new(sub.pop().unwrap())),
+            Ctor::ArrayLen(_) => Witness::Array(sub.into()),
+            Ctor::ArrayAtLeast {
+                prefix,
+                suffix,
+                len,
+            } => {
+                let right = sub.split_off(*prefix);
+                let mut left = sub;
+                left.extend(repeat_n(Witness::Wildcard, len - prefix - suffix));
+                Witness::ArrayWithRest(left.into(), right.into())
+            }
+        };
+        once(witness).chain(rest).collect()
+    }
+    // returns the values matched by `row` that aren't matched by any row of
+    // `matrix`, an empty result means `row` is useless
+    fn useful(&mut self, matrix: &[Vec<Pat>], row: &[Pat], tys: &[Type]) -> Vec<Vec<Witness>> {
+        let Some((first, row_rest)) = row.split_first() else {
+            return if matrix.is_empty() {
+                vec![Vec::new()]
+            } else {
+                Vec::new()
+            };
+        };
+        let mut column = self.column(&tys[0]);
+        let heads: Vec<_> = matrix.iter().filter_map(|row| row[0].head()).collect();
+        let has_default = heads.len() != matrix.len();
+        let ctors = match first.head() {
+            Some(PatternKind::Array(ListPattern::ListWithRest(_))) => {
+                let heads = heads.iter().copied().chain(first.head());
+                array_ctors(heads)
+                    .into_iter()
+                    .filter(|ctor| first.specialize(ctor).is_some())
+                    .collect()
+            }
+            Some(head) => column.ctor_of(head).into_iter().collect(),
+            None => match self.complete_ctors(&mut column, &heads, has_default) {
+                Some(ctors) => ctors,
+                None => {
+                    let default: Vec<_> = matrix
+                        .iter()
+                        .filter(|row| row[0].head().is_none())
+                        .map(|row| row[1..].to_vec())
+                        .collect();
+                    return self
+                        .useful(&default, row_rest, &tys[1..])
+                        .into_iter()
+                        .map(|witnesses| once(Witness::Wildcard).chain(witnesses).collect())
+                        .collect();
+                }
+            },
+        };
+        let mut result = Vec::new();
+        for ctor in ctors {
+            let specialized: Vec<_> = matrix
+                .iter()
+                .filter_map(|row| {
+                    let mut new_row = row[0].specialize(&ctor)?;
+                    new_row.extend_from_slice(&row[1..]);
+                    Some(new_row)
+                })
+                .collect();
+            let Some(mut new_row) = first.specialize(&ctor) else {
+                continue;
+            };
+            new_row.extend_from_slice(row_rest);
+            let mut new_tys = column.sub_types(&ctor);
+            new_tys.extend_from_slice(&tys[1..]);
+            for witnesses in self.useful(&specialized, &new_row, &new_tys) {
+                result.push(Self::witness(&column, &ctor, witnesses));
+            }
+        }
+        result
+    }
+}
+pub(super) fn check_match(
+    arm: &[MatchArm<Type>],
+    ty: &Type,
+    span: Span,
+    var_state: &mut VarState,
+) -> Result<(), TypeError> {
+    let matrix: Vec<_> = arm.iter().map(|arm| vec![Pat::new(&arm.pattern)]).collect();
+    let tys = [ty.clone()];
+    let mut checker = Checker {
+        var_state,
+        close_union: true,
+    };
+    let missing = checker.useful(&matrix, &[Pat::Wild], &tys);
+    if !missing.is_empty() {
+        let mut witnesses: Vec<Witness> = Vec::new();
+        for witness in missing.into_iter().flatten() {
+            if !witnesses.contains(&witness) {
+                witnesses.push(witness);
+            }
+        }
+        return Err(TypeError::NonExhaustive(witnesses.into(), span));
+    }
+    checker.close_union = false;
+    for (i, row) in matrix.iter().enumerate() {
+        if checker.useful(&matrix[..i], row, &tys).is_empty() {
+            checker
+                .var_state
+                .warn(TypeWarning::UnreachableArm(i, arm[i].pattern.span));
+        }
+    }
+    Ok(())
+}
+#[cfg(test)]
+mod test {
+    use crate::{test_infer_with_warnings, TypeError, TypeWarning};
+    use parser::{expr_parser, EasyParser};
+
+    fn infer_warnings(src: &str) -> Result<Vec<TypeWarning>, TypeError> {
+        let (expr, rest) = expr_parser().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        test_infer_with_warnings(expr).map(|(_, warnings)| warnings)
+    }
+    fn missing(src: &str) -> String {
+        match infer_warnings(src) {
+            Err(err @ TypeError::NonExhaustive(_, _)) => err.to_string(),
+            result => panic!("expected non-exhaustive match, found {result:?}"),
+        }
+    }
+    #[test]
+    fn missing_tag() {
+        let src = "{ color = if true { @red } else { @blue }; match color { @red => 1 } }";
+        assert_eq!(missing(src), "non-exhaustive match, missing `@blue`");
+    }
+    #[test]
+    fn missing_array_length() {
+        let src = "(arr) => match arr { [] => 1, [a] => 2 }";
+        assert_eq!(missing(src), "non-exhaustive match, missing `[_, _, *_]`");
+        let err = infer_warnings(src).unwrap_err();
+        assert_eq!(err.span().map(|span| span.range()), Some(15..18));
+    }
+    #[test]
+    fn missing_nested() {
+        let src = "(x) => match x { (a = @some v, b = true) => 1, (a = @none, b = _) => 2 }";
+        assert_eq!(
+            missing(src),
+            "non-exhaustive match, missing `(a = @some _, b = false)`",
+        );
+    }
+    #[test]
+    fn closed_by_match() {
+        let src = "(color) => match color { @red => 1, @green => 2 }";
+        assert_eq!(infer_warnings(src), Ok(Vec::new()));
+    }
+    #[test]
+    fn unreachable_arm() {
+        let src = "(x) => match x { (a, true) => 1, (_, false) => 2, (a, b) => 3 }";
+        let warnings = infer_warnings(src).unwrap();
+        let [TypeWarning::UnreachableArm(2, span)] = warnings[..] else {
+            panic!("expected an unreachable arm, found {warnings:?}");
+        };
+        assert_eq!(span.range(), 50..56);
+    }
+}
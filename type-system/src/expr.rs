@@ -1,28 +1,49 @@
 use crate::{
+    annotation::{annotation_type, declare_aliases, unify_annotation},
+    diverge::Divergence,
+    exhaustive::check_match,
+    module::{infer_module_declare, infer_module_member, module_path},
+    newtype::{declare_constructor, declare_newtypes, field_type},
     pattern::InferablePattern,
     substitute_hir,
+    traits::{
+        bound_predicate, declare_methods, declare_traits, generalize_given, infer_impl,
+        instantiate_use,
+    },
     ty::{
         cons::OrderedAnd,
         cons::{Cons, Keyed},
-        Env, MutType, Subs, Substitutable, Type, TypeError, Unifiable, Var, VarState,
+        num::NumBound,
+        Constraint, Env, FreeVars, Kind, KindedVar, MutType, Predicate, Scheme, SchemeMut, Subs,
+        Substitutable, Type, TypeError, TypeWarning, Unifiable, Var, VarState,
     },
     Typed,
 };
 use hir::{
     expr::{
-        Arg, Assign, Binary, BinaryType, Block, Bound, Call, Collection, ControlFlow, Element,
-        ElementKind, Expr, ExprKind, Field, FieldAccess, If, Index, Jump, Literal, PlaceExpr,
-        Range, Slice, Tag, Unary, UnaryType, WithSplat,
+        Annotated, Arg, Assign, Binary, BinaryType, Block, Bound, Call, Collection, ControlFlow,
+        Element, ElementKind, Expr, ExprKind, Field, FieldAccess, For, Fun, If, Index, Jump,
+        Literal, Match, MatchArm, PlaceExpr, Range, Slice, Str, Tag, Unary, UnaryType, While,
+        WithSplat,
     },
     keyword,
-    statement::{Declare, Statement},
-    Atom,
+    pattern::{Pattern, PatternKind},
+    statement::{Declare, FunDeclare, ImplDeclare, Statement},
+    visit::{walk_control_flow, walk_jump, walk_pattern, Visit},
+    Atom, PrettyPrintType, Span, TraverseType,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    iter::once,
 };
-use std::{collections::HashMap, iter::once};
 
 pub(super) fn unit() -> Type {
     Type::Cons(Cons::RecordTuple(OrderedAnd::NonRow(vec![].into())))
 }
+/// A number whose representation must hold `bound`
+pub(super) fn num(bound: NumBound, var_state: &mut VarState) -> Type {
+    Type::Cons(Cons::Num(var_state.new_num(bound)))
+}
 pub(super) trait Inferable {
     type TypedSelf;
     fn infer(
@@ -49,21 +70,132 @@ pub(super) trait Inferable {
         self.infer(subs, var_state, env).map(|typed| (None, typed))
     }
 }
+/// Mutability of the variable the place expression is rooted at, `None` for
+/// other expressions
+fn root_mutability(expr: &Expr<()>, env: &Env) -> Option<bool> {
+    match &expr.expr {
+        ExprKind::Place(place) => place.var().and_then(|var| env.get_mut(Var::new_bare(var))),
+        _ => None,
+    }
+}
+/// Whether the place can be mutated while a duplicate of its value is still
+/// around, either with its variable or through a mutable reference
+fn is_mutable_place(
+    mut_var: Option<Var>,
+    root_mutability: Option<bool>,
+    var_state: &VarState,
+) -> Result<bool, TypeError> {
+    match mut_var {
+        Some(var) => {
+            let mut mutability = MutType::Var(var);
+            mutability.substitute(var_state.bindings())?;
+            Ok(mutability == MutType::Mut)
+        }
+        None => Ok(root_mutability == Some(true)),
+    }
+}
+/// Requires the value to be implicitly copyable
+fn require_copy(
+    ty: &mut Type,
+    value: &mut impl TraverseType<Type = Type>,
+    subs: &mut Subs,
+    var_state: &mut VarState,
+) -> Result<(), TypeError> {
+    let mut copy_subs = Subs::new();
+    var_state.constrain(&mut copy_subs, ty, Constraint::Copy)?;
+    ty.substitute(&copy_subs)?;
+    substitute_hir(value, &copy_subs)?;
+    subs.compose_with(copy_subs)?;
+    Ok(())
+}
+/// Binding a place to a pattern duplicates its value into every variable not
+/// bound by reference, these must be copyable when either side is mutable
+fn require_copy_bindings(
+    pattern: &Pattern<Type>,
+    mutable_place: bool,
+    var_state: &mut VarState,
+) -> Result<Subs, TypeError> {
+    struct Bindings(Vec<(bool, Type)>);
+    impl Visit<Type> for Bindings {
+        fn visit_pattern(&mut self, pattern: &Pattern<Type>) {
+            match &pattern.pattern {
+                PatternKind::Var(var) if !var.bind_to_ref => {
+                    self.0.push((var.mutable, pattern.ty.clone()));
+                }
+                _ => walk_pattern(self, pattern),
+            }
+        }
+    }
+    let mut bindings = Bindings(Vec::new());
+    bindings.visit_pattern(pattern);
+    let mut copy_subs = Subs::new();
+    for (mutable, mut ty) in bindings.0 {
+        if mutable || mutable_place {
+            ty.substitute(&copy_subs)?;
+            var_state.constrain(&mut copy_subs, &ty, Constraint::Copy)?;
+        }
+    }
+    Ok(copy_subs)
+}
+/// Declares the target of `return` or `break`, jumping to it with an operand
+/// of type `ty`
+fn declare_jump(env: &mut Env, keyword: Atom, ty: Type) {
+    env.insert(
+        Var::new_bare(keyword),
+        SchemeMut {
+            is_mut: false,
+            is_method: false,
+            scheme: Scheme {
+                for_all: HashSet::new(),
+                given: Vec::new(),
+                ty,
+            },
+        },
+    );
+}
+/// Whether the type is `Never` once its variables are substituted
+pub(super) fn is_never(ty: &Type, var_state: &VarState) -> Result<bool, TypeError> {
+    let mut ty = ty.clone();
+    ty.substitute(var_state.bindings())?;
+    Ok(ty.unalias() == Type::Cons(Cons::Never))
+}
+/// Mutability of references to a place, `None` when it may be either. Places
+/// behind a reference are as mutable as the reference, others are as mutable
+/// as their variable.
+fn place_mutability(mut_var: Option<Var>, immutable: bool) -> Option<MutType> {
+    match mut_var {
+        Some(var) => Some(MutType::Var(var)),
+        None if immutable => Some(MutType::Imm),
+        None => None,
+    }
+}
 impl Inferable for Literal {
     type TypedSelf = Literal;
 
     fn infer(
         self,
         _: &mut Subs,
-        _: &mut VarState,
+        var_state: &mut VarState,
         _: &Env,
     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
-        let cons = match self {
-            Literal::True | Literal::False => Cons::Bool,
-            Literal::UInt(_) | Literal::Float(_) => Cons::Num,
+        let ty = match self {
+            Literal::True | Literal::False => Type::Cons(Cons::Bool),
+            Literal::Char(_) => Type::Cons(Cons::Char),
+            Literal::UInt(_) | Literal::Float(_) => num(NumBound::literal(&self), var_state),
         };
+        Ok(Typed { ty, value: self })
+    }
+}
+impl Inferable for Str {
+    type TypedSelf = Str;
+
+    fn infer(self, _: &mut Subs, _: &mut VarState, _: &Env) -> Result<Typed<Str>, TypeError> {
+        // escapes may write any byte
+        if str::from_utf8(&self.0).is_err() {
+            return Err(TypeError::InvalidUtf8(self));
+        }
         Ok(Typed {
-            ty: Type::Cons(cons),
+            ty: Type::Cons(Cons::Str),
             value: self,
         })
     }
@@ -82,7 +214,7 @@ impl Inferable for Atom {
                 ty: scheme.instantiate(var_state)?,
                 value: self,
             }),
-            None => Err(TypeError::UnboundVar),
+            None => Err(TypeError::UnboundVar(self)),
         }
     }
 }
@@ -95,10 +227,30 @@ impl Inferable for FieldAccess<()> {
         var_state: &mut VarState,
         env: &Env,
     ) -> Result<(Option<Var>, Typed<Self::TypedSelf>), TypeError> {
+        if let Some(module) = module_path(&self.expr, env)? {
+            let (ty, value) = infer_module_member(self, module, var_state, env)?;
+            return Ok((None, Typed { ty, value }));
+        }
         let name = self.name;
         let (mut_var, typed_expr) = self.expr.infer_with_mut(subs, var_state, env)?;
         let operand_ty = typed_expr.ty;
         let mut operand_expr = typed_expr.value;
+        // fields of a newtype are only found once its type is known
+        let mut known_ty = operand_ty.clone();
+        known_ty.substitute(var_state.bindings())?;
+        if let Type::Cons(Cons::Newtype(newtype)) = known_ty.unalias() {
+            let ty = field_type(&newtype, &name, env, var_state)?;
+            return Ok((
+                mut_var,
+                Typed {
+                    ty,
+                    value: FieldAccess {
+                        expr: Box::new(operand_expr),
+                        name,
+                    },
+                },
+            ));
+        }
         let var = var_state.new_var();
         let mut operand_subs = Subs::new();
         operand_ty.unify_with(
@@ -152,7 +304,11 @@ impl Inferable for Index<()> {
         let index_ty = typed_index.ty;
         let mut index_expr = typed_index.value;
         let mut index_subs = Subs::new();
-        index_ty.unify_with(Type::Cons(Cons::Num), &mut index_subs, var_state)?;
+        index_ty.unify_with(
+            num(NumBound::INTEGRAL, var_state),
+            &mut index_subs,
+            var_state,
+        )?;
         substitute_hir(&mut index_expr, &index_subs)?;
         subs.compose_with(index_subs)?;
         Ok((
@@ -213,7 +369,17 @@ impl Inferable for PlaceExpr<()> {
         env: &Env,
     ) -> Result<(Option<Var>, Typed<PlaceExpr<Type>>), TypeError> {
         let mut_typed = match self {
-            Self::Var(var) => (None, var.infer(subs, var_state, env)?.map(PlaceExpr::Var)),
+            Self::Var(var, span) => {
+                let scheme = env
+                    .get_scheme_mut(&Var::new_bare(var.clone()))
+                    .ok_or_else(|| TypeError::UnboundVar(var.clone()))?
+                    .clone();
+                let typed = Typed {
+                    ty: instantiate_use(span, scheme, var_state)?,
+                    value: PlaceExpr::Var(var, span),
+                };
+                (None, typed)
+            }
             Self::FieldAccess(expr) => {
                 let (mut_var, typed) = expr.infer_with_mut(subs, var_state, env)?;
                 (mut_var, typed.map(PlaceExpr::FieldAccess))
@@ -242,7 +408,7 @@ impl Inferable for PlaceExpr<()> {
                 (
                     mut_var,
                     Typed {
-                        ty: Type::Cons(Cons::Num),
+                        ty: num(NumBound::INTEGRAL, var_state),
                         value: PlaceExpr::Len(Box::new(operand_expr)),
                     },
                 )
@@ -324,13 +490,16 @@ impl Inferable for Option<Bound<()>> {
         var_state: &mut VarState,
         env: &Env,
     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
+        // the type of the bound, an absent bound may be of any integer
+        let mut ty = num(NumBound::INTEGRAL, var_state);
         let expr = match self {
             Some(bound) => {
                 let typed = bound.expr.infer(subs, var_state, env)?;
                 let bound_ty = typed.ty;
                 let mut bound_expr = typed.value;
                 let mut bound_subs = Subs::new();
-                bound_ty.unify_with(Type::Cons(Cons::Num), &mut bound_subs, var_state)?;
+                bound_ty.unify_with(ty.clone(), &mut bound_subs, var_state)?;
+                ty.substitute(&bound_subs)?;
                 substitute_hir(&mut bound_expr, &bound_subs)?;
                 subs.compose_with(bound_subs)?;
                 Some(Bound {
@@ -340,10 +509,7 @@ impl Inferable for Option<Bound<()>> {
             }
             None => None,
         };
-        Ok(Typed {
-            ty: unit(),
-            value: expr,
-        })
+        Ok(Typed { ty, value: expr })
     }
 }
 impl Inferable for Range<()> {
@@ -355,10 +521,22 @@ impl Inferable for Range<()> {
         var_state: &mut VarState,
         env: &Env,
     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
-        let left = self.left.infer(subs, var_state, env)?.value;
-        let right = self.right.infer(subs, var_state, env)?.value;
+        let left = self.left.infer(subs, var_state, env)?;
+        let mut elem_ty = left.ty;
+        let mut left = left.value;
+        let right = self.right.infer(subs, var_state, env)?;
+        let right_ty = right.ty;
+        let mut right = right.value;
+        let mut range_subs = Subs::new();
+        elem_ty
+            .clone()
+            .unify_with(right_ty, &mut range_subs, var_state)?;
+        elem_ty.substitute(&range_subs)?;
+        substitute_hir(&mut left, &range_subs)?;
+        substitute_hir(&mut right, &range_subs)?;
+        subs.compose_with(range_subs)?;
         Ok(Typed {
-            ty: Type::Cons(Cons::Array(Box::new(Type::Cons(Cons::Num)))),
+            ty: Type::Cons(Cons::Array(Box::new(elem_ty))),
             value: (Range { left, right }),
         })
     }
@@ -581,20 +759,34 @@ impl Inferable for Unary<()> {
         var_state: &mut VarState,
         env: &Env,
     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
+        let root_mutability = root_mutability(&self.expr, env);
         let (mut_var, typed) = self.expr.infer_with_mut(subs, var_state, env)?;
         let typed = match self.kind {
-            // TODO: implement error when cloning function and mutable reference
-            // Or maybe not, constraining Clonables may better be implemented
-            // by typeclasses or trait, which we don't have yet
-            kind @ (UnaryType::Move | UnaryType::Clone) => typed.map(|expr| Unary {
-                kind,
+            UnaryType::Move => typed.map(|expr| Unary {
+                kind: UnaryType::Move,
                 expr: Box::new(expr),
             }),
+            UnaryType::Clone => {
+                let mut operand_ty = typed.ty;
+                let mut operand_expr = typed.value;
+                let mut clone_subs = Subs::new();
+                var_state.constrain(&mut clone_subs, &operand_ty, Constraint::Clone)?;
+                operand_ty.substitute(&clone_subs)?;
+                substitute_hir(&mut operand_expr, &clone_subs)?;
+                subs.compose_with(clone_subs)?;
+                Typed {
+                    ty: operand_ty,
+                    value: Unary {
+                        kind: UnaryType::Clone,
+                        expr: Box::new(operand_expr),
+                    },
+                }
+            }
             kind @ (UnaryType::Minus | UnaryType::Not) => {
                 let operand_ty = typed.ty;
                 let mut operand_expr = typed.value;
                 let ty = match kind {
-                    UnaryType::Minus => Type::Cons(Cons::Num),
+                    UnaryType::Minus => num(NumBound::NEGATIVE, var_state),
                     UnaryType::Not => Type::Cons(Cons::Bool),
                     _ => unreachable!(),
                 };
@@ -613,9 +805,10 @@ impl Inferable for Unary<()> {
             UnaryType::Ref => {
                 let operand_ty = typed.ty;
                 let operand_expr = typed.value;
-                let var = mut_var.unwrap_or_else(|| var_state.new_var());
+                let mutability = place_mutability(mut_var, root_mutability == Some(false))
+                    .unwrap_or_else(|| MutType::Var(var_state.new_var()));
                 Typed {
-                    ty: Type::Cons(Cons::Ref(MutType::Var(var), Box::new(operand_ty))),
+                    ty: Type::Cons(Cons::Ref(mutability, Box::new(operand_ty))),
                     value: Unary {
                         kind: UnaryType::Ref,
                         expr: Box::new(operand_expr),
@@ -643,22 +836,39 @@ impl Inferable for Binary<()> {
         let mut right_expr = right.value;
         let (op_type, mut return_type) = match self.kind {
             BinaryType::Concatenate => {
-                let var = var_state.new_var();
-                let ty = Type::Cons(Cons::Array(Box::new(Type::Var(var))));
+                let ty = Type::Var(var_state.new_var());
+                var_state.wanted.push(Predicate {
+                    trait_name: keyword!("Concat"),
+                    ty: ty.clone(),
+                });
+                (ty.clone(), ty)
+            }
+            BinaryType::Div => {
+                let ty = num(NumBound::FRACTIONAL, var_state);
                 (ty.clone(), ty)
             }
             BinaryType::Add
             | BinaryType::Sub
             | BinaryType::Multiply
-            | BinaryType::Div
             | BinaryType::FloorDiv
-            | BinaryType::Mod => (Type::Cons(Cons::Num), Type::Cons(Cons::Num)),
-            BinaryType::Equal
-            | BinaryType::NotEqual
-            | BinaryType::Greater
+            | BinaryType::Mod => {
+                let ty = num(NumBound::default(), var_state);
+                (ty.clone(), ty)
+            }
+            BinaryType::Equal | BinaryType::NotEqual => {
+                let ty = Type::Var(var_state.new_var());
+                var_state.wanted.push(Predicate {
+                    trait_name: keyword!("Eq"),
+                    ty: ty.clone(),
+                });
+                (ty, Type::Cons(Cons::Bool))
+            }
+            BinaryType::Greater
             | BinaryType::GreaterEqual
             | BinaryType::Less
-            | BinaryType::LessEqual => (Type::Cons(Cons::Num), Type::Cons(Cons::Bool)),
+            | BinaryType::LessEqual => {
+                (num(NumBound::default(), var_state), Type::Cons(Cons::Bool))
+            }
             BinaryType::And | BinaryType::Or | BinaryType::LazyAnd | BinaryType::LazyOr => {
                 (Type::Cons(Cons::Bool), Type::Cons(Cons::Bool))
             }
@@ -683,96 +893,115 @@ impl Inferable for Binary<()> {
         })
     }
 }
-// impl Inferable for Fun<()> {
-//     type TypedSelf = Fun<Type>;
+impl Inferable for Fun<()> {
+    type TypedSelf = Fun<Type>;
 
-//     fn infer(
-//         self,
-//         subs: &mut Subs,
-//         var_state: &mut VarState,
-//         env: &Env,
-//     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
-//         // TODO: handle `ref` parameters
-//         let param_map: HashMap<_, _> = self
-//             .param
-//             .iter()
-//             .map(|var| {
-//                 (
-//                     var.ident.clone(),
-//                     (var_state.new_named(var.ident.clone()), var.clone()),
-//                 )
-//             })
-//             .collect();
-//         let mut env = env.clone();
-//         env.extend(param_map.iter().map(|(var, (new_var, var_hir))| {
-//             (
-//                 Var::new_bare(var.clone()),
-//                 SchemeMut {
-//                     is_mut: var_hir.mutable,
-//                     scheme: Scheme {
-//                         for_all: HashSet::new(),
-//                         ty: Type::Var(new_var.clone()),
-//                     },
-//                 },
-//             )
-//         }));
-//         let return_var = var_state.new_var();
-//         env.insert(
-//             Var::new_bare(keyword!("return")),
-//             SchemeMut {
-//                 is_mut: false,
-//                 scheme: Scheme {
-//                     for_all: HashSet::new(),
-//                     ty: Type::Var(return_var.clone()),
-//                 },
-//             },
-//         );
-//         let mut param_ty = Type::Cons(Cons::RecordTuple(OrderedAnd::NonRow(
-//             self.param
-//                 .iter()
-//                 .map(|var| {
-//                     (
-//                         var.ident.clone(),
-//                         Type::Var(param_map.get(&var.ident).unwrap().0.clone()),
-//                     )
-//                 })
-//                 .collect::<Vec<_>>()
-//                 .into(),
-//         )));
-//         let mut body_subs = Subs::new();
-//         let body = self.body.infer(&mut body_subs, var_state, &env)?;
-//         let mut return_ty = Type::Var(return_var);
-//         return_ty.substitute(&body_subs)?;
-//         param_ty.substitute(&body_subs)?;
-//         let param: Vec<_> = self.param.into();
-//         let typed_param = param
-//             .into_iter()
-//             .map(|var| {
-//                 let mut ty = Type::Var(param_map.get(&var.ident).unwrap().0.clone());
-//                 ty.substitute(&body_subs)?;
-//                 Ok(pattern::Var {
-//                     ident: var.ident,
-//                     mutable: var.mutable,
-//                     bind_to_ref: var.bind_to_ref,
-//                     ty,
-//                 })
-//             })
-//             .collect::<Result<Vec<_>, TypeError>>()?;
-//         subs.compose_with(body_subs)?;
-//         let mut body_ty = body.ty;
-//         let mut body_return_subs = Subs::new();
-//         return_ty.unify_with(body_ty.clone(), &mut body_return_subs, var_state)?;
-//         body_ty.substitute(&body_return_subs)?;
-//         subs.compose_with(body_return_subs)?;
-//         Ok(Typed {
-//             ty: Type::Cons(Cons::Fun(Box::new(param_ty), Box::new(body_ty))),
-//             value: Fun {
-//                 param: typed_param.into(),
-//                 body: Box::new(body.value),
-//             },
-//         })
-//     }
-// }
+    fn infer(
+        self,
+        subs: &mut Subs,
+        var_state: &mut VarState,
+        env: &Env,
+    ) -> Result<Typed<Self::TypedSelf>, TypeError> {
+        infer_fun(self, None, subs, var_state, env)
+    }
+}
+/// Infers a function, an `expected` function type is unified with the
+/// parameters and return type before the body is inferred
+pub(super) fn infer_fun(
+    fun: Fun<()>,
+    expected: Option<Type>,
+    subs: &mut Subs,
+    var_state: &mut VarState,
+    env: &Env,
+) -> Result<Typed<Fun<Type>>, TypeError> {
+    let mut env = env.clone();
+    let return_var = var_state.new_var();
+    declare_jump(&mut env, keyword!("return"), Type::Var(return_var.clone()));
+    // loops outside the function can't be broken from within
+    env.remove(Var::new_bare(keyword!("break")));
+    let typed_param = fun.param.infer(None, var_state, &mut env)?;
+    let mut param_ty = typed_param.ty;
+    let mut param_pattern = typed_param.value;
+    if let Some(expected) = expected {
+        Type::Cons(Cons::Fun(
+            Box::new(param_ty.clone()),
+            Box::new(Type::Var(return_var.clone())),
+        ))
+        .unify_with(expected, &mut Subs::new(), var_state)?;
+    }
+    let ret_ty = match &fun.ret {
+        Some(ret) => {
+            let ret_ty = annotation_type(ret, var_state, &mut env)?;
+            Type::Var(return_var.clone()).unify_with(
+                ret_ty.clone(),
+                &mut Subs::new(),
+                var_state,
+            )?;
+            Some(ret_ty)
+        }
+        None => None,
+    };
+    let typed_body = fun.body.infer(subs, var_state, &env)?;
+    let mut body_ty = typed_body.ty;
+    let mut body_expr = typed_body.value;
+    let mut body_subs = Subs::new();
+    match ret_ty {
+        Some(ret_ty) => unify_annotation(body_ty.clone(), ret_ty, &mut body_subs, var_state)?,
+        None => {
+            body_ty
+                .clone()
+                .unify_with(Type::Var(return_var.clone()), &mut body_subs, var_state)?
+        }
+    }
+    param_ty.substitute(&body_subs)?;
+    body_ty.substitute(&body_subs)?;
+    substitute_hir(&mut param_pattern, &body_subs)?;
+    substitute_hir(&mut body_expr, &body_subs)?;
+    subs.compose_with(body_subs)?;
+    // a diverging body returns only through `return`, the function never
+    // returns when there's none
+    if is_never(&body_ty, var_state)? {
+        let mut return_ty = Type::Var(return_var.clone());
+        return_ty.substitute(var_state.bindings())?;
+        if return_ty != Type::Var(return_var) {
+            body_ty = return_ty;
+        }
+    }
+    Ok(Typed {
+        ty: Type::Cons(Cons::Fun(Box::new(param_ty), Box::new(body_ty))),
+        value: Fun {
+            param: param_pattern,
+            ret: fun.ret,
+            body: Box::new(body_expr),
+        },
+    })
+}
+impl Inferable for Annotated<()> {
+    type TypedSelf = Annotated<Type>;
+
+    fn infer(
+        self,
+        subs: &mut Subs,
+        var_state: &mut VarState,
+        env: &Env,
+    ) -> Result<Typed<Self::TypedSelf>, TypeError> {
+        let typed_expr = self.expr.infer(subs, var_state, env)?;
+        let mut expr = typed_expr.value;
+        let mut ty = annotation_type(&self.annotation, var_state, &mut env.clone())?;
+        let mut annotation_subs = Subs::new();
+        unify_annotation(typed_expr.ty, ty.clone(), &mut annotation_subs, var_state)?;
+        ty.substitute(&annotation_subs)?;
+        substitute_hir(&mut expr, &annotation_subs)?;
+        subs.compose_with(annotation_subs)?;
+        Ok(Typed {
+            ty,
+            value: Annotated {
+                expr: Box::new(expr),
+                annotation: self.annotation,
+            },
+        })
+    }
+}
 impl Inferable for Arg<()> {
     type TypedSelf = Arg<Type>;
 
@@ -824,7 +1053,8 @@ impl Inferable for Call<()> {
     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
         let var = var_state.new_var();
         let mut subs1 = Subs::new();
-        let typed1 = self.expr.infer(&mut subs1, var_state, env)?;
+        // calling a function doesn't duplicate it
+        let (_, typed1) = self.expr.infer_with_mut(&mut subs1, var_state, env)?;
         let mut env2 = env.clone();
         env2.substitute(&subs1)?;
         let mut subs2 = Subs::new();
@@ -832,7 +1062,7 @@ impl Inferable for Call<()> {
         let mut ty1 = typed1.ty;
         ty1.substitute(&subs2)?;
         let mut subs3 = Subs::new();
-        ty1.unify_with(
+        ty1.clone().unify_with(
             Type::Cons(Cons::Fun(
                 Box::new(typed2.ty),
                 Box::new(Type::Var(var.clone())),
@@ -842,6 +1072,13 @@ impl Inferable for Call<()> {
         )?;
         let mut ty = Type::Var(var);
         ty.substitute(&subs3)?;
+        // `Never` unifies without binding the return type
+        ty1.substitute(var_state.bindings())?;
+        if let Type::Cons(Cons::Fun(_, return_ty)) = ty1.unalias() {
+            if is_never(&return_ty, var_state)? {
+                ty = Type::Cons(Cons::Never);
+            }
+        }
         let mut callee_expr = typed1.value;
         let mut arg_expr = typed2.value;
         substitute_hir(&mut callee_expr, &subs3)?;
@@ -868,25 +1105,39 @@ impl Inferable for Assign<()> {
         env: &Env,
     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
         let var = self.place.var();
-        if let Some(var) = var {
-            match env.get_mut(Var::new_bare(var)) {
-                Some(true) => (),
-                Some(false) => return Err(TypeError::AssignedImm),
-                None => return Err(TypeError::UnboundVar),
-            }
-        }
+        let from_place = matches!(self.expr.expr, ExprKind::Place(_));
         let typed_expr = self.expr.infer(subs, var_state, env)?;
-        let expr_ty = typed_expr.ty;
+        let mut expr_ty = typed_expr.ty;
         let mut expr_expr = typed_expr.value;
+        // the value is duplicated into a place that is always mutable
+        if from_place {
+            require_copy(&mut expr_ty, &mut expr_expr, subs, var_state)?;
+        }
         let (mut_var, typed_place) = self.place.infer_with_mut(subs, var_state, env)?;
         let place_ty = typed_place.ty;
         let mut place_expr = typed_place.value;
-        if let Some(mut_var) = mut_var {
-            let mut mut_subs = Subs::new();
-            MutType::Var(mut_var).unify_with(MutType::Mut, &mut mut_subs, var_state)?;
-            substitute_hir(&mut expr_expr, &mut_subs)?;
-            substitute_hir(&mut place_expr, &mut_subs)?;
-            subs.compose_with(mut_subs)?;
+        match (mut_var, var) {
+            // places behind a reference are as mutable as the reference
+            (Some(mut_var), _) => {
+                let mut mut_subs = Subs::new();
+                MutType::Var(mut_var)
+                    .unify_with(MutType::Mut, &mut mut_subs, var_state)
+                    .map_err(|err| match err {
+                        TypeError::MismatchMut => TypeError::AssignedImm,
+                        err => err,
+                    })?;
+                substitute_hir(&mut expr_expr, &mut_subs)?;
+                substitute_hir(&mut place_expr, &mut_subs)?;
+                subs.compose_with(mut_subs)?;
+            }
+            (None, Some(var)) => match env.get_mut(Var::new_bare(var.clone())) {
+                Some(true) => (),
+                Some(false) => return Err(TypeError::AssignedImm),
+                // members of a module can't be assigned from outside of it
+                None if env.get_module(&var).is_some() => return Err(TypeError::AssignedImm),
+                None => return Err(TypeError::UnboundVar(var)),
+            },
+            (None, None) => (),
         }
         let mut place_subs = Subs::new();
         place_ty.unify_with(expr_ty, &mut place_subs, var_state)?;
@@ -933,37 +1184,82 @@ impl Inferable for Jump<()> {
         env: &Env,
     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
         let typed = match self {
-            Jump::Break(_) => todo!(),
-            Jump::Continue => todo!(),
-            Jump::Return(expr) => {
-                let typed_expr = match expr {
-                    Some(expr) => expr.infer(subs, var_state, env)?.map(Some),
-                    None => Typed {
-                        ty: unit(),
-                        value: None,
-                    },
-                };
-                let operand_ty = typed_expr.ty;
-                let mut operand_expr = typed_expr.value;
-                let mut return_subs = Subs::new();
-                operand_ty.unify_with(
-                    keyword!("return").infer(subs, var_state, env)?.ty,
-                    &mut return_subs,
-                    var_state,
-                )?;
-                substitute_hir(&mut operand_expr, &return_subs)?;
-                subs.compose_with(return_subs)?;
-                Jump::Return(operand_expr.map(Box::new))
+            Jump::Break(expr) => Jump::Break(infer_jump_operand(
+                expr,
+                keyword!("break"),
+                subs,
+                var_state,
+                env,
+            )?),
+            Jump::Continue => {
+                keyword!("break").infer(subs, var_state, env)?;
+                Jump::Continue
             }
+            Jump::Return(expr) => Jump::Return(infer_jump_operand(
+                expr,
+                keyword!("return"),
+                subs,
+                var_state,
+                env,
+            )?),
         };
-        let var = var_state.new_var();
         Ok(Typed {
-            ty: Type::Var(var),
+            ty: Type::Cons(Cons::Never),
             value: typed,
         })
     }
 }
-fn infer_statement(
+/// Infers the operand of `break` or `return`, unit when there's none
+fn infer_jump_operand(
+    expr: Option<Box<Expr<()>>>,
+    keyword: Atom,
+    subs: &mut Subs,
+    var_state: &mut VarState,
+    env: &Env,
+) -> Result<Option<Box<Expr<Type>>>, TypeError> {
+    let typed_expr = match expr {
+        Some(expr) => expr.infer(subs, var_state, env)?.map(Some),
+        None => Typed {
+            ty: unit(),
+            value: None,
+        },
+    };
+    let operand_ty = typed_expr.ty;
+    let mut operand_expr = typed_expr.value;
+    let mut jump_subs = Subs::new();
+    operand_ty.unify_with(
+        keyword.infer(subs, var_state, env)?.ty,
+        &mut jump_subs,
+        var_state,
+    )?;
+    substitute_hir(&mut operand_expr, &jump_subs)?;
+    subs.compose_with(jump_subs)?;
+    Ok(operand_expr.map(Box::new))
+}
+pub(super) fn infer_statement(
+    subs: &mut Subs,
+    env: &mut Env,
+    var_state: &mut VarState,
+    statement: Statement<()>,
+) -> Result<Statement<Type>, TypeError> {
+    // errors not within an expression are located at the declaration
+    let span = statement_span(&statement);
+    infer_declaration(subs, env, var_state, statement).map_err(|err| err.at(span))
+}
+/// The pattern of a variable declaration, the name of other declarations
+fn statement_span<T: PrettyPrintType>(statement: &Statement<T>) -> Span {
+    match statement {
+        Statement::Declare(declare) => declare.pattern.span,
+        Statement::FunDeclare(fun) => fun.span,
+        Statement::AliasDeclare(alias) => alias.span,
+        Statement::NewtypeDeclare(newtype) => newtype.span,
+        Statement::TraitDeclare(trait_declare) => trait_declare.span,
+        Statement::ImplDeclare(impl_declare) => impl_declare.span,
+        Statement::ModuleDeclare(module) => module.span,
+        Statement::Expr(expr) => expr.span,
+    }
+}
+fn infer_declaration(
     subs: &mut Subs,
     env: &mut Env,
     var_state: &mut VarState,
@@ -971,61 +1267,137 @@ fn infer_statement(
 ) -> Result<Statement<Type>, TypeError> {
     let typed = match statement {
         Statement::Declare(declare) => {
-            let typed_expr = declare.expr.infer(subs, var_state, env)?;
+            let root_mutability = root_mutability(&declare.expr, env);
+            let (mut_var, typed_expr) = declare.expr.infer_with_mut(subs, var_state, env)?;
+            let mutability = place_mutability(mut_var.clone(), root_mutability == Some(false));
             let operand_ty = typed_expr.ty;
             let mut operand_expr = typed_expr.value;
-            let typed_pattern = declare.pattern.infer(None, var_state, env)?;
+            let else_part = match declare.else_part {
+                Some(else_part) => {
+                    let typed = else_part.infer(subs, var_state, env)?.value;
+                    let divergence = Divergence { var_state };
+                    if !divergence.block(&typed) {
+                        return Err(TypeError::NonDivergingElse(typed.span));
+                    }
+                    Some(typed)
+                }
+                None if declare.pattern.is_refutable() => {
+                    return Err(TypeError::RefutablePattern(declare.pattern.span));
+                }
+                None => None,
+            };
+            let typed_pattern = declare.pattern.infer(mutability, var_state, env)?;
             let pattern_ty = typed_pattern.ty;
             let mut pattern_expr = typed_pattern.value;
             let mut more_subs = Subs::new();
-            operand_ty.unify_with(pattern_ty, &mut more_subs, var_state)?;
+            if let PatternKind::Annotated(_) = pattern_expr.pattern {
+                unify_annotation(operand_ty, pattern_ty, &mut more_subs, var_state)?;
+            } else {
+                operand_ty.unify_with(pattern_ty, &mut more_subs, var_state)?;
+            }
+            substitute_hir(&mut pattern_expr, &more_subs)?;
+            if let ExprKind::Place(_) = operand_expr.expr {
+                let mutable_place = is_mutable_place(mut_var, root_mutability, var_state)?;
+                let copy_subs = require_copy_bindings(&pattern_expr, mutable_place, var_state)?;
+                substitute_hir(&mut pattern_expr, &copy_subs)?;
+                more_subs.compose_with(copy_subs)?;
+            }
             env.substitute(&more_subs)?;
             substitute_hir(&mut operand_expr, &more_subs)?;
-            substitute_hir(&mut pattern_expr, &more_subs)?;
             subs.compose_with(more_subs)?;
             Statement::Declare(Declare {
+                visibility: declare.visibility,
                 pattern: pattern_expr,
                 expr: operand_expr,
+                else_part,
             })
         }
-        Statement::FunDeclare(_fun) => todo!(),
-        // Statement::FunDeclare(fun) => {
-        //     let var = Var::new_bare(fun.ident.clone());
-        //     env.remove(var.clone());
-        //     let mut ty = Type::Cons(Cons::Fun(
-        //         Box::new(Type::Var(var_state.new_var())),
-        //         Box::new(Type::Var(var_state.new_var())),
-        //     ));
-        //     env.insert(
-        //         var.clone(),
-        //         SchemeMut {
-        //             is_mut: false,
-        //             scheme: Scheme {
-        //                 for_all: HashSet::new(),
-        //                 ty: ty.clone(),
-        //             },
-        //         },
-        //     );
-        //     let typed_fun = fun.fun.infer(subs, var_state, env)?;
-        //     let mut more_subs = Subs::new();
-        //     typed_fun
-        //         .ty
-        //         .unify_with(ty.clone(), &mut more_subs, var_state)?;
-        //     ty.substitute(&more_subs)?;
-        //     subs.compose_with(more_subs)?;
-        //     env.insert(
-        //         var,
-        //         SchemeMut {
-        //             is_mut: false,
-        //             scheme: env.generalize(ty.clone()),
-        //         },
-        //     );
-        //     Statement::FunDeclare(FunDeclare {
-        //         ident: fun.ident,
-        //         fun: typed_fun.value,
-        //         ty,
-        //     })
-        // }
+        Statement::FunDeclare(fun) => {
+            let var = Var::new_bare(fun.ident.clone());
+            let mut ty = Type::Var(var_state.new_named(fun.ident.clone()));
+            env.insert(
+                var.clone(),
+                SchemeMut {
+                    is_mut: false,
+                    is_method: false,
+                    scheme: Scheme {
+                        for_all: HashSet::new(),
+                        given: Vec::new(),
+                        ty: ty.clone(),
+                    },
+                },
+            );
+            let mut fun_env = env.clone();
+            let wanted_start = var_state.wanted.len();
+            let generics: Vec<_> = fun
+                .generics
+                .iter()
+                .map(|name| {
+                    let var = KindedVar {
+                        kind: Kind::Type,
+                        var: var_state.new_rigid(name.clone()),
+                    };
+                    fun_env.insert_type_var(name.clone(), var.clone());
+                    var
+                })
+                .collect();
+            let declared = fun
+                .given
+                .iter()
+                .map(|bound| bound_predicate(bound, var_state, &mut fun_env))
+                .collect::<Result<Vec<_>, _>>()?;
+            let typed_fun = fun.fun.infer(subs, var_state, &fun_env)?;
+            let mut fun_expr = typed_fun.value;
+            let mut more_subs = Subs::new();
+            typed_fun
+                .ty
+                .unify_with(ty.clone(), &mut more_subs, var_state)?;
+            substitute_hir(&mut fun_expr, &more_subs)?;
+            subs.compose_with(more_subs)?;
+            ty.substitute(var_state.bindings())?;
+            env.remove(var.clone());
+            env.substitute(var_state.bindings())?;
+            // rigid variables can't be bound, so the scheme is at least as
+            // general as declared unless they escape into the environment
+            let env_free_vars = env.free_vars();
+            if let Some(var) = generics.into_iter().find(|var| env_free_vars.contains(var)) {
+                return Err(TypeError::EscapedRigid(var.var.name));
+            }
+            let mut scheme = env.generalize(ty);
+            generalize_given(&mut scheme, wanted_start, declared, var_state)?;
+            env.insert(
+                var,
+                SchemeMut {
+                    is_mut: false,
+                    is_method: false,
+                    scheme: scheme.clone(),
+                },
+            );
+            Statement::FunDeclare(FunDeclare {
+                visibility: fun.visibility,
+                ident: fun.ident,
+                span: fun.span,
+                given: fun.given,
+                generics: fun.generics,
+                fun: fun_expr,
+                ty: scheme,
+            })
+        }
+        Statement::AliasDeclare(alias) => Statement::AliasDeclare(alias),
+        Statement::NewtypeDeclare(newtype) => {
+            declare_constructor(&newtype, env, var_state)?;
+            Statement::NewtypeDeclare(newtype)
+        }
+        Statement::TraitDeclare(trait_declare) => {
+            declare_methods(&trait_declare, env, var_state)?;
+            Statement::TraitDeclare(trait_declare)
+        }
+        Statement::ImplDeclare(impl_declare) => {
+            Statement::ImplDeclare(infer_impl(impl_declare, subs, var_state, env)?)
+        }
+        Statement::ModuleDeclare(module) => {
+            Statement::ModuleDeclare(infer_module_declare(module, subs, var_state, env)?)
+        }
         Statement::Expr(expr) => Statement::Expr(expr.infer(subs, var_state, env)?.value),
     };
     Ok(typed)
@@ -1041,6 +1413,9 @@ impl Inferable for Block<()> {
     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
         let mut typed_statement = Vec::with_capacity(self.statement.len());
         let mut env = env.clone();
+        declare_aliases(&self.statement, &mut env)?;
+        declare_newtypes(&self.statement, &mut env, var_state)?;
+        declare_traits(&self.statement, &env, var_state)?;
         let statement: Vec<_> = self.statement.into();
         let mut more_subs = Subs::new();
         for statement in statement {
@@ -1054,7 +1429,22 @@ impl Inferable for Block<()> {
                 value: None,
             },
         };
+        let divergence = Divergence { var_state };
+        let diverges_at = typed_statement
+            .iter()
+            .position(|statement| divergence.statement(statement));
         let mut ty = typed_expr.ty;
+        if let Some(position) = diverges_at {
+            let unreachable = match (typed_statement.get(position + 1), &typed_expr.value) {
+                (Some(statement), _) => Some(statement_span(statement)),
+                (None, Some(expr)) => Some(expr.span),
+                (None, None) => None,
+            };
+            if let Some(span) = unreachable {
+                var_state.warn(TypeWarning::UnreachableCode(span));
+            }
+            ty = Type::Cons(Cons::Never);
+        }
         ty.substitute(&more_subs)?;
         subs.compose_with(more_subs)?;
         Ok(Typed {
@@ -1062,6 +1452,7 @@ impl Inferable for Block<()> {
             value: Block {
                 statement: typed_statement.into(),
                 expr: typed_expr.value.map(Box::new),
+                span: self.span,
             },
         })
     }
@@ -1102,7 +1493,11 @@ impl Inferable for If<()> {
         let mut body_else_subs = Subs::new();
         body_ty
             .clone()
-            .unify_with(else_ty, &mut body_else_subs, var_state)?;
+            .unify_with(else_ty.clone(), &mut body_else_subs, var_state)?;
+        // a diverging branch takes the type of the other
+        if body_ty.clone().unalias() == Type::Cons(Cons::Never) {
+            body_ty = else_ty;
+        }
         body_ty.substitute(&body_else_subs)?;
         substitute_hir(&mut condition_expr, &body_else_subs)?;
         substitute_hir(&mut body_expr, &body_else_subs)?;
@@ -1118,6 +1513,168 @@ impl Inferable for If<()> {
         })
     }
 }
+impl Inferable for Match<()> {
+    type TypedSelf = Match<Type>;
+
+    fn infer(
+        self,
+        subs: &mut Subs,
+        var_state: &mut VarState,
+        env: &Env,
+    ) -> Result<Typed<Self::TypedSelf>, TypeError> {
+        let root_mutability = root_mutability(&self.expr, env);
+        let (mut_var, typed_expr) = self.expr.infer_with_mut(subs, var_state, env)?;
+        let mutability = place_mutability(mut_var.clone(), root_mutability == Some(false));
+        let mut expr_ty = typed_expr.ty;
+        let mut expr_expr = typed_expr.value;
+        let from_place = matches!(expr_expr.expr, ExprKind::Place(_));
+        let mut ty = Type::Var(var_state.new_var());
+        let arms: Vec<_> = self.arm.into();
+        let mut typed_arms = Vec::with_capacity(arms.len());
+        for arm in arms {
+            let mut arm_env = env.clone();
+            let typed_pattern = arm
+                .pattern
+                .infer(mutability.clone(), var_state, &mut arm_env)?;
+            let mut pattern_expr = typed_pattern.value;
+            let mut pattern_subs = Subs::new();
+            typed_pattern
+                .ty
+                .unify_with(expr_ty.clone(), &mut pattern_subs, var_state)?;
+            expr_ty.substitute(&pattern_subs)?;
+            arm_env.substitute(&pattern_subs)?;
+            substitute_hir(&mut expr_expr, &pattern_subs)?;
+            substitute_hir(&mut pattern_expr, &pattern_subs)?;
+            if from_place {
+                let mutable_place = is_mutable_place(mut_var.clone(), root_mutability, var_state)?;
+                let copy_subs = require_copy_bindings(&pattern_expr, mutable_place, var_state)?;
+                expr_ty.substitute(&copy_subs)?;
+                arm_env.substitute(&copy_subs)?;
+                substitute_hir(&mut expr_expr, &copy_subs)?;
+                substitute_hir(&mut pattern_expr, &copy_subs)?;
+                pattern_subs.compose_with(copy_subs)?;
+            }
+            subs.compose_with(pattern_subs)?;
+            let typed_arm = arm.expr.infer(subs, var_state, &arm_env)?;
+            let mut arm_expr = typed_arm.value;
+            let mut arm_subs = Subs::new();
+            typed_arm
+                .ty
+                .unify_with(ty.clone(), &mut arm_subs, var_state)?;
+            ty.substitute(&arm_subs)?;
+            substitute_hir(&mut arm_expr, &arm_subs)?;
+            subs.compose_with(arm_subs)?;
+            typed_arms.push(MatchArm {
+                pattern: pattern_expr,
+                expr: arm_expr,
+            });
+        }
+        check_match(&typed_arms, &expr_ty, expr_expr.span, var_state)?;
+        Ok(Typed {
+            ty,
+            value: Match {
+                expr: Box::new(expr_expr),
+                arm: typed_arms.into(),
+            },
+        })
+    }
+}
+impl Inferable for For<()> {
+    type TypedSelf = For<Type>;
+
+    fn infer(
+        self,
+        subs: &mut Subs,
+        var_state: &mut VarState,
+        env: &Env,
+    ) -> Result<Typed<Self::TypedSelf>, TypeError> {
+        if self.pattern.is_refutable() {
+            return Err(TypeError::RefutablePattern(self.pattern.span));
+        }
+        let mut expr_expr = self.expr.infer(subs, var_state, env)?;
+        let mut body_env = env.clone();
+        let typed_pattern = self.pattern.infer(None, var_state, &mut body_env)?;
+        let mut pattern_expr = typed_pattern.value;
+        let mut pattern_subs = Subs::new();
+        expr_expr.ty.clone().unify_with(
+            Type::Cons(Cons::Array(Box::new(typed_pattern.ty))),
+            &mut pattern_subs,
+            var_state,
+        )?;
+        body_env.substitute(&pattern_subs)?;
+        substitute_hir(&mut expr_expr.value, &pattern_subs)?;
+        substitute_hir(&mut pattern_expr, &pattern_subs)?;
+        subs.compose_with(pattern_subs)?;
+        declare_jump(&mut body_env, keyword!("break"), unit());
+        let body = self.body.infer(subs, var_state, &body_env)?.value;
+        Ok(Typed {
+            ty: unit(),
+            value: For {
+                pattern: pattern_expr,
+                expr: Box::new(expr_expr.value),
+                body,
+            },
+        })
+    }
+}
+impl Inferable for While<()> {
+    type TypedSelf = While<Type>;
+
+    fn infer(
+        self,
+        subs: &mut Subs,
+        var_state: &mut VarState,
+        env: &Env,
+    ) -> Result<Typed<Self::TypedSelf>, TypeError> {
+        let mut body_env = env.clone();
+        declare_jump(&mut body_env, keyword!("break"), unit());
+        let typed_condition = self.condition.infer(subs, var_state, &body_env)?;
+        let mut condition_expr = typed_condition.value;
+        let mut condition_subs = Subs::new();
+        typed_condition
+            .ty
+            .unify_with(Type::Cons(Cons::Bool), &mut condition_subs, var_state)?;
+        substitute_hir(&mut condition_expr, &condition_subs)?;
+        body_env.substitute(&condition_subs)?;
+        subs.compose_with(condition_subs)?;
+        let body = self.body.infer(subs, var_state, &body_env)?.value;
+        Ok(Typed {
+            ty: unit(),
+            value: While {
+                condition: Box::new(condition_expr),
+                body,
+            },
+        })
+    }
+}
+/// Finds whether a `loop` body breaks out of it, jumps within nested loops and
+/// functions target those instead
+struct Breaks(bool);
+impl Breaks {
+    fn find(body: &Block<()>) -> bool {
+        let mut breaks = Breaks(false);
+        breaks.visit_block(body);
+        breaks.0
+    }
+}
+impl Visit<()> for Breaks {
+    fn visit_fun_declare(&mut self, _: &FunDeclare<()>) {}
+    fn visit_impl_declare(&mut self, _: &ImplDeclare<()>) {}
+    fn visit_fun(&mut self, _: &Fun<()>) {}
+    fn visit_control_flow(&mut self, control_flow: &ControlFlow<()>) {
+        match control_flow {
+            ControlFlow::For(for_expr) => self.visit_expr(&for_expr.expr),
+            ControlFlow::While(_) | ControlFlow::Loop(_) => (),
+            control_flow => walk_control_flow(self, control_flow),
+        }
+    }
+    fn visit_jump(&mut self, jump: &Jump<()>) {
+        if let Jump::Break(_) = jump {
+            self.0 = true;
+        }
+        walk_jump(self, jump);
+    }
+}
 impl Inferable for ControlFlow<()> {
     type TypedSelf = ControlFlow<Type>;
 
@@ -1130,10 +1687,36 @@ impl Inferable for ControlFlow<()> {
         let typed = match self {
             Self::Block(block) => block.infer(subs, var_state, env)?.map(ControlFlow::Block),
             Self::If(if_expr) => if_expr.infer(subs, var_state, env)?.map(ControlFlow::If),
-            Self::For(_) => todo!(),
-            Self::While(_) => todo!(),
-            Self::Loop(_) => todo!(),
-            Self::Match(_) => todo!(),
+            Self::For(for_expr) => for_expr.infer(subs, var_state, env)?.map(ControlFlow::For),
+            Self::While(while_expr) => while_expr
+                .infer(subs, var_state, env)?
+                .map(ControlFlow::While),
+            Self::Loop(body) => {
+                let breaks = Breaks::find(&body);
+                let break_var = var_state.new_var();
+                let mut body_env = env.clone();
+                declare_jump(
+                    &mut body_env,
+                    keyword!("break"),
+                    Type::Var(break_var.clone()),
+                );
+                let body = body.infer(subs, var_state, &body_env)?.value;
+                // without `break` the loop never ends
+                let ty = if breaks {
+                    let mut ty = Type::Var(break_var);
+                    ty.substitute(subs)?;
+                    ty
+                } else {
+                    Type::Cons(Cons::Never)
+                };
+                Typed {
+                    ty,
+                    value: ControlFlow::Loop(body),
+                }
+            }
+            Self::Match(match_expr) => match_expr
+                .infer(subs, var_state, env)?
+                .map(ControlFlow::Match),
         };
         Ok(typed)
     }
@@ -1149,6 +1732,7 @@ impl Inferable for ExprKind<()> {
     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
         let ty_expr = match self {
             Self::Literal(literal) => literal.infer(subs, var_state, env)?.map(ExprKind::Literal),
+            Self::Str(string) => string.infer(subs, var_state, env)?.map(ExprKind::Str),
             Self::Place(place) => place.infer(subs, var_state, env)?.map(ExprKind::Place),
             Self::Array(elements) => elements.infer(subs, var_state, env)?.map(ExprKind::Array),
             Self::ArrayRange(range) => range.infer(subs, var_state, env)?.map(ExprKind::ArrayRange),
@@ -1159,6 +1743,10 @@ impl Inferable for ExprKind<()> {
                 ty: unit(),
                 value: ExprKind::Unit,
             },
+            Self::Never => Typed {
+                ty: Type::Cons(Cons::Never),
+                value: ExprKind::Never,
+            },
             Self::Splat(splat) => {
                 let splat = splat.infer(subs, var_state, env)?;
                 let operand_ty = splat.ty;
@@ -1181,10 +1769,13 @@ impl Inferable for ExprKind<()> {
             }
             Self::Unary(unary) => unary.infer(subs, var_state, env)?.map(ExprKind::Unary),
             Self::Binary(binary) => binary.infer(subs, var_state, env)?.map(ExprKind::Binary),
-            Self::Fun(_fun) => todo!(),
+            Self::Fun(fun) => fun.infer(subs, var_state, env)?.map(ExprKind::Fun),
             Self::Call(call) => call.infer(subs, var_state, env)?.map(ExprKind::Call),
             Self::Assign(assigns) => assigns.infer(subs, var_state, env)?.map(ExprKind::Assign),
             Self::Jump(jump) => jump.infer(subs, var_state, env)?.map(ExprKind::Jump),
+            Self::Annotated(annotated) => annotated
+                .infer(subs, var_state, env)?
+                .map(ExprKind::Annotated),
             Self::ControlFlow(control_flow) => control_flow
                 .infer(subs, var_state, env)?
                 .map(ExprKind::ControlFlow),
@@ -1217,14 +1808,53 @@ impl Inferable for Expr<()> {
     where
         Self: Sized,
     {
-        let typed = self.expr.infer(subs, var_state, env)?;
+        // using a place as a value implicitly copies it, which is only
+        // observable when the place can be mutated afterwards
+        let root_mutability = root_mutability(&self, env);
+        let span = self.span;
+        let (mut_var, mut typed) = self.infer_with_mut(subs, var_state, env)?;
+        if is_mutable_place(mut_var, root_mutability, var_state)? {
+            require_copy(&mut typed.ty, &mut typed.value, subs, var_state)
+                .map_err(|err| err.at(span))?;
+        }
+        Ok(typed)
+    }
+    fn infer_with_mut(
+        self,
+        subs: &mut Subs,
+        var_state: &mut VarState,
+        env: &Env,
+    ) -> Result<(Option<Var>, Typed<Self::TypedSelf>), TypeError> {
+        // jumps find their target through a pseudo-variable bound by the
+        // enclosing loop or function
+        if let ExprKind::Jump(jump) = &self.expr {
+            let (target, keyword) = match jump {
+                Jump::Break(_) => (keyword!("break"), keyword!("break")),
+                Jump::Continue => (keyword!("break"), keyword!("continue")),
+                Jump::Return(_) => (keyword!("return"), keyword!("return")),
+            };
+            if env.get_ty(Var::new_bare(target)).is_none() {
+                return Err(TypeError::MisplacedJump {
+                    keyword,
+                    span: self.span,
+                });
+            }
+        }
+        let (mut_var, typed) = self
+            .expr
+            .infer_with_mut(subs, var_state, env)
+            .map_err(|err| err.at(self.span))?;
         let ty = typed.ty.clone();
-        Ok(Typed {
-            ty,
-            value: Expr {
-                expr: typed.value,
-                ty: typed.ty,
+        Ok((
+            mut_var,
+            Typed {
+                ty,
+                value: Expr {
+                    expr: typed.value,
+                    ty: typed.ty,
+                    span: self.span,
+                },
             },
-        })
+        ))
     }
 }
@@ -3,16 +3,34 @@
 #![forbid(unsafe_code)]
 
 use expr::Inferable;
-use hir::{expr::Expr, statement::Statement, TraverseType};
-use ty::{Env, Subs, Substitutable, VarState};
+use hir::{expr::Expr, statement::Statement, TraverseType, TypeSite};
+use module::infer_module;
+use std::collections::HashSet;
+use string::declare_conversions;
+use traits::{declare_prelude, solve_wanted};
+use ty::{Env, FreeVars, Kind, KindedVar, Subs, Substitutable, VarState};
 
+mod annotation;
+mod checker;
+mod diverge;
+mod elaborate;
+mod exhaustive;
 mod expr;
+mod module;
+mod newtype;
 mod pattern;
+mod string;
+mod traits;
 mod ty;
 
-pub use crate::ty::{
-    cons::{Cons, Keyed},
-    MutType, Type, TypeError, Var,
+pub use crate::{
+    checker::{Checker, Declared},
+    exhaustive::Witness,
+    ty::{
+        cons::{Cons, Keyed, Newtype},
+        num::{NumType, Repr},
+        Alias, MutType, Predicate, Scheme, Type, TypeError, TypeWarning, Var,
+    },
 };
 struct Typed<T> {
     ty: Type,
@@ -27,23 +45,999 @@ impl<T> Typed<T> {
     }
 }
 fn substitute_hir(hir: &mut impl TraverseType<Type = Type>, subs: &Subs) -> Result<(), TypeError> {
-    hir.traverse_type(
-        subs,
-        |ty, subs| ty.substitute(subs),
-        |scheme, subs| {
-            scheme.substitute(subs)?;
-            subs.filter_off(&scheme.for_all);
-            Ok(())
-        },
-    )
+    // generic variables of a scheme are never substituted within its function
+    let mut scopes: Vec<Subs> = Vec::new();
+    hir.traverse_type(&mut |site| {
+        let subs = scopes.last().unwrap_or(subs);
+        match site {
+            TypeSite::Type(ty) => ty.substitute(subs)?,
+            TypeSite::Scheme(scheme) => {
+                scheme.substitute(subs)?;
+                let mut subs = subs.clone();
+                subs.filter_off(&scheme.for_all);
+                scopes.push(subs);
+            }
+            TypeSite::EndScheme => {
+                scopes.pop();
+            }
+        }
+        Ok(())
+    })
+}
+pub fn infer(statements: Vec<Statement<()>>) -> Result<Vec<Statement<Type>>, TypeError> {
+    infer_with_warnings(statements).map(|(statements, _)| statements)
+}
+pub fn infer_with_warnings(
+    statements: Vec<Statement<()>>,
+) -> Result<(Vec<Statement<Type>>, Vec<TypeWarning>), TypeError> {
+    let (statements, mut var_state) = infer_program(statements)?;
+    Ok((statements, var_state.take_warnings()))
+}
+/// Infers the statements then passes trait implementations as dictionaries,
+/// see [`elaborate`] for how the statements are rewritten
+pub fn infer_elaborated(statements: Vec<Statement<()>>) -> Result<Vec<Statement<Type>>, TypeError> {
+    let (statements, var_state) = infer_program(statements)?;
+    Ok(elaborate::elaborate(statements, &var_state))
+}
+fn infer_program(
+    statements: Vec<Statement<()>>,
+) -> Result<(Vec<Statement<Type>>, VarState), TypeError> {
+    let mut subs = Subs::new();
+    let mut var_state = VarState::new();
+    declare_prelude(&mut var_state);
+    let mut env = Env::new();
+    declare_conversions(&mut env);
+    let (mut statements, _) = infer_module(statements, &mut subs, &mut var_state, &env)?;
+    solve_wanted(&mut var_state, true)?;
+    for statement in &mut statements {
+        substitute_hir(statement, var_state.bindings())?;
+    }
+    default_nums(&mut statements, &var_state)?;
+    Ok((statements, var_state))
 }
-pub fn infer(_statements: Vec<Statement<()>>) -> Result<Vec<Statement<Type>>, TypeError> {
-    todo!()
+/// Gives numbers with a representation still unknown their default, numbers
+/// generic to a function are left as they are
+fn default_nums(statements: &mut [Statement<Type>], var_state: &VarState) -> Result<(), TypeError> {
+    let mut vars = HashSet::new();
+    for statement in statements.iter_mut() {
+        statement.traverse_type(&mut |site| {
+            match site {
+                TypeSite::Type(ty) => vars.extend(ty.free_vars()),
+                TypeSite::Scheme(scheme) => vars.extend(scheme.free_vars()),
+                TypeSite::EndScheme => (),
+            }
+            Ok::<_, TypeError>(())
+        })?;
+    }
+    let subs = default_subs(vars, var_state);
+    for statement in statements {
+        substitute_hir(statement, &subs)?;
+    }
+    Ok(())
+}
+fn default_subs(vars: HashSet<KindedVar>, var_state: &VarState) -> Subs {
+    let mut subs = Subs::new();
+    for var in vars {
+        if var.kind == Kind::NumType {
+            if let Some(repr) = var_state.default_repr(&var.var) {
+                subs.insert_num(var.var, NumType::Repr(repr));
+            }
+        }
+    }
+    subs
 }
 pub fn test_infer(expr: Expr<()>) -> Result<Type, TypeError> {
+    test_infer_with_warnings(expr).map(|(ty, _)| ty)
+}
+pub fn test_infer_with_warnings(expr: Expr<()>) -> Result<(Type, Vec<TypeWarning>), TypeError> {
     let mut subs = Subs::new();
-    let typed_expr = expr.infer(&mut subs, &mut VarState::new(), &Env::new())?;
+    let mut var_state = VarState::new();
+    declare_prelude(&mut var_state);
+    let mut env = Env::new();
+    declare_conversions(&mut env);
+    let typed_expr = expr.infer(&mut subs, &mut var_state, &env)?;
+    solve_wanted(&mut var_state, true)?;
     let mut ty = typed_expr.ty;
-    ty.substitute(&subs)?;
-    Ok(ty)
+    ty.substitute(var_state.bindings())?;
+    ty.substitute(&default_subs(ty.free_vars(), &var_state))?;
+    Ok((ty, var_state.take_warnings()))
+}
+#[cfg(test)]
+mod test {
+    use crate::{test_infer, test_infer_with_warnings, ty::Kind, Type, TypeError, TypeWarning};
+    use hir::{pattern::PatternKind, statement::Statement, Atom, Span, TraverseType};
+    use parser::{ast, expr_parser, EasyParser};
+
+    // where errors are located is tested on its own
+    fn infer(src: &str) -> Result<(), TypeError> {
+        let (expr, rest) = expr_parser().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        test_infer(expr).map(|_| ()).map_err(TypeError::unlocated)
+    }
+    fn infer_warnings(src: &str) -> Result<Vec<TypeWarning>, TypeError> {
+        let (expr, rest) = expr_parser().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        test_infer_with_warnings(expr)
+            .map(|(_, warnings)| warnings)
+            .map_err(TypeError::unlocated)
+    }
+    #[test]
+    fn mismatching_concatenation() {
+        assert_eq!(
+            infer("[true] ++ [1]").unwrap_err().to_string(),
+            "mismatched types `Num` and `Bool`"
+        );
+    }
+    #[test]
+    fn mismatching_arity() {
+        assert_eq!(
+            infer("[(1, 2), (1, 2, 3)]").unwrap_err().to_string(),
+            "mismatched number of fields or elements between records, tuples or unions"
+        );
+    }
+    #[test]
+    fn declare_else() {
+        let src = "(x) => { @val y = x else { return 0; }; y + 1 }";
+        assert_eq!(infer(src), Ok(()));
+    }
+    #[test]
+    fn declare_else_diverging_call() {
+        let src = "{
+            abort() => loop {};
+            f(x) => { @val y = x else { abort() }; y };
+            f
+        }";
+        assert_eq!(infer(src), Ok(()));
+    }
+    #[test]
+    fn generic_return_doesnt_diverge() {
+        let src = "{
+            abort() => abort();
+            f(x) => { @val y = x else { abort() }; y };
+            f
+        }";
+        assert!(matches!(infer(src), Err(TypeError::NonDivergingElse(_))));
+    }
+    #[test]
+    fn loops() {
+        let src = "(arr) => { mut sum = 0; for x in arr { sum <- sum + x; }; sum }";
+        let (expr, _) = expr_parser().easy_parse(src).unwrap();
+        assert_eq!(
+            test_infer(expr).unwrap().to_string(),
+            "ordered(arr = [I64], ) -> I64"
+        );
+        let src = "(x) => { mut i = 0; while i < x { i <- i + 1; if i == 3 { break; } }; i }";
+        assert_eq!(infer(src), Ok(()));
+        let src = "{ mut i = 0; loop { i <- i + 1; if i == 10 { break i * 2; } } }";
+        let (expr, _) = expr_parser().easy_parse(src).unwrap();
+        assert_eq!(test_infer(expr).unwrap().to_string(), "I64");
+        let src = "{ mut i = 0; loop { i <- i + 1; } }";
+        let (expr, _) = expr_parser().easy_parse(src).unwrap();
+        assert_eq!(test_infer(expr).unwrap().to_string(), "Never");
+    }
+    #[test]
+    fn jump_targets() {
+        assert_eq!(
+            infer("while true { break 1; }").unwrap_err().to_string(),
+            "mismatched types `Num` and `()`"
+        );
+        let err = infer("{ break; }").unwrap_err();
+        assert_eq!(err.to_string(), "`break` outside of a loop");
+        assert_eq!(err.span().map(Span::range), Some(2..7));
+        let err = infer("{ continue; }").unwrap_err();
+        assert_eq!(err.to_string(), "`continue` outside of a loop");
+        let err = infer("loop { f() => { break; }; }").unwrap_err();
+        assert_eq!(err.to_string(), "`break` outside of a loop");
+        assert_eq!(err.span().map(Span::range), Some(16..21));
+        let err = infer("{ return 1; }").unwrap_err();
+        assert_eq!(err.to_string(), "`return` outside of a function");
+        let src = "loop { for x in [1] { break; }; }";
+        let (expr, _) = expr_parser().easy_parse(src).unwrap();
+        assert_eq!(test_infer(expr).unwrap().to_string(), "Never");
+    }
+    #[test]
+    fn unify_rows() {
+        let infer_ty = |src| {
+            let (expr, rest) = expr_parser().easy_parse(src).unwrap();
+            assert_eq!(rest, "");
+            test_infer(expr).unwrap().to_string()
+        };
+        let src =
+            "{ f(t: (Num, *_), u: (*_, Bool)) => [t, u]; f((1, 'a', true), (2, 'b', false)) }";
+        assert_eq!(infer_ty(src), "[(I64, Char, Bool, )]");
+        let src = "{ f(t: (Num, *_), u: (*_, Bool)) => [t, u]; f((1, true), (2, 'b', false)) }";
+        assert_eq!(infer(src), Err(TypeError::MismatchArity));
+        let src = "{ f(t) => { (a, *_) = t; (*_, b) = t; a }; f((1, true)) }";
+        assert_eq!(infer_ty(src), "I64");
+        let src = "{ f(t) => { (a, *_) = t; (*_, b) = t; b }; f(('a', 2, true)) }";
+        assert_eq!(infer_ty(src), "Bool");
+        let src = "{
+            f(t) => { (a, *_) = t; (b, c, *_) = t; (*_, d) = t; [a, b, c, d] };
+            f((1, 2, 3, 4))
+        }";
+        assert_eq!(infer_ty(src), "[I64]");
+    }
+    #[test]
+    fn refutable_for() {
+        let src = "(arr) => for @val x in arr { x; }";
+        assert!(matches!(infer(src), Err(TypeError::RefutablePattern(_))));
+    }
+    #[test]
+    fn non_diverging_else() {
+        let src = "(x) => { @val y = x else { 0 }; y }";
+        let err = infer(src).unwrap_err();
+        assert_eq!(err.span().map(Span::range), Some(25..30));
+        assert_eq!(
+            err.to_string(),
+            "the `else` block of a declaration must diverge, with a jump or an expression of type `Never`",
+        );
+    }
+    #[test]
+    fn error_location() {
+        fn located(src: &str) -> (String, &str) {
+            let (statements, rest) = ast().easy_parse(src).unwrap();
+            assert_eq!(rest, "");
+            let err = crate::infer(statements).unwrap_err();
+            (err.to_string(), &src[err.span().unwrap().range()])
+        }
+        assert_eq!(
+            located("f(x) => x(x);"),
+            (
+                "infinite type, a type variable would contain itself".to_string(),
+                "x(x)"
+            ),
+        );
+        assert_eq!(
+            located("mod m { pub a = 1; }; b = m.c;"),
+            ("`c` is not declared".to_string(), "m.c"),
+        );
+        assert_eq!(
+            located("x = 1; y = [x, true];"),
+            ("mismatched types `Bool` and `I64`".to_string(), "[x, true]"),
+        );
+        assert_eq!(
+            located("alias A = (a, b);"),
+            ("type `a` is not declared".to_string(), "A"),
+        );
+    }
+    #[test]
+    fn refutable_declare() {
+        let src = "(x) => { @val y = x; y }";
+        let err = infer(src).unwrap_err();
+        assert!(matches!(err, TypeError::RefutablePattern(_)));
+        assert_eq!(err.span().map(Span::range), Some(9..15));
+    }
+    /// The code each warning about unreachable code points at
+    fn unreachable(src: &str) -> Vec<&str> {
+        infer_warnings(src)
+            .unwrap()
+            .into_iter()
+            .map(|warning| match warning {
+                TypeWarning::UnreachableCode(span) => &src[span.range()],
+                warning => panic!("expected unreachable code, found {warning:?}"),
+            })
+            .collect()
+    }
+    #[test]
+    fn unreachable_code() {
+        let src = "(x) => { return x; x }";
+        assert_eq!(unreachable(src), ["x"]);
+        let src = "(x) => { if x { return 1; } else { never }; 2 }";
+        assert_eq!(unreachable(src), ["2"]);
+        let src = "(x) => { if x { return 1; }; 2 }";
+        assert_eq!(infer_warnings(src), Ok(Vec::new()));
+    }
+    #[test]
+    fn unreachable_after_loop_jump() {
+        let src = "(arr) => for x in arr { break; x; }";
+        assert_eq!(unreachable(src), ["x"]);
+        let src = "(arr) => for x in arr { continue; x; }";
+        assert_eq!(unreachable(src), ["x"]);
+        let src = "loop { break 1; 2 }";
+        assert_eq!(unreachable(src), ["2"]);
+        // the jump leaves the loop, code after it is reachable
+        let src = "{ loop { break; }; 1 }";
+        assert_eq!(infer_warnings(src), Ok(Vec::new()));
+    }
+    fn infer_type(src: &str) -> String {
+        let (expr, rest) = expr_parser().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        test_infer(expr).unwrap().to_string()
+    }
+    #[test]
+    fn never_arms() {
+        let src = "(x) => match x { true => never, false => 1 }";
+        assert_eq!(infer_type(src), "ordered(x = Bool, ) -> I64");
+        let src = "(x) => match x { true => 1, false => return 2 }";
+        assert_eq!(infer_type(src), "ordered(x = Bool, ) -> I64");
+        let src = "(x) => if x { return \"a\"; } else { \"b\" }";
+        assert_eq!(infer_type(src), "ordered(x = Bool, ) -> Str");
+        let src = "(x) => match x { true => never, false => 1, _ => \"a\" }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "mismatched types `Str` and `Num`"
+        );
+    }
+    #[test]
+    fn never_annotated() {
+        let src = "(c) => { x: Num = if c { never } else { 1 }; x }";
+        assert_eq!(infer_type(src), "ordered(c = Bool, ) -> I64");
+        let src = "{ x: Num = never; x }";
+        assert_eq!(unreachable(src), ["x"]);
+        assert_eq!(infer_type(src), "Never");
+    }
+    #[test]
+    fn never_nested() {
+        let src = "(x) => { { { return x; }; }; 1 }";
+        assert_eq!(unreachable(src), ["1"]);
+        let src = "(c) => if c { { { never } } } else { 1 }";
+        assert_eq!(infer_type(src), "ordered(c = Bool, ) -> I64");
+        let src = "() => { { never }; }";
+        assert_eq!(infer_type(src), "() -> Never");
+    }
+    #[test]
+    fn never() {
+        let src = "(c) => {
+            fail() => never;
+            x: Str = if c { fail() } else { \"a\" };
+            y: Bool = if c { true } else { fail() };
+            x
+        }";
+        let (expr, _) = expr_parser().easy_parse(src).unwrap();
+        assert_eq!(
+            test_infer(expr).unwrap().to_string(),
+            "ordered(c = Bool, ) -> Str"
+        );
+        let src = "{ fail() => never; x: Str = fail(); x }";
+        let (expr, _) = expr_parser().easy_parse(src).unwrap();
+        assert_eq!(test_infer(expr).unwrap().to_string(), "Never");
+        let src = "(x) => if x { never } else { 1 }";
+        let (expr, _) = expr_parser().easy_parse(src).unwrap();
+        assert_eq!(
+            test_infer(expr).unwrap().to_string(),
+            "ordered(x = Bool, ) -> I64"
+        );
+        let src = "{ x = if true { never } else { 1 }; y: Str = x; y }";
+        assert!(matches!(
+            infer(src),
+            Err(TypeError::MismatchAnnotation { .. })
+        ));
+    }
+    #[test]
+    fn annotated_expr() {
+        let (expr, _) = expr_parser().easy_parse("[] : [Bool]").unwrap();
+        assert_eq!(test_infer(expr).unwrap().to_string(), "[Bool]");
+    }
+    #[test]
+    fn annotated_declare() {
+        let src = "{ x : Num = true; x }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "mismatched type, expected `Num` from annotation, found `Bool`",
+        );
+    }
+    #[test]
+    fn annotated_return() {
+        let src = "{ f(x : Num) -> Bool => x; f }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "mismatched type, expected `Bool` from annotation, found `Num`",
+        );
+    }
+    #[test]
+    fn annotated_param() {
+        assert_eq!(
+            infer("{ f(x : Str) => x; f(1) }").unwrap_err().to_string(),
+            "mismatched types `Num` and `Str`"
+        );
+        assert_eq!(
+            infer("{ f(x : a, y : a) => x; f(1, true) }")
+                .unwrap_err()
+                .to_string(),
+            "mismatched types `Bool` and `Num`"
+        );
+        assert_eq!(infer("{ f(x : a, y : a) => x; f(1, 2) }"), Ok(()));
+    }
+    #[test]
+    fn unbound_type() {
+        assert_eq!(
+            infer("1 : Foo").unwrap_err().to_string(),
+            "type `Foo` is not declared"
+        );
+    }
+    #[test]
+    fn generics() {
+        let (expr, _) = expr_parser()
+            .easy_parse("{ :(a): id(x : a) -> a => x; id }")
+            .unwrap();
+        assert_eq!(
+            test_infer(expr).unwrap().to_string(),
+            "ordered(x = a#2, ) -> a#2"
+        );
+    }
+    #[test]
+    fn concrete_generic() {
+        let src = "{ :(a): f(x : a) -> a => 1; f }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "mismatched type, expected generic type `a`, found `Num`",
+        );
+        let src = "{ :(a, b): f(x : a, y : b) -> a => y; f }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "mismatched type, expected generic type `b`, found `a#1`",
+        );
+    }
+    #[test]
+    fn escaped_generic() {
+        let src = "(y) => { :(a): f(x : a) => { _ = [x, y]; x }; f }";
+        assert_eq!(infer(src), Err(TypeError::EscapedRigid(Atom::from("a"))));
+    }
+    #[test]
+    fn alias() {
+        let (expr, _) = expr_parser()
+            .easy_parse("{ alias Option(a) = @val a | @none; x : Option(Num) = @none; x }")
+            .unwrap();
+        assert_eq!(test_infer(expr).unwrap().to_string(), "Option(I64, )");
+        let src = "{ x : Pair(Bool) = (1, 2); alias Pair(a) = (a, a); x }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "mismatched type, expected `Pair(Bool, )` from annotation, found `(Num, Num, )`",
+        );
+    }
+    #[test]
+    fn alias_arity() {
+        assert_eq!(
+            infer("{ alias Id(a) = a; x : Id = 1; x }")
+                .unwrap_err()
+                .to_string(),
+            "mismatched number of type arguments, `Id` takes 1, found 0"
+        );
+        assert_eq!(
+            infer("{ alias Id = a; x : Id = 1; x }")
+                .unwrap_err()
+                .to_string(),
+            "type `a` is not declared"
+        );
+    }
+    #[test]
+    fn cyclic_alias() {
+        let src = "{ alias List(a) = @cons (a, List(a)) | @nil; 1 }";
+        assert_eq!(infer(src), Err(TypeError::CyclicAlias(Atom::from("List"))));
+        let src = "{ alias A = [B]; alias B = (A, ); 1 }";
+        assert!(matches!(infer(src), Err(TypeError::CyclicAlias(_))));
+    }
+    #[test]
+    fn module() {
+        let src = "{
+            mod math {
+                pub pi = 3;
+                pub id(x) => x;
+                pub mod consts { pub e = 2; };
+            }
+            (math.id(math.pi), math.id(true), math.consts.e)
+        }";
+        assert_eq!(infer(src), Ok(()));
+        let src = "{ math = mod (pub pi = 3;); math.tau }";
+        assert_eq!(infer(src), Err(TypeError::UnboundVar(Atom::from("tau"))));
+        let src = "{ mod math { pub mut pi = 3; }; math.pi <- 4; }";
+        assert_eq!(infer(src), Err(TypeError::AssignedImm));
+    }
+    #[test]
+    fn private_member() {
+        let src = "{ mod math { pi = 3; pub tau = pi * 2; }; math.pi }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "`pi` is private, it is declared in module `math`",
+        );
+        let src = "{ mod a { mod b { pub x = 1; }; pub y = b.x; }; a.y }";
+        assert_eq!(infer(src), Ok(()));
+        let src = "{ mod a { mod b { pub x = 1; }; }; a.b.x }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "`b` is private, it is declared in module `a`",
+        );
+    }
+    #[test]
+    fn restricted_member() {
+        let src = "{
+            mod app {
+                mod db { pub(app) query = 1; };
+                pub run = db.query;
+            };
+            app.run
+        }";
+        assert_eq!(infer(src), Ok(()));
+        let src = "{
+            mod app { pub mod db { pub(app) query = 1; }; };
+            app.db.query
+        }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "`query` is private, it is declared in module `app.db`",
+        );
+    }
+    #[test]
+    fn newtype() {
+        let src = "{ newtype Point(x: Num, y: Num); p = Point(x = 1, y = 2); p.x + p.y }";
+        assert_eq!(infer(src), Ok(()));
+        let (expr, _) = expr_parser()
+            .easy_parse("{ :(a): newtype Pair(first: a, second: a); Pair(1, 2) }")
+            .unwrap();
+        assert_eq!(test_infer(expr).unwrap().to_string(), "Pair(I64, )");
+        let src = "{ newtype A(x: Num); newtype B(x: Num); f(a: A) => a.x; f(B(1)) }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "mismatched types `B` and `A`"
+        );
+        let src = "{ newtype A(x: Num); f(a: (x: Num)) => a.x; f(A(1)) }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "mismatched types `A` and `(x : Num, )`"
+        );
+        let src = "{ newtype Box(value: a); Box(1) }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "type `a` is not declared"
+        );
+        let src = "{ newtype A(x: Num); a = A(1); a.y }";
+        assert_eq!(infer(src).unwrap_err().to_string(), "`A` has no field `y`",);
+    }
+    #[test]
+    fn private_field() {
+        let src = "{
+            mod geo {
+                pub newtype Point(x: Num, #y: Num);
+                pub origin = Point(x = 0, #y = 0);
+                pub height(point: Point) => point.#y;
+            };
+            geo.height(geo.origin) + geo.origin.x
+        }";
+        assert_eq!(infer(src), Ok(()));
+        let src = "{
+            mod geo { pub newtype Point(x: Num, #y: Num); pub origin = Point(0, 0); };
+            geo.origin.#y
+        }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "field `#y` of `Point` is private",
+        );
+        let src = "{ mod geo { pub newtype Point(x: Num, #y: Num); }; geo.Point(0, 0) }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "`Point` is private, it is declared in module `geo`",
+        );
+        let src = "{
+            mod app {
+                mod geo { pub newtype Point(x: Num, pub(app) #y: Num); };
+                pub y = geo.Point(0, 1).#y;
+            };
+            app.y
+        }";
+        assert_eq!(infer(src), Ok(()));
+    }
+    #[test]
+    fn cyclic_newtype() {
+        let src = "{ newtype A(x: Num, next: @some B | @none); newtype B(a: A); 1 }";
+        assert_eq!(infer(src), Err(TypeError::CyclicNewtype(Atom::from("A"))));
+    }
+    #[test]
+    fn unloaded_module() {
+        let (statements, _) = ast().easy_parse("mod math;").unwrap();
+        let err = crate::infer(statements).unwrap_err();
+        assert_eq!(err.span().map(Span::range), Some(4..8));
+        assert_eq!(
+            err.unlocated(),
+            TypeError::UnloadedModule(Atom::from("math")),
+        );
+    }
+    #[test]
+    fn mutability_polymorphism() {
+        let src = "{
+            get_name(user) => &user^.name;
+            mut a = (name = 1);
+            b = (name = true);
+            r = get_name(&a);
+            r^ <- 2;
+            (r, get_name(&b))
+        }";
+        let (expr, _) = expr_parser().easy_parse(src).unwrap();
+        assert_eq!(
+            test_infer(expr).unwrap().to_string(),
+            "(&:mut I64, &:imm Bool, )"
+        );
+        let src = "{ get_name(user) => &user^.name; a = (name = 1); r = get_name(&a); r^ <- 2; }";
+        assert_eq!(infer(src), Err(TypeError::AssignedImm));
+    }
+    #[test]
+    fn generalized_mutability() {
+        let (statements, _) = ast().easy_parse("get_name(user) => &user^.name;").unwrap();
+        let typed = crate::infer(statements).unwrap();
+        let Statement::FunDeclare(fun) = &typed[0] else {
+            panic!("expected a function declaration");
+        };
+        let kinds: Vec<_> = fun.ty.for_all.iter().map(|var| var.kind).collect();
+        assert!(kinds.contains(&Kind::MutType));
+    }
+    #[test]
+    fn required_mut() {
+        let src = "{ set(r) => { r^ <- 1; }; x = 1; set(&x) }";
+        assert_eq!(
+            infer(src).unwrap_err().to_string(),
+            "mismatched mutability, an immutable reference is used where a mutable one is required",
+        );
+        assert_eq!(
+            infer("{ set(r) => { r^ <- 1; }; mut x = 1; set(&x) }"),
+            Ok(())
+        );
+    }
+    #[test]
+    fn assign_through_ref() {
+        assert_eq!(infer("{ mut x = 1; r = &x; r^ <- 2; }"), Ok(()));
+        assert_eq!(
+            infer("{ x = 1; r = &x; r^ <- 2; }"),
+            Err(TypeError::AssignedImm),
+        );
+        let src = "(user, new_name) => { &(ref name, *_) = user; name^ <- >new_name; }";
+        assert_eq!(infer(src), Ok(()));
+        let src = "{ user = (name = 1); (name = ref name) = user; name^ <- 2; }";
+        assert_eq!(infer(src), Err(TypeError::AssignedImm));
+    }
+    #[test]
+    fn clone() {
+        assert_eq!(infer("{ mut xs = [1]; ys = clone xs; }"), Ok(()));
+        assert!(matches!(
+            infer("{ f(x) => x; g = clone f; }"),
+            Err(TypeError::NotClone(_)),
+        ));
+        assert!(matches!(
+            infer("{ mut x = 1; r = &x; r^ <- 2; s = clone r; }"),
+            Err(TypeError::NotClone(_)),
+        ));
+        assert_eq!(infer("{ x = [1]; r = clone &x; }"), Ok(()));
+    }
+    #[test]
+    fn implicit_copy() {
+        assert_eq!(infer("{ mut x = (a = 1, b = true); y = x; }"), Ok(()));
+        assert_eq!(infer("{ xs = [1]; ys = xs; }"), Ok(()));
+        assert_eq!(infer("{ mut xs = [1]; ys = >xs; }"), Ok(()));
+        assert_eq!(
+            infer("{ mut xs = [1]; ys = xs; }").unwrap_err().to_string(),
+            "`[Num]` can't be implicitly copied, clone it with `clone` or move it with `>`",
+        );
+        assert!(matches!(
+            infer("{ xs = [1]; mut ys = xs; }"),
+            Err(TypeError::NotCopy(_)),
+        ));
+        assert!(matches!(
+            infer("{ mut xs = [1]; r = &xs; r^ <- [2]; ys = r^; }"),
+            Err(TypeError::NotCopy(_)),
+        ));
+    }
+    #[test]
+    fn newtype_copy() {
+        assert_eq!(
+            infer("{ newtype Point(x: Num); mut p = Point(1); q = p; }"),
+            Ok(()),
+        );
+        assert!(matches!(
+            infer("{ newtype Wrap(xs: [Num]); mut w = Wrap([1]); v = w; }"),
+            Err(TypeError::NotCopy(_)),
+        ));
+    }
+    #[test]
+    fn copy_constraint() {
+        assert_eq!(infer("{ dup(x) => { mut y = x; y }; dup(1) }"), Ok(()));
+        assert!(matches!(
+            infer("{ dup(x) => { mut y = x; y }; dup([1]) }"),
+            Err(TypeError::NotCopy(_)),
+        ));
+    }
+    fn infer_program(src: &str) -> Result<Vec<Statement<Type>>, TypeError> {
+        let (statements, rest) = ast().easy_parse(src).unwrap();
+        assert_eq!(rest, "");
+        crate::infer(statements).map_err(TypeError::unlocated)
+    }
+    fn scheme(statements: &[Statement<Type>], name: &str) -> String {
+        statements
+            .iter()
+            .find_map(|statement| match statement {
+                Statement::FunDeclare(fun) if fun.ident == *name => Some(fun.ty.to_string()),
+                _ => None,
+            })
+            .unwrap()
+    }
+    const SHOW: &str = "
+        trait Show(a) { show(x: &a) -> Num; }
+        impl Show(Num) { show(x) => x^; }
+        given Show(a): impl Show([a]) { show(xs) => show(&xs^[0]); }
+    ";
+    #[test]
+    fn trait_constraint() {
+        let src = format!("{SHOW} describe(x) => show(&x) + 1;");
+        let statements = infer_program(&src).unwrap();
+        let scheme = scheme(&statements, "describe");
+        assert!(scheme.contains("given Show(a#"), "{scheme}");
+        let src = format!("{SHOW} x = show(&[[1]]);");
+        assert_eq!(infer_program(&src).map(|_| ()), Ok(()));
+    }
+    #[test]
+    fn declared_given() {
+        let src = format!("{SHOW} given Show(a): f(x: a) => show(&x); y = f([1]);");
+        let statements = infer_program(&src).unwrap();
+        let scheme = scheme(&statements, "f");
+        assert!(scheme.contains("given Show(a#"), "{scheme}");
+        let src = format!("{SHOW} given Show(a): :(a): f(x: a) => show(&x);");
+        assert_eq!(infer_program(&src).map(|_| ()), Ok(()));
+        let src = format!("{SHOW} given Show(a): f(x: a) => 1; y = f(true);");
+        assert!(matches!(infer_program(&src), Err(TypeError::NoInstance(_)),));
+        let src = "trait Show(a) { show(x: &a) -> Num; } :(a): f(x: a) => show(&x);";
+        assert!(matches!(infer_program(src), Err(TypeError::NoInstance(_))));
+    }
+    #[test]
+    fn no_instance() {
+        let src = format!("{SHOW} x = show(&true);");
+        assert_eq!(
+            infer_program(&src).unwrap_err().to_string(),
+            "no implementation of `Show(Bool)`",
+        );
+        let src = "
+            trait Show(a) { show(x: &a) -> Num; }
+            impl Show([a]) { show(xs) => show(&xs^[0]); }
+        ";
+        assert!(matches!(infer_program(src), Err(TypeError::NoInstance(_)),));
+    }
+    #[test]
+    fn overlapping_impls() {
+        let src = format!("{SHOW} impl Show([Num]) {{ show(xs) => 0; }}");
+        assert!(matches!(
+            infer_program(&src),
+            Err(TypeError::OverlappingImpls(_, _)),
+        ));
+    }
+    #[test]
+    fn impl_methods() {
+        let src = "trait Show(a) { show(x: &a) -> Num; } impl Show(Num) {}";
+        assert!(matches!(
+            infer_program(src),
+            Err(TypeError::MissingMethod { .. }),
+        ));
+        let src = "trait Show(a) { show(x: &a) -> Num; } impl Show(Num) { show(x) => true; }";
+        assert_eq!(
+            infer_program(src).unwrap_err().to_string(),
+            "mismatched types `Bool` and `Num`"
+        );
+    }
+    #[test]
+    fn user_equality() {
+        let src = "
+            alias Point = (x: Num, y: Num);
+            impl Eq(Point) { equal(a, b) => a^.x == b^.x && a^.y == b^.y; }
+            same = (x = 1, y = 2) == (x = 1, y = 2);
+            different = (x = 1, y = 2) != (x = 2, y = 1);
+        ";
+        assert_eq!(infer_program(src).map(|_| ()), Ok(()));
+        assert_eq!(infer("[1] ++ [2]"), Ok(()));
+        // field access needs the type of the parameters from the trait
+        let src = "
+            newtype A(x: Num);
+            impl Eq(A) { equal(a, b) => a^.x == b^.x; }
+            same = A(x = 1) == A(x = 1);
+        ";
+        assert_eq!(infer_program(src).map(|_| ()), Ok(()));
+    }
+    #[test]
+    fn builtin_equality() {
+        assert_eq!(infer("true == false"), Ok(()));
+        assert_eq!(infer("[1] == [1]"), Ok(()));
+        assert_eq!(infer("[[true]] != [[false]]"), Ok(()));
+        assert_eq!(infer("[\"a\"] == [\"b\"]"), Ok(()));
+        assert!(matches!(
+            infer("[(a = 1)] == [(a = 1)]"),
+            Err(TypeError::NoInstance(_))
+        ));
+        let src = "(a, b) => [a] == [b]";
+        let (expr, _) = expr_parser().easy_parse(src).unwrap();
+        let ty = test_infer(expr).unwrap();
+        assert_eq!(ty.to_string(), "ordered(a = I64, b = I64, ) -> Bool");
+    }
+    #[test]
+    fn elaborate() {
+        let src = "
+            alias Point = (x: Num, y: Num);
+            impl Eq(Point) { equal(a, b) => a^.x == b^.x; }
+            member(xs, x) => xs[0] == x;
+            differs(a: Point, b) => a != b;
+            same = [[1]] == [[2]] ++ [[3]];
+            found = member([(x = 1, y = 2)], (x = 1, y = 2));
+            points = [(x = 1, y = 2)] == [(x = 1, y = 2)];
+        ";
+        let (statements, _) = ast().easy_parse(src).unwrap();
+        let elaborated: Vec<_> = crate::infer_elaborated(statements)
+            .unwrap()
+            .into_iter()
+            .map(|statement| statement.erase_types())
+            .collect();
+        let names: Vec<_> = elaborated
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::FunDeclare(fun) => Some(fun.ident.to_string()),
+                _ => None,
+            })
+            .collect();
+        // the built-in dictionary of arrays is declared first, equality of
+        // numbers is primitive and needs none, implementations become
+        // functions returning their dictionary in place
+        assert_eq!(names, ["Eq#6", "Eq#7", "member", "differs"]);
+        // both take the dictionary of what they compare
+        for name in ["member", "Eq#6"] {
+            let fun = elaborated
+                .iter()
+                .find_map(|statement| match statement {
+                    Statement::FunDeclare(fun) if fun.ident == *name => Some(fun),
+                    _ => None,
+                })
+                .unwrap();
+            let PatternKind::Param(params) = &fun.fun.param.pattern else {
+                unreachable!();
+            };
+            assert!(params[0].var.ident.starts_with("Eq#"));
+        }
+    }
+    fn value(statements: &[Statement<Type>], name: &str) -> String {
+        statements
+            .iter()
+            .find_map(|statement| match statement {
+                Statement::Declare(declare) => match &declare.pattern.pattern {
+                    PatternKind::Var(var) if var.ident == *name => {
+                        Some(declare.pattern.ty.to_string())
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .unwrap()
+    }
+    #[test]
+    fn number_repr() {
+        let src = "
+            half(x) => x / 2;
+            a = 1;
+            b = 2.5;
+            c = half(3);
+            d: U8 = 200;
+            g = d + 1;
+            e = [1, 2][a];
+            f = 7 // 2;
+        ";
+        let statements = infer_program(src).unwrap();
+        assert_eq!(scheme(&statements, "half"), "ordered(x = Num, ) -> Num");
+        for (name, ty) in [
+            ("a", "I64"),
+            ("b", "F64"),
+            ("c", "F64"),
+            ("g", "U8"),
+            ("e", "I64"),
+            ("f", "I64"),
+        ] {
+            assert_eq!(value(&statements, name), ty, "{name}");
+        }
+    }
+    #[test]
+    fn default_repr() {
+        let src = "
+            a = 9223372036854775807;
+            b = 9223372036854775808;
+            c = -9223372036854775807;
+            d = 1.0e300;
+            e = [1][0];
+            f = 3 / 4;
+            g = -18446744073709551615;
+        ";
+        let statements = infer_program(src).unwrap();
+        for (name, ty) in [
+            ("a", "I64"),
+            ("b", "U64"),
+            ("c", "I64"),
+            ("d", "F64"),
+            ("e", "I64"),
+            ("f", "F64"),
+            ("g", "F64"),
+        ] {
+            assert_eq!(value(&statements, name), ty, "{name}");
+        }
+        assert_eq!(infer_type("18446744073709551615"), "U64");
+        assert_eq!(infer_type("0.5"), "F64");
+        assert_eq!(infer_type("(x) => x * 2"), "ordered(x = I64, ) -> I64");
+        assert_eq!(infer_type("(x: U8) => x * 2"), "ordered(x = U8, ) -> U8");
+    }
+    #[test]
+    fn conflicting_repr() {
+        assert_eq!(
+            infer_program("x: U8 = 1; y: I32 = 2; z = x + y;")
+                .unwrap_err()
+                .to_string(),
+            "mismatched types `I32` and `U8`",
+        );
+        assert_eq!(
+            infer_program("x = [1][-18446744073709551615];")
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "mismatched number representation, no integer representation holds both negative numbers and {}",
+                u64::MAX,
+            ),
+        );
+        let fractional = "a fractional number is used where an integer is required";
+        let float = "`F64` is used where an integer is required";
+        for (src, message) in [
+            ("x = [1][1.5];", fractional),
+            ("x = 0.5; y = [1, 2][x];", float),
+            ("arr = [1, 2]; x = arr.len + 0.5;", fractional),
+            ("arr = [1, 2]; x = arr[0.5..];", fractional),
+        ] {
+            assert_eq!(
+                infer_program(src).unwrap_err().to_string(),
+                format!("mismatched number representation, {message}"),
+                "{src}",
+            );
+        }
+    }
+    #[test]
+    fn mismatched_repr() {
+        for (src, message) in [
+            ("x: U8 = 300;", "`U8` can't hold 300"),
+            ("x: U8 = -1;", "`U8` can't hold negative numbers"),
+            ("x: I8 = 1.5;", "`I8` can't hold fractional numbers"),
+            (
+                "x = [1][2 / 3];",
+                "a fractional number is used where an integer is required",
+            ),
+        ] {
+            assert_eq!(
+                infer_program(src).unwrap_err().to_string(),
+                format!("mismatched number representation, {message}"),
+            );
+        }
+        assert_eq!(
+            infer_program("x: U8 = 1; y: I64 = x;")
+                .unwrap_err()
+                .to_string(),
+            "mismatched type, expected `I64` from annotation, found `U8`",
+        );
+    }
+    #[test]
+    fn strings() {
+        let src = r#"
+            greet(name: Str) -> Str => "hello " ++ name ++ "!";
+            a = greet("world");
+            b = bytes(a);
+            c = 'ß' == 's';
+            d = code('a');
+            e = chars("abc");
+            f = match from_utf8(b) { @val s => s, @none => "" };
+        "#;
+        let statements = infer_program(src).unwrap();
+        for (name, ty) in [
+            ("a", "Str"),
+            ("b", "[U8]"),
+            ("d", "U32"),
+            ("e", "[Char]"),
+            ("f", "Str"),
+        ] {
+            assert_eq!(value(&statements, name), ty, "{name}");
+        }
+        assert_eq!(
+            infer_program(r#"x = "\xff";"#).unwrap_err().to_string(),
+            r#"string literal "\xff" is not valid UTF-8"#,
+        );
+        assert_eq!(
+            infer_program(r#"x: [Num] = "a";"#).unwrap_err().to_string(),
+            "mismatched type, expected `[Num]` from annotation, found `Str`",
+        );
+        assert_eq!(
+            infer("'a' + 1").unwrap_err().to_string(),
+            "mismatched types `Char` and `Num`"
+        );
+    }
 }
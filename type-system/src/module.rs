@@ -0,0 +1,207 @@
+use crate::{
+    annotation::declare_aliases,
+    expr::{infer_statement, unit},
+    newtype::{constructor_visibility, declare_newtypes},
+    traits::{declare_traits, instantiate_use},
+    ty::{
+        num::{NumType, Repr},
+        Env, FreeVars, Kind, Subs, Substitutable, Unifiable, Var, VarState,
+    },
+    Type, TypeError,
+};
+use hir::{
+    expr::{Expr, ExprKind, FieldAccess, PlaceExpr},
+    pattern,
+    statement::{Module, ModuleDeclare, Statement},
+    visit::Visit,
+    Atom,
+};
+
+pub(super) struct PatternNames(pub(super) Vec<Atom>);
+impl Visit<()> for PatternNames {
+    fn visit_var(&mut self, var: &pattern::Var) {
+        self.0.push(var.ident.clone());
+    }
+}
+/// Names a statement declares as values, excluding functions
+pub(super) fn declared_values(statement: &Statement<()>) -> Vec<Atom> {
+    match statement {
+        Statement::Declare(declare) => {
+            let mut names = PatternNames(Vec::new());
+            names.visit_pattern(&declare.pattern);
+            names.0
+        }
+        _ => Vec::new(),
+    }
+}
+/// Gives the numbers in the types of values declared at the top of a module
+/// their default representation, such a value has a single representation
+/// fixed by its own declaration
+pub(super) fn default_declared_nums(
+    names: &[Atom],
+    subs: &mut Subs,
+    env: &mut Env,
+    var_state: &mut VarState,
+) -> Result<(), TypeError> {
+    let mut num_subs = Subs::new();
+    for name in names {
+        let Some(mut scheme) = env.get_ty(Var::new_bare(name.clone())) else {
+            continue;
+        };
+        scheme.substitute(var_state.bindings())?;
+        for var in scheme.free_vars() {
+            if var.kind == Kind::NumType {
+                let repr = var_state.default_repr(&var.var).unwrap_or(Repr::I64);
+                NumType::Var(var.var).unify_with(NumType::Repr(repr), &mut num_subs, var_state)?;
+            }
+        }
+    }
+    env.substitute(&num_subs)?;
+    subs.compose_with(num_subs)
+}
+/// Infers the statements of a module, returning the environment of its
+/// members
+pub(super) fn infer_module(
+    statements: Vec<Statement<()>>,
+    subs: &mut Subs,
+    var_state: &mut VarState,
+    env: &Env,
+) -> Result<(Vec<Statement<Type>>, Env), TypeError> {
+    let mut module_env = env.clone();
+    declare_aliases(&statements, &mut module_env)?;
+    declare_newtypes(&statements, &mut module_env, var_state)?;
+    declare_traits(&statements, &module_env, var_state)?;
+    let mut members = Vec::new();
+    let mut typed_statements = Vec::with_capacity(statements.len());
+    for statement in statements {
+        match &statement {
+            Statement::Declare(declare) => {
+                let mut names = PatternNames(Vec::new());
+                names.visit_pattern(&declare.pattern);
+                members.extend(
+                    names
+                        .0
+                        .into_iter()
+                        .map(|name| (name, declare.visibility.clone())),
+                );
+            }
+            Statement::FunDeclare(fun) => {
+                members.push((fun.ident.clone(), fun.visibility.clone()));
+            }
+            Statement::NewtypeDeclare(newtype) => {
+                members.push((newtype.ident.clone(), constructor_visibility(newtype)));
+            }
+            Statement::ModuleDeclare(module) => {
+                members.push((module.ident.clone(), module.visibility.clone()));
+            }
+            Statement::TraitDeclare(trait_declare) => {
+                members.extend(
+                    trait_declare
+                        .methods
+                        .iter()
+                        .map(|method| (method.ident.clone(), trait_declare.visibility.clone())),
+                );
+            }
+            Statement::AliasDeclare(_) | Statement::ImplDeclare(_) | Statement::Expr(_) => (),
+        }
+        let names = declared_values(&statement);
+        typed_statements.push(infer_statement(
+            subs,
+            &mut module_env,
+            var_state,
+            statement,
+        )?);
+        default_declared_nums(&names, subs, &mut module_env, var_state)?;
+    }
+    module_env.substitute(var_state.bindings())?;
+    Ok((typed_statements, module_env.members(members)))
+}
+pub(super) fn infer_module_declare(
+    module: ModuleDeclare<()>,
+    subs: &mut Subs,
+    var_state: &mut VarState,
+    env: &mut Env,
+) -> Result<ModuleDeclare<Type>, TypeError> {
+    let statements = match module.module {
+        Module::Inline(statements) => statements,
+        Module::File(_) => return Err(TypeError::UnloadedModule(module.ident)),
+    };
+    let mut module_env = env.clone();
+    module_env.enter_module(module.ident.clone());
+    let (typed_statements, members) =
+        infer_module(statements.into(), subs, var_state, &module_env)?;
+    env.insert_module(module.ident.clone(), members);
+    Ok(ModuleDeclare {
+        visibility: module.visibility,
+        ident: module.ident,
+        span: module.span,
+        module: Module::Inline(typed_statements.into()),
+    })
+}
+fn check_visibility(module: &Env, name: &Atom, env: &Env) -> Result<(), TypeError> {
+    if module.is_visible(name, env.path()) {
+        Ok(())
+    } else {
+        Err(TypeError::PrivateMember {
+            name: name.clone(),
+            module: module.path().into(),
+        })
+    }
+}
+/// Resolves a path of field accesses rooted on a module name such as
+/// `math.consts`, returns `None` if it's not a module
+pub(super) fn module_path<'a>(expr: &Expr<()>, env: &'a Env) -> Result<Option<&'a Env>, TypeError> {
+    match &expr.expr {
+        ExprKind::Place(PlaceExpr::Var(name, _)) => Ok(env.get_module(name)),
+        ExprKind::Place(PlaceExpr::FieldAccess(access)) => {
+            let Some(module) = module_path(&access.expr, env)? else {
+                return Ok(None);
+            };
+            match module.get_module(&access.name) {
+                Some(inner) => {
+                    check_visibility(module, &access.name, env)?;
+                    Ok(Some(inner))
+                }
+                None => Ok(None),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+// module paths have no value of their own, they're typed as unit
+fn typed_module_path(expr: Expr<()>) -> Expr<Type> {
+    let span = expr.span;
+    let place = match expr.expr {
+        ExprKind::Place(PlaceExpr::Var(name, span)) => PlaceExpr::Var(name, span),
+        ExprKind::Place(PlaceExpr::FieldAccess(access)) => PlaceExpr::FieldAccess(FieldAccess {
+            expr: Box::new(typed_module_path(*access.expr)),
+            name: access.name,
+        }),
+        _ => unreachable!(),
+    };
+    Expr {
+        expr: ExprKind::Place(place),
+        ty: unit(),
+        span,
+    }
+}
+pub(super) fn infer_module_member(
+    access: FieldAccess<()>,
+    module: &Env,
+    var_state: &mut VarState,
+    env: &Env,
+) -> Result<(Type, FieldAccess<Type>), TypeError> {
+    let scheme = module
+        .get_scheme_mut(&Var::new_bare(access.name.clone()))
+        .ok_or_else(|| TypeError::UnboundVar(access.name.clone()))?
+        .clone();
+    check_visibility(module, &access.name, env)?;
+    // the module path stands for the member, there's no other use there
+    Ok((
+        instantiate_use(access.expr.span, scheme, var_state)?,
+        FieldAccess {
+            expr: Box::new(typed_module_path(*access.expr)),
+            name: access.name,
+        },
+    ))
+}
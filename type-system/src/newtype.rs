@@ -0,0 +1,277 @@
+//! Nominal types declared with `newtype`. These are declared upfront for the
+//! whole scope they're in like aliases, but a newtype is only equal to itself
+//! even if another type has the same fields. Its name is also its
+//! constructor.
+//!
+//! Fields named with a leading `#` are private to the declaring module unless
+//! declared with another visibility, the constructor is then just as private.
+
+use crate::{
+    annotation::{annotation_type, references},
+    ty::{
+        cons::{Cons, Newtype, OrderedAnd},
+        is_visible, Env, FreeVars, Kind, KindedVar, Scheme, SchemeMut, Subs, Substitutable, Type,
+        TypeError, Var, VarState,
+    },
+};
+use hir::{
+    statement::{NewtypeDeclare, Statement, Visibility},
+    ty::TypeExpr,
+    Atom, Span,
+};
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) struct NewtypeDef {
+    span: Span,
+    name: Atom,
+    params: Vec<Var>,
+    fields: Vec<(Atom, Type, Visibility)>,
+    /// Path of the module it's declared in
+    path: Box<[Atom]>,
+}
+impl NewtypeDef {
+    pub(super) fn arity(&self) -> usize {
+        self.params.len()
+    }
+    /// Type of each field with `args` in place of the parameters
+    pub(super) fn field_types(&self, args: &[Type]) -> Result<Vec<Type>, TypeError> {
+        let subs = self.subs(args);
+        self.fields
+            .iter()
+            .map(|(_, ty, _)| {
+                let mut ty = ty.clone();
+                ty.substitute(&subs)?;
+                Ok(ty)
+            })
+            .collect()
+    }
+    fn subs(&self, args: &[Type]) -> Subs {
+        let mut subs = Subs::new();
+        for (param, arg) in self.params.iter().zip(args) {
+            subs.insert_type(param.clone(), arg.clone());
+        }
+        subs
+    }
+}
+fn declared_id(span: Span, var_state: &VarState) -> Option<usize> {
+    var_state
+        .newtypes
+        .iter()
+        .position(|declared| declared.span == span)
+}
+/// Brings every newtype declared in a block or module into `env` and
+/// `var_state`, newtypes are visible throughout the whole block
+pub(super) fn declare_newtypes(
+    statements: &[Statement<()>],
+    env: &mut Env,
+    var_state: &mut VarState,
+) -> Result<(), TypeError> {
+    let newtypes: Vec<_> = statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::NewtypeDeclare(newtype) => Some(newtype),
+            _ => None,
+        })
+        .collect();
+    // every name is declared first so fields may refer to any of them
+    let mut undeclared = Vec::new();
+    for newtype in &newtypes {
+        // the same declaration is met again when its module is inferred
+        let id = match declared_id(newtype.span, var_state) {
+            Some(id) => id,
+            None => {
+                let params = newtype
+                    .generics
+                    .iter()
+                    .map(|name| var_state.new_named(name.clone()))
+                    .collect();
+                var_state.newtypes.push(NewtypeDef {
+                    span: newtype.span,
+                    name: newtype.ident.clone(),
+                    params,
+                    fields: Vec::new(),
+                    path: env.path().into(),
+                });
+                undeclared.push(*newtype);
+                var_state.newtypes.len() - 1
+            }
+        };
+        env.insert_newtype(newtype.ident.clone(), id);
+    }
+    check_cycles(&newtypes, env)?;
+    for newtype in undeclared {
+        let id = declared_id(newtype.span, var_state).unwrap();
+        let params = var_state.newtypes[id].params.clone();
+        let mut newtype_env = env.clone();
+        for (name, var) in newtype.generics.iter().zip(&params) {
+            newtype_env.insert_type_var(
+                name.clone(),
+                KindedVar {
+                    kind: Kind::Type,
+                    var: var.clone(),
+                },
+            );
+        }
+        let mut fields = Vec::with_capacity(newtype.fields.len());
+        for field in newtype.fields.iter() {
+            let ty = annotation_type(&field.ty, var_state, &mut newtype_env)
+                .map_err(|err| err.at(newtype.span))?;
+            // a `Num` field has a single representation throughout the program
+            if ty
+                .free_vars()
+                .into_iter()
+                .any(|var| var.kind != Kind::NumType && !params.contains(&var.var))
+            {
+                let mut vars = Vec::new();
+                references(&field.ty, &mut vars, &mut Vec::new());
+                let name = vars
+                    .into_iter()
+                    .find(|var| !newtype.generics.contains(var))
+                    .cloned()
+                    .unwrap_or_else(|| Atom::from("_"));
+                return Err(TypeError::UnboundType(name).at(newtype.span));
+            }
+            fields.push((field.name.clone(), ty, field.visibility.clone()));
+        }
+        var_state.newtypes[id].fields = fields;
+    }
+    Ok(())
+}
+// names of the types an annotation refers to, aliases are expanded
+fn dependencies<'a>(ty: &'a TypeExpr, env: &'a Env, names: &mut Vec<&'a Atom>) {
+    let mut referenced = Vec::new();
+    references(ty, &mut Vec::new(), &mut referenced);
+    for name in referenced {
+        match env.get_alias(name) {
+            Some(alias) => dependencies(&alias.ty, env, names),
+            None => names.push(name),
+        }
+    }
+}
+// a newtype containing itself would be infinitely large
+fn check_cycles(newtypes: &[&NewtypeDeclare], env: &Env) -> Result<(), TypeError> {
+    let edges: HashMap<_, _> = newtypes
+        .iter()
+        .map(|newtype| {
+            let mut names = Vec::new();
+            for field in newtype.fields.iter() {
+                dependencies(&field.ty, env, &mut names);
+            }
+            (&newtype.ident, names)
+        })
+        .collect();
+    for newtype in newtypes {
+        let mut stack: Vec<_> = edges[&newtype.ident].clone();
+        let mut visited = Vec::new();
+        while let Some(name) = stack.pop() {
+            if name == &newtype.ident {
+                return Err(TypeError::CyclicNewtype(name.clone()).at(newtype.span));
+            }
+            if let Some(more) = edges.get(name) {
+                if !visited.contains(&name) {
+                    visited.push(name);
+                    stack.extend(more);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+/// Declares the constructor of a newtype, a function taking the fields in
+/// order
+pub(super) fn declare_constructor(
+    newtype: &NewtypeDeclare,
+    env: &mut Env,
+    var_state: &VarState,
+) -> Result<(), TypeError> {
+    let id = declared_id(newtype.span, var_state)
+        .ok_or_else(|| TypeError::UnboundType(newtype.ident.clone()))?;
+    let declared = &var_state.newtypes[id];
+    let fields = declared
+        .fields
+        .iter()
+        .map(|(name, ty, _)| (name.clone(), ty.clone()))
+        .collect();
+    let ty = Type::Cons(Cons::Fun(
+        Box::new(Type::Cons(Cons::RecordTuple(OrderedAnd::NonRow(fields)))),
+        Box::new(Type::Cons(Cons::Newtype(Newtype {
+            name: declared.name.clone(),
+            id,
+            args: declared.params.iter().cloned().map(Type::Var).collect(),
+        }))),
+    ));
+    let for_all = declared
+        .params
+        .iter()
+        .map(|var| KindedVar {
+            kind: Kind::Type,
+            var: var.clone(),
+        })
+        .collect();
+    env.insert(
+        Var::new_bare(newtype.ident.clone()),
+        SchemeMut {
+            is_mut: false,
+            is_method: false,
+            scheme: Scheme {
+                for_all,
+                given: Vec::new(),
+                ty,
+            },
+        },
+    );
+    Ok(())
+}
+/// The constructor is only as visible as the newtype and each of its fields
+pub(super) fn constructor_visibility(newtype: &NewtypeDeclare) -> Visibility {
+    newtype
+        .fields
+        .iter()
+        .fold(newtype.visibility.clone(), |visibility, field| {
+            narrower(visibility, &field.visibility)
+        })
+}
+fn narrower(first: Visibility, second: &Visibility) -> Visibility {
+    match (first, second) {
+        (Visibility::Private, _) | (_, Visibility::Private) => Visibility::Private,
+        (Visibility::Public, visibility) => visibility.clone(),
+        (visibility, Visibility::Public) => visibility,
+        (Visibility::Restricted(first), Visibility::Restricted(second)) => {
+            if first.starts_with(second) {
+                Visibility::Restricted(first)
+            } else if second.starts_with(&first) {
+                Visibility::Restricted(second.clone())
+            } else {
+                Visibility::Private
+            }
+        }
+    }
+}
+/// Type of a field of a newtype, the field must be visible from the module
+/// being inferred
+pub(super) fn field_type(
+    newtype: &Newtype,
+    name: &Atom,
+    env: &Env,
+    var_state: &VarState,
+) -> Result<Type, TypeError> {
+    let declared = &var_state.newtypes[newtype.id];
+    let (_, ty, visibility) = declared
+        .fields
+        .iter()
+        .find(|(field, _, _)| field == name)
+        .ok_or_else(|| TypeError::UnboundField {
+            name: name.clone(),
+            newtype: newtype.name.clone(),
+        })?;
+    if !is_visible(visibility, &declared.path, env.path()) {
+        return Err(TypeError::PrivateField {
+            name: name.clone(),
+            newtype: newtype.name.clone(),
+        });
+    }
+    let mut ty = ty.clone();
+    ty.substitute(&declared.subs(&newtype.args))?;
+    Ok(ty)
+}
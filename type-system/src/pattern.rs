@@ -1,17 +1,30 @@
 use crate::{
-    expr::unit,
-    ty::{Env, Scheme, SchemeMut, Subs, Substitutable, Unifiable, VarState},
+    annotation::{annotation_type, unify_annotation},
+    expr::{self, unit},
+    ty::{
+        cons::OrderedAnd, num::NumBound, Env, Scheme, SchemeMut, Subs, Substitutable, Unifiable,
+        VarState,
+    },
     Cons, Keyed, MutType, Type, TypeError, Typed, Var,
 };
-use hir::pattern::{self, ListPattern, ListWithRest, Pattern, PatternKind, TaggedPattern};
-use std::{collections::HashSet, iter::once};
+use hir::{
+    expr::Literal,
+    pattern::{
+        self, AnnotatedPattern, ListPattern, ListWithRest, Pattern, PatternKind, RecordPattern,
+        TaggedPattern, TypedVar,
+    },
+};
+use std::{
+    collections::{HashMap, HashSet},
+    iter::once,
+};
 
 pub(super) trait InferablePattern {
     type TypedSelf;
 
     fn infer(
         self,
-        mut_var: Option<Var>,
+        mutability: Option<MutType>,
         var_state: &mut VarState,
         env: &mut Env,
     ) -> Result<Typed<Self::TypedSelf>, TypeError>;
@@ -21,7 +34,7 @@ impl InferablePattern for pattern::Var {
 
     fn infer(
         self,
-        mut_var: Option<Var>,
+        mutability: Option<MutType>,
         var_state: &mut VarState,
         env: &mut Env,
     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
@@ -29,7 +42,7 @@ impl InferablePattern for pattern::Var {
         let mut ty = Type::Var(var.clone());
         if self.bind_to_ref {
             ty = Type::Cons(Cons::Ref(
-                MutType::Var(mut_var.unwrap_or_else(|| var_state.new_var())),
+                mutability.unwrap_or_else(|| MutType::Var(var_state.new_var())),
                 Box::new(ty),
             ));
         }
@@ -37,8 +50,10 @@ impl InferablePattern for pattern::Var {
             Var::new_bare(self.ident.clone()),
             SchemeMut {
                 is_mut: self.mutable,
+                is_method: false,
                 scheme: Scheme {
                     for_all: HashSet::new(),
+                    given: Vec::new(),
                     ty,
                 },
             },
@@ -47,6 +62,7 @@ impl InferablePattern for pattern::Var {
             ty: Type::Var(var),
             value: pattern::Var {
                 ident: self.ident,
+                span: self.span,
                 mutable: self.mutable,
                 bind_to_ref: self.bind_to_ref,
             },
@@ -58,13 +74,13 @@ impl InferablePattern for TaggedPattern<()> {
 
     fn infer(
         self,
-        mut_var: Option<Var>,
+        mutability: Option<MutType>,
         var_state: &mut VarState,
         env: &mut Env,
     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
         let (pattern, ty) = match self.pattern {
             Some(pattern) => {
-                let typed = pattern.infer(mut_var, var_state, env)?;
+                let typed = pattern.infer(mutability, var_state, env)?;
                 (Some(typed.value), typed.ty)
             }
             None => (None, unit()),
@@ -86,12 +102,12 @@ impl InferablePattern for PatternKind<()> {
 
     fn infer(
         self,
-        mut_var: Option<Var>,
+        mutability: Option<MutType>,
         var_state: &mut VarState,
         env: &mut Env,
     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
         let typed = match self {
-            PatternKind::Var(var) => var.infer(mut_var, var_state, env)?.map(PatternKind::Var),
+            PatternKind::Var(var) => var.infer(mutability, var_state, env)?.map(PatternKind::Var),
             PatternKind::True => Typed {
                 ty: Type::Cons(Cons::Bool),
                 value: PatternKind::True,
@@ -101,20 +117,129 @@ impl InferablePattern for PatternKind<()> {
                 value: PatternKind::False,
             },
             PatternKind::UInt(num) => Typed {
-                ty: Type::Cons(Cons::Num),
+                ty: expr::num(NumBound::literal(&Literal::UInt(num)), var_state),
                 value: PatternKind::UInt(num),
             },
             PatternKind::Int(num) => Typed {
-                ty: Type::Cons(Cons::Num),
+                ty: expr::num(NumBound::int(num), var_state),
                 value: PatternKind::Int(num),
             },
             PatternKind::Discard => Typed {
                 ty: Type::Var(var_state.new_var()),
                 value: PatternKind::Discard,
             },
-            PatternKind::Record(_) => todo!(),
-            PatternKind::Tuple(_) => todo!(),
-            PatternKind::Param(_) => todo!(),
+            PatternKind::Record(record) => {
+                let mut fields = HashMap::with_capacity(record.fields.len());
+                let mut typed_fields = HashMap::with_capacity(record.fields.len());
+                for (name, pattern) in record.fields {
+                    let typed = pattern.infer(mutability.clone(), var_state, env)?;
+                    fields.insert(name.clone(), typed.ty);
+                    typed_fields.insert(name, typed.value);
+                }
+                let (rest, typed_rest) = match record.rest {
+                    Some(rest) => {
+                        let typed = rest.infer(mutability, var_state, env)?;
+                        let var = var_state.new_var();
+                        let mut rest_subs = Subs::new();
+                        typed.ty.unify_with(
+                            Type::Cons(Cons::Record(Keyed {
+                                fields: HashMap::new(),
+                                rest: Some(var.clone()),
+                            })),
+                            &mut rest_subs,
+                            var_state,
+                        )?;
+                        (Some(var), Some(Box::new(typed.value)))
+                    }
+                    None => (None, None),
+                };
+                Typed {
+                    ty: Type::Cons(Cons::Record(Keyed { fields, rest })),
+                    value: PatternKind::Record(RecordPattern {
+                        fields: typed_fields,
+                        rest: typed_rest,
+                    }),
+                }
+            }
+            PatternKind::Tuple(tuple) => match tuple {
+                ListPattern::List(list) => {
+                    let list: Vec<_> = list.into();
+                    let mut tys = Vec::with_capacity(list.len());
+                    let mut typed_list = Vec::with_capacity(list.len());
+                    for pattern in list {
+                        let typed = pattern.infer(mutability.clone(), var_state, env)?;
+                        tys.push(typed.ty);
+                        typed_list.push(typed.value);
+                    }
+                    Typed {
+                        ty: Type::Cons(Cons::Tuple(OrderedAnd::NonRow(tys.into()))),
+                        value: PatternKind::Tuple(ListPattern::List(typed_list.into())),
+                    }
+                }
+                ListPattern::ListWithRest(list) => {
+                    let mut left_tys = Vec::with_capacity(list.left.len());
+                    let mut left = Vec::with_capacity(list.left.len());
+                    for pattern in Vec::from(list.left) {
+                        let typed = pattern.infer(mutability.clone(), var_state, env)?;
+                        left_tys.push(typed.ty);
+                        left.push(typed.value);
+                    }
+                    let typed_rest = list.rest.infer(mutability.clone(), var_state, env)?;
+                    let mut right_tys = Vec::with_capacity(list.right.len());
+                    let mut right = Vec::with_capacity(list.right.len());
+                    for pattern in Vec::from(list.right) {
+                        let typed = pattern.infer(mutability.clone(), var_state, env)?;
+                        right_tys.push(typed.ty);
+                        right.push(typed.value);
+                    }
+                    let var = var_state.new_var();
+                    let mut rest_subs = Subs::new();
+                    typed_rest.ty.unify_with(
+                        Type::Cons(Cons::Tuple(OrderedAnd::Row(
+                            Vec::new(),
+                            var.clone(),
+                            Vec::new(),
+                        ))),
+                        &mut rest_subs,
+                        var_state,
+                    )?;
+                    Typed {
+                        ty: Type::Cons(Cons::Tuple(OrderedAnd::Row(left_tys, var, right_tys))),
+                        value: PatternKind::Tuple(ListPattern::ListWithRest(ListWithRest {
+                            left: left.into(),
+                            rest: Box::new(typed_rest.value),
+                            right: right.into(),
+                        })),
+                    }
+                }
+            },
+            PatternKind::Param(param) => {
+                let param: Vec<_> = param.into();
+                let mut fields = Vec::with_capacity(param.len());
+                let mut typed_param = Vec::with_capacity(param.len());
+                for typed_var in param {
+                    let typed = typed_var.var.infer(mutability.clone(), var_state, env)?;
+                    if let Some(annotation) = &typed_var.annotation {
+                        let annotation = annotation_type(annotation, var_state, env)?;
+                        unify_annotation(
+                            typed.ty.clone(),
+                            annotation,
+                            &mut Subs::new(),
+                            var_state,
+                        )?;
+                    }
+                    fields.push((typed.value.ident.clone(), typed.ty.clone()));
+                    typed_param.push(TypedVar {
+                        var: typed.value,
+                        annotation: typed_var.annotation,
+                        ty: typed.ty,
+                    });
+                }
+                Typed {
+                    ty: Type::Cons(Cons::RecordTuple(OrderedAnd::NonRow(fields.into()))),
+                    value: PatternKind::Param(typed_param.into()),
+                }
+            }
             PatternKind::Array(array) => {
                 let mut ty_var = Type::Var(var_state.new_var());
                 let mut arr_ty = Type::Cons(Cons::Array(Box::new(ty_var.clone())));
@@ -122,7 +247,7 @@ impl InferablePattern for PatternKind<()> {
                     pattern::ListPattern::List(list) => {
                         let mut typed_list = Vec::new();
                         for element in Vec::from(list) {
-                            let typed = element.infer(mut_var.clone(), var_state, env)?;
+                            let typed = element.infer(mutability.clone(), var_state, env)?;
                             typed_list.push(typed.value);
                             let mut arr_subs = Subs::new();
                             typed
@@ -138,7 +263,7 @@ impl InferablePattern for PatternKind<()> {
                         // TODO: remove repeated code
                         let mut left = Vec::new();
                         for element in Vec::from(list.left) {
-                            let typed = element.infer(mut_var.clone(), var_state, env)?;
+                            let typed = element.infer(mutability.clone(), var_state, env)?;
                             left.push(typed.value);
                             let mut arr_subs = Subs::new();
                             typed
@@ -150,7 +275,7 @@ impl InferablePattern for PatternKind<()> {
                         }
                         let mut right = Vec::new();
                         for element in Vec::from(list.right) {
-                            let typed = element.infer(mut_var.clone(), var_state, env)?;
+                            let typed = element.infer(mutability.clone(), var_state, env)?;
                             right.push(typed.value);
                             let mut arr_subs = Subs::new();
                             typed
@@ -160,7 +285,7 @@ impl InferablePattern for PatternKind<()> {
                             arr_ty.substitute(&arr_subs)?;
                             // subs.compose_with(arr_subs)?;
                         }
-                        let typed = list.rest.infer(mut_var, var_state, env)?;
+                        let typed = list.rest.infer(mutability, var_state, env)?;
                         let mut arr_subs = Subs::new();
                         typed
                             .ty
@@ -181,20 +306,34 @@ impl InferablePattern for PatternKind<()> {
                 }
             }
             PatternKind::Tag(tag) => {
-                let typed = tag.infer(mut_var, var_state, env)?;
+                let typed = tag.infer(mutability, var_state, env)?;
                 Typed {
                     ty: typed.ty,
                     value: PatternKind::Tag(typed.value),
                 }
             }
             PatternKind::Ref(pattern) => {
-                let typed = pattern.infer(mut_var, var_state, env)?;
-                let var = var_state.new_var();
+                // the pattern within is behind the reference, it's as mutable
+                // as the reference
+                let mutability = MutType::Var(var_state.new_var());
+                let typed = pattern.infer(Some(mutability.clone()), var_state, env)?;
                 Typed {
-                    ty: Type::Cons(Cons::Ref(MutType::Var(var), Box::new(typed.ty))),
+                    ty: Type::Cons(Cons::Ref(mutability, Box::new(typed.ty))),
                     value: PatternKind::Ref(Box::new(typed.value)),
                 }
             }
+            PatternKind::Annotated(annotated) => {
+                let typed = annotated.pattern.infer(mutability, var_state, env)?;
+                let ty = annotation_type(&annotated.annotation, var_state, env)?;
+                unify_annotation(typed.ty, ty.clone(), &mut Subs::new(), var_state)?;
+                Typed {
+                    ty,
+                    value: PatternKind::Annotated(AnnotatedPattern {
+                        pattern: Box::new(typed.value),
+                        annotation: annotated.annotation,
+                    }),
+                }
+            }
         };
         Ok(typed)
     }
@@ -204,16 +343,20 @@ impl InferablePattern for Pattern<()> {
 
     fn infer(
         self,
-        mut_var: Option<Var>,
+        mutability: Option<MutType>,
         var_state: &mut VarState,
         env: &mut Env,
     ) -> Result<Typed<Self::TypedSelf>, TypeError> {
-        let typed = self.pattern.infer(mut_var, var_state, env)?;
+        let typed = self
+            .pattern
+            .infer(mutability, var_state, env)
+            .map_err(|err| err.at(self.span))?;
         let ty = typed.ty.clone();
         Ok(Typed {
             value: Pattern {
                 pattern: typed.value,
                 ty: typed.ty,
+                span: self.span,
             },
             ty,
         })
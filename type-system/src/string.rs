@@ -0,0 +1,77 @@
+//! Strings and characters. A `Str` is made of bytes that are valid UTF-8,
+//! string literals are checked for it and other bytes only become a string
+//! through `from_utf8`. A `Char` is a Unicode scalar value.
+
+use crate::{
+    expr::unit,
+    ty::{
+        cons::{Cons, Keyed, OrderedAnd},
+        num::{NumType, Repr},
+        Env, FreeVars, Scheme, SchemeMut, Type, Var,
+    },
+};
+use hir::Atom;
+
+fn fun(param: &str, param_ty: Type, ret: Type) -> Type {
+    let params = vec![(Atom::from(param), param_ty)];
+    Type::Cons(Cons::Fun(
+        Box::new(Type::Cons(Cons::RecordTuple(OrderedAnd::NonRow(
+            params.into(),
+        )))),
+        Box::new(ret),
+    ))
+}
+// `@val ty` when the conversion succeeds and `@none` otherwise, open to more
+// tags like any other tag
+fn optional(ty: Type, rest: &Var) -> Type {
+    Type::Cons(Cons::Union(Keyed {
+        fields: [(Atom::from("val"), ty), (Atom::from("none"), unit())]
+            .into_iter()
+            .collect(),
+        rest: Some(rest.clone()),
+    }))
+}
+/// Binds the conversions between strings, characters, and numbers into
+/// `env`
+pub(super) fn declare_conversions(env: &mut Env) {
+    let rest = Var {
+        name: Atom::from("r"),
+        id: 0,
+    };
+    let byte = || Type::Cons(Cons::Num(NumType::Repr(Repr::U8)));
+    let code = || Type::Cons(Cons::Num(NumType::Repr(Repr::U32)));
+    let bytes = || Type::Cons(Cons::Array(Box::new(byte())));
+    let string = || Type::Cons(Cons::Str);
+    let char = || Type::Cons(Cons::Char);
+    let conversions = [
+        ("bytes", fun("string", string(), bytes())),
+        (
+            "from_utf8",
+            fun("bytes", bytes(), optional(string(), &rest)),
+        ),
+        (
+            "chars",
+            fun(
+                "string",
+                string(),
+                Type::Cons(Cons::Array(Box::new(char()))),
+            ),
+        ),
+        ("code", fun("char", char(), code())),
+        ("from_code", fun("code", code(), optional(char(), &rest))),
+    ];
+    for (name, ty) in conversions {
+        env.insert(
+            Var::new_bare(Atom::from(name)),
+            SchemeMut {
+                is_mut: false,
+                is_method: false,
+                scheme: Scheme {
+                    for_all: ty.free_vars(),
+                    given: Vec::new(),
+                    ty,
+                },
+            },
+        );
+    }
+}
@@ -0,0 +1,731 @@
+//! Traits and their implementations. Both are declared upfront for the whole
+//! scope they're in along with the inline modules within it, so code may use
+//! an implementation declared after it. Trait names are global.
+//!
+//! Predicates required by an expression are collected as wanted. These are
+//! reduced by implementations until only predicates on type variables are
+//! left, which become `given` predicates of the scheme generalizing them.
+
+use crate::{
+    annotation::{annotation_type, declare_aliases, references},
+    expr::infer_fun,
+    newtype::declare_newtypes,
+    substitute_hir,
+    ty::{
+        cons::{Cons, Keyed, OrderedAnd},
+        num::NumType,
+        Env, FreeVars, Kind, KindedVar, MutType, Predicate, Scheme, SchemeMut, Subs, Substitutable,
+        Type, TypeError, Unifiable, Var, VarState,
+    },
+};
+use hir::{
+    expr::BinaryType,
+    keyword,
+    statement::{FunDeclare, ImplDeclare, Module, Statement, TraitDeclare},
+    ty::TraitBound,
+    Atom, Span,
+};
+use std::{collections::HashSet, mem::take};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) struct Trait {
+    span: Span,
+    pub(super) param: Var,
+    /// Type of each method, with `param` standing for the implementing type
+    pub(super) methods: Vec<(Atom, Type)>,
+}
+impl Trait {
+    /// Type of the dictionary implementing the trait for `ty`, a record of
+    /// its methods
+    pub(super) fn dictionary_type(&self, ty: &Type) -> Type {
+        let mut subs = Subs::new();
+        subs.insert_type(self.param.clone(), ty.clone());
+        let fields = self
+            .methods
+            .iter()
+            .map(|(name, method)| {
+                let mut method = method.clone();
+                // the parameter is a type variable, it can't mismatch kinds
+                method.substitute(&subs).unwrap();
+                (name.clone(), method)
+            })
+            .collect();
+        Type::Cons(Cons::Record(Keyed { fields, rest: None }))
+    }
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) struct Impl {
+    pub(super) span: Span,
+    pub(super) head: Predicate,
+    pub(super) for_all: HashSet<KindedVar>,
+    pub(super) given: Vec<Predicate>,
+    /// Type variables named on the head, they're rigid within the methods
+    type_vars: Vec<(Atom, KindedVar)>,
+    /// Name of the function returning the dictionary after elaboration
+    pub(super) dictionary: Atom,
+    /// The primitive operator implementing it when the implementations it's
+    /// given are primitive too, only implementations from the prelude have one
+    pub(super) operator: Option<BinaryType>,
+}
+impl Impl {
+    /// The head and `given` predicates with fresh type variables
+    fn instantiate(
+        &self,
+        var_state: &mut VarState,
+    ) -> Result<(Predicate, Vec<Predicate>), TypeError> {
+        let scheme = Scheme {
+            for_all: self.for_all.clone(),
+            given: self.given.clone(),
+            ty: self.head.ty.clone(),
+        };
+        let (ty, given) = scheme.instantiate_given(var_state)?;
+        let head = Predicate {
+            trait_name: self.head.trait_name.clone(),
+            ty,
+        };
+        Ok((head, given))
+    }
+}
+/// A use of a variable with `given` predicates, elaboration passes the
+/// dictionaries of these predicates to it
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) struct Site {
+    pub(super) span: Span,
+    pub(super) is_method: bool,
+    pub(super) given: Box<[Predicate]>,
+}
+/// Declares the built-in traits, `==` and `!=` want `Eq` while `++` wants
+/// `Concat`
+pub(super) fn declare_prelude(var_state: &mut VarState) {
+    let param = Var {
+        name: Atom::from("a"),
+        id: 0,
+    };
+    let fun = |params: Vec<(&str, Type)>, ret| {
+        let params = params
+            .into_iter()
+            .map(|(name, ty)| (Atom::from(name), ty))
+            .collect();
+        Type::Cons(Cons::Fun(
+            Box::new(Type::Cons(Cons::RecordTuple(OrderedAnd::NonRow(params)))),
+            Box::new(ret),
+        ))
+    };
+    let reference = Type::Cons(Cons::Ref(MutType::Imm, Box::new(Type::Var(param.clone()))));
+    let equal = fun(
+        vec![("a", reference.clone()), ("b", reference)],
+        Type::Cons(Cons::Bool),
+    );
+    let concat = fun(
+        vec![
+            ("a", Type::Var(param.clone())),
+            ("b", Type::Var(param.clone())),
+        ],
+        Type::Var(param.clone()),
+    );
+    for (name, method, ty) in [
+        (keyword!("Eq"), keyword!("equal"), equal),
+        (keyword!("Concat"), keyword!("concat"), concat),
+    ] {
+        let declared = Trait {
+            span: Span::default(),
+            param: param.clone(),
+            methods: vec![(method, ty)],
+        };
+        var_state.traits.insert(name, declared);
+    }
+    let element = Type::Var(param.clone());
+    for (head, operator) in [
+        (
+            Predicate {
+                trait_name: keyword!("Eq"),
+                ty: Type::Cons(Cons::Num(NumType::Var(Var {
+                    name: Atom::from("n"),
+                    id: 0,
+                }))),
+            },
+            BinaryType::Equal,
+        ),
+        (
+            Predicate {
+                trait_name: keyword!("Concat"),
+                ty: Type::Cons(Cons::Array(Box::new(element))),
+            },
+            BinaryType::Concatenate,
+        ),
+        (
+            Predicate {
+                trait_name: keyword!("Eq"),
+                ty: Type::Cons(Cons::Char),
+            },
+            BinaryType::Equal,
+        ),
+        (
+            Predicate {
+                trait_name: keyword!("Eq"),
+                ty: Type::Cons(Cons::Str),
+            },
+            BinaryType::Equal,
+        ),
+        (
+            Predicate {
+                trait_name: keyword!("Concat"),
+                ty: Type::Cons(Cons::Str),
+            },
+            BinaryType::Concatenate,
+        ),
+        (
+            Predicate {
+                trait_name: keyword!("Eq"),
+                ty: Type::Cons(Cons::Bool),
+            },
+            BinaryType::Equal,
+        ),
+    ] {
+        declare_builtin_impl(var_state, head, Vec::new(), operator);
+    }
+    // arrays are equal when their elements are
+    declare_builtin_impl(
+        var_state,
+        Predicate {
+            trait_name: keyword!("Eq"),
+            ty: Type::Cons(Cons::Array(Box::new(Type::Var(param.clone())))),
+        },
+        vec![Predicate {
+            trait_name: keyword!("Eq"),
+            ty: Type::Var(param),
+        }],
+        BinaryType::Equal,
+    );
+}
+fn declare_builtin_impl(
+    var_state: &mut VarState,
+    head: Predicate,
+    given: Vec<Predicate>,
+    operator: BinaryType,
+) {
+    let dictionary = Atom::from(format!("{}#{}", head.trait_name, var_state.impls.len()));
+    var_state.impls.push(Impl {
+        span: Span::default(),
+        for_all: head.free_vars(),
+        head,
+        given,
+        type_vars: Vec::new(),
+        dictionary,
+        operator: Some(operator),
+    });
+}
+/// Brings every trait and implementation declared in a block or module into
+/// `var_state`, those of inline modules within it are declared as well
+pub(super) fn declare_traits(
+    statements: &[Statement<()>],
+    env: &Env,
+    var_state: &mut VarState,
+) -> Result<(), TypeError> {
+    // every trait is declared first so implementations may come before them
+    fn visit(
+        statements: &[Statement<()>],
+        env: &Env,
+        var_state: &mut VarState,
+        declare: &mut impl FnMut(&Statement<()>, &Env, &mut VarState) -> Result<(), TypeError>,
+    ) -> Result<(), TypeError> {
+        for statement in statements {
+            match statement {
+                Statement::ModuleDeclare(module) => {
+                    if let Module::Inline(statements) = &module.module {
+                        let mut module_env = env.clone();
+                        module_env.enter_module(module.ident.clone());
+                        declare_aliases(statements, &mut module_env)?;
+                        declare_newtypes(statements, &mut module_env, var_state)?;
+                        visit(statements, &module_env, var_state, declare)?;
+                    }
+                }
+                statement => declare(statement, env, var_state)?,
+            }
+        }
+        Ok(())
+    }
+    visit(
+        statements,
+        env,
+        var_state,
+        &mut |statement, env, var_state| match statement {
+            Statement::TraitDeclare(trait_declare) => declare_trait(trait_declare, env, var_state)
+                .map_err(|err| err.at(trait_declare.span)),
+            _ => Ok(()),
+        },
+    )?;
+    visit(
+        statements,
+        env,
+        var_state,
+        &mut |statement, env, var_state| match statement {
+            Statement::ImplDeclare(impl_declare) => {
+                declare_impl(impl_declare, env, var_state).map_err(|err| err.at(impl_declare.span))
+            }
+            _ => Ok(()),
+        },
+    )
+}
+fn declare_trait(
+    trait_declare: &TraitDeclare,
+    env: &Env,
+    var_state: &mut VarState,
+) -> Result<(), TypeError> {
+    if let Some(declared) = var_state.traits.get(&trait_declare.ident) {
+        // the same declaration is met again when its module is inferred
+        return if declared.span == trait_declare.span {
+            Ok(())
+        } else {
+            Err(TypeError::DuplicateTrait(trait_declare.ident.clone()))
+        };
+    }
+    let mut trait_env = env.clone();
+    let param = var_state.new_named(trait_declare.param.clone());
+    trait_env.insert_type_var(
+        trait_declare.param.clone(),
+        KindedVar {
+            kind: Kind::Type,
+            var: param.clone(),
+        },
+    );
+    let mut methods = Vec::with_capacity(trait_declare.methods.len());
+    for method in trait_declare.methods.iter() {
+        // other type variables are local to each method
+        let mut method_env = trait_env.clone();
+        let mut params = Vec::with_capacity(method.params.len());
+        for (name, ty) in method.params.iter() {
+            params.push((
+                name.clone(),
+                annotation_type(ty, var_state, &mut method_env)?,
+            ));
+        }
+        let ret = annotation_type(&method.ret, var_state, &mut method_env)?;
+        let ty = Type::Cons(Cons::Fun(
+            Box::new(Type::Cons(Cons::RecordTuple(OrderedAnd::NonRow(
+                params.into(),
+            )))),
+            Box::new(ret),
+        ));
+        methods.push((method.ident.clone(), ty));
+    }
+    var_state.traits.insert(
+        trait_declare.ident.clone(),
+        Trait {
+            span: trait_declare.span,
+            param,
+            methods,
+        },
+    );
+    Ok(())
+}
+pub(super) fn bound_predicate(
+    bound: &TraitBound,
+    var_state: &mut VarState,
+    env: &mut Env,
+) -> Result<Predicate, TypeError> {
+    if !var_state.traits.contains_key(&bound.name) {
+        return Err(TypeError::UnboundTrait(bound.name.clone()));
+    }
+    Ok(Predicate {
+        trait_name: bound.name.clone(),
+        ty: annotation_type(&bound.ty, var_state, env)?,
+    })
+}
+fn declare_impl(
+    impl_declare: &ImplDeclare<()>,
+    env: &Env,
+    var_state: &mut VarState,
+) -> Result<(), TypeError> {
+    if var_state
+        .impls
+        .iter()
+        .any(|implementation| implementation.span == impl_declare.span)
+    {
+        return Ok(());
+    }
+    let mut impl_env = env.clone();
+    let head = bound_predicate(&impl_declare.bound, var_state, &mut impl_env)?;
+    let mut head_vars = Vec::new();
+    references(&impl_declare.bound.ty, &mut head_vars, &mut Vec::new());
+    let mut given = Vec::with_capacity(impl_declare.given.len());
+    for bound in impl_declare.given.iter() {
+        let mut vars = Vec::new();
+        references(&bound.ty, &mut vars, &mut Vec::new());
+        // a predicate on anything else could never be found
+        if let Some(var) = vars.into_iter().find(|var| !head_vars.contains(var)) {
+            return Err(TypeError::UnboundType(var.clone()));
+        }
+        given.push(bound_predicate(bound, var_state, &mut impl_env)?);
+    }
+    let mut type_vars: Vec<(Atom, KindedVar)> = Vec::new();
+    for name in head_vars {
+        if type_vars.iter().all(|(declared, _)| declared != name) {
+            let var = impl_env.get_type_var(name).unwrap();
+            var_state.make_rigid(var.var.clone());
+            type_vars.push((name.clone(), var));
+        }
+    }
+    let for_all = head
+        .free_vars()
+        .into_iter()
+        .chain(given.iter().flat_map(Predicate::free_vars))
+        .collect();
+    let dictionary = Atom::from(format!("{}#{}", head.trait_name, var_state.impls.len()));
+    let implementation = Impl {
+        span: impl_declare.span,
+        head,
+        for_all,
+        given,
+        type_vars,
+        dictionary,
+        operator: None,
+    };
+    for other in &var_state.impls {
+        if other.head.trait_name == implementation.head.trait_name
+            && overlaps(other, &implementation, var_state)?
+        {
+            return Err(TypeError::OverlappingImpls(
+                Box::new(other.head.clone()),
+                Box::new(implementation.head),
+            ));
+        }
+    }
+    var_state.impls.push(implementation);
+    Ok(())
+}
+// implementations overlap when a predicate can be an instance of both
+fn overlaps(first: &Impl, second: &Impl, var_state: &VarState) -> Result<bool, TypeError> {
+    let mut scratch = var_state.clone();
+    let (first, _) = first.instantiate(&mut scratch)?;
+    let (second, _) = second.instantiate(&mut scratch)?;
+    Ok(first
+        .ty
+        .unify_with(second.ty, &mut Subs::new(), &mut scratch)
+        .is_ok())
+}
+/// Finds the implementation the predicate is an instance of, along with the
+/// predicates it's given in place of the head's type variables
+pub(super) fn find_impl(
+    predicate: &Predicate,
+    var_state: &VarState,
+) -> Result<Option<(usize, Vec<Predicate>)>, TypeError> {
+    for (index, implementation) in var_state.impls.iter().enumerate() {
+        if implementation.head.trait_name != predicate.trait_name {
+            continue;
+        }
+        let mut scratch = var_state.clone();
+        // only the head may be bound, the predicate must be left as it is
+        for var in predicate.free_vars() {
+            scratch.make_rigid(var.var);
+        }
+        let (head, mut given) = implementation.instantiate(&mut scratch)?;
+        if head
+            .ty
+            .unify_with(predicate.ty.clone(), &mut Subs::new(), &mut scratch)
+            .is_ok()
+        {
+            for predicate in &mut given {
+                predicate.substitute(scratch.bindings())?;
+            }
+            return Ok(Some((index, given)));
+        }
+    }
+    Ok(None)
+}
+/// Reduces the predicate by implementations until only predicates on type
+/// variables are left
+fn reduce(predicate: Predicate, var_state: &VarState) -> Result<Vec<Predicate>, TypeError> {
+    let mut predicate = predicate;
+    predicate.substitute(var_state.bindings())?;
+    if let Type::Var(var) = predicate.ty.clone().unalias() {
+        return Ok(vec![Predicate {
+            trait_name: predicate.trait_name,
+            ty: Type::Var(var),
+        }]);
+    }
+    match find_impl(&predicate, var_state)? {
+        Some((_, given)) => {
+            let mut reduced = Vec::new();
+            for predicate in given {
+                reduced.extend(reduce(predicate, var_state)?);
+            }
+            Ok(reduced)
+        }
+        None => Err(TypeError::NoInstance(Box::new(predicate))),
+    }
+}
+fn push_unique(predicates: &mut Vec<Predicate>, predicate: Predicate) {
+    if !predicates.contains(&predicate) {
+        predicates.push(predicate);
+    }
+}
+/// Takes the predicates wanted since `start`, those on generic variables of
+/// the scheme become its `given` predicates while the rest are left wanted.
+/// The `declared` predicates come first, and declared generics can't be given
+/// anything else
+pub(super) fn generalize_given(
+    scheme: &mut Scheme,
+    start: usize,
+    declared: Vec<Predicate>,
+    var_state: &mut VarState,
+) -> Result<(), TypeError> {
+    let mut allowed = Vec::with_capacity(declared.len());
+    for predicate in declared {
+        allowed.extend(reduce(predicate, var_state)?);
+    }
+    let wanted = var_state.wanted.split_off(start);
+    for predicate in allowed.clone().into_iter().chain(wanted) {
+        for predicate in reduce(predicate, var_state)? {
+            let Type::Var(var) = &predicate.ty else {
+                unreachable!()
+            };
+            let var = KindedVar {
+                kind: Kind::Type,
+                var: var.clone(),
+            };
+            if !scheme.for_all.contains(&var) {
+                push_unique(&mut var_state.wanted, predicate);
+            } else if var_state.is_rigid(&var.var) && !allowed.contains(&predicate) {
+                return Err(TypeError::NoInstance(Box::new(predicate)));
+            } else {
+                push_unique(&mut scheme.given, predicate);
+            }
+        }
+    }
+    Ok(())
+}
+/// Resolves every predicate wanted so far. With `default`, those left on type
+/// variables are defaulted to the built-in implementation of the trait,
+/// otherwise they're left wanted
+pub(super) fn solve_wanted(var_state: &mut VarState, default: bool) -> Result<(), TypeError> {
+    let mut remaining = Vec::new();
+    for predicate in take(&mut var_state.wanted) {
+        for predicate in reduce(predicate, var_state)? {
+            push_unique(&mut remaining, predicate);
+        }
+    }
+    if !default {
+        var_state.wanted = remaining;
+        return Ok(());
+    }
+    while let Some(mut predicate) = remaining.pop() {
+        predicate.substitute(var_state.bindings())?;
+        if let Type::Var(var) = &predicate.ty {
+            let builtin = var_state.impls.iter().find(|implementation| {
+                implementation.operator.is_some()
+                    && implementation.head.trait_name == predicate.trait_name
+            });
+            let Some(builtin) = builtin.cloned() else {
+                return Err(TypeError::AmbiguousInstance(Box::new(predicate)));
+            };
+            let (head, _) = builtin.instantiate(var_state)?;
+            Type::Var(var.clone()).unify_with(head.ty, &mut Subs::new(), var_state)?;
+        }
+        remaining.extend(reduce(predicate, var_state)?);
+    }
+    Ok(())
+}
+/// Instantiates the scheme of a variable used at `span`, its `given`
+/// predicates are then wanted
+pub(super) fn instantiate_use(
+    span: Span,
+    scheme: SchemeMut,
+    var_state: &mut VarState,
+) -> Result<Type, TypeError> {
+    let (ty, given) = scheme.scheme.instantiate_given(var_state)?;
+    if !given.is_empty() {
+        var_state.wanted.extend(given.iter().cloned());
+        var_state.sites.push(Site {
+            span,
+            is_method: scheme.is_method,
+            given: given.into(),
+        });
+    }
+    Ok(ty)
+}
+/// Binds the methods of a declared trait into `env`, each is generic over the
+/// implementing type given it implements the trait
+pub(super) fn declare_methods(
+    trait_declare: &TraitDeclare,
+    env: &mut Env,
+    var_state: &VarState,
+) -> Result<(), TypeError> {
+    let declared = var_state
+        .traits
+        .get(&trait_declare.ident)
+        .ok_or_else(|| TypeError::UnboundTrait(trait_declare.ident.clone()))?;
+    for (name, ty) in &declared.methods {
+        // a `Num` in a signature has a single representation throughout the
+        // program, every implementation agrees on it
+        let for_all = ty
+            .free_vars()
+            .into_iter()
+            .filter(|var| var.kind != Kind::NumType)
+            .collect();
+        let scheme = Scheme {
+            for_all,
+            given: vec![Predicate {
+                trait_name: trait_declare.ident.clone(),
+                ty: Type::Var(declared.param.clone()),
+            }],
+            ty: ty.clone(),
+        };
+        env.insert(
+            Var::new_bare(name.clone()),
+            SchemeMut {
+                is_mut: false,
+                is_method: true,
+                scheme,
+            },
+        );
+    }
+    Ok(())
+}
+/// Infers the methods of an implementation, each must be as general as the
+/// trait declares it for the implementing type
+/// Method type with the parameters unnamed, implementations may name them
+/// differently from the trait
+fn positional(ty: Type) -> Type {
+    match ty {
+        Type::Cons(Cons::Fun(param, ret)) => match *param {
+            Type::Cons(Cons::RecordTuple(OrderedAnd::NonRow(params))) => {
+                let params = params.into_vec().into_iter().map(|(_, ty)| ty).collect();
+                Type::Cons(Cons::Fun(
+                    Box::new(Type::Cons(Cons::Tuple(OrderedAnd::NonRow(params)))),
+                    ret,
+                ))
+            }
+            param => Type::Cons(Cons::Fun(Box::new(param), ret)),
+        },
+        ty => ty,
+    }
+}
+pub(super) fn infer_impl(
+    impl_declare: ImplDeclare<()>,
+    subs: &mut Subs,
+    var_state: &mut VarState,
+    env: &Env,
+) -> Result<ImplDeclare<Type>, TypeError> {
+    let implementation = var_state
+        .impls
+        .iter()
+        .find(|implementation| implementation.span == impl_declare.span)
+        .cloned()
+        .ok_or_else(|| TypeError::UnboundTrait(impl_declare.bound.name.clone()))?;
+    let declared = var_state.traits[&implementation.head.trait_name].clone();
+    let mut impl_env = env.clone();
+    for (name, var) in &implementation.type_vars {
+        impl_env.insert_type_var(name.clone(), var.clone());
+    }
+    let mut param_subs = Subs::new();
+    param_subs.insert_type(declared.param.clone(), implementation.head.ty.clone());
+    let mut methods = Vec::with_capacity(impl_declare.methods.len());
+    for method in Vec::from(impl_declare.methods) {
+        let Some((_, method_ty)) = declared
+            .methods
+            .iter()
+            .find(|(name, _)| *name == method.ident)
+        else {
+            return Err(TypeError::UnknownMethod {
+                trait_name: implementation.head.trait_name,
+                method: method.ident,
+            });
+        };
+        let mut expected = positional(method_ty.clone());
+        expected.substitute(&param_subs)?;
+        let head_vars = implementation.head.free_vars();
+        let for_all = expected
+            .free_vars()
+            .into_iter()
+            .filter(|var| !head_vars.contains(var) && var.kind != Kind::NumType)
+            .collect();
+        let expected = Scheme {
+            for_all,
+            given: Vec::new(),
+            ty: expected,
+        }
+        .instantiate(var_state)?;
+        // the method can't assume more of the types than the trait does
+        for var in expected.free_vars() {
+            if var.kind != Kind::NumType {
+                var_state.make_rigid(var.var);
+            }
+        }
+        let start = var_state.wanted.len();
+        // the method isn't bound within its body, the name refers to the
+        // trait method there
+        let mut fun_env = impl_env.clone();
+        for name in method.generics.iter() {
+            let var = KindedVar {
+                kind: Kind::Type,
+                var: var_state.new_rigid(name.clone()),
+            };
+            fun_env.insert_type_var(name.clone(), var);
+        }
+        // a method may only restate what its implementation is given
+        for bound in method.given.iter() {
+            let predicate = bound_predicate(bound, var_state, &mut fun_env)?;
+            if !implementation.given.contains(&predicate) {
+                return Err(TypeError::NoInstance(Box::new(predicate)));
+            }
+        }
+        // the parameters are known while the body is inferred
+        let typed_fun = infer_fun(
+            method.fun,
+            Some(expected.clone()),
+            subs,
+            var_state,
+            &fun_env,
+        )?;
+        let mut fun = typed_fun.value;
+        let mut more_subs = Subs::new();
+        typed_fun
+            .ty
+            .clone()
+            .unify_with(expected, &mut more_subs, var_state)?;
+        substitute_hir(&mut fun, &more_subs)?;
+        subs.compose_with(more_subs)?;
+        let mut ty = typed_fun.ty;
+        ty.substitute(var_state.bindings())?;
+        let typed = FunDeclare {
+            visibility: method.visibility,
+            ident: method.ident,
+            span: method.span,
+            given: method.given,
+            generics: method.generics,
+            fun,
+            ty: impl_env.generalize(ty),
+        };
+        // predicates on the implementing type must be given to the
+        // implementation
+        for predicate in var_state.wanted.split_off(start) {
+            for predicate in reduce(predicate, var_state)? {
+                let Type::Var(var) = &predicate.ty else {
+                    unreachable!()
+                };
+                if implementation.given.contains(&predicate) {
+                    continue;
+                } else if var_state.is_rigid(var) {
+                    return Err(TypeError::NoInstance(Box::new(predicate)));
+                }
+                push_unique(&mut var_state.wanted, predicate);
+            }
+        }
+        methods.push(typed);
+    }
+    for (name, _) in &declared.methods {
+        if methods.iter().all(|method| method.ident != *name) {
+            return Err(TypeError::MissingMethod {
+                trait_name: implementation.head.trait_name,
+                method: name.clone(),
+            });
+        }
+    }
+    Ok(ImplDeclare {
+        given: impl_declare.given,
+        bound: impl_declare.bound,
+        span: impl_declare.span,
+        methods: methods.into(),
+    })
+}
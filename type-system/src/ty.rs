@@ -1,17 +1,29 @@
-use crate::ty::cons::Cons;
+use crate::{
+    exhaustive::Witness,
+    newtype::NewtypeDef,
+    traits::{Impl, Site, Trait},
+    ty::{
+        cons::{Cons, OrderedAnd},
+        num::{NumBound, NumType, Repr},
+    },
+};
 use hir::{
+    expr::Str,
     keyword,
-    pretty_print::{PrettyPrint, PrettyPrintTree},
-    Atom, PrettyPrintFunScheme, PrettyPrintType,
+    pretty_print::{bracket, line, postfix, sequence, PrettyPrint, PrettyPrintTree},
+    statement::{AliasDeclare, Visibility},
+    Atom, PrettyPrintFunScheme, PrettyPrintType, Span,
 };
 use std::{
     collections::{HashMap, HashSet},
     fmt::{self, Display, Formatter},
     hash::Hash,
     iter::once,
+    mem::take,
 };
 
 pub mod cons;
+pub mod num;
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct Var {
@@ -34,7 +46,25 @@ impl Display for Var {
     }
 }
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
-pub(super) struct VarState(HashMap<Atom, u32>);
+pub(super) struct VarState {
+    count: HashMap<Atom, u32>,
+    bindings: Subs,
+    rigid: HashSet<Var>,
+    constraints: HashMap<Var, Constraint>,
+    /// What the representation of each numeric variable must hold
+    num_bounds: HashMap<Var, NumBound>,
+    warnings: Vec<TypeWarning>,
+    pub(super) traits: HashMap<Atom, Trait>,
+    pub(super) impls: Vec<Impl>,
+    /// Predicates required so far that are yet to be resolved or turned into
+    /// `given` predicates of a scheme
+    pub(super) wanted: Vec<Predicate>,
+    /// Uses of variables with `given` predicates, the elaboration passes
+    /// dictionaries to them
+    pub(super) sites: Vec<Site>,
+    /// Every newtype declared so far, indexed by the id of its type
+    pub(super) newtypes: Vec<NewtypeDef>,
+}
 impl VarState {
     pub fn new() -> Self {
         Self::default()
@@ -43,26 +73,249 @@ impl VarState {
         self.new_named(keyword!(""))
     }
     pub fn new_named(&mut self, name: Atom) -> Var {
-        let Self(map) = self;
-        let state = map.entry(name.clone()).or_insert(1);
+        let state = self.count.entry(name.clone()).or_insert(1);
         let id = *state;
         *state += 1;
         Var { name, id }
     }
+    /// A number whose representation is left to inference
+    pub fn new_num(&mut self, bound: NumBound) -> NumType {
+        let var = self.new_var();
+        if bound != NumBound::default() {
+            self.num_bounds.insert(var.clone(), bound);
+        }
+        NumType::Var(var)
+    }
+    /// A variable for an explicitly declared generic, it can't be bound to
+    /// anything else
+    pub fn new_rigid(&mut self, name: Atom) -> Var {
+        let var = self.new_named(name);
+        self.rigid.insert(var.clone());
+        var
+    }
+    pub fn is_rigid(&self, var: &Var) -> bool {
+        self.rigid.contains(var)
+    }
+    pub fn make_rigid(&mut self, var: Var) {
+        self.rigid.insert(var);
+    }
+    /// Every binding made by unification so far, kept idempotent so that a
+    /// single substitution fully resolves any type
+    pub fn bindings(&self) -> &Subs {
+        &self.bindings
+    }
+    pub fn warn(&mut self, warning: TypeWarning) {
+        self.warnings.push(warning);
+    }
+    pub fn take_warnings(&mut self) -> Vec<TypeWarning> {
+        take(&mut self.warnings)
+    }
+    /// Requires values of the type to be clonable or implicitly copyable,
+    /// type variables carry the constraint until they're bound
+    pub fn constrain(
+        &mut self,
+        subs: &mut Subs,
+        ty: &Type,
+        constraint: Constraint,
+    ) -> Result<(), TypeError> {
+        let mut ty = ty.clone();
+        ty.substitute(&self.bindings)?;
+        if self.satisfy(subs, &ty, constraint)? {
+            Ok(())
+        } else {
+            ty.substitute(&self.bindings)?;
+            Err(constraint.error(ty))
+        }
+    }
+    /// Requires the representation of a number to hold `bound`, variables
+    /// carry the bound until they're bound
+    pub fn require_num(&mut self, num: &NumType, bound: NumBound) -> Result<(), TypeError> {
+        let mut num = num.clone();
+        num.substitute(&self.bindings)?;
+        match num {
+            NumType::Var(var) => {
+                let current = self.num_bounds.entry(var).or_default();
+                *current = current.join(bound);
+                if current.default_repr().is_none() {
+                    return Err(TypeError::NoRepr(*current));
+                }
+            }
+            NumType::Repr(repr) => {
+                if !bound.holds(repr) {
+                    return Err(TypeError::MismatchRepr { repr, bound });
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Representation of a numeric variable left unknown after inference
+    pub fn default_repr(&self, var: &Var) -> Option<Repr> {
+        self.num_bounds
+            .get(var)
+            .copied()
+            .unwrap_or_default()
+            .default_repr()
+    }
+    fn satisfy(
+        &mut self,
+        subs: &mut Subs,
+        ty: &Type,
+        constraint: Constraint,
+    ) -> Result<bool, TypeError> {
+        let satisfied = match ty {
+            Type::Var(var) if self.is_rigid(var) => false,
+            Type::Var(var) => {
+                let current = self.constraints.entry(var.clone()).or_insert(constraint);
+                *current = (*current).max(constraint);
+                true
+            }
+            Type::Alias(alias) => self.satisfy(subs, &alias.ty, constraint)?,
+            Type::Cons(cons) => match cons {
+                Cons::Num(_) | Cons::Bool | Cons::Char | Cons::Never => true,
+                Cons::Fun(_, _) => false,
+                Cons::Str => constraint == Constraint::Clone,
+                // references are shallowly copied and only the immutable ones
+                // can be
+                Cons::Ref(mutability, _) => {
+                    match mutability.clone().unify_with(MutType::Imm, subs, self) {
+                        Ok(()) => true,
+                        Err(TypeError::MismatchMut) => false,
+                        Err(err) => return Err(err),
+                    }
+                }
+                Cons::Array(_) if constraint == Constraint::Copy => false,
+                Cons::Array(ty) => self.satisfy(subs, ty, constraint)?,
+                Cons::Record(keyed) | Cons::Union(keyed) => {
+                    let rest = keyed.rest.clone().map(Type::Var);
+                    self.satisfy_all(subs, keyed.fields.values().chain(&rest), constraint)?
+                }
+                Cons::Tuple(OrderedAnd::NonRow(tuple)) => {
+                    self.satisfy_all(subs, tuple.iter(), constraint)?
+                }
+                Cons::Tuple(OrderedAnd::Row(left, rest, right)) => {
+                    let rest = Type::Var(rest.clone());
+                    let types = left.iter().chain([&rest]).chain(right);
+                    self.satisfy_all(subs, types, constraint)?
+                }
+                Cons::RecordTuple(OrderedAnd::NonRow(record_tuple)) => {
+                    let types = record_tuple.iter().map(|(_, ty)| ty);
+                    self.satisfy_all(subs, types, constraint)?
+                }
+                Cons::RecordTuple(OrderedAnd::Row(left, rest, right)) => {
+                    let rest = Type::Var(rest.clone());
+                    let types = left
+                        .iter()
+                        .map(|(_, ty)| ty)
+                        .chain([&rest])
+                        .chain(right.iter().map(|(_, ty)| ty));
+                    self.satisfy_all(subs, types, constraint)?
+                }
+                Cons::Newtype(newtype) => {
+                    let types = self.newtypes[newtype.id].field_types(&newtype.args)?;
+                    self.satisfy_all(subs, &types, constraint)?
+                }
+            },
+        };
+        Ok(satisfied)
+    }
+    fn satisfy_all<'a>(
+        &mut self,
+        subs: &mut Subs,
+        types: impl IntoIterator<Item = &'a Type>,
+        constraint: Constraint,
+    ) -> Result<bool, TypeError> {
+        for ty in types {
+            if !self.satisfy(subs, ty, constraint)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+    fn bind(&mut self, subs: &mut Subs, var: Var, ty: Type1) -> Result<(), TypeError> {
+        if self.is_rigid(&var) {
+            return match ty {
+                Type1::Type(mut ty) => {
+                    ty.substitute(&self.bindings)?;
+                    Err(TypeError::MismatchRigid {
+                        generic: var.name,
+                        found: Box::new(ty),
+                    })
+                }
+                Type1::MutType(_) | Type1::NumType(_) => Err(TypeError::MismatchKind),
+            };
+        }
+        if let Some(bound) = self.bindings.get(var.clone()) {
+            return match (bound, ty) {
+                (Type1::Type(ty1), Type1::Type(ty2)) => ty1.unify_with(ty2, subs, self),
+                (Type1::MutType(ty1), Type1::MutType(ty2)) => ty1.unify_with(ty2, subs, self),
+                (Type1::NumType(ty1), Type1::NumType(ty2)) => ty1.unify_with(ty2, subs, self),
+                _ => Err(TypeError::MismatchKind),
+            };
+        }
+        let single = once((var.clone(), ty.clone())).collect();
+        for bound in self.bindings.hashmap_mut().values_mut() {
+            bound.substitute(&single)?;
+        }
+        self.bindings.insert(var.clone(), ty.clone());
+        subs.insert(var.clone(), ty.clone());
+        match ty {
+            Type1::Type(ty) => {
+                if let Some(constraint) = self.constraints.remove(&var) {
+                    self.constrain(subs, &ty, constraint)?;
+                }
+            }
+            Type1::NumType(num) => {
+                if let Some(bound) = self.num_bounds.remove(&var) {
+                    self.require_num(&num, bound)?;
+                }
+            }
+            Type1::MutType(_) => (),
+        }
+        Ok(())
+    }
+}
+/// What can be done to duplicate values of a type
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+pub(super) enum Constraint {
+    /// Can be duplicated with `clone`, these are everything but functions
+    /// and mutable references
+    Clone,
+    /// Can be duplicated implicitly, these are numbers, booleans, immutable
+    /// references, and records, tuples, and tags of these
+    Copy,
+}
+impl Constraint {
+    fn error(self, ty: Type) -> TypeError {
+        match self {
+            Self::Clone => TypeError::NotClone(Box::new(ty)),
+            Self::Copy => TypeError::NotCopy(Box::new(ty)),
+        }
+    }
 }
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum Kind {
     Type,
     MutType,
+    NumType,
 }
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct KindedVar {
-    kind: Kind,
-    var: Var,
+    pub(super) kind: Kind,
+    pub(super) var: Var,
 }
-trait FreeVars {
+pub(super) trait FreeVars {
     fn free_vars(&self) -> HashSet<KindedVar>;
 }
+/// Free variables in the order they first appear, unlike [`FreeVars`] the
+/// order is stable so types can be compared up to renaming
+pub(super) trait OrderedVars {
+    fn ordered_vars(&self, vars: &mut Vec<KindedVar>);
+}
+pub(super) fn push_var(vars: &mut Vec<KindedVar>, var: KindedVar) {
+    if !vars.contains(&var) {
+        vars.push(var);
+    }
+}
 pub(super) trait Substitutable {
     fn substitute(&mut self, subs: &Subs) -> Result<(), TypeError>;
 }
@@ -78,15 +331,73 @@ pub(super) trait Unifiable {
 pub enum Type {
     Var(Var),
     Cons(Cons),
+    Alias(Alias),
+}
+impl Type {
+    /// Expands aliases until the outermost type is no longer an alias
+    pub(super) fn unalias(self) -> Self {
+        match self {
+            Self::Alias(alias) => alias.ty.unalias(),
+            ty => ty,
+        }
+    }
+    /// Whether a value of this type may hold a reference, type variables
+    /// may stand for one
+    pub fn may_hold_ref(&self) -> bool {
+        match self {
+            Self::Var(_) => true,
+            Self::Cons(cons) => cons.may_hold_ref(),
+            Self::Alias(alias) => alias.ty.may_hold_ref(),
+        }
+    }
 }
 impl PrettyPrint for Type {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
         match self {
             Self::Var(var) => Box::new(var.to_string()),
             Self::Cons(cons) => cons.to_pretty_print(),
+            Self::Alias(alias) => alias.to_pretty_print(),
         }
     }
 }
+/// An expanded type alias, it's only kept for printing and behaves just like
+/// its expansion
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Alias {
+    pub name: Atom,
+    pub args: Box<[Type]>,
+    pub ty: Box<Type>,
+}
+impl PrettyPrint for Alias {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        if self.args.is_empty() {
+            Box::new(self.name.to_string())
+        } else {
+            line([
+                Box::new(self.name.to_string()),
+                bracket(
+                    "(",
+                    ")",
+                    sequence(
+                        self.args
+                            .iter()
+                            .map(|ty| postfix(", ", PrettyPrint::to_pretty_print(ty))),
+                    ),
+                ),
+            ])
+        }
+    }
+}
+impl Display for Type {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let mut buffer = Vec::new();
+        PrettyPrint::to_pretty_print(self)
+            .write(&mut buffer, "", usize::MAX)
+            .map_err(|_| fmt::Error)?;
+        write!(fmt, "{}", String::from_utf8_lossy(&buffer))?;
+        Ok(())
+    }
+}
 impl PrettyPrintType for Type {
     const TYPED: bool = true;
     type FunScheme = Scheme;
@@ -104,6 +415,22 @@ impl FreeVars for Type {
             })
             .collect(),
             Self::Cons(cons) => cons.free_vars(),
+            Self::Alias(alias) => alias.ty.free_vars(),
+        }
+    }
+}
+impl OrderedVars for Type {
+    fn ordered_vars(&self, vars: &mut Vec<KindedVar>) {
+        match self {
+            Self::Var(var) => push_var(
+                vars,
+                KindedVar {
+                    kind: Kind::Type,
+                    var: var.clone(),
+                },
+            ),
+            Self::Cons(cons) => cons.ordered_vars(vars),
+            Self::Alias(alias) => alias.ty.ordered_vars(vars),
         }
     }
 }
@@ -114,11 +441,19 @@ impl Substitutable for Type {
                 if let Some(ty) = subs.get(var.clone()) {
                     match ty {
                         Type1::Type(ty) => *self = ty,
-                        Type1::MutType(_) => return Err(TypeError::MismatchKind),
+                        Type1::MutType(_) | Type1::NumType(_) => {
+                            return Err(TypeError::MismatchKind)
+                        }
                     }
                 }
             }
             Self::Cons(cons) => cons.substitute(subs)?,
+            Self::Alias(alias) => {
+                for arg in alias.args.iter_mut() {
+                    arg.substitute(subs)?;
+                }
+                alias.ty.substitute(subs)?;
+            }
         }
         Ok(())
     }
@@ -129,6 +464,12 @@ impl FreeVars for (Atom, Type) {
         ty.free_vars()
     }
 }
+impl OrderedVars for (Atom, Type) {
+    fn ordered_vars(&self, vars: &mut Vec<KindedVar>) {
+        let (_, ty) = self;
+        ty.ordered_vars(vars);
+    }
+}
 impl Substitutable for (Atom, Type) {
     fn substitute(&mut self, subs: &Subs) -> Result<(), TypeError> {
         let (_, ty) = self;
@@ -142,29 +483,47 @@ impl Unifiable for Type {
         subs: &mut Subs,
         var_state: &mut VarState,
     ) -> Result<(), TypeError> {
-        match (self, other) {
-            (Self::Cons(cons1), Self::Cons(cons2)) => cons1.unify_with(cons2, subs, var_state)?,
+        let mut this = self;
+        let mut other = other;
+        this.substitute(var_state.bindings())?;
+        other.substitute(var_state.bindings())?;
+        match (this, other) {
+            // the bottom type unifies with anything without binding it
+            (Self::Cons(Cons::Never), _) | (_, Self::Cons(Cons::Never)) => (),
+            // variables are matched before aliases so bound types keep their
+            // alias names
             (Self::Var(var), ty) | (ty, Self::Var(var)) => {
-                if ty == Self::Var(var.clone()) {
+                if ty.clone().unalias() == Self::Var(var.clone()) {
                     // do nothing
                 } else if ty.free_vars().contains(&KindedVar {
                     kind: Kind::Type,
                     var: var.clone(),
                 }) {
                     return Err(TypeError::InfiniteOccurrence);
+                } else if var_state.is_rigid(&var) {
+                    match ty.unalias() {
+                        Type::Var(other) if !var_state.is_rigid(&other) => {
+                            var_state.bind(subs, other, Type1::Type(Type::Var(var)))?;
+                        }
+                        ty => var_state.bind(subs, var, Type1::Type(ty))?,
+                    }
                 } else if var.name == keyword!("") {
-                    subs.insert(var, Type1::Type(ty));
+                    var_state.bind(subs, var, Type1::Type(ty))?;
                 } else if let Type::Var(
                     var1 @ Var {
                         name: keyword!(""), ..
                     },
                 ) = ty
                 {
-                    subs.insert(var1, Type1::Type(Type::Var(var)));
+                    var_state.bind(subs, var1, Type1::Type(Type::Var(var)))?;
                 } else {
-                    subs.insert(var, Type1::Type(ty));
+                    var_state.bind(subs, var, Type1::Type(ty))?;
                 }
             }
+            (Self::Alias(alias), ty) | (ty, Self::Alias(alias)) => {
+                alias.ty.unify_with(ty, subs, var_state)?;
+            }
+            (Self::Cons(cons1), Self::Cons(cons2)) => cons1.unify_with(cons2, subs, var_state)?,
         }
         Ok(())
     }
@@ -212,13 +571,26 @@ impl FreeVars for MutType {
         }
     }
 }
+impl OrderedVars for MutType {
+    fn ordered_vars(&self, vars: &mut Vec<KindedVar>) {
+        if let Self::Var(var) = self {
+            push_var(
+                vars,
+                KindedVar {
+                    kind: Kind::MutType,
+                    var: var.clone(),
+                },
+            );
+        }
+    }
+}
 impl Substitutable for MutType {
     fn substitute(&mut self, subs: &Subs) -> Result<(), TypeError> {
         if let Self::Var(var) = self {
             if let Some(ty) = subs.get(var.clone()) {
                 match ty {
                     Type1::MutType(mutability) => *self = mutability,
-                    Type1::Type(_) => return Err(TypeError::MismatchKind),
+                    Type1::Type(_) | Type1::NumType(_) => return Err(TypeError::MismatchKind),
                 }
             }
         }
@@ -226,8 +598,17 @@ impl Substitutable for MutType {
     }
 }
 impl Unifiable for MutType {
-    fn unify_with(self, other: Self, subs: &mut Subs, _: &mut VarState) -> Result<(), TypeError> {
-        match (self, other) {
+    fn unify_with(
+        self,
+        other: Self,
+        subs: &mut Subs,
+        var_state: &mut VarState,
+    ) -> Result<(), TypeError> {
+        let mut this = self;
+        let mut other = other;
+        this.substitute(var_state.bindings())?;
+        other.substitute(var_state.bindings())?;
+        match (this, other) {
             (Self::Mut, Self::Mut) | (Self::Imm, Self::Imm) => (),
             (Self::Var(var), ty) | (ty, Self::Var(var)) => {
                 if ty == Self::Var(var.clone()) {
@@ -238,19 +619,19 @@ impl Unifiable for MutType {
                 }) {
                     return Err(TypeError::InfiniteOccurrence);
                 } else if var.name == keyword!("") {
-                    subs.insert(var, Type1::MutType(ty));
+                    var_state.bind(subs, var, Type1::MutType(ty))?;
                 } else if let MutType::Var(
                     var1 @ Var {
                         name: keyword!(""), ..
                     },
                 ) = ty
                 {
-                    subs.insert(var1, Type1::MutType(MutType::Var(var)));
+                    var_state.bind(subs, var1, Type1::MutType(MutType::Var(var)))?;
                 } else {
-                    subs.insert(var, Type1::MutType(ty));
+                    var_state.bind(subs, var, Type1::MutType(ty))?;
                 }
             }
-            _ => return Err(TypeError::MismatchCons),
+            _ => return Err(TypeError::MismatchMut),
         }
         Ok(())
     }
@@ -259,12 +640,22 @@ impl Unifiable for MutType {
 enum Type1 {
     Type(Type),
     MutType(MutType),
+    NumType(NumType),
 }
 impl From<KindedVar> for Type1 {
     fn from(var: KindedVar) -> Self {
         match var.kind {
             Kind::Type => Self::Type(Type::Var(var.var)),
             Kind::MutType => Self::MutType(MutType::Var(var.var)),
+            Kind::NumType => Self::NumType(NumType::Var(var.var)),
+        }
+    }
+}
+impl Type1 {
+    pub(super) fn unalias(self) -> Self {
+        match self {
+            Self::Type(ty) => Self::Type(ty.unalias()),
+            ty => ty,
         }
     }
 }
@@ -273,6 +664,7 @@ impl FreeVars for Type1 {
         match self {
             Self::Type(ty) => ty.free_vars(),
             Self::MutType(ty) => ty.free_vars(),
+            Self::NumType(ty) => ty.free_vars(),
         }
     }
 }
@@ -281,28 +673,77 @@ impl Substitutable for Type1 {
         match self {
             Self::Type(ty) => ty.substitute(subs),
             Self::MutType(ty) => ty.substitute(subs),
+            Self::NumType(ty) => ty.substitute(subs),
         }
     }
 }
+/// A trait applied to a type, `Eq(a)` requires `a` to implement `Eq`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Predicate {
+    pub trait_name: Atom,
+    pub ty: Type,
+}
+impl Display for Predicate {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "{}({})", self.trait_name, self.ty)?;
+        Ok(())
+    }
+}
+impl FreeVars for Predicate {
+    fn free_vars(&self) -> HashSet<KindedVar> {
+        self.ty.free_vars()
+    }
+}
+impl Substitutable for Predicate {
+    fn substitute(&mut self, subs: &Subs) -> Result<(), TypeError> {
+        self.ty.substitute(subs)
+    }
+}
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Scheme {
     pub for_all: HashSet<KindedVar>,
+    /// Predicates the generic variables must satisfy, each is passed as a
+    /// dictionary after elaboration
+    pub given: Vec<Predicate>,
     pub ty: Type,
 }
 impl PrettyPrintFunScheme for Scheme {
     fn to_pretty_print_generics(&self) -> Box<[Box<dyn PrettyPrintTree>]> {
+        // numbers of any representation are written `Num`
         self.for_all
             .iter()
+            .filter(|var| var.kind != Kind::NumType)
             .map(|var| Box::new(var.var.to_string()) as Box<dyn PrettyPrintTree>)
             .collect::<Vec<_>>()
             .into()
     }
 }
+impl Display for Scheme {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let mut generics: Vec<_> = self
+            .for_all
+            .iter()
+            .filter(|var| var.kind != Kind::NumType)
+            .map(|var| var.var.to_string())
+            .collect();
+        if !generics.is_empty() {
+            generics.sort();
+            write!(fmt, ":({}): ", generics.join(", "))?;
+        }
+        if !self.given.is_empty() {
+            let given: Vec<_> = self.given.iter().map(Predicate::to_string).collect();
+            write!(fmt, "given {}: ", given.join(", "))?;
+        }
+        write!(fmt, "{}", self.ty)?;
+        Ok(())
+    }
+}
 impl FreeVars for Scheme {
     fn free_vars(&self) -> HashSet<KindedVar> {
         self.ty
             .free_vars()
             .into_iter()
+            .chain(self.given.iter().flat_map(Predicate::free_vars))
             .filter(|var| !self.for_all.contains(var))
             .collect()
     }
@@ -312,28 +753,94 @@ impl Substitutable for Scheme {
         let mut subs = subs.clone();
         subs.filter_off(&self.for_all);
         self.ty.substitute(&subs)?;
+        for predicate in &mut self.given {
+            predicate.substitute(&subs)?;
+        }
         Ok(())
     }
 }
 impl Scheme {
+    /// Whether both are the same scheme, generic variables may be named
+    /// differently
+    pub fn is_equivalent(&self, other: &Self) -> bool {
+        self.canonical() == other.canonical()
+    }
+    /// Renames the generic variables by the order they appear
+    fn canonical(&self) -> Self {
+        let mut vars = Vec::new();
+        self.ty.ordered_vars(&mut vars);
+        for predicate in &self.given {
+            predicate.ty.ordered_vars(&mut vars);
+        }
+        vars.retain(|var| self.for_all.contains(var));
+        let renamed: Vec<_> = vars
+            .into_iter()
+            .enumerate()
+            .map(|(i, var)| {
+                let new_var = KindedVar {
+                    kind: var.kind,
+                    var: Var {
+                        name: keyword!(""),
+                        id: i as u32 + 1,
+                    },
+                };
+                (var, new_var)
+            })
+            .collect();
+        let subs = renamed
+            .iter()
+            .map(|(var, new_var)| (var.var.clone(), Type1::from(new_var.clone())))
+            .collect();
+        let mut ty = self.ty.clone();
+        let mut given = self.given.clone();
+        // the renamed variables are fresh, they can't mismatch kinds
+        ty.substitute(&subs).unwrap();
+        for predicate in &mut given {
+            predicate.substitute(&subs).unwrap();
+        }
+        Self {
+            for_all: renamed.into_iter().map(|(_, new_var)| new_var).collect(),
+            given,
+            ty,
+        }
+    }
     pub(super) fn instantiate(self, var_state: &mut VarState) -> Result<Type, TypeError> {
+        self.instantiate_given(var_state).map(|(ty, _)| ty)
+    }
+    /// Instantiates the type along with the `given` predicates, these are
+    /// then required of the instantiated type
+    pub(super) fn instantiate_given(
+        self,
+        var_state: &mut VarState,
+    ) -> Result<(Type, Vec<Predicate>), TypeError> {
         let subs = self
             .for_all
             .into_iter()
             .map(|var| {
                 let new_var = var_state.new_named(var.var.name.clone());
+                if let Some(constraint) = var_state.constraints.get(&var.var).copied() {
+                    var_state.constraints.insert(new_var.clone(), constraint);
+                }
+                if let Some(bound) = var_state.num_bounds.get(&var.var).copied() {
+                    var_state.num_bounds.insert(new_var.clone(), bound);
+                }
                 (
                     var.var,
                     match var.kind {
                         Kind::Type => Type1::Type(Type::Var(new_var)),
                         Kind::MutType => Type1::MutType(MutType::Var(new_var)),
+                        Kind::NumType => Type1::NumType(NumType::Var(new_var)),
                     },
                 )
             })
             .collect();
         let mut ty = self.ty;
         ty.substitute(&subs)?;
-        Ok(ty)
+        let mut given = self.given;
+        for predicate in &mut given {
+            predicate.substitute(&subs)?;
+        }
+        Ok((ty, given))
     }
 }
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
@@ -358,11 +865,17 @@ impl Subs {
         self.hashmap().is_empty()
     }
     fn get(&self, var: Var) -> Option<Type1> {
-        self.hashmap().get(&var).map(Type1::clone)
+        self.hashmap().get(&var).cloned()
     }
     fn insert(&mut self, var: Var, ty: Type1) {
         self.hashmap_mut().insert(var, ty);
     }
+    pub fn insert_type(&mut self, var: Var, ty: Type) {
+        self.insert(var, Type1::Type(ty));
+    }
+    pub fn insert_num(&mut self, var: Var, num: NumType) {
+        self.insert(var, Type1::NumType(num));
+    }
     pub fn filter_off(&mut self, vars: &HashSet<KindedVar>) {
         for var in vars {
             self.hashmap_mut().remove(&var.var);
@@ -392,6 +905,8 @@ impl FromIterator<(Var, Type1)> for Subs {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub(super) struct SchemeMut {
     pub(super) is_mut: bool,
+    /// Whether it's a method of the trait in its `given` predicate
+    pub(super) is_method: bool,
     pub(super) scheme: Scheme,
 }
 impl FreeVars for SchemeMut {
@@ -405,31 +920,106 @@ impl Substitutable for SchemeMut {
     }
 }
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
-pub(super) struct Env(HashMap<Var, SchemeMut>);
+pub(super) struct Env {
+    vars: HashMap<Var, SchemeMut>,
+    type_vars: HashMap<Atom, KindedVar>,
+    aliases: HashMap<Atom, AliasDeclare>,
+    newtypes: HashMap<Atom, usize>,
+    modules: HashMap<Atom, Env>,
+    path: Box<[Atom]>,
+    visibility: HashMap<Atom, Visibility>,
+}
 impl Env {
     pub fn new() -> Self {
         Self::default()
     }
     fn hashmap(&self) -> &HashMap<Var, SchemeMut> {
-        let Self(map) = self;
-        map
+        &self.vars
     }
     fn hashmap_mut(&mut self) -> &mut HashMap<Var, SchemeMut> {
-        let Self(map) = self;
-        map
+        &mut self.vars
     }
     pub fn get_ty(&self, var: Var) -> Option<Scheme> {
         self.hashmap().get(&var).map(|x| Scheme::clone(&x.scheme))
     }
+    pub fn get_scheme_mut(&self, var: &Var) -> Option<&SchemeMut> {
+        self.hashmap().get(var)
+    }
     pub fn get_mut(&self, var: Var) -> Option<bool> {
         self.hashmap().get(&var).map(|x| x.is_mut)
     }
     pub fn insert(&mut self, var: Var, scheme_mut: SchemeMut) -> Option<SchemeMut> {
+        self.modules.remove(&var.name);
         self.hashmap_mut().insert(var, scheme_mut)
     }
     pub fn remove(&mut self, var: Var) {
         self.hashmap_mut().remove(&var);
     }
+    /// Type variable named on type annotations
+    pub fn get_type_var(&self, name: &Atom) -> Option<KindedVar> {
+        self.type_vars.get(name).cloned()
+    }
+    pub fn insert_type_var(&mut self, name: Atom, var: KindedVar) {
+        self.type_vars.insert(name, var);
+    }
+    pub fn get_alias(&self, name: &Atom) -> Option<&AliasDeclare> {
+        self.aliases.get(name)
+    }
+    pub fn insert_alias(&mut self, alias: AliasDeclare) {
+        self.newtypes.remove(&alias.ident);
+        self.aliases.insert(alias.ident.clone(), alias);
+    }
+    /// Id of the newtype named on type annotations
+    pub fn get_newtype(&self, name: &Atom) -> Option<usize> {
+        self.newtypes.get(name).copied()
+    }
+    pub fn insert_newtype(&mut self, name: Atom, id: usize) {
+        self.aliases.remove(&name);
+        self.newtypes.insert(name, id);
+    }
+    pub fn get_module(&self, name: &Atom) -> Option<&Env> {
+        self.modules.get(name)
+    }
+    pub fn insert_module(&mut self, name: Atom, module: Env) {
+        self.vars.remove(&Var::new_bare(name.clone()));
+        self.modules.insert(name, module);
+    }
+    /// Path of the module currently being inferred, relative to the root
+    /// module
+    pub fn path(&self) -> &[Atom] {
+        &self.path
+    }
+    pub fn enter_module(&mut self, name: Atom) {
+        self.path = self.path.iter().cloned().chain(once(name)).collect();
+    }
+    /// Keeps only the variables and modules declared on `members`, used to
+    /// take the members of a module out of its environment
+    pub fn members(self, members: Vec<(Atom, Visibility)>) -> Self {
+        let visibility: HashMap<_, _> = members.into_iter().collect();
+        let vars = self
+            .vars
+            .into_iter()
+            .filter(|(var, _)| visibility.contains_key(&var.name))
+            .collect();
+        let modules = self
+            .modules
+            .into_iter()
+            .filter(|(name, _)| visibility.contains_key(name))
+            .collect();
+        Self {
+            vars,
+            modules,
+            path: self.path,
+            visibility,
+            ..Self::default()
+        }
+    }
+    /// Whether a member of this module can be accessed from the module at
+    /// `path`
+    pub fn is_visible(&self, name: &Atom, path: &[Atom]) -> bool {
+        let visibility = self.visibility.get(name).unwrap_or(&Visibility::Private);
+        is_visible(visibility, &self.path, path)
+    }
     pub fn generalize(&self, ty: Type) -> Scheme {
         let env_free_vars = self.free_vars();
         let for_all = ty
@@ -437,7 +1027,20 @@ impl Env {
             .into_iter()
             .filter(|var| !env_free_vars.contains(var))
             .collect();
-        Scheme { for_all, ty }
+        Scheme {
+            for_all,
+            given: Vec::new(),
+            ty,
+        }
+    }
+}
+/// Whether something declared with `visibility` in the module at `declared`
+/// can be accessed from the module at `path`
+pub(super) fn is_visible(visibility: &Visibility, declared: &[Atom], path: &[Atom]) -> bool {
+    match visibility {
+        Visibility::Public => true,
+        Visibility::Restricted(allowed) => path.starts_with(allowed) || path.starts_with(declared),
+        Visibility::Private => path.starts_with(declared),
     }
 }
 impl FreeVars for Env {
@@ -445,6 +1048,8 @@ impl FreeVars for Env {
         self.hashmap()
             .values()
             .flat_map(SchemeMut::free_vars)
+            .chain(self.type_vars.values().cloned())
+            .chain(self.modules.values().flat_map(Env::free_vars))
             .collect()
     }
 }
@@ -453,6 +1058,9 @@ impl Substitutable for Env {
         for ty in self.hashmap_mut().values_mut() {
             ty.substitute(subs)?;
         }
+        for module in self.modules.values_mut() {
+            module.substitute(subs)?;
+        }
         Ok(())
     }
 }
@@ -461,19 +1069,264 @@ impl Extend<(Var, SchemeMut)> for Env {
         self.hashmap_mut().extend(iter);
     }
 }
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TypeError {
-    MismatchCons,
+    MismatchCons(Box<Type>, Box<Type>),
+    MismatchMut,
     MismatchKind,
     MismatchArity,
     MismatchName,
     InfiniteOccurrence,
     Overlap,
-    UnboundVar,
+    UnboundVar(Atom),
     AssignedImm,
+    /// `break` or `continue` outside of a loop, or `return` outside of a
+    /// function
+    MisplacedJump {
+        keyword: Atom,
+        span: Span,
+    },
+    /// Pattern of a declaration without `else` or of a `for` loop that may
+    /// not match
+    RefutablePattern(Span),
+    /// The `else` block of a declaration
+    NonDivergingElse(Span),
+    /// The examples of unmatched values and the scrutinee
+    NonExhaustive(Box<[Witness]>, Span),
+    UnboundType(Atom),
+    MismatchTypeArgs {
+        name: Atom,
+        expected: usize,
+        found: usize,
+    },
+    MismatchRigid {
+        generic: Atom,
+        found: Box<Type>,
+    },
+    EscapedRigid(Atom),
+    CyclicAlias(Atom),
+    CyclicNewtype(Atom),
+    InvalidUtf8(Str),
+    UnloadedModule(Atom),
+    PrivateMember {
+        name: Atom,
+        module: Box<[Atom]>,
+    },
+    PrivateField {
+        name: Atom,
+        newtype: Atom,
+    },
+    UnboundField {
+        name: Atom,
+        newtype: Atom,
+    },
+    MismatchAnnotation {
+        annotation: Box<Type>,
+        found: Box<Type>,
+    },
+    MismatchRepr {
+        repr: Repr,
+        bound: NumBound,
+    },
+    NoRepr(NumBound),
+    NotClone(Box<Type>),
+    NotCopy(Box<Type>),
+    UnboundTrait(Atom),
+    DuplicateTrait(Atom),
+    NoInstance(Box<Predicate>),
+    AmbiguousInstance(Box<Predicate>),
+    OverlappingImpls(Box<Predicate>, Box<Predicate>),
+    MissingMethod {
+        trait_name: Atom,
+        method: Atom,
+    },
+    UnknownMethod {
+        trait_name: Atom,
+        method: Atom,
+    },
+    /// An error within the expression, pattern or declaration at the span
+    At(Box<TypeError>, Span),
+}
+impl TypeError {
+    /// Where the error is, when it's known
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::RefutablePattern(span)
+            | Self::NonDivergingElse(span)
+            | Self::MisplacedJump { span, .. }
+            | Self::NonExhaustive(_, span)
+            | Self::At(_, span) => Some(*span),
+            _ => None,
+        }
+    }
+    /// Locates the error at `span` unless it's already located somewhere
+    /// within it
+    pub fn at(self, span: Span) -> Self {
+        if self.span().is_some() {
+            self
+        } else {
+            Self::At(Box::new(self), span)
+        }
+    }
+    /// The error without the span it's located at
+    pub fn unlocated(self) -> Self {
+        match self {
+            Self::At(err, _) => *err,
+            err => err,
+        }
+    }
 }
 impl Display for TypeError {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
-        write!(fmt, "TypeError")
+        match self {
+            Self::NonExhaustive(witnesses, _) => {
+                write!(fmt, "non-exhaustive match, missing ")?;
+                for (i, witness) in witnesses.iter().enumerate() {
+                    if i != 0 {
+                        write!(fmt, ", ")?;
+                    }
+                    write!(fmt, "`{witness}`")?;
+                }
+            }
+            Self::MismatchRigid { generic, found } => write!(
+                fmt,
+                "mismatched type, expected generic type `{generic}`, found `{found}`"
+            )?,
+            Self::EscapedRigid(generic) => {
+                write!(fmt, "generic type `{generic}` escapes its function")?
+            }
+            Self::PrivateMember { name, module } => {
+                let module: Vec<&str> = module.iter().map(Atom::as_ref).collect();
+                write!(
+                    fmt,
+                    "`{name}` is private, it is declared in module `{}`",
+                    module.join("."),
+                )?
+            }
+            Self::PrivateField { name, newtype } => {
+                write!(fmt, "field `{name}` of `{newtype}` is private")?
+            }
+            Self::UnboundField { name, newtype } => {
+                write!(fmt, "`{newtype}` has no field `{name}`")?
+            }
+            Self::UnloadedModule(module) => write!(fmt, "module `{module}` is not loaded")?,
+            Self::CyclicAlias(alias) => write!(fmt, "type alias `{alias}` refers to itself")?,
+            Self::CyclicNewtype(newtype) => write!(fmt, "newtype `{newtype}` contains itself")?,
+            Self::InvalidUtf8(string) => write!(fmt, "string literal {string} is not valid UTF-8")?,
+            Self::MismatchRepr { repr, bound } => write!(
+                fmt,
+                "mismatched number representation, {}",
+                bound.rejects(*repr).unwrap_or_default()
+            )?,
+            Self::NoRepr(bound) => write!(fmt, "mismatched number representation, {bound}")?,
+            Self::NotClone(ty) => write!(
+                fmt,
+                "`{ty}` can't be cloned, functions and mutable references are never cloned"
+            )?,
+            Self::NotCopy(ty) => write!(
+                fmt,
+                "`{ty}` can't be implicitly copied, clone it with `clone` or move it with `>`"
+            )?,
+            Self::UnboundTrait(name) => write!(fmt, "trait `{name}` is not declared")?,
+            Self::DuplicateTrait(name) => write!(fmt, "trait `{name}` is already declared")?,
+            Self::NoInstance(predicate) => write!(fmt, "no implementation of `{predicate}`")?,
+            Self::AmbiguousInstance(predicate) => write!(
+                fmt,
+                "ambiguous implementation of `{predicate}`, the type needs an annotation"
+            )?,
+            Self::OverlappingImpls(first, second) => write!(
+                fmt,
+                "implementations of `{first}` and `{second}` overlap"
+            )?,
+            Self::MissingMethod { trait_name, method } => write!(
+                fmt,
+                "method `{method}` of trait `{trait_name}` is not implemented"
+            )?,
+            Self::UnknownMethod { trait_name, method } => {
+                write!(fmt, "`{method}` is not a method of trait `{trait_name}`")?
+            }
+            Self::MismatchMut => write!(
+                fmt,
+                "mismatched mutability, an immutable reference is used where a mutable one is required"
+            )?,
+            Self::AssignedImm => write!(
+                fmt,
+                "assignment to an immutable place, it is neither declared with `mut` nor behind a mutable reference"
+            )?,
+            Self::MisplacedJump { keyword, .. } if *keyword == keyword!("return") => {
+                write!(fmt, "`return` outside of a function")?
+            }
+            Self::MisplacedJump { keyword, .. } => write!(fmt, "`{keyword}` outside of a loop")?,
+            Self::RefutablePattern(_) => write!(
+                fmt,
+                "refutable pattern, only declarations with an `else` block may have patterns that don't match every value"
+            )?,
+            Self::NonDivergingElse(_) => write!(
+                fmt,
+                "the `else` block of a declaration must diverge, with a jump or an expression of type `Never`"
+            )?,
+            Self::MismatchAnnotation { annotation, found } => write!(
+                fmt,
+                "mismatched type, expected `{annotation}` from annotation, found `{found}`"
+            )?,
+            Self::MismatchCons(first, second) => {
+                write!(fmt, "mismatched types `{first}` and `{second}`")?
+            }
+            Self::MismatchArity => write!(
+                fmt,
+                "mismatched number of fields or elements between records, tuples or unions"
+            )?,
+            Self::UnboundType(name) => write!(fmt, "type `{name}` is not declared")?,
+            Self::MismatchTypeArgs {
+                name,
+                expected,
+                found,
+            } => write!(
+                fmt,
+                "mismatched number of type arguments, `{name}` takes {expected}, found {found}"
+            )?,
+            Self::MismatchKind => write!(
+                fmt,
+                "mismatched kinds, such as a tuple used where a record is expected"
+            )?,
+            Self::MismatchName => write!(fmt, "mismatched field names between records")?,
+            Self::InfiniteOccurrence => {
+                write!(fmt, "infinite type, a type variable would contain itself")?
+            }
+            Self::Overlap => write!(
+                fmt,
+                "overlapping fields between a record or union and its rest"
+            )?,
+            Self::UnboundVar(name) => write!(fmt, "`{name}` is not declared")?,
+            Self::At(err, _) => write!(fmt, "{err}")?,
+        }
+        Ok(())
+    }
+}
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TypeWarning {
+    /// The index of the arm and its pattern
+    UnreachableArm(usize, Span),
+    /// Statements or the value of a block after an expression that diverges,
+    /// located at the first of them
+    UnreachableCode(Span),
+}
+impl TypeWarning {
+    /// Where the warning is
+    pub fn span(&self) -> Span {
+        match self {
+            Self::UnreachableArm(_, span) | Self::UnreachableCode(span) => *span,
+        }
+    }
+}
+impl Display for TypeWarning {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnreachableArm(arm, _) => write!(fmt, "match arm {} is unreachable", arm + 1)?,
+            Self::UnreachableCode(_) => {
+                write!(fmt, "code after a diverging expression is unreachable")?
+            }
+        }
+        Ok(())
     }
 }
@@ -1,6 +1,7 @@
 use super::FreeVars;
 use crate::ty::{
-    Kind, KindedVar, MutType, Subs, Substitutable, Type, Type1, TypeError, Unifiable, Var, VarState,
+    num::NumType, push_var, Kind, KindedVar, MutType, OrderedVars, Subs, Substitutable, Type,
+    Type1, TypeError, Unifiable, Var, VarState,
 };
 use hir::{
     pretty_print::{bracket, line, postfix, prefix, sequence, PrettyPrint, PrettyPrintTree},
@@ -15,8 +16,12 @@ use std::{
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Cons {
-    Num,
+    Num(NumType),
     Bool,
+    /// A Unicode scalar value
+    Char,
+    /// Bytes that are valid UTF-8
+    Str,
     Ref(MutType, Box<Type>),
     Array(Box<Type>),
     Fun(Box<Type>, Box<Type>),
@@ -24,12 +29,36 @@ pub enum Cons {
     Record(Keyed),
     Tuple(OrderedAnd<Type>),
     Union(Keyed),
+    Newtype(Newtype),
+    /// The type of expressions that never produce a value, it unifies with
+    /// any type
+    Never,
+}
+impl Cons {
+    pub(super) fn may_hold_ref(&self) -> bool {
+        match self {
+            Self::Num(_) | Self::Bool | Self::Char | Self::Str | Self::Never => false,
+            // functions hold what they capture
+            Self::Ref(_, _) | Self::Fun(_, _) => true,
+            Self::Array(ty) => ty.may_hold_ref(),
+            Self::Record(keyed) | Self::Union(keyed) => {
+                keyed.rest.is_some() || keyed.fields.values().any(Type::may_hold_ref)
+            }
+            Self::Tuple(tuple) => tuple.any(Type::may_hold_ref),
+            Self::RecordTuple(record_tuple) => record_tuple.any(|(_, ty)| ty.may_hold_ref()),
+            // fields aren't part of the type, any of them may be a reference
+            Self::Newtype(_) => true,
+        }
+    }
 }
 impl PrettyPrint for Cons {
     fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
         match self {
-            Self::Num => Box::new("Num".to_string()),
+            Self::Num(num) => Box::new(num.to_string()),
             Self::Bool => Box::new("Bool".to_string()),
+            Self::Char => Box::new("Char".to_string()),
+            Self::Str => Box::new("Str".to_string()),
+            Self::Never => Box::new("Never".to_string()),
             Self::Ref(mut_type, ty) => line([
                 Box::new("&:".to_string()),
                 Box::new(mut_type.to_string()),
@@ -149,13 +178,15 @@ impl PrettyPrint for Cons {
                     }
                 }
             }
+            Self::Newtype(newtype) => newtype.to_pretty_print(),
         }
     }
 }
 impl FreeVars for Cons {
     fn free_vars(&self) -> HashSet<KindedVar> {
         match self {
-            Self::Num | Self::Bool => HashSet::new(),
+            Self::Num(num) => num.free_vars(),
+            Self::Bool | Self::Char | Self::Str | Self::Never => HashSet::new(),
             Self::Ref(mutability, ty) => [mutability.free_vars(), ty.free_vars()]
                 .into_iter()
                 .flatten()
@@ -170,13 +201,41 @@ impl FreeVars for Cons {
             Self::Tuple(tuple) => tuple.free_vars(),
             Self::RecordTuple(record_tuple) => record_tuple.free_vars(),
             Self::Union(union) => union.free_vars(),
+            Self::Newtype(newtype) => newtype.args.iter().flat_map(Type::free_vars).collect(),
+        }
+    }
+}
+impl OrderedVars for Cons {
+    fn ordered_vars(&self, vars: &mut Vec<KindedVar>) {
+        match self {
+            Self::Num(num) => num.ordered_vars(vars),
+            Self::Bool | Self::Char | Self::Str | Self::Never => (),
+            Self::Ref(mutability, ty) => {
+                mutability.ordered_vars(vars);
+                ty.ordered_vars(vars);
+            }
+            Self::Array(ty) => ty.ordered_vars(vars),
+            Self::Fun(param, ret) => {
+                param.ordered_vars(vars);
+                ret.ordered_vars(vars);
+            }
+            Self::Record(record) => record.ordered_vars(vars),
+            Self::Tuple(tuple) => tuple.ordered_vars(vars),
+            Self::RecordTuple(record_tuple) => record_tuple.ordered_vars(vars),
+            Self::Union(union) => union.ordered_vars(vars),
+            Self::Newtype(newtype) => {
+                for arg in newtype.args.iter() {
+                    arg.ordered_vars(vars);
+                }
+            }
         }
     }
 }
 impl Substitutable for Cons {
     fn substitute(&mut self, subs: &Subs) -> Result<(), TypeError> {
         match self {
-            Self::Num | Self::Bool => (),
+            Self::Num(num) => num.substitute(subs)?,
+            Self::Bool | Self::Char | Self::Str | Self::Never => (),
             Self::Ref(mutability, ty) => {
                 mutability.substitute(subs)?;
                 ty.substitute(subs)?;
@@ -202,7 +261,7 @@ impl Substitutable for Cons {
                         ty.substitute(subs)?;
                     }
                 }
-                OrderedAnd::Row(_, rest, _) => match subs.get(rest.clone()) {
+                OrderedAnd::Row(_, rest, _) => match subs.get(rest.clone()).map(Type1::unalias) {
                     Some(Type1::Type(Type::Var(_) | Type::Cons(Cons::RecordTuple(_)))) => {
                         record_tuple.substitute(subs, |cons| match cons {
                             Cons::RecordTuple(record_tuple) => Some(record_tuple),
@@ -210,7 +269,7 @@ impl Substitutable for Cons {
                         })?;
                     }
                     Some(Type1::Type(Type::Cons(other @ (Cons::Record(_) | Cons::Tuple(_))))) => {
-                        let record_tuple = match replace(self, Cons::Num) {
+                        let record_tuple = match replace(self, Cons::Bool) {
                             Cons::RecordTuple(record_tuple) => record_tuple,
                             _ => unreachable!(),
                         };
@@ -221,13 +280,24 @@ impl Substitutable for Cons {
                         };
                         self.substitute(subs)?;
                     }
-                    _ => return Err(TypeError::MismatchCons),
+                    Some(Type1::Type(ty)) => {
+                        return Err(TypeError::MismatchCons(
+                            Box::new(Type::Cons(self.clone())),
+                            Box::new(ty),
+                        ))
+                    }
+                    _ => return Err(TypeError::MismatchKind),
                 },
             },
             Self::Union(union) => union.substitute(subs, |cons| match cons {
                 Cons::Union(ty) => Some(ty),
                 _ => None,
             })?,
+            Self::Newtype(newtype) => {
+                for arg in newtype.args.iter_mut() {
+                    arg.substitute(subs)?;
+                }
+            }
         }
         Ok(())
     }
@@ -240,7 +310,8 @@ impl Unifiable for Cons {
         var_state: &mut VarState,
     ) -> Result<(), TypeError> {
         match (self, other) {
-            (Self::Bool, Self::Bool) | (Self::Num, Self::Num) => (),
+            (Self::Bool, Self::Bool) | (Self::Char, Self::Char) | (Self::Str, Self::Str) => (),
+            (Self::Num(num1), Self::Num(num2)) => num1.unify_with(num2, subs, var_state)?,
             (Self::Ref(mut1, ty1), Self::Ref(mut2, ty2)) => {
                 mut1.unify_with(mut2, subs, var_state)?;
                 ty1.unify_with(*ty2, subs, var_state)?;
@@ -270,11 +341,53 @@ impl Unifiable for Cons {
             (Self::Union(union1), Self::Union(union2)) => {
                 union1.unify_with(union2, subs, var_state, Cons::Union)?
             }
-            _ => return Err(TypeError::MismatchCons),
+            (Self::Newtype(newtype1), Self::Newtype(newtype2)) if newtype1.id == newtype2.id => {
+                let args1: Vec<_> = newtype1.args.into();
+                let args2: Vec<_> = newtype2.args.into();
+                for (ty1, ty2) in args1.into_iter().zip(args2) {
+                    ty1.unify_with(ty2, subs, var_state)?;
+                }
+            }
+            (cons1, cons2) => {
+                return Err(TypeError::MismatchCons(
+                    Box::new(Type::Cons(cons1)),
+                    Box::new(Type::Cons(cons2)),
+                ))
+            }
         }
         Ok(())
     }
 }
+/// A type declared with `newtype`, it's only equal to itself even if
+/// another has the same fields
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Newtype {
+    pub name: Atom,
+    /// Index of its declaration, newtypes of the same name may be declared
+    /// in different scopes
+    pub id: usize,
+    pub args: Box<[Type]>,
+}
+impl PrettyPrint for Newtype {
+    fn to_pretty_print(&self) -> Box<dyn PrettyPrintTree> {
+        if self.args.is_empty() {
+            Box::new(self.name.to_string())
+        } else {
+            line([
+                Box::new(self.name.to_string()),
+                bracket(
+                    "(",
+                    ")",
+                    sequence(
+                        self.args
+                            .iter()
+                            .map(|ty| postfix(", ", ty.to_pretty_print())),
+                    ),
+                ),
+            ])
+        }
+    }
+}
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct Keyed {
     pub fields: HashMap<Atom, Type>,
@@ -292,6 +405,24 @@ impl FreeVars for Keyed {
             .collect()
     }
 }
+impl OrderedVars for Keyed {
+    fn ordered_vars(&self, vars: &mut Vec<KindedVar>) {
+        let mut fields: Vec<_> = self.fields.iter().collect();
+        fields.sort_unstable_by_key(|(name, _)| *name);
+        for (_, ty) in fields {
+            ty.ordered_vars(vars);
+        }
+        if let Some(rest) = &self.rest {
+            push_var(
+                vars,
+                KindedVar {
+                    kind: Kind::Type,
+                    var: rest.clone(),
+                },
+            );
+        }
+    }
+}
 impl Keyed {
     fn substitute(
         &mut self,
@@ -302,12 +433,12 @@ impl Keyed {
             ty.substitute(subs)?;
         }
         if let Some(var) = &self.rest {
-            match subs.get(var.clone()) {
+            match subs.get(var.clone()).map(Type1::unalias) {
                 Some(Type1::Type(Type::Var(new_var))) => {
                     self.rest = Some(new_var);
                 }
                 Some(Type1::Type(Type::Cons(cons))) => {
-                    let new_rest = matcher(cons).ok_or(TypeError::MismatchCons)?;
+                    let new_rest = matcher(cons).ok_or(TypeError::MismatchKind)?;
                     let new_fields = &new_rest.fields;
                     let fields = &mut self.fields;
                     fields.reserve(new_fields.len());
@@ -324,7 +455,7 @@ impl Keyed {
                     }
                     self.rest = new_rest.rest;
                 }
-                Some(_) => return Err(TypeError::MismatchCons),
+                Some(_) => return Err(TypeError::MismatchKind),
                 None => (),
             }
         }
@@ -343,34 +474,42 @@ impl Keyed {
             ty1.unify_with(ty2, subs, var_state)?;
         }
         match (self.rest, map1, other.rest, map2) {
+            (Some(rest1), map1, Some(rest2), map2) if rest1 == rest2 => {
+                if !map1.is_empty() || !map2.is_empty() {
+                    return Err(TypeError::MismatchArity);
+                }
+            }
             (Some(rest1), map1, Some(rest2), map2) => {
                 let new_var = var_state.new_var();
-                subs.insert(
+                var_state.bind(
+                    subs,
                     rest1,
                     Type1::Type(Type::Cons(cons(Keyed {
                         fields: map2,
                         rest: Some(new_var.clone()),
                     }))),
-                );
-                subs.insert(
+                )?;
+                var_state.bind(
+                    subs,
                     rest2,
                     Type1::Type(Type::Cons(cons(Keyed {
                         fields: map1,
                         rest: Some(new_var),
                     }))),
-                );
+                )?;
             }
             (Some(rest1), map1, None, map2) | (None, map2, Some(rest1), map1) => {
                 if !map1.is_empty() {
                     return Err(TypeError::MismatchArity);
                 }
-                subs.insert(
+                var_state.bind(
+                    subs,
                     rest1,
                     Type1::Type(Type::Cons(cons(Keyed {
                         fields: map2,
                         rest: None,
                     }))),
-                );
+                )?;
             }
             (None, map1, None, map2) => {
                 if !map1.is_empty() || !map2.is_empty() {
@@ -397,7 +536,7 @@ impl OrderedAnd<(Atom, Type)> {
                 }
             }
             Self::Row(left, rest, right) => Keyed {
-                fields: left.into_iter().chain(right.into_iter()).collect(),
+                fields: left.into_iter().chain(right).collect(),
                 rest: Some(rest),
             },
         }
@@ -436,7 +575,44 @@ where
         }
     }
 }
+impl<T> OrderedVars for OrderedAnd<T>
+where
+    T: OrderedVars,
+{
+    fn ordered_vars(&self, vars: &mut Vec<KindedVar>) {
+        match self {
+            Self::NonRow(tuple) => {
+                for element in tuple.iter() {
+                    element.ordered_vars(vars);
+                }
+            }
+            Self::Row(left, rest, right) => {
+                for element in left {
+                    element.ordered_vars(vars);
+                }
+                push_var(
+                    vars,
+                    KindedVar {
+                        kind: Kind::Type,
+                        var: rest.clone(),
+                    },
+                );
+                for element in right {
+                    element.ordered_vars(vars);
+                }
+            }
+        }
+    }
+}
 impl<T> OrderedAnd<T> {
+    /// Whether any of the elements satisfies `predicate`, the rest may
+    /// hold anything
+    fn any(&self, predicate: impl FnMut(&T) -> bool) -> bool {
+        match self {
+            Self::NonRow(tuple) => tuple.iter().any(predicate),
+            Self::Row(_, _, _) => true,
+        }
+    }
     fn substitute(
         &mut self,
         subs: &Subs,
@@ -458,12 +634,12 @@ impl<T> OrderedAnd<T> {
                 for ty in right.iter_mut() {
                     ty.substitute(subs)?
                 }
-                match subs.get(rest.clone()) {
+                match subs.get(rest.clone()).map(Type1::unalias) {
                     Some(Type1::Type(Type::Var(var))) => {
                         *rest = var;
                     }
                     Some(Type1::Type(Type::Cons(cons))) => {
-                        match matcher(cons).ok_or(TypeError::MismatchCons)? {
+                        match matcher(cons).ok_or(TypeError::MismatchKind)? {
                             Self::Row(more_left, new_rest, mut more_right) => {
                                 left.extend(more_left);
                                 *rest = new_rest;
@@ -481,15 +657,12 @@ impl<T> OrderedAnd<T> {
                                     }
                                 };
                                 *self = Self::NonRow(
-                                    left.into_iter()
-                                        .chain(new_tuple.into_iter())
-                                        .chain(right.into_iter())
-                                        .collect(),
+                                    left.into_iter().chain(new_tuple).chain(right).collect(),
                                 );
                             }
                         }
                     }
-                    Some(_) => return Err(TypeError::MismatchCons),
+                    Some(_) => return Err(TypeError::MismatchKind),
                     None => (),
                 }
             }
@@ -513,7 +686,7 @@ impl<T> OrderedAnd<T> {
                 }
                 let tup1: Vec<_> = tup1.into();
                 let tup2: Vec<_> = tup2.into();
-                for (ty1, ty2) in tup1.into_iter().zip(tup2.into_iter()) {
+                for (ty1, ty2) in tup1.into_iter().zip(tup2) {
                     ty1.unify_with(ty2, subs, var_state)?;
                 }
             }
@@ -526,18 +699,61 @@ impl<T> OrderedAnd<T> {
                 let mut left2 = tup;
                 let mut rest2 = left2.split_off(left.len());
                 let right2 = rest2.split_off(rest2.len() - right.len());
-                for (ty1, ty2) in left.into_iter().zip(left2.into_iter()) {
+                for (ty1, ty2) in left.into_iter().zip(left2) {
                     ty1.unify_with(ty2, subs, var_state)?;
                 }
-                for (ty1, ty2) in right.into_iter().zip(right2.into_iter()) {
+                for (ty1, ty2) in right.into_iter().zip(right2) {
                     ty1.unify_with(ty2, subs, var_state)?;
                 }
-                subs.insert(
+                var_state.bind(
+                    subs,
                     rest,
                     Type1::Type(Type::Cons(cons(Self::NonRow(rest2.into())))),
-                )
+                )?;
+            }
+            (Self::Row(mut left1, rest1, mut right1), Self::Row(mut left2, rest2, mut right2)) => {
+                let left = left1.len().min(left2.len());
+                let more_left1 = left1.split_off(left);
+                let more_left2 = left2.split_off(left);
+                let right = right1.len().min(right2.len());
+                let more_right1: Vec<_> = right1.drain(..right1.len() - right).collect();
+                let more_right2: Vec<_> = right2.drain(..right2.len() - right).collect();
+                for (ty1, ty2) in left1
+                    .into_iter()
+                    .zip(left2)
+                    .chain(right1.into_iter().zip(right2))
+                {
+                    ty1.unify_with(ty2, subs, var_state)?;
+                }
+                let mut row = |left: Vec<T>, rest, right: Vec<T>| {
+                    if left.is_empty() && right.is_empty() {
+                        Type::Var(rest)
+                    } else {
+                        Type::Cons(cons(Self::Row(left, rest, right)))
+                    }
+                };
+                let more1 = !more_left1.is_empty() || !more_right1.is_empty();
+                let more2 = !more_left2.is_empty() || !more_right2.is_empty();
+                if rest1 == rest2 {
+                    if more1 || more2 {
+                        return Err(TypeError::MismatchArity);
+                    }
+                } else if !more1 {
+                    let ty = row(more_left2, rest2, more_right2);
+                    Type::Var(rest1).unify_with(ty, subs, var_state)?;
+                } else if !more2 {
+                    let ty = row(more_left1, rest1, more_right1);
+                    Type::Var(rest2).unify_with(ty, subs, var_state)?;
+                } else {
+                    // the extra elements are on opposite ends, both rests
+                    // share what's between them
+                    let rest = var_state.new_var();
+                    let ty1 = row(more_left2, rest.clone(), more_right2);
+                    let ty2 = row(more_left1, rest, more_right1);
+                    Type::Var(rest1).unify_with(ty1, subs, var_state)?;
+                    Type::Var(rest2).unify_with(ty2, subs, var_state)?;
+                }
             }
-            (Self::Row(_left1, _rest1, _right1), Self::Row(_left2, _rest2, _right2)) => todo!(),
         }
         Ok(())
     }
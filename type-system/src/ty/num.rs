@@ -0,0 +1,352 @@
+//! Representations of numbers. A number is either an integer of some width
+//! and signedness or a floating-point number. Literals and operations bound
+//! what the representation must hold, a number whose representation is still
+//! unknown after inference gets the first of `I64`, `U64` and `F64` that
+//! holds everything required of it.
+
+use crate::ty::{
+    push_var, Cons, FreeVars, Kind, KindedVar, OrderedVars, Subs, Substitutable, Type, Type1,
+    TypeError, Unifiable, Var, VarState,
+};
+use hir::expr::Literal;
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    iter::once,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Repr {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+}
+impl Repr {
+    const ALL: [Self; 10] = [
+        Self::U8,
+        Self::U16,
+        Self::U32,
+        Self::U64,
+        Self::I8,
+        Self::I16,
+        Self::I32,
+        Self::I64,
+        Self::F32,
+        Self::F64,
+    ];
+    /// The representation named on type annotations
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|repr| repr.to_string() == name)
+    }
+    pub fn is_float(self) -> bool {
+        matches!(self, Self::F32 | Self::F64)
+    }
+    pub fn is_signed(self) -> bool {
+        !matches!(self, Self::U8 | Self::U16 | Self::U32 | Self::U64)
+    }
+    // floating-point numbers approximate large integers
+    fn max_integer(self) -> u64 {
+        match self {
+            Self::U8 => u8::MAX.into(),
+            Self::U16 => u16::MAX.into(),
+            Self::U32 => u32::MAX.into(),
+            Self::I8 => i8::MAX as u64,
+            Self::I16 => i16::MAX as u64,
+            Self::I32 => i32::MAX as u64,
+            Self::I64 => i64::MAX as u64,
+            Self::U64 | Self::F32 | Self::F64 => u64::MAX,
+        }
+    }
+}
+impl Display for Repr {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "{self:?}")
+    }
+}
+/// What the representation of a number must hold, it only grows as the
+/// number is used
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct NumBound {
+    /// The largest magnitude among its integer literals
+    max: u64,
+    negative: bool,
+    /// Required by fractional literals and `/`
+    fractional: bool,
+    /// Required by fractional literals beyond the range of `F32`
+    wide: bool,
+    /// Required by indices, lengths, and range bounds
+    integral: bool,
+}
+impl NumBound {
+    pub const NEGATIVE: Self = Self {
+        max: 0,
+        negative: true,
+        fractional: false,
+        wide: false,
+        integral: false,
+    };
+    pub const FRACTIONAL: Self = Self {
+        max: 0,
+        negative: false,
+        fractional: true,
+        wide: false,
+        integral: false,
+    };
+    pub const INTEGRAL: Self = Self {
+        max: 0,
+        negative: false,
+        fractional: false,
+        wide: false,
+        integral: true,
+    };
+    pub fn literal(literal: &Literal) -> Self {
+        match literal {
+            Literal::UInt(num) => Self {
+                max: *num,
+                ..Self::default()
+            },
+            Literal::Float(num) => Self {
+                fractional: true,
+                wide: num.abs() > f64::from(f32::MAX),
+                ..Self::default()
+            },
+            Literal::True | Literal::False | Literal::Char(_) => Self::default(),
+        }
+    }
+    /// Bound of a signed literal pattern
+    pub fn int(num: i64) -> Self {
+        if num < 0 {
+            // the negative range reaches one further than the positive
+            Self {
+                max: num.unsigned_abs() - 1,
+                ..Self::NEGATIVE
+            }
+        } else {
+            Self::literal(&Literal::UInt(num.unsigned_abs()))
+        }
+    }
+    pub fn join(self, other: Self) -> Self {
+        Self {
+            max: self.max.max(other.max),
+            negative: self.negative || other.negative,
+            fractional: self.fractional || other.fractional,
+            wide: self.wide || other.wide,
+            integral: self.integral || other.integral,
+        }
+    }
+    /// What `repr` can't hold of it
+    pub fn rejects(&self, repr: Repr) -> Option<String> {
+        if self.integral && repr.is_float() {
+            Some(format!("`{repr}` is used where an integer is required"))
+        } else if self.fractional && !repr.is_float() {
+            Some(format!("`{repr}` can't hold fractional numbers"))
+        } else if self.negative && !repr.is_signed() {
+            Some(format!("`{repr}` can't hold negative numbers"))
+        } else if self.max > repr.max_integer() {
+            Some(format!("`{repr}` can't hold {}", self.max))
+        } else if self.wide && repr == Repr::F32 {
+            Some(format!("`{repr}` can't hold a literal this large"))
+        } else {
+            None
+        }
+    }
+    pub fn holds(&self, repr: Repr) -> bool {
+        self.rejects(repr).is_none()
+    }
+    /// Representation of a number left unknown after inference
+    pub fn default_repr(&self) -> Option<Repr> {
+        [Repr::I64, Repr::U64, Repr::F64]
+            .into_iter()
+            .find(|repr| self.holds(*repr))
+    }
+}
+impl Display for NumBound {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        if self.fractional && self.integral {
+            write!(
+                fmt,
+                "a fractional number is used where an integer is required"
+            )
+        } else {
+            write!(
+                fmt,
+                "no integer representation holds both negative numbers and {}",
+                self.max
+            )
+        }
+    }
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum NumType {
+    Var(Var),
+    Repr(Repr),
+}
+impl Display for NumType {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            // the representation is left to inference
+            Self::Var(_) => write!(fmt, "Num"),
+            Self::Repr(repr) => write!(fmt, "{repr}"),
+        }
+    }
+}
+impl FreeVars for NumType {
+    fn free_vars(&self) -> HashSet<KindedVar> {
+        match self {
+            Self::Var(var) => once(KindedVar {
+                kind: Kind::NumType,
+                var: var.clone(),
+            })
+            .collect(),
+            Self::Repr(_) => HashSet::new(),
+        }
+    }
+}
+impl OrderedVars for NumType {
+    fn ordered_vars(&self, vars: &mut Vec<KindedVar>) {
+        if let Self::Var(var) = self {
+            push_var(
+                vars,
+                KindedVar {
+                    kind: Kind::NumType,
+                    var: var.clone(),
+                },
+            );
+        }
+    }
+}
+impl Substitutable for NumType {
+    fn substitute(&mut self, subs: &Subs) -> Result<(), TypeError> {
+        if let Self::Var(var) = self {
+            if let Some(ty) = subs.get(var.clone()) {
+                match ty {
+                    Type1::NumType(num) => *self = num,
+                    _ => return Err(TypeError::MismatchKind),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+impl Unifiable for NumType {
+    fn unify_with(
+        self,
+        other: Self,
+        subs: &mut Subs,
+        var_state: &mut VarState,
+    ) -> Result<(), TypeError> {
+        let mut this = self;
+        let mut other = other;
+        this.substitute(var_state.bindings())?;
+        other.substitute(var_state.bindings())?;
+        match (this, other) {
+            (Self::Repr(repr1), Self::Repr(repr2)) if repr1 == repr2 => (),
+            (Self::Var(var1), Self::Var(var2)) if var1 == var2 => (),
+            // a rigid variable is only bound to another variable
+            (Self::Var(var1), Self::Var(var2)) if var_state.is_rigid(&var1) => {
+                var_state.bind(subs, var2, Type1::NumType(Self::Var(var1)))?;
+            }
+            (Self::Var(var), num) | (num, Self::Var(var)) => {
+                var_state.bind(subs, var, Type1::NumType(num))?;
+            }
+            (num1, num2) => {
+                return Err(TypeError::MismatchCons(
+                    Box::new(Type::Cons(Cons::Num(num1))),
+                    Box::new(Type::Cons(Cons::Num(num2))),
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::{NumBound, Repr};
+    use hir::expr::Literal;
+
+    #[test]
+    fn default_repr() {
+        let small = NumBound::literal(&Literal::UInt(1));
+        assert_eq!(small.default_repr(), Some(Repr::I64));
+        assert_eq!(
+            small.join(NumBound::NEGATIVE).default_repr(),
+            Some(Repr::I64)
+        );
+        let large = NumBound::literal(&Literal::UInt(u64::MAX));
+        assert_eq!(large.default_repr(), Some(Repr::U64));
+        assert_eq!(NumBound::FRACTIONAL.default_repr(), Some(Repr::F64));
+        assert_eq!(
+            large.join(NumBound::FRACTIONAL).default_repr(),
+            Some(Repr::F64)
+        );
+        let wide = NumBound::literal(&Literal::Float(1e300));
+        assert_eq!(wide.default_repr(), Some(Repr::F64));
+        assert_eq!(NumBound::INTEGRAL.default_repr(), Some(Repr::I64));
+    }
+    #[test]
+    fn conflicting_bounds() {
+        let float = NumBound::literal(&Literal::Float(0.5));
+        let index = float.join(NumBound::INTEGRAL);
+        assert_eq!(index.default_repr(), None);
+        assert_eq!(
+            index.to_string(),
+            "a fractional number is used where an integer is required"
+        );
+        let large = NumBound::literal(&Literal::UInt(u64::MAX)).join(NumBound::NEGATIVE);
+        assert_eq!(large.default_repr(), Some(Repr::F64));
+        let large = large.join(NumBound::INTEGRAL);
+        assert_eq!(large.default_repr(), None);
+        assert_eq!(
+            large.to_string(),
+            format!(
+                "no integer representation holds both negative numbers and {}",
+                u64::MAX
+            )
+        );
+    }
+    #[test]
+    fn negative_range() {
+        assert!(NumBound::int(-128).holds(Repr::I8));
+        assert!(!NumBound::int(-129).holds(Repr::I8));
+        assert!(NumBound::int(127).holds(Repr::I8));
+        assert!(!NumBound::int(128).holds(Repr::I8));
+        assert!(!NumBound::int(-1).holds(Repr::U64));
+        assert!(NumBound::int(i64::MIN).holds(Repr::I64));
+    }
+    #[test]
+    fn rejects() {
+        let bound = NumBound::literal(&Literal::UInt(256));
+        assert_eq!(
+            bound.rejects(Repr::U8),
+            Some("`U8` can't hold 256".to_string())
+        );
+        assert_eq!(bound.rejects(Repr::U16), None);
+        assert_eq!(
+            NumBound::INTEGRAL.rejects(Repr::F32),
+            Some("`F32` is used where an integer is required".to_string())
+        );
+        let wide = NumBound::literal(&Literal::Float(1e39));
+        assert_eq!(
+            wide.rejects(Repr::F32),
+            Some("`F32` can't hold a literal this large".to_string())
+        );
+        assert_eq!(wide.rejects(Repr::F64), None);
+        // floating-point numbers approximate any integer
+        let large = NumBound::literal(&Literal::UInt(u64::MAX));
+        assert_eq!(large.rejects(Repr::F32), None);
+    }
+    #[test]
+    fn from_name() {
+        assert_eq!(Repr::from_name("U8"), Some(Repr::U8));
+        assert_eq!(Repr::from_name("F64"), Some(Repr::F64));
+        assert_eq!(Repr::from_name("Num"), None);
+        assert_eq!(Repr::from_name("u8"), None);
+    }
+}